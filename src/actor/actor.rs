@@ -21,7 +21,7 @@ use {
     std::any::type_name,
 };
 
-use super::TracePlugins;
+use super::{ProjectilePlugins, PropPlugins, TracePlugins};
 
 #[derive(Default, Component)]
 pub struct Actor;
@@ -39,7 +39,7 @@ impl Plugin for ActorPlugins {
         #[cfg(feature = "temp-container")]
         app.add_systems(Startup, setup);
         app.add_event::<UnloadActorsEvent>()
-            .add_plugins(TracePlugins)
+            .add_plugins((TracePlugins, PropPlugins, ProjectilePlugins))
             .add_systems(Update, unload_actors);
     }
 }
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::lobby::LobbyState;
+use crate::world::Me;
+
+use super::{fall_characters, Airborne, MoveVelocity, HALPH_PLAYER_SIZE};
+
+/// Horizontal speed from which a character runs
+const RUN_SPEED: f32 = 0.5;
+/// Vertical speed from which an observed character is in the air
+const AIRBORNE_SPEED: f32 = 0.5;
+/// Seconds the landing pose is held
+const LAND_DURATION: f32 = 0.15;
+/// How fast the body blends into the pose of a new state, per second
+const CROSSFADE_RATE: f32 = 12.;
+/// Bobs per second while running, in radians
+const RUN_BOB_RATE: f32 = 12.;
+
+/// Pose of a character, computed by the side simulating it and replicated in
+/// [`PlayerTransportData`](crate::lobby::PlayerTransportData).
+///
+/// Serialized as its discriminant, unknown discriminants (e.g. a state added by a newer host)
+/// read as [`AnimationState::Idle`], so new states can be added without breaking the protocol.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AnimationState {
+    #[default]
+    Idle = 0,
+    Run = 1,
+    Jump = 2,
+    Fall = 3,
+    Land = 4,
+}
+
+impl From<u8> for AnimationState {
+    fn from(discriminant: u8) -> Self {
+        match discriminant {
+            1 => AnimationState::Run,
+            2 => AnimationState::Jump,
+            3 => AnimationState::Fall,
+            4 => AnimationState::Land,
+            _ => AnimationState::Idle,
+        }
+    }
+}
+
+impl Serialize for AnimationState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnimationState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u8::deserialize(deserializer).map(AnimationState::from)
+    }
+}
+
+impl AnimationState {
+    /// State following `self` after `elapsed` seconds in it, for a character moving horizontally
+    /// at `speed` and in the air at `vertical` speed (`None` when grounded).
+    pub fn next(self, elapsed: f32, speed: f32, vertical: Option<f32>) -> Self {
+        match (vertical, self) {
+            (Some(vertical), _) if vertical > 0. => AnimationState::Jump,
+            (Some(_), _) => AnimationState::Fall,
+            (None, AnimationState::Jump | AnimationState::Fall) => AnimationState::Land,
+            (None, AnimationState::Land) if elapsed < LAND_DURATION => AnimationState::Land,
+            (None, _) if speed > RUN_SPEED => AnimationState::Run,
+            (None, _) => AnimationState::Idle,
+        }
+    }
+
+    /// Scale of the body in this state, `time` drives the cyclic poses.
+    fn pose(self, time: f32) -> Vec3 {
+        match self {
+            AnimationState::Idle => Vec3::ONE,
+            AnimationState::Run => {
+                let bob = (time * RUN_BOB_RATE).sin() * 0.05;
+                Vec3::new(1. - bob, 1. + bob, 1. - bob)
+            }
+            AnimationState::Jump => Vec3::new(0.85, 1.2, 0.85),
+            AnimationState::Fall => Vec3::new(0.92, 1.1, 0.92),
+            AnimationState::Land => Vec3::new(1.2, 0.75, 1.2),
+        }
+    }
+}
+
+/// Current [`AnimationState`] of a character.
+///
+/// Computed on the host and single, replicated to clients except for the own character,
+/// which a client computes from the motion it sees.
+#[derive(Debug, Default, Component)]
+pub struct CharacterAnimation {
+    pub state: AnimationState,
+    /// Seconds in `state`
+    elapsed: f32,
+    /// Position at the last estimation, see [`estimate_own_animation`]
+    previous_position: Option<Vec3>,
+}
+
+impl CharacterAnimation {
+    fn set(&mut self, state: AnimationState) {
+        if state != self.state {
+            self.state = state;
+            self.elapsed = 0.;
+        }
+    }
+}
+
+/// State of a remote character from the last snapshot.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct ReplicatedAnimation(pub AnimationState);
+
+/// Child entity holding the mesh of a character, posed by its [`CharacterAnimation`].
+///
+/// Kept apart from the character so the pose never scales its collider.
+#[derive(Debug, Default, Component)]
+pub struct CharacterBody;
+
+pub struct CharacterAnimationPlugins;
+
+impl Plugin for CharacterAnimationPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            compute_animation_states.after(fall_characters).run_if(
+                not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+            ),
+        )
+        .add_systems(
+            Update,
+            (estimate_own_animation, follow_replicated_animation)
+                .run_if(in_state(LobbyState::Client)),
+        )
+        .add_systems(
+            Update,
+            animate_bodies
+                .after(estimate_own_animation)
+                .after(follow_replicated_animation)
+                .run_if(not(in_state(LobbyState::None))),
+        );
+    }
+}
+
+fn compute_animation_states(
+    time: Res<Time>,
+    mut query: Query<(&mut CharacterAnimation, &MoveVelocity, Option<&Airborne>)>,
+) {
+    for (mut animation, velocity, airborne) in query.iter_mut() {
+        animation.elapsed += time.delta_seconds();
+        let vertical = airborne.map(|airborne| airborne.velocity);
+        let state = animation
+            .state
+            .next(animation.elapsed, velocity.0.length(), vertical);
+        animation.set(state);
+    }
+}
+
+/// The own character of a client is posed from its observed motion, without waiting for the host.
+fn estimate_own_animation(
+    time: Res<Time>,
+    mut query: Query<(&mut CharacterAnimation, &Transform), With<Me>>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0. {
+        return;
+    }
+    for (mut animation, transform) in query.iter_mut() {
+        let position = transform.translation;
+        let velocity = animation
+            .previous_position
+            .map_or(Vec3::ZERO, |previous| (position - previous) / dt);
+        animation.previous_position = Some(position);
+        animation.elapsed += dt;
+
+        let vertical = (velocity.y.abs() > AIRBORNE_SPEED).then_some(velocity.y);
+        let state = animation
+            .state
+            .next(animation.elapsed, velocity.xz().length(), vertical);
+        animation.set(state);
+    }
+}
+
+fn follow_replicated_animation(
+    time: Res<Time>,
+    mut query: Query<(&mut CharacterAnimation, &ReplicatedAnimation), Without<Me>>,
+) {
+    for (mut animation, ReplicatedAnimation(state)) in query.iter_mut() {
+        animation.elapsed += time.delta_seconds();
+        animation.set(*state);
+    }
+}
+
+/// Blends the bodies towards the pose of their state, so a late or lost snapshot does not make them flicker.
+fn animate_bodies(
+    time: Res<Time>,
+    animation_query: Query<(&CharacterAnimation, &Children)>,
+    mut body_query: Query<&mut Transform, With<CharacterBody>>,
+) {
+    let blend = 1. - (-CROSSFADE_RATE * time.delta_seconds()).exp();
+    for (animation, children) in animation_query.iter() {
+        let pose = animation.state.pose(time.elapsed_seconds());
+        for child in children.iter() {
+            if let Ok(mut transform) = body_query.get_mut(*child) {
+                transform.scale = transform.scale.lerp(pose, blend);
+                // squashed or stretched from the feet, not from the center
+                transform.translation.y = (transform.scale.y - 1.) * HALPH_PLAYER_SIZE;
+            }
+        }
+    }
+}
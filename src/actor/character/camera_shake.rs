@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+
+use crate::actor::Owner;
+use crate::component::Health;
+use crate::lobby::{Character, LobbyState};
+use crate::settings::Settings;
+use crate::world::Me;
+
+use super::TiedCamera;
+
+/// Summed magnitude of the overlapping shakes is clamped to it
+const MAX_MAGNITUDE: f32 = 1.;
+/// Offset of the camera at full magnitude, in units
+const MAX_OFFSET: f32 = 0.4;
+/// Roll, pitch and yaw of the camera at full magnitude, in radians
+const MAX_ANGLE: f32 = 0.05;
+/// Units from the camera a shake with a source fades out at
+const FALLOFF_DISTANCE: f32 = 25.;
+
+/// Falling speed (units per second) from which a landing shakes, a jump lands slower
+const BIG_FALL_SPEED: f32 = 14.;
+/// Falling speed of the strongest landing shake
+const MAX_FALL_SPEED: f32 = 40.;
+/// Seconds without a vertical move after which a fall counts as landed
+const LAND_SETTLE: f32 = 0.2;
+/// Vertical move between two positions taken for a teleport rather than a fall
+const TELEPORT_STEP: f32 = 10.;
+
+/// Shakes the own camera, summed with the other shakes running.
+///
+/// Only the local camera moves: the character transform, and so the replication,
+/// is never touched.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct CameraShakeEvent {
+    /// `0` to `1`, at `1` the camera moves by the most it ever does
+    pub magnitude: f32,
+    /// Seconds the shake takes to decay
+    pub duration: f32,
+    /// Where it comes from, it fades with the distance to the camera
+    pub source: Option<Vec3>,
+}
+
+impl CameraShakeEvent {
+    pub fn landing(fall_speed: f32) -> Self {
+        let strength = (fall_speed - BIG_FALL_SPEED) / (MAX_FALL_SPEED - BIG_FALL_SPEED);
+        Self {
+            magnitude: 0.3 + 0.5 * strength.clamp(0., 1.),
+            duration: 0.35,
+            source: None,
+        }
+    }
+
+    pub fn damage(fraction: f32) -> Self {
+        Self {
+            magnitude: (0.3 + fraction).min(0.8),
+            duration: 0.3,
+            source: None,
+        }
+    }
+
+    pub fn impact(position: Vec3) -> Self {
+        Self {
+            magnitude: 0.5,
+            duration: 0.4,
+            source: Some(position),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveShake {
+    magnitude: f32,
+    duration: f32,
+    elapsed: f32,
+    /// Phase of the noise, so two shakes do not move the camera the same way
+    seed: f32,
+}
+
+impl ActiveShake {
+    fn current(&self) -> f32 {
+        let left = (1. - self.elapsed / self.duration).max(0.);
+        self.magnitude * left * left
+    }
+}
+
+/// Offset of the tied camera from the running shakes, applied by the camera follow.
+#[derive(Debug, Default, Resource)]
+pub struct CameraShake {
+    shakes: Vec<ActiveShake>,
+    /// Local offset of the camera, the follow scales it down towards first person
+    pub translation: Vec3,
+    pub rotation: Quat,
+    seeds: f32,
+}
+
+impl CameraShake {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Position of the own character, to tell a big fall from its moves.
+#[derive(Debug, Default)]
+struct FallTracker {
+    position: Option<Vec3>,
+    /// Seconds since the last vertical move
+    still: f32,
+    /// Fastest fall since the character was last grounded
+    fall_speed: f32,
+}
+
+pub struct CameraShakePlugins;
+
+impl Plugin for CameraShakePlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CameraShakeEvent>()
+            .init_resource::<CameraShake>()
+            .add_systems(
+                Update,
+                ((shake_on_landing, shake_on_damage), update_camera_shake)
+                    .chain()
+                    .run_if(not(in_state(LobbyState::None))),
+            )
+            .add_systems(OnEnter(LobbyState::None), clear_camera_shake);
+    }
+}
+
+/// Smooth noise in `-1..1`, a few unrelated sines instead of a real perlin noise.
+fn noise(time: f32, seed: f32) -> f32 {
+    ((time * 23. + seed).sin() + 0.6 * (time * 37. + seed * 1.7).sin()
+        + 0.3 * (time * 59. + seed * 2.3).sin())
+        / 1.9
+}
+
+fn update_camera_shake(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut shake: ResMut<CameraShake>,
+    mut shake_event: EventReader<CameraShakeEvent>,
+    camera_query: Query<&GlobalTransform, With<TiedCamera>>,
+) {
+    let camera = camera_query.iter().next().map(GlobalTransform::translation);
+    for event in shake_event.read() {
+        let falloff = match (event.source, camera) {
+            (Some(source), Some(camera)) => {
+                (1. - source.distance(camera) / FALLOFF_DISTANCE).max(0.).powi(2)
+            }
+            (Some(_), None) => 0.,
+            (None, _) => 1.,
+        };
+        let magnitude = event.magnitude * falloff;
+        if magnitude <= 0. || event.duration <= 0. {
+            continue;
+        }
+        shake.seeds += 1.;
+        let seed = shake.seeds * 7.13;
+        shake.shakes.push(ActiveShake {
+            magnitude,
+            duration: event.duration,
+            elapsed: 0.,
+            seed,
+        });
+    }
+
+    let dt = time.delta_seconds();
+    shake.shakes.retain_mut(|active| {
+        active.elapsed += dt;
+        active.elapsed < active.duration
+    });
+
+    // the accessibility setting takes all of it away, the shakes still run out
+    if settings.reduce_motion || shake.shakes.is_empty() {
+        shake.translation = Vec3::ZERO;
+        shake.rotation = Quat::IDENTITY;
+        return;
+    }
+    let t = time.elapsed_seconds();
+    let mut offset = Vec3::ZERO;
+    let mut angles = Vec3::ZERO;
+    for active in shake.shakes.iter() {
+        let amount = active.current();
+        let axis = |index: f32| noise(t, active.seed + index * 11.) * amount;
+        offset += Vec3::new(axis(0.), axis(1.), axis(2.));
+        angles += Vec3::new(axis(3.), axis(4.), axis(5.));
+    }
+    // overlapping shakes sum, up to the strongest one allowed
+    let limit = Vec3::splat(MAX_MAGNITUDE);
+    shake.translation = offset.clamp(-limit, limit) * MAX_OFFSET;
+    let angles = angles.clamp(-limit, limit) * MAX_ANGLE;
+    shake.rotation = Quat::from_euler(EulerRot::YXZ, angles.y, angles.x, angles.z);
+}
+
+/// A landing is seen from the moves of the own character, so clients shake like the host.
+fn shake_on_landing(
+    time: Res<Time>,
+    mut tracker: Local<FallTracker>,
+    me_query: Query<&Transform, (With<Me>, With<Character>)>,
+    mut shake_event: EventWriter<CameraShakeEvent>,
+) {
+    let Ok(transform) = me_query.get_single() else {
+        *tracker = FallTracker::default();
+        return;
+    };
+    let position = transform.translation;
+    let previous = tracker.position.replace(position);
+    tracker.still += time.delta_seconds();
+    let Some(previous) = previous else {
+        return;
+    };
+
+    let step = position.y - previous.y;
+    if step.abs() > TELEPORT_STEP {
+        tracker.fall_speed = 0.;
+        tracker.still = 0.;
+        return;
+    }
+    // a client sees its character move only when a snapshot arrives
+    if step != 0. && tracker.still > 0. {
+        let speed = -step / tracker.still;
+        tracker.still = 0.;
+        if speed > 0. {
+            tracker.fall_speed = tracker.fall_speed.max(speed);
+            return;
+        }
+    } else if tracker.still < LAND_SETTLE {
+        return;
+    }
+
+    if tracker.fall_speed >= BIG_FALL_SPEED {
+        shake_event.send(CameraShakeEvent::landing(tracker.fall_speed));
+    }
+    tracker.fall_speed = 0.;
+}
+
+/// The own health going down shakes by the share of it lost.
+fn shake_on_damage(
+    mut previous: Local<Option<(Entity, f32)>>,
+    me_query: Query<(Entity, &Health), (With<Me>, With<Character>)>,
+    mut shake_event: EventWriter<CameraShakeEvent>,
+) {
+    let Ok((entity, health)) = me_query.get_single() else {
+        *previous = None;
+        return;
+    };
+    if let Some((known, current)) = *previous {
+        if known == entity && health.current < current && health.max > 0. {
+            let fraction = (current - health.current) / health.max;
+            shake_event.send(CameraShakeEvent::damage(fraction));
+        }
+    }
+    *previous = Some((entity, health.current));
+}
+
+/// Shakes for the projectiles among `despawned`, at their last position.
+pub fn shake_on_projectile_despawn(world: &mut World, despawned: &[(Entity, Vec3)]) {
+    for (entity, position) in despawned {
+        if world.get::<Owner>(*entity).is_some() {
+            world.send_event(CameraShakeEvent::impact(*position));
+        }
+    }
+}
+
+fn clear_camera_shake(mut shake: ResMut<CameraShake>) {
+    shake.clear();
+}
@@ -1,48 +1,202 @@
 
 
-use crate::component::{AxisName, DespawnReason, NoclipDuration, Respawn};
+use crate::component::{
+    AxisName, DespawnReason, Health, InGravityZone, NoclipDuration, Respawn, Teleported,
+};
+use crate::core::CoreAction;
 use crate::extend_commands;
+use crate::lobby::client::send_to_server;
+use crate::lobby::host_settings::ServerRules;
 use crate::lobby::Character;
-use crate::lobby::{LobbyState, PlayerId, PlayerView};
-use crate::world::MainCamera;
+use crate::lobby::quick_chat::QuickChatWheel;
+use crate::lobby::validation::{InputSequence, MovementInput};
+use crate::lobby::{ClientMessages, Lobby, LobbyState, PlayerId, PlayerView};
+use crate::network::Channel;
+use crate::physics::groups::character_groups;
+use crate::settings::{ApplySettings, CameraMode, Settings};
+use crate::ui::MouseGrabState;
+use crate::world::{FreeCamera, LevelPhysics, MainCamera};
 use crate::world::Me;
 use crate::world::SpawnProperty;
-use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy::{ecs::system::EntityCommands, input::mouse::MouseMotion, prelude::*};
+use bevy_controls::contract::InputsContainer;
+use bevy_rapier3d::plugin::RapierContext;
+use bevy_rapier3d::prelude::{Collider, QueryFilter, RigidBody};
+use renet::RenetClient;
 
 use serde::{Deserialize, Serialize};
 
-//pub const PLAYER_MOVE_SPEED: f32 = 0.07;
+use super::{CameraShake, CameraShakePlugins};
+
+/// Maximum horizontal speed (units per second) a character can reach by moving, unless the level changes it
+pub const PLAYER_MAX_SPEED: f32 = 20.;
+/// Horizontal acceleration (units per second squared) of a character, unless the level changes it
+pub const PLAYER_ACCELERATION: f32 = 60.;
 pub const PLAYER_SIZE: f32 = 2.;
 pub const HALPH_PLAYER_SIZE: f32 = PLAYER_SIZE / 2.;
-//const SHIFT_ACCELERATION: f32 = 2.0;
-//const SENSITIVITY: f32 = 0.5;
-//const JUMP_HEIGHT_MULTIPLICATOR: f32 = 1.1;
+/// Radians the view turns per pixel of mouse motion at [`Settings::mouse_sensitivity`] `1`
+const MOUSE_RADIANS_PER_PIXEL: f32 = 0.002;
 
 const DEFAULT_CAMERA_DISTANCE: f32 = 20.;
+/// Point above the character the third person camera orbits
+const THIRD_PERSON_PIVOT: Vec3 = Vec3::new(0., 2., 0.);
+/// Position of the first person camera relative to the character
+const FIRST_PERSON_HEAD_OFFSET: Vec3 = Vec3::new(0., HALPH_PLAYER_SIZE * 0.75, 0.);
+/// Seconds the camera takes to move between the [`CameraMode`]s
+const CAMERA_MODE_TRANSITION: f32 = 0.15;
 
 #[derive(Component, Debug, Serialize, Deserialize)]
 pub struct TiedCamera(Entity);
 
-//#[derive(Component, Debug)]
-//struct JumpHelper {
-//    last_viable_normal: Vec3,
-//}
+/// How far a tied camera is into first person, `0` is third and `1` first person.
+///
+/// Eased towards [`Settings::camera_mode`], so a switch never makes the camera jump.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct CameraModeBlend(pub f32);
+
+impl CameraModeBlend {
+    fn target(mode: CameraMode) -> f32 {
+        match mode {
+            CameraMode::ThirdPerson => 0.,
+            CameraMode::FirstPerson => 1.,
+        }
+    }
+
+    /// Whether the camera is close enough to the head to be inside the character
+    pub fn is_first_person(&self) -> bool {
+        self.0 > 0.5
+    }
+}
+
+impl TiedCamera {
+    /// Entity followed by the camera
+    pub fn target(&self) -> Entity {
+        self.0
+    }
+}
+
+/// Jump tuning of the characters, used by the simulating side.
+#[derive(Debug, Clone, Copy, Resource, Reflect)]
+pub struct JumpConfig {
+    /// Upward speed (units per second) a jump gives
+    pub impulse: f32,
+    /// Distance between the character bottom and the ground under which it is still grounded
+    pub ground_tolerance: f32,
+}
+
+impl Default for JumpConfig {
+    fn default() -> Self {
+        Self {
+            impulse: 8.,
+            ground_tolerance: 0.1,
+        }
+    }
+}
+
+/// Movement tuning of the loaded level, used by the simulating side.
+///
+/// Set when a level is loaded from [`level_movement`](crate::level::level_movement).
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect)]
+pub struct MovementTuning {
+    /// Maximum horizontal speed (units per second)
+    pub max_speed: f32,
+    /// Horizontal acceleration (units per second squared), also used to stop
+    pub acceleration: f32,
+}
+
+impl Default for MovementTuning {
+    fn default() -> Self {
+        Self {
+            max_speed: PLAYER_MAX_SPEED,
+            acceleration: PLAYER_ACCELERATION,
+        }
+    }
+}
+
+/// Horizontal velocity of a character, accelerated towards its movement input.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct MoveVelocity(pub Vec3);
+
+/// What the horizontal movement of a character integrates, see [`step_movement`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MoveState {
+    pub position: Vec3,
+    /// Horizontal, the vertical speed is the one of [`Airborne`]
+    pub velocity: Vec3,
+}
+
+/// Moves `state` by `direction` for `dt` seconds of simulated time.
+///
+/// The one movement of the characters: the host simulates it and a client predicts its own
+/// character with it, so both land on the same position for the same inputs.
+pub fn step_movement(
+    state: MoveState,
+    direction: Vec2,
+    tuning: &MovementTuning,
+    dt: f32,
+) -> MoveState {
+    let target = Vec3::new(direction.x, 0., direction.y) * tuning.max_speed;
+    let velocity =
+        state.velocity + (target - state.velocity).clamp_length_max(tuning.acceleration * dt);
+    MoveState {
+        position: state.position + velocity * dt,
+        velocity,
+    }
+}
+
+/// Makes the character jump on the next fixed tick, dropped if it is not grounded then.
+#[derive(Debug, Default, Component)]
+pub struct JumpRequest;
+
+/// Vertical speed of a character in the air, removed once it lands.
+///
+/// Characters are kinematic, so the jump and the fall are integrated here rather than by rapier.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Airborne {
+    pub velocity: f32,
+}
 
 pub struct CharacterPlugins;
 
 impl Plugin for CharacterPlugins {
     fn build(&self, app: &mut App) {
-        app
+        app.add_plugins((CharacterAnimationPlugins, CameraShakePlugins))
+            .init_resource::<JumpConfig>()
+            .register_type::<JumpConfig>()
+            .init_resource::<MovementTuning>()
+            .register_type::<MovementTuning>()
             .add_systems(
                 FixedUpdate,
-                move_characters/*, update_jump_normals*/.run_if(
-                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                (move_characters, (jump_characters, fall_characters).chain()).run_if(
+                    not(in_state(LobbyState::None))
+                        .and_then(not(in_state(LobbyState::Client)))
+                        .and_then(resource_exists::<Lobby>),
                 ),
             )
+            .add_systems(OnEnter(LobbyState::None), reset_movement_tuning)
             .add_systems(
                 Update,
-                /*jump, */rotate_camera.run_if(
-                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                (request_jump, toggle_camera_mode)
+                    .run_if(not(in_state(LobbyState::None)).and_then(resource_exists::<Lobby>)),
+            )
+            .add_systems(
+                Update,
+                rotate_camera.run_if(
+                    not(in_state(LobbyState::None))
+                        .and_then(not(in_state(LobbyState::Client)))
+                        .and_then(in_state(MouseGrabState::Enable))
+                        .and_then(not(any_with_component::<FreeCamera>))
+                        .and_then(|wheel: Res<QuickChatWheel>| !wheel.open),
+                ),
+            )
+            .add_systems(
+                Update,
+                face_view.after(rotate_camera).run_if(
+                    not(in_state(LobbyState::None))
+                        .and_then(not(in_state(LobbyState::Client)))
+                        .and_then(|settings: Res<Settings>| {
+                            settings.camera_mode == CameraMode::FirstPerson
+                        }),
                 ),
             )
             //.add_systems(
@@ -53,25 +207,65 @@ impl Plugin for CharacterPlugins {
             //)
             .add_systems(
                 PostUpdate,
-                tied_camera_follow.run_if(not(in_state(LobbyState::None))),
+                (tied_camera_follow, hide_own_body)
+                    .chain()
+                    .run_if(not(in_state(LobbyState::None))),
             );
     }
 }
 
+/// Switches the [`CameraMode`] on [`CoreAction::CameraMode`] and saves it.
+fn toggle_camera_mode(
+    inputs_container: Res<Lobby>,
+    mut settings: ResMut<Settings>,
+    mut apply_settings: EventWriter<ApplySettings>,
+) {
+    let pressed = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::CameraMode))
+        .unwrap_or(false);
+    if !pressed {
+        return;
+    }
+    settings.camera_mode = settings.camera_mode.toggled();
+    apply_settings.send(ApplySettings);
+}
+
+/// Turns the own character with the view yaw in first person.
+fn face_view(mut query: Query<(&mut Transform, &PlayerView), (With<Me>, With<Character>)>) {
+    for (mut transform, view) in query.iter_mut() {
+        let (yaw, _, _) = view.direction.to_euler(EulerRot::YXZ);
+        transform.rotation = Quat::from_rotation_y(yaw);
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn tied_camera_follow(
-    mut tied_camera_query: Query<(&TiedCamera, &Children, &mut Transform)>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    shake: Res<CameraShake>,
+    mut tied_camera_query: Query<(&TiedCamera, &mut CameraModeBlend, &Children, &mut Transform)>,
     mut camera_query: Query<&mut Transform, (Without<TiedCamera>, With<Camera>)>,
     view_direction_query: Query<&PlayerView, With<Me>>,
     transform_query: Query<&Transform, (Without<TiedCamera>, Without<Camera>)>,
 ) {
-    for (TiedCamera(target), children, mut transform) in tied_camera_query.iter_mut() {
+    let target_blend = CameraModeBlend::target(settings.camera_mode);
+    let step = time.delta_seconds() / CAMERA_MODE_TRANSITION;
+    for (TiedCamera(target), mut blend, children, mut transform) in tied_camera_query.iter_mut() {
+        blend.0 += (target_blend - blend.0).clamp(-step, step);
         if let Ok(target_transform) = transform_query.get(*target) {
-            transform.translation = target_transform.translation + Vec3::Y * 2.;
+            let offset = THIRD_PERSON_PIVOT.lerp(FIRST_PERSON_HEAD_OFFSET, blend.0);
+            transform.translation = target_transform.translation + offset;
             if let Ok(view) = view_direction_query.get_single() {
                 transform.rotation = view.direction;
                 if let Some(child) = children.iter().next() {
                     if let Ok(mut camera_transform) = camera_query.get_mut(*child) {
-                        camera_transform.translation = view.distance * Vec3::Z;
+                        // the view keeps its distance, it is only replicated as 0
+                        let distance = view.distance * (1. - blend.0) * Vec3::Z;
+                        // on top of the distance, a shake never moves the eye out of the head
+                        camera_transform.translation =
+                            distance + shake.translation * (1. - blend.0);
+                        camera_transform.rotation = shake.rotation;
                     }
                 }
             }
@@ -84,138 +278,225 @@ fn tied_camera_follow(
     }
 }
 
-// TODO jump for rapiera
-//fn update_jump_normals(
-//    mut query: Query<(&mut JumpHelper, Entity, &GlobalTransform)>,
-//    collisions: Res<Collisions>,
-//) {
-//    let mut direction_vector = Vec3::ZERO;
-//    for (mut jump_direction, player_entity, transform) in query.iter_mut() {
-//        for collision in collisions.collisions_with_entity(player_entity) {
-//            for manifold in collision.manifolds.iter() {
-//                for contact in manifold.contacts.iter() {
-//                    direction_vector += transform.translation() - contact.point1;
-//                }
-//            }
-//        }
-//        jump_direction.last_viable_normal = direction_vector.normalize_or_zero();
-//    }
-//}
-
-//fn jump(
-//    mut query: Query<(
-//        &mut LinearVelocity,
-//        &PlayerView,
-//        &mut PlayerInputs,
-//        Entity,
-//        &JumpHelper,
-//    )>, /* , time: Res<Time> */
-//    gravity: Res<Gravity>,
-//    collisions: Res<Collisions>,
-//) {
-//    for (mut linear_velocity, view_direction, mut player_inputs, player_entity, jump_direction) in
-//        query.iter_mut()
-//    {
-//        let jumped = player_inputs.is_input_changed_to_true_and_set_to_false(InputType::Jump);
-//
-//        let dx = (player_inputs.get().right as i8 - player_inputs.get().left as i8) as f32;
-//        let dy = (player_inputs.get().down as i8 - player_inputs.get().up as i8) as f32;
-//
-//        let local_x = view_direction.direction.mul_vec3(Vec3::X);
-//        let local_y = view_direction.direction.mul_vec3(Vec3::Z);
-//
-//        if jumped
-//            && collisions
-//                .collisions_with_entity(player_entity)
-//                .next()
-//                .is_some()
-//        {
-//            **linear_velocity += ((jump_direction.last_viable_normal + local_x * dx + local_y * dy)
-//                    .normalize_or_zero())
-//                    * (-gravity.0.y * 2.0 * PLAYER_SIZE).sqrt() // sqrt(2gh)
-//                    * JUMP_HEIGHT_MULTIPLICATOR;
-//            log::debug!("{:?}", jump_direction.last_viable_normal);
-//        }
-//    }
-//}
-
-fn move_characters(// mut query: Query<(&mut Velocity, &PlayerView, &PlayerInputs)>, /* , time: Res<Time> */
+/// Hides the own character from a first person camera it would be inside of.
+fn hide_own_body(
+    blend_query: Query<&CameraModeBlend>,
+    free_camera_query: Query<(), With<FreeCamera>>,
+    me_query: Query<&Children, (With<Me>, With<Character>)>,
+    mut body_query: Query<&mut Visibility, With<CharacterBody>>,
+) {
+    let hidden = free_camera_query.is_empty()
+        && blend_query.iter().any(CameraModeBlend::is_first_person);
+    let visibility = if hidden {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for children in me_query.iter() {
+        let mut bodies = body_query.iter_many_mut(children);
+        while let Some(mut body_visibility) = bodies.fetch_next() {
+            // only touched on a change, so the body is not re-extracted every frame
+            body_visibility.set_if_neq(visibility);
+        }
+    }
+}
+
+/// Asks for a jump of the own character, the host and single simulate it,
+/// clients ask the host with [`ClientMessages::Jump`].
+fn request_jump(
+    mut commands: Commands,
+    inputs_container: Res<Lobby>,
+    lobby_state: Res<State<LobbyState>>,
+    client: Option<ResMut<RenetClient>>,
+    me_query: Query<Entity, (With<Me>, With<Character>)>,
+) {
+    let jumped = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::Jump))
+        .unwrap_or(false);
+    if !jumped {
+        return;
+    }
+
+    match lobby_state.get() {
+        LobbyState::Client => {
+            if let Some(mut client) = client {
+                send_to_server(&mut client, &ClientMessages::Jump, Channel::Control);
+            }
+        }
+        _ => {
+            if let Ok(entity) = me_query.get_single() {
+                commands.entity(entity).insert(JumpRequest);
+            }
+        }
+    }
+}
+
+/// Height of the ground under a character at `position`,
+/// `None` if it is farther than `tolerance` from the character bottom.
+fn ground_below(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    position: Vec3,
+    tolerance: f32,
+) -> Option<f32> {
+    let filter = QueryFilter::default()
+        .exclude_collider(entity)
+        .exclude_sensors();
+    rapier_context
+        .cast_ray(position, Vec3::NEG_Y, HALPH_PLAYER_SIZE + tolerance, true, filter)
+        .map(|(_, toi)| position.y - toi)
+}
+
+fn jump_characters(
+    mut commands: Commands,
+    config: Res<JumpConfig>,
+    rapier_context: Res<RapierContext>,
+    query: Query<(Entity, &Transform, Has<Airborne>), (With<Character>, With<JumpRequest>)>,
+) {
+    for (entity, transform, airborne) in query.iter() {
+        commands.entity(entity).remove::<JumpRequest>();
+        // no jumping again before landing, even when the ground is still within the tolerance
+        if airborne
+            || ground_below(&rapier_context, entity, transform.translation, config.ground_tolerance)
+                .is_none()
+        {
+            continue;
+        }
+        commands.entity(entity).insert(Airborne {
+            velocity: config.impulse,
+        });
+    }
+}
+
+pub(crate) fn fall_characters(
+    mut commands: Commands,
+    time: Res<Time>,
+    level_physics: Res<LevelPhysics>,
+    rapier_context: Res<RapierContext>,
+    mut query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Airborne,
+            Option<Ref<Teleported>>,
+            Option<&InGravityZone>,
+        ),
+        With<Character>,
+    >,
 ) {
-    // TODO:
-    //for (mut velocity, view_direction, input) in query.iter_mut() {
-    //    let input = input.get();
-    //    let dx = (input.right as i8 - input.left as i8) as f32;
-    //    let dy = (input.down as i8 - input.up as i8) as f32;
-
-    //    // convert axises to global
-    //    let view_direction_x = view_direction.direction.mul_vec3(Vec3::X);
-    //    let view_direction_y = view_direction.direction.mul_vec3(Vec3::Z);
-
-    //    // never use delta time in fixed update !!!
-    //    let shift_acceleration = SHIFT_ACCELERATION.powf(input.sprint as i32 as f32);
-
-    //    // move by x axis
-    //    velocity.linvel.x += dx * PLAYER_MOVE_SPEED * view_direction_x.x * shift_acceleration;
-    //    velocity.linvel.z += dx * PLAYER_MOVE_SPEED * view_direction_x.z * shift_acceleration;
-
-    //    // move by y axis
-    //    velocity.linvel.x += dy * PLAYER_MOVE_SPEED * view_direction_y.x * shift_acceleration;
-    //    velocity.linvel.z += dy * PLAYER_MOVE_SPEED * view_direction_y.z * shift_acceleration;
-    //}
+    // the same simulated time as rapier, see `LevelPhysics::time_scale`
+    let dt = time.delta_seconds() * level_physics.time_scale;
+    for (entity, mut transform, mut airborne, teleported, zone) in query.iter_mut() {
+        // a respawn ends the jump
+        if teleported.is_some_and(|teleported| teleported.is_changed()) {
+            commands.entity(entity).remove::<Airborne>();
+            continue;
+        }
+
+        let gravity = zone.map_or(level_physics.gravity, |InGravityZone(gravity)| *gravity);
+        airborne.velocity += gravity.y * dt;
+        let step = airborne.velocity * dt;
+        if step < 0. {
+            if let Some(ground) = ground_below(&rapier_context, entity, transform.translation, -step) {
+                transform.translation.y = ground + HALPH_PLAYER_SIZE;
+                commands.entity(entity).remove::<Airborne>();
+                continue;
+            }
+        }
+        transform.translation.y += step;
+    }
+}
+
+/// World space horizontal direction (x, z) the own character is moved in by the move actions,
+/// relative to its `view`. Zero while the free camera takes the move actions.
+pub fn movement_direction(inputs_container: &Lobby, view: &PlayerView, free_camera: bool) -> Vec2 {
+    let Some(inputs) = inputs_container.me().filter(|_| !free_camera) else {
+        return Vec2::ZERO;
+    };
+    let pressed = |action| inputs.get_pressed(action).unwrap_or(false) as i8 as f32;
+
+    let forward = pressed(CoreAction::MoveForward) - pressed(CoreAction::MoveBack);
+    let right = pressed(CoreAction::MoveRight) - pressed(CoreAction::MoveLeft);
+
+    // the view pitch does not slow down the move
+    let forward_axis = (view.direction * Vec3::NEG_Z).xz().normalize_or_zero();
+    let right_axis = (view.direction * Vec3::X).xz().normalize_or_zero();
+    (forward_axis * forward + right_axis * right).normalize_or_zero()
 }
 
+/// Moves the characters by their [`MoveVelocity`], the own one by the move actions
+/// and the client ones by their last [`MovementInput`].
 #[allow(clippy::type_complexity)]
-fn rotate_camera(// TODO:
-    //mut query: Query<(
-    //    &mut PlayerView,
-    //    &Transform,
-    //    &PlayerInputs,
-    //    //Option<&mut RayCaster>,
-    //    //Option<&RayHits>,
-    //)>,
-    //time: Res<Time>,
+fn move_characters(
+    time: Res<Time>,
+    level_physics: Res<LevelPhysics>,
+    tuning: Res<MovementTuning>,
+    inputs_container: Res<Lobby>,
+    free_camera_query: Query<(), With<FreeCamera>>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut MoveVelocity,
+            &PlayerView,
+            Option<&MovementInput>,
+            Option<&mut InputSequence>,
+            Has<Me>,
+        ),
+        With<Character>,
+    >,
 ) {
-    // TODO:
-    //let delta_seconds = time.delta_seconds();
-    //for (mut view, transform, input) in query.iter_mut() {
-    //    let input = input.get();
-
-    //    // camera turn
-    //    let rotation = Quat::from_rotation_y(input.turn_horizontal * SENSITIVITY * delta_seconds);
-    //    // global rotation (!ORDER OF MULTIPLICATION MATTERS!)
-    //    view.direction = rotation * view.direction;
-
-    //    let rotation = Quat::from_rotation_x(input.turn_vertical * SENSITIVITY * delta_seconds);
-    //    // local rotation (!ORDER OF MULTIPLICATION MATTERS!)
-    //    view.direction *= rotation;
-
-    //    view.distance = DEFAULT_CAMERA_DISTANCE;
-
-    //    // TODO:
-    //    // let ray_pos = Vec3::new(1.0, 2.0, 3.0);
-    //    // let ray_dir = Vec3::new(0.0, 1.0, 0.0);
-    //    // let max_toi = 4.0;
-    //    // let solid = true;
-    //    // let filter = QueryFilter::default();
-    //    // //rapier_context.cast_ray(ray_pos, ray_dir, max_toi, solid, filter)
-    //    //
-    //    // if let (Some(hits), Some(mut ray)) = (hits, ray) {
-    //    //     let h = transform.rotation.conjugate();
-    //    //     let start_point = h.mul_vec3(Vec3::Y * 2.);
-    //    //     let offset = h
-    //    //         .mul_vec3(view.direction.mul_vec3(DEFAULT_CAMERA_DISTANCE * Vec3::Z))
-    //    //         // need to normalize ray.direction to time_of_impact work correctly
-    //    //         .normalize();
-    //    //     ray.origin = start_point;
-    //    //     ray.direction = offset;
-    //    //
-    //    //     if let Some(firs_hit) = hits.iter_sorted().next() {
-    //    //         if firs_hit.time_of_impact < DEFAULT_CAMERA_DISTANCE {
-    //    //             view.distance = firs_hit.time_of_impact;
-    //    //         }
-    //    //     }
-    //    // }
-    //}
+    // the same simulated time as rapier, see `LevelPhysics::time_scale`
+    let dt = time.delta_seconds() * level_physics.time_scale;
+    for (mut transform, mut velocity, view, input, sequence, me) in query.iter_mut() {
+        let direction = if me {
+            movement_direction(&inputs_container, view, !free_camera_query.is_empty())
+        } else {
+            input.map_or(Vec2::ZERO, |input| input.0)
+        };
+        // the snapshot tells the client which of its inputs this position includes
+        if let Some(mut sequence) = sequence {
+            sequence.applied = sequence.received;
+        }
+
+        let state = MoveState {
+            position: transform.translation,
+            velocity: velocity.0,
+        };
+        let state = step_movement(state, direction, &tuning, dt);
+        transform.translation = state.position;
+        velocity.0 = state.velocity;
+    }
+}
+
+fn reset_movement_tuning(mut tuning: ResMut<MovementTuning>) {
+    *tuning = MovementTuning::default();
+}
+
+fn rotate_camera(
+    mut mouse_motion: EventReader<MouseMotion>,
+    settings: Res<Settings>,
+    mut query: Query<&mut PlayerView, With<Me>>,
+) {
+    let delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    if delta == Vec2::ZERO {
+        return;
+    }
+
+    let sensitivity = settings.mouse_sensitivity * MOUSE_RADIANS_PER_PIXEL;
+    let pitch_sign = if settings.invert_y { 1. } else { -1. };
+    for mut view in query.iter_mut() {
+        // camera turn
+        let rotation = Quat::from_rotation_y(-delta.x * sensitivity);
+        // global rotation (!ORDER OF MULTIPLICATION MATTERS!)
+        view.direction = rotation * view.direction;
+
+        let rotation = Quat::from_rotation_x(pitch_sign * delta.y * sensitivity);
+        // local rotation (!ORDER OF MULTIPLICATION MATTERS!)
+        view.direction *= rotation;
+
+        // TODO: shorten view.distance by a raycast so the camera does not go through walls
+    }
 }
 
 extend_commands!(
@@ -230,6 +511,11 @@ extend_commands!(
       .resource_mut::<Assets<StandardMaterial>>()
       .add(color);
 
+    // the delay of the session rules, kept up to date by `apply_respawn_delay`
+    let respawn_delay = world
+      .get_resource::<ServerRules>()
+      .map_or(0., |rules| rules.respawn_delay);
+
       // some raycast magic
     let _start_point = Vec3::Y * 2.;
     let _offset = Vec3::new(0., 0., DEFAULT_CAMERA_DISTANCE);
@@ -237,29 +523,43 @@ extend_commands!(
     world
         .entity_mut(entity_id)
         .insert((
-            PbrBundle {
-            mesh,
-            material,
-            ..Default::default()
-            },
+            SpatialBundle::default(),
             // TODO: RayCaster::new(start_point, offset),
-            // TODO: JumpHelper{last_viable_normal: Vec3::Y},
-            Respawn::new((
-                DespawnReason::More(200., AxisName::Y),
-                DespawnReason::Less(-10., AxisName::Y),
-                DespawnReason::More(100., AxisName::X),
-                DespawnReason::Less(-100., AxisName::X),
-                DespawnReason::More(100., AxisName::Z),
-                DespawnReason::Less(-100., AxisName::Z)
-            ),
-            SpawnProperty::new(spawn_point),
-            NoclipDuration::Timer(10.)),
+            Respawn::new()
+                .with_spawn(SpawnProperty::new(spawn_point))
+                .with_reasons((
+                    DespawnReason::More(200., AxisName::Y),
+                    DespawnReason::More(100., AxisName::X),
+                    DespawnReason::Less(-100., AxisName::X),
+                    DespawnReason::More(100., AxisName::Z),
+                    DespawnReason::Less(-100., AxisName::Z)
+                ))
+                .on_fall_below(-10.)
+                .with_delay(respawn_delay)
+                .with_noclip(NoclipDuration::Timer(10.)),
             // TODO: PlayerInputs::default(),
             Character { id: player_id },
             PlayerView::new(Quat::default(), 325_f32.sqrt()),
+            MoveVelocity::default(),
+            CharacterAnimation::default(),
+            Health::default(),
             Name::new(format!("Character:{:#?}", player_id)),
             // PhysicsOptimalTrace::new(0.5, 0.05, color, PLAYER_SIZE / 2.),
+            // moved by its replicated transform, pushes props simulated on the host
+            RigidBody::KinematicPositionBased,
+            Collider::cuboid(HALPH_PLAYER_SIZE, HALPH_PLAYER_SIZE, HALPH_PLAYER_SIZE),
+            character_groups(),
         ))
+        .with_children(|parent| {
+            parent.spawn((
+                PbrBundle {
+                    mesh,
+                    material,
+                    ..Default::default()
+                },
+                CharacterBody,
+            ));
+        })
         // TODO:
         //.insert((
         //    Friction::new(0.4),
@@ -289,16 +589,26 @@ extend_commands!(
     world
      .entity_mut(entity_id)
      .insert((
-       PbrBundle {
-          mesh,
-          material,
+       SpatialBundle {
           transform: Transform::from_xyz(spawn_point.x, spawn_point.y, spawn_point.z),
           ..Default::default()
        },
         // TransformOptimalTrace::new(0.5, 0.05, color, PLAYER_SIZE / 2.),
         // TODO: PlayerInputs::default(),
         Name::new(format!("Character:{:#?}", player_id)),
-        PlayerView::new(Quat::default(), 325_f32.sqrt())));
+        PlayerView::new(Quat::default(), 325_f32.sqrt()),
+        CharacterAnimation::default(),
+        Health::default()))
+     .with_children(|parent| {
+        parent.spawn((
+            PbrBundle {
+                mesh,
+                material,
+                ..Default::default()
+            },
+            CharacterBody,
+        ));
+     });
   }
 );
 
@@ -325,6 +635,12 @@ extend_commands!(
         // TODO find light prd without mesh
         PbrBundle::default(),
         TiedCamera(target),
+        // the camera starts where the saved mode puts it
+        CameraModeBlend(
+            world
+              .get_resource::<Settings>()
+              .map_or(0., |settings| CameraModeBlend::target(settings.camera_mode)),
+        ),
         Name::new("TiedCamera"),
       ))
       .with_children(|parent| {
@@ -339,3 +655,120 @@ extend_commands!(
       });
   }
 );
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::scene::ScenePlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use bevy_rapier3d::plugin::{NoUserData, PhysicsSet, RapierPhysicsPlugin};
+    use bevy_rapier3d::prelude::Velocity;
+
+    use super::*;
+    use crate::physics::groups::projectile_groups;
+    use crate::world::SimulationPlugins;
+    use crate::ASSET_DIR;
+
+    /// Fixed ticks run by an app, they alone decide how far a body got.
+    #[derive(Debug, Default, Resource)]
+    struct Ticks(u32);
+
+    /// Positions of the character and the projectile after each fixed tick.
+    #[derive(Debug, Default, Resource)]
+    struct Trajectory(Vec<(Vec3, Vec3)>);
+
+    #[derive(Component)]
+    struct Tracked;
+
+    /// Positions after each of `ticks` fixed ticks of a character moving along x while jumping
+    /// and a projectile flying along z, rendered at `fps` frames per second.
+    fn trajectory(ticks: u32, fps: u32) -> Vec<(Vec3, Vec3)> {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: ASSET_DIR.into(),
+                ..default()
+            },
+            TransformPlugin,
+            HierarchyPlugin,
+            ScenePlugin,
+            // as the game runs it, stepped at the rate `SimulationPlugins` configures
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+            SimulationPlugins,
+        ))
+        .init_asset::<Mesh>()
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1) / fps))
+        .init_resource::<Ticks>()
+        .init_resource::<Trajectory>()
+        .init_resource::<Lobby>()
+        .init_resource::<MovementTuning>()
+        .add_systems(
+            FixedUpdate,
+            (
+                (
+                    |mut ticks: ResMut<Ticks>| ticks.0 += 1,
+                    move_characters,
+                    fall_characters,
+                )
+                    .chain()
+                    .before(PhysicsSet::SyncBackend),
+                record_trajectory.after(PhysicsSet::Writeback),
+            ),
+        );
+        app.world.spawn((
+            Character {
+                id: PlayerId::Client(renet::ClientId::from_raw(1)),
+            },
+            TransformBundle::default(),
+            MoveVelocity::default(),
+            PlayerView::default(),
+            MovementInput(Vec2::X),
+            Airborne {
+                velocity: JumpConfig::default().impulse,
+            },
+            RigidBody::KinematicPositionBased,
+            Collider::cuboid(HALPH_PLAYER_SIZE, HALPH_PLAYER_SIZE, HALPH_PLAYER_SIZE),
+            character_groups(),
+        ));
+        app.world.spawn((
+            Tracked,
+            TransformBundle::from_transform(Transform::from_xyz(0., 5., 50.)),
+            RigidBody::Dynamic,
+            Collider::ball(0.1),
+            projectile_groups(),
+            Velocity::linear(Vec3::new(0., 5., 30.)),
+        ));
+
+        while app.world.resource::<Ticks>().0 < ticks {
+            app.update();
+        }
+        let mut trajectory = std::mem::take(&mut app.world.resource_mut::<Trajectory>().0);
+        trajectory.truncate(ticks as usize);
+        trajectory
+    }
+
+    fn record_trajectory(
+        mut trajectory: ResMut<Trajectory>,
+        character_query: Query<&Transform, With<Character>>,
+        projectile_query: Query<&Transform, With<Tracked>>,
+    ) {
+        trajectory.0.push((
+            character_query.single().translation,
+            projectile_query.single().translation,
+        ));
+    }
+
+    #[test]
+    fn simulation_does_not_depend_on_frame_rate() {
+        // none of the rates divides the 64 Hz tick, frames run a varying number of ticks
+        let reference = trajectory(64, 60);
+        let (character, projectile) = *reference.last().unwrap();
+        assert!(character.x > 0.);
+        assert!(projectile.z > 50.);
+        for fps in [30, 240] {
+            assert_eq!(trajectory(64, fps), reference, "at {} FPS", fps);
+        }
+    }
+}
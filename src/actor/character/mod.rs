@@ -1,4 +1,8 @@
 #![allow(clippy::module_inception)]
 
+mod animation;
+mod camera_shake;
 mod character;
+pub use animation::*;
+pub use camera_shake::*;
 pub use character::*;
@@ -1,9 +1,15 @@
 #![allow(clippy::module_inception)]
 
 mod actor;
+mod prefab;
+mod projectile;
+mod prop;
 mod trace;
 
 pub mod character;
 
 pub use actor::*;
+pub use prefab::*;
+pub use projectile::*;
+pub use prop::*;
 pub use trace::*;
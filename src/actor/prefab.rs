@@ -0,0 +1,297 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::extend_commands;
+use crate::lobby::PlayerId;
+use crate::world::LinkId;
+
+/// Color of the stand-in of a prefab this build does not know
+const PLACEHOLDER_COLOR: Color = Color::rgb(1., 0., 1.);
+/// Half side of the stand-in of a prefab without a size
+const PLACEHOLDER_HALF_SIZE: f32 = 0.5;
+
+/// Name of a kind of actor, the same on every build that knows it.
+///
+/// A string rather than an enum, so a prefab of a newer build still decodes and shows up
+/// as a placeholder, see [`spawn_prefab`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PrefabId(Cow<'static, str>);
+
+impl PrefabId {
+    pub const PROP: PrefabId = PrefabId::new_static("prop");
+    pub const PROJECTILE: PrefabId = PrefabId::new_static("projectile");
+
+    pub const fn new_static(name: &'static str) -> Self {
+        Self(Cow::Borrowed(name))
+    }
+}
+
+impl fmt::Display for PrefabId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What a prefab is spawned with, each prefab reads the fields it needs.
+#[derive(Debug, Clone)]
+pub struct PrefabArgs {
+    pub link_id: LinkId,
+    /// Inserted after the prefab, so every prefab can be placed and rotated
+    pub transform: Transform,
+    /// Half of each side of a box shaped prefab
+    pub half_size: Vec3,
+    pub color: Color,
+    pub owner: Option<PlayerId>,
+    /// Initial velocity of a moving prefab, only used by the simulating side
+    pub velocity: Vec3,
+}
+
+impl PrefabArgs {
+    pub fn new(link_id: LinkId) -> Self {
+        Self {
+            link_id,
+            transform: Transform::default(),
+            half_size: Vec3::ZERO,
+            color: Color::WHITE,
+            owner: None,
+            velocity: Vec3::ZERO,
+        }
+    }
+}
+
+/// Inserts the components of a prefab into a spawned entity.
+pub type PrefabSpawnFn = fn(&mut World, Entity, PrefabArgs);
+
+/// Spawn functions of a prefab.
+#[derive(Debug, Clone, Copy)]
+pub struct Prefab {
+    /// Full set of components of the simulating side, the host or single
+    pub authoritative: PrefabSpawnFn,
+    /// Display only, moved by the host actor sync on a client
+    pub shell: PrefabSpawnFn,
+}
+
+/// The prefabs known to this build, registered by the plugins of the actors.
+///
+/// See [`PrefabAppExt::register_prefab`].
+#[derive(Debug, Default, Resource)]
+pub struct PrefabRegistry(HashMap<PrefabId, Prefab>);
+
+impl PrefabRegistry {
+    /// Adds a prefab, replacing the previous one of the same id.
+    pub fn register(&mut self, id: PrefabId, prefab: Prefab) {
+        if self.0.insert(id.clone(), prefab).is_some() {
+            log::warn!("Prefab {} is registered twice, the last one is kept", id);
+        }
+    }
+
+    pub fn get(&self, id: &PrefabId) -> Option<&Prefab> {
+        self.0.get(id)
+    }
+
+    /// The spawn function of `id` for this side, see [`Prefab`].
+    pub fn spawn_fn(&self, id: &PrefabId, shell: bool) -> Option<PrefabSpawnFn> {
+        self.get(id)
+            .map(|prefab| if shell { prefab.shell } else { prefab.authoritative })
+    }
+}
+
+pub trait PrefabAppExt {
+    /// Registers a prefab, see [`PrefabRegistry::register`].
+    fn register_prefab(
+        &mut self,
+        id: PrefabId,
+        authoritative: PrefabSpawnFn,
+        shell: PrefabSpawnFn,
+    ) -> &mut Self;
+}
+
+impl PrefabAppExt for App {
+    fn register_prefab(
+        &mut self,
+        id: PrefabId,
+        authoritative: PrefabSpawnFn,
+        shell: PrefabSpawnFn,
+    ) -> &mut Self {
+        self.init_resource::<PrefabRegistry>();
+        self.world
+            .resource_mut::<PrefabRegistry>()
+            .register(id, Prefab { authoritative, shell });
+        self
+    }
+}
+
+extend_commands!(
+  spawn_prefab(id: PrefabId, args: PrefabArgs, shell: bool),
+  |world: &mut World, entity_id: Entity, id: PrefabId, args: PrefabArgs, shell: bool| {
+    let transform = args.transform;
+    let spawn_fn = world
+        .get_resource::<PrefabRegistry>()
+        .and_then(|registry| registry.spawn_fn(&id, shell));
+    match spawn_fn {
+        Some(spawn_fn) => spawn_fn(world, entity_id, args),
+        // a content mismatch with the host must be obvious
+        None => {
+            log::warn!("Unknown prefab {} of {:?}, spawned as a placeholder", id, args.link_id);
+            spawn_placeholder(world, entity_id, &id, args);
+        }
+    }
+    world.entity_mut(entity_id).insert(transform);
+  }
+);
+
+/// A magenta cube in place of a prefab this build does not know, without physics.
+fn spawn_placeholder(world: &mut World, entity_id: Entity, id: &PrefabId, args: PrefabArgs) {
+    let half_size = if args.half_size == Vec3::ZERO {
+        Vec3::splat(PLACEHOLDER_HALF_SIZE)
+    } else {
+        args.half_size
+    };
+    let mesh = world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Mesh::from(Cuboid { half_size }));
+    let material = world
+        .resource_mut::<Assets<StandardMaterial>>()
+        .add(StandardMaterial {
+            base_color: PLACEHOLDER_COLOR,
+            unlit: true,
+            ..default()
+        });
+
+    world.entity_mut(entity_id).insert((
+        PbrBundle {
+            mesh,
+            material,
+            ..default()
+        },
+        Name::new(format!("Placeholder:{}:{}", id, args.link_id)),
+        args.link_id,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[derive(Component)]
+    struct Authoritative;
+
+    #[derive(Component)]
+    struct Shell;
+
+    fn spawn_authoritative(world: &mut World, entity_id: Entity, args: PrefabArgs) {
+        world.entity_mut(entity_id).insert((Authoritative, args.link_id));
+    }
+
+    fn spawn_shell(world: &mut World, entity_id: Entity, args: PrefabArgs) {
+        world.entity_mut(entity_id).insert((Shell, args.link_id));
+    }
+
+    fn link() -> LinkId {
+        LinkId::Scene("crate".into())
+    }
+
+    fn world() -> World {
+        let mut app = App::new();
+        app.register_prefab(PrefabId::PROP, spawn_authoritative, spawn_shell);
+        let mut world = std::mem::take(&mut app.world);
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<StandardMaterial>>();
+        world
+    }
+
+    fn spawn(world: &mut World, id: PrefabId, shell: bool) -> Entity {
+        let args = PrefabArgs {
+            transform: Transform::from_xyz(1., 2., 3.),
+            ..PrefabArgs::new(link())
+        };
+        world.run_system_once(move |mut commands: Commands| {
+            commands.spawn_prefab(id.clone(), args.clone(), shell);
+        });
+        world
+            .query_filtered::<Entity, With<LinkId>>()
+            .single(world)
+    }
+
+    #[test]
+    fn lookup_picks_the_side() {
+        let mut world = world();
+        let registry = world.remove_resource::<PrefabRegistry>().unwrap();
+        assert!(registry.get(&PrefabId::PROP).is_some());
+        assert!(registry.spawn_fn(&PrefabId::PROJECTILE, false).is_none());
+
+        for shell in [false, true] {
+            let entity = world.spawn_empty().id();
+            let spawn_fn = registry.spawn_fn(&PrefabId::PROP, shell).unwrap();
+            spawn_fn(&mut world, entity, PrefabArgs::new(link()));
+            assert_eq!(world.get::<Shell>(entity).is_some(), shell);
+            assert_eq!(world.get::<Authoritative>(entity).is_some(), !shell);
+        }
+    }
+
+    #[test]
+    fn registering_again_replaces() {
+        let mut app = App::new();
+        app.register_prefab(PrefabId::PROP, spawn_authoritative, spawn_authoritative)
+            .register_prefab(PrefabId::PROP, spawn_shell, spawn_shell);
+
+        let entity = app.world.spawn_empty().id();
+        let spawn_fn = app
+            .world
+            .resource::<PrefabRegistry>()
+            .spawn_fn(&PrefabId::PROP, false)
+            .unwrap();
+        spawn_fn(&mut app.world, entity, PrefabArgs::new(link()));
+        assert!(app.world.get::<Shell>(entity).is_some());
+    }
+
+    #[test]
+    fn spawn_prefab_spawns_the_authoritative_side() {
+        let mut world = world();
+        let entity = spawn(&mut world, PrefabId::PROP, false);
+        assert!(world.get::<Authoritative>(entity).is_some());
+        assert_eq!(
+            world.get::<Transform>(entity).unwrap().translation,
+            Vec3::new(1., 2., 3.)
+        );
+    }
+
+    #[test]
+    fn spawn_prefab_spawns_the_shell() {
+        let mut world = world();
+        let entity = spawn(&mut world, PrefabId::PROP, true);
+        assert!(world.get::<Shell>(entity).is_some());
+        assert!(world.get::<Authoritative>(entity).is_none());
+    }
+
+    #[test]
+    fn unknown_prefab_spawns_a_placeholder() {
+        let mut world = world();
+        let entity = spawn(&mut world, PrefabId::new_static("newer"), true);
+        assert!(world.get::<Shell>(entity).is_none());
+        assert_eq!(world.get::<LinkId>(entity), Some(&link()));
+
+        let material = world.get::<Handle<StandardMaterial>>(entity).unwrap();
+        let material = world
+            .resource::<Assets<StandardMaterial>>()
+            .get(material)
+            .unwrap();
+        assert_eq!(material.base_color, PLACEHOLDER_COLOR);
+        assert!(world.get::<Handle<Mesh>>(entity).is_some());
+    }
+
+    #[test]
+    fn unknown_ids_still_decode() {
+        let bytes = bincode::serialize(&PrefabId::new_static("newer")).unwrap();
+        let id: PrefabId = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(id.to_string(), "newer");
+        assert_eq!(id, PrefabId::new_static("newer"));
+    }
+}
@@ -0,0 +1,304 @@
+use bevy::prelude::*;
+use bevy_controls::contract::InputsContainer;
+use bevy_rapier3d::plugin::PhysicsSet;
+use bevy_rapier3d::prelude::{ActiveEvents, Ccd, Collider, CollisionEvent, RigidBody, Velocity};
+use renet::RenetClient;
+
+use crate::actor::character::{CameraShakeEvent, HALPH_PLAYER_SIZE};
+use crate::actor::{spawn_prefab, Actor, PrefabAppExt, PrefabArgs, PrefabId};
+use crate::component::{
+    AxisName, Despawn, DespawnReason, Health, HealthChangedEvent, Lifetime, Respawn,
+};
+use crate::core::CoreAction;
+use crate::lobby::client::send_to_server;
+use crate::lobby::host::{DespawnActorEvent, ServerSettings, SpawnProjectileEvent};
+use crate::lobby::sync_policy::SyncPolicy;
+use crate::lobby::{
+    Character, ClientMessages, DeathCause, Lobby, LobbyState, PlayerDiedEvent, PlayerId, PlayerView,
+};
+use crate::network::Channel;
+use crate::physics::groups::projectile_groups;
+use crate::world::{LinkId, LinkIdAllocator, Me};
+
+/// Radius of the projectile shell shown on clients
+const PROJECTILE_RADIUS: f32 = 0.1;
+/// Gap between the character and a projectile it fires
+const MUZZLE_GAP: f32 = 0.1;
+
+/// Tuning of the projectiles, used by the simulating side.
+#[derive(Debug, Clone, Copy, Resource, Reflect)]
+pub struct ProjectileConfig {
+    /// Health a hit takes
+    pub damage: f32,
+    /// Units per second
+    pub speed: f32,
+    /// Seconds before a projectile that hit nothing disappears, see [`Lifetime`]
+    pub lifetime: f32,
+    /// Seconds the owner cannot be hit by their own projectile
+    pub owner_grace: f32,
+}
+
+impl Default for ProjectileConfig {
+    fn default() -> Self {
+        Self {
+            damage: 25.,
+            speed: 30.,
+            lifetime: 3.,
+            owner_grace: 0.2,
+        }
+    }
+}
+
+/// Player who fired a projectile, used to attribute its hits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Owner(pub PlayerId);
+
+/// A projectile simulated by the host, damaging the character it hits.
+#[derive(Debug, Component)]
+pub struct Projectile {
+    pub damage: f32,
+    /// Running while the owner is ignored, so a projectile does not hit the one firing it
+    owner_grace: Timer,
+}
+
+/// Makes the character fire on the next fixed tick.
+#[derive(Debug, Default, Component)]
+pub struct FireRequest;
+
+pub struct ProjectilePlugins;
+
+impl Plugin for ProjectilePlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProjectileConfig>()
+            .register_type::<ProjectileConfig>()
+            .register_prefab(PrefabId::PROJECTILE, insert_projectile, insert_projectile_shell)
+            .add_systems(
+                Update,
+                request_fire
+                    .run_if(not(in_state(LobbyState::None)).and_then(resource_exists::<Lobby>)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (fire_projectiles, projectile_hits.after(PhysicsSet::Writeback)).run_if(
+                    not(in_state(LobbyState::None))
+                        .and_then(not(in_state(LobbyState::Client)))
+                        .and_then(resource_exists::<Lobby>),
+                ),
+            );
+    }
+}
+
+/// The [`PrefabId::PROJECTILE`] prefab, moving by `args.velocity` and tuned by the
+/// [`ProjectileConfig`] of the world.
+fn insert_projectile(world: &mut World, entity_id: Entity, args: PrefabArgs) {
+    let config = *world.resource::<ProjectileConfig>();
+    let mesh = world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Mesh::from(Sphere::new(PROJECTILE_RADIUS)));
+    let material = world
+        .resource_mut::<Assets<StandardMaterial>>()
+        .add(args.color);
+
+    world.entity_mut(entity_id).insert((
+        PbrBundle {
+            mesh,
+            material,
+            transform: args.transform,
+            ..default()
+        },
+        Actor,
+        Owner(args.owner.unwrap_or_default()),
+        Projectile {
+            damage: config.damage,
+            owner_grace: Timer::from_seconds(config.owner_grace, TimerMode::Once),
+        },
+        Lifetime(config.lifetime),
+        Despawn::new(DespawnReason::Less(-10., AxisName::Y)),
+        RigidBody::Dynamic,
+        Collider::ball(PROJECTILE_RADIUS),
+        projectile_groups(),
+        Velocity::linear(args.velocity),
+        // fast enough to pass through a character between two ticks
+        Ccd::enabled(),
+        ActiveEvents::COLLISION_EVENTS,
+        SyncPolicy::kind("projectile"),
+        Name::new(format!("Projectile:{}", args.link_id)),
+        args.link_id,
+    ));
+}
+
+/// Shell of [`insert_projectile`], placed by the host actor sync like the props.
+fn insert_projectile_shell(world: &mut World, entity_id: Entity, args: PrefabArgs) {
+    let mesh = world
+        .resource_mut::<Assets<Mesh>>()
+        .add(Mesh::from(Sphere::new(PROJECTILE_RADIUS)));
+    let material = world
+        .resource_mut::<Assets<StandardMaterial>>()
+        .add(args.color);
+
+    world.entity_mut(entity_id).insert((
+        PbrBundle {
+            mesh,
+            material,
+            ..default()
+        },
+        Actor,
+        Owner(args.owner.unwrap_or_default()),
+        Name::new(format!("Projectile:{}", args.link_id)),
+        args.link_id,
+    ));
+}
+
+/// Fires from the own character, the host and single simulate it,
+/// clients ask the host with [`ClientMessages::Fire`].
+fn request_fire(
+    mut commands: Commands,
+    inputs_container: Res<Lobby>,
+    lobby_state: Res<State<LobbyState>>,
+    client: Option<ResMut<RenetClient>>,
+    me_query: Query<Entity, (With<Me>, With<Character>)>,
+) {
+    let fired = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::Fire))
+        .unwrap_or(false);
+    if !fired {
+        return;
+    }
+
+    match lobby_state.get() {
+        LobbyState::Client => {
+            if let Some(mut client) = client {
+                send_to_server(&mut client, &ClientMessages::Fire, Channel::Control);
+            }
+        }
+        _ => {
+            if let Ok(entity) = me_query.get_single() {
+                commands.entity(entity).insert(FireRequest);
+            }
+        }
+    }
+}
+
+fn fire_projectiles(
+    mut commands: Commands,
+    config: Res<ProjectileConfig>,
+    lobby: Res<Lobby>,
+    mut allocator: ResMut<LinkIdAllocator>,
+    mut spawn_projectile_event: EventWriter<SpawnProjectileEvent>,
+    query: Query<(Entity, &Transform, &PlayerView, &Character), With<FireRequest>>,
+) {
+    for (entity, transform, view, character) in query.iter() {
+        commands.entity(entity).remove::<FireRequest>();
+
+        // along the view, the camera looks down its -Z
+        let direction = view.direction * Vec3::NEG_Z;
+        // spawned ahead of the character so it does not start inside it
+        let position = transform.translation
+            + direction * (HALPH_PLAYER_SIZE * 3_f32.sqrt() + PROJECTILE_RADIUS + MUZZLE_GAP);
+        let color = lobby
+            .player(&character.id)
+            .map(|player_data| player_data.color)
+            .unwrap_or(Color::WHITE);
+
+        let link_id = allocator.allocate();
+        commands.spawn_prefab(
+            PrefabId::PROJECTILE,
+            PrefabArgs {
+                transform: Transform::from_translation(position),
+                color,
+                owner: Some(character.id),
+                velocity: direction * config.speed,
+                ..PrefabArgs::new(link_id.clone())
+            },
+            false,
+        );
+        spawn_projectile_event.send(SpawnProjectileEvent {
+            link_id,
+            owner: character.id,
+        });
+    }
+}
+
+/// A projectile disappears on its first hit, damaging the character it hit
+/// unless it is a teammate of the owner and [`TeamRules::friendly_fire`] is off.
+///
+/// [`TeamRules::friendly_fire`]: crate::lobby::team::TeamRules::friendly_fire
+#[allow(clippy::too_many_arguments)]
+fn projectile_hits(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<ServerSettings>,
+    lobby: Res<Lobby>,
+    mut collision_event: EventReader<CollisionEvent>,
+    mut projectile_query: Query<(&mut Projectile, &Owner, &LinkId, &Transform)>,
+    mut character_query: Query<(&Character, &mut Health, &mut Respawn)>,
+    mut health_changed_event: EventWriter<HealthChangedEvent>,
+    mut player_died_event: EventWriter<PlayerDiedEvent>,
+    mut despawn_actor_event: EventWriter<DespawnActorEvent>,
+    mut camera_shake_event: EventWriter<CameraShakeEvent>,
+) {
+    for (mut projectile, ..) in projectile_query.iter_mut() {
+        projectile.owner_grace.tick(time.delta());
+    }
+
+    let mut hit = Vec::new();
+    for event in collision_event.read() {
+        let CollisionEvent::Started(a, b, _) = event else {
+            continue;
+        };
+        let (projectile_entity, target) = if projectile_query.contains(*a) {
+            (*a, *b)
+        } else if projectile_query.contains(*b) {
+            (*b, *a)
+        } else {
+            continue;
+        };
+        // touching several colliders at once, only the first one counts
+        if hit.contains(&projectile_entity) {
+            continue;
+        }
+        let Ok((projectile, Owner(owner), link_id, transform)) =
+            projectile_query.get(projectile_entity)
+        else {
+            continue;
+        };
+
+        if let Ok((character, mut health, mut respawn)) = character_query.get_mut(target) {
+            if character.id == *owner && !projectile.owner_grace.finished() {
+                continue;
+            }
+            if respawn.is_pending() {
+                continue;
+            }
+            let team_of = |id: &PlayerId| lobby.player(id).and_then(|player_data| player_data.team);
+            let teammate = character.id != *owner
+                && team_of(&character.id).is_some()
+                && team_of(&character.id) == team_of(owner);
+            // a spared teammate still stops the projectile
+            let spared = teammate && settings.teams.is_some_and(|rules| !rules.friendly_fire);
+            if !spared {
+                if health.damage(projectile.damage) {
+                    // the kill is reported here with its killer, the respawn itself is not a death
+                    respawn.insert_reason(DespawnReason::Forced);
+                    player_died_event.send(PlayerDiedEvent {
+                        id: character.id,
+                        killer: Some(*owner),
+                        cause: DeathCause::Killed,
+                    });
+                }
+                health_changed_event.send(HealthChangedEvent {
+                    id: character.id,
+                    health: *health,
+                    attacker: Some(*owner),
+                });
+            }
+        }
+
+        hit.push(projectile_entity);
+        // clients shake on the despawn, see `shake_on_projectile_despawn`
+        camera_shake_event.send(CameraShakeEvent::impact(transform.translation));
+        despawn_actor_event.send(DespawnActorEvent(link_id.clone()));
+        commands.entity(projectile_entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_controls::contract::InputsContainer;
+use bevy_rapier3d::prelude::{Collider, ExternalImpulse, ReadMassProperties, RigidBody, Sleeping};
+use renet::RenetClient;
+
+use crate::actor::{PrefabAppExt, PrefabId};
+use crate::core::CoreAction;
+use crate::extend_commands;
+use crate::lobby::client::send_to_server;
+use crate::lobby::{ClientMessages, Lobby, LobbyState};
+use crate::network::Channel;
+use crate::physics::groups::prop_groups;
+use crate::world::{LinkId, Me};
+
+/// Farthest a character can be from a prop it kicks
+pub const MAX_IMPULSE_RANGE: f32 = 4.;
+/// Strongest impulse a single kick can apply
+pub const MAX_IMPULSE: f32 = 30.;
+/// Impulse of the kick action
+const KICK_IMPULSE: f32 = 15.;
+
+/// A dynamic object simulated by the host, clients only display it.
+#[derive(Debug, Default, Component)]
+pub struct Prop;
+
+pub struct PropPlugins;
+
+impl Plugin for PropPlugins {
+    fn build(&self, app: &mut App) {
+        app.register_prefab(
+            PrefabId::PROP,
+            |world, entity_id, args| {
+                let position = args.transform.translation;
+                insert_prop(world, entity_id, args.link_id, args.half_size, position, false);
+            },
+            |world, entity_id, args| {
+                let position = args.transform.translation;
+                insert_prop(world, entity_id, args.link_id, args.half_size, position, true);
+            },
+        )
+        .add_systems(
+            Update,
+            kick.run_if(not(in_state(LobbyState::None)).and_then(resource_exists::<Lobby>)),
+        );
+    }
+}
+
+/// Clamps a client requested `impulse` on a prop at `prop_position`,
+/// `None` if the character at `character_position` is too far to touch it.
+pub fn validate_impulse(character_position: Vec3, prop_position: Vec3, impulse: Vec3) -> Option<Vec3> {
+    if !impulse.is_finite() || character_position.distance(prop_position) > MAX_IMPULSE_RANGE {
+        return None;
+    }
+    Some(impulse.clamp_length_max(MAX_IMPULSE))
+}
+
+/// Kicks the closest prop in range away from the own character.
+///
+/// The host and single apply the impulse directly, clients ask the host with [`ClientMessages::ImpulseRequest`].
+fn kick(
+    mut commands: Commands,
+    inputs_container: Res<Lobby>,
+    lobby_state: Res<State<LobbyState>>,
+    client: Option<ResMut<RenetClient>>,
+    me_query: Query<&Transform, With<Me>>,
+    prop_query: Query<(Entity, &Transform, &LinkId), With<Prop>>,
+) {
+    let kicked = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::Kick))
+        .unwrap_or(false);
+    if !kicked {
+        return;
+    }
+    let Ok(me) = me_query.get_single() else {
+        return;
+    };
+
+    let closest = prop_query
+        .iter()
+        .map(|(entity, transform, link_id)| {
+            (entity, transform, link_id, transform.translation.distance(me.translation))
+        })
+        .filter(|(.., distance)| *distance <= MAX_IMPULSE_RANGE)
+        .min_by(|(.., a), (.., b)| a.total_cmp(b));
+    let Some((entity, transform, link_id, _)) = closest else {
+        return;
+    };
+
+    let direction = (transform.translation - me.translation).normalize_or_zero() + Vec3::Y * 0.5;
+    let impulse = direction.normalize_or_zero() * KICK_IMPULSE;
+
+    match lobby_state.get() {
+        LobbyState::Client => {
+            if let Some(mut client) = client {
+                send_to_server(
+                    &mut client,
+                    &ClientMessages::ImpulseRequest {
+                        target: link_id.clone(),
+                        impulse,
+                    },
+                    Channel::Control,
+                );
+            }
+        }
+        _ => {
+            commands.entity(entity).insert(ExternalImpulse {
+                impulse,
+                ..default()
+            });
+        }
+    }
+}
+
+extend_commands!(
+  spawn_prop(link_id: LinkId, half_size: Vec3, position: Vec3, shell: bool),
+  insert_prop
+);
+
+/// Also the [`PrefabId::PROP`] prefab.
+fn insert_prop(
+    world: &mut World,
+    entity_id: Entity,
+    link_id: LinkId,
+    half_size: Vec3,
+    position: Vec3,
+    shell: bool,
+) {
+    let mesh = world
+      .resource_mut::<Assets<Mesh>>()
+      .add(Mesh::from(Cuboid { half_size }));
+    let material = world
+      .resource_mut::<Assets<StandardMaterial>>()
+      .add(Color::rgb(0.6, 0.4, 0.2));
+
+    let mut entity = world.entity_mut(entity_id);
+    entity.insert((
+        PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(position),
+            ..default()
+        },
+        Prop,
+        Name::new(format!("Prop:{}", link_id)),
+        link_id,
+    ));
+
+    // clients only display props, their motion comes from the host actor sync
+    if !shell {
+        entity.insert((
+            RigidBody::Dynamic,
+            Collider::cuboid(half_size.x, half_size.y, half_size.z),
+            prop_groups(),
+            Sleeping::default(),
+            // for the gravity zones
+            ReadMassProperties::default(),
+        ));
+    }
+}
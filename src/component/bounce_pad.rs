@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use bevy_rapier3d::plugin::PhysicsSet;
+use bevy_rapier3d::prelude::{
+    ActiveCollisionTypes, ActiveEvents, Collider, CollisionEvent, ExternalImpulse,
+    ReadMassProperties, Sensor,
+};
+
+use crate::actor::character::{Airborne, MoveVelocity};
+use crate::lobby::validation::MovementGrace;
+use crate::lobby::{Character, LobbyState};
+use crate::physics::groups::sensor_groups;
+
+/// Seconds the moves of a launched character are not validated, about the flight of a launch
+const LAUNCH_GRACE: f32 = 2.;
+
+/// Box volume launching the bodies entering it along its up axis, placed in the level scene.
+///
+/// Only entering launches, a body resting on the pad is not launched again until it leaves.
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct BouncePad {
+    /// Speed (units per second) the launch gives, the same for every body whatever its mass
+    pub impulse: f32,
+    /// Half extents of the volume, scaled by the entity transform
+    pub half_size: Vec3,
+}
+
+impl Default for BouncePad {
+    fn default() -> Self {
+        Self {
+            impulse: 15.,
+            half_size: Vec3::new(1., 0.1, 1.),
+        }
+    }
+}
+
+pub struct BouncePadPlugin;
+
+impl Plugin for BouncePadPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<BouncePad>()
+            .add_systems(Update, insert_pad_sensors)
+            // entry events come from the physics step, so the launch does not depend on the frame rate
+            .add_systems(
+                FixedUpdate,
+                bounce.after(PhysicsSet::Writeback).run_if(
+                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                ),
+            );
+    }
+}
+
+/// Pads come from the level scene without a collider.
+fn insert_pad_sensors(
+    mut commands: Commands,
+    query: Query<(Entity, &BouncePad), Added<BouncePad>>,
+) {
+    for (entity, pad) in query.iter() {
+        commands.entity(entity).insert((
+            Collider::cuboid(pad.half_size.x, pad.half_size.y, pad.half_size.z),
+            Sensor,
+            sensor_groups(),
+            ActiveEvents::COLLISION_EVENTS,
+            // characters are kinematic, sensors ignore them by default
+            ActiveCollisionTypes::all(),
+        ));
+    }
+}
+
+fn bounce(
+    mut commands: Commands,
+    mut collision_event: EventReader<CollisionEvent>,
+    pad_query: Query<(&BouncePad, &GlobalTransform)>,
+    mut character_query: Query<(&mut MoveVelocity, Option<&mut Airborne>), With<Character>>,
+    body_query: Query<&ReadMassProperties>,
+) {
+    for event in collision_event.read() {
+        let CollisionEvent::Started(a, b, _) = event else {
+            continue;
+        };
+        let (pad, body) = match (pad_query.get(*a), pad_query.get(*b)) {
+            (Ok(pad), _) => (pad, *b),
+            (_, Ok(pad)) => (pad, *a),
+            _ => continue,
+        };
+        let (BouncePad { impulse, .. }, transform) = pad;
+        let launch = transform.up() * *impulse;
+
+        if let Ok((mut velocity, airborne)) = character_query.get_mut(body) {
+            // characters are kinematic, the launch is their own jump
+            velocity.0 += Vec3::new(launch.x, 0., launch.z);
+            // faster than the character moves on its own, the host caused it
+            commands.entity(body).insert(MovementGrace(LAUNCH_GRACE));
+            match airborne {
+                Some(mut airborne) => airborne.velocity = launch.y,
+                None => {
+                    commands.entity(body).insert(Airborne { velocity: launch.y });
+                }
+            }
+        } else if let Ok(mass) = body_query.get(body) {
+            commands.entity(body).insert(ExternalImpulse {
+                impulse: launch * mass.get().mass,
+                ..default()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy_rapier3d::prelude::CollisionEventFlags;
+    use renet::ClientId;
+
+    use super::*;
+    use crate::lobby::validation::{
+        validate_movement, CheatStrikes, CheatSuspectedEvent, MovementValidation,
+        ValidatedPosition,
+    };
+    use crate::lobby::PlayerId;
+
+    #[test]
+    fn angled_launch_is_not_rejected() {
+        let mut world = World::new();
+        let mut time = Time::<Fixed>::from_hz(64.);
+        time.advance_by(Duration::from_secs_f64(1. / 64.));
+        world.insert_resource(time);
+        world.init_resource::<MovementValidation>();
+        world.init_resource::<CheatStrikes>();
+        world.init_resource::<Events<CheatSuspectedEvent>>();
+        world.init_resource::<Events<CollisionEvent>>();
+
+        let pad = world
+            .spawn((
+                BouncePad {
+                    impulse: 80.,
+                    ..default()
+                },
+                GlobalTransform::from(Transform::from_rotation(Quat::from_rotation_z(-0.8))),
+            ))
+            .id();
+        let character = world
+            .spawn((
+                Character {
+                    id: PlayerId::Client(ClientId::from_raw(1)),
+                },
+                MoveVelocity::default(),
+                Transform::default(),
+                ValidatedPosition(Vec3::ZERO),
+            ))
+            .id();
+        world.send_event(CollisionEvent::Started(
+            pad,
+            character,
+            CollisionEventFlags::SENSOR,
+        ));
+        world.run_system_once(bounce);
+
+        let velocity = world.get::<MoveVelocity>(character).unwrap().0;
+        assert!(velocity.x > 40., "{:?}", velocity);
+        assert!(world.get::<MovementGrace>(character).is_some());
+
+        // the tick after the launch, far beyond the walking speed
+        let moved = velocity / 64.;
+        world.get_mut::<Transform>(character).unwrap().translation = moved;
+        world.run_system_once(validate_movement);
+
+        assert_eq!(world.get::<Transform>(character).unwrap().translation, moved);
+        assert_eq!(world.get::<ValidatedPosition>(character).unwrap().0, moved);
+    }
+}
@@ -2,41 +2,62 @@ use std::time::Duration;
 
 use bevy::app::{App, PreUpdate, Update};
 use bevy::ecs::entity::Entity;
-use bevy::ecs::event::EventWriter;
+use bevy::ecs::event::{EventReader, EventWriter};
 use bevy::ecs::system::{Commands, Query, Res};
 use bevy::hierarchy::DespawnRecursiveExt;
-use bevy::prelude::{Component, Deref, DerefMut, Plugin, Vec3};
+use bevy::prelude::{Component, Deref, DerefMut, Event, Plugin, Vec3};
 use bevy::reflect::Reflect;
-use bevy::time::{Time, Timer};
+use bevy::log::warn;
+use bevy::time::{Time, Timer, TimerMode};
 use bevy::transform::components::{GlobalTransform, Transform};
+use bevy_rapier3d::dynamics::Velocity;
 
+use crate::actor::character::{Airborne, MoveVelocity};
 use crate::component::AxisName;
 use crate::lobby::host::DespawnActorEvent;
+use crate::lobby::Character;
 use crate::world::{LinkId, SpawnProperty};
 
 use super::despawn_type::{DespawnReason, IntoDespawnTypeVec};
-use super::SpawnPlugin;
+use super::{Health, HealthChangedEvent, SpawnPlugin};
 
 /// A component representing respawn behavior for an entity.
 ///
 /// The [`Respawn`] component is used to control how an entity respawns in a game. It includes information about the respawn reasons,
 /// the spawn point, and a timer value for keeping the entity untouched upon spawn.
-#[derive(Component, Reflect)]
+///
+/// # Examples
+///
+/// ```ignore
+/// let respawn = Respawn::new()
+///     .with_spawn(SpawnProperty::new(Vec3::ZERO))
+///     .on_fall_below(-10.)
+///     .with_delay(1.5);
+/// ```
+#[derive(Component, Reflect, Default)]
 pub struct Respawn {
-    /// Reasons for respawning.
+    /// Reasons for respawning. Behaves like an ordered set, see [`Respawn::insert_reason`].
     reason: Vec<DespawnReason>,
     /// The spawn point for the entity.
     spawn_point: SpawnProperty,
     /// Duration for keeping the [`CollisionLayers`] into [`noclip`](CollisionLayer::ActorNoclip) [`CollisionLayer`] upon spawn.
     noclip: NoclipDuration,
+    /// Seconds between a reason being triggered and the actual respawn.
+    delay: f32,
+    /// Running while a triggered respawn waits for its `delay`.
+    pending: Option<Timer>,
+    /// Reason of the pending respawn, its [`RespawnBehavior`](super::RespawnBehavior) applies
+    /// once it is executed.
+    pending_reason: Option<DespawnReason>,
 }
 
 /// An enumeration representing the duration of time an actor will remain [`noclip`](CollisionLayer::ActorNoclip).
 ///
 /// The [`NoclipDuration`] enum is used to specify how long an actor should remain [`noclip`](CollisionLayer::ActorNoclip) before some action or event takes place.
-#[derive(PartialEq, Debug, Reflect)]
+#[derive(PartialEq, Debug, Reflect, Default)]
 pub enum NoclipDuration {
     /// Indicates that there is no [`noclip`](CollisionLayer::ActorNoclip) duration, and the actor can be acted upon immediately.
+    #[default]
     None,
     /// Specifies a timed duration in seconds before the actor can be acted upon.
     Timer(f32),
@@ -49,25 +70,18 @@ pub enum NoclipDuration {
 #[derive(Deref, DerefMut, Component)]
 pub struct NoclipTimer(Timer);
 
+/// Marks an entity that was moved by the game itself (respawn, map change) rather than by its own movement.
+///
+/// Movement checks accept the new position of such entities as is and remove the marker.
+#[derive(Component, Debug, Default)]
+pub struct Teleported;
+
 impl Respawn {
-    /// Creates a new `Respawn` instance.
-    ///
-    /// # Arguments
+    /// Creates a new `Respawn` instance without reasons, with an empty spawn point and no delay.
     ///
-    /// * `reason` - A container of reasons why the entity might be respawned.
-    /// * `spawn_point` - The location where the entity will respawn.
-    /// * `untouched_on_spawn` - Duration for which the entity remains in [`noclip`](CollisionLayer::ActorNoclip) mode upon respawn.
-    #[allow(dead_code)]
-    pub fn new<T: IntoDespawnTypeVec>(
-        reason: T,
-        spawn_point: SpawnProperty,
-        untouched_on_spawn: NoclipDuration,
-    ) -> Self {
-        Self {
-            reason: reason.into_despawn_type_vec(),
-            spawn_point,
-            noclip: untouched_on_spawn,
-        }
+    /// Use the `with_*` / `on_*` builder methods to configure it.
+    pub fn new() -> Self {
+        Self::default()
     }
 
     /// Creates a new `Respawn` instance with the specified spawn point and default values for other fields.
@@ -78,26 +92,82 @@ impl Respawn {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```ignore
     /// let respawn = Respawn::from_vec3(Vec3::new(0.0, 0.0, 0.0));
     /// ```
     #[allow(dead_code)]
     pub fn from_vec3(spawn_point: Vec3) -> Self {
-        Self {
-            reason: vec![],
-            spawn_point: SpawnProperty::new(spawn_point),
-            noclip: NoclipDuration::None,
+        Self::new().with_spawn(SpawnProperty::new(spawn_point))
+    }
+
+    /// Sets the spawn point the entity is moved to on respawn.
+    pub fn with_spawn(mut self, spawn_point: SpawnProperty) -> Self {
+        self.spawn_point = spawn_point;
+        self
+    }
+
+    /// Adds a single respawn reason, see [`Respawn::insert_reason`].
+    pub fn with_reason(mut self, reason: DespawnReason) -> Self {
+        self.insert_reason(reason);
+        self
+    }
+
+    /// Adds a container of respawn reasons, see [`Respawn::insert_reason`].
+    pub fn with_reasons<T: IntoDespawnTypeVec>(mut self, reasons: T) -> Self {
+        for reason in reasons.into_despawn_type_vec() {
+            self.insert_reason(reason);
         }
+        self
+    }
+
+    /// Respawns the entity when it falls below `y`.
+    pub fn on_fall_below(self, y: f32) -> Self {
+        self.with_reason(DespawnReason::Less(y, AxisName::Y))
+    }
+
+    /// Waits `secs` seconds between a triggered reason and the actual respawn.
+    ///
+    /// The spawn point is resolved when the delay is over,
+    /// so a spawn point replaced mid-delay is respected.
+    pub fn with_delay(mut self, secs: f32) -> Self {
+        self.delay = secs.max(0.);
+        self
+    }
+
+    /// Sets how long the entity stays in [`noclip`](CollisionLayer::ActorNoclip) mode upon respawn.
+    pub fn with_noclip(mut self, noclip: NoclipDuration) -> Self {
+        self.noclip = noclip;
+        self
+    }
+
+    /// Seconds between a triggered reason and the actual respawn.
+    pub fn delay(&self) -> f32 {
+        self.delay
+    }
+
+    /// Changes the delay of the next respawns, see [`Respawn::with_delay`].
+    ///
+    /// A respawn already pending keeps its delay.
+    pub fn set_delay(&mut self, secs: f32) {
+        self.delay = secs.max(0.);
+    }
+
+    /// Returns `true` if a respawn was already triggered and has not been executed yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some() || self.reason.iter().any(DespawnReason::is_one_shot)
     }
 
     /// Adds a new respawn reason to the list of reasons.
     ///
+    /// Reasons are kept as an ordered set: inserting a reason that is already present does nothing.
+    ///
     /// # Arguments
     ///
     /// * `reason` - The [`DespawnReason`] to be added to the respawn reasons list.
-    #[allow(dead_code)]
     pub fn insert_reason(&mut self, reason: DespawnReason) {
-        self.reason.push(reason);
+        if !self.reason.contains(&reason) {
+            self.reason.push(reason);
+        }
     }
 
     /// Clears the current spawn point, resetting it to the default.
@@ -111,9 +181,21 @@ impl Respawn {
     /// # Arguments
     ///
     /// * `spawn_point` - The new spawn point for the entity.
+    pub fn replace_spawn_point(&mut self, spawn_point: SpawnProperty) {
+        self.spawn_point = spawn_point;
+    }
+
+    #[deprecated(note = "use `replace_spawn_point` instead")]
     #[allow(dead_code)]
     pub fn replase_spawn_point(&mut self, spawn_point: SpawnProperty) {
-        self.spawn_point = spawn_point;
+        self.replace_spawn_point(spawn_point);
+    }
+
+    /// Removes one-shot reasons ([`DespawnReason::is_one_shot`]) after they were triggered.
+    ///
+    /// Positional and timed reasons stay, they describe a condition rather than an event.
+    fn consume_one_shot(&mut self) {
+        self.reason.retain(|reason| !reason.is_one_shot());
     }
 }
 
@@ -142,12 +224,39 @@ impl Despawn {
     }
 }
 
+/// Seconds left before an actor is despawned, ticked down by the simulating side.
+///
+/// A linked actor is removed from the clients too with a [`DespawnActorEvent`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Reflect)]
+pub struct Lifetime(pub f32);
+
+/// Sent when an entity with [`Respawn`] starts respawning.
+#[derive(Debug, Event)]
+pub struct RespawnEvent {
+    pub entity: Entity,
+    /// The reason that triggered it, a crossed bound is [`DespawnReason::Void`].
+    /// See [`DespawnReason::behavior`] for whether it is a death.
+    pub reason: DespawnReason,
+}
+
 pub struct ComponentPlugins;
 
 impl Plugin for ComponentPlugins {
     fn build(&self, app: &mut App) {
-        app.add_plugins(SpawnPlugin)
-            .add_systems(PreUpdate, (respawn, despawn))
+        app.add_event::<RespawnEvent>()
+            .add_event::<HealthChangedEvent>()
+            .register_type::<Health>()
+            .register_type::<Lifetime>()
+            .add_plugins((
+                SpawnPlugin,
+                GravityZonePlugin,
+                BouncePadPlugin,
+                MovingPlatformPlugin,
+            ))
+            .add_systems(
+                PreUpdate,
+                (respawn, despawn, lifetime, restore_health.after(respawn)),
+            )
             .add_systems(Update, noclip_timer);
     }
 }
@@ -175,49 +284,101 @@ fn noclip_timer(
     }
 }
 
+/// Returns the reason among `reason` that is triggered, if any.
+///
+/// A crossed bound is returned as [`DespawnReason::Void`], and a one-shot reason wins over
+/// the conditions since it was inserted on purpose. Every reason is evaluated even if an
+/// earlier one already matched, so timers keep ticking regardless of the reasons order.
 fn match_reason(
     reason: &mut [DespawnReason],
     global_translation: &Vec3,
     delta_time: &Duration,
-) -> bool {
+) -> Option<DespawnReason> {
+    let mut triggered: Option<DespawnReason> = None;
     for reason in reason.iter_mut() {
-        if match reason {
-            DespawnReason::Forced => true,
-            DespawnReason::After(ref mut timer) => timer.update(*delta_time).just_finished(),
-            DespawnReason::Less(val, axis) => match axis {
-                AxisName::X => global_translation.x < *val,
-                AxisName::Y => global_translation.y < *val,
-                AxisName::Z => global_translation.z < *val,
-            },
-            DespawnReason::More(val, axis) => match axis {
-                AxisName::X => global_translation.x > *val,
-                AxisName::Y => global_translation.y > *val,
-                AxisName::Z => global_translation.z > *val,
-            },
-        } {
-            return true;
+        let matched = match reason {
+            DespawnReason::Forced | DespawnReason::MapChange | DespawnReason::Void => {
+                Some(reason.clone())
+            }
+            DespawnReason::After(ref mut timer) => {
+                let finished = timer.update(*delta_time).just_finished();
+                finished.then(|| reason.clone())
+            }
+            DespawnReason::Less(val, axis) => {
+                let crossed = match axis {
+                    AxisName::X => global_translation.x < *val,
+                    AxisName::Y => global_translation.y < *val,
+                    AxisName::Z => global_translation.z < *val,
+                };
+                crossed.then_some(DespawnReason::Void)
+            }
+            DespawnReason::More(val, axis) => {
+                let crossed = match axis {
+                    AxisName::X => global_translation.x > *val,
+                    AxisName::Y => global_translation.y > *val,
+                    AxisName::Z => global_translation.z > *val,
+                };
+                crossed.then_some(DespawnReason::Void)
+            }
+        };
+        let Some(matched) = matched else {
+            continue;
+        };
+        let explicit = matched.is_one_shot() && !matches!(matched, DespawnReason::Void);
+        if triggered.is_none() || explicit {
+            triggered = Some(matched);
         }
     }
 
-    false
+    triggered
 }
 
 /// Processes a [`Entity`] with [`Respawn`] [`Component`]
 ///
-/// Move actors on respawn position and optionally rest [`LinearVelocity`] and [`AngularVelocity`]
-/// if one of `reason` ([`DespawnReason`]) is true
+/// Move actors on respawn position and reset their velocities
+/// if one of `reason` ([`DespawnReason`]) is true and its behavior says so
 fn respawn(
     mut commands: Commands,
     mut respawn_query: Query<(&mut Respawn, &mut Transform, &GlobalTransform, Entity)>,
-    // TODO: mut velocity_query: Query<(&mut LinearVelocity, &mut AngularVelocity), With<Respawn>>,
+    mut velocity_query: Query<
+        (
+            Option<&mut MoveVelocity>,
+            Option<&mut Airborne>,
+            Option<&mut Velocity>,
+        ),
+        With<Respawn>,
+    >,
     time: Res<Time>,
+    mut respawn_event: EventWriter<RespawnEvent>,
 ) {
     for (mut respawn, mut transform, global_transform, entity) in respawn_query.iter_mut() {
-        if !match_reason(
+        if let Some(reason) = match_reason(
             &mut respawn.reason,
             &global_transform.translation(),
             &time.delta(),
         ) {
+            respawn.consume_one_shot();
+            if respawn.pending.is_none() {
+                respawn.pending = Some(Timer::from_seconds(respawn.delay, TimerMode::Once));
+                respawn.pending_reason = Some(reason.clone());
+                respawn_event.send(RespawnEvent { entity, reason });
+            }
+        }
+
+        let Some(timer) = respawn.pending.as_mut() else {
+            continue;
+        };
+        if !timer.tick(time.delta()).finished() {
+            continue;
+        }
+        respawn.pending = None;
+        let behavior = respawn
+            .pending_reason
+            .take()
+            .map_or(DespawnReason::Forced.behavior(), |reason| reason.behavior());
+
+        if respawn.spawn_point.is_empty() {
+            warn!("Respawn of {:?} skipped: spawn point is empty", entity);
             continue;
         }
 
@@ -226,7 +387,7 @@ fn respawn(
                 .entity(entity)
                 .insert(NoclipTimer(Timer::from_seconds(
                     val,
-                    bevy::time::TimerMode::Once,
+                    TimerMode::Once,
                 )))
                 // TODO:
                 //.insert(CollisionLayers::new(
@@ -236,15 +397,23 @@ fn respawn(
                 ;
         }
         transform.translation = respawn.spawn_point.random_point();
-        // TODO:
-        // if let Ok((mut linear_velocity, mut angular_velocity)) = velocity_query.get_mut(entity) {
-        //     linear_velocity.0 = Vec3::ZERO;
-        //     angular_velocity.0 = Vec3::ZERO;
-        // }
-
-        respawn
-            .reason
-            .retain(|reason| reason != &DespawnReason::Forced);
+        commands.entity(entity).insert(Teleported);
+        if !behavior.reset_velocity {
+            continue;
+        }
+        let Ok((move_velocity, airborne, velocity)) = velocity_query.get_mut(entity) else {
+            continue;
+        };
+        if let Some(mut move_velocity) = move_velocity {
+            move_velocity.0 = Vec3::ZERO;
+        }
+        // still falls from the spawn point, only without the speed it had
+        if let Some(mut airborne) = airborne {
+            airborne.velocity = 0.;
+        }
+        if let Some(mut velocity) = velocity {
+            *velocity = Velocity::zero();
+        }
     }
 }
 
@@ -255,11 +424,33 @@ fn despawn(
     time: Res<Time>,
 ) {
     for (mut respawn, global_transform, id_option, entity) in despawn_query.iter_mut() {
-        if !match_reason(
+        if match_reason(
             &mut respawn.reason,
             &global_transform.translation(),
             &time.delta(),
-        ) {
+        )
+        .is_none()
+        {
+            continue;
+        }
+
+        if let Some(id) = id_option {
+            despawn_actor_event.send(DespawnActorEvent(id.clone()));
+        }
+
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn lifetime(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Lifetime, Option<&LinkId>)>,
+    mut despawn_actor_event: EventWriter<DespawnActorEvent>,
+    time: Res<Time>,
+) {
+    for (entity, mut lifetime, id_option) in query.iter_mut() {
+        lifetime.0 -= time.delta_seconds();
+        if lifetime.0 > 0. {
             continue;
         }
 
@@ -270,3 +461,149 @@ fn despawn(
         commands.entity(entity).despawn_recursive();
     }
 }
+
+/// A respawning character is back to full health, whatever killed it.
+fn restore_health(
+    mut respawn_event: EventReader<RespawnEvent>,
+    mut query: Query<(&Character, &mut Health)>,
+    mut health_changed_event: EventWriter<HealthChangedEvent>,
+) {
+    for event in respawn_event.read() {
+        let Ok((character, mut health)) = query.get_mut(event.entity) else {
+            continue;
+        };
+        if health.current == health.max {
+            continue;
+        }
+        health.restore();
+        health_changed_event.send(HealthChangedEvent {
+            id: character.id,
+            health: *health,
+            attacker: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::ecs::world::World;
+
+    use super::*;
+
+    fn advance(world: &mut World, secs: f32) {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(secs));
+    }
+
+    #[test]
+    fn builder_sets_reasons_and_delay() {
+        let respawn = Respawn::new()
+            .with_spawn(SpawnProperty::new(Vec3::ZERO))
+            .on_fall_below(-10.)
+            .with_reason(DespawnReason::Forced)
+            .with_delay(1.5);
+        assert_eq!(
+            respawn.reason,
+            vec![DespawnReason::Less(-10., AxisName::Y), DespawnReason::Forced]
+        );
+        assert_eq!(respawn.delay(), 1.5);
+        assert_eq!(Respawn::new().with_delay(-1.).delay(), 0.);
+    }
+
+    #[test]
+    fn insert_reason_is_idempotent() {
+        let mut respawn = Respawn::new().with_reasons((
+            DespawnReason::Void,
+            DespawnReason::Less(-10., AxisName::Y),
+        ));
+        respawn.insert_reason(DespawnReason::Void);
+        respawn.insert_reason(DespawnReason::Less(-10., AxisName::Y));
+        assert_eq!(respawn.reason.len(), 2);
+    }
+
+    #[test]
+    fn one_shot_reason_is_pending_until_consumed() {
+        let mut respawn = Respawn::new().on_fall_below(-10.);
+        assert!(!respawn.is_pending());
+
+        respawn.insert_reason(DespawnReason::Forced);
+        assert!(respawn.is_pending());
+
+        respawn.consume_one_shot();
+        assert!(!respawn.is_pending());
+        // the bound describes the entity, it stays
+        assert_eq!(respawn.reason, vec![DespawnReason::Less(-10., AxisName::Y)]);
+    }
+
+    #[test]
+    fn spawn_point_replaced_mid_delay_is_used_once() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Events<RespawnEvent>>();
+        let entity = world
+            .spawn((
+                Respawn::new()
+                    .with_spawn(SpawnProperty::new(Vec3::X))
+                    .with_reason(DespawnReason::Forced)
+                    .with_delay(1.),
+                Transform::default(),
+                GlobalTransform::default(),
+            ))
+            .id();
+
+        world.run_system_once(respawn);
+        assert!(world.get::<Respawn>(entity).unwrap().is_pending());
+        assert_eq!(world.resource::<Events<RespawnEvent>>().len(), 1);
+
+        advance(&mut world, 0.5);
+        world.run_system_once(respawn);
+        assert_eq!(world.get::<Transform>(entity).unwrap().translation, Vec3::ZERO);
+        world
+            .get_mut::<Respawn>(entity)
+            .unwrap()
+            .replace_spawn_point(SpawnProperty::new(Vec3::Y * 5.));
+
+        advance(&mut world, 0.6);
+        world.run_system_once(respawn);
+        assert_eq!(world.get::<Transform>(entity).unwrap().translation, Vec3::Y * 5.);
+        assert!(world.get::<Teleported>(entity).is_some());
+        assert!(!world.get::<Respawn>(entity).unwrap().is_pending());
+
+        // consumed: moving away does not bring it back
+        world.get_mut::<Transform>(entity).unwrap().translation = Vec3::Z;
+        for _ in 0..3 {
+            advance(&mut world, 1.);
+            world.run_system_once(respawn);
+        }
+        assert_eq!(world.get::<Transform>(entity).unwrap().translation, Vec3::Z);
+        assert_eq!(world.resource::<Events<RespawnEvent>>().len(), 1);
+    }
+
+    #[test]
+    fn actor_is_despawned_after_its_lifetime() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Events<DespawnActorEvent>>();
+        let link_id = LinkId::Allocated {
+            session: 0,
+            index: 1,
+        };
+        let entity = world.spawn((Lifetime(1.), link_id.clone())).id();
+
+        advance(&mut world, 0.6);
+        world.run_system_once(lifetime);
+        assert!(world.get_entity(entity).is_some());
+        assert!(world.resource::<Events<DespawnActorEvent>>().is_empty());
+
+        advance(&mut world, 0.6);
+        world.run_system_once(lifetime);
+        assert!(world.get_entity(entity).is_none());
+        let events = world.resource::<Events<DespawnActorEvent>>();
+        let mut reader = events.get_reader();
+        let sent: Vec<_> = reader.read(events).map(|event| event.0.clone()).collect();
+        assert_eq!(sent, vec![link_id]);
+    }
+}
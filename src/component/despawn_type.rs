@@ -12,6 +12,11 @@ use bevy::{
 pub enum DespawnReason {
     /// Indicates that the entity was forcefully despawned. After been removed if object must respawn ([`Respawn`](crate::component::Respawn))
     Forced,
+    /// The level changed under the entity, it is moved to a spawn point of the new one.
+    MapChange,
+    /// The entity left the bounds of the world, inserted when a [`More`](Self::More) or
+    /// [`Less`](Self::Less) bound is crossed.
+    Void,
     /// Specifies that the entity was despawned because it exceeded a certain value along a specific axis.
     More(f32, AxisName),
     /// Specifies that the entity was despawned because it fell below a certain value along a specific axis.
@@ -20,6 +25,44 @@ pub enum DespawnReason {
     After(DespawnTimer),
 }
 
+/// What a respawn triggered by a [`DespawnReason`] does besides moving the entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RespawnBehavior {
+    /// Linear and angular velocity are zeroed, a soft teleport would keep them
+    pub reset_velocity: bool,
+    /// Counted as a death by the stats and the kill feed
+    pub counts_as_death: bool,
+}
+
+impl DespawnReason {
+    pub fn behavior(&self) -> RespawnBehavior {
+        match self {
+            // a kill reports the death itself, with its killer
+            DespawnReason::Forced | DespawnReason::MapChange => RespawnBehavior {
+                reset_velocity: true,
+                counts_as_death: false,
+            },
+            DespawnReason::Void
+            | DespawnReason::More(..)
+            | DespawnReason::Less(..)
+            | DespawnReason::After(_) => RespawnBehavior {
+                reset_velocity: true,
+                counts_as_death: true,
+            },
+        }
+    }
+
+    /// Reasons inserted for one respawn, removed once it is triggered.
+    ///
+    /// The others describe a condition of the entity and stay.
+    pub fn is_one_shot(&self) -> bool {
+        matches!(
+            self,
+            DespawnReason::Forced | DespawnReason::MapChange | DespawnReason::Void
+        )
+    }
+}
+
 /// A timer used to despawn an entity after a certain amount of time.
 #[derive(Debug, Clone, PartialEq, Reflect)]
 pub struct DespawnTimer(Timer);
@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy_rapier3d::plugin::{PhysicsSet, RapierContext};
+use bevy_rapier3d::prelude::{
+    ActiveCollisionTypes, Collider, ExternalForce, GravityScale, ReadMassProperties, RigidBody,
+    Sensor,
+};
+
+use crate::actor::character::Airborne;
+use crate::lobby::{Character, LobbyState};
+use crate::physics::groups::sensor_groups;
+
+/// Box volume replacing the level gravity for the bodies inside it, placed in the level scene.
+///
+/// Gravities of overlapping zones are summed. Characters only feel the vertical part,
+/// their horizontal move is driven by the input.
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component)]
+pub struct GravityZone {
+    pub direction: Vec3,
+    pub strength: f32,
+    /// Half extents of the volume, scaled by the entity transform
+    pub half_size: Vec3,
+}
+
+impl Default for GravityZone {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::NEG_Y,
+            strength: 9.81,
+            half_size: Vec3::ONE,
+        }
+    }
+}
+
+impl GravityZone {
+    pub fn gravity(&self) -> Vec3 {
+        self.direction.normalize_or_zero() * self.strength
+    }
+}
+
+/// Gravity a body is under instead of the level one, kept by the zones it is in.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct InGravityZone(pub Vec3);
+
+pub struct GravityZonePlugin;
+
+impl Plugin for GravityZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<GravityZone>()
+            .add_systems(Update, insert_zone_sensors)
+            .add_systems(
+                FixedUpdate,
+                (detect_gravity_zones, apply_zone_gravity)
+                    .chain()
+                    .after(PhysicsSet::Writeback)
+                    .run_if(
+                        not(in_state(LobbyState::None))
+                            .and_then(not(in_state(LobbyState::Client))),
+                    ),
+            );
+    }
+}
+
+/// Zones come from the level scene without a collider.
+fn insert_zone_sensors(
+    mut commands: Commands,
+    query: Query<(Entity, &GravityZone), Added<GravityZone>>,
+) {
+    for (entity, zone) in query.iter() {
+        commands.entity(entity).insert((
+            Collider::cuboid(zone.half_size.x, zone.half_size.y, zone.half_size.z),
+            Sensor,
+            sensor_groups(),
+            // characters are kinematic, sensors ignore them by default
+            ActiveCollisionTypes::all(),
+        ));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn detect_gravity_zones(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    zone_query: Query<(Entity, &GravityZone)>,
+    body_query: Query<(Entity, Option<&InGravityZone>), Or<(With<Character>, With<RigidBody>)>>,
+) {
+    for (entity, in_zone) in body_query.iter() {
+        let gravity = zone_query
+            .iter()
+            .filter(|(zone_entity, _)| {
+                *zone_entity != entity
+                    && rapier_context.intersection_pair(*zone_entity, entity) == Some(true)
+            })
+            .map(|(_, zone)| zone.gravity())
+            .reduce(|sum, gravity| sum + gravity);
+
+        match (gravity, in_zone) {
+            (Some(gravity), Some(InGravityZone(current))) if gravity == *current => {}
+            (Some(gravity), _) => {
+                commands.entity(entity).insert(InGravityZone(gravity));
+            }
+            (None, Some(_)) => {
+                commands
+                    .entity(entity)
+                    .remove::<(InGravityZone, GravityScale, ExternalForce)>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Dynamic bodies get the zone gravity as a force, characters fall by it (see `fall_characters`).
+#[allow(clippy::type_complexity)]
+fn apply_zone_gravity(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &InGravityZone,
+            Option<&RigidBody>,
+            Option<&ReadMassProperties>,
+            Has<Character>,
+            Has<Airborne>,
+        ),
+        Changed<InGravityZone>,
+    >,
+) {
+    for (entity, InGravityZone(gravity), body, mass, character, airborne) in query.iter() {
+        if character {
+            // an upward zone lifts a standing character
+            if !airborne && gravity.y > 0. {
+                commands.entity(entity).insert(Airborne { velocity: 0. });
+            }
+            continue;
+        }
+        if body != Some(&RigidBody::Dynamic) {
+            continue;
+        }
+        // bodies that do not read their mass (props do) keep the level gravity
+        let Some(mass) = mass else {
+            continue;
+        };
+        commands.entity(entity).insert((
+            GravityScale(0.),
+            ExternalForce {
+                force: *gravity * mass.get().mass,
+                ..default()
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::actor::character::fall_characters;
+    use crate::lobby::PlayerId;
+    use crate::world::LevelPhysics;
+
+    #[test]
+    fn zone_gravity_is_direction_times_strength() {
+        let zone = GravityZone {
+            direction: Vec3::new(0., 2., 0.),
+            strength: 5.,
+            ..default()
+        };
+        assert_eq!(zone.gravity(), Vec3::new(0., 5., 0.));
+        let zone = GravityZone {
+            direction: Vec3::ZERO,
+            ..default()
+        };
+        assert_eq!(zone.gravity(), Vec3::ZERO);
+    }
+
+    #[test]
+    fn character_in_upward_zone_accelerates_upward() {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f64(1. / 64.));
+        world.insert_resource(time);
+        world.init_resource::<LevelPhysics>();
+        world.init_resource::<RapierContext>();
+        let character = world
+            .spawn((
+                Character {
+                    id: PlayerId::HostOrSingle,
+                },
+                Transform::default(),
+                InGravityZone(Vec3::Y * 9.81),
+            ))
+            .id();
+
+        // a standing character is lifted off the ground
+        world.run_system_once(apply_zone_gravity);
+        assert_eq!(world.get::<Airborne>(character).unwrap().velocity, 0.);
+
+        let mut last_velocity = 0.;
+        for _ in 0..10 {
+            world.run_system_once(fall_characters);
+            let velocity = world.get::<Airborne>(character).unwrap().velocity;
+            assert!(velocity > last_velocity);
+            last_velocity = velocity;
+        }
+        assert!(world.get::<Transform>(character).unwrap().translation.y > 0.);
+    }
+}
@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::lobby::PlayerId;
+
+/// Health of a character used by the simulating side, clients mirror it for the health bars
+/// from [`ServerMessages::HealthChanged`](crate::lobby::ServerMessages::HealthChanged).
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new(100.)
+    }
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Subtracts `amount`, returns `true` if it brought the health to zero.
+    pub fn damage(&mut self, amount: f32) -> bool {
+        let alive = self.current > 0.;
+        self.current = (self.current - amount).max(0.);
+        alive && self.current == 0.
+    }
+
+    pub fn restore(&mut self) {
+        self.current = self.max;
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0. {
+            self.current / self.max
+        } else {
+            0.
+        }
+    }
+}
+
+/// Health of a character changed on the simulating side, broadcast to clients by the host.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct HealthChangedEvent {
+    pub id: PlayerId,
+    pub health: Health,
+    /// Player whose hit changed it, `None` for the level, a heal or a respawn
+    pub attacker: Option<PlayerId>,
+}
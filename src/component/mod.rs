@@ -1,10 +1,18 @@
 #![allow(clippy::module_inception)]
 
+mod bounce_pad;
 mod component;
 mod despawn_type;
+mod gravity_zone;
+mod health;
+mod moving_platform;
 mod test_component;
 mod spawn;
+pub use bounce_pad::*;
 pub use component::*;
 pub use despawn_type::*;
+pub use gravity_zone::*;
+pub use health::*;
+pub use moving_platform::*;
 pub use test_component::*;
 pub use spawn::*;
@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bevy_rapier3d::plugin::{PhysicsSet, RapierContext};
+use bevy_rapier3d::prelude::{Collider, QueryFilter, RigidBody};
+
+use crate::actor::character::{Airborne, HALPH_PLAYER_SIZE};
+use crate::lobby::sync_policy::{SyncChannel, SyncPolicy};
+use crate::lobby::validation::ValidatedPosition;
+use crate::lobby::{Character, LobbyState};
+use crate::physics::groups::level_groups;
+use crate::world::{LevelPhysics, LinkId};
+
+/// Distance below its bottom a character still stands on a platform
+const STANDING_TOLERANCE: f32 = 0.1;
+/// Platforms are slow and never turn, every 4th snapshot (5 Hz by default) is enough
+const PLATFORM_SYNC: SyncPolicy = SyncPolicy {
+    kind: "platform",
+    divisor: 4,
+    channel: SyncChannel::Unreliable,
+    position_only: true,
+};
+
+/// Box moved back and forth along a path by the host, placed in the level scene.
+///
+/// Synced to the clients as `LinkId::Scene("platform_<name>")` like the other actors
+/// at a lower rate, so the scene node needs a unique name. Clients only follow the synced position.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component)]
+pub struct MovingPlatform {
+    /// Waypoints relative to the start position, the start is the first one
+    pub path: Vec<Vec3>,
+    /// Units per second along the path
+    pub speed: f32,
+    /// Half extents of the box, scaled by the entity transform
+    pub half_size: Vec3,
+}
+
+impl Default for MovingPlatform {
+    fn default() -> Self {
+        Self {
+            path: Vec::new(),
+            speed: 2.,
+            half_size: Vec3::new(2., 0.25, 2.),
+        }
+    }
+}
+
+impl MovingPlatform {
+    /// Offset from the start after travelling `distance`, going back when the path ends.
+    pub fn offset_at(&self, distance: f32) -> Vec3 {
+        let points: Vec<Vec3> = std::iter::once(Vec3::ZERO)
+            .chain(self.path.iter().copied())
+            .collect();
+        let length: f32 = points.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+        if length <= 0. {
+            return Vec3::ZERO;
+        }
+
+        let mut distance = distance.rem_euclid(length * 2.);
+        if distance > length {
+            distance = length * 2. - distance;
+        }
+        for pair in points.windows(2) {
+            let segment = pair[0].distance(pair[1]);
+            if distance <= segment {
+                return pair[0].lerp(pair[1], distance / segment);
+            }
+            distance -= segment;
+        }
+        points[points.len() - 1]
+    }
+}
+
+/// Progress of a platform along its path, only on the host and single.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PlatformMotion {
+    origin: Vec3,
+    distance: f32,
+    /// Move of the last tick, given to the characters standing on the platform
+    step: Vec3,
+}
+
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MovingPlatform>()
+            .register_type::<Vec<Vec3>>()
+            .add_systems(Update, setup_platforms)
+            .add_systems(
+                FixedUpdate,
+                (move_platforms, carry_characters)
+                    .chain()
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(
+                        not(in_state(LobbyState::None))
+                            .and_then(not(in_state(LobbyState::Client))),
+                    ),
+            );
+    }
+}
+
+/// Platforms come from the level scene without a body, clients do not simulate them.
+fn setup_platforms(
+    mut commands: Commands,
+    lobby_state: Res<State<LobbyState>>,
+    query: Query<(Entity, &MovingPlatform, &Transform, Option<&Name>), Added<MovingPlatform>>,
+) {
+    for (entity, platform, transform, name) in query.iter() {
+        let half_size = platform.half_size;
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.insert((
+            Collider::cuboid(half_size.x, half_size.y, half_size.z),
+            level_groups(),
+        ));
+        match name {
+            Some(name) => {
+                entity_commands.insert((
+                    LinkId::Scene(format!("platform_{}", name)),
+                    PLATFORM_SYNC,
+                ));
+            }
+            None => log::warn!("Moving platform {:?} has no name, it is not synced", entity),
+        }
+
+        if *lobby_state.get() != LobbyState::Client {
+            entity_commands.insert((
+                RigidBody::KinematicPositionBased,
+                PlatformMotion {
+                    origin: transform.translation,
+                    distance: 0.,
+                    step: Vec3::ZERO,
+                },
+            ));
+        }
+    }
+}
+
+fn move_platforms(
+    time: Res<Time>,
+    level_physics: Res<LevelPhysics>,
+    mut query: Query<(&MovingPlatform, &mut PlatformMotion, &mut Transform)>,
+) {
+    // the same simulated time as rapier, see `LevelPhysics::time_scale`
+    let dt = time.delta_seconds() * level_physics.time_scale;
+    for (platform, mut motion, mut transform) in query.iter_mut() {
+        motion.distance += platform.speed * dt;
+        let position = motion.origin + platform.offset_at(motion.distance);
+        motion.step = position - transform.translation;
+        transform.translation = position;
+    }
+}
+
+/// Characters are kinematic and not pushed by the platform, they are moved with it instead.
+///
+/// The carry is not a move of the character itself, so its [`ValidatedPosition`] moves along.
+#[allow(clippy::type_complexity)]
+fn carry_characters(
+    rapier_context: Res<RapierContext>,
+    platform_query: Query<&PlatformMotion>,
+    mut character_query: Query<
+        (Entity, &mut Transform, Option<&mut ValidatedPosition>),
+        (With<Character>, Without<Airborne>),
+    >,
+) {
+    for (entity, mut transform, validated) in character_query.iter_mut() {
+        let filter = QueryFilter::default()
+            .exclude_collider(entity)
+            .exclude_sensors();
+        // the physics still has the platforms where they were before this tick
+        let Some((ground, _)) = rapier_context.cast_ray(
+            transform.translation,
+            Vec3::NEG_Y,
+            HALPH_PLAYER_SIZE + STANDING_TOLERANCE,
+            true,
+            filter,
+        ) else {
+            continue;
+        };
+        if let Ok(motion) = platform_query.get(ground) {
+            transform.translation += motion.step;
+            if let Some(mut validated) = validated {
+                validated.0 += motion.step;
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use bevy::{
     app::{App, Plugin, Update},
-    ecs::{component::Component, query::With, system::{Query, ResMut, Commands}, entity::Entity},
+    ecs::{component::Component, reflect::ReflectComponent, system::{Query, ResMut, Commands}, entity::Entity},
     reflect::Reflect,
     transform::components::GlobalTransform,
 };
@@ -8,7 +8,12 @@ use bevy::{
 use crate::world;
 
 #[derive(Component, Reflect, Default, Debug)]
-pub struct SpawnPoint;
+#[reflect(Component)]
+pub struct SpawnPoint {
+    /// Restricts the point to some players, e.g. `team_a`, usable by everyone if empty
+    #[reflect(default)]
+    pub tag: String,
+}
 
 pub struct SpawnPlugin;
 
@@ -22,12 +27,16 @@ impl Plugin for SpawnPlugin {
 
 fn process_spawn_point(
   mut commands: Commands,
-  query: Query<(Entity, &GlobalTransform), With<SpawnPoint>>,
+  query: Query<(Entity, &GlobalTransform, &SpawnPoint)>,
   mut resource: ResMut<world::SpawnProperty>,
 ) {
     // TODO: spawn point not only like vec3 but like entity (moveble point)
-    for (entity, global_transform) in &query {
-         resource.push(global_transform.translation());
+    for (entity, global_transform, spawn_point) in &query {
+         if spawn_point.tag.is_empty() {
+             resource.push(global_transform.translation());
+         } else {
+             resource.push_tagged(spawn_point.tag.clone(), global_transform.translation());
+         }
          commands.entity(entity).despawn(); // TODO: ugly realization
     }
 }
@@ -0,0 +1,642 @@
+//! Drop-down developer console, toggled with the grave key.
+//!
+//! Commands live in the [`ConsoleCommands`] registry, plugins add their own with
+//! [`ConsoleAppExt::add_console_command`]. Submitted lines run in an exclusive system,
+//! so a command gets the whole [`World`]. While the console is open the game does not see
+//! keyboard and mouse buttons.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use bevy::app::AppExit;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::{ButtonState, InputSystem};
+use bevy::prelude::*;
+use bevy_egui::egui::text::CCursor;
+use bevy_egui::egui::text_edit::CCursorRange;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::component::{DespawnReason, Respawn, Teleported};
+use crate::core::{KnownLevel, LoadLevelEvent};
+use crate::level::level_path;
+use crate::lobby::afk::{AfkAction, AfkRules};
+use crate::lobby::client::send_to_server;
+use crate::lobby::host::ServerSettings;
+use crate::lobby::team::{TeamId, TeamRules, TeamSwitchRequest};
+use crate::lobby::{ChangeMapLobbyEvent, ClientMessages, LevelCode, Lobby, LobbyState, PlayerId};
+use crate::network::Channel;
+use crate::ui::{MouseGrabState, ViewportRect};
+use crate::world::{
+    ChangeEnvironmentEvent, ChangePhysicsEvent, EnvironmentSettings, LevelPhysics, Me,
+};
+
+/// Lines kept in the scrollback
+const MAX_OUTPUT_LINES: usize = 500;
+/// Submitted lines kept in the history
+const MAX_HISTORY: usize = 100;
+/// Part of the viewport height the console covers
+const CONSOLE_HEIGHT: f32 = 0.4;
+
+/// Where a command may run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandScope {
+    /// Only affects this game (e.g. listing players), runs anywhere.
+    Local,
+    /// Changes the simulated session, runs in a single player game or on the host.
+    Authority,
+}
+
+/// Printed into the console, `Err` in red.
+pub type CommandResult = Result<Option<String>, String>;
+
+type CommandRunner = Box<dyn Fn(&mut World, &[&str]) -> CommandResult + Send + Sync>;
+
+pub struct ConsoleCommand {
+    /// Arguments shown by `help`, e.g. `<x> <y> <z>`
+    pub usage: &'static str,
+    pub scope: CommandScope,
+    run: CommandRunner,
+}
+
+/// Commands of the console by name.
+#[derive(Default, Resource)]
+pub struct ConsoleCommands(BTreeMap<&'static str, ConsoleCommand>);
+
+impl ConsoleCommands {
+    /// Registers `name`, `parse` turns the arguments into the input of `run`.
+    pub fn register<A: 'static>(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        scope: CommandScope,
+        parse: fn(&[&str]) -> Result<A, String>,
+        run: fn(&mut World, A) -> CommandResult,
+    ) {
+        let run: CommandRunner = Box::new(move |world, args| run(world, parse(args)?));
+        if self
+            .0
+            .insert(name, ConsoleCommand { usage, scope, run })
+            .is_some()
+        {
+            log::warn!("Console command {} is registered twice, the last one is kept", name);
+        }
+    }
+
+    /// `help` and the registered names in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        std::iter::once("help").chain(self.0.keys().copied())
+    }
+}
+
+pub trait ConsoleAppExt {
+    /// Registers a console command, see [`ConsoleCommands::register`].
+    fn add_console_command<A: 'static>(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        scope: CommandScope,
+        parse: fn(&[&str]) -> Result<A, String>,
+        run: fn(&mut World, A) -> CommandResult,
+    ) -> &mut Self;
+}
+
+impl ConsoleAppExt for App {
+    fn add_console_command<A: 'static>(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        scope: CommandScope,
+        parse: fn(&[&str]) -> Result<A, String>,
+        run: fn(&mut World, A) -> CommandResult,
+    ) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        self.world
+            .resource_mut::<ConsoleCommands>()
+            .register(name, usage, scope, parse, run);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct ConsoleLine {
+    text: String,
+    error: bool,
+}
+
+#[derive(Debug, Default, Resource)]
+pub struct ConsoleState {
+    pub open: bool,
+    input: String,
+    output: Vec<ConsoleLine>,
+    history: Vec<String>,
+    /// Position in `history` while browsing it with the arrows
+    history_index: Option<usize>,
+    /// Lines submitted this frame, run by [`run_commands`]
+    pending: Vec<String>,
+    /// Mouse grab before the console was opened, restored when it closes
+    grab_before: Option<MouseGrabState>,
+}
+
+impl ConsoleState {
+    pub fn print(&mut self, text: impl Into<String>) {
+        self.push_line(text.into(), false);
+    }
+
+    pub fn print_error(&mut self, text: impl Into<String>) {
+        self.push_line(text.into(), true);
+    }
+
+    fn push_line(&mut self, text: String, error: bool) {
+        for line in text.lines() {
+            self.output.push(ConsoleLine {
+                text: line.to_string(),
+                error,
+            });
+        }
+        let overflow = self.output.len().saturating_sub(MAX_OUTPUT_LINES);
+        self.output.drain(..overflow);
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input).trim().to_string();
+        self.history_index = None;
+        if line.is_empty() {
+            return;
+        }
+        self.print(format!("> {}", line));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            let overflow = self.history.len().saturating_sub(MAX_HISTORY);
+            self.history.drain(..overflow);
+        }
+        self.pending.push(line);
+    }
+
+    /// Steps through the history, `back` towards older lines.
+    fn browse_history(&mut self, back: bool) {
+        let index = match (self.history_index, back) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) => Some(index + 1).filter(|index| *index < self.history.len()),
+        };
+        self.history_index = index;
+        self.input = index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+
+    /// Completes the command name being typed, lists the candidates if there are several.
+    fn complete(&mut self, commands: &ConsoleCommands) {
+        let prefix = self.input.trim_start();
+        if prefix.contains(char::is_whitespace) {
+            return;
+        }
+        let candidates: Vec<&str> = commands
+            .names()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        match candidates.as_slice() {
+            [] => {}
+            [name] => self.input = format!("{} ", name),
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.len(), |len, name| {
+                    first
+                        .bytes()
+                        .zip(name.bytes())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                self.input = first[..common].to_string();
+                self.print(candidates.join("  "));
+            }
+        }
+    }
+}
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleState>()
+            .init_resource::<ConsoleCommands>()
+            .add_systems(
+                PreUpdate,
+                (
+                    toggle_console,
+                    suppress_game_input.run_if(|console: Res<ConsoleState>| console.open),
+                )
+                    .chain()
+                    .after(InputSystem),
+            )
+            .add_systems(
+                Update,
+                (console_ui, run_commands)
+                    .chain()
+                    .run_if(|console: Res<ConsoleState>| console.open),
+            )
+            .add_console_command(
+                "map",
+                "<hub|level file>",
+                CommandScope::Authority,
+                parse_level,
+                change_map,
+            )
+            .add_console_command(
+                "tp",
+                "<x> <y> <z>",
+                CommandScope::Authority,
+                parse_vec3,
+                teleport,
+            )
+            .add_console_command(
+                "kill",
+                "",
+                CommandScope::Authority,
+                no_args,
+                kill,
+            )
+            .add_console_command(
+                "gravity",
+                "<x> <y> <z>",
+                CommandScope::Authority,
+                parse_vec3,
+                gravity,
+            )
+            .add_console_command(
+                "timeofday",
+                "<0..1>",
+                CommandScope::Authority,
+                parse_phase,
+                time_of_day,
+            )
+            .add_console_command(
+                "teams",
+                "<off|on|ff>",
+                CommandScope::Authority,
+                parse_teams,
+                teams,
+            )
+            .add_console_command(
+                "afk",
+                "<off|spectate|kick>",
+                CommandScope::Authority,
+                parse_afk,
+                afk,
+            )
+            .add_console_command(
+                "team",
+                "<a|b>",
+                CommandScope::Local,
+                parse_team,
+                switch_team,
+            )
+            .add_console_command(
+                "list_players",
+                "",
+                CommandScope::Local,
+                no_args,
+                list_players,
+            )
+            .add_console_command(
+                "quit",
+                "",
+                CommandScope::Local,
+                no_args,
+                quit,
+            );
+    }
+}
+
+fn toggle_console(
+    mut keyboard_input: EventReader<KeyboardInput>,
+    mut console: ResMut<ConsoleState>,
+    mouse_grab_state: Res<State<MouseGrabState>>,
+    mut next_state_mouse_grab: ResMut<NextState<MouseGrabState>>,
+) {
+    // raw events, the button state is cleared while the console is open
+    let toggled = keyboard_input.read().any(|input| {
+        input.state == ButtonState::Pressed
+            && (input.key_code == KeyCode::Backquote
+                || (console.open && input.key_code == KeyCode::Escape))
+    });
+    if !toggled {
+        return;
+    }
+
+    console.open = !console.open;
+    if console.open {
+        console.grab_before = Some(*mouse_grab_state.get());
+        next_state_mouse_grab.set(MouseGrabState::Disable);
+    } else if let Some(grab_before) = console.grab_before.take() {
+        next_state_mouse_grab.set(grab_before);
+    }
+}
+
+/// Typing into the console must not move the character or open menus.
+fn suppress_game_input(
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+) {
+    keyboard.reset_all();
+    mouse.reset_all();
+}
+
+fn console_ui(
+    mut context: EguiContexts,
+    mut console: ResMut<ConsoleState>,
+    commands: Res<ConsoleCommands>,
+    ui_frame_rect: Res<ViewportRect>,
+) {
+    let ctx = context.ctx_mut();
+    let input_id = egui::Id::new("console_input");
+
+    egui::TopBottomPanel::top("console")
+        .resizable(false)
+        .frame(
+            egui::Frame::default()
+                .fill(egui::Color32::from_black_alpha(220))
+                .inner_margin(8.),
+        )
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(ui_frame_rect.height() * CONSOLE_HEIGHT)
+                .auto_shrink([false, true])
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in console.output.iter() {
+                        let text = egui::RichText::new(&line.text).monospace();
+                        if line.error {
+                            ui.colored_label(egui::Color32::RED, text);
+                        } else {
+                            ui.label(text);
+                        }
+                    }
+                });
+
+            // taken before the text edit sees them, tab would move the focus away
+            let (tab, up, down) = ui.input_mut(|input| {
+                (
+                    input.consume_key(egui::Modifiers::NONE, egui::Key::Tab),
+                    input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                    input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                )
+            });
+            if tab {
+                console.complete(&commands);
+            }
+            if up || down {
+                console.browse_history(up);
+            }
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .id(input_id)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY)
+                    .lock_focus(true),
+            );
+            // the toggle key is typed too
+            console.input.retain(|c| c != '`');
+
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                console.submit();
+            }
+            response.request_focus();
+
+            if tab || up || down {
+                // the replaced text is edited from its end
+                if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), input_id) {
+                    let end = CCursor::new(console.input.chars().count());
+                    state.set_ccursor_range(Some(CCursorRange::one(end)));
+                    state.store(ui.ctx(), input_id);
+                }
+            }
+        });
+}
+
+fn run_commands(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<ConsoleState>().pending);
+    if pending.is_empty() {
+        return;
+    }
+    let authority = matches!(
+        world.resource::<State<LobbyState>>().get(),
+        LobbyState::Single | LobbyState::Host
+    );
+
+    world.resource_scope(|world, commands: Mut<ConsoleCommands>| {
+        for line in pending {
+            let result = run_line(world, &commands, &line, authority);
+            let mut console = world.resource_mut::<ConsoleState>();
+            match result {
+                Ok(Some(text)) => console.print(text),
+                Ok(None) => {}
+                Err(err) => console.print_error(err),
+            }
+        }
+    });
+}
+
+fn run_line(
+    world: &mut World,
+    commands: &ConsoleCommands,
+    line: &str,
+    authority: bool,
+) -> CommandResult {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return Ok(None);
+    };
+    let args: Vec<&str> = words.collect();
+
+    if name == "help" {
+        let help = commands
+            .0
+            .iter()
+            .map(|(name, command)| format!("{} {}", name, command.usage))
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Ok(Some(help));
+    }
+
+    let command = commands
+        .0
+        .get(name)
+        .ok_or_else(|| format!("unknown command `{}`, see `help`", name))?;
+    if command.scope == CommandScope::Authority && !authority {
+        return Err(format!(
+            "`{}` changes the session, it only runs in a single player game or on the host",
+            name
+        ));
+    }
+    (command.run)(world, &args).map_err(|err| format!("{}: {}", name, err))
+}
+
+/// Parses exactly `count` arguments as `T`.
+pub fn parse_args<T: FromStr>(args: &[&str], count: usize) -> Result<Vec<T>, String> {
+    if args.len() != count {
+        return Err(format!("expected {} arguments, got {}", count, args.len()));
+    }
+    args.iter()
+        .map(|arg| arg.parse().map_err(|_| format!("invalid argument `{}`", arg)))
+        .collect()
+}
+
+pub fn no_args(args: &[&str]) -> Result<(), String> {
+    parse_args::<String>(args, 0).map(|_| ())
+}
+
+pub fn parse_vec3(args: &[&str]) -> Result<Vec3, String> {
+    parse_args::<f32>(args, 3).map(|xyz| Vec3::new(xyz[0], xyz[1], xyz[2]))
+}
+
+fn parse_level(args: &[&str]) -> Result<LevelCode, String> {
+    let [name] = args else {
+        return Err(format!("expected 1 argument, got {}", args.len()));
+    };
+    if name.eq_ignore_ascii_case("hub") {
+        return Ok(LevelCode::Known(KnownLevel::Hub));
+    }
+    if !level_path(name).exists() {
+        return Err(format!("no level file {:?}", level_path(name)));
+    }
+    Ok(LevelCode::Path(name.to_string()))
+}
+
+fn change_map(world: &mut World, level: LevelCode) -> CommandResult {
+    world.send_event(ChangeMapLobbyEvent(level.clone()));
+    world.send_event(LoadLevelEvent::new(level));
+    Ok(None)
+}
+
+fn teleport(world: &mut World, position: Vec3) -> CommandResult {
+    let mut query = world.query_filtered::<(Entity, &mut Transform), With<Me>>();
+    let (entity, mut transform) = query
+        .get_single_mut(world)
+        .map_err(|_| "no character to teleport".to_string())?;
+    transform.translation = position;
+    world.entity_mut(entity).insert(Teleported);
+    Ok(None)
+}
+
+fn kill(world: &mut World, _: ()) -> CommandResult {
+    let mut query = world.query_filtered::<&mut Respawn, With<Me>>();
+    let mut respawn = query
+        .get_single_mut(world)
+        .map_err(|_| "no character to kill".to_string())?;
+    respawn.insert_reason(DespawnReason::Forced);
+    Ok(None)
+}
+
+fn gravity(world: &mut World, gravity: Vec3) -> CommandResult {
+    let physics = LevelPhysics {
+        gravity,
+        ..*world.resource::<LevelPhysics>()
+    };
+    world.send_event(ChangePhysicsEvent(physics));
+    Ok(None)
+}
+
+fn parse_phase(args: &[&str]) -> Result<f32, String> {
+    let phase = parse_args::<f32>(args, 1)?[0];
+    if !(0. ..=1.).contains(&phase) {
+        return Err("the time of day is from 0 to 1".to_string());
+    }
+    Ok(phase)
+}
+
+fn time_of_day(world: &mut World, phase: f32) -> CommandResult {
+    let environment = world.resource::<EnvironmentSettings>().clone();
+    let day_cycle = environment.day_cycle.is_some();
+    world.send_event(ChangeEnvironmentEvent { environment, phase });
+    if !day_cycle {
+        return Ok(Some("the level has no day cycle".to_string()));
+    }
+    Ok(None)
+}
+
+/// `ff` enables the team mode with friendly fire.
+fn parse_teams(args: &[&str]) -> Result<Option<TeamRules>, String> {
+    match args {
+        ["off"] => Ok(None),
+        ["on"] => Ok(Some(TeamRules {
+            friendly_fire: false,
+        })),
+        ["ff"] => Ok(Some(TeamRules {
+            friendly_fire: true,
+        })),
+        [arg] => Err(format!("invalid argument `{}`", arg)),
+        _ => Err(format!("expected 1 argument, got {}", args.len())),
+    }
+}
+
+fn teams(world: &mut World, rules: Option<TeamRules>) -> CommandResult {
+    world.resource_mut::<ServerSettings>().teams = rules;
+    Ok(None)
+}
+
+fn parse_afk(args: &[&str]) -> Result<Option<AfkAction>, String> {
+    match args {
+        ["off"] => Ok(None),
+        ["spectate"] => Ok(Some(AfkAction::Spectate)),
+        ["kick"] => Ok(Some(AfkAction::Disconnect)),
+        [arg] => Err(format!("invalid argument `{}`", arg)),
+        _ => Err(format!("expected 1 argument, got {}", args.len())),
+    }
+}
+
+fn afk(world: &mut World, action: Option<AfkAction>) -> CommandResult {
+    world.resource_mut::<ServerSettings>().afk = action.map(|action| AfkRules {
+        action,
+        ..default()
+    });
+    Ok(None)
+}
+
+fn parse_team(args: &[&str]) -> Result<TeamId, String> {
+    match args {
+        [arg] if arg.eq_ignore_ascii_case("a") => Ok(TeamId::A),
+        [arg] if arg.eq_ignore_ascii_case("b") => Ok(TeamId::B),
+        [arg] => Err(format!("invalid argument `{}`", arg)),
+        _ => Err(format!("expected 1 argument, got {}", args.len())),
+    }
+}
+
+/// Asks the host to switch the own team, the host answers the request itself.
+fn switch_team(world: &mut World, team: TeamId) -> CommandResult {
+    match world.resource::<State<LobbyState>>().get() {
+        LobbyState::Client => {
+            let mut client = world
+                .get_resource_mut::<renet::RenetClient>()
+                .ok_or_else(|| "not connected".to_string())?;
+            send_to_server(&mut client, &ClientMessages::SwitchTeam { team }, Channel::Control);
+        }
+        LobbyState::Host => {
+            world.send_event(TeamSwitchRequest {
+                id: PlayerId::HostOrSingle,
+                team,
+            });
+        }
+        _ => return Err("teams are only played in a hosted game".to_string()),
+    }
+    Ok(None)
+}
+
+fn list_players(world: &mut World, _: ()) -> CommandResult {
+    let lobby = world
+        .get_resource::<Lobby>()
+        .ok_or_else(|| "not in a lobby".to_string())?;
+    let me = std::iter::once(format!("me: {}", lobby.me.username));
+    let others = lobby
+        .players
+        .iter()
+        .map(|(id, player_data)| format!("{:?}: {}", id, player_data.username));
+    Ok(Some(me.chain(others).collect::<Vec<_>>().join("\n")))
+}
+
+fn quit(world: &mut World, _: ()) -> CommandResult {
+    world.send_event(AppExit);
+    Ok(None)
+}
@@ -1,20 +1,29 @@
+use std::sync::Arc;
+
 use bevy::{
-    app::{App, Plugin, Update},
+    app::{App, Last, Plugin, Update},
     ecs::{
+        event::EventReader,
         schedule::{NextState, State},
-        system::{Res, ResMut},
+        system::{Commands, Res, ResMut},
     },
-    input::keyboard::KeyCode,
+    log::error,
 };
 use bevy_controls::{
     contract::InputsContainer,
     plugin::ControlsPlugin,
     resource::{Binding, BindingCondition, BindingConfig, Controls, InputType},
 };
+use strum::IntoEnumIterator;
 
 use crate::{
     core::{CoreAction, CoreGameState},
     lobby::Lobby,
+    settings::{
+        capture_binding, key_bindings_path, read_key_bindings, write_key_bindings,
+        AppliedKeyBindings, ApplyKeyBindings, BoundInput, ExemptKeyBindings, KeyBindingCapture,
+        KeyBindings, KeyBindingsPath,
+    },
     ui::{GameMenuActionState, MouseGrabState},
 };
 
@@ -23,28 +32,101 @@ pub struct ControlsPlugins;
 
 impl Plugin for ControlsPlugins {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, in_game_menu)
+        // the controls plugin needs its bindings right away, so the file is read at build
+        let path = key_bindings_path();
+        let bindings = read_key_bindings(&path);
+
+        app.insert_resource(KeyBindingsPath(Arc::new(path)))
+            .insert_resource(AppliedKeyBindings(bindings.clone()))
+            .init_resource::<KeyBindingCapture>()
+            .add_event::<ApplyKeyBindings>()
+            .add_event::<ExemptKeyBindings>()
+            .add_systems(Update, in_game_menu)
+            // after the frame, so the captured input does not also act in it
+            .add_systems(Last, (capture_binding, apply_key_bindings, exempt_key_bindings))
             .add_plugins((ControlsPlugin::<CoreAction, Lobby, CoreGameState>::new(
-                Controls::<CoreAction, CoreGameState>::new()
-                    .with(
-                        CoreAction::InGameMenu,
-                        BindingConfig::from_vec(vec![Binding::from_single(InputType::Keyboard(
-                            KeyCode::Escape,
-                        ))
-                        .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
-                    )
-                    .build(),
-            ),));
+                build_controls(&bindings),
+            ),))
+            .insert_resource(bindings);
+    }
+}
+
+fn input_type(input: BoundInput) -> InputType {
+    match input {
+        BoundInput::Keyboard(key) => InputType::Keyboard(key),
+        BoundInput::Mouse(button) => InputType::Mouse(button),
+        BoundInput::Gamepad(button) => InputType::Gamepad(button),
+    }
+}
+
+/// Controls with every [`CoreAction`] bound as described by `bindings`
+fn build_controls(bindings: &KeyBindings) -> Controls<CoreAction, CoreGameState> {
+    CoreAction::iter()
+        .fold(Controls::<CoreAction, CoreGameState>::new(), |controls, action| {
+            let Some(input) = bindings.get(action) else {
+                return controls;
+            };
+            controls.with(
+                action,
+                BindingConfig::from_vec(vec![Binding::from_single(input_type(input))
+                    .with_condition(BindingCondition::InGameState(CoreGameState::InGame))]),
+            )
+        })
+        .build()
+}
+
+fn apply_key_bindings(
+    mut event: EventReader<ApplyKeyBindings>,
+    bindings: Res<KeyBindings>,
+    mut applied_bindings: ResMut<AppliedKeyBindings>,
+    mut controls: ResMut<Controls<CoreAction, CoreGameState>>,
+    path: Res<KeyBindingsPath>,
+) {
+    for _ in event.read() {
+        // the window does not allow it, but never apply ambiguous bindings
+        if !bindings.conflicts().is_empty() {
+            error!("Key bindings have conflicts, not applied");
+            continue;
+        }
+
+        *controls = build_controls(&bindings);
+        applied_bindings.0 = bindings.clone();
+
+        if let Err(err) = write_key_bindings(path.as_ref().as_ref(), &bindings) {
+            error!(
+                "Failed to write key bindings file ({:#?}) \n error: {}",
+                path.as_ref(),
+                err
+            );
+        }
+    }
+}
+
+fn exempt_key_bindings(
+    mut commands: Commands,
+    mut event: EventReader<ExemptKeyBindings>,
+    applied_bindings: Res<AppliedKeyBindings>,
+    mut capture: ResMut<KeyBindingCapture>,
+) {
+    for _ in event.read() {
+        capture.0 = None;
+        commands.insert_resource(applied_bindings.0.clone());
     }
 }
 
 fn in_game_menu(
     inputs_container: Res<Lobby>,
+    capture: Res<KeyBindingCapture>,
     mut next_state_mouse_grab: ResMut<NextState<MouseGrabState>>,
     mouse_grab_state: Res<State<MouseGrabState>>,
     mut next_state_game_menu_action: ResMut<NextState<GameMenuActionState>>,
     game_menu_action: Res<State<GameMenuActionState>>,
 ) {
+    // the pressed input is being bound, it does not act
+    if capture.0.is_some() {
+        return;
+    }
+
     let player_inputs = inputs_container.me().expect("This is bad");
 
     if player_inputs
@@ -1,22 +1,48 @@
-use std::{fs::OpenOptions, io::Write, path::Path};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
-use bevy::{gltf::Gltf, prelude::*};
+use bevy::{app::AppExit, gltf::Gltf, prelude::*};
 use bevy_asset_loader::prelude::*;
 
 use bevy_controls_derive::{Action, GameState};
 use bevy_kira_audio::AudioSource;
+use serde::{Deserialize, Serialize};
 use strum_macros::EnumIter;
 
 use crate::{
+    actor::character::MovementTuning,
     controls::ControlsPlugins,
-    lobby::LevelCode,
-    world::WorldPlugins,
+    level::{level_environment, level_movement, level_path, level_physics},
+    lobby::{LevelCode, LobbyState, MapLoaderState},
+    replay::ReplayPlayback,
+    world::{DayPhase, EnvironmentSettings, LevelPhysics, SimulationConfig, WorldPlugins},
     ASSET_DIR,
 };
 
-#[derive(PartialEq, Eq, Hash, EnumIter, Clone, Copy, Debug, Action)]
+#[derive(PartialEq, Eq, Hash, EnumIter, Clone, Copy, Debug, Action, Serialize, Deserialize)]
 pub enum CoreAction {
     InGameMenu,
+    QuickChat,
+    Kick,
+    Screenshot,
+    RecordReplay,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    FreeCamera,
+    NetworkStats,
+    Jump,
+    Fire,
+    Scoreboard,
+    CameraMode,
+    Quit,
 }
 
 #[derive(States, PartialEq, Eq, Clone, Hash, Debug, Default, GameState)]
@@ -29,7 +55,7 @@ pub enum CoreGameState {
     InGame,
 }
 
-#[derive(PartialEq, Eq, Clone, Hash, Debug)]
+#[derive(PartialEq, Eq, Clone, Hash, Debug, Serialize, Deserialize)]
 pub enum KnownLevel {
     Hub,
 }
@@ -37,11 +63,51 @@ pub enum KnownLevel {
 #[derive(Debug, Event, Clone)]
 pub struct LoadLevelEvent {
     pub level_code: LevelCode,
+    /// Physics to use instead of the level ones, e.g. the host ones on a client.
+    pub physics: Option<LevelPhysics>,
+    /// Environment and day phase to use instead of the level ones, e.g. the host ones on a client.
+    pub environment: Option<(EnvironmentSettings, f32)>,
 }
 
 impl LoadLevelEvent {
     pub fn new(level_code: LevelCode) -> Self {
-      LoadLevelEvent { level_code }
+      LoadLevelEvent { level_code, physics: None, environment: None }
+    }
+
+    pub fn with_physics(mut self, physics: LevelPhysics) -> Self {
+        self.physics = Some(physics);
+        self
+    }
+
+    pub fn with_environment(mut self, environment: EnvironmentSettings, phase: f32) -> Self {
+        self.environment = Some((environment, phase));
+        self
+    }
+}
+
+/// Describes the level loading currently in progress, shown by the loading screen.
+#[derive(Debug, Default, Resource, Clone)]
+pub struct LoadingProgress {
+    /// Human readable name of the current loading stage.
+    pub stage: String,
+    /// Progress of the current stage in `0.0..=1.0`, if it is known (e.g. a download).
+    pub fraction: Option<f32>,
+}
+
+impl LoadingProgress {
+    pub fn set_stage(&mut self, stage: impl Into<String>) {
+        self.stage = stage.into();
+        self.fraction = None;
+    }
+}
+
+/// The level loaded last, [`KnownLevel::Hub`] until another one is requested.
+#[derive(Debug, Resource, Clone, PartialEq, Deref)]
+pub struct CurrentLevel(pub LevelCode);
+
+impl Default for CurrentLevel {
+    fn default() -> Self {
+        Self(LevelCode::Known(KnownLevel::Hub))
     }
 }
 
@@ -57,12 +123,96 @@ pub struct AudioAssets {
     pub background: Handle<AudioSource>,
 }
 
+/// Set by the Ctrl+C handler, turned into an [`AppExit`] by [`exit_on_ctrl_c`]
+static CTRL_C_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Default of [`CoreConfig::connect_timeout`]
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Main plugin of the game
-pub struct CorePlugins;
+/// Configuration of [`CorePlugins`], available as a resource to the game systems.
+#[derive(Debug, Clone, Resource)]
+pub struct CoreConfig {
+    /// Physics of the levels that do not override them
+    pub default_physics: LevelPhysics,
+    /// Level a single player or hosted game starts on
+    pub start_level: LevelCode,
+    /// Lobby entered once the primary assets are loaded instead of the main menu,
+    /// with the default `HostResource` and `ClientResource`
+    pub start_lobby: Option<LobbyState>,
+    /// Tick and snapshot rates, changed at runtime through the [`SimulationConfig`] resource
+    pub simulation: SimulationConfig,
+    /// Time a client waits for the host to accept it before giving up
+    pub connect_timeout: Duration,
+}
+
+impl Default for CoreConfig {
+    fn default() -> Self {
+        Self {
+            default_physics: LevelPhysics::default(),
+            start_level: LevelCode::Known(KnownLevel::Hub),
+            start_lobby: None,
+            simulation: SimulationConfig::default(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+}
+
+/// The whole game, `CorePlugins::default()` is the game as shipped.
+///
+/// ```ignore
+/// app.add_plugins(
+///     CorePlugins::default()
+///         .with_start_level(LevelCode::Path("arena".into()))
+///         .with_start_lobby(LobbyState::Single),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CorePlugins {
+    config: CoreConfig,
+}
+
+impl CorePlugins {
+    pub fn new(config: CoreConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn with_default_physics(mut self, physics: LevelPhysics) -> Self {
+        self.config.default_physics = physics;
+        self
+    }
+
+    pub fn with_start_level(mut self, level: LevelCode) -> Self {
+        self.config.start_level = level;
+        self
+    }
+
+    pub fn with_start_lobby(mut self, lobby: LobbyState) -> Self {
+        self.config.start_lobby = Some(lobby);
+        self
+    }
+
+    pub fn with_simulation(mut self, simulation: SimulationConfig) -> Self {
+        self.config.simulation = simulation;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.config.connect_timeout = timeout;
+        self
+    }
+}
 
 impl Plugin for CorePlugins {
     fn build(&self, app: &mut App) {
+        // before `SimulationPlugins`, which keeps an existing resource
+        app.insert_resource(self.config.default_physics)
+            .insert_resource(self.config.simulation)
+            .insert_resource(self.config.clone());
+
         app.add_event::<LoadLevelEvent>()
+            .init_resource::<LoadingProgress>()
+            .init_resource::<CurrentLevel>()
             .add_loading_state(
                 LoadingState::new(CoreGameState::PrimaryLoad)
                     .continue_to_state(CoreGameState::Hub)
@@ -80,7 +230,17 @@ impl Plugin for CorePlugins {
                     .load_collection::<GameLevel>(),
             )
             .add_plugins((WorldPlugins, ControlsPlugins))
-            .add_systems(Update, load_level_event);
+            .add_systems(Update, (load_level_event, exit_on_ctrl_c))
+            .add_systems(OnExit(CoreGameState::PrimaryLoad), enter_start_lobby)
+            .add_systems(OnEnter(CoreGameState::LoadLobby), loading_stage_lobby)
+            .add_systems(OnEnter(CoreGameState::InGame), loading_stage_spawn)
+            .add_systems(Last, validate_transitions);
+
+        // exit through AppExit so exit hooks (e.g. host shutdown broadcast) still run
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(err) = ctrlc::set_handler(|| CTRL_C_RECEIVED.store(true, Ordering::SeqCst)) {
+            log::warn!("Failed to set Ctrl+C handler: {}", err);
+        }
 
         #[cfg(debug_assertions)]
         app.add_systems(
@@ -95,17 +255,139 @@ fn change_state_log(core_state: Res<State<CoreGameState>>) {
     log::debug!("new state: {:#?}", core_state);
 }
 
+/// Skips the main menu if [`CoreConfig::start_lobby`] is set, like its buttons would.
+fn enter_start_lobby(
+    config: Res<CoreConfig>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+) {
+    let Some(lobby) = config.start_lobby else {
+        return;
+    };
+    log::info!("Entering {:?} right away", lobby);
+    next_state_lobby.set(lobby);
+    // a client gets the level of the host
+    if matches!(lobby, LobbyState::Single | LobbyState::Host) {
+        load_level_event.send(LoadLevelEvent::new(config.start_level.clone()));
+    }
+}
+
+/// Whether the game may go from `from` to `to`. Levels are loaded from the hub or a session,
+/// and anything goes back to the hub.
+pub fn core_transition_allowed(from: &CoreGameState, to: &CoreGameState) -> bool {
+    use CoreGameState::*;
+    from == to
+        || matches!(
+            (from, to),
+            (PrimaryLoad, Hub)
+                | (Hub | LoadLobby | InGame, LoadCustomLevel)
+                | (LoadCustomLevel, LoadLobby)
+                | (LoadLobby, InGame)
+                | (LoadCustomLevel | LoadLobby | InGame, Hub)
+        )
+}
+
+/// Whether the lobby may go from `from` to `to`. Sessions are entered from the menu and left
+/// to it, a client only becomes the host by migration.
+pub fn lobby_transition_allowed(from: &LobbyState, to: &LobbyState) -> bool {
+    from == to
+        || *from == LobbyState::None
+        || *to == LobbyState::None
+        || (*from, *to) == (LobbyState::Client, LobbyState::Host)
+}
+
+/// Whether the game may stay in `core` with `lobby`: only the menu runs without a session.
+pub fn combination_allowed(core: &CoreGameState, lobby: &LobbyState) -> bool {
+    *lobby != LobbyState::None || matches!(core, CoreGameState::PrimaryLoad | CoreGameState::Hub)
+}
+
+/// Logs the transitions requested against the rules above, they are still applied.
+/// A replay plays a session back without one, so it is not held to the combination rule.
+fn validate_transitions(
+    core_state: Res<State<CoreGameState>>,
+    next_core_state: Res<NextState<CoreGameState>>,
+    lobby_state: Res<State<LobbyState>>,
+    next_lobby_state: Res<NextState<LobbyState>>,
+    replay: Option<Res<ReplayPlayback>>,
+    mut reported: Local<Option<(CoreGameState, LobbyState)>>,
+) {
+    if let Some(next) = &next_core_state.0 {
+        if !core_transition_allowed(core_state.get(), next) {
+            log::error!("Illegal transition from {:?} to {:?}", core_state.get(), next);
+        }
+    }
+    if let Some(next) = &next_lobby_state.0 {
+        if !lobby_transition_allowed(lobby_state.get(), next) {
+            log::error!("Illegal lobby transition from {:?} to {:?}", lobby_state.get(), next);
+        }
+    }
+
+    // the other state follows a frame later, e.g. the hub is loaded once the session is left
+    if next_core_state.0.is_some() || next_lobby_state.0.is_some() || replay.is_some() {
+        return;
+    }
+    let current = (core_state.get().clone(), *lobby_state.get());
+    if combination_allowed(&current.0, &current.1) {
+        *reported = None;
+    } else if reported.as_ref() != Some(&current) {
+        log::error!("Illegal state {:?} while the lobby is {:?}", current.0, current.1);
+        *reported = Some(current);
+    }
+}
+
+fn loading_stage_lobby(mut progress: ResMut<LoadingProgress>, current_level: Res<CurrentLevel>) {
+    let _span = info_span!("load_level", level = ?current_level.0).entered();
+    log::info!("Preparing lobby");
+    progress.set_stage("Preparing lobby");
+}
+
+fn loading_stage_spawn(mut progress: ResMut<LoadingProgress>, current_level: Res<CurrentLevel>) {
+    let _span = info_span!("load_level", level = ?current_level.0).entered();
+    log::info!("Spawning players");
+    progress.set_stage("Spawning players");
+}
+
+fn exit_on_ctrl_c(mut exit: EventWriter<AppExit>) {
+    if CTRL_C_RECEIVED.swap(false, Ordering::SeqCst) {
+        log::info!("Ctrl+C received, exiting");
+        exit.send(AppExit);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn load_level_event(
     mut load_level_event: EventReader<LoadLevelEvent>,
     mut next_state: ResMut<NextState<CoreGameState>>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
+    mut progress: ResMut<LoadingProgress>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut physics: ResMut<LevelPhysics>,
+    mut movement: ResMut<MovementTuning>,
+    mut environment: ResMut<EnvironmentSettings>,
+    mut day_phase: ResMut<DayPhase>,
+    config: Res<CoreConfig>,
 ) {
     if let Some(event) = load_level_event.read().next() {
+        let _span = info_span!("load_level", level = ?event.level_code).entered();
+        next_state_map.set(MapLoaderState::No);
+        current_level.0 = event.level_code.clone();
+        // overrides of the previous level do not leak into this one
+        *physics = event
+            .physics
+            .unwrap_or_else(|| level_physics(&event.level_code, config.default_physics));
+        *movement = level_movement(&event.level_code);
+        let (settings, phase) = event.environment.clone().unwrap_or_else(|| {
+            let settings = level_environment(&event.level_code);
+            let phase = settings.start_phase();
+            (settings, phase)
+        });
+        *environment = settings;
+        day_phase.0 = phase;
+        progress.set_stage(format!("Loading level {:?}", event.level_code));
         match &event.level_code {
             LevelCode::Path(path) => {
                 log::info!("load level: {}", path);
-                let path = Path::new(ASSET_DIR)
-                    .join("level")
-                    .join(format!("{path}.glb"));
+                let path = level_path(path);
                 let path_ron = Path::new(ASSET_DIR).join("dynamic_map.assets.ron");
 
                 if path.exists() {
@@ -0,0 +1,355 @@
+//! Crash reports written by the panic hook, and a clean disconnect before the process dies.
+//!
+//! [`install_panic_hook`] writes a report into [`CRASH_DIR`] next to the executable: the panic,
+//! its backtrace, the version, the states of the game and the last [`CRASH_LOG_LINES`] log lines
+//! kept by the layer of [`with_crash_log_tail`]. The hook runs without the world, it only reads
+//! what was copied out of it and never waits on a lock.
+//!
+//! The network is torn down by [`CrashReportPlugin`] instead: it runs the `Main` schedule
+//! catching the unwind of a panicking system, and disconnects the clients of the host (or the
+//! client from its host) before the panic goes on. A panic outside the main world, e.g. in the
+//! render thread, only gets its report.
+//!
+//! The next launch finds the report through [`FreshCrashReport`], the menu offers to open it.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError, TryLockError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::ecs::schedule::{ExecutorKind, ScheduleLabel};
+use bevy::log::tracing_subscriber::layer::{Context, SubscriberExt};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::BoxedSubscriber;
+use bevy::prelude::*;
+use bevy::utils::tracing::span::{Attributes, Id};
+use bevy::utils::tracing::{Event, Subscriber};
+use renet::transport::{NetcodeClientTransport, NetcodeServerTransport};
+use renet::RenetServer;
+
+use crate::core::CoreGameState;
+use crate::log_file::{format_line, SpanLabels};
+use crate::lobby::LobbyState;
+
+/// Log lines kept for the report
+pub const CRASH_LOG_LINES: usize = 200;
+/// Directory next to the executable the reports are written into
+pub const CRASH_DIR: &str = "crashes";
+/// File of [`CRASH_DIR`] naming the report not seen in the menu yet
+const UNSEEN_FILE: &str = "unseen";
+
+lazy_static::lazy_static! {
+    /// Last [`CRASH_LOG_LINES`] log lines, oldest first
+    static ref LOG_TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+    /// States of the game, copied by [`track_game_states`]
+    static ref GAME_STATES: Mutex<GameStates> = Mutex::new(GameStates::default());
+}
+
+/// Only the first panic is reported, the ones it causes would hide it
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone)]
+struct GameStates {
+    lobby: String,
+    core: String,
+}
+
+impl Default for GameStates {
+    fn default() -> Self {
+        Self {
+            lobby: "unknown".to_string(),
+            core: "unknown".to_string(),
+        }
+    }
+}
+
+/// What is known of the game when it panicked.
+#[derive(Debug, Clone)]
+pub struct CrashReport {
+    /// Seconds since the unix epoch
+    pub time: u64,
+    pub version: String,
+    /// The panic message with its location
+    pub panic: String,
+    pub backtrace: String,
+    pub lobby_state: String,
+    pub core_state: String,
+    pub log_tail: Vec<String>,
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Crash report of v{}", self.version)?;
+        writeln!(f, "Time: {}", self.time)?;
+        writeln!(f, "Lobby state: {}", self.lobby_state)?;
+        writeln!(f, "Core state: {}", self.core_state)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.panic)?;
+        writeln!(f)?;
+        writeln!(f, "Backtrace:")?;
+        writeln!(f, "{}", self.backtrace.trim_end())?;
+        writeln!(f)?;
+        writeln!(f, "Last {} log lines:", self.log_tail.len())?;
+        for line in self.log_tail.iter() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Last `count` of `lines`, without their line breaks.
+pub fn log_tail(lines: &VecDeque<String>, count: usize) -> Vec<String> {
+    lines
+        .iter()
+        .skip(lines.len().saturating_sub(count))
+        .map(|line| line.trim_end().to_string())
+        .collect()
+}
+
+/// Reads `mutex` if nobody holds it, a poisoned one is read anyway.
+fn try_read<T, R>(mutex: &Mutex<T>, read: impl FnOnce(&T) -> R) -> Option<R> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(read(&guard)),
+        Err(TryLockError::Poisoned(poisoned)) => Some(read(&poisoned.into_inner())),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Copies the last log lines into [`LOG_TAIL`].
+struct CrashLogLayer(SpanLabels);
+
+impl<S: Subscriber> Layer<S> for CrashLogLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.0.new_span(attrs, id, &ctx);
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.0.close(&id);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let line = format_line(event, self.0.of(event, &ctx).as_deref());
+        let mut lines = LOG_TAIL.lock().unwrap_or_else(PoisonError::into_inner);
+        if lines.len() >= CRASH_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// Adds the log tail of the crash reports to the subscriber of the `LogPlugin`.
+pub fn with_crash_log_tail(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    Box::new(subscriber.with(CrashLogLayer(SpanLabels::default())))
+}
+
+fn crash_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(CRASH_DIR)))
+        .unwrap_or_else(|| PathBuf::from(CRASH_DIR))
+}
+
+/// Writes `report` and marks it unseen, returns its path.
+fn write_report(report: &CrashReport) -> io::Result<PathBuf> {
+    let dir = crash_dir();
+    fs::create_dir_all(&dir)?;
+    let name = format!("crash-{}.txt", report.time);
+    let path = dir.join(&name);
+    fs::write(&path, report.to_string())?;
+    fs::write(dir.join(UNSEEN_FILE), name)?;
+    Ok(path)
+}
+
+/// Writes a [`CrashReport`] on the first panic, after the previous hook printed it.
+pub fn install_panic_hook(version: String) {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous_hook(info);
+        if REPORTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let states = try_read(&GAME_STATES, GameStates::clone).unwrap_or_default();
+        let report = CrashReport {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            version: version.clone(),
+            panic: info.to_string(),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            lobby_state: states.lobby,
+            core_state: states.core,
+            // held by the panicking thread if the log itself panicked
+            log_tail: try_read(&LOG_TAIL, |lines| log_tail(lines, CRASH_LOG_LINES))
+                .unwrap_or_default(),
+        };
+        match write_report(&report) {
+            Ok(path) => eprintln!("Crash report written to {:?}", path),
+            Err(err) => eprintln!("Failed to write the crash report: {}", err),
+        }
+    }));
+}
+
+/// Report of a crash of an earlier launch not seen in the menu yet.
+#[derive(Debug, Default, Resource)]
+pub struct FreshCrashReport(pub Option<PathBuf>);
+
+impl FreshCrashReport {
+    fn find() -> Self {
+        let dir = crash_dir();
+        let path = fs::read_to_string(dir.join(UNSEEN_FILE))
+            .ok()
+            .map(|name| dir.join(name.trim()))
+            .filter(|path| path.is_file());
+        Self(path)
+    }
+
+    /// The report is not offered on the next launch anymore.
+    pub fn dismiss(&mut self) {
+        if self.0.take().is_some() {
+            if let Err(err) = fs::remove_file(crash_dir().join(UNSEEN_FILE)) {
+                log::warn!("Failed to dismiss the crash report: {}", err);
+            }
+        }
+    }
+}
+
+/// Opens `dir` in the file manager of the system.
+pub fn open_folder(dir: &Path) -> io::Result<()> {
+    let opener = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(opener).arg(dir).spawn().map(|_| ())
+}
+
+/// Runs `Main` catching the unwind, see [`CrashReportPlugin`].
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct GuardedMain;
+
+/// Disconnects the network when a system panics and finds the report of the last crash.
+pub struct CrashReportPlugin;
+
+impl Plugin for CrashReportPlugin {
+    fn build(&self, app: &mut App) {
+        let mut guarded_main = Schedule::new(GuardedMain);
+        guarded_main.set_executor_kind(ExecutorKind::SingleThreaded);
+        app.add_schedule(guarded_main)
+            .add_systems(GuardedMain, run_guarded_main)
+            .insert_resource(FreshCrashReport::find())
+            .add_systems(
+                Last,
+                track_game_states.run_if(
+                    state_changed::<LobbyState>.or_else(state_changed::<CoreGameState>),
+                ),
+            );
+        app.main_schedule_label = GuardedMain.intern();
+    }
+}
+
+fn run_guarded_main(world: &mut World) {
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| world.run_schedule(Main)));
+    if let Err(payload) = result {
+        // the world is half updated, nothing of it is trusted to not panic again
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| disconnect_on_crash(world)));
+        std::panic::resume_unwind(payload);
+    }
+}
+
+/// Best effort: the clients see a disconnect instead of waiting for their timeout.
+fn disconnect_on_crash(world: &mut World) {
+    if let Some(mut transport) = world.remove_resource::<NetcodeServerTransport>() {
+        if let Some(mut server) = world.get_resource_mut::<RenetServer>() {
+            eprintln!("Disconnecting {} clients after the crash", server.connected_clients());
+            server.disconnect_all();
+            transport.send_packets(&mut server);
+        }
+    }
+    if let Some(mut transport) = world.get_resource_mut::<NetcodeClientTransport>() {
+        transport.disconnect();
+    }
+}
+
+fn track_game_states(
+    lobby_state: Res<State<LobbyState>>,
+    core_state: Res<State<CoreGameState>>,
+) {
+    let mut states = GAME_STATES.lock().unwrap_or_else(PoisonError::into_inner);
+    states.lobby = format!("{:?}", lobby_state.get());
+    states.core = format!("{:?}", core_state.get());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> CrashReport {
+        CrashReport {
+            time: 1_700_000_000,
+            version: "1.2.3".to_string(),
+            panic: "panicked at src/main.rs:1:1:\noops".to_string(),
+            backtrace: "0: main\n1: start\n\n".to_string(),
+            lobby_state: "Host".to_string(),
+            core_state: "InGame".to_string(),
+            log_tail: vec!["first".to_string(), "second".to_string()],
+        }
+    }
+
+    #[test]
+    fn report_lists_everything_in_order() {
+        let text = report().to_string();
+        let expected = "Crash report of v1.2.3\n\
+                        Time: 1700000000\n\
+                        Lobby state: Host\n\
+                        Core state: InGame\n\
+                        \n\
+                        panicked at src/main.rs:1:1:\noops\n\
+                        \n\
+                        Backtrace:\n\
+                        0: main\n1: start\n\
+                        \n\
+                        Last 2 log lines:\n\
+                        first\n\
+                        second\n";
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn log_tail_keeps_the_last_lines() {
+        let lines: VecDeque<String> = (0..10).map(|i| format!("line {i}\n")).collect();
+        let tail = log_tail(&lines, 3);
+        assert_eq!(tail, vec!["line 7", "line 8", "line 9"]);
+    }
+
+    #[test]
+    fn log_tail_of_fewer_lines_keeps_them_all() {
+        let lines: VecDeque<String> = ["a", "b"].into_iter().map(String::from).collect();
+        assert_eq!(log_tail(&lines, CRASH_LOG_LINES), vec!["a", "b"]);
+        assert!(log_tail(&VecDeque::new(), CRASH_LOG_LINES).is_empty());
+    }
+
+    #[test]
+    fn try_read_gives_up_on_a_held_lock_and_reads_a_poisoned_one() {
+        let mutex = Mutex::new(1);
+        {
+            let _guard = mutex.lock().unwrap();
+            assert_eq!(try_read(&mutex, |value| *value), None);
+        }
+
+        let _ = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = mutex.lock().unwrap();
+            panic!("poison");
+        }));
+        assert!(mutex.is_poisoned());
+        assert_eq!(try_read(&mutex, |value| *value), Some(1));
+    }
+}
@@ -1,5 +1,31 @@
 use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy::window::PrimaryWindow;
+use bevy_editor_pls::default_windows::hierarchy::HierarchyWindow;
+use bevy_editor_pls::editor::Editor;
 use bevy_editor_pls::{controls, EditorPlugin};
+use bevy_egui::EguiContexts;
+use bevy_rapier3d::plugin::RapierContext;
+use bevy_rapier3d::prelude::QueryFilter;
+
+use crate::world::LinkId;
+
+/// Farthest a click picks an entity at
+const PICK_DISTANCE: f32 = 1000.;
+/// Switches the [`PickMode`]
+const PICK_MODE_KEY: KeyCode = KeyCode::F7;
+/// Radius of the marker of a selected entity without a mesh
+const SELECTION_MARKER_RADIUS: f32 = 0.2;
+
+/// What a click in the editor viewport hits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub enum PickMode {
+    /// Rapier colliders, sensors included
+    #[default]
+    Colliders,
+    /// Bounding boxes of the visible meshes
+    Visuals,
+}
 
 pub struct EditorPlugins;
 
@@ -10,7 +36,14 @@ impl Plugin for EditorPlugins {
             // its included egui plugin and egui_inspector plugin
             EditorPlugin::default(),
         )
-        .insert_resource(editor_controls());
+        .insert_resource(editor_controls())
+        .init_resource::<PickMode>()
+        .add_systems(
+            Update,
+            (toggle_pick_mode, pick_entity, highlight_selection)
+                .chain()
+                .run_if(|editor: Res<Editor>| editor.active()),
+        );
     }
 }
 
@@ -31,3 +64,142 @@ fn editor_controls() -> controls::EditorControls {
 
     editor_controls
 }
+
+fn toggle_pick_mode(keyboard: Res<ButtonInput<KeyCode>>, mut mode: ResMut<PickMode>) {
+    if !keyboard.just_pressed(PICK_MODE_KEY) {
+        return;
+    }
+    *mode = match *mode {
+        PickMode::Colliders => PickMode::Visuals,
+        PickMode::Visuals => PickMode::Colliders,
+    };
+    log::info!("Editor picks {:?}", *mode);
+}
+
+/// Selects the entity under the cursor in the hierarchy, so the inspector shows it.
+///
+/// Selects the nearest ancestor with a [`Name`] or a [`LinkId`] of the hit entity,
+/// a click on nothing clears the selection.
+#[allow(clippy::too_many_arguments)]
+fn pick_entity(
+    mut context: EguiContexts,
+    mut editor: ResMut<Editor>,
+    mode: Res<PickMode>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    rapier_context: Res<RapierContext>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    visual_query: Query<(Entity, &GlobalTransform, &Aabb, &ViewVisibility), With<Handle<Mesh>>>,
+    parent_query: Query<&Parent>,
+    named_query: Query<(), Or<(With<Name>, With<LinkId>)>>,
+) {
+    // a drag of the editor gizmo or a window is not a pick
+    if !mouse.just_pressed(MouseButton::Left) || context.ctx_mut().is_using_pointer() {
+        return;
+    }
+    let Some(cursor) = window_query.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+    let viewport = editor.viewport();
+    if !viewport.contains(bevy_egui::egui::pos2(cursor.x, cursor.y)) {
+        return;
+    }
+
+    // the editor camera while it flies, the game one otherwise
+    let Some(ray) = camera_query
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .max_by_key(|(camera, _)| camera.order)
+        .and_then(|(camera, camera_transform)| {
+            let origin = camera.logical_viewport_rect().map_or(Vec2::ZERO, |rect| rect.min);
+            camera.viewport_to_world(camera_transform, cursor - origin)
+        })
+    else {
+        return;
+    };
+
+    let hit = match *mode {
+        PickMode::Colliders => rapier_context
+            .cast_ray(ray.origin, *ray.direction, PICK_DISTANCE, true, QueryFilter::default())
+            .map(|(entity, _)| entity),
+        PickMode::Visuals => visual_query
+            .iter()
+            .filter(|(.., visibility)| visibility.get())
+            .filter_map(|(entity, transform, aabb, _)| {
+                let distance = ray_aabb_distance(ray, transform, aabb)?;
+                Some((entity, distance))
+            })
+            .filter(|(_, distance)| *distance <= PICK_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| entity),
+    };
+    let picked = hit.map(|entity| {
+        std::iter::once(entity)
+            .chain(parent_query.iter_ancestors(entity))
+            .find(|entity| named_query.contains(*entity))
+            .unwrap_or(entity)
+    });
+
+    let Some(hierarchy) = editor.window_state_mut::<HierarchyWindow>() else {
+        return;
+    };
+    match picked {
+        Some(entity) => hierarchy.selected.select_replace(entity),
+        None => hierarchy.selected.clear(),
+    }
+}
+
+/// Distance along `ray` to the bounding box of a mesh, `None` if the ray misses it.
+fn ray_aabb_distance(ray: Ray3d, transform: &GlobalTransform, aabb: &Aabb) -> Option<f32> {
+    // in the space of the mesh the box is axis aligned
+    let world_to_local = transform.affine().inverse();
+    let origin = world_to_local.transform_point3(ray.origin);
+    let direction = world_to_local.transform_vector3(*ray.direction);
+
+    let min = Vec3::from(aabb.center - aabb.half_extents);
+    let max = Vec3::from(aabb.center + aabb.half_extents);
+    let to_min = (min - origin) / direction;
+    let to_max = (max - origin) / direction;
+    let near = to_min.min(to_max).max_element();
+    let far = to_min.max(to_max).min_element();
+    if near > far || far < 0. {
+        return None;
+    }
+    // the scale of the transform is undone, so is the one of the distance
+    let local_hit = origin + direction * near.max(0.);
+    Some(transform.transform_point(local_hit).distance(ray.origin))
+}
+
+/// Outlines the bounding boxes of the selected entities and their children.
+fn highlight_selection(
+    editor: Res<Editor>,
+    mut gizmos: Gizmos,
+    transform_query: Query<&GlobalTransform>,
+    aabb_query: Query<(&GlobalTransform, &Aabb)>,
+    children_query: Query<&Children>,
+) {
+    let Some(hierarchy) = editor.window_state::<HierarchyWindow>() else {
+        return;
+    };
+    for selected in hierarchy.selected.iter() {
+        let mut outlined = false;
+        let entities = std::iter::once(selected).chain(children_query.iter_descendants(selected));
+        for (transform, aabb) in aabb_query.iter_many(entities) {
+            let bounds = Transform::from_translation(aabb.center.into())
+                .with_scale(Vec3::from(aabb.half_extents) * 2.);
+            gizmos.cuboid(transform.mul_transform(bounds), Color::YELLOW);
+            outlined = true;
+        }
+        if outlined {
+            continue;
+        }
+        if let Ok(transform) = transform_query.get(selected) {
+            gizmos.sphere(
+                transform.translation(),
+                Quat::IDENTITY,
+                SELECTION_MARKER_RADIUS,
+                Color::YELLOW,
+            );
+        }
+    }
+}
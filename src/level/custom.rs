@@ -5,9 +5,14 @@ use bevy::{
     ecs::{
         component::Component,
         reflect::ReflectComponent,
-        schedule::OnEnter,
+        entity::Entity,
+        query::{Or, With},
+        schedule::{OnEnter, OnExit},
+        schedule::State,
         system::{Commands, Query, Res},
     },
+    gltf::Gltf,
+    hierarchy::DespawnRecursiveExt,
     reflect::Reflect,
     scene::SceneBundle,
     utils::default,
@@ -17,9 +22,13 @@ use bevy_gltf_components::ComponentsFromGltfPlugin;
 
 use crate::{
     component::ComponentsTestPlugin,
-    core::{CoreGameState, GameLevel}, world::SpawnProperty,
+    core::{CoreGameState, CurrentLevel, GameLevel},
+    lobby::{LevelCode, LobbyState},
+    world::SpawnProperty,
 };
 
+use super::{apply_overlay, Affiliation};
+
 #[derive(Component, Reflect, Default, Debug)]
 #[reflect(Component)]
 pub struct LoadedMarker;
@@ -29,7 +38,8 @@ pub struct CustomPlugins;
 impl Plugin for CustomPlugins {
     fn build(&self, app: &mut App) {
         app.add_plugins(ComponentsFromGltfPlugin::default(),)
-            .add_systems(OnEnter(CoreGameState::InGame), spawn_level);
+            .add_systems(OnEnter(CoreGameState::InGame), spawn_level)
+            .add_systems(OnExit(CoreGameState::InGame), unload_level);
     }
 }
 
@@ -37,21 +47,46 @@ fn spawn_level(
     mut commands: Commands,
     scene_markers: Query<&LoadedMarker>,
     model_assets: Res<GameLevel>,
-    models: Res<Assets<bevy::gltf::Gltf>>,
+    models: Res<Assets<Gltf>>,
+    current_level: Res<CurrentLevel>,
+    lobby_state: Res<State<LobbyState>>,
 ) {
     commands.insert_resource(SpawnProperty::empty());
     let gltf = models.get(model_assets.level.clone()).unwrap();
     if scene_markers.is_empty() {
         log::info!("spawning scene");
-        commands.spawn((
-            SceneBundle {
-                scene: gltf.scenes[0].clone(),
-                ..default()
-            },
-            LoadedMarker,
-            Name::new("Level1"),
-        ));
+        // the host simulates the overlay props, clients only display them
+        let shell = *lobby_state.get() == LobbyState::Client;
+        spawn_level_scene(&mut commands, gltf, &current_level, shell);
     } else {
         log::error!("scene already exist");
     }
 }
+
+/// The scene of a loaded glTF level and its overlay, the spawn points come with them.
+pub fn spawn_level_scene(
+    commands: &mut Commands,
+    gltf: &Gltf,
+    level_code: &LevelCode,
+    shell: bool,
+) {
+    commands.spawn((
+        SceneBundle {
+            scene: gltf.scenes[0].clone(),
+            ..default()
+        },
+        LoadedMarker,
+        Name::new("Level1"),
+    ));
+    apply_overlay(commands, level_code, shell);
+}
+
+/// The scene and the overlay props of the level, the next level or the menu starts blank.
+fn unload_level(
+    mut commands: Commands,
+    level_query: Query<Entity, Or<(With<LoadedMarker>, With<Affiliation>)>>,
+) {
+    for entity in level_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,439 @@
+//! In-game level editor, places spawn points, kill volumes and props in a working copy
+//! of the loaded level content and exports it as a [`LevelOverlay`].
+//!
+//! Opened with the `edit` console command in a single player session. The view is moved with
+//! the free camera ([`CoreAction::FreeCamera`](crate::core::CoreAction::FreeCamera)), a left click
+//! places with the selected tool where the cursor points, or at the screen center while the mouse is grabbed.
+//! With the spawn point tool, pressing on a spawn point drags it along the ground instead.
+//! The spawn points are used by the level right away, without an export.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::plugin::RapierContext;
+use bevy_rapier3d::prelude::QueryFilter;
+
+use crate::actor::character::HALPH_PLAYER_SIZE;
+use crate::actor::PrefabId;
+use crate::console::{no_args, CommandResult, CommandScope, ConsoleAppExt};
+use crate::core::CurrentLevel;
+use crate::lobby::{LevelCode, LobbyState};
+use crate::ui::MouseGrabState;
+use crate::world::{MainCamera, SpawnProperty};
+
+use super::{overlay_path, KillVolume, KillVolumes, LevelOverlay, PropPlacement};
+
+/// Placements that can be undone
+const MAX_UNDO: usize = 32;
+/// Farthest a click places something at
+const PLACE_DISTANCE: f32 = 100.;
+const DEFAULT_PROP_HALF_SIZE: f32 = 0.25;
+const DEFAULT_KILL_VOLUME_HALF_SIZE: f32 = 1.;
+/// Radius of the spawn point markers
+const SPAWN_POINT_RADIUS: f32 = 0.3;
+/// Farthest from its marker a click still picks a spawn point up
+const PICK_DISTANCE: f32 = SPAWN_POINT_RADIUS * 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum EditorTool {
+    #[default]
+    SpawnPoint,
+    KillVolume,
+    Prop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    SpawnPoint(usize),
+    KillVolume(usize),
+    Prop(usize),
+}
+
+/// State of the level editor, the working copy is only written to disk on export.
+#[derive(Debug, Default, Resource)]
+pub struct LevelEditor {
+    pub active: bool,
+    tool: EditorTool,
+    overlay: LevelOverlay,
+    /// Tools of the last placements, newest last
+    placements: VecDeque<EditorTool>,
+    selected: Option<Selection>,
+    /// Spawn point moved while the left button is held
+    dragging: Option<usize>,
+    /// Result of the last export or import
+    status: Option<Result<String, String>>,
+}
+
+impl LevelEditor {
+    fn place(&mut self, tool: EditorTool, position: Vec3) {
+        let selection = match tool {
+            EditorTool::SpawnPoint => {
+                self.overlay.spawn_points.push(position + Vec3::Y * HALPH_PLAYER_SIZE);
+                Selection::SpawnPoint(self.overlay.spawn_points.len() - 1)
+            }
+            EditorTool::KillVolume => {
+                self.overlay.kill_volumes.push(KillVolume {
+                    center: position,
+                    half_size: Vec3::splat(DEFAULT_KILL_VOLUME_HALF_SIZE),
+                });
+                Selection::KillVolume(self.overlay.kill_volumes.len() - 1)
+            }
+            EditorTool::Prop => {
+                let id = self.free_prop_id();
+                self.overlay.props.push(PropPlacement {
+                    id,
+                    prefab: PrefabId::PROP,
+                    half_size: Vec3::splat(DEFAULT_PROP_HALF_SIZE),
+                    position: position + Vec3::Y * DEFAULT_PROP_HALF_SIZE,
+                    rotation: Quat::IDENTITY,
+                });
+                Selection::Prop(self.overlay.props.len() - 1)
+            }
+        };
+        self.selected = Some(selection);
+        self.placements.push_back(tool);
+        if self.placements.len() > MAX_UNDO {
+            self.placements.pop_front();
+        }
+    }
+
+    /// Removes the last placement, placements are always the last of their list.
+    fn undo(&mut self) {
+        let Some(tool) = self.placements.pop_back() else {
+            return;
+        };
+        match tool {
+            EditorTool::SpawnPoint => {
+                self.overlay.spawn_points.pop();
+            }
+            EditorTool::KillVolume => {
+                self.overlay.kill_volumes.pop();
+            }
+            EditorTool::Prop => {
+                self.overlay.props.pop();
+            }
+        }
+        self.selected = None;
+        self.dragging = None;
+    }
+
+    /// Spawn point whose marker is the closest to `ray`, if one is close enough.
+    fn spawn_point_at(&self, ray: Ray3d) -> Option<usize> {
+        self.overlay
+            .spawn_points
+            .iter()
+            .enumerate()
+            .filter_map(|(i, point)| {
+                let along = (*point - ray.origin).dot(*ray.direction);
+                let distance = (ray.origin + *ray.direction * along).distance(*point);
+                (along > 0. && distance <= PICK_DISTANCE).then_some((i, along))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
+
+    /// Lowest `prop_<n>` id not used yet, so props keep unique [`LinkId`](crate::world::LinkId)s.
+    fn free_prop_id(&self) -> String {
+        (0..)
+            .map(|n| format!("prop_{}", n))
+            .find(|id| self.overlay.props.iter().all(|prop| prop.id != *id))
+            .unwrap()
+    }
+
+    fn replace(&mut self, overlay: LevelOverlay) {
+        self.overlay = overlay;
+        self.placements.clear();
+        self.selected = None;
+        self.dragging = None;
+    }
+}
+
+pub struct LevelEditorPlugins;
+
+impl Plugin for LevelEditorPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelEditor>()
+            .add_systems(
+                Update,
+                (place, editor_window, sync_spawn_points, draw_overlay)
+                    .chain()
+                    .run_if(|editor: Res<LevelEditor>| editor.active),
+            )
+            .add_systems(OnExit(LobbyState::Single), close_editor)
+            .add_console_command("edit", "", CommandScope::Local, no_args, toggle_editor);
+    }
+}
+
+/// Opens the editor on the content of the loaded level, or closes it.
+fn toggle_editor(world: &mut World, _: ()) -> CommandResult {
+    if *world.resource::<State<LobbyState>>().get() != LobbyState::Single {
+        return Err("the editor is only available in single".to_string());
+    }
+    if world.resource::<LevelEditor>().active {
+        world.resource_mut::<LevelEditor>().active = false;
+        return Ok(Some("editor closed, unexported changes are kept until the session ends".into()));
+    }
+
+    // the working copy starts from what the level uses now
+    let overlay = LevelOverlay {
+        spawn_points: world.resource::<SpawnProperty>().points().to_vec(),
+        kill_volumes: world.resource::<KillVolumes>().0.clone(),
+        props: Vec::new(),
+    };
+    let mut editor = world.resource_mut::<LevelEditor>();
+    if editor.overlay == LevelOverlay::default() {
+        editor.replace(overlay);
+    }
+    editor.active = true;
+    Ok(Some("editor opened, fly with the free camera and click to place".into()))
+}
+
+fn close_editor(mut editor: ResMut<LevelEditor>) {
+    *editor = LevelEditor::default();
+}
+
+fn place(
+    mut context: EguiContexts,
+    mut editor: ResMut<LevelEditor>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mouse_grab_state: Res<State<MouseGrabState>>,
+    rapier_context: Res<RapierContext>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) {
+    if !mouse.pressed(MouseButton::Left) {
+        editor.dragging = None;
+    }
+    let pressed = mouse.just_pressed(MouseButton::Left);
+    if editor.dragging.is_none() && (!pressed || context.ctx_mut().wants_pointer_input()) {
+        return;
+    }
+    let (Ok(window), Ok((camera, camera_transform))) =
+        (window_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+
+    let cursor = match mouse_grab_state.get() {
+        MouseGrabState::Enable => Some(Vec2::new(window.width(), window.height()) / 2.),
+        MouseGrabState::Disable => window.cursor_position(),
+    };
+    let Some(ray) = cursor.and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+    else {
+        return;
+    };
+    let hit = rapier_context.cast_ray(
+        ray.origin,
+        *ray.direction,
+        PLACE_DISTANCE,
+        true,
+        QueryFilter::default().exclude_sensors(),
+    );
+    let hit = hit.map(|(_, toi)| ray.origin + *ray.direction * toi);
+
+    if let Some(i) = editor.dragging {
+        if let (Some(hit), Some(point)) = (hit, editor.overlay.spawn_points.get_mut(i)) {
+            *point = hit + Vec3::Y * HALPH_PLAYER_SIZE;
+        }
+        return;
+    }
+    let tool = editor.tool;
+    // pressing on a spawn point picks it up rather than placing another one over it
+    if let Some(i) = editor.spawn_point_at(ray).filter(|_| tool == EditorTool::SpawnPoint) {
+        editor.selected = Some(Selection::SpawnPoint(i));
+        editor.dragging = Some(i);
+        return;
+    }
+    if let Some(hit) = hit {
+        editor.place(tool, hit);
+    }
+}
+
+/// The level spawns at the edited points right away, a level left without any keeps its own.
+fn sync_spawn_points(editor: Res<LevelEditor>, mut spawn_property: ResMut<SpawnProperty>) {
+    let points = &editor.overlay.spawn_points;
+    if points.is_empty() || spawn_property.points() == points.as_slice() {
+        return;
+    }
+    *spawn_property = SpawnProperty::new(points.clone());
+}
+
+fn draw_overlay(editor: Res<LevelEditor>, mut gizmos: Gizmos) {
+    let color = |selection, color| {
+        if editor.selected == Some(selection) {
+            Color::YELLOW
+        } else {
+            color
+        }
+    };
+
+    for (i, point) in editor.overlay.spawn_points.iter().enumerate() {
+        gizmos.sphere(
+            *point,
+            Quat::IDENTITY,
+            SPAWN_POINT_RADIUS,
+            color(Selection::SpawnPoint(i), Color::GREEN),
+        );
+    }
+    for (i, volume) in editor.overlay.kill_volumes.iter().enumerate() {
+        gizmos.cuboid(
+            Transform::from_translation(volume.center).with_scale(volume.half_size * 2.),
+            color(Selection::KillVolume(i), Color::RED),
+        );
+    }
+    for (i, prop) in editor.overlay.props.iter().enumerate() {
+        gizmos.cuboid(
+            Transform::from_translation(prop.position)
+                .with_rotation(prop.rotation)
+                .with_scale(prop.half_size * 2.),
+            color(Selection::Prop(i), Color::ORANGE),
+        );
+    }
+}
+
+fn editor_window(
+    mut context: EguiContexts,
+    mut editor: ResMut<LevelEditor>,
+    current_level: Res<CurrentLevel>,
+) {
+    let path = match &**current_level {
+        LevelCode::Path(path) => Some(overlay_path(path)),
+        _ => None,
+    };
+    let mut open = true;
+
+    egui::Window::new("Level editor")
+        .open(&mut open)
+        .default_width(260.)
+        .show(context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut editor.tool, EditorTool::SpawnPoint, "Spawn point");
+                ui.selectable_value(&mut editor.tool, EditorTool::KillVolume, "Kill volume");
+                ui.selectable_value(&mut editor.tool, EditorTool::Prop, "Prop");
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(200.).show(ui, |ui| {
+                let LevelEditor {
+                    overlay, selected, ..
+                } = &mut *editor;
+                for i in 0..overlay.spawn_points.len() {
+                    let selection = Selection::SpawnPoint(i);
+                    ui.selectable_value(selected, Some(selection), format!("spawn point {}", i));
+                }
+                for i in 0..overlay.kill_volumes.len() {
+                    let selection = Selection::KillVolume(i);
+                    ui.selectable_value(selected, Some(selection), format!("kill volume {}", i));
+                }
+                for (i, prop) in overlay.props.iter().enumerate() {
+                    let label = format!("{} ({})", prop.id, prop.prefab);
+                    ui.selectable_value(selected, Some(Selection::Prop(i)), label);
+                }
+            });
+            ui.separator();
+
+            selected_properties(ui, &mut editor);
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                let undo = egui::Button::new("Undo");
+                if ui.add_enabled(!editor.placements.is_empty(), undo).clicked() {
+                    editor.undo();
+                }
+                if ui.add_enabled(path.is_some(), egui::Button::new("Export")).clicked() {
+                    if let Some(path) = path.as_ref() {
+                        if editor.overlay.spawn_points.is_empty() {
+                            log::warn!("Exporting {} without spawn points", path.display());
+                        }
+                        editor.status = Some(
+                            editor
+                                .overlay
+                                .save(path)
+                                .map(|_| format!("exported to {}", path.display()))
+                                .map_err(|err| err.to_string()),
+                        );
+                    }
+                }
+                if ui.add_enabled(path.is_some(), egui::Button::new("Import")).clicked() {
+                    if let Some(path) = path.as_ref() {
+                        editor.status = Some(match LevelOverlay::load(path) {
+                            Ok(overlay) => {
+                                editor.replace(overlay);
+                                Ok(format!("imported {}", path.display()))
+                            }
+                            Err(err) => Err(err.to_string()),
+                        });
+                    }
+                }
+            });
+
+            if path.is_none() {
+                ui.colored_label(egui::Color32::RED, "Only file levels can be exported");
+            }
+            if editor.overlay.spawn_points.is_empty() {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    "No spawn points: characters cannot spawn in this level",
+                );
+            }
+            match &editor.status {
+                Some(Ok(status)) => {
+                    ui.label(status.as_str());
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::RED, err.as_str());
+                }
+                None => {}
+            }
+        });
+
+    if !open {
+        editor.active = false;
+    }
+}
+
+/// Position, size and rotation fields of the selected placement.
+fn selected_properties(ui: &mut egui::Ui, editor: &mut LevelEditor) {
+    let overlay = &mut editor.overlay;
+    match editor.selected {
+        Some(Selection::SpawnPoint(i)) => {
+            if let Some(point) = overlay.spawn_points.get_mut(i) {
+                vec3_field(ui, "position", point);
+            }
+        }
+        Some(Selection::KillVolume(i)) => {
+            if let Some(volume) = overlay.kill_volumes.get_mut(i) {
+                vec3_field(ui, "center", &mut volume.center);
+                vec3_field(ui, "half size", &mut volume.half_size);
+                volume.half_size = volume.half_size.max(Vec3::splat(0.01));
+            }
+        }
+        Some(Selection::Prop(i)) => {
+            if let Some(prop) = overlay.props.get_mut(i) {
+                vec3_field(ui, "position", &mut prop.position);
+                vec3_field(ui, "half size", &mut prop.half_size);
+                prop.half_size = prop.half_size.max(Vec3::splat(0.01));
+
+                let (yaw, pitch, roll) = prop.rotation.to_euler(EulerRot::YXZ);
+                let mut angles = Vec3::new(yaw, pitch, roll) * 180. / std::f32::consts::PI;
+                vec3_field(ui, "yaw pitch roll", &mut angles);
+                let angles = angles * std::f32::consts::PI / 180.;
+                prop.rotation = Quat::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z);
+            }
+        }
+        None => {
+            ui.label("click a placement to edit it");
+        }
+    }
+}
+
+fn vec3_field(ui: &mut egui::Ui, label: &str, value: &mut Vec3) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(&mut value.x).speed(0.05));
+        ui.add(egui::DragValue::new(&mut value.y).speed(0.05));
+        ui.add(egui::DragValue::new(&mut value.z).speed(0.05));
+    });
+}
@@ -0,0 +1,240 @@
+//! Reloads the level of a single player session when its files change on disk, so a level
+//! is iterated on without restarting, dev builds only.
+//!
+//! The glTF and the overlay of a [`LevelCode::Path`] level are polled, a burst of writes is
+//! reloaded once the files stay unchanged for [`DEBOUNCE`]. A changed overlay respawns the
+//! actors and the overlay props in place. A changed glTF goes through the load path again:
+//! the actors are unloaded, the scene is respawned and [`MapLoaderState::No`] lets the lobby
+//! move the own character to the new spawn points with its [`Respawn`].
+
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use bevy::asset::LoadState;
+use bevy::gltf::Gltf;
+use bevy::prelude::*;
+
+use crate::actor::UnloadActorsEvent;
+use crate::component::Respawn;
+use crate::core::{CoreGameState, CurrentLevel, GameLevel};
+use crate::lobby::{LevelCode, LobbyState, MapLoaderState};
+use crate::world::{Me, SpawnProperty};
+
+use super::custom::{spawn_level_scene, LoadedMarker};
+use super::{apply_overlay, level_path, overlay_path, Affiliation};
+
+/// Seconds between two looks at the level files
+const POLL_INTERVAL: f32 = 0.25;
+/// Seconds the files must stay unchanged before they are reloaded, an export writes in bursts
+const DEBOUNCE: f64 = 0.5;
+
+/// Modification times of the level files, `None` for a missing file.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct LevelFiles {
+    scene: Option<SystemTime>,
+    overlay: Option<SystemTime>,
+}
+
+impl LevelFiles {
+    fn read(path: &str) -> Self {
+        let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        Self {
+            scene: modified(&level_path(path)),
+            overlay: modified(&overlay_path(path)),
+        }
+    }
+}
+
+/// Watch of the files of the loaded level.
+#[derive(Debug, Resource)]
+struct LevelWatcher {
+    level: Option<LevelCode>,
+    files: LevelFiles,
+    poll: Timer,
+    /// When the last change not reloaded yet was seen, in [`Time::elapsed_seconds_f64`]
+    changed_at: Option<f64>,
+    /// The glTF is among the pending changes
+    scene_changed: bool,
+    /// The glTF is being reloaded by the asset server, the level follows once it is
+    awaiting_scene: bool,
+}
+
+impl Default for LevelWatcher {
+    fn default() -> Self {
+        Self {
+            level: None,
+            files: LevelFiles::default(),
+            poll: Timer::from_seconds(POLL_INTERVAL, TimerMode::Repeating),
+            changed_at: None,
+            scene_changed: false,
+            awaiting_scene: false,
+        }
+    }
+}
+
+/// Respawns the level, with its scene or only with its overlay.
+#[derive(Debug, Clone, Copy, Event)]
+struct ReloadLevelEvent {
+    scene: bool,
+}
+
+pub struct LevelHotReloadPlugins;
+
+impl Plugin for LevelHotReloadPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelWatcher>()
+            .add_event::<ReloadLevelEvent>()
+            .add_systems(
+                Update,
+                (watch_level_files, finish_scene_reload, reload_level)
+                    .chain()
+                    .run_if(
+                        in_state(CoreGameState::InGame)
+                            .and_then(in_state(LobbyState::Single))
+                            .and_then(resource_exists::<GameLevel>),
+                    ),
+            )
+            .add_systems(
+                Update,
+                follow_spawn_points.run_if(
+                    in_state(LobbyState::Single)
+                        .and_then(in_state(MapLoaderState::Yes))
+                        .and_then(resource_changed::<SpawnProperty>),
+                ),
+            )
+            .add_systems(OnExit(CoreGameState::InGame), forget_level);
+    }
+}
+
+fn watch_level_files(
+    time: Res<Time>,
+    current_level: Res<CurrentLevel>,
+    asset_server: Res<AssetServer>,
+    game_level: Res<GameLevel>,
+    mut watcher: ResMut<LevelWatcher>,
+    mut reload_event: EventWriter<ReloadLevelEvent>,
+) {
+    let LevelCode::Path(path) = &**current_level else {
+        watcher.level = None;
+        return;
+    };
+    // the files as they were loaded are the reference, the first look reloads nothing
+    if watcher.level.as_ref() != Some(&current_level.0) {
+        *watcher = LevelWatcher {
+            level: Some(current_level.0.clone()),
+            files: LevelFiles::read(path),
+            ..default()
+        };
+        return;
+    }
+    if watcher.awaiting_scene || !watcher.poll.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let now = time.elapsed_seconds_f64();
+    let files = LevelFiles::read(path);
+    if files != watcher.files {
+        watcher.scene_changed |= files.scene != watcher.files.scene;
+        watcher.files = files;
+        watcher.changed_at = Some(now);
+        return;
+    }
+    if !watcher.changed_at.is_some_and(|changed_at| now - changed_at >= DEBOUNCE) {
+        return;
+    }
+
+    watcher.changed_at = None;
+    if !std::mem::take(&mut watcher.scene_changed) {
+        log::info!("Level overlay of {} changed, reloading it", path);
+        reload_event.send(ReloadLevelEvent { scene: false });
+        return;
+    }
+    let Some(asset_path) = asset_server.get_path(game_level.level.id()) else {
+        log::warn!("Level {} has no asset path, not reloaded", path);
+        return;
+    };
+    log::info!("Level {} changed, reloading {}", path, asset_path);
+    asset_server.reload(asset_path.into_owned());
+    watcher.awaiting_scene = true;
+}
+
+/// Reloads the level once the asset server replaced its glTF.
+fn finish_scene_reload(
+    asset_server: Res<AssetServer>,
+    game_level: Res<GameLevel>,
+    mut gltf_events: EventReader<AssetEvent<Gltf>>,
+    mut watcher: ResMut<LevelWatcher>,
+    mut reload_event: EventWriter<ReloadLevelEvent>,
+) {
+    let id = game_level.level.id();
+    let reloaded = gltf_events
+        .read()
+        .any(|event| event.is_modified(id) || event.is_loaded_with_dependencies(id));
+    if !watcher.awaiting_scene {
+        return;
+    }
+    if reloaded {
+        watcher.awaiting_scene = false;
+        reload_event.send(ReloadLevelEvent { scene: true });
+    } else if asset_server.load_state(id) == LoadState::Failed {
+        // the level stays as it was, a later write is tried again
+        log::error!("Failed to reload the level, see the asset server error");
+        watcher.awaiting_scene = false;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reload_level(
+    mut commands: Commands,
+    mut reload_event: EventReader<ReloadLevelEvent>,
+    current_level: Res<CurrentLevel>,
+    game_level: Res<GameLevel>,
+    models: Res<Assets<Gltf>>,
+    level_query: Query<(Entity, Has<LoadedMarker>), Or<(With<LoadedMarker>, With<Affiliation>)>>,
+    mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
+) {
+    // a glTF reload covers the overlay too
+    let Some(scene) = reload_event.read().map(|event| event.scene).reduce(|a, b| a || b) else {
+        return;
+    };
+    unload_actors_event.send(UnloadActorsEvent);
+
+    if !scene {
+        // the scene stays, so do the spawn points unless the overlay replaces them
+        for (entity, _) in level_query.iter().filter(|(_, scene)| !scene) {
+            commands.entity(entity).despawn_recursive();
+        }
+        apply_overlay(&mut commands, &current_level, false);
+        return;
+    }
+    let Some(gltf) = models.get(game_level.level.id()) else {
+        log::error!("Reloaded level is not loaded, nothing respawned");
+        return;
+    };
+    for (entity, _) in level_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // filled again by the spawn points of the scene and the overlay
+    commands.insert_resource(SpawnProperty::empty());
+    spawn_level_scene(&mut commands, gltf, &current_level, false);
+    next_state_map.set(MapLoaderState::No);
+}
+
+/// The own character respawns at the spawn points in use, without being moved now.
+fn follow_spawn_points(
+    spawn_property: Res<SpawnProperty>,
+    mut respawn_query: Query<&mut Respawn, With<Me>>,
+) {
+    if spawn_property.is_empty() {
+        return;
+    }
+    for mut respawn in respawn_query.iter_mut() {
+        respawn.replace_spawn_point(spawn_property.clone());
+    }
+}
+
+fn forget_level(mut watcher: ResMut<LevelWatcher>) {
+    *watcher = LevelWatcher::default();
+}
@@ -1,11 +1,20 @@
-use crate::{core::{CoreGameState, KnownLevel}, ui::MainCamera, lobby::LevelCode};
+use crate::{actor::spawn_prop, core::{CoreGameState, KnownLevel}, ui::MainCamera, lobby::{LevelCode, LobbyState}};
+use crate::physics::groups::level_groups;
+use crate::world::{DayCycle, EnvironmentSettings, Lighting, LinkId};
 
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::Collider;
 use std::f32::consts::PI;
 
 use super::Affiliation;
 
 const PRIMARY_CAMERA_ORDER: isize = 3;
+/// Rows of the crate pyramid, the bottom row has this many crates
+const CRATE_ROWS: usize = 4;
+const CRATE_HALF_SIZE: f32 = 0.25;
+
+/// Seconds of a hub day
+const DAY_PERIOD: f32 = 240.;
 
 #[derive(Component)]
 struct OrbitLight {
@@ -27,24 +36,56 @@ impl Plugin for HubPlugins {
     }
 }
 
+/// A day going round from dawn, see [`level_environment`](super::level_environment).
+pub fn hub_environment() -> EnvironmentSettings {
+    let lighting = |sun: Color, illuminance: f32, ambient: f32, sky: Color| Lighting {
+        sun_color: sun,
+        sun_illuminance: illuminance,
+        ambient_color: Color::WHITE,
+        ambient_brightness: ambient,
+        clear_color: sky,
+    };
+    let noon = lighting(Color::WHITE, 8000., 300., Color::rgb(0.5, 0.7, 0.95));
+    EnvironmentSettings {
+        // rises in the east at phase 0 and is highest at 0.25
+        sun_direction: Some(Vec3::new(0., 0., 1.)),
+        lighting: noon,
+        fog: None,
+        day_cycle: Some(DayCycle {
+            period: DAY_PERIOD,
+            start: 0.25,
+            keyframes: vec![
+                (0., lighting(Color::rgb(1., 0.6, 0.4), 2000., 150., Color::rgb(0.9, 0.6, 0.5))),
+                (0.25, noon),
+                (0.5, lighting(Color::rgb(1., 0.5, 0.3), 2000., 150., Color::rgb(0.8, 0.4, 0.3))),
+                (0.75, lighting(Color::rgb(0.4, 0.5, 0.8), 0., 50., Color::rgb(0.02, 0.02, 0.08))),
+            ],
+        }),
+    }
+}
+
 fn load(
     mut commands: Commands,
     mut mesh: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    lobby_state: Res<State<LobbyState>>,
 ) {
-    commands
-        .spawn((
-            Camera3dBundle {
-                transform: Transform::from_xyz(5., 2.5, 5.).looking_at(Vec3::ZERO, Vec3::Y),
-                camera: Camera {
-                    order: PRIMARY_CAMERA_ORDER,
-                    ..default()
+    // the menu brings its own camera going round the hub
+    if *lobby_state.get() != LobbyState::None {
+        commands
+            .spawn((
+                Camera3dBundle {
+                    transform: Transform::from_xyz(5., 2.5, 5.).looking_at(Vec3::ZERO, Vec3::Y),
+                    camera: Camera {
+                        order: PRIMARY_CAMERA_ORDER,
+                        ..default()
+                    },
+                    ..Default::default()
                 },
-                ..Default::default()
-            },
-            MainCamera,
-        ))
-        .insert(Affiliation(LevelCode::Known(KnownLevel::Hub)));
+                MainCamera,
+            ))
+            .insert(Affiliation(LevelCode::Known(KnownLevel::Hub)));
+    }
 
     commands
         .spawn((
@@ -68,12 +109,14 @@ fn load(
     commands
         .spawn((
             PbrBundle {
-                mesh: mesh.add(Mesh::from(Plane3d::new(Vec3::Y))),
+                mesh: mesh.add(Plane3d::new(Vec3::Y).mesh().size(20., 20.)),
                 material: materials.add(Color::GREEN),
                 transform: Transform::from_xyz(0., 0., 0.),
                 ..Default::default()
             },
             Name::new("Terrain"),
+            Collider::cuboid(10., 0.01, 10.),
+            level_groups(),
         ))
         .insert(Affiliation(LevelCode::Known(KnownLevel::Hub)));
 
@@ -87,8 +130,33 @@ fn load(
                 ..Default::default()
             },
             Name::new("Cube"),
+            Collider::cuboid(0.25, 0.25, 0.25),
+            level_groups(),
         ))
         .insert(Affiliation(LevelCode::Known(KnownLevel::Hub)));
+
+    // the host simulates the crates, clients only display them
+    let shell = *lobby_state.get() == LobbyState::Client;
+    let mut index = 0;
+    for row in 0..CRATE_ROWS {
+        let crates = CRATE_ROWS - row;
+        for column in 0..crates {
+            let position = Vec3::new(
+                3. + (column as f32 - (crates - 1) as f32 / 2.) * CRATE_HALF_SIZE * 2.,
+                CRATE_HALF_SIZE + row as f32 * CRATE_HALF_SIZE * 2.,
+                0.,
+            );
+            commands
+                .spawn_prop(
+                    LinkId::Scene(format!("hub_crate_{}", index)),
+                    Vec3::splat(CRATE_HALF_SIZE),
+                    position,
+                    shell,
+                )
+                .insert(Affiliation(LevelCode::Known(KnownLevel::Hub)));
+            index += 1;
+        }
+    }
 }
 
 fn unload(mut commands: Commands, affiliation_query: Query<Entity, With<Affiliation>>) {
@@ -1,8 +1,18 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 use bevy::prelude::*;
+use sha2::{Digest, Sha256};
 
-use crate::{world::SpawnProperty, lobby::LevelCode};
+use crate::{
+    actor::character::MovementTuning,
+    core::KnownLevel,
+    lobby::LevelCode,
+    world::{EnvironmentSettings, LevelPhysics, SpawnProperty},
+    ASSET_DIR,
+};
 
-use super::{hub::HubPlugins, custom::CustomPlugins};
+use super::{hub::{hub_environment, HubPlugins}, custom::CustomPlugins, OverlayPlugins};
 
 #[derive(Component)]
 pub struct Affiliation(pub LevelCode);
@@ -11,6 +21,111 @@ pub struct MapPlugins;
 
 impl Plugin for MapPlugins {
     fn build(&self, app: &mut App) {
-        app.init_resource::<SpawnProperty>().add_plugins((HubPlugins, CustomPlugins));
+        app.init_resource::<SpawnProperty>()
+            .add_plugins((HubPlugins, CustomPlugins, OverlayPlugins));
+
+        #[cfg(feature = "dev")]
+        app.add_plugins((
+            super::editor::LevelEditorPlugins,
+            super::hot_reload::LevelHotReloadPlugins,
+        ));
+    }
+}
+
+/// Path of the glTF file of a [`LevelCode::Path`] level.
+pub fn level_path(path: &str) -> PathBuf {
+    Path::new(ASSET_DIR)
+        .join("level")
+        .join(format!("{path}.glb"))
+}
+
+/// Whether `level_code` can be loaded here, a file level may have been removed since it was saved.
+pub fn level_exists(level_code: &LevelCode) -> bool {
+    match level_code {
+        LevelCode::Known(_) => true,
+        LevelCode::Path(path) => level_path(path).exists(),
+        // levels are not downloaded from urls yet
+        LevelCode::Url(_) => false,
+    }
+}
+
+/// Checksum of a level geometry, used to detect host and client having different versions of a level.
+///
+/// File levels hash the file content, built-in levels hash their name since their geometry
+/// lives in code. Returns `None` if the level is not available locally.
+pub fn level_checksum(level_code: &LevelCode) -> Option<u64> {
+    let mut hasher = Sha256::new();
+    match level_code {
+        LevelCode::Path(path) => hasher.update(fs::read(level_path(path)).ok()?),
+        LevelCode::Url(_) => return None,
+        LevelCode::Known(known_level) => {
+            let name = match known_level {
+                KnownLevel::Hub => "hub",
+            };
+            hasher.update(name.as_bytes());
+        }
+    }
+
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Physics of a level, `default` unless the level overrides it,
+/// see [`CoreConfig::default_physics`](crate::core::CoreConfig::default_physics).
+pub fn level_physics(level_code: &LevelCode, default: LevelPhysics) -> LevelPhysics {
+    match level_code {
+        LevelCode::Known(KnownLevel::Hub) => default,
+        LevelCode::Path(_) | LevelCode::Url(_) => default,
+    }
+}
+
+/// Character movement of a level, [`MovementTuning::default`] unless the level overrides it.
+pub fn level_movement(level_code: &LevelCode) -> MovementTuning {
+    match level_code {
+        LevelCode::Known(KnownLevel::Hub) => MovementTuning::default(),
+        LevelCode::Path(_) | LevelCode::Url(_) => MovementTuning::default(),
+    }
+}
+
+/// Lights, sky and fog of a level, [`EnvironmentSettings::default`] unless the level has its own.
+pub fn level_environment(level_code: &LevelCode) -> EnvironmentSettings {
+    match level_code {
+        LevelCode::Known(KnownLevel::Hub) => hub_environment(),
+        LevelCode::Path(_) | LevelCode::Url(_) => EnvironmentSettings::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_is_stable() {
+        let hub = LevelCode::Known(KnownLevel::Hub);
+        // first 8 bytes of the sha256 of "hub", a change here breaks every older peer
+        assert_eq!(level_checksum(&hub), Some(13966832247592309512));
+        assert_eq!(level_checksum(&hub), level_checksum(&hub));
+
+        let level = LevelCode::Path("Level1".to_string());
+        assert!(level_checksum(&level).is_some());
+        assert_eq!(level_checksum(&level), level_checksum(&level));
+    }
+
+    #[test]
+    fn checksum_differs_between_levels() {
+        let hub = level_checksum(&LevelCode::Known(KnownLevel::Hub));
+        let level1 = level_checksum(&LevelCode::Path("Level1".to_string()));
+        let level2 = level_checksum(&LevelCode::Path("Level2".to_string()));
+        assert_ne!(hub, level1);
+        assert_ne!(level1, level2);
+    }
+
+    #[test]
+    fn missing_levels_have_no_checksum() {
+        assert_eq!(level_checksum(&LevelCode::Path("missing".to_string())), None);
+        let url = LevelCode::Url("https://example.com/level.glb".to_string());
+        assert_eq!(level_checksum(&url), None);
     }
 }
@@ -1,7 +1,13 @@
 #![allow(clippy::module_inception)]
 
 mod custom;
+#[cfg(feature = "dev")]
+mod editor;
+#[cfg(feature = "dev")]
+mod hot_reload;
 mod hub;
 mod level;
+mod overlay;
 
 pub use level::*;
+pub use overlay::*;
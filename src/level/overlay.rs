@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::{spawn_prefab, PrefabArgs, PrefabId};
+use crate::component::{DespawnReason, Respawn};
+use crate::core::CoreGameState;
+use crate::lobby::{LevelCode, LobbyState};
+use crate::world::{LinkId, SpawnProperty};
+use crate::ASSET_DIR;
+
+use super::Affiliation;
+
+/// A box that kills the characters entering it, axis aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KillVolume {
+    pub center: Vec3,
+    pub half_size: Vec3,
+}
+
+impl KillVolume {
+    pub fn contains(&self, point: Vec3) -> bool {
+        (point - self.center).abs().cmple(self.half_size).all()
+    }
+}
+
+/// An actor placed in the level, simulated by the host like every prop.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PropPlacement {
+    /// Unique in the overlay, the prop is synced as `LinkId::Scene("overlay_<id>")`
+    pub id: String,
+    /// What is placed, overlays of older builds only place [`PrefabId::PROP`]
+    #[serde(default = "prop_prefab")]
+    pub prefab: PrefabId,
+    pub half_size: Vec3,
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+impl PropPlacement {
+    pub fn link_id(&self) -> LinkId {
+        LinkId::Scene(format!("overlay_{}", self.id))
+    }
+}
+
+fn prop_prefab() -> PrefabId {
+    PrefabId::PROP
+}
+
+/// Content placed on top of a level scene, authored in the level editor.
+///
+/// Stored as RON next to the glTF of a [`LevelCode::Path`] level, see [`overlay_path`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelOverlay {
+    /// Replace the spawn points of the scene if not empty
+    pub spawn_points: Vec<Vec3>,
+    pub kill_volumes: Vec<KillVolume>,
+    pub props: Vec<PropPlacement>,
+}
+
+impl LevelOverlay {
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn from_ron(content: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(content)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::from_ron(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Path of the overlay of a [`LevelCode::Path`] level.
+pub fn overlay_path(path: &str) -> PathBuf {
+    Path::new(ASSET_DIR)
+        .join("level")
+        .join(format!("{path}.overlay.ron"))
+}
+
+/// Kill volumes of the loaded level.
+#[derive(Debug, Default, Resource, Deref)]
+pub struct KillVolumes(pub Vec<KillVolume>);
+
+pub struct OverlayPlugins;
+
+impl Plugin for OverlayPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillVolumes>()
+            .add_systems(
+                FixedUpdate,
+                kill_in_volumes.run_if(
+                    not(in_state(LobbyState::None)).and_then(not(in_state(LobbyState::Client))),
+                ),
+            )
+            // a level without overlay must not keep the volumes of the previous one
+            .add_systems(OnExit(CoreGameState::InGame), clear_kill_volumes);
+    }
+}
+
+/// Spawns the overlay of `level_code` if it has one, a missing overlay is not an error.
+pub fn apply_overlay(commands: &mut Commands, level_code: &LevelCode, shell: bool) {
+    let LevelCode::Path(path) = level_code else {
+        return;
+    };
+    let path = overlay_path(path);
+    if !path.exists() {
+        return;
+    }
+    let overlay = match LevelOverlay::load(&path) {
+        Ok(overlay) => overlay,
+        Err(err) => {
+            log::error!("Failed to load level overlay {}: {}", path.display(), err);
+            return;
+        }
+    };
+    log::info!("Applying level overlay {}", path.display());
+
+    if !overlay.spawn_points.is_empty() {
+        commands.insert_resource(SpawnProperty::new(overlay.spawn_points.clone()));
+    }
+    commands.insert_resource(KillVolumes(overlay.kill_volumes.clone()));
+    for prop in overlay.props.iter() {
+        let args = PrefabArgs {
+            transform: Transform::from_translation(prop.position).with_rotation(prop.rotation),
+            half_size: prop.half_size,
+            ..PrefabArgs::new(prop.link_id())
+        };
+        commands
+            .spawn_prefab(prop.prefab.clone(), args, shell)
+            .insert(Affiliation(level_code.clone()));
+    }
+}
+
+fn kill_in_volumes(
+    kill_volumes: Res<KillVolumes>,
+    mut query: Query<(&GlobalTransform, &mut Respawn)>,
+) {
+    if kill_volumes.is_empty() {
+        return;
+    }
+    for (transform, mut respawn) in query.iter_mut() {
+        if respawn.is_pending() {
+            continue;
+        }
+        let position = transform.translation();
+        if kill_volumes.iter().any(|volume| volume.contains(position)) {
+            respawn.insert_reason(DespawnReason::Void);
+        }
+    }
+}
+
+fn clear_kill_volumes(mut kill_volumes: ResMut<KillVolumes>) {
+    kill_volumes.0.clear();
+}
@@ -5,18 +5,38 @@ mod component;
 mod controls;
 mod level;
 mod lobby;
+mod network;
+mod physics;
 mod settings;
 mod sound;
+mod stats;
 mod ui;
 mod util;
 mod world;
 
+#[cfg(feature = "dev")]
+pub mod console;
+#[cfg(feature = "dev")]
+pub mod log_console;
 #[cfg(all(debug_assertions, feature = "dev"))]
 pub mod editor;
 pub mod core;
+pub mod crash_report;
+pub mod log_file;
+pub mod log_filter;
+pub mod match_results;
+pub mod replay;
+pub mod save;
+#[cfg(feature = "test-utils")]
+pub mod test_harness;
+pub mod window_icon;
 
 pub const ASSET_DIR: &str = "asset";
 
+// taken by `core::CorePlugins`, their modules stay private
+pub use lobby::{LevelCode, LobbyState};
+pub use world::{LevelPhysics, SimulationConfig};
+
 #[cfg(feature = "dev")]
 lazy_static::lazy_static! {
     /// If the application is running in debug mode
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use renet::{ClientId, RenetServer};
+
+use crate::core::{KnownLevel, LoadLevelEvent};
+use crate::level::level_path;
+
+use super::bots::MAX_BOTS;
+use super::host::ServerSettings;
+use super::outbox::ServerOutbox;
+use super::ready::MatchSetup;
+use super::quick_chat::ChatFeed;
+use super::vote_kick::VoteCommand;
+use super::{ChangeMapLobbyEvent, LevelCode, Lobby, LobbyState, PlayerId, ServerMessages};
+
+const USAGE: &str = "commands: /login <password>, /kick <name>, /ban <name>, \
+                     /map <hub|level file>, /ff <on|off>, /bots <count>, \
+                     /match <off|hub|level file> [min players], \
+                     /votekick <name>, /vote <yes|no>";
+
+/// A chat message starting with `/`, run by the host instead of being broadcast.
+#[derive(Debug, Clone, Event)]
+pub struct AdminCommandRequest {
+    pub from: PlayerId,
+    pub text: String,
+}
+
+/// Disconnects a client, e.g. on an admin command.
+#[derive(Debug, Clone, Event)]
+pub struct KickPlayerEvent {
+    pub client_id: ClientId,
+    pub reason: String,
+}
+
+/// Usernames refused by the host until it closes the lobby.
+#[derive(Debug, Default, Resource)]
+pub struct BanList(pub HashSet<String>);
+
+/// Clients that logged in with [`ServerSettings::admin_password`], the host is always an admin.
+#[derive(Debug, Default, Resource)]
+pub struct Admins(HashSet<ClientId>);
+
+impl Admins {
+    /// Whether a client admin is still in the lobby, the host does not count
+    pub fn any_connected(&self, lobby: &Lobby) -> bool {
+        lobby.players.keys().any(|id| match id {
+            PlayerId::Client(client_id) => self.0.contains(client_id),
+            PlayerId::HostOrSingle | PlayerId::Bot(_) => false,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    Login(String),
+    Kick(String),
+    Ban(String),
+    Map(LevelCode),
+    FriendlyFire(bool),
+    /// Number of bots, see [`ServerSettings::bots`]
+    Bots(usize),
+    /// See [`ServerSettings::match_setup`]
+    Match(Option<MatchSetup>),
+}
+
+impl AdminCommand {
+    /// Parses a chat message, `None` if it is not an admin command.
+    pub fn parse(text: &str) -> Option<Result<AdminCommand, String>> {
+        // everybody can vote, see `VoteKickPlugins`
+        if VoteCommand::parse(text).is_some() {
+            return None;
+        }
+        let text = text.strip_prefix('/')?;
+        let (name, argument) = text.split_once(' ').unwrap_or((text, ""));
+        let argument = argument.trim();
+        let command = match (name, argument) {
+            (_, "") => Err(USAGE.to_string()),
+            ("login", password) => Ok(AdminCommand::Login(password.to_string())),
+            ("kick", username) => Ok(AdminCommand::Kick(username.to_string())),
+            ("ban", username) => Ok(AdminCommand::Ban(username.to_string())),
+            ("map", level) => parse_level(level).map(AdminCommand::Map),
+            ("ff", "on") => Ok(AdminCommand::FriendlyFire(true)),
+            ("ff", "off") => Ok(AdminCommand::FriendlyFire(false)),
+            ("bots", count) => match count.parse() {
+                Ok(count) if count <= MAX_BOTS => Ok(AdminCommand::Bots(count)),
+                _ => Err(format!("bots: 0 to {}", MAX_BOTS)),
+            },
+            ("match", "off") => Ok(AdminCommand::Match(None)),
+            ("match", setup) => {
+                parse_match_setup(setup).map(|setup| AdminCommand::Match(Some(setup)))
+            }
+            _ => Err(USAGE.to_string()),
+        };
+        Some(command)
+    }
+}
+
+fn parse_level(name: &str) -> Result<LevelCode, String> {
+    if name.eq_ignore_ascii_case("hub") {
+        return Ok(LevelCode::Known(KnownLevel::Hub));
+    }
+    if !level_path(name).exists() {
+        return Err(format!("no level file {:?}", name));
+    }
+    Ok(LevelCode::Path(name.to_string()))
+}
+
+/// `<level> [min players]`, a single player is enough by default.
+fn parse_match_setup(argument: &str) -> Result<MatchSetup, String> {
+    let (level, min_players) = argument.split_once(' ').unwrap_or((argument, "1"));
+    let min_players = match min_players.trim().parse() {
+        Ok(min_players) if min_players > 0 => min_players,
+        _ => return Err("match: min players is a positive number".to_string()),
+    };
+    Ok(MatchSetup {
+        level: parse_level(level)?,
+        min_players,
+    })
+}
+
+pub struct AdminPlugins;
+
+impl Plugin for AdminPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AdminCommandRequest>()
+            .add_event::<KickPlayerEvent>()
+            .init_resource::<BanList>()
+            .init_resource::<Admins>()
+            .add_systems(
+                Update,
+                (run_admin_commands, kick_players)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(OnExit(LobbyState::Host), clear_admins);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_admin_commands(
+    mut requests: EventReader<AdminCommandRequest>,
+    mut settings: ResMut<ServerSettings>,
+    mut admins: ResMut<Admins>,
+    mut ban_list: ResMut<BanList>,
+    lobby: Res<Lobby>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut chat_feed: ResMut<ChatFeed>,
+    mut kick_event: EventWriter<KickPlayerEvent>,
+    mut change_map_event: EventWriter<ChangeMapLobbyEvent>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+) {
+    for AdminCommandRequest { from, text } in requests.read() {
+        let Some(command) = AdminCommand::parse(text) else {
+            continue;
+        };
+        let is_admin = match from {
+            PlayerId::HostOrSingle => true,
+            PlayerId::Client(client_id) => admins.0.contains(client_id),
+            PlayerId::Bot(_) => false,
+        };
+
+        let reply = match command {
+            Err(usage) => usage,
+            Ok(AdminCommand::Login(password)) => {
+                let accepted = settings.admin_password.as_ref() == Some(&password);
+                match from {
+                    PlayerId::Client(client_id) if accepted => {
+                        admins.0.insert(*client_id);
+                        log::info!("{:?} logged in as admin", from);
+                        "logged in as admin".to_string()
+                    }
+                    _ => "permission denied".to_string(),
+                }
+            }
+            Ok(_) if !is_admin => "permission denied".to_string(),
+            Ok(AdminCommand::Kick(username)) => match find_client(&lobby, &username) {
+                Some(client_id) => {
+                    kick_event.send(KickPlayerEvent {
+                        client_id,
+                        reason: format!("kicked by {:?}", from),
+                    });
+                    format!("kicked {}", username)
+                }
+                None => format!("no player {}", username),
+            },
+            Ok(AdminCommand::Ban(username)) => {
+                // refused on the next connection even if not connected now
+                ban_list.0.insert(username.clone());
+                if let Some(client_id) = find_client(&lobby, &username) {
+                    kick_event.send(KickPlayerEvent {
+                        client_id,
+                        reason: format!("banned by {:?}", from),
+                    });
+                }
+                format!("banned {}", username)
+            }
+            Ok(AdminCommand::Map(level)) => {
+                change_map_event.send(ChangeMapLobbyEvent(level.clone()));
+                load_level_event.send(LoadLevelEvent::new(level));
+                "changing the map".to_string()
+            }
+            Ok(AdminCommand::FriendlyFire(friendly_fire)) => match settings.teams.as_mut() {
+                Some(rules) => {
+                    rules.friendly_fire = friendly_fire;
+                    format!("friendly fire {}", if friendly_fire { "on" } else { "off" })
+                }
+                None => "no team mode".to_string(),
+            },
+            Ok(AdminCommand::Bots(count)) => {
+                settings.bots = count;
+                format!("{} bots", count)
+            }
+            Ok(AdminCommand::Match(setup)) => {
+                let reply = match &setup {
+                    Some(setup) => format!(
+                        "match on {:?} once {} players are ready",
+                        setup.level, setup.min_players
+                    ),
+                    None => "no match, free play".to_string(),
+                };
+                settings.match_setup = setup;
+                reply
+            }
+        };
+
+        match from {
+            PlayerId::HostOrSingle => chat_feed.push("Host".to_string(), reply),
+            PlayerId::Client(client_id) => outbox.queue_for(
+                *client_id,
+                ServerMessages::Chat {
+                    from: PlayerId::HostOrSingle,
+                    text: reply,
+                },
+            ),
+            PlayerId::Bot(_) => {}
+        }
+    }
+}
+
+/// Client of the player named `username`, the host cannot be kicked.
+pub fn find_client(lobby: &Lobby, username: &str) -> Option<ClientId> {
+    lobby.players.iter().find_map(|(id, player_data)| match id {
+        PlayerId::Client(client_id) if player_data.username == username => Some(*client_id),
+        _ => None,
+    })
+}
+
+fn kick_players(mut kick_event: EventReader<KickPlayerEvent>, mut server: ResMut<RenetServer>) {
+    for KickPlayerEvent { client_id, reason } in kick_event.read() {
+        log::info!("Disconnecting {}: {}", client_id, reason);
+        server.disconnect(*client_id);
+    }
+}
+
+fn clear_admins(mut admins: ResMut<Admins>, mut ban_list: ResMut<BanList>) {
+    admins.0.clear();
+    ban_list.0.clear();
+}
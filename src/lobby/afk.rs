@@ -0,0 +1,184 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::ColliderDisabled;
+use renet::RenetServer;
+
+use crate::component::{DespawnReason, Respawn};
+use crate::world::{SimulationConfig, SimulationTick};
+
+use super::host::ServerSettings;
+use super::outbox::ServerOutbox;
+use super::{ChangeMapLobbyEvent, Lobby, LobbyState, MapLoaderState, PlayerId, ServerMessages};
+
+/// What the host does with a player who ignored the [`ServerMessages::AfkWarning`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AfkAction {
+    /// Takes the character out of the world until the player is active again
+    #[default]
+    Spectate,
+    /// Frees the slot of the player
+    Disconnect,
+}
+
+/// Handling of inactive clients, enabled by [`ServerSettings::afk`].
+///
+/// Only deliberate inputs count as activity, see
+/// [`ClientMessages::is_deliberate`](super::ClientMessages::is_deliberate).
+/// The host player is never inactive.
+#[derive(Debug, Clone, Copy)]
+pub struct AfkRules {
+    /// Seconds without activity before the player is warned
+    pub timeout: f32,
+    /// Seconds between the warning and the [`AfkAction`]
+    pub grace: f32,
+    pub action: AfkAction,
+}
+
+impl Default for AfkRules {
+    fn default() -> Self {
+        Self {
+            timeout: 300.,
+            grace: 30.,
+            action: AfkAction::Spectate,
+        }
+    }
+}
+
+/// Character of an inactive player, hidden and without collisions until the player is back.
+#[derive(Debug, Default, Component)]
+pub struct AfkSpectator;
+
+/// Players already sent a [`ServerMessages::AfkWarning`], warned once per inactivity.
+#[derive(Debug, Default, Resource)]
+struct AfkWarned(HashSet<PlayerId>);
+
+/// Inactivity warning of the host shown on the client.
+#[derive(Debug, Default, Resource)]
+pub struct AfkNotice(pub Option<Timer>);
+
+pub struct AfkPlugins;
+
+impl Plugin for AfkPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AfkWarned>()
+            .init_resource::<AfkNotice>()
+            .add_systems(
+                Update,
+                (
+                    reset_activity,
+                    monitor_afk.run_if(in_state(MapLoaderState::Yes)),
+                )
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(OnExit(LobbyState::Host), clear_warned)
+            .add_systems(OnExit(LobbyState::Client), clear_notice);
+    }
+}
+
+/// A new level takes a while to load, nobody is inactive while it does.
+fn reset_activity(
+    mut change_map_event: EventReader<ChangeMapLobbyEvent>,
+    tick: Res<SimulationTick>,
+    mut lobby: ResMut<Lobby>,
+    mut warned: ResMut<AfkWarned>,
+) {
+    if change_map_event.read().count() == 0 {
+        return;
+    }
+    for player_data in lobby.players.values_mut() {
+        player_data.last_activity = **tick;
+    }
+    warned.0.clear();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn monitor_afk(
+    mut commands: Commands,
+    settings: Res<ServerSettings>,
+    config: Res<SimulationConfig>,
+    tick: Res<SimulationTick>,
+    lobby: Res<Lobby>,
+    mut warned: ResMut<AfkWarned>,
+    mut server: ResMut<RenetServer>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut spectator_query: Query<(Has<AfkSpectator>, &mut Respawn)>,
+) {
+    for (id, player_data) in lobby.players.iter() {
+        let PlayerId::Client(client_id) = id else {
+            continue;
+        };
+        let Some(entity) = player_data.entity() else {
+            continue;
+        };
+        let Ok((spectating, mut respawn)) = spectator_query.get_mut(entity) else {
+            continue;
+        };
+        let idle_ticks = tick.saturating_sub(player_data.last_activity);
+        let idle = idle_ticks as f32 / config.physics_hz as f32;
+
+        let Some(rules) = settings.afk.filter(|rules| idle >= rules.timeout) else {
+            // active again, or the rules were turned off
+            if warned.0.remove(id) {
+                outbox.queue_for(*client_id, ServerMessages::AfkWarning { seconds_left: 0 });
+            }
+            if spectating {
+                log::info!("{} is back from spectating", player_data.username);
+                commands
+                    .entity(entity)
+                    .remove::<(AfkSpectator, ColliderDisabled)>()
+                    .insert(Visibility::Inherited);
+                if !respawn.is_pending() {
+                    respawn.insert_reason(DespawnReason::Forced);
+                }
+                outbox.queue(ServerMessages::PlayerSpectating {
+                    id: *id,
+                    spectating: false,
+                });
+            }
+            continue;
+        };
+        if spectating {
+            continue;
+        }
+
+        if idle < rules.timeout + rules.grace {
+            if warned.0.insert(*id) {
+                outbox.queue_for(
+                    *client_id,
+                    ServerMessages::AfkWarning {
+                        seconds_left: rules.grace.ceil() as u32,
+                    },
+                );
+            }
+            continue;
+        }
+
+        warned.0.remove(id);
+        match rules.action {
+            AfkAction::Spectate => {
+                log::info!("{} is inactive, moved to spectators", player_data.username);
+                commands
+                    .entity(entity)
+                    .insert((AfkSpectator, ColliderDisabled, Visibility::Hidden));
+                outbox.queue(ServerMessages::PlayerSpectating {
+                    id: *id,
+                    spectating: true,
+                });
+            }
+            AfkAction::Disconnect => {
+                log::info!("Disconnecting {}: inactive", player_data.username);
+                server.disconnect(*client_id);
+            }
+        }
+    }
+}
+
+fn clear_warned(mut warned: ResMut<AfkWarned>) {
+    warned.0.clear();
+}
+
+fn clear_notice(mut notice: ResMut<AfkNotice>) {
+    notice.0 = None;
+}
@@ -0,0 +1,261 @@
+//! Joins the host lets through, decided before the client gets a character.
+//!
+//! A connected client waits in [`PendingApproval`] while its [`ConnectionRequestEvent`] is
+//! decided by the [`ApprovalPolicy`], or later by game code with a [`ConnectionDecisionEvent`].
+//! Only an approved client is admitted by the host, see [`ClientApprovedEvent`]. A refused one
+//! is told why with [`ServerMessages::ConnectionRefused`] and disconnected, so is one left
+//! undecided for [`APPROVAL_TIMEOUT`].
+//!
+//! [`ApprovalPolicy::AllowAll`] approves in the frame the client connects, the join goes on
+//! exactly as without an approval. Whatever the policy, a client is refused once the lobby has
+//! [`ServerSettings::max_players`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use renet::ClientId;
+
+use super::admin::KickPlayerEvent;
+use super::host::ServerSettings;
+use super::host_settings::player_count;
+use super::outbox::ServerOutbox;
+use super::{ConnectInfo, Lobby, LobbyState, ServerMessages};
+
+/// Time a client may wait for a decision before it is refused
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time given to the transport to deliver [`ServerMessages::ConnectionRefused`]
+const REFUSAL_FLUSH_DELAY: Duration = Duration::from_millis(200);
+
+/// A client connected and waits for the host to let it join.
+#[derive(Debug, Clone, Event)]
+pub struct ConnectionRequestEvent {
+    pub client_id: ClientId,
+    /// Name the client joins with, after the word filter
+    pub username: String,
+    /// What the client sent, the username as it was typed
+    pub info: ConnectInfo,
+}
+
+/// Outcome of a [`ConnectionRequestEvent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Approval {
+    Approve,
+    /// The reason is shown to the refused player
+    Refuse(String),
+    /// Left to a [`ConnectionDecisionEvent`], until [`APPROVAL_TIMEOUT`]
+    Undecided,
+}
+
+/// Decides a client left [`Approval::Undecided`] by the policy, e.g. after asking the host.
+#[derive(Debug, Clone, Event)]
+pub struct ConnectionDecisionEvent {
+    pub client_id: ClientId,
+    pub approval: Approval,
+}
+
+/// A client was approved, the host spawns its character.
+#[derive(Debug, Clone, Event)]
+pub struct ClientApprovedEvent {
+    pub client_id: ClientId,
+    pub username: String,
+}
+
+pub type ApprovalFn = Arc<dyn Fn(&ConnectionRequestEvent) -> Approval + Send + Sync>;
+
+/// Who the host lets join.
+#[derive(Clone, Resource)]
+pub enum ApprovalPolicy {
+    AllowAll,
+    /// Clients sending this password in their [`ConnectInfo`]
+    PasswordMatch(String),
+    /// Clients with one of these usernames, as they typed them
+    Allowlist(Vec<String>),
+    /// Decided by game code
+    Custom(ApprovalFn),
+}
+
+/// [`ApprovalPolicy::PasswordMatch`] with `JOIN_PASSWORD` if it is set.
+impl Default for ApprovalPolicy {
+    fn default() -> Self {
+        match std::env::var("JOIN_PASSWORD") {
+            Ok(password) if !password.is_empty() => Self::PasswordMatch(password),
+            _ => Self::AllowAll,
+        }
+    }
+}
+
+impl fmt::Debug for ApprovalPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AllowAll => write!(f, "AllowAll"),
+            // the password is not logged
+            Self::PasswordMatch(_) => write!(f, "PasswordMatch"),
+            Self::Allowlist(names) => f.debug_tuple("Allowlist").field(names).finish(),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl ApprovalPolicy {
+    pub fn custom(
+        decide: impl Fn(&ConnectionRequestEvent) -> Approval + Send + Sync + 'static,
+    ) -> Self {
+        Self::Custom(Arc::new(decide))
+    }
+
+    pub fn decide(&self, request: &ConnectionRequestEvent) -> Approval {
+        match self {
+            Self::AllowAll => Approval::Approve,
+            Self::PasswordMatch(password) => {
+                if request.info.password.as_ref() == Some(password) {
+                    Approval::Approve
+                } else {
+                    Approval::Refuse("wrong password".to_string())
+                }
+            }
+            Self::Allowlist(names) => {
+                if names.contains(&request.info.username) {
+                    Approval::Approve
+                } else {
+                    Approval::Refuse("not on the allowlist".to_string())
+                }
+            }
+            Self::Custom(decide) => decide(request),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingClient {
+    username: String,
+    /// Until the client is refused, or disconnected once it is
+    timer: Timer,
+    refused: bool,
+}
+
+/// Clients connected to the host but not admitted yet.
+///
+/// Their messages are left unread until they are, a refused client stays until it is
+/// disconnected.
+#[derive(Debug, Default, Resource)]
+pub struct PendingApproval(HashMap<ClientId, PendingClient>);
+
+impl PendingApproval {
+    pub fn insert(&mut self, client_id: ClientId, username: String) {
+        self.0.insert(
+            client_id,
+            PendingClient {
+                username,
+                timer: Timer::new(APPROVAL_TIMEOUT, TimerMode::Once),
+                refused: false,
+            },
+        );
+    }
+
+    /// Whether `client_id` was still waiting, or refused
+    pub fn remove(&mut self, client_id: ClientId) -> bool {
+        self.0.remove(&client_id).is_some()
+    }
+
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        self.0.contains_key(&client_id)
+    }
+
+    /// Tells `client_id` it was refused, it is disconnected once the message is out.
+    fn refuse(&mut self, client_id: ClientId, reason: String, outbox: &mut ServerOutbox) {
+        let Some(pending) = self.0.get_mut(&client_id).filter(|pending| !pending.refused) else {
+            return;
+        };
+        log::info!("Refused {} ({}): {}", client_id, pending.username, reason);
+        pending.refused = true;
+        pending.timer = Timer::new(REFUSAL_FLUSH_DELAY, TimerMode::Once);
+        outbox.queue_for(client_id, ServerMessages::ConnectionRefused { reason });
+    }
+}
+
+pub struct ApprovalPlugins;
+
+impl Plugin for ApprovalPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ApprovalPolicy>()
+            .init_resource::<PendingApproval>()
+            .add_event::<ConnectionRequestEvent>()
+            .add_event::<ConnectionDecisionEvent>()
+            .add_event::<ClientApprovedEvent>()
+            .add_systems(OnExit(LobbyState::Host), clear_pending_approval);
+    }
+}
+
+/// Applies the policy to the new requests and the decisions of game code,
+/// ordered by the host between the connections and the admission.
+#[allow(clippy::too_many_arguments)]
+pub fn approve_connections(
+    time: Res<Time>,
+    policy: Res<ApprovalPolicy>,
+    settings: Res<ServerSettings>,
+    lobby: Res<Lobby>,
+    mut pending: ResMut<PendingApproval>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut requests: EventReader<ConnectionRequestEvent>,
+    mut decisions: EventReader<ConnectionDecisionEvent>,
+    mut approved_event: EventWriter<ClientApprovedEvent>,
+    mut kick_event: EventWriter<KickPlayerEvent>,
+) {
+    let decided = requests
+        .read()
+        .map(|request| (request.client_id, policy.decide(request)))
+        .chain(
+            decisions
+                .read()
+                .map(|decision| (decision.client_id, decision.approval.clone())),
+        );
+    // the ones approved in this frame are not in the lobby yet
+    let mut players = player_count(&lobby);
+    for (client_id, approval) in decided {
+        match approval {
+            Approval::Approve => {
+                if pending.0.get(&client_id).map_or(true, |client| client.refused) {
+                    continue;
+                }
+                if players >= settings.max_players {
+                    pending.refuse(client_id, "the server is full".to_string(), &mut outbox);
+                    continue;
+                }
+                players += 1;
+                if let Some(client) = pending.0.remove(&client_id) {
+                    approved_event.send(ClientApprovedEvent {
+                        client_id,
+                        username: client.username,
+                    });
+                }
+            }
+            Approval::Refuse(reason) => pending.refuse(client_id, reason, &mut outbox),
+            Approval::Undecided => {}
+        }
+    }
+
+    let mut timed_out = Vec::new();
+    for (client_id, client) in pending.0.iter_mut() {
+        if !client.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        if client.refused {
+            kick_event.send(KickPlayerEvent {
+                client_id: *client_id,
+                reason: "connection refused".to_string(),
+            });
+        } else {
+            timed_out.push(*client_id);
+        }
+    }
+    for client_id in timed_out {
+        pending.refuse(client_id, "no approval in time".to_string(), &mut outbox);
+    }
+}
+
+fn clear_pending_approval(mut pending: ResMut<PendingApproval>) {
+    pending.0.clear();
+}
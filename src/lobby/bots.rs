@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+use rand::Rng;
+use renet::RenetServer;
+
+use crate::actor::character::spawn_character;
+use crate::actor::FireRequest;
+use crate::world::SpawnProperty;
+
+use super::afk::AfkSpectator;
+use super::host::{generate_player_color, ServerSettings};
+use super::outbox::ServerOutbox;
+use super::team::{balanced_team, team_color, team_sizes, team_spawn};
+use super::validation::MovementInput;
+use super::{
+    Character, Lobby, LobbyState, MapLoaderState, PlayerData, PlayerId, PlayerView, ServerMessages,
+};
+
+/// Most bots [`ServerSettings::bots`] can ask for
+pub const MAX_BOTS: usize = 16;
+/// Players closer than this are chased, bots wander between the spawn points otherwise
+const CHASE_RADIUS: f32 = 40.;
+/// Players closer than this are fired at
+const FIRE_RANGE: f32 = 25.;
+const FIRE_INTERVAL: f32 = 1.5;
+/// Distance to a wander goal at which the bot picks the next one
+const GOAL_REACHED: f32 = 2.;
+/// Seconds after which a bot gives up a wander goal it cannot reach
+const GOAL_TIMEOUT: f32 = 15.;
+
+/// Character of a server-side bot, driven by [`drive_bots`] instead of network input.
+///
+/// Its moves go through the [`MovementInput`] a client input would land in,
+/// so it is simulated and replicated like a client character.
+#[derive(Debug, Component)]
+pub struct Bot {
+    /// Spawn point the bot walks to while nobody is around
+    goal: Option<Vec3>,
+    goal_timer: Timer,
+    fire_timer: Timer,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self {
+            goal: None,
+            goal_timer: Timer::from_seconds(GOAL_TIMEOUT, TimerMode::Once),
+            fire_timer: Timer::from_seconds(FIRE_INTERVAL, TimerMode::Once),
+        }
+    }
+}
+
+/// Number of the next bot, so a removed bot is not confused with a new one on the clients.
+#[derive(Debug, Default, Resource)]
+struct BotSeq(u32);
+
+pub struct BotPlugins;
+
+impl Plugin for BotPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BotSeq>()
+            .add_systems(
+                Update,
+                (fill_bots, drive_bots)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>))
+                    // spawn points are only known once the level is loaded
+                    .run_if(in_state(MapLoaderState::Yes)),
+            )
+            .add_systems(OnExit(LobbyState::Host), clear_bot_seq);
+    }
+}
+
+/// Spawns or removes bots until there are [`ServerSettings::bots`] of them.
+fn fill_bots(
+    mut commands: Commands,
+    settings: Res<ServerSettings>,
+    spawn_point: Res<SpawnProperty>,
+    mut lobby: ResMut<Lobby>,
+    mut seq: ResMut<BotSeq>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    if spawn_point.is_empty() {
+        return;
+    }
+    let mut bots: Vec<PlayerId> = lobby
+        .players
+        .keys()
+        .filter(|id| matches!(id, PlayerId::Bot(_)))
+        .copied()
+        .collect();
+    let wanted = settings.bots.min(MAX_BOTS);
+
+    // the newest bots leave first
+    bots.sort_by_key(|id| match id {
+        PlayerId::Bot(number) => *number,
+        _ => 0,
+    });
+    while bots.len() > wanted {
+        let Some(id) = bots.pop() else {
+            break;
+        };
+        remove_bot(&mut commands, &mut lobby, &mut outbox, id);
+    }
+
+    for _ in bots.len()..wanted {
+        seq.0 += 1;
+        let id = PlayerId::Bot(seq.0);
+        lobby.players_seq += 1;
+        let team = settings.teams.map(|_| balanced_team(&lobby));
+        let color = match team {
+            Some(team) => team_color(team, team_sizes(&lobby)[team.index()] as u32),
+            None => generate_player_color(lobby.players_seq as u32),
+        };
+        let entity = commands
+            .spawn_character(id, color, team_spawn(&spawn_point, team).random_point())
+            .insert((Bot::default(), MovementInput::default()))
+            .id();
+
+        let username = format!("bot {}", seq.0);
+        log::info!("Adding {}", username);
+        let mut player_data = PlayerData::new(entity, color, username.clone());
+        player_data.team = team;
+        lobby.players.insert(id, player_data);
+
+        outbox.queue(ServerMessages::PlayerConnected {
+            id,
+            color,
+            username,
+            team,
+        });
+    }
+}
+
+/// Cleans up after a bot like after a `ClientDisconnected`.
+fn remove_bot(
+    commands: &mut Commands,
+    lobby: &mut Lobby,
+    outbox: &mut ServerOutbox,
+    id: PlayerId,
+) {
+    if let Some(player_data) = lobby.players.remove(&id) {
+        log::info!("Removing {}", player_data.username);
+        if let Some(entity) = player_data.entity() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    outbox.queue(ServerMessages::PlayerDisconnected { id });
+}
+
+/// Chases and fires at the nearest player of another team, or wanders between spawn points.
+#[allow(clippy::type_complexity)]
+fn drive_bots(
+    mut commands: Commands,
+    time: Res<Time>,
+    lobby: Res<Lobby>,
+    spawn_point: Res<SpawnProperty>,
+    target_query: Query<(Entity, &Transform, &Character), Without<AfkSpectator>>,
+    mut bot_query: Query<(
+        Entity,
+        &Transform,
+        &Character,
+        &mut Bot,
+        &mut MovementInput,
+        &mut PlayerView,
+    )>,
+) {
+    let team = |id: &PlayerId| lobby.player(id).and_then(|player_data| player_data.team);
+    for (entity, transform, character, mut bot, mut input, mut view) in bot_query.iter_mut() {
+        bot.goal_timer.tick(time.delta());
+        bot.fire_timer.tick(time.delta());
+        let position = transform.translation;
+
+        let own_team = team(&character.id);
+        let target = target_query
+            .iter()
+            .filter(|(target, _, other)| {
+                *target != entity && (own_team.is_none() || team(&other.id) != own_team)
+            })
+            .map(|(_, target_transform, _)| target_transform.translation)
+            .filter(|target| target.distance(position) <= CHASE_RADIUS)
+            .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+
+        let goal = match target {
+            Some(target) => {
+                bot.goal = None;
+                target
+            }
+            None => {
+                let reached = bot
+                    .goal
+                    .map_or(true, |goal| goal.xz().distance(position.xz()) <= GOAL_REACHED);
+                if reached || bot.goal_timer.finished() {
+                    bot.goal = Some(spawn_point.random_point());
+                    bot.goal_timer.reset();
+                }
+                bot.goal.unwrap_or(position)
+            }
+        };
+
+        let direction = (goal - position).xz().normalize_or_zero();
+        input.0 = direction;
+        if direction != Vec2::ZERO {
+            // the projectiles leave along the view, see `FireRequest`
+            let aim = (goal - position).normalize_or_zero();
+            view.direction = Transform::IDENTITY.looking_to(aim, Vec3::Y).rotation;
+        }
+
+        let in_range = target.is_some_and(|target| target.distance(position) <= FIRE_RANGE);
+        if in_range && bot.fire_timer.finished() {
+            commands.entity(entity).insert(FireRequest);
+            // a little late now and then, so the bots do not fire in sync
+            let jitter = rand::thread_rng().gen_range(0. ..FIRE_INTERVAL / 2.);
+            bot.fire_timer = Timer::from_seconds(FIRE_INTERVAL + jitter, TimerMode::Once);
+        }
+    }
+}
+
+fn clear_bot_seq(mut seq: ResMut<BotSeq>) {
+    seq.0 = 0;
+}
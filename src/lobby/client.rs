@@ -1,84 +1,252 @@
-use std::net::UdpSocket;
 use std::time::SystemTime;
 
-use crate::actor::character::{spawn_character_shell, spawn_tied_camera, TiedCamera};
-use crate::actor::UnloadActorsEvent;
+use crate::actor::character::{
+    movement_direction, shake_on_projectile_despawn, spawn_character_shell, spawn_tied_camera,
+    MovementTuning, ReplicatedAnimation, TiedCamera,
+};
+use crate::actor::{spawn_prefab, PrefabArgs, UnloadActorsEvent};
+use crate::component::HealthChangedEvent;
+use crate::core::{CoreConfig, CoreGameState, LoadLevelEvent, LoadingProgress};
+use crate::level::level_checksum;
 use crate::lobby::{LobbyState, PlayerId};
-use crate::world::{LinkId, Me};
-use bevy::app::{App, Plugin, Update};
+use crate::network::{
+    connection_config, decode, new_client_transport, payload_head, Channel, ChunkReceiver,
+    MAX_SERVER_MESSAGE_SIZE,
+};
+use crate::replay::{ReplayChannel, ReplayRecorder};
+use crate::tr;
+use crate::world::{
+    ChangeEnvironmentEvent, ChangePhysicsEvent, FreeCamera, LevelPhysics, LinkId, Me,
+    SimulationTick,
+};
+use bevy::app::{App, FixedUpdate, Plugin, Update};
 use bevy::ecs::entity::Entity;
-use bevy::ecs::event::EventWriter;
-use bevy::ecs::query::With;
-use bevy::ecs::schedule::{Condition, OnExit};
-use bevy::ecs::system::{Query, Res, ResMut, Resource};
+use bevy::ecs::event::{EventReader, EventWriter};
+use bevy::ecs::query::{With, Without};
+use bevy::ecs::schedule::{Condition, NextState, OnExit};
+use bevy::ecs::system::{Query, Res, ResMut, Resource, SystemParam};
+use bevy::ecs::world::World;
 use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::log::info_span;
 use bevy::math::Vec3;
-use bevy::prelude::{in_state, Commands, IntoSystemConfigs, OnEnter};
+use bevy::render::view::Visibility;
+use bevy::time::{Time, Timer, TimerMode};
+use bevy::prelude::{in_state, resource_exists, Color, Commands, IntoSystemConfigs, OnEnter};
 use bevy::transform::components::Transform;
 use bevy_renet::transport::NetcodeClientPlugin;
 use bevy_renet::RenetClientPlugin;
-use renet::transport::{ClientAuthentication, NetcodeClientTransport};
-use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient};
+use renet::transport::{NetcodeClientTransport, NetcodeTransportError};
+use renet::{ClientId, RenetClient};
 
 #[derive(Default, Debug, Resource)]
 pub struct OwnId(Option<ClientId>);
 
+impl OwnId {
+    /// Player id of this client, `None` until the server has initialized the connection.
+    pub fn player_id(&self) -> Option<PlayerId> {
+        self.0.map(PlayerId::Client)
+    }
+
+    pub fn client_id(&self) -> Option<ClientId> {
+        self.0
+    }
+}
+
+use super::afk::AfkNotice;
+use super::migration::{HostLostEvent, MigrationPlan};
+use super::pending_links::{apply_pending_links, clear_pending_links, LinkedActors, PendingLinks};
+use super::prediction::{reconcile_own, Prediction, PredictionPlugins};
+use super::quick_chat::{ChatEvent, QuickChatEvent};
+use super::ready::{MatchCountdown, MatchStartedEvent, ReadyStates};
 use super::{
-    ClientResource, Lobby, PlayerData, ServerMessages, TransportDataResource, Username, PROTOCOL_ID,
+    Character, ClientMessages, ClientResource, ConnectInfo, Lobby, MapLoaderState,
+    NetworkSetupErrorEvent, PlayerData, PlayerDiedEvent, PlayerView, ServerMessages, TransportData,
+    TransportDataResource,
 };
 
 pub struct ClientLobbyPlugins;
 
 impl Plugin for ClientLobbyPlugins {
     fn build(&self, app: &mut App) {
-        app.add_plugins((RenetClientPlugin, NetcodeClientPlugin))
+        app.add_plugins((RenetClientPlugin, NetcodeClientPlugin, PredictionPlugins))
             .add_systems(OnEnter(LobbyState::Client), (setup, new_renet_client))
+            .add_systems(Update, transport_errors.run_if(in_state(LobbyState::Client)))
+            .add_systems(
+                Update,
+                connect_timeout.run_if(
+                    in_state(LobbyState::Client).and_then(resource_exists::<ConnectAttempt>),
+                ),
+            )
+            .add_systems(
+                Update,
+                send_hello.run_if(
+                    in_state(LobbyState::Client).and_then(bevy_renet::client_just_connected),
+                ),
+            )
+            .init_resource::<PendingLinks>()
             .add_systems(
                 Update,
                 client_sync_players
                     .run_if(in_state(LobbyState::Client).and_then(bevy_renet::client_connected)),
             )
-            .add_systems(OnExit(LobbyState::Client), teardown);
+            // also after the replay playback, it goes through the same handler
+            .add_systems(Update, apply_pending_links.after(client_sync_players))
+            .add_systems(
+                FixedUpdate,
+                client_send_input.run_if(
+                    in_state(LobbyState::Client)
+                        .and_then(bevy_renet::client_connected)
+                        .and_then(resource_exists::<Lobby>),
+                ),
+            )
+            .add_systems(
+                OnEnter(CoreGameState::InGame),
+                map_loaded.run_if(in_state(LobbyState::Client)),
+            )
+            .add_systems(OnExit(LobbyState::Client), (teardown, clear_pending_links));
     }
 }
 
-pub fn new_renet_client(settings: Res<ClientResource>, mut commands: Commands) {
-    commands.insert_resource(RenetClient::new(ConnectionConfig::default()));
-    let server_addr = settings.address.clone().unwrap().parse().unwrap();
-    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
-    let current_time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap();
-    let client_id = current_time.as_millis() as u64;
+/// Connection to the host not accepted yet, given up on once the timer finishes.
+#[derive(Debug, Resource)]
+pub struct ConnectAttempt {
+    address: String,
+    timer: Timer,
+}
 
-    let username_netcode =
-        match Username(settings.username.clone().unwrap().clone()).to_netcode_data() {
-            Ok(bytes) => Some(bytes),
-            Err(_) => None,
-        };
+/// The renet end of a connection to the host, also built when reconnecting to a migrated host.
+pub fn new_client() -> RenetClient {
+    RenetClient::new(connection_config())
+}
 
-    let authentication = ClientAuthentication::Unsecure {
-        client_id,
-        protocol_id: PROTOCOL_ID,
-        server_addr,
-        user_data: username_netcode,
+pub fn new_renet_client(
+    settings: Res<ClientResource>,
+    config: Res<CoreConfig>,
+    mut commands: Commands,
+    mut progress: ResMut<LoadingProgress>,
+    mut setup_error_event: EventWriter<NetworkSetupErrorEvent>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+) {
+    let address = settings.address.clone().unwrap_or_default();
+    let client_id = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let info = ConnectInfo {
+        username: settings.username.clone().unwrap_or_default(),
+        password: settings.password.clone(),
+    };
+    let transport = match new_client_transport(&address, info, client_id) {
+        Ok(transport) => transport,
+        Err(err) => {
+            log::error!("Failed to connect to {}: {}", address, err);
+            setup_error_event.send(NetworkSetupErrorEvent(tr!(
+                "error.connect",
+                address = address,
+                error = err
+            )));
+            next_state_lobby.set(LobbyState::None);
+            return;
+        }
     };
 
-    commands.insert_resource(
-        NetcodeClientTransport::new(current_time, authentication, socket).unwrap(),
+    commands.insert_resource(new_client());
+    commands.insert_resource(transport);
+    // the loading screen shows it until the host sends the level
+    progress.set_stage(tr!("menu.connecting", address = address));
+    commands.insert_resource(ConnectAttempt {
+        address,
+        timer: Timer::new(config.connect_timeout, TimerMode::Once),
+    });
+}
+
+/// Leaves the lobby if the host has not accepted the connection in time,
+/// the attempt is over as soon as it does.
+fn connect_timeout(
+    mut commands: Commands,
+    time: Res<Time>,
+    client: Option<Res<RenetClient>>,
+    mut attempt: ResMut<ConnectAttempt>,
+    mut setup_error_event: EventWriter<NetworkSetupErrorEvent>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+) {
+    if client.is_some_and(|client| client.is_connected()) {
+        log::info!("Connected to {}", attempt.address);
+        commands.remove_resource::<ConnectAttempt>();
+        return;
+    }
+    if !attempt.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let seconds = attempt.timer.duration().as_secs_f32();
+    log::error!("Could not connect to {} in {:.0}s", attempt.address, seconds);
+    setup_error_event.send(NetworkSetupErrorEvent(tr!(
+        "error.connect_timeout",
+        address = attempt.address,
+        seconds = format!("{:.0}", seconds)
+    )));
+    // the teardown drops the transport
+    next_state_lobby.set(LobbyState::None);
+}
+
+/// Serializes and sends a message to the host, every client sender goes through here.
+pub fn send_to_server(client: &mut RenetClient, message: &ClientMessages, channel: Channel) {
+    match bincode::serialize(message) {
+        Ok(payload) => client.send_message(channel, payload),
+        Err(err) => log::error!("Failed to serialize {:?}: {}", message, err),
+    }
+}
+
+fn send_hello(mut client: ResMut<RenetClient>) {
+    send_to_server(
+        &mut client,
+        &ClientMessages::Hello {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+        Channel::Control,
     );
 }
 
-// TODO:
-//pub fn client_send_input(
-//    mut player_input_query: Query<&mut PlayerInputs, With<Me>>,
-//    mut client: ResMut<RenetClient>,
-//) {
-//    if let Ok(player_input) = player_input_query.get_single_mut() {
-//        let input_message = bincode::serialize(&player_input.get()).unwrap();
-//        client.send_message(DefaultChannel::ReliableOrdered, input_message);
-//    }
-//}
+/// Reports the host as lost when the transport fails, e.g. the server is unreachable or timed out.
+///
+/// The session migrates if it can, otherwise the lobby is left, see [`migration`](super::migration).
+fn transport_errors(
+    mut transport_errors: EventReader<NetcodeTransportError>,
+    mut host_lost_event: EventWriter<HostLostEvent>,
+) {
+    let Some(err) = transport_errors.read().last() else {
+        return;
+    };
+    log::error!("Client transport error: {}", err);
+    host_lost_event.send(HostLostEvent(format!("Connection lost: {}", err)));
+}
+
+/// Sends the movement direction of the own character every tick, the host moves it.
+/// The character moves by it right away, see [`prediction`](super::prediction).
+#[allow(clippy::too_many_arguments)]
+fn client_send_input(
+    lobby: Res<Lobby>,
+    time: Res<Time>,
+    level_physics: Res<LevelPhysics>,
+    tuning: Res<MovementTuning>,
+    mut client: ResMut<RenetClient>,
+    mut prediction: ResMut<Prediction>,
+    mut me_query: Query<(&PlayerView, &mut Transform), With<Me>>,
+    free_camera_query: Query<(), With<FreeCamera>>,
+) {
+    let Ok((view, mut transform)) = me_query.get_single_mut() else {
+        return;
+    };
+    let movement = movement_direction(&lobby, view, !free_camera_query.is_empty());
+    let sequence = prediction.next_sequence();
+    send_to_server(&mut client, &ClientMessages::Input { movement, sequence }, Channel::State);
+
+    // the same simulated time as the host, see `LevelPhysics::time_scale`
+    let dt = time.delta_seconds() * level_physics.time_scale;
+    if let Some(position) = prediction.predict(sequence, movement, dt, &tuning) {
+        transform.translation = position;
+    }
+}
 
 fn setup(mut commands: Commands) {
     // me
@@ -89,127 +257,540 @@ fn setup(mut commands: Commands) {
     commands.init_resource::<Lobby>();
     commands.init_resource::<OwnId>();
     commands.init_resource::<TransportDataResource>();
+    commands.init_resource::<ChunkReceiver>();
+}
+
+/// The client has no spawn processing, the level is ready once spawned.
+fn map_loaded(mut next_state_map: ResMut<NextState<MapLoaderState>>) {
+    next_state_map.set(MapLoaderState::Yes);
 }
 
+/// Leaves the world and the resources as before joining, like the host teardown.
+/// A client taking over as the host gets its resources from the host setup right after.
 fn teardown(
-    _commands: Commands,
-    _tied_camera_query: Query<Entity, With<TiedCamera>>,
-    // char_query: Query<Entity, With<PlayerInputs>>,
-    _unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut commands: Commands,
+    client: Option<ResMut<RenetClient>>,
+    transport: Option<ResMut<NetcodeClientTransport>>,
+    tied_camera_query: Query<Entity, With<TiedCamera>>,
+    char_query: Query<Entity, With<Character>>,
+    mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
 ) {
-    // TODO:
-    //for entity in tied_camera_query.iter() {
-    //    commands.entity(entity).despawn_recursive();
-    //}
-    //for entity in char_query.iter() {
-    //    commands.entity(entity).despawn_recursive();
-    //}
-    //commands.remove_resource::<Lobby>();
-    //commands.remove_resource::<OwnId>();
-    //commands.remove_resource::<TransportDataResource>();
+    next_state_map.set(MapLoaderState::No);
+    // the host frees the slot right away instead of waiting for the timeout
+    if let (Some(mut client), Some(mut transport)) = (client, transport) {
+        client.disconnect();
+        transport.disconnect();
+    }
+    commands.remove_resource::<RenetClient>();
+    commands.remove_resource::<NetcodeClientTransport>();
+    commands.remove_resource::<ConnectAttempt>();
+
+    for entity in tied_camera_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in char_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<Lobby>();
+    commands.remove_resource::<OwnId>();
+    commands.remove_resource::<TransportDataResource>();
+    commands.remove_resource::<ChunkReceiver>();
 
-    //unload_actors_event.send(UnloadActorsEvent);
+    unload_actors_event.send(UnloadActorsEvent);
 }
 
-#[allow(clippy::too_many_arguments)]
 pub fn client_sync_players(
-    mut commands: Commands,
     mut client: ResMut<RenetClient>,
     mut transport_data: ResMut<TransportDataResource>,
-    mut lobby: ResMut<Lobby>,
-    mut own_id: ResMut<OwnId>,
-    //mut next_state_map: ResMut<NextState<MapState>>,
-    lincked_obj_query: Query<(Entity, &LinkId)>,
-    mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut chunk_receiver: ResMut<ChunkReceiver>,
+    mut handler: ServerMessageHandler,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<SimulationTick>,
+    time: Res<Time>,
+    #[cfg(all(debug_assertions, feature = "dev"))] mut conditioner: ResMut<
+        crate::network::LinkConditioner,
+    >,
+    #[cfg(feature = "test-utils")] mut received: Option<
+        ResMut<crate::test_harness::ReceivedServerMessages>,
+    >,
 ) {
-    // player existence manager
-    while let Some(message) = client.receive_message(DefaultChannel::ReliableOrdered) {
-        let server_message = bincode::deserialize(&message).unwrap();
-        match server_message {
-            ServerMessages::InitConnection { id, /*map_state*/ } => {
+    let _span = info_span!("sync_receive", tick = **tick).entered();
+    // player existence manager, large transfers come over the bulk channel
+    let mut messages = Vec::new();
+    for channel in [Channel::Control, Channel::Bulk] {
+        while let Some(message) = client.receive_message(channel) {
+            messages.push((channel, message));
+        }
+    }
+    for (channel, message) in messages {
+        let mut message = message.to_vec();
+        let Some(mut server_message) = decode_server_message(channel, &message) else {
+            continue;
+        };
+        if let ServerMessages::Chunk(chunk) = server_message {
+            let Some(payload) = chunk_receiver.receive(chunk, time.elapsed_seconds()) else {
+                continue;
+            };
+            let Some(reassembled) = decode_server_message(channel, &payload) else {
+                continue;
+            };
+            server_message = reassembled;
+            message = payload;
+        }
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.record(ReplayChannel::Reliable, &message);
+        }
+        #[cfg(feature = "test-utils")]
+        if let Some(received) = received.as_deref_mut() {
+            received.0.push(message.clone());
+        }
+        if !handler.handle_message(server_message) {
+            client.disconnect();
+            return;
+        }
+    }
+
+    // movements
+    let mut messages = Vec::new();
+    while let Some(message) = client.receive_message(Channel::State) {
+        messages.push(message.to_vec());
+    }
+    #[cfg(all(debug_assertions, feature = "dev"))]
+    let messages = conditioner.condition(None, messages);
+    for message in messages {
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.record(ReplayChannel::Unreliable, &message);
+        }
+        match decode(&message, MAX_SERVER_MESSAGE_SIZE) {
+            Ok(data) => transport_data.data = data,
+            Err(err) => {
+                log::warn!("Dropped a snapshot: {} {}", err, payload_head(&message));
+                continue;
+            }
+        }
+        handler.apply_snapshot(&transport_data.data);
+    }
+}
+
+/// A message of the host, `None` if it does not decode, e.g. corrupted on the way.
+fn decode_server_message(channel: Channel, payload: &[u8]) -> Option<ServerMessages> {
+    decode(payload, MAX_SERVER_MESSAGE_SIZE)
+        .map_err(|err| {
+            log::warn!(
+                "Dropped a message of the host on {:?}: {} {}",
+                channel,
+                err,
+                payload_head(payload)
+            );
+        })
+        .ok()
+}
+
+/// Components a snapshot writes in place, an insert through the commands would move the
+/// entity to another archetype for every synced entity of every snapshot.
+#[derive(SystemParam)]
+struct SyncTargets<'w, 's> {
+    visibility: Query<'w, 's, &'static mut Visibility>,
+    /// Characters are not linked actors, so this never aliases the [`LinkedActors`] transforms
+    #[allow(clippy::type_complexity)]
+    characters: Query<
+        'w,
+        's,
+        (
+            Option<&'static mut Transform>,
+            Option<&'static mut PlayerView>,
+            Option<&'static mut ReplicatedAnimation>,
+        ),
+        (With<Character>, Without<LinkId>),
+    >,
+}
+
+/// Applies [`ServerMessages`] and [`TransportData`] snapshots to the world.
+///
+/// Shared by [`client_sync_players`] and the replay playback so both reproduce the session the same way.
+#[derive(SystemParam)]
+pub struct ServerMessageHandler<'w, 's> {
+    commands: Commands<'w, 's>,
+    lobby: ResMut<'w, Lobby>,
+    own_id: ResMut<'w, OwnId>,
+    linked: LinkedActors<'w, 's>,
+    unload_actors_event: EventWriter<'w, UnloadActorsEvent>,
+    host_lost_event: EventWriter<'w, HostLostEvent>,
+    migration_plan: ResMut<'w, MigrationPlan>,
+    load_level_event: EventWriter<'w, LoadLevelEvent>,
+    targets: SyncTargets<'w, 's>,
+    quick_chat_event: EventWriter<'w, QuickChatEvent>,
+    chat_event: EventWriter<'w, ChatEvent>,
+    player_died_event: EventWriter<'w, PlayerDiedEvent>,
+    change_physics_event: EventWriter<'w, ChangePhysicsEvent>,
+    afk_notice: ResMut<'w, AfkNotice>,
+    tied_camera_query: Query<'w, 's, (Entity, &'static TiedCamera)>,
+}
+
+impl ServerMessageHandler<'_, '_> {
+    /// Handles a reliable message, returns `false` if the server has shut down
+    /// or the level of the host differs from the local one.
+    pub fn handle_message(&mut self, message: ServerMessages) -> bool {
+        match message {
+            ServerMessages::InitConnection { id, seed /*map_state*/ } => {
                 //next_state_map.set(map_state);
-                if own_id.0.is_some() {
+                if self.own_id.0.is_some() {
                     panic!("Yeah, I knew it. The server only had to initialize me once. Redo it, you idiot.");
                 } else {
-                    *own_id = OwnId(Some(id));
+                    *self.own_id = OwnId(Some(id));
                 }
+                self.commands.insert_resource(seed);
             }
-            ServerMessages::ChangeMap { /*map_state*/ } => {
+            ServerMessages::ChangeMap {
+                level,
+                checksum,
+                physics,
+                environment,
+                phase,
+            } => {
                 //next_state_map.set(map_state);
-                unload_actors_event.send(UnloadActorsEvent);
+                let local_checksum = level_checksum(&level);
+                // TODO: download the host version once levels load from urls, leave until then
+                if checksum.is_some() && local_checksum != checksum {
+                    log::error!(
+                        "Level {:?} differs from the host one (local {:?}, host {:?}), leaving",
+                        level,
+                        local_checksum,
+                        checksum
+                    );
+                    // the session goes on for everyone else, nothing to take over
+                    self.migration_plan.candidates.clear();
+                    self.host_lost_event.send(HostLostEvent(tr!(
+                        "menu.level_mismatch",
+                        level = format!("{:?}", level)
+                    )));
+                    return false;
+                }
+
+                self.unload_actors_event.send(UnloadActorsEvent);
+                // what waits for the actors of the previous level is stale
+                self.linked.clear_pending();
+                self.load_level_event.send(
+                    LoadLevelEvent::new(level)
+                        .with_physics(physics)
+                        .with_environment(environment, phase),
+                );
             }
             ServerMessages::PlayerConnected {
                 id: player_id,
                 color,
                 username,
+                team,
             } => {
-                let player_entity = commands
+                let player_entity = self
+                    .commands
                     .spawn_character_shell(player_id, color, Vec3::ZERO)
                     .id();
                 if let PlayerId::Client(id) = player_id {
-                    if Some(id) == own_id.0 {
-                        commands.entity(player_entity).insert(Me);
-                        commands.spawn_tied_camera(player_entity);
+                    if Some(id) == self.own_id.0 {
+                        self.commands.entity(player_entity).insert(Me);
+                        self.commands.spawn_tied_camera(player_entity);
                         log::info!("{username} ({id}), welcome.");
                     } else {
                         log::info!("Player {} ({}) connected.", username, id);
                     }
+                } else if let PlayerId::Bot(_) = player_id {
+                    log::info!("Bot {} connected.", username);
                 } else {
                     log::info!("Host {} ({:?}).", username, player_id);
                 }
 
-                lobby
-                    .players
-                    .insert(player_id, PlayerData::new(player_entity, color, username));
+                let mut player_data = PlayerData::new(player_entity, color, username);
+                player_data.team = team;
+                self.lobby.players.insert(player_id, player_data);
             }
             ServerMessages::PlayerDisconnected { id } => {
-                let name = "noname";
-
-                log::info!("Player {} ({:?}) disconnected.", name, id);
-                if let Some(player_data) = lobby.players.remove(&id) {
-                    commands.entity(player_data.entity()).despawn();
+                let Some(player_data) = self.lobby.players.remove(&id) else {
+                    log::info!("Unknown player {:?} disconnected.", id);
+                    return true;
+                };
+                log::info!("Player {} ({:?}) disconnected.", player_data.username, id);
+                let Some(entity) = player_data.entity() else {
+                    log::warn!("Player {:?} had no character to despawn", id);
+                    return true;
+                };
+                self.commands.entity(entity).despawn_recursive();
+                // only ours should follow a player, but a camera left on a despawned target stays
+                for (camera, tied_camera) in self.tied_camera_query.iter() {
+                    if tied_camera.target() == entity {
+                        self.commands.entity(camera).despawn_recursive();
+                    }
                 }
             }
             ServerMessages::ActorDespawn { id } => {
-                for (entity, link_id) in lincked_obj_query.iter() {
-                    if link_id == &id {
-                        commands.entity(entity).despawn_recursive();
+                // a projectile going away is an impact, a shell not placed yet is skipped
+                let despawned: Vec<(Entity, Vec3)> = self
+                    .linked
+                    .entities(&id)
+                    .into_iter()
+                    .filter_map(|(entity, transform)| Some((entity, transform?.translation)))
+                    .collect();
+                if !despawned.is_empty() {
+                    self.commands.add(move |world: &mut World| {
+                        shake_on_projectile_despawn(world, &despawned);
+                    });
+                }
+                self.linked.despawn(&mut self.commands, id);
+            }
+            ServerMessages::ProjectileSpawn {
+                id,
+                prefab,
+                owner,
+                color,
+            } => {
+                // the owner may have left in the meantime, the projectile is still shown
+                let color = if self.lobby.player(&owner).is_some() {
+                    color
+                } else {
+                    log::debug!("Projectile {:?} of unknown player {:?}", id, owner);
+                    Color::WHITE
+                };
+                // the host despawned it before this message was sent, e.g. hit right away
+                if self.linked.take_despawn(&id) {
+                    log::debug!("Projectile {:?} despawned before its spawn, skipped", id);
+                    return true;
+                }
+                let args = PrefabArgs {
+                    color,
+                    owner: Some(owner),
+                    ..PrefabArgs::new(id.clone())
+                };
+                let entity = self.commands.spawn_prefab(prefab, args, true).id();
+                self.linked.queue_spawn(id, entity);
+            }
+            ServerMessages::ServerShutdown { reason } => {
+                log::info!("Server shut down: {reason}");
+                // the host ended the game on purpose, nobody takes it over
+                self.migration_plan.candidates.clear();
+                self.host_lost_event
+                    .send(HostLostEvent(tr!("menu.host_ended", reason = reason)));
+                return false;
+            }
+            ServerMessages::RulesUpdate { rules } => {
+                log::info!("Session rules: {:?}", rules);
+                self.commands.insert_resource(rules);
+            }
+            ServerMessages::SessionLog {
+                started_at,
+                entries,
+            } => super::session_log::receive(started_at, entries),
+            ServerMessages::ConnectionRefused { reason } => {
+                log::info!("Host refused the connection: {reason}");
+                // never joined, there is no session to migrate
+                self.commands.add(move |world: &mut World| {
+                    world.send_event(NetworkSetupErrorEvent(tr!(
+                        "error.connection_refused",
+                        reason = reason
+                    )));
+                    world.resource_mut::<NextState<LobbyState>>().set(LobbyState::None);
+                });
+                return false;
+            }
+            ServerMessages::OutOfInterest { players, actors } => {
+                for player_id in players {
+                    let entity = self.lobby.players.get(&player_id).and_then(PlayerData::entity);
+                    if let Some(entity) = entity {
+                        if let Ok(mut visibility) = self.targets.visibility.get_mut(entity) {
+                            *visibility = Visibility::Hidden;
+                        }
+                    }
+                }
+                for (entity, link_id) in self.linked.iter() {
+                    if actors.contains(link_id) {
+                        if let Ok(mut visibility) = self.targets.visibility.get_mut(entity) {
+                            *visibility = Visibility::Hidden;
+                        }
                     }
                 }
             }
-            ServerMessages::ProjectileSpawn { id: _, color: _ } => todo!(),
+            ServerMessages::Chunk(_) => log::error!("Nested chunked messages are not supported"),
+            ServerMessages::QuickChat {
+                from,
+                kind,
+                world_pos,
+            } => {
+                self.quick_chat_event.send(QuickChatEvent {
+                    from,
+                    kind,
+                    world_pos,
+                });
+            }
+            ServerMessages::PlayerDied { id, killer, cause } => {
+                self.player_died_event.send(PlayerDiedEvent { id, killer, cause });
+            }
+            ServerMessages::Chat { from, text } => {
+                self.chat_event.send(ChatEvent { from, text });
+            }
+            ServerMessages::MigrationCandidates { candidates } => {
+                self.migration_plan.candidates = candidates;
+            }
+            ServerMessages::ChangePhysics { physics } => {
+                self.change_physics_event.send(ChangePhysicsEvent(physics));
+            }
+            ServerMessages::ChangeEnvironment { environment, phase } => {
+                self.commands.add(move |world: &mut World| {
+                    world.send_event(ChangeEnvironmentEvent { environment, phase });
+                });
+            }
+            ServerMessages::HealthChanged {
+                id,
+                health,
+                attacker,
+            } => {
+                if let Some(entity) = self.lobby.players.get(&id).and_then(PlayerData::entity) {
+                    self.commands.entity(entity).try_insert(health);
+                }
+                // the hits of the host, for the hit marker of the attacker
+                if attacker.is_some() {
+                    self.commands.add(move |world: &mut World| {
+                        world.send_event(HealthChangedEvent {
+                            id,
+                            health,
+                            attacker,
+                        });
+                    });
+                }
+            }
+            ServerMessages::AfkWarning { seconds_left } => {
+                self.afk_notice.0 = (seconds_left > 0)
+                    .then(|| Timer::from_seconds(seconds_left as f32, TimerMode::Once));
+            }
+            ServerMessages::PlayerSpectating { id, spectating } => {
+                if let Some(entity) = self.lobby.players.get(&id).and_then(PlayerData::entity) {
+                    if let Ok(mut visibility) = self.targets.visibility.get_mut(entity) {
+                        *visibility = if spectating {
+                            Visibility::Hidden
+                        } else {
+                            Visibility::Inherited
+                        };
+                    }
+                }
+                if !spectating && Some(id) == self.own_id.player_id() {
+                    self.afk_notice.0 = None;
+                }
+            }
+            ServerMessages::ReliableSync(data) => self.apply_snapshot(&data),
+            ServerMessages::Batch(messages) => {
+                for message in messages {
+                    if !self.handle_message(message) {
+                        return false;
+                    }
+                }
+            }
+            ServerMessages::ReadyStates(states) => {
+                self.commands.add(move |world: &mut World| {
+                    world.insert_resource(ReadyStates(states));
+                });
+            }
+            ServerMessages::MatchStarting { countdown_secs } => {
+                self.commands.add(move |world: &mut World| {
+                    world.resource_mut::<MatchCountdown>().start(countdown_secs);
+                });
+            }
+            ServerMessages::MatchCountdownCancelled => {
+                self.commands.add(|world: &mut World| {
+                    world.resource_mut::<MatchCountdown>().0 = None;
+                });
+            }
+            ServerMessages::MatchStarted => {
+                self.commands.add(|world: &mut World| {
+                    world.resource_mut::<MatchCountdown>().0 = None;
+                    world.send_event(MatchStartedEvent);
+                });
+            }
+            ServerMessages::ColliderGhosts(positions) => {
+                #[cfg(feature = "dev")]
+                self.commands.add(move |world: &mut World| {
+                    super::ghosts::receive(world, positions);
+                });
+                #[cfg(not(feature = "dev"))]
+                log::debug!("{} collider ghosts ignored: not a dev build", positions.len());
+            }
+            ServerMessages::TeamAssigned { id, team, color } => {
+                // the character follows the new color, see `TeamPlugins`
+                if let Some(player_data) = self.lobby.players.get_mut(&id) {
+                    player_data.team = team;
+                    player_data.color = color;
+                }
+            }
         }
+
+        true
     }
 
-    // movements
-    while let Some(message) = client.receive_message(DefaultChannel::Unreliable) {
-        transport_data.data = bincode::deserialize(&message).unwrap();
-        for (player_id, data) in transport_data.data.players.iter() {
-            if let Some(player_data) = lobby.players.get(player_id) {
-                let transform = Transform {
-                    translation: data.position,
-                    rotation: data.rotation,
-                    ..Default::default()
-                };
-                // TODO: why transform to default?
-                commands
-                    .entity(player_data.entity())
-                    .insert(transform)
-                    .insert(data.player_view);
+    /// Moves players and linked actors to the positions of a snapshot.
+    pub fn apply_snapshot(&mut self, data: &TransportData) {
+        for (player_id, data) in data.players.iter() {
+            let Some(entity) = self.lobby.players.get(player_id).and_then(PlayerData::entity) else {
+                continue;
+            };
+            let Ok((transform, view, animation)) = self.targets.characters.get_mut(entity) else {
+                // spawned this frame, its components are not applied yet
+                self.commands.entity(entity).try_insert((
+                    Transform::from_translation(data.position).with_rotation(data.rotation),
+                    data.player_view,
+                    ReplicatedAnimation(data.animation),
+                ));
+                continue;
+            };
+            if self.own_id.player_id() == Some(*player_id) {
+                let transform = Transform::from_translation(data.position)
+                    .with_rotation(data.rotation)
+                    .with_scale(transform.map_or(Vec3::ONE, |transform| transform.scale));
+                let sequence = data.input_sequence;
+                self.commands.add(move |world: &mut World| {
+                    reconcile_own(world, entity, transform, sequence);
+                });
+            } else {
+                match transform {
+                    Some(mut transform) => {
+                        transform.translation = data.position;
+                        transform.rotation = data.rotation;
+                    }
+                    None => {
+                        self.commands.entity(entity).try_insert(
+                            Transform::from_translation(data.position).with_rotation(data.rotation),
+                        );
+                    }
+                }
             }
-        }
-
-        for (link_id, data) in transport_data.data.actors.iter() {
-            for (entity, id) in lincked_obj_query.iter() {
-                if id == link_id {
-                    let transform = Transform {
-                        translation: data.position,
-                        rotation: data.rotation,
-                        ..Default::default()
-                    };
-                    commands.entity(entity).try_insert(transform);
+            match view {
+                Some(mut view) => *view = data.player_view,
+                None => {
+                    self.commands.entity(entity).try_insert(data.player_view);
+                }
+            }
+            match animation {
+                Some(mut animation) => animation.0 = data.animation,
+                None => {
+                    self.commands
+                        .entity(entity)
+                        .try_insert(ReplicatedAnimation(data.animation));
                 }
             }
+            show_in_interest(&mut self.targets.visibility, entity);
+        }
+
+        let moved = self
+            .linked
+            .apply_snapshot(&mut self.commands, &data.actors, &data.positions);
+        for entity in moved {
+            show_in_interest(&mut self.targets.visibility, entity);
+        }
+    }
+}
+
+/// Shows an entity hidden by [`ServerMessages::OutOfInterest`] once it is synced again.
+fn show_in_interest(visibility_query: &mut Query<&mut Visibility>, entity: Entity) {
+    if let Ok(mut visibility) = visibility_query.get_mut(entity) {
+        if *visibility == Visibility::Hidden {
+            *visibility = Visibility::Inherited;
         }
     }
 }
@@ -0,0 +1,74 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::ecs::system::Resource;
+use renet::ClientId;
+
+use crate::world::LinkId;
+
+use super::ActorTransportData;
+
+/// Position change (units) under which an actor is considered at rest.
+const POSITION_EPSILON: f32 = 1e-3;
+/// Rotation change (`1 - |dot|` of the quaternions) under which an actor is considered at rest.
+const ROTATION_EPSILON: f32 = 1e-6;
+/// Every this many snapshots all actors are sent, in case an unreliable update got lost.
+const KEYFRAME_INTERVAL: u32 = 60;
+
+/// Last actor transforms sent by the host, actors at rest are left out of snapshots.
+#[derive(Debug, Default, Resource)]
+pub struct ActorDelta {
+    last_sent: HashMap<LinkId, ActorTransportData>,
+    known_clients: HashSet<ClientId>,
+    since_keyframe: u32,
+    snapshot: u64,
+}
+
+impl ActorDelta {
+    /// Returns the `due` actors that moved since they were last sent
+    /// and remembers their current state.
+    ///
+    /// Actors that are not due keep the state they were last sent with,
+    /// see [`SyncPolicy`](super::sync_policy::SyncPolicy).
+    pub fn moved(
+        &mut self,
+        actors: &HashMap<LinkId, ActorTransportData>,
+        due: impl Fn(&LinkId) -> bool,
+    ) -> HashSet<LinkId> {
+        self.last_sent.retain(|link_id, _| actors.contains_key(link_id));
+
+        let mut moved = HashSet::new();
+        for (link_id, data) in actors.iter().filter(|(link_id, _)| due(link_id)) {
+            let at_rest = self.last_sent.get(link_id).is_some_and(|last| {
+                last.position.distance(data.position) < POSITION_EPSILON
+                    && 1. - last.rotation.dot(data.rotation).abs() < ROTATION_EPSILON
+            });
+            if !at_rest {
+                self.last_sent.insert(link_id.clone(), data.clone());
+                moved.insert(link_id.clone());
+            }
+        }
+        moved
+    }
+
+    /// Number of the current snapshot, counted by [`ActorDelta::resend_all`].
+    pub fn snapshot(&self) -> u64 {
+        self.snapshot
+    }
+
+    /// Whether this snapshot must contain every actor:
+    /// clients connected since the previous call or a periodic keyframe is due.
+    pub fn resend_all(&mut self, clients: &[ClientId]) -> bool {
+        let has_new = clients
+            .iter()
+            .any(|client_id| !self.known_clients.contains(client_id));
+        self.known_clients = clients.iter().copied().collect();
+
+        self.snapshot += 1;
+        self.since_keyframe += 1;
+        if has_new || self.since_keyframe >= KEYFRAME_INTERVAL {
+            self.since_keyframe = 0;
+            return true;
+        }
+        false
+    }
+}
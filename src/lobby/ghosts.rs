@@ -0,0 +1,177 @@
+//! Server collider ghosts: a client sees the character colliders of the host simulation as
+//! translucent boxes, to compare them with what the snapshots show. Toggled with the `ghosts`
+//! console command, the host only sends them to the clients that asked.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use renet::{ClientId, RenetClient, RenetServer};
+
+use crate::actor::character::HALPH_PLAYER_SIZE;
+use crate::console::{no_args, CommandResult, CommandScope, ConsoleAppExt};
+use crate::network::Channel;
+use crate::world::net_sync_tick;
+
+use super::client::send_to_server;
+use super::host::server_sync_actor;
+use super::outbox::ServerOutbox;
+use super::{Character, ClientMessages, LobbyState, PlayerId, ServerMessages};
+
+const GHOST_COLOR: Color = Color::rgba(1., 0., 1., 0.3);
+
+/// Clients that asked for [`ServerMessages::ColliderGhosts`], kept by the host.
+#[derive(Debug, Default, Resource)]
+pub struct ColliderGhostSubscribers(HashSet<ClientId>);
+
+/// Character collider positions last received from the host, drawn while `enabled`.
+#[derive(Debug, Default, Resource)]
+pub struct ColliderGhosts {
+    pub enabled: bool,
+    positions: HashMap<PlayerId, (Vec3, Quat)>,
+}
+
+/// Box drawn at the host position of the character of a player
+#[derive(Component)]
+struct ColliderGhost(PlayerId);
+
+pub struct ColliderGhostPlugins;
+
+impl Plugin for ColliderGhostPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ColliderGhostSubscribers>()
+            .init_resource::<ColliderGhosts>()
+            .add_systems(
+                FixedUpdate,
+                send_collider_ghosts.after(server_sync_actor).run_if(
+                    in_state(LobbyState::Host)
+                        .and_then(resource_exists::<RenetServer>)
+                        .and_then(net_sync_tick),
+                ),
+            )
+            .add_systems(Update, update_ghosts.run_if(in_state(LobbyState::Client)))
+            .add_systems(OnExit(LobbyState::Host), clear_subscribers)
+            .add_systems(OnExit(LobbyState::Client), clear_ghosts)
+            .add_console_command("ghosts", "", CommandScope::Local, no_args, toggle_ghosts);
+    }
+}
+
+/// Adds or removes a client asking with [`ClientMessages::RequestColliderGhosts`].
+pub fn subscribe(world: &mut World, client_id: ClientId, enabled: bool) {
+    let mut subscribers = world.resource_mut::<ColliderGhostSubscribers>();
+    if enabled {
+        subscribers.0.insert(client_id);
+    } else {
+        subscribers.0.remove(&client_id);
+    }
+}
+
+/// Keeps the positions of a [`ServerMessages::ColliderGhosts`], late ones are dropped once off.
+pub fn receive(world: &mut World, positions: HashMap<PlayerId, (Vec3, Quat)>) {
+    let mut ghosts = world.resource_mut::<ColliderGhosts>();
+    if ghosts.enabled {
+        ghosts.positions = positions;
+    }
+}
+
+/// Sent next to the snapshot of the tick, reliably like everything else in the outbox.
+fn send_collider_ghosts(
+    server: Res<RenetServer>,
+    mut subscribers: ResMut<ColliderGhostSubscribers>,
+    character_query: Query<(&Character, &Transform)>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    if subscribers.0.is_empty() {
+        return;
+    }
+    // a disconnected client does not unsubscribe
+    let clients = server.clients_id();
+    subscribers.0.retain(|client_id| clients.contains(client_id));
+
+    let positions: HashMap<PlayerId, (Vec3, Quat)> = character_query
+        .iter()
+        .map(|(character, transform)| (character.id, (transform.translation, transform.rotation)))
+        .collect();
+    for client_id in subscribers.0.iter() {
+        outbox.queue_for(*client_id, ServerMessages::ColliderGhosts(positions.clone()));
+    }
+}
+
+fn update_ghosts(
+    mut commands: Commands,
+    ghosts: Res<ColliderGhosts>,
+    mut ghost_query: Query<(Entity, &ColliderGhost, &mut Transform)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut handles: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+) {
+    let mut shown = HashSet::new();
+    for (entity, ghost, mut transform) in ghost_query.iter_mut() {
+        match ghosts.positions.get(&ghost.0) {
+            Some((position, rotation)) => {
+                transform.translation = *position;
+                transform.rotation = *rotation;
+                shown.insert(ghost.0);
+            }
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+
+    let (mesh, material) = handles.get_or_insert_with(|| {
+        let size = HALPH_PLAYER_SIZE * 2.;
+        let mesh = meshes.add(Cuboid::new(size, size, size));
+        let material = materials.add(StandardMaterial {
+            base_color: GHOST_COLOR,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        });
+        (mesh, material)
+    });
+    for (id, (position, rotation)) in ghosts.positions.iter() {
+        if shown.contains(id) {
+            continue;
+        }
+        commands.spawn((
+            ColliderGhost(*id),
+            NotShadowCaster,
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(*position).with_rotation(*rotation),
+                ..default()
+            },
+        ));
+    }
+}
+
+fn toggle_ghosts(world: &mut World, _: ()) -> CommandResult {
+    if *world.resource::<State<LobbyState>>().get() != LobbyState::Client {
+        return Err("ghosts show the host simulation, only a client has them".into());
+    }
+    let enabled = !world.resource::<ColliderGhosts>().enabled;
+    let mut client = world
+        .get_resource_mut::<RenetClient>()
+        .ok_or_else(|| "not connected".to_string())?;
+    send_to_server(&mut client, &ClientMessages::RequestColliderGhosts(enabled), Channel::Control);
+
+    let mut ghosts = world.resource_mut::<ColliderGhosts>();
+    ghosts.enabled = enabled;
+    ghosts.positions.clear();
+    Ok(Some(format!("collider ghosts {}", if enabled { "on" } else { "off" })))
+}
+
+fn clear_subscribers(mut subscribers: ResMut<ColliderGhostSubscribers>) {
+    subscribers.0.clear();
+}
+
+fn clear_ghosts(
+    mut commands: Commands,
+    mut ghosts: ResMut<ColliderGhosts>,
+    ghost_query: Query<Entity, With<ColliderGhost>>,
+) {
+    *ghosts = ColliderGhosts::default();
+    for entity in ghost_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,144 +1,490 @@
-use std::net::UdpSocket;
-use std::time::SystemTime;
-
-use crate::actor::character::{spawn_character, spawn_tied_camera, TiedCamera};
-use crate::actor::UnloadActorsEvent;
-use crate::component::{DespawnReason, Respawn};
-use crate::core::{KnownLevel};
-use crate::lobby::{LobbyState, PlayerData, PlayerId, ServerMessages, Username};
-use crate::world::{LinkId, Me, SpawnProperty};
-use bevy::app::{App, Plugin, Update};
+use std::collections::HashMap;
+
+use crate::actor::character::{
+    spawn_character, spawn_tied_camera, CharacterAnimation, JumpRequest, TiedCamera,
+};
+use crate::actor::{validate_impulse, FireRequest, Owner, PrefabId, Prop, UnloadActorsEvent};
+use crate::component::{DespawnReason, Health, HealthChangedEvent, Respawn};
+use crate::core::CoreConfig;
+use crate::level::{level_checksum, level_environment, level_physics};
+use crate::lobby::{ConnectInfo, LobbyState, PlayerData, PlayerId, ServerMessages};
+use crate::network::{
+    connection_config, decode, new_server_transport, payload_head, Channel, ChunkSender,
+    DecodeError, HostAddresses, MAX_CLIENT_MESSAGE_SIZE, MAX_UNCHUNKED_SIZE,
+};
+use crate::replay::{ReplayChannel, ReplayRecorder};
+use crate::settings::{CameraMode, Settings};
+use crate::tr;
+use crate::world::{
+    net_sync_tick, ChangeEnvironmentEvent, ChangePhysicsEvent, DayPhase, EnvironmentSettings,
+    LevelPhysics, LinkId, Me, SeededSpawn, SimulationTick,
+};
+use bevy::app::{App, AppExit, FixedUpdate, Last, Plugin, Update};
 use bevy::ecs::entity::Entity;
 use bevy::ecs::event::{Event, EventReader, EventWriter};
-use bevy::ecs::query::With;
+use bevy::ecs::query::{Has, With};
 use bevy::ecs::schedule::{Condition, NextState, OnExit};
-use bevy::ecs::system::{Query, Res, ResMut};
+use bevy::ecs::system::{Query, Res, ResMut, Resource, SystemParam};
+use bevy::ecs::world::World;
 use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::log::info_span;
+use bevy::math::{Quat, Vec3};
+use bevy::time::Time;
+use bevy::transform::components::Transform;
 
-use bevy::prelude::{in_state, Color, Commands, IntoSystemConfigs, OnEnter};
+use bevy::prelude::{in_state, resource_exists, Color, Commands, IntoSystemConfigs, OnEnter};
+use bevy_rapier3d::prelude::ExternalImpulse;
 use bevy_renet::transport::NetcodeServerPlugin;
 use bevy_renet::RenetServerPlugin;
-use renet::transport::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
-use renet::{ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
+use renet::transport::NetcodeServerTransport;
+use renet::{ClientId, RenetServer, ServerEvent};
 
+use super::admin::{AdminCommandRequest, AdminPlugins, BanList, KickPlayerEvent};
+use super::approval::{
+    approve_connections, ApprovalPlugins, ClientApprovedEvent, ConnectionRequestEvent,
+    PendingApproval,
+};
+use super::afk::{AfkRules, AfkSpectator};
+use super::bots::{BotPlugins, MAX_BOTS};
+use super::host_address::HostAddressPlugins;
+use super::host_settings::{HostSettings, ServerRules};
+use super::delta::ActorDelta;
+use super::migration::{MigratedSession, MigrationRoster};
+use super::outbox::{OutboxPlugins, ServerOutbox};
+use super::ready::{MatchSetup, ReadyRequest};
+use super::interest::{filter_snapshot, snapshot_keys, ClientInterest, InterestKey};
+use super::quick_chat::{ChatEvent, ChatFeed, QuickChatEvent, QuickChatLimiter};
+use super::sync_policy::{split_snapshot, SyncBandwidth, SyncPolicy, SyncPolicyPlugins};
+use super::team::{balanced_team, team_color, team_sizes, team_spawn, TeamRules, TeamSwitchRequest};
+use super::validation::{
+    count_malformed, receive_input, CheatSuspectedEvent, InputRateLimiter, InputSequence,
+    MovementValidationPlugins,
+};
+use super::vote_kick::VoteKickPlugins;
+use super::word_filter::WordFilter;
 use super::{
-    ChangeMapLobbyEvent, Character, HostResource, LevelCode, Lobby, MapLoaderState, TransportDataResource, PROTOCOL_ID,
+    ActorTransportData, ChangeMapLobbyEvent, Character, ClientMessages, HostResource, Lobby,
+    MapLoaderState, NetworkSetupErrorEvent, PlayerDiedEvent, PlayerTransportData, PlayerView,
+    TransportDataResource, MAX_CHAT_LEN,
 };
 
+/// Host-side tuning of the session.
+#[derive(Debug, Clone, Resource)]
+pub struct ServerSettings {
+    /// Players and actors farther than this from a client character are not synced to that client,
+    /// `0` disables the filtering.
+    pub interest_radius: f32,
+    /// Disconnect players as soon as they are suspected of cheating.
+    pub kick_cheaters: bool,
+    /// Splits the players into two teams, `None` plays without teams.
+    pub teams: Option<TeamRules>,
+    /// Warns and then removes inactive clients, `None` lets them stay.
+    pub afk: Option<AfkRules>,
+    /// Lets clients run admin chat commands after `/login <password>`, from `ADMIN_PASSWORD`.
+    /// `None` leaves them to the host
+    pub admin_password: Option<String>,
+    /// Bots playing along with the clients, up to [`MAX_BOTS`]
+    pub bots: usize,
+    /// Level started once every player is ready, `None` keeps the players in the hub.
+    pub match_setup: Option<MatchSetup>,
+    /// Players including the host, see [`HostSettings::max_players`]
+    pub max_players: usize,
+    /// Seconds between a death and the respawn of a character
+    pub respawn_delay: f32,
+    /// Events kept in the [`SessionLog`](super::session_log::SessionLog), the oldest are dropped
+    pub session_log_capacity: usize,
+    /// Clients may ask for the session log, see [`ServerRules::share_session_log`]
+    pub share_session_log: bool,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            interest_radius: 200.,
+            kick_cheaters: false,
+            teams: None,
+            afk: None,
+            admin_password: std::env::var("ADMIN_PASSWORD").ok(),
+            bots: 0,
+            match_setup: None,
+            max_players: HostSettings::MAX_PLAYERS,
+            respawn_delay: 0.,
+            session_log_capacity: 10_000,
+            share_session_log: false,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// The part of the settings the clients are told, see [`ServerMessages::RulesUpdate`].
+    pub fn rules(&self) -> ServerRules {
+        ServerRules {
+            respawn_delay: self.respawn_delay,
+            friendly_fire: self.teams.is_some_and(|rules| rules.friendly_fire),
+            share_session_log: self.share_session_log,
+        }
+    }
+}
+
 #[derive(Debug, Event)]
 pub struct DespawnActorEvent(pub LinkId);
 #[derive(Debug, Event)]
-pub struct SpawnProjectileEvent(pub LinkId, pub Color);
+pub struct SpawnProjectileEvent {
+    /// Taken from the [`LinkIdAllocator`](crate::world::LinkIdAllocator)
+    pub link_id: LinkId,
+    pub owner: PlayerId,
+}
 
 pub struct HostLobbyPlugins;
 
 impl Plugin for HostLobbyPlugins {
     fn build(&self, app: &mut App) {
-        app.add_event::<DespawnActorEvent>()
+        app.init_resource::<ServerSettings>()
+            .add_event::<DespawnActorEvent>()
             .add_event::<SpawnProjectileEvent>()
-            .add_plugins((RenetServerPlugin, NetcodeServerPlugin))
+            .add_plugins((
+                RenetServerPlugin,
+                NetcodeServerPlugin,
+                MovementValidationPlugins,
+                OutboxPlugins,
+                SyncPolicyPlugins,
+                AdminPlugins,
+                ApprovalPlugins,
+                VoteKickPlugins,
+                BotPlugins,
+                HostAddressPlugins,
+            ))
             .add_systems(OnEnter(LobbyState::Host), setup)
             .add_systems(
                 Update,
-                (send_change_map, spawn_projectile, despawn_actor)
-                    .run_if(in_state(LobbyState::Host)),
+                (
+                    send_change_map,
+                    send_physics_change,
+                    send_environment_change,
+                    spawn_projectile,
+                    despawn_actor,
+                    broadcast_player_deaths,
+                    broadcast_health_changes,
+                    handle_cheat_suspects,
+                )
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
             )
             .add_systems(
                 Update,
-                server_update_system.run_if(in_state(LobbyState::Host)),
+                (
+                    server_update_system,
+                    approve_connections,
+                    admit_clients,
+                    send_joining_environment,
+                    server_receive_messages,
+                )
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(
+                FixedUpdate,
+                server_sync_actor.run_if(
+                    in_state(LobbyState::Host)
+                        .and_then(resource_exists::<RenetServer>)
+                        .and_then(net_sync_tick),
+                ),
+            )
+            .add_systems(
+                Last,
+                shutdown_on_exit.run_if(resource_exists::<RenetServer>),
             )
             .add_systems(OnExit(LobbyState::Host), teardown)
             .add_systems(
                 Update,
-                load_processing
-                    .run_if(in_state(LobbyState::Host).and_then(in_state(MapLoaderState::No))),
+                load_processing.run_if(
+                    in_state(LobbyState::Host)
+                        .and_then(resource_exists::<RenetServer>)
+                        .and_then(in_state(MapLoaderState::No)),
+                ),
             );
     }
 }
 
+/// Broadcasts a message over [`Channel::Control`], recording it into the replay if any.
+pub fn broadcast_reliable(
+    server: &mut RenetServer,
+    recorder: Option<&mut ReplayRecorder>,
+    message: Vec<u8>,
+) {
+    if let Some(recorder) = recorder {
+        recorder.record(ReplayChannel::Reliable, &message);
+    }
+    server.broadcast_message(Channel::Control, message);
+}
+
 pub fn spawn_projectile(
+    mut commands: Commands,
     mut event_reader: EventReader<SpawnProjectileEvent>,
-    mut server: ResMut<RenetServer>,
+    lobby: Res<Lobby>,
+    link_query: Query<(Entity, &LinkId)>,
+    mut outbox: ResMut<ServerOutbox>,
 ) {
-    for SpawnProjectileEvent(link_id, color) in event_reader.read() {
-        let message = bincode::serialize(&ServerMessages::ProjectileSpawn {
+    for SpawnProjectileEvent { link_id, owner } in event_reader.read() {
+        let color = lobby
+            .player(owner)
+            .map(|player_data| player_data.color)
+            .unwrap_or(Color::WHITE);
+        if let Some((entity, _)) = link_query.iter().find(|(_, id)| *id == link_id) {
+            commands.entity(entity).insert(Owner(*owner));
+        }
+
+        outbox.queue(ServerMessages::ProjectileSpawn {
             id: link_id.clone(),
-            color: *color,
-        })
-        .unwrap();
-        server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+            prefab: PrefabId::PROJECTILE,
+            owner: *owner,
+            color,
+        });
     }
 }
 
 pub fn despawn_actor(
     mut event_reader: EventReader<DespawnActorEvent>,
-    mut server: ResMut<RenetServer>,
+    mut outbox: ResMut<ServerOutbox>,
 ) {
     for DespawnActorEvent(link_id) in event_reader.read() {
-        let message = bincode::serialize(&ServerMessages::ActorDespawn {
+        outbox.queue(ServerMessages::ActorDespawn {
             id: link_id.clone(),
+        });
+    }
+}
+
+pub fn broadcast_health_changes(
+    mut event_reader: EventReader<HealthChangedEvent>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    for HealthChangedEvent {
+        id,
+        health,
+        attacker,
+    } in event_reader.read()
+    {
+        let message = bincode::serialize(&ServerMessages::HealthChanged {
+            id: *id,
+            health: *health,
+            attacker: *attacker,
         })
         .unwrap();
-        server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+        broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
     }
 }
 
-pub fn new_renet_server(addr: &str) -> (RenetServer, NetcodeServerTransport) {
-    let server = RenetServer::new(ConnectionConfig::default());
-
-    let public_addr = addr.parse().unwrap();
-    let socket = UdpSocket::bind(public_addr).unwrap();
-    let current_time = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
+pub fn broadcast_player_deaths(
+    mut event_reader: EventReader<PlayerDiedEvent>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    for PlayerDiedEvent { id, killer, cause } in event_reader.read() {
+        let message = bincode::serialize(&ServerMessages::PlayerDied {
+            id: *id,
+            killer: *killer,
+            cause: *cause,
+        })
         .unwrap();
-    let server_config = ServerConfig {
-        current_time,
-        max_clients: 64,
-        protocol_id: PROTOCOL_ID,
-        public_addresses: vec![public_addr],
-        authentication: ServerAuthentication::Unsecure,
+        broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
+    }
+}
+
+/// Reports suspected players in the chat feed and kicks them if [`ServerSettings::kick_cheaters`].
+pub fn handle_cheat_suspects(
+    mut event_reader: EventReader<CheatSuspectedEvent>,
+    mut server: ResMut<RenetServer>,
+    settings: Res<ServerSettings>,
+    lobby: Res<Lobby>,
+    mut chat_feed: ResMut<ChatFeed>,
+) {
+    for CheatSuspectedEvent(player_id) in event_reader.read() {
+        let username = lobby
+            .players
+            .get(player_id)
+            .map(|player_data| player_data.username.clone())
+            .unwrap_or_else(|| format!("{:?}", player_id));
+        chat_feed.push(
+            "Host".to_string(),
+            format!("{} is suspected of cheating", username),
+        );
+
+        if settings.kick_cheaters {
+            if let PlayerId::Client(client_id) = player_id {
+                log::info!("Kicking {} ({}): suspected of cheating", username, client_id);
+                server.disconnect(*client_id);
+            }
+        }
+    }
+}
+
+/// Serializes `message`, `None` with an error log if it cannot be.
+fn serialize_message(message: &ServerMessages) -> Option<Vec<u8>> {
+    match bincode::serialize(message) {
+        Ok(payload) => Some(payload),
+        Err(err) => {
+            log::error!("Failed to serialize {:?}: {}", message, err);
+            None
+        }
+    }
+}
+
+/// Serializes `message`, splitting it into [`ServerMessages::Chunk`]s if it is too large.
+///
+/// Nothing is sent of a message that cannot be serialized, see [`serialize_message`].
+fn serialize_chunked(message: &ServerMessages, chunk_sender: &mut ChunkSender) -> Vec<Vec<u8>> {
+    let Some(payload) = serialize_message(message) else {
+        return Vec::new();
+    };
+    if payload.len() <= MAX_UNCHUNKED_SIZE {
+        return vec![payload];
+    }
+
+    chunk_sender
+        .split(&payload)
+        .into_iter()
+        .map(|chunk| serialize_message(&ServerMessages::Chunk(chunk)))
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+/// Sends a message of any size to one client over [`Channel::Bulk`].
+pub fn send_large_message(
+    server: &mut RenetServer,
+    chunk_sender: &mut ChunkSender,
+    client_id: ClientId,
+    message: &ServerMessages,
+) {
+    for payload in serialize_chunked(message, chunk_sender) {
+        server.send_message(client_id, Channel::Bulk, payload);
+    }
+}
+
+pub fn new_renet_server(
+    addr: &str,
+) -> Result<(RenetServer, NetcodeServerTransport, HostAddresses), Box<dyn std::error::Error>> {
+    let server = RenetServer::new(connection_config());
+
+    let (transport, addresses) = new_server_transport(addr)?;
+
+    Ok((server, transport, addresses))
+}
+
+/// Broadcasts [`ServerMessages::ServerShutdown`] and disconnects every client.
+///
+/// The message is flushed to the socket before the disconnect packets so clients learn the
+/// reason instead of waiting for the netcode timeout.
+pub fn shutdown_server(
+    server: &mut RenetServer,
+    transport: &mut NetcodeServerTransport,
+    reason: &str,
+) {
+    log::info!("Shutting down server: {reason}");
+    let message = ServerMessages::ServerShutdown {
+        reason: reason.to_string(),
     };
+    if let Some(payload) = serialize_message(&message) {
+        server.broadcast_message(Channel::Control, payload);
+        transport.send_packets(server);
+    }
 
-    let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    transport.disconnect_all(server);
+}
 
-    (server, transport)
+fn shutdown_on_exit(
+    mut commands: Commands,
+    mut exit_event: EventReader<AppExit>,
+    mut server: ResMut<RenetServer>,
+    mut transport: ResMut<NetcodeServerTransport>,
+) {
+    if exit_event.read().next().is_some() {
+        shutdown_server(&mut server, &mut transport, "Host quit the game");
+        commands.remove_resource::<RenetServer>();
+        commands.remove_resource::<NetcodeServerTransport>();
+    }
 }
 
 fn setup(
     mut commands: Commands,
-    host_resource: Res<HostResource>,
+    mut host_resource: ResMut<HostResource>,
     mut change_map_event: EventWriter<ChangeMapLobbyEvent>,
+    mut setup_error_event: EventWriter<NetworkSetupErrorEvent>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+    migrated_session: Option<Res<MigratedSession>>,
+    config: Res<CoreConfig>,
 ) {
-    // resources for server
-    commands.init_resource::<TransportDataResource>();
-    commands.insert_resource(Lobby::default());
-
     // spanw server
-    let (server, transport) = new_renet_server(host_resource.address.clone().unwrap().as_str());
+    let address = host_resource.address.clone().unwrap_or_default();
+    let (server, transport, addresses) = match new_renet_server(&address) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("Failed to host on {}: {}", address, err);
+            setup_error_event.send(NetworkSetupErrorEvent(tr!(
+                "error.host",
+                address = address,
+                error = err
+            )));
+            next_state_lobby.set(LobbyState::None);
+            return;
+        }
+    };
+    log::info!("Hosting on {}, reachable on {}", addresses.bound, addresses.public);
+    host_resource.bound_address = Some(addresses.bound);
+    host_resource.public_address = Some(addresses.public);
     commands.insert_resource(server);
     commands.insert_resource(transport);
 
-    change_map_event.send(ChangeMapLobbyEvent(LevelCode::Known(KnownLevel::Hub)));
+    // resources for server
+    commands.init_resource::<TransportDataResource>();
+    commands.init_resource::<ChunkSender>();
+    commands.init_resource::<ClientInterest>();
+    commands.init_resource::<ActorDelta>();
+    commands.insert_resource(Lobby::default());
+    let word_filter = host_resource.word_filter.as_deref().map(WordFilter::load);
+    commands.insert_resource(word_filter.unwrap_or_default());
+
+    // a migrated session goes on where it was
+    let level = migrated_session
+        .map(|session| session.level.clone())
+        .unwrap_or_else(|| config.start_level.clone());
+    change_map_event.send(ChangeMapLobbyEvent(level));
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn load_processing(
     mut commands: Commands,
-    spawn_point: Res<SpawnProperty>,
+    spawn_point: SeededSpawn,
     mut lobby_res: ResMut<Lobby>,
     host_resource: Res<HostResource>,
+    settings: Res<ServerSettings>,
     query: Query<(), With<Me>>,
-    mut character_respawn_query: Query<&mut Respawn, With<Character>>,
+    mut character_respawn_query: Query<(&Character, &mut Respawn)>,
     mut next_state_map: ResMut<NextState<MapLoaderState>>,
+    mut outbox: ResMut<ServerOutbox>,
+    migrated_session: Option<Res<MigratedSession>>,
 ) {
-    log::info!("LoadProcessing: {:#?}", spawn_point);
-    if !spawn_point.is_empty() {
+    log::info!("LoadProcessing: {:#?}", *spawn_point.points);
+    if !spawn_point.points.is_empty() {
         if query.get_single().is_err() {
             // spawn host character
             lobby_res.players_seq += 1;
-            let color = generate_player_color(lobby_res.players_seq as u32);
+            let team = settings.teams.map(|_| balanced_team(&lobby_res));
+            let color = match team {
+                Some(team) => team_color(team, team_sizes(&lobby_res)[team.index()] as u32),
+                None => migrated_session
+                    .and_then(|session| session.own_color)
+                    .unwrap_or_else(|| generate_player_color(lobby_res.players_seq as u32)),
+            };
 
             let player_entity = commands
-                .spawn_character(PlayerId::HostOrSingle, color, spawn_point.random_point())
+                .spawn_character(
+                    PlayerId::HostOrSingle,
+                    color,
+                    spawn_point.first_spawn(
+                        &team_spawn(&spawn_point.points, team),
+                        lobby_res.players_seq as u32,
+                    ),
+                )
                 .insert(Me)
                 .id();
             commands.spawn_tied_camera(player_entity);
@@ -148,11 +494,23 @@ pub fn load_processing(
                 color,
                 host_resource.username.clone().unwrap(),
             );
+            lobby_res.me.team = team;
+
+            outbox.queue(ServerMessages::PlayerConnected {
+                id: PlayerId::HostOrSingle,
+                color,
+                username: lobby_res.me.username.clone(),
+                team,
+            });
         }
 
-        for mut respawn in character_respawn_query.iter_mut() {
-            respawn.replase_spawn_point(spawn_point.clone());
-            respawn.insert_reason(DespawnReason::Forced);
+        for (character, mut respawn) in character_respawn_query.iter_mut() {
+            let team = lobby_res.player(&character.id).and_then(|player_data| player_data.team);
+            respawn.replace_spawn_point(team_spawn(&spawn_point.points, team));
+            // a character that is already respawning will pick up the new spawn point
+            if !respawn.is_pending() {
+                respawn.insert_reason(DespawnReason::MapChange);
+            }
         }
 
         next_state_map.set(MapLoaderState::Yes);
@@ -162,25 +520,95 @@ pub fn load_processing(
 pub fn send_change_map(
     mut change_map_event: EventReader<ChangeMapLobbyEvent>,
     mut server: ResMut<RenetServer>,
-    // mut next_state_map: ResMut<NextState<MapState>>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
     mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    config: Res<CoreConfig>,
 ) {
-    for ChangeMapLobbyEvent(_state) in change_map_event.read() {
+    for ChangeMapLobbyEvent(level) in change_map_event.read() {
         // next_state_map.set(*state);
-        let message =
-            bincode::serialize(&ServerMessages::ChangeMap { /*map_state: *state*/ }).unwrap();
-        server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+        let environment = level_environment(level);
+        let message = bincode::serialize(&ServerMessages::ChangeMap {
+            level: level.clone(),
+            checksum: level_checksum(level),
+            physics: level_physics(level, config.default_physics),
+            phase: environment.start_phase(),
+            environment,
+        })
+        .unwrap();
+        broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
 
+        next_state_map.set(MapLoaderState::No);
         unload_actors_event.send(UnloadActorsEvent);
     }
 }
 
+fn send_physics_change(
+    mut change_physics_event: EventReader<ChangePhysicsEvent>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    for ChangePhysicsEvent(physics) in change_physics_event.read() {
+        let message =
+            bincode::serialize(&ServerMessages::ChangePhysics { physics: *physics }).unwrap();
+        broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
+    }
+}
+
+fn send_environment_change(
+    mut change_environment_event: EventReader<ChangeEnvironmentEvent>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    for ChangeEnvironmentEvent { environment, phase } in change_environment_event.read() {
+        let message = bincode::serialize(&ServerMessages::ChangeEnvironment {
+            environment: environment.clone(),
+            phase: *phase,
+        })
+        .unwrap();
+        broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
+    }
+}
+
+/// Sends the environment and the time of day to the clients admitted by [`admit_clients`],
+/// the day has gone on since the level was loaded.
+fn send_joining_environment(
+    mut approved_event: EventReader<ClientApprovedEvent>,
+    lobby: Res<Lobby>,
+    environment: Res<EnvironmentSettings>,
+    day_phase: Res<DayPhase>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    for ClientApprovedEvent { client_id, .. } in approved_event.read() {
+        if !lobby.players.contains_key(&PlayerId::Client(*client_id)) {
+            continue;
+        }
+        outbox.queue_for(
+            *client_id,
+            ServerMessages::ChangeEnvironment {
+                environment: environment.clone(),
+                phase: **day_phase,
+            },
+        );
+    }
+}
+
 fn teardown(
     mut commands: Commands,
+    server: Option<ResMut<RenetServer>>,
+    transport: Option<ResMut<NetcodeServerTransport>>,
     tied_camera_query: Query<Entity, With<TiedCamera>>,
     char_query: Query<Entity, With<Character>>,
     mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
 ) {
+    next_state_map.set(MapLoaderState::No);
+    if let (Some(mut server), Some(mut transport)) = (server, transport) {
+        shutdown_server(&mut server, &mut transport, "Host stopped the game");
+    }
+    commands.remove_resource::<RenetServer>();
+    commands.remove_resource::<NetcodeServerTransport>();
+
     for entity in tied_camera_query.iter() {
         commands.entity(entity).despawn_recursive();
     }
@@ -189,6 +617,10 @@ fn teardown(
     }
     commands.remove_resource::<Lobby>();
     commands.remove_resource::<TransportDataResource>();
+    commands.remove_resource::<ChunkSender>();
+    commands.remove_resource::<ClientInterest>();
+    commands.remove_resource::<ActorDelta>();
+    commands.remove_resource::<WordFilter>();
 
     unload_actors_event.send(UnloadActorsEvent);
 }
@@ -199,144 +631,718 @@ pub fn generate_player_color(player_number: u32) -> Color {
     Color::hsl(hue, 1.0, 0.5)
 }
 
+/// Turns new connections into [`ConnectionRequestEvent`]s, a banned username is refused at once,
+/// and cleans up after the clients that disconnect.
 #[allow(clippy::too_many_arguments)]
 pub fn server_update_system(
     mut server_events: EventReader<ServerEvent>,
     mut commands: Commands,
     mut lobby: ResMut<Lobby>,
-    mut server: ResMut<RenetServer>,
+    mut outbox: ResMut<ServerOutbox>,
     transport: Res<NetcodeServerTransport>,
-    spawn_point: Res<SpawnProperty>,
-    //map_state: ResMut<State<MapState>>,
-
-    //mut input_query: Query<&mut PlayerInputs>,
+    tick: Res<SimulationTick>,
+    ban_list: Res<BanList>,
+    word_filter: Res<WordFilter>,
+    mut pending: ResMut<PendingApproval>,
+    mut request_event: EventWriter<ConnectionRequestEvent>,
+    mut kick_event: EventWriter<KickPlayerEvent>,
 ) {
     for event in server_events.read() {
+        let (ServerEvent::ClientConnected { client_id }
+        | ServerEvent::ClientDisconnected { client_id, .. }) = event;
+        let _span = info_span!("connection", client_id = %client_id, tick = **tick).entered();
         match event {
             ServerEvent::ClientConnected { client_id } => {
                 log::info!("Player {} connected.", client_id);
 
-                // TODO remove
-                let message = bincode::serialize(&ServerMessages::InitConnection {
-                    id: *client_id,
-                    //map_state: *map_state.get(),
-                })
-                .unwrap();
-                server.send_message(*client_id, DefaultChannel::ReliableOrdered, message);
-
-                lobby.players_seq += 1;
-                let color = generate_player_color(lobby.players_seq as u32);
-
-                // Spawn player cube
-                let player_entity = commands
-                    .spawn_character(
-                        PlayerId::Client(*client_id),
-                        color,
-                        spawn_point.random_point(),
-                    )
-                    .id();
-
-                // We could send an InitState with all the players id and positions for the multiplayer
-                // but this is easier to do.
-                for (player_id, player_data) in &lobby.players {
-                    let message = bincode::serialize(&ServerMessages::PlayerConnected {
-                        id: *player_id,
-                        color: player_data.color,
-                        username: player_data.username.clone(),
-                    })
-                    .unwrap();
-                    server.send_message(*client_id, DefaultChannel::ReliableOrdered, message);
-                }
-
-                let data = transport.user_data(*client_id).unwrap();
-                let username = match Username::from_user_data(&data) {
-                    Ok(name) => name,
-                    Err(_) => "@corapted@".to_string(),
+                let Some(data) = transport.user_data(*client_id) else {
+                    log::warn!("No connect data of {}, already disconnected", client_id);
+                    continue;
                 };
-                // let username = "noname".to_string();
-
-                lobby.players.insert(
-                    PlayerId::Client(*client_id),
-                    PlayerData::new(player_entity, color, username.clone()),
-                );
+                let info = match ConnectInfo::from_user_data(&data) {
+                    Ok(info) => info,
+                    Err(err) => {
+                        log::warn!(
+                            "Malformed connect data of {}: {} {}",
+                            client_id,
+                            err,
+                            payload_head(&data)
+                        );
+                        ConnectInfo {
+                            username: "@corapted@".to_string(),
+                            password: None,
+                        }
+                    }
+                };
+                if ban_list.0.contains(&info.username) {
+                    kick_event.send(KickPlayerEvent {
+                        client_id: *client_id,
+                        reason: format!("{} is banned", info.username),
+                    });
+                    continue;
+                }
+                let username = word_filter.username(info.username.clone(), *client_id);
 
-                let message = bincode::serialize(&ServerMessages::PlayerConnected {
-                    id: PlayerId::Client(*client_id),
-                    color,
+                pending.insert(*client_id, username.clone());
+                request_event.send(ConnectionRequestEvent {
+                    client_id: *client_id,
                     username,
-                })
-                .unwrap();
-                server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+                    info,
+                });
             }
             ServerEvent::ClientDisconnected { client_id, reason } => {
                 log::info!("Player {} disconnected: {}", client_id, reason);
+                // never joined, the others do not know of it
+                if pending.remove(*client_id) {
+                    continue;
+                }
                 if let Some(player_data) = lobby.players.remove(&PlayerId::Client(*client_id)) {
-                    commands.entity(player_data.entity()).despawn();
+                    despawn_player(&mut commands, PlayerId::Client(*client_id), &player_data);
                 }
 
-                let message = bincode::serialize(&ServerMessages::PlayerDisconnected {
+                outbox.queue(ServerMessages::PlayerDisconnected {
                     id: PlayerId::Client(*client_id),
-                })
-                .unwrap();
-                server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+                });
             }
         }
     }
+}
+
+/// Despawns the character of a player that left, with everything attached under it.
+fn despawn_player(commands: &mut Commands, id: PlayerId, player_data: &PlayerData) {
+    match player_data.entity() {
+        Some(entity) => commands.entity(entity).despawn_recursive(),
+        None => log::warn!("Player {:?} had no character to despawn", id),
+    }
+}
+
+/// Spawns the character of the clients approved by [`approve_connections`] and tells them the
+/// state of the session, then tells the others about them.
+#[allow(clippy::too_many_arguments)]
+pub fn admit_clients(
+    mut approved_event: EventReader<ClientApprovedEvent>,
+    mut commands: Commands,
+    mut lobby: ResMut<Lobby>,
+    mut outbox: ResMut<ServerOutbox>,
+    spawn_point: SeededSpawn,
+    settings: Res<ServerSettings>,
+    host_character_query: Query<(), With<Me>>,
+    health_query: Query<(&Character, &Health)>,
+    spectator_query: Query<&Character, With<AfkSpectator>>,
+    migrated_session: Option<Res<MigratedSession>>,
+    level_physics: Res<LevelPhysics>,
+    tick: Res<SimulationTick>,
+) {
+    for ClientApprovedEvent {
+        client_id,
+        username,
+    } in approved_event.read()
+    {
+        let _span = info_span!("connection", client_id = %client_id, tick = **tick).entered();
+        log::info!("Player {} joins as {}.", client_id, username);
+        let username = username.clone();
 
+        // TODO remove
+        outbox.queue_for(
+            *client_id,
+            ServerMessages::InitConnection {
+                id: *client_id,
+                seed: *spawn_point.seed,
+                //map_state: *map_state.get(),
+            },
+        );
+
+        // the level may have been changed since it was loaded
+        outbox.queue_for(
+            *client_id,
+            ServerMessages::ChangePhysics {
+                physics: *level_physics,
+            },
+        );
+
+        lobby.players_seq += 1;
+        let team = settings.teams.map(|_| balanced_team(&lobby));
+        let color = match team {
+            Some(team) => team_color(team, team_sizes(&lobby)[team.index()] as u32),
+            None => migrated_session
+                .as_ref()
+                .and_then(|session| session.colors.get(client_id).copied())
+                .unwrap_or_else(|| generate_player_color(lobby.players_seq as u32)),
+        };
+
+        // Spawn player cube
+        let player_entity = commands
+            .spawn_character(
+                PlayerId::Client(*client_id),
+                color,
+                spawn_point.first_spawn(
+                    &team_spawn(&spawn_point.points, team),
+                    lobby.players_seq as u32,
+                ),
+            )
+            .id();
+
+        // We could send an InitState with all the players id and positions for the multiplayer
+        // but this is easier to do.
+        if !host_character_query.is_empty() {
+            outbox.queue_for(
+                *client_id,
+                ServerMessages::PlayerConnected {
+                    id: PlayerId::HostOrSingle,
+                    color: lobby.me.color,
+                    username: lobby.me.username.clone(),
+                    team: lobby.me.team,
+                },
+            );
+        }
+        for (player_id, player_data) in &lobby.players {
+            outbox.queue_for(
+                *client_id,
+                ServerMessages::PlayerConnected {
+                    id: *player_id,
+                    color: player_data.color,
+                    username: player_data.username.clone(),
+                    team: player_data.team,
+                },
+            );
+        }
+        // full health is the default on the client
+        for (character, health) in health_query.iter() {
+            if health.current == health.max {
+                continue;
+            }
+            outbox.queue_for(
+                *client_id,
+                ServerMessages::HealthChanged {
+                    id: character.id,
+                    health: *health,
+                    attacker: None,
+                },
+            );
+        }
+        // visible is the default on the client
+        for character in spectator_query.iter() {
+            outbox.queue_for(
+                *client_id,
+                ServerMessages::PlayerSpectating {
+                    id: character.id,
+                    spectating: true,
+                },
+            );
+        }
+
+        let mut player_data = PlayerData::new(player_entity, color, username.clone());
+        player_data.team = team;
+        player_data.last_activity = **tick;
+        lobby.players.insert(PlayerId::Client(*client_id), player_data);
+
+        outbox.queue(ServerMessages::PlayerConnected {
+            id: PlayerId::Client(*client_id),
+            color,
+            username,
+            team,
+        });
+    }
+}
+
+/// What a chat message of a client goes through before it is broadcast.
+#[derive(SystemParam)]
+pub struct ChatGuard<'w> {
+    time: Res<'w, Time>,
+    limiter: ResMut<'w, QuickChatLimiter>,
+    word_filter: Res<'w, WordFilter>,
+}
+
+/// Handles [`ClientMessages`], malformed or invalid requests are dropped without disconnecting.
+///
+/// Only a [`ClientMessages::Hello`] of another version disconnects the client.
+#[allow(clippy::too_many_arguments)]
+pub fn server_receive_messages(
+    mut commands: Commands,
+    mut lobby: ResMut<Lobby>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    pending: Res<PendingApproval>,
+    mut chat_guard: ChatGuard,
+    mut quick_chat_event: EventWriter<QuickChatEvent>,
+    mut chat_event: EventWriter<ChatEvent>,
+    mut migration_roster: ResMut<MigrationRoster>,
+    transform_query: Query<&Transform>,
+    prop_query: Query<(Entity, &Transform, &LinkId), With<Prop>>,
+    tick: Res<SimulationTick>,
+    mut input_limiter: ResMut<InputRateLimiter>,
+    mut team_switch_request: EventWriter<TeamSwitchRequest>,
+    mut admin_command_request: EventWriter<AdminCommandRequest>,
+    #[cfg(all(debug_assertions, feature = "dev"))] mut conditioner: ResMut<
+        crate::network::LinkConditioner,
+    >,
+) {
     for client_id in server.clients_id().into_iter() {
-        let _first = true;
-        while let Some(_message) = server.receive_message(client_id, DefaultChannel::ReliableOrdered)
+        // read once the client is admitted, e.g. its `Hello`
+        if pending.contains(client_id) {
+            continue;
+        }
+        let player_id = PlayerId::Client(client_id);
+        let mut messages = Vec::new();
+        while let Some(message) = server.receive_message(client_id, Channel::State) {
+            messages.push(message.to_vec());
+        }
+        #[cfg(all(debug_assertions, feature = "dev"))]
+        let messages = conditioner.condition(Some(client_id), messages);
+        for message in messages {
+            let message =
+                read_client_message(&mut lobby, player_id, **tick, Channel::State, &message);
+            let Some(player_data) = lobby.players.get(&player_id) else {
+                continue;
+            };
+            match message {
+                Ok(ClientMessages::Input { movement, sequence }) => {
+                    if !input_limiter.allow(player_id, **tick) {
+                        log::debug!("Dropped input of {:?}: rate limited", player_id);
+                        continue;
+                    }
+                    let Some(entity) = player_data.entity() else {
+                        log::warn!("Dropped input of {:?}: no character", player_id);
+                        continue;
+                    };
+                    // out of the system params, it checks the sequence of the character
+                    commands.add(move |world: &mut World| {
+                        receive_input(world, entity, movement, sequence);
+                    });
+                }
+                Ok(message) => log::warn!(
+                    "Unexpected unreliable message from {:?}: {:?}",
+                    player_id,
+                    message
+                ),
+                Err(_) => commands.add(move |world: &mut World| {
+                    count_malformed(world, client_id);
+                }),
+            }
+        }
+
+        while let Some(message) = server.receive_message(client_id, Channel::Control)
         {
-            // let input: Inputs = bincode::deserialize(&message).unwrap();
-            if let Some(_player_data) = lobby.players.get(&PlayerId::Client(client_id)) {
-                // TODO:
-                // if let Ok(mut player_input) = input_query.get_mut(player_data.entity()) {
-                //     if first {
-                //         player_input.insert_inputs(input);
-                //         first = false;
-                //     } else {
-                //         player_input.add(input);
-                //     }
-                // }
-            } else {
+            let message =
+                read_client_message(&mut lobby, player_id, **tick, Channel::Control, &message);
+            let Some(player_data) = lobby.players.get(&player_id) else {
                 log::error!("Player not found");
+                continue;
+            };
+
+            match message {
+                Ok(ClientMessages::QuickChat { kind, world_pos }) => {
+                    // spam is dropped silently, the client is not punished for it
+                    if !chat_guard.limiter.allow(player_id, chat_guard.time.elapsed_seconds()) {
+                        log::debug!("Dropped quick chat of {:?}: rate limited", player_id);
+                        continue;
+                    }
+                    let world_pos = world_pos.filter(|pos| pos.is_finite());
+                    let message = bincode::serialize(&ServerMessages::QuickChat {
+                        from: player_id,
+                        kind,
+                        world_pos,
+                    })
+                    .unwrap();
+                    broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
+                    quick_chat_event.send(QuickChatEvent {
+                        from: player_id,
+                        kind,
+                        world_pos,
+                    });
+                }
+                Ok(ClientMessages::ImpulseRequest { target, impulse }) => {
+                    let character = player_data
+                        .entity()
+                        .and_then(|entity| transform_query.get(entity).ok());
+                    let Some(character) = character else {
+                        continue;
+                    };
+                    let Some((entity, prop, _)) =
+                        prop_query.iter().find(|(_, _, link_id)| **link_id == target)
+                    else {
+                        log::debug!("Dropped impulse of {:?}: no prop {:?}", player_id, target);
+                        continue;
+                    };
+                    match validate_impulse(character.translation, prop.translation, impulse) {
+                        Some(impulse) => {
+                            commands.entity(entity).insert(ExternalImpulse {
+                                impulse,
+                                ..Default::default()
+                            });
+                        }
+                        None => log::debug!("Dropped impulse of {:?}: out of range", player_id),
+                    }
+                }
+                Ok(ClientMessages::Chat { text }) => {
+                    if !chat_guard.limiter.allow(player_id, chat_guard.time.elapsed_seconds()) {
+                        log::debug!("Dropped chat of {:?}: rate limited", player_id);
+                        continue;
+                    }
+                    let text = truncate_chat(text.trim());
+                    // commands, and the passwords in them, are not broadcast
+                    if text.starts_with('/') {
+                        admin_command_request.send(AdminCommandRequest {
+                            from: player_id,
+                            text,
+                        });
+                        continue;
+                    }
+                    if text.is_empty() {
+                        continue;
+                    }
+                    let text = chat_guard.word_filter.mask(&text);
+                    let message = bincode::serialize(&ServerMessages::Chat {
+                        from: player_id,
+                        text: text.clone(),
+                    })
+                    .unwrap();
+                    broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
+                    chat_event.send(ChatEvent {
+                        from: player_id,
+                        text,
+                    });
+                }
+                Ok(ClientMessages::Hello { version }) => {
+                    if version != env!("CARGO_PKG_VERSION") {
+                        log::warn!(
+                            "Disconnecting {:?}: version {} does not match the host {}",
+                            player_id,
+                            version,
+                            env!("CARGO_PKG_VERSION")
+                        );
+                        server.disconnect(client_id);
+                        break;
+                    }
+                }
+                Ok(ClientMessages::MigrationPort { port }) => {
+                    migration_roster.advertise(client_id, port);
+                }
+                Ok(ClientMessages::Jump) => {
+                    let Some(entity) = player_data.entity() else {
+                        log::warn!("Dropped jump of {:?}: no character", player_id);
+                        continue;
+                    };
+                    commands.entity(entity).insert(JumpRequest);
+                }
+                Ok(ClientMessages::Fire) => {
+                    let Some(entity) = player_data.entity() else {
+                        log::warn!("Dropped fire of {:?}: no character", player_id);
+                        continue;
+                    };
+                    commands.entity(entity).insert(FireRequest);
+                }
+                Ok(ClientMessages::SwitchTeam { team }) => {
+                    team_switch_request.send(TeamSwitchRequest {
+                        id: player_id,
+                        team,
+                    });
+                }
+                Ok(ClientMessages::SetReady(ready)) => {
+                    // out of the system params, see `ReadyPlugins`
+                    commands.add(move |world: &mut World| {
+                        world.send_event(ReadyRequest {
+                            id: player_id,
+                            ready,
+                        });
+                    });
+                }
+                Ok(ClientMessages::RequestColliderGhosts(enabled)) => {
+                    #[cfg(feature = "dev")]
+                    commands.add(move |world: &mut World| {
+                        super::ghosts::subscribe(world, client_id, enabled);
+                    });
+                    #[cfg(not(feature = "dev"))]
+                    log::debug!("Ghosts ({}) of {:?} ignored: not a dev build", enabled, player_id);
+                }
+                Ok(ClientMessages::RequestSessionLog) => {
+                    commands.add(move |world: &mut World| {
+                        super::session_log::send(world, client_id);
+                    });
+                }
+                Ok(ClientMessages::Input { .. }) => {
+                    log::warn!("Input of {:?} on the reliable channel, dropped", player_id)
+                }
+                Err(_) => commands.add(move |world: &mut World| {
+                    count_malformed(world, client_id);
+                }),
+            }
+        }
+    }
+}
+
+/// Deserializes a message of `player_id`, a deliberate one marks the player active.
+///
+/// A message that does not decode is logged, the caller counts it with [`count_malformed`].
+fn read_client_message(
+    lobby: &mut Lobby,
+    player_id: PlayerId,
+    tick: u64,
+    channel: Channel,
+    payload: &[u8],
+) -> Result<ClientMessages, DecodeError> {
+    let message = decode::<ClientMessages>(payload, MAX_CLIENT_MESSAGE_SIZE);
+    if let Err(err) = &message {
+        log::warn!(
+            "Dropped a message of {:?} on {:?}: {} {}",
+            player_id,
+            channel,
+            err,
+            payload_head(payload)
+        );
+    }
+    if message.as_ref().is_ok_and(ClientMessages::is_deliberate) {
+        if let Some(player_data) = lobby.players.get_mut(&player_id) {
+            player_data.last_activity = tick;
+        }
+    }
+    message
+}
+
+/// Cuts `text` to [`MAX_CHAT_LEN`] bytes on a char boundary.
+fn truncate_chat(text: &str) -> String {
+    let mut end = text.len().min(MAX_CHAT_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text[..end].to_string()
+}
+
+/// Sends positions of characters and linked actors, runs on [`net_sync_tick`] fixed ticks.
+///
+/// Each client only receives what is within [`ServerSettings::interest_radius`] of its character
+/// and is told with [`ServerMessages::OutOfInterest`] about what left that radius.
+/// Actors with a [`SyncPolicy`] are sent at its rate, on its channel.
+#[allow(clippy::too_many_arguments)]
+pub fn server_sync_actor(
+    mut server: ResMut<RenetServer>,
+    settings: Res<ServerSettings>,
+    local_settings: Res<Settings>,
+    lobby: Res<Lobby>,
+    mut interest: ResMut<ClientInterest>,
+    mut delta: ResMut<ActorDelta>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut bandwidth: ResMut<SyncBandwidth>,
+    // TODO a nahooya tut resours, daun
+    mut data: ResMut<TransportDataResource>,
+    character_query: Query<(
+        &Transform,
+        &PlayerView,
+        &Character,
+        Option<&CharacterAnimation>,
+        Option<&InputSequence>,
+        Has<AfkSpectator>,
+        Has<Me>,
+    )>,
+    moveble_actor_query: Query<(&Transform, &LinkId, Option<&SyncPolicy>)>,
+    transform_query: Query<&Transform>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    tick: Res<SimulationTick>,
+) {
+    let _span = info_span!("sync_send", tick = **tick).entered();
+    let data = &mut data.data;
+    let first_person = local_settings.camera_mode == CameraMode::FirstPerson;
+    for (transform, view_direction, character, animation, sequence, spectating, me) in
+        character_query.iter()
+    {
+        // a snapshot would show the hidden character again on the clients
+        if spectating {
+            data.players.remove(&character.id);
+            continue;
+        }
+        let mut player_view = *view_direction;
+        // tells the others the camera is at the head
+        if me && first_person {
+            player_view.distance = 0.;
+        }
+        data.players.insert(
+            character.id,
+            PlayerTransportData {
+                position: transform.translation,
+                rotation: transform.rotation,
+                player_view,
+                animation: animation.map(|animation| animation.state).unwrap_or_default(),
+                input_sequence: sequence.map_or(0, |sequence| sequence.applied),
+            },
+        );
+    }
+
+    let mut policies = HashMap::new();
+    for (transform, link_id, policy) in moveble_actor_query.iter() {
+        let policy = policy.copied().unwrap_or_default();
+        data.actors.insert(
+            link_id.clone(),
+            ActorTransportData {
+                position: transform.translation,
+                // a rotation that is not sent must not count as a move
+                rotation: if policy.position_only {
+                    Quat::IDENTITY
+                } else {
+                    transform.rotation
+                },
+            },
+        );
+        policies.insert(link_id.clone(), policy);
+    }
+
+    let clients = server.clients_id();
+    let resend_all = delta.resend_all(&clients);
+    let snapshot_index = delta.snapshot();
+    // actors at rest (e.g. sleeping props) are not sent again, nor those not due this snapshot
+    let moved = delta.moved(&data.actors, |link_id| {
+        policies
+            .get(link_id)
+            .map_or(true, |policy| policy.is_due(snapshot_index))
+    });
+
+    // the replay is a spectator, it gets the whole unfiltered snapshot
+    if let Some(mut recorder) = recorder {
+        let mut snapshot = data.clone();
+        snapshot.actors.retain(|link_id, _| moved.contains(link_id));
+        let (mut snapshot, reliable) = split_snapshot(snapshot, &policies);
+        snapshot.actors.extend(reliable.actors);
+        snapshot.positions.extend(reliable.positions);
+        recorder.record(ReplayChannel::Unreliable, &bincode::serialize(&snapshot).unwrap());
+    }
+
+    if settings.interest_radius <= 0. {
+        let mut snapshot = data.clone();
+        if !resend_all {
+            snapshot.actors.retain(|link_id, _| moved.contains(link_id));
+        }
+        let (snapshot, reliable) = split_snapshot(snapshot, &policies);
+        for _ in clients.iter() {
+            bandwidth.count(&snapshot, &policies);
+            bandwidth.count(&reliable, &policies);
+        }
+        if !reliable.actors.is_empty() || !reliable.positions.is_empty() {
+            outbox.queue(ServerMessages::ReliableSync(reliable));
+        }
+        let sync_message = bincode::serialize(&snapshot).unwrap();
+        server.broadcast_message(Channel::State, sync_message);
+    } else {
+        interest.retain_clients(&clients);
+
+        for client_id in clients {
+            let player_id = PlayerId::Client(client_id);
+            let center = lobby
+                .players
+                .get(&player_id)
+                .and_then(PlayerData::entity)
+                .and_then(|entity| transform_query.get(entity).ok())
+                .map(|transform| transform.translation);
+
+            let mut snapshot = match center {
+                Some(center) => filter_snapshot(data, center, settings.interest_radius, player_id),
+                // no character yet, nothing to filter around
+                None => filter_snapshot(data, Vec3::ZERO, f32::INFINITY, player_id),
+            };
+
+            let keys = snapshot_keys(&snapshot);
+            // actors that just entered the interest are sent even at rest
+            snapshot.actors.retain(|link_id, _| {
+                resend_all
+                    || moved.contains(link_id)
+                    || !interest.was_visible(client_id, &InterestKey::Actor(link_id.clone()))
+            });
+
+            let left = interest.update(client_id, keys);
+            if !left.is_empty() {
+                let (mut players, mut actors) = (Vec::new(), Vec::new());
+                for key in left {
+                    match key {
+                        InterestKey::Player(id) => players.push(id),
+                        InterestKey::Actor(id) => actors.push(id),
+                    }
+                }
+                let message =
+                    bincode::serialize(&ServerMessages::OutOfInterest { players, actors }).unwrap();
+                server.send_message(client_id, Channel::Control, message);
             }
+
+            let (snapshot, reliable) = split_snapshot(snapshot, &policies);
+            bandwidth.count(&snapshot, &policies);
+            bandwidth.count(&reliable, &policies);
+            if !reliable.actors.is_empty() || !reliable.positions.is_empty() {
+                outbox.queue_for(client_id, ServerMessages::ReliableSync(reliable));
+            }
+            let sync_message = bincode::serialize(&snapshot).unwrap();
+            server.send_message(client_id, Channel::State, sync_message);
         }
     }
+
+    data.players.clear();
+    data.actors.clear();
 }
 
-// pub fn server_sync_actor(
-//     mut server: ResMut<RenetServer>,
-//     // TODO a nahooya tut resours, daun
-//     mut data: ResMut<TransportDataResource>,
-//     character_query: Query<(&Position, &Rotation, &PlayerView, &Character)>,
-//     moveble_actor_query: Query<(&Transform, &LinkId)>,
-// ) {
-//     let data = &mut data.data;
-//     for (position, rotation, view_direction, character) in character_query.iter() {
-//         data.players.insert(
-//             character.id,
-//             PlayerTransportData {
-//                 position: position.0,
-//                 rotation: rotation.0,
-//                 player_view: *view_direction,
-//             },
-//         );
-//     }
-//
-//     for (transform, link_id) in moveble_actor_query.iter() {
-//         data.actors.insert(
-//             link_id.clone(),
-//             ActorTransportData {
-//                 position: transform.translation,
-//                 rotation: transform.rotation,
-//             },
-//         );
-//     }
-//
-//     let sync_message = bincode::serialize(&data).unwrap();
-//     server.broadcast_message(DefaultChannel::Unreliable, sync_message);
-//
-//     data.players.clear();
-//     data.actors.clear();
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::hierarchy::BuildWorldChildren;
+
+    use crate::lobby::client::new_client;
+
+    #[test]
+    fn disconnect_despawns_character_subtree() {
+        let mut world = World::new();
+        let collider = world.spawn_empty().id();
+        let visual = world.spawn_empty().id();
+        let character = world.spawn_empty().push_children(&[collider, visual]).id();
+        let player_data = PlayerData::new(character, Color::RED, "player".into());
+
+        world.run_system_once(move |mut commands: Commands| {
+            despawn_player(&mut commands, PlayerId::Client(ClientId::from_raw(1)), &player_data);
+        });
+
+        for entity in [character, collider, visual] {
+            assert!(world.get_entity(entity).is_none());
+        }
+    }
+
+    #[test]
+    fn disconnect_without_character_does_not_panic() {
+        let mut world = World::new();
+        let player_data = PlayerData::default();
+
+        world.run_system_once(move |mut commands: Commands| {
+            despawn_player(&mut commands, PlayerId::Client(ClientId::from_raw(1)), &player_data);
+        });
+    }
+
+    /// The host and its clients build their ends apart, a channel missing or configured
+    /// differently on one of them loses the messages sent on it.
+    #[test]
+    fn host_and_client_exchange_on_every_channel() {
+        let (mut server, _transport, _addresses) = new_renet_server("127.0.0.1:0").unwrap();
+        let mut client = new_client();
+        let client_id = ClientId::from_raw(1);
+        server.add_connection(client_id);
+        client.set_connected();
+
+        for channel in Channel::ALL {
+            let message = vec![u8::from(channel); 3];
+            server.send_message(client_id, channel, message.clone());
+            client.send_message(channel, message.clone());
+            for packet in server.get_packets_to_send(client_id).unwrap() {
+                client.process_packet(&packet);
+            }
+            for packet in client.get_packets_to_send() {
+                server.process_packet_from(&packet, client_id).unwrap();
+            }
+
+            assert_eq!(
+                client.receive_message(channel).as_deref(),
+                Some(&message[..]),
+                "{:?} to the client",
+                channel
+            );
+            assert_eq!(
+                server.receive_message(client_id, channel).as_deref(),
+                Some(&message[..]),
+                "{:?} to the host",
+                channel
+            );
+        }
+    }
+}
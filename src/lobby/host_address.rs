@@ -0,0 +1,100 @@
+//! Address of the host seen from the internet, for the players outside its network.
+//!
+//! Looked up over plain HTTP when [`Settings::lookup_public_address`] is on. The lookup runs
+//! on the [`IoTaskPool`], hosting goes on without it and a failed one is only logged.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+
+use crate::settings::Settings;
+
+use super::{HostResource, LobbyState};
+
+/// Answers a plain `GET /` with the address of the caller as text
+const LOOKUP_HOST: &str = "api.ipify.org";
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bytes of the response read at most, the address is a few of them
+const MAX_RESPONSE_LEN: u64 = 4096;
+
+/// Result of the running lookup, replaced by a new one for every session
+/// so a late answer of a previous one is not taken.
+#[derive(Debug, Default, Resource)]
+struct ExternalIpLookup(Arc<Mutex<Option<Result<IpAddr, String>>>>);
+
+pub struct HostAddressPlugins;
+
+impl Plugin for HostAddressPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExternalIpLookup>()
+            .add_systems(
+                OnEnter(LobbyState::Host),
+                start_lookup.run_if(|settings: Res<Settings>| settings.lookup_public_address),
+            )
+            .add_systems(Update, finish_lookup.run_if(in_state(LobbyState::Host)))
+            .add_systems(OnExit(LobbyState::Host), clear_addresses);
+    }
+}
+
+fn start_lookup(mut lookup: ResMut<ExternalIpLookup>) {
+    *lookup = ExternalIpLookup::default();
+    let result = lookup.0.clone();
+    IoTaskPool::get()
+        .spawn(async move {
+            let ip = lookup_external_ip();
+            *result.lock().unwrap_or_else(PoisonError::into_inner) = Some(ip);
+        })
+        .detach();
+}
+
+fn finish_lookup(lookup: Res<ExternalIpLookup>, mut host_resource: ResMut<HostResource>) {
+    let Some(result) = lookup.0.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+        return;
+    };
+    match result {
+        Ok(ip) => {
+            log::info!("Public address of the host: {}", ip);
+            host_resource.external_ip = Some(ip);
+        }
+        Err(err) => log::warn!("Failed to look up the public address of the host: {}", err),
+    }
+}
+
+fn clear_addresses(mut lookup: ResMut<ExternalIpLookup>, mut host_resource: ResMut<HostResource>) {
+    *lookup = ExternalIpLookup::default();
+    host_resource.bound_address = None;
+    host_resource.public_address = None;
+    host_resource.external_ip = None;
+}
+
+fn lookup_external_ip() -> Result<IpAddr, String> {
+    let address = (LOOKUP_HOST, 80)
+        .to_socket_addrs()
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or_else(|| format!("{} has no address", LOOKUP_HOST))?;
+    let mut stream =
+        TcpStream::connect_timeout(&address, LOOKUP_TIMEOUT).map_err(|err| err.to_string())?;
+    stream
+        .set_read_timeout(Some(LOOKUP_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(LOOKUP_TIMEOUT)))
+        .map_err(|err| err.to_string())?;
+    write!(stream, "GET / HTTP/1.0\r\nHost: {}\r\n\r\n", LOOKUP_HOST)
+        .map_err(|err| err.to_string())?;
+
+    let mut response = String::new();
+    stream
+        .take(MAX_RESPONSE_LEN)
+        .read_to_string(&mut response)
+        .map_err(|err| err.to_string())?;
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| "malformed response".to_string())?;
+    body.trim()
+        .parse()
+        .map_err(|err| format!("{}: {:?}", err, body.trim()))
+}
@@ -0,0 +1,351 @@
+//! Live settings of a hosted session, edited in the Server Admin window or with the `server`
+//! console command of dev builds, and saved for the next session.
+//!
+//! [`HostSettings`] gathers the knobs spread over [`ServerSettings`] and [`SimulationConfig`].
+//! A [`ChangeHostSettingsEvent`] is validated, applied right away and written into
+//! [`HOST_SETTINGS_FILE`] next to the settings file, which is applied when the next session is
+//! hosted. The part the clients play by, [`ServerRules`], is broadcast with
+//! [`ServerMessages::RulesUpdate`] whenever it changes, whatever changed it.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use renet::RenetServer;
+use serde::{Deserialize, Serialize};
+
+use crate::component::Respawn;
+#[cfg(feature = "dev")]
+use crate::console::{CommandResult, CommandScope, ConsoleAppExt};
+use crate::network::MAX_CLIENTS;
+use crate::settings::settings_dir;
+use crate::world::SimulationConfig;
+
+use super::afk::AfkRules;
+use super::approval::ClientApprovedEvent;
+use super::host::ServerSettings;
+use super::outbox::ServerOutbox;
+use super::{Character, Lobby, LobbyState, PlayerId, ServerMessages};
+
+/// File of [`settings_dir`] the host settings are saved into
+pub const HOST_SETTINGS_FILE: &str = "host.yaml";
+
+/// Rules of the session the clients need to know, e.g. to show them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Resource, Serialize, Deserialize)]
+pub struct ServerRules {
+    /// Seconds between a death and the respawn of a character
+    pub respawn_delay: f32,
+    /// Projectiles damage teammates, only meaningful in a team mode
+    pub friendly_fire: bool,
+    /// The clients may ask for the [`SessionLog`](super::session_log::SessionLog)
+    pub share_session_log: bool,
+}
+
+/// Knobs of a hosted session, as shown in the Server Admin window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostSettings {
+    /// Players in the lobby including the host, further clients are refused
+    pub max_players: usize,
+    /// See [`SimulationConfig::net_sync_hz`]
+    pub net_sync_hz: f64,
+    /// See [`ServerSettings::interest_radius`]
+    pub interest_radius: f32,
+    /// See [`ServerRules::respawn_delay`]
+    pub respawn_delay: f32,
+    /// Seconds without activity before a client is warned, `None` lets inactive clients stay.
+    /// See [`AfkRules::timeout`]
+    pub afk_timeout: Option<f32>,
+    /// Applied while [`ServerSettings::teams`] is on
+    pub friendly_fire: bool,
+    /// See [`ServerRules::share_session_log`]
+    pub share_session_log: bool,
+}
+
+impl Default for HostSettings {
+    fn default() -> Self {
+        Self::current(&ServerSettings::default(), &SimulationConfig::default())
+    }
+}
+
+impl HostSettings {
+    /// The host itself and every client the transport accepts
+    pub const MAX_PLAYERS: usize = MAX_CLIENTS + 1;
+    pub const NET_SYNC_RANGE: std::ops::RangeInclusive<f64> = 1.0..=128.0;
+    pub const INTEREST_RADIUS_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2000.0;
+    pub const RESPAWN_DELAY_RANGE: std::ops::RangeInclusive<f32> = 0.0..=60.0;
+    pub const AFK_TIMEOUT_RANGE: std::ops::RangeInclusive<f32> = 10.0..=3600.0;
+
+    /// The settings the session runs with now.
+    pub fn current(settings: &ServerSettings, simulation: &SimulationConfig) -> Self {
+        Self {
+            max_players: settings.max_players,
+            net_sync_hz: simulation.net_sync_hz,
+            interest_radius: settings.interest_radius,
+            respawn_delay: settings.respawn_delay,
+            afk_timeout: settings.afk.map(|rules| rules.timeout),
+            friendly_fire: settings.teams.is_some_and(|rules| rules.friendly_fire),
+            share_session_log: settings.share_session_log,
+        }
+    }
+
+    /// Why the settings cannot be applied to a lobby of `players`, if they cannot.
+    pub fn validate(&self, players: usize) -> Result<(), String> {
+        if !(1..=Self::MAX_PLAYERS).contains(&self.max_players) {
+            return Err(format!("max players is from 1 to {}", Self::MAX_PLAYERS));
+        }
+        if self.max_players < players {
+            return Err(format!("{} players are in the lobby already", players));
+        }
+        if !Self::NET_SYNC_RANGE.contains(&self.net_sync_hz) {
+            return Err(format!("net sync rate is {:?} Hz", Self::NET_SYNC_RANGE));
+        }
+        if !Self::INTEREST_RADIUS_RANGE.contains(&self.interest_radius) {
+            return Err(format!("interest radius is {:?}", Self::INTEREST_RADIUS_RANGE));
+        }
+        if !Self::RESPAWN_DELAY_RANGE.contains(&self.respawn_delay) {
+            return Err(format!("respawn delay is {:?} seconds", Self::RESPAWN_DELAY_RANGE));
+        }
+        if self
+            .afk_timeout
+            .is_some_and(|timeout| !Self::AFK_TIMEOUT_RANGE.contains(&timeout))
+        {
+            return Err(format!("AFK timeout is {:?} seconds", Self::AFK_TIMEOUT_RANGE));
+        }
+        Ok(())
+    }
+
+    /// Changes the session to these settings, the AFK action of enabled rules is kept.
+    pub fn apply(&self, settings: &mut ServerSettings, simulation: &mut SimulationConfig) {
+        settings.max_players = self.max_players;
+        settings.interest_radius = self.interest_radius;
+        settings.respawn_delay = self.respawn_delay;
+        settings.share_session_log = self.share_session_log;
+        settings.afk = self.afk_timeout.map(|timeout| AfkRules {
+            timeout,
+            ..settings.afk.unwrap_or_default()
+        });
+        if let Some(rules) = settings.teams.as_mut() {
+            rules.friendly_fire = self.friendly_fire;
+        }
+        if simulation.net_sync_hz != self.net_sync_hz {
+            simulation.net_sync_hz = self.net_sync_hz;
+        }
+    }
+}
+
+/// Players counted against [`HostSettings::max_players`]: the host and the clients, not the bots.
+pub fn player_count(lobby: &Lobby) -> usize {
+    let clients = lobby
+        .players
+        .keys()
+        .filter(|id| matches!(id, PlayerId::Client(_)))
+        .count();
+    clients + 1
+}
+
+/// Applies and saves new [`HostSettings`], ignored if they do not validate.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct ChangeHostSettingsEvent(pub HostSettings);
+
+pub struct HostSettingsPlugins;
+
+impl Plugin for HostSettingsPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerRules>()
+            .add_event::<ChangeHostSettingsEvent>()
+            .add_systems(OnEnter(LobbyState::Host), load_host_settings)
+            .add_systems(
+                Update,
+                (change_host_settings, broadcast_rules, send_rules_to_joined)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(
+                Update,
+                apply_respawn_delay
+                    .run_if(in_state(LobbyState::Host).or_else(in_state(LobbyState::Client))),
+            )
+            .add_systems(OnExit(LobbyState::Host), reset_rules)
+            .add_systems(OnExit(LobbyState::Client), reset_rules);
+        #[cfg(feature = "dev")]
+        app.add_console_command(
+            "server",
+            "[max_players|sync_hz|interest_radius|respawn_delay|afk_timeout|ff|share_log <value>]",
+            CommandScope::Authority,
+            parse_host_setting,
+            host_setting,
+        );
+    }
+}
+
+fn host_settings_path() -> PathBuf {
+    settings_dir().join(HOST_SETTINGS_FILE)
+}
+
+fn write_host_settings(
+    path: &Path,
+    settings: &HostSettings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    file.write_all(serde_yaml::to_string(settings)?.as_bytes())?;
+    Ok(())
+}
+
+/// The settings saved by an earlier session, `None` if there are none or they are unusable.
+fn read_host_settings(path: &Path) -> Option<HostSettings> {
+    let text = fs::read_to_string(path).ok()?;
+    let settings: HostSettings = match serde_yaml::from_str(&text) {
+        Ok(settings) => settings,
+        Err(err) => {
+            log::warn!("Failed to read host settings file ({:?}), ignoring it: {}", path, err);
+            return None;
+        }
+    };
+    match settings.validate(0) {
+        Ok(()) => Some(settings),
+        Err(err) => {
+            log::warn!("Ignoring host settings file ({:?}): {}", path, err);
+            None
+        }
+    }
+}
+
+/// The last session's settings replace the defaults and the `--sync-rate` argument.
+fn load_host_settings(
+    mut settings: ResMut<ServerSettings>,
+    mut simulation: ResMut<SimulationConfig>,
+) {
+    if let Some(saved) = read_host_settings(&host_settings_path()) {
+        log::info!("Hosting with the saved settings: {:?}", saved);
+        saved.apply(&mut settings, &mut simulation);
+    }
+}
+
+fn change_host_settings(
+    mut change_event: EventReader<ChangeHostSettingsEvent>,
+    lobby: Res<Lobby>,
+    mut settings: ResMut<ServerSettings>,
+    mut simulation: ResMut<SimulationConfig>,
+) {
+    let Some(ChangeHostSettingsEvent(host_settings)) = change_event.read().last() else {
+        return;
+    };
+    if let Err(err) = host_settings.validate(player_count(&lobby)) {
+        log::warn!("Host settings not changed: {}", err);
+        return;
+    }
+    log::info!("Host settings changed: {:?}", host_settings);
+    host_settings.apply(&mut settings, &mut simulation);
+    let path = host_settings_path();
+    if let Err(err) = write_host_settings(&path, host_settings) {
+        log::error!("Failed to write host settings file ({:?}): {}", path, err);
+    }
+}
+
+/// Tells the clients the rules once they changed, e.g. by the admin chat commands too.
+fn broadcast_rules(
+    settings: Res<ServerSettings>,
+    mut rules: ResMut<ServerRules>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    let current = settings.rules();
+    if *rules != current {
+        *rules = current;
+        outbox.queue(ServerMessages::RulesUpdate { rules: current });
+    }
+}
+
+fn send_rules_to_joined(
+    mut approved_event: EventReader<ClientApprovedEvent>,
+    rules: Res<ServerRules>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    for ClientApprovedEvent { client_id, .. } in approved_event.read() {
+        outbox.queue_for(*client_id, ServerMessages::RulesUpdate { rules: *rules });
+    }
+}
+
+/// Characters spawned later get the delay too.
+fn apply_respawn_delay(
+    rules: Res<ServerRules>,
+    mut respawn_query: Query<&mut Respawn, With<Character>>,
+) {
+    for mut respawn in respawn_query.iter_mut() {
+        if respawn.delay() != rules.respawn_delay {
+            respawn.set_delay(rules.respawn_delay);
+        }
+    }
+}
+
+fn reset_rules(mut rules: ResMut<ServerRules>) {
+    *rules = ServerRules::default();
+}
+
+/// One knob of [`HostSettings`] changed by the `server` console command.
+#[cfg(feature = "dev")]
+#[derive(Debug, Clone, Copy)]
+enum HostSetting {
+    MaxPlayers(usize),
+    NetSyncHz(f64),
+    InterestRadius(f32),
+    RespawnDelay(f32),
+    AfkTimeout(Option<f32>),
+    FriendlyFire(bool),
+    ShareSessionLog(bool),
+}
+
+/// No arguments prints the current settings.
+#[cfg(feature = "dev")]
+fn parse_host_setting(args: &[&str]) -> Result<Option<HostSetting>, String> {
+    fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+        value.parse().map_err(|_| format!("invalid argument `{}`", value))
+    }
+    let setting = match args {
+        [] => return Ok(None),
+        ["max_players", value] => HostSetting::MaxPlayers(number(value)?),
+        ["sync_hz", value] => HostSetting::NetSyncHz(number(value)?),
+        ["interest_radius", value] => HostSetting::InterestRadius(number(value)?),
+        ["respawn_delay", value] => HostSetting::RespawnDelay(number(value)?),
+        ["afk_timeout", "off"] => HostSetting::AfkTimeout(None),
+        ["afk_timeout", value] => HostSetting::AfkTimeout(Some(number(value)?)),
+        ["ff", "on"] => HostSetting::FriendlyFire(true),
+        ["ff", "off"] => HostSetting::FriendlyFire(false),
+        ["share_log", "on"] => HostSetting::ShareSessionLog(true),
+        ["share_log", "off"] => HostSetting::ShareSessionLog(false),
+        [name, _] => return Err(format!("unknown setting `{}`", name)),
+        _ => return Err(format!("expected 0 or 2 arguments, got {}", args.len())),
+    };
+    Ok(Some(setting))
+}
+
+#[cfg(feature = "dev")]
+fn host_setting(world: &mut World, setting: Option<HostSetting>) -> CommandResult {
+    if *world.resource::<State<LobbyState>>().get() != LobbyState::Host {
+        return Err("only a hosted game has server settings".to_string());
+    }
+    let mut host_settings = HostSettings::current(
+        world.resource::<ServerSettings>(),
+        world.resource::<SimulationConfig>(),
+    );
+    let Some(setting) = setting else {
+        return Ok(Some(format!("{:#?}", host_settings)));
+    };
+    match setting {
+        HostSetting::MaxPlayers(max_players) => host_settings.max_players = max_players,
+        HostSetting::NetSyncHz(hz) => host_settings.net_sync_hz = hz,
+        HostSetting::InterestRadius(radius) => host_settings.interest_radius = radius,
+        HostSetting::RespawnDelay(delay) => host_settings.respawn_delay = delay,
+        HostSetting::AfkTimeout(timeout) => host_settings.afk_timeout = timeout,
+        HostSetting::FriendlyFire(_) if world.resource::<ServerSettings>().teams.is_none() => {
+            return Err("no team mode, see `teams`".to_string());
+        }
+        HostSetting::FriendlyFire(friendly_fire) => host_settings.friendly_fire = friendly_fire,
+        HostSetting::ShareSessionLog(share) => host_settings.share_session_log = share,
+    }
+    let players = world.get_resource::<Lobby>().map_or(1, player_count);
+    host_settings.validate(players)?;
+    world.send_event(ChangeHostSettingsEvent(host_settings));
+    Ok(None)
+}
@@ -0,0 +1,94 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::ecs::system::Resource;
+use bevy::math::Vec3;
+use renet::ClientId;
+
+use crate::world::LinkId;
+
+use super::{PlayerId, TransportData};
+
+/// Something a client can be interested in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InterestKey {
+    Player(PlayerId),
+    Actor(LinkId),
+}
+
+/// What every client received in its last personalized snapshot.
+#[derive(Debug, Default, Resource)]
+pub struct ClientInterest(HashMap<ClientId, HashSet<InterestKey>>);
+
+impl ClientInterest {
+    /// Stores what `client_id` sees now and returns what it stopped seeing since the last call.
+    pub fn update(&mut self, client_id: ClientId, visible: HashSet<InterestKey>) -> Vec<InterestKey> {
+        let previous = self.0.insert(client_id, visible).unwrap_or_default();
+        let current = &self.0[&client_id];
+        previous
+            .into_iter()
+            .filter(|key| !current.contains(key))
+            .collect()
+    }
+
+    /// Whether `key` was in the last snapshot of `client_id`.
+    pub fn was_visible(&self, client_id: ClientId, key: &InterestKey) -> bool {
+        self.0
+            .get(&client_id)
+            .is_some_and(|visible| visible.contains(key))
+    }
+
+    /// Forgets clients that are not connected anymore.
+    pub fn retain_clients(&mut self, clients: &[ClientId]) {
+        self.0.retain(|client_id, _| clients.contains(client_id));
+    }
+}
+
+/// Keeps the entries of `snapshot` within `radius` of `center`.
+///
+/// The client's own character (`own`) is always kept.
+pub fn filter_snapshot(
+    snapshot: &TransportData,
+    center: Vec3,
+    radius: f32,
+    own: PlayerId,
+) -> TransportData {
+    let radius_squared = radius * radius;
+    TransportData {
+        players: snapshot
+            .players
+            .iter()
+            .filter(|(id, data)| {
+                **id == own || data.position.distance_squared(center) <= radius_squared
+            })
+            .map(|(id, data)| (*id, data.clone()))
+            .collect(),
+        actors: snapshot
+            .actors
+            .iter()
+            .filter(|(_, data)| data.position.distance_squared(center) <= radius_squared)
+            .map(|(id, data)| (id.clone(), data.clone()))
+            .collect(),
+        positions: snapshot
+            .positions
+            .iter()
+            .filter(|(_, position)| position.distance_squared(center) <= radius_squared)
+            .map(|(id, position)| (id.clone(), *position))
+            .collect(),
+    }
+}
+
+/// Keys of everything a snapshot contains.
+pub fn snapshot_keys(snapshot: &TransportData) -> HashSet<InterestKey> {
+    snapshot
+        .players
+        .keys()
+        .map(|id| InterestKey::Player(*id))
+        .chain(
+            snapshot
+                .actors
+                .keys()
+                .chain(snapshot.positions.keys())
+                .map(|id| InterestKey::Actor(id.clone())),
+        )
+        .collect()
+}
@@ -1,8 +1,16 @@
+use crate::actor::character::AnimationState;
+use crate::actor::PrefabId;
+use crate::component::{DespawnReason, Health, RespawnEvent};
 use crate::core::{CoreAction, KnownLevel};
-use crate::world::LinkId;
-use bevy::app::{App, Plugin};
-use bevy::ecs::event::Event;
-use bevy::math::{Quat, Vec3};
+use crate::replay::ReplayRecordPlugins;
+use crate::save::WorldSavePlugins;
+use crate::network::{Chunk, NetStatsPlugin};
+use crate::world::{EnvironmentSettings, LevelPhysics, LinkId, SharedSeed};
+use bevy::app::{App, Plugin, Update};
+use bevy::ecs::event::{Event, EventReader, EventWriter};
+use bevy::ecs::schedule::{common_conditions::in_state, Condition, IntoSystemConfigs};
+use bevy::ecs::system::Query;
+use bevy::math::{Quat, Vec2, Vec3};
 use bevy::prelude::{Color, Component, Entity, Resource, States};
 use bevy::reflect::Reflect;
 use bevy_controls::contract::InputsContainer;
@@ -11,10 +19,19 @@ use renet::transport::NETCODE_USER_DATA_BYTES;
 use renet::ClientId;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 
+use super::afk::AfkPlugins;
 use super::client::ClientLobbyPlugins;
 use super::host::HostLobbyPlugins;
+use super::host_settings::{HostSettingsPlugins, ServerRules};
+use super::migration::{HostMigrationPlugins, MigrationCandidate};
+use super::quick_chat::{QuickChatKind, QuickChatPlugins};
+use super::ready::ReadyPlugins;
+use super::session_log::{SessionLogEntry, SessionLogPlugins};
 use super::single::SingleLobbyPlugins;
+use super::team::{TeamId, TeamPlugins};
 
 //use super::host::HostLobbyPlugins;
 //use super::single::SingleLobbyPlugins;
@@ -47,23 +64,33 @@ pub enum LobbyState {
 pub enum ServerMessages {
     /// Sent when initializing a connection with a client.
     ///
-    /// This message includes the client's ID and their initial map state.
+    /// This message includes the client's ID and the seed of the session.
     ///
     /// # Fields
     ///
     /// * `id` - Unique identifier for the connecting client.
-    /// * `map_state` - Initial state of the client's map.
+    /// * `seed` - [`SharedSeed`] of the session, for the randomness every instance shares.
     InitConnection {
         id: ClientId,
+        seed: SharedSeed,
         //map_state: MapState,
     },
     /// Sent to notify a change in the map's state.
     ///
     /// # Fields
     ///
-    /// * `map_state` - The new state of the map.
+    /// * `level` - The level to load.
+    /// * `checksum` - [`level_checksum`](crate::level::level_checksum) of the host level,
+    ///   `None` if the host could not compute it.
+    /// * `physics` - Physics of the level, clients do not look them up themselves.
+    /// * `environment` - Lights, sky and fog of the level.
+    /// * `phase` - [`DayPhase`](crate::world::DayPhase) the level starts at.
     ChangeMap {
-        //map_state: MapState,
+        level: LevelCode,
+        checksum: Option<u64>,
+        physics: LevelPhysics,
+        environment: EnvironmentSettings,
+        phase: f32,
     },
     /// Indicates that a player has connected to the server.
     ///
@@ -72,10 +99,12 @@ pub enum ServerMessages {
     /// * `id` - Unique identifier for the player.
     /// * `color` - The color assigned to the player.
     /// * `username` - The player's chosen username.
+    /// * `team` - Team of the player, `None` without a team mode.
     PlayerConnected {
         id: PlayerId,
         color: Color,
         username: String,
+        team: Option<TeamId>,
     },
     /// Indicates that a player has disconnected from the server.
     ///
@@ -85,13 +114,277 @@ pub enum ServerMessages {
     PlayerDisconnected {
         id: PlayerId,
     },
+    /// A projectile was fired.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - The projectile actor.
+    /// * `prefab` - Kind of the projectile, spawned as its shell.
+    /// * `owner` - The player who fired it.
+    /// * `color` - Color of the owner, white if they left before the projectile was spawned.
     ProjectileSpawn {
         id: LinkId,
+        prefab: PrefabId,
+        owner: PlayerId,
         color: Color,
     },
     ActorDespawn {
         id: LinkId,
     },
+    /// Sent to every client right before the server stops.
+    ///
+    /// The host ended the game on purpose, the clients go back to the main menu instead of
+    /// migrating the session.
+    ///
+    /// # Fields
+    ///
+    /// * `reason` - Human readable reason shown to the players.
+    ServerShutdown {
+        reason: String,
+    },
+    /// Lists players and actors that left the client's interest radius.
+    ///
+    /// The client hides them until they appear in a snapshot again.
+    OutOfInterest {
+        players: Vec<PlayerId>,
+        actors: Vec<LinkId>,
+    },
+    /// A fragment of a serialized [`ServerMessages`] too large to be sent at once.
+    ///
+    /// Reassembled by the receiver with a [`ChunkReceiver`](crate::network::ChunkReceiver).
+    Chunk(Chunk),
+    /// A quick chat message of a player, accepted by the host.
+    ///
+    /// # Fields
+    ///
+    /// * `from` - The player who sent the message.
+    /// * `kind` - The predefined message.
+    /// * `world_pos` - Where the player pinged the level, if they aimed at it.
+    QuickChat {
+        from: PlayerId,
+        kind: QuickChatKind,
+        world_pos: Option<Vec3>,
+    },
+    /// A player character died and respawns.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - The player who died.
+    /// * `killer` - The player who killed them, `None` for deaths by the level.
+    /// * `cause` - What they died of.
+    PlayerDied {
+        id: PlayerId,
+        killer: Option<PlayerId>,
+        cause: DeathCause,
+    },
+    /// A text chat message of a player, accepted by the host.
+    ///
+    /// # Fields
+    ///
+    /// * `from` - The player who sent the message.
+    /// * `text` - The message, at most [`MAX_CHAT_LEN`] bytes.
+    Chat {
+        from: PlayerId,
+        text: String,
+    },
+    /// Clients that can take over the session if the host leaves, in the order they would.
+    ///
+    /// Broadcast periodically, see [`migration`](crate::lobby::migration).
+    MigrationCandidates {
+        candidates: Vec<MigrationCandidate>,
+    },
+    /// The host changed the physics of the running level, also sent to joining clients.
+    ///
+    /// # Fields
+    ///
+    /// * `physics` - The new physics.
+    ChangePhysics {
+        physics: LevelPhysics,
+    },
+    /// The host changed the environment or the time of day, also sent to joining clients.
+    ///
+    /// # Fields
+    ///
+    /// * `environment` - The new environment.
+    /// * `phase` - The new [`DayPhase`](crate::world::DayPhase).
+    ChangeEnvironment {
+        environment: EnvironmentSettings,
+        phase: f32,
+    },
+    /// Health of a player character changed, also sent to joining clients for the damaged ones.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - The player whose character it is.
+    /// * `health` - The new health.
+    /// * `attacker` - The player whose hit changed it, confirming the hit to them.
+    HealthChanged {
+        id: PlayerId,
+        health: Health,
+        attacker: Option<PlayerId>,
+    },
+    /// A player was moved to another team, or out of the teams when the team mode ended.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - The moved player.
+    /// * `team` - The new team.
+    /// * `color` - The new color of the player, taken from the team.
+    TeamAssigned {
+        id: PlayerId,
+        team: Option<TeamId>,
+        color: Color,
+    },
+    /// The client has been inactive for [`AfkRules::timeout`](super::afk::AfkRules::timeout),
+    /// sent to that client only.
+    ///
+    /// # Fields
+    ///
+    /// * `seconds_left` - Time to become active again before the host acts,
+    ///   `0` withdraws the warning once the client is active again.
+    AfkWarning {
+        seconds_left: u32,
+    },
+    /// An inactive player was taken out of the world, or came back.
+    ///
+    /// # Fields
+    ///
+    /// * `id` - The player.
+    /// * `spectating` - Whether the character of the player is hidden.
+    PlayerSpectating {
+        id: PlayerId,
+        spectating: bool,
+    },
+    /// Ready state of every player while the host has a match set up, empty without one.
+    ///
+    /// See [`ReadyPlugins`].
+    ReadyStates(HashMap<PlayerId, bool>),
+    /// Everyone is ready, the match starts after the countdown. Also sent to joining clients.
+    ///
+    /// # Fields
+    ///
+    /// * `countdown_secs` - Seconds left before the match level is loaded.
+    MatchStarting {
+        countdown_secs: f32,
+    },
+    /// A player is not ready anymore, the countdown stopped.
+    MatchCountdownCancelled,
+    /// The countdown ended, the match level follows with a [`ServerMessages::ChangeMap`].
+    MatchStarted,
+    /// Character collider positions of the host simulation, sent with every snapshot to the
+    /// clients that asked with [`ClientMessages::RequestColliderGhosts`].
+    /// Ignored by builds without the `dev` feature.
+    ColliderGhosts(HashMap<PlayerId, (Vec3, Quat)>),
+    /// Snapshot of the actors synced on [`SyncChannel::Reliable`](super::sync_policy::SyncChannel),
+    /// merged like the unreliable ones.
+    ReliableSync(TransportData),
+    /// Several messages sent at once by the [`ServerOutbox`](crate::lobby::outbox::ServerOutbox),
+    /// handled in order as if they came one by one.
+    Batch(Vec<ServerMessages>),
+    /// The host did not let the client join, it is disconnected right after.
+    ///
+    /// See [`ApprovalPolicy`](super::approval::ApprovalPolicy).
+    ///
+    /// # Fields
+    ///
+    /// * `reason` - Human readable reason shown to the player.
+    ConnectionRefused {
+        reason: String,
+    },
+    /// The rules of the session changed, also sent to a joining client.
+    RulesUpdate {
+        rules: ServerRules,
+    },
+    /// Answer to [`ClientMessages::RequestSessionLog`], sent over
+    /// [`Channel::Bulk`](crate::network::Channel::Bulk).
+    ///
+    /// # Fields
+    ///
+    /// * `started_at` - Unix time in seconds the host started the session at.
+    /// * `entries` - The events of the session, oldest first.
+    SessionLog {
+        started_at: u64,
+        entries: Vec<SessionLogEntry>,
+    },
+}
+
+impl ServerMessages {
+    /// The messages of a [`ServerMessages::Batch`], nested batches included, or the message itself.
+    pub fn unbatch(self) -> Vec<ServerMessages> {
+        match self {
+            ServerMessages::Batch(messages) => {
+                messages.into_iter().flat_map(ServerMessages::unbatch).collect()
+            }
+            message => vec![message],
+        }
+    }
+}
+
+/// Longest text chat message in bytes, longer ones are truncated by the host.
+pub const MAX_CHAT_LEN: usize = 200;
+
+/// Represents different types of messages that a client can send to the host.
+///
+/// Sent with [`send_to_server`](crate::lobby::client::send_to_server).
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ClientMessages {
+    /// First message of a client, the host disconnects clients of another version.
+    Hello {
+        version: String,
+    },
+    /// A quick chat message, rate limited by the host.
+    QuickChat {
+        kind: QuickChatKind,
+        world_pos: Option<Vec3>,
+    },
+    /// Asks the host to push a prop, validated against the distance to the client character.
+    ImpulseRequest {
+        target: LinkId,
+        impulse: Vec3,
+    },
+    /// World space horizontal direction (x, z) the client character moves in, sent unreliably.
+    ///
+    /// The host clamps every axis to `-1..=1` and rate limits these per tick.
+    /// `sequence` counts up from `1` with every input, the snapshots tell which one they include.
+    Input {
+        movement: Vec2,
+        sequence: u32,
+    },
+    /// A text chat message, rate limited by the host like the quick chat.
+    Chat {
+        text: String,
+    },
+    /// Port the client can host on if the session migrates to it.
+    MigrationPort {
+        port: u16,
+    },
+    /// Asks the host to make the client character jump, ignored if it is not grounded.
+    Jump,
+    /// Asks the host to fire a projectile from the client character along its view.
+    Fire,
+    /// Asks the host to move the client to another team, refused if the teams become unbalanced.
+    SwitchTeam {
+        team: TeamId,
+    },
+    /// Whether the client is ready for the match set up by the host.
+    SetReady(bool),
+    /// Asks the host for [`ServerMessages::ColliderGhosts`] or to stop them, dev builds only.
+    RequestColliderGhosts(bool),
+    /// Asks the host for [`ServerMessages::SessionLog`], ignored unless the host shares it.
+    RequestSessionLog,
+}
+
+impl ClientMessages {
+    /// Sent because the player did something, unlike the messages the client sends by itself.
+    ///
+    /// Idle [`ClientMessages::Input`]s are sent every frame, only a movement counts.
+    pub fn is_deliberate(&self) -> bool {
+        match self {
+            ClientMessages::Input { movement, .. } => *movement != Vec2::ZERO,
+            ClientMessages::Hello { .. } | ClientMessages::MigrationPort { .. } => false,
+            _ => true,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
@@ -111,11 +404,20 @@ impl Default for Username {
 }
 
 impl Username {
+    /// Maximum username length in bytes that fits into the netcode user data,
+    /// next to a password of [`ConnectInfo::MAX_PASSWORD_LEN`].
+    pub const MAX_LEN: usize = NETCODE_USER_DATA_BYTES - 16 - ConnectInfo::MAX_PASSWORD_LEN;
+
+    /// Whether `name` can be sent as a username.
+    pub fn is_valid(name: &str) -> bool {
+        !name.trim().is_empty() && name.len() <= Self::MAX_LEN
+    }
+
     pub fn to_netcode_data(
         &self,
     ) -> Result<[u8; NETCODE_USER_DATA_BYTES], Box<dyn std::error::Error>> {
         let mut data = [0u8; NETCODE_USER_DATA_BYTES];
-        if self.0.len() > NETCODE_USER_DATA_BYTES - 8 {
+        if self.0.len() > Self::MAX_LEN {
             let err = Err(From::from("Your username to long"));
             log::error!("{:?}", err);
             return err;
@@ -140,16 +442,111 @@ impl Username {
     }
 }
 
+/// What a client tells the host when it connects, in the netcode user data.
+///
+/// The [`Username`] layout comes first, the password follows it the same way: its length as
+/// a `u64` and its bytes. Without a password the data is the one of the username alone,
+/// zeros after it read as no password.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectInfo {
+    pub username: String,
+    /// Asked for by [`ApprovalPolicy::PasswordMatch`](super::approval::ApprovalPolicy)
+    pub password: Option<String>,
+}
+
+impl ConnectInfo {
+    /// Maximum password length in bytes, the rest of the user data is left to the username.
+    pub const MAX_PASSWORD_LEN: usize = 64;
+
+    pub fn to_user_data(
+        &self,
+    ) -> Result<[u8; NETCODE_USER_DATA_BYTES], Box<dyn std::error::Error>> {
+        let mut data = Username(self.username.clone()).to_netcode_data()?;
+        let Some(password) = self.password.as_ref().filter(|password| !password.is_empty())
+        else {
+            return Ok(data);
+        };
+        if password.len() > Self::MAX_PASSWORD_LEN {
+            return Err(From::from("Your password is too long"));
+        }
+        let start = 8 + self.username.len();
+        data[start..start + 8].copy_from_slice(&(password.len() as u64).to_le_bytes());
+        data[start + 8..start + 8 + password.len()].copy_from_slice(password.as_bytes());
+
+        Ok(data)
+    }
+
+    pub fn from_user_data(
+        user_data: &[u8; NETCODE_USER_DATA_BYTES],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let username = Username::from_user_data(user_data)?;
+        let start = 8 + username.len();
+        // a username filling the data, from before the passwords
+        if start + 8 > NETCODE_USER_DATA_BYTES {
+            return Ok(Self {
+                username,
+                password: None,
+            });
+        }
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(&user_data[start..start + 8]);
+        let len = (u64::from_le_bytes(buffer) as usize)
+            .min(Self::MAX_PASSWORD_LEN)
+            .min(NETCODE_USER_DATA_BYTES - start - 8);
+        let password = String::from_utf8(user_data[start + 8..start + 8 + len].to_vec())?;
+
+        Ok(Self {
+            username,
+            password: (!password.is_empty()).then_some(password),
+        })
+    }
+}
+
+/// Reason of the last session end the player has not been told about yet.
+///
+/// Set when the lobby is left involuntarily, shown by the main menu.
+#[derive(Debug, Default, Resource)]
+pub struct DisconnectNotice(pub Option<String>);
+
+/// The host or the client could not be started or lost its transport, the lobby is left.
+#[derive(Debug, Clone, Event)]
+pub struct NetworkSetupErrorEvent(pub String);
+
 #[derive(Debug, Default, Resource)]
 pub struct ClientResource {
     pub address: Option<String>,
     pub username: Option<String>,
+    /// Password of the host, see [`ConnectInfo`]
+    pub password: Option<String>,
 }
 
-#[derive(Debug, Default, Resource)]
+#[derive(Debug, Resource)]
 pub struct HostResource {
     pub address: Option<String>,
     pub username: Option<String>,
+    /// Word list masked in chat and refused in usernames, from `WORD_FILTER`.
+    /// `None` turns the filter off, see [`WordFilter`](super::word_filter::WordFilter)
+    pub word_filter: Option<PathBuf>,
+    /// Address the host listens on with the real port, set once it is hosting
+    pub bound_address: Option<SocketAddr>,
+    /// Address the players of the host network connect to, in the connect tokens
+    pub public_address: Option<SocketAddr>,
+    /// Address of the host seen from the internet, see
+    /// [`Settings::lookup_public_address`](crate::settings::Settings::lookup_public_address)
+    pub external_ip: Option<IpAddr>,
+}
+
+impl Default for HostResource {
+    fn default() -> Self {
+        Self {
+            address: None,
+            username: None,
+            word_filter: std::env::var_os("WORD_FILTER").map(PathBuf::from),
+            bound_address: None,
+            public_address: None,
+            external_ip: None,
+        }
+    }
 }
 
 #[derive(Resource, Default, Clone, Debug)]
@@ -160,6 +557,26 @@ pub struct Lobby {
     pub players_seq: usize,
 }
 
+impl Lobby {
+    /// Data of `id`, the host or single player is [`Lobby::me`] on the host.
+    pub fn player(&self, id: &PlayerId) -> Option<&PlayerData> {
+        match self.players.get(id) {
+            Some(player_data) => Some(player_data),
+            None if *id == PlayerId::HostOrSingle => Some(&self.me),
+            None => None,
+        }
+    }
+
+    /// Mutable [`Lobby::player`].
+    pub fn player_mut(&mut self, id: &PlayerId) -> Option<&mut PlayerData> {
+        match self.players.get_mut(id) {
+            Some(player_data) => Some(player_data),
+            None if *id == PlayerId::HostOrSingle => Some(&mut self.me),
+            None => None,
+        }
+    }
+}
+
 impl InputsContainer<CoreAction> for Lobby {
     fn iter_inputs<'a>(&'a self) -> Box<dyn Iterator<Item = &'a PlayerActions<CoreAction>> + 'a> {
         todo!()
@@ -179,6 +596,8 @@ pub enum PlayerId {
     #[default]
     HostOrSingle, // TODO: depricated
     Client(ClientId),
+    /// Played by the host, see [`Bot`](super::bots::Bot)
+    Bot(u32),
 }
 
 impl PlayerId {
@@ -186,7 +605,7 @@ impl PlayerId {
     #[allow(dead_code)]
     pub fn client_id(&self) -> Option<ClientId> {
         match self {
-            PlayerId::HostOrSingle => None,
+            PlayerId::HostOrSingle | PlayerId::Bot(_) => None,
             PlayerId::Client(id) => Some(*id),
         }
     }
@@ -197,6 +616,11 @@ pub struct PlayerData {
     entity: Option<Entity>,
     pub color: Color,
     pub username: String,
+    /// Team of the player while the host plays a team mode
+    pub team: Option<TeamId>,
+    /// [`SimulationTick`](crate::world::SimulationTick) of the last deliberate input of a client,
+    /// only kept by the host
+    pub last_activity: u64,
     pub inputs: PlayerActions<CoreAction>,
 }
 
@@ -206,15 +630,15 @@ impl PlayerData {
             entity: Some(entity),
             color,
             username,
+            team: None,
+            last_activity: 0,
             inputs: PlayerActions::<CoreAction>::default(),
         }
     }
 
-    pub fn entity(&self) -> Entity {
-        match self.entity {
-            Some(entity) => entity,
-            None => panic!(),
-        }
+    /// Character of the player, `None` before the host character is spawned.
+    pub fn entity(&self) -> Option<Entity> {
+        self.entity
     }
 }
 
@@ -224,6 +648,8 @@ impl Default for PlayerData {
             entity: None,
             color: Color::RED,
             username: "noname".into(),
+            team: None,
+            last_activity: 0,
             inputs: PlayerActions::<CoreAction>::default(),
         }
     }
@@ -234,23 +660,30 @@ pub struct Character {
     pub id: PlayerId,
 }
 
-#[derive(Resource, Default, Debug, Serialize, Deserialize)]
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerTransportData {
     pub position: Vec3,
     pub rotation: Quat,
     pub player_view: PlayerView,
+    pub animation: AnimationState,
+    /// Last input of the client of this player the position includes, `0` for the others.
+    ///
+    /// See [`prediction`](super::prediction).
+    pub input_sequence: u32,
 }
 
-#[derive(Resource, Default, Debug, Serialize, Deserialize)]
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
 pub struct ActorTransportData {
     pub position: Vec3,
     pub rotation: Quat,
 }
 
-#[derive(Resource, Default, Debug, Serialize, Deserialize)]
+#[derive(Resource, Default, Debug, Clone, Serialize, Deserialize)]
 pub struct TransportData {
     pub players: HashMap<PlayerId, PlayerTransportData>,
     pub actors: HashMap<LinkId, ActorTransportData>,
+    /// Actors synced without their rotation, see [`SyncPolicy`](super::sync_policy::SyncPolicy)
+    pub positions: HashMap<LinkId, Vec3>,
 }
 
 #[derive(Resource, Default, Debug, Serialize, Deserialize)]
@@ -274,7 +707,7 @@ impl PlayerView {
 }
 
 // TODO: to core.rs
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LevelCode {
     Url(String),
     Path(String),
@@ -284,15 +717,198 @@ pub enum LevelCode {
 #[derive(Debug, Event)]
 pub struct ChangeMapLobbyEvent(pub LevelCode);
 
+/// A player character died, emitted by the simulating side and by clients on [`ServerMessages::PlayerDied`].
+#[derive(Debug, Clone, Copy, Event)]
+pub struct PlayerDiedEvent {
+    pub id: PlayerId,
+    pub killer: Option<PlayerId>,
+    pub cause: DeathCause,
+}
+
+/// What a player died of, for the kill feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Killed by a player, the killer of the [`PlayerDiedEvent`]
+    #[default]
+    Killed,
+    /// Fell out of the world or into a kill volume, see [`DespawnReason::Void`]
+    Void,
+    /// Anything else of the level
+    Level,
+}
+
 pub struct LobbyPlugins;
 
 impl Plugin for LobbyPlugins {
     fn build(&self, app: &mut App) {
         app.add_event::<ChangeMapLobbyEvent>()
+            .add_event::<PlayerDiedEvent>()
+            .add_event::<NetworkSetupErrorEvent>()
             .insert_state(LobbyState::default())
             .insert_state(MapLoaderState::default())
             .init_resource::<HostResource>()
             .init_resource::<ClientResource>()
-            .add_plugins((HostLobbyPlugins, SingleLobbyPlugins, ClientLobbyPlugins));
+            .init_resource::<DisconnectNotice>()
+            .add_plugins((
+                HostLobbyPlugins,
+                SingleLobbyPlugins,
+                ClientLobbyPlugins,
+                QuickChatPlugins,
+                WorldSavePlugins,
+                ReplayRecordPlugins,
+                HostMigrationPlugins,
+                TeamPlugins,
+                AfkPlugins,
+                ReadyPlugins,
+                NetStatsPlugin,
+                HostSettingsPlugins,
+                SessionLogPlugins,
+            ))
+            .add_systems(
+                Update,
+                player_deaths.run_if(in_state(LobbyState::Single).or_else(in_state(LobbyState::Host))),
+            );
+    }
+}
+
+/// Turns respawns of characters into [`PlayerDiedEvent`]s, if their reason counts as a death.
+///
+/// Map changes and other forced moves are not deaths, see [`DespawnReason::behavior`].
+fn player_deaths(
+    mut respawn_event: EventReader<RespawnEvent>,
+    mut player_died_event: EventWriter<PlayerDiedEvent>,
+    character_query: Query<&Character>,
+) {
+    for event in respawn_event.read() {
+        if !event.reason.behavior().counts_as_death {
+            continue;
+        }
+        let cause = match event.reason {
+            DespawnReason::Void => DeathCause::Void,
+            _ => DeathCause::Level,
+        };
+        if let Ok(character) = character_query.get(event.entity) {
+            player_died_event.send(PlayerDiedEvent {
+                id: character.id,
+                killer: None,
+                cause,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One message of every [`ClientMessages`] variant.
+    fn every_client_message() -> Vec<ClientMessages> {
+        vec![
+            ClientMessages::Hello {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            ClientMessages::QuickChat {
+                kind: QuickChatKind::Attack,
+                world_pos: Some(Vec3::new(1., 2., 3.)),
+            },
+            ClientMessages::ImpulseRequest {
+                target: LinkId::Scene("Crate".to_string()),
+                impulse: Vec3::X,
+            },
+            ClientMessages::Input {
+                movement: Vec2::new(0.5, -1.),
+                sequence: 42,
+            },
+            ClientMessages::Chat {
+                text: "hello".to_string(),
+            },
+            ClientMessages::MigrationPort { port: 5000 },
+            ClientMessages::Jump,
+            ClientMessages::Fire,
+            ClientMessages::SwitchTeam { team: TeamId::B },
+            ClientMessages::SetReady(true),
+            ClientMessages::RequestColliderGhosts(true),
+            ClientMessages::RequestSessionLog,
+        ]
+    }
+
+    #[test]
+    fn client_messages_round_trip() {
+        let messages = every_client_message();
+        for message in messages.iter() {
+            // a new variant does not compile here until it is added to `every_client_message`
+            match message {
+                ClientMessages::Hello { .. }
+                | ClientMessages::QuickChat { .. }
+                | ClientMessages::ImpulseRequest { .. }
+                | ClientMessages::Input { .. }
+                | ClientMessages::Chat { .. }
+                | ClientMessages::MigrationPort { .. }
+                | ClientMessages::Jump
+                | ClientMessages::Fire
+                | ClientMessages::SwitchTeam { .. }
+                | ClientMessages::SetReady(_)
+                | ClientMessages::RequestColliderGhosts(_)
+                | ClientMessages::RequestSessionLog => {}
+            }
+
+            let bytes = bincode::serialize(message).unwrap();
+            let decoded: ClientMessages = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", message));
+        }
+    }
+
+    fn projectile_link() -> LinkId {
+        LinkId::Allocated {
+            session: 7,
+            index: 11,
+        }
+    }
+
+    fn projectile_spawn(owner: PlayerId) -> ServerMessages {
+        ServerMessages::ProjectileSpawn {
+            id: projectile_link(),
+            prefab: PrefabId::PROJECTILE,
+            owner,
+            color: Color::rgb(0.2, 0.4, 0.6),
+        }
+    }
+
+    #[test]
+    fn projectile_spawn_round_trips() {
+        let client = PlayerId::Client(ClientId::from_raw(3));
+        // alone and batched like the outbox sends it
+        for message in [
+            projectile_spawn(client),
+            ServerMessages::Batch(vec![projectile_spawn(client)]),
+        ] {
+            let bytes = bincode::serialize(&message).unwrap();
+            let decoded: ServerMessages = bincode::deserialize(&bytes).unwrap();
+            let mut decoded = decoded.unbatch();
+            assert_eq!(decoded.len(), 1);
+            match decoded.remove(0) {
+                ServerMessages::ProjectileSpawn {
+                    id,
+                    prefab,
+                    owner,
+                    color,
+                } => {
+                    assert_eq!(id, projectile_link());
+                    assert_eq!(prefab, PrefabId::PROJECTILE);
+                    assert_eq!(owner, client);
+                    assert_eq!(color, Color::rgb(0.2, 0.4, 0.6));
+                }
+                other => panic!("decoded {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_client_message_does_not_decode() {
+        let bytes = bincode::serialize(&ClientMessages::Chat {
+            text: "hello".to_string(),
+        })
+        .unwrap();
+        assert!(bincode::deserialize::<ClientMessages>(&bytes[..bytes.len() - 1]).is_err());
     }
 }
@@ -0,0 +1,329 @@
+//! Best-effort host migration.
+//!
+//! Every client advertises a port it could host on, the host broadcasts the clients in join order
+//! with their addresses ([`ServerMessages::MigrationCandidates`]). When the host is lost,
+//! the first candidate becomes the host on its port and the others reconnect to it with their old client ids,
+//! so player ids, usernames, colors and scores survive. Characters respawn at the spawn points of the new host.
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use bevy::app::{App, Plugin, Update};
+use bevy::ecs::entity::Entity;
+use bevy::ecs::event::{Event, EventReader, EventWriter};
+use bevy::ecs::query::With;
+use bevy::ecs::schedule::{Condition, IntoSystemConfigs, NextState, OnExit};
+use bevy::ecs::system::{Commands, Local, Query, Res, ResMut, Resource};
+use bevy::hierarchy::DespawnRecursiveExt;
+use bevy::prelude::{in_state, resource_exists, Color};
+use bevy::time::{Time, Timer, TimerMode};
+use renet::transport::{NetcodeClientTransport, NetcodeServerTransport};
+use renet::{ClientId, RenetClient, RenetServer, ServerEvent};
+use serde::{Deserialize, Serialize};
+
+use crate::actor::character::TiedCamera;
+use crate::actor::UnloadActorsEvent;
+use crate::core::CurrentLevel;
+use crate::network::{new_client_transport, Channel, ChunkReceiver};
+use crate::tr;
+use crate::ui::SessionScores;
+
+use super::client::{new_client, send_to_server, OwnId};
+use super::{
+    ClientMessages, ClientResource, ConnectInfo, DisconnectNotice, HostResource, LevelCode, Lobby,
+    LobbyState, NetworkSetupErrorEvent, PlayerId, ServerMessages, TransportDataResource,
+};
+
+/// How often the host broadcasts [`ServerMessages::MigrationCandidates`]
+const CANDIDATES_INTERVAL: Duration = Duration::from_secs(5);
+/// Time given to the new host to accept the reconnection before giving up
+const MIGRATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A client that can take over the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationCandidate {
+    pub id: ClientId,
+    /// Address the others reconnect to if this client becomes the host.
+    pub address: SocketAddr,
+}
+
+/// The connection to the host is gone, the session migrates if it can.
+#[derive(Debug, Clone, Event)]
+pub struct HostLostEvent(pub String);
+
+/// Host side: clients in join order and the ports they advertised.
+#[derive(Debug, Default, Resource)]
+pub struct MigrationRoster {
+    order: Vec<ClientId>,
+    ports: HashMap<ClientId, u16>,
+}
+
+impl MigrationRoster {
+    pub fn advertise(&mut self, client_id: ClientId, port: u16) {
+        if port != 0 {
+            self.ports.insert(client_id, port);
+        }
+    }
+}
+
+/// Client side: the last candidates received and the port this client advertised.
+#[derive(Debug, Default, Resource)]
+pub struct MigrationPlan {
+    pub candidates: Vec<MigrationCandidate>,
+    port: Option<u16>,
+}
+
+/// State the new host restores from the copy of the lobby its client had.
+#[derive(Debug, Resource)]
+pub struct MigratedSession {
+    pub level: LevelCode,
+    pub own_color: Option<Color>,
+    pub colors: HashMap<ClientId, Color>,
+}
+
+/// Reconnection to the new host in progress.
+#[derive(Debug, Resource)]
+struct Migrating(Timer);
+
+pub struct HostMigrationPlugins;
+
+impl Plugin for HostMigrationPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<HostLostEvent>()
+            .init_resource::<MigrationPlan>()
+            .init_resource::<MigrationRoster>()
+            .add_systems(
+                Update,
+                (track_roster, broadcast_candidates)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    advertise_port.run_if(bevy_renet::client_just_connected),
+                    migrate,
+                    migration_timeout.run_if(resource_exists::<Migrating>),
+                )
+                    .chain()
+                    .run_if(in_state(LobbyState::Client)),
+            )
+            .add_systems(OnExit(LobbyState::Host), reset_host)
+            .add_systems(OnExit(LobbyState::Client), reset_client);
+    }
+}
+
+fn track_roster(mut server_events: EventReader<ServerEvent>, mut roster: ResMut<MigrationRoster>) {
+    for event in server_events.read() {
+        match event {
+            ServerEvent::ClientConnected { client_id } => roster.order.push(*client_id),
+            ServerEvent::ClientDisconnected { client_id, .. } => {
+                roster.order.retain(|id| id != client_id);
+                roster.ports.remove(client_id);
+            }
+        }
+    }
+}
+
+fn broadcast_candidates(
+    mut timer: Local<Option<Timer>>,
+    time: Res<Time>,
+    roster: Res<MigrationRoster>,
+    mut server: ResMut<RenetServer>,
+    transport: Res<NetcodeServerTransport>,
+) {
+    let timer =
+        timer.get_or_insert_with(|| Timer::new(CANDIDATES_INTERVAL, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() && !roster.is_changed() {
+        return;
+    }
+
+    let candidates = roster
+        .order
+        .iter()
+        .filter_map(|id| {
+            let port = *roster.ports.get(id)?;
+            let address = transport.client_addr(*id)?;
+            Some(MigrationCandidate {
+                id: *id,
+                address: SocketAddr::new(address.ip(), port),
+            })
+        })
+        .collect();
+    let message = bincode::serialize(&ServerMessages::MigrationCandidates { candidates }).unwrap();
+    server.broadcast_message(Channel::Control, message);
+}
+
+/// Reserves a free port and tells the host this client can host on it.
+fn advertise_port(mut plan: ResMut<MigrationPlan>, mut client: ResMut<RenetClient>) {
+    // the socket is dropped right away, the port stays free until a migration binds it
+    let port = match UdpSocket::bind("0.0.0.0:0").and_then(|socket| socket.local_addr()) {
+        Ok(address) => address.port(),
+        Err(err) => {
+            log::warn!("No port to advertise for host migration: {}", err);
+            return;
+        }
+    };
+    plan.port = Some(port);
+    send_to_server(&mut client, &ClientMessages::MigrationPort { port }, Channel::Control);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn migrate(
+    mut commands: Commands,
+    mut host_lost_event: EventReader<HostLostEvent>,
+    mut plan: ResMut<MigrationPlan>,
+    mut lobby: ResMut<Lobby>,
+    mut own_id: ResMut<OwnId>,
+    mut host_resource: ResMut<HostResource>,
+    mut client_resource: ResMut<ClientResource>,
+    current_level: Res<CurrentLevel>,
+    migrating: Option<Res<Migrating>>,
+    tied_camera_query: Query<Entity, With<TiedCamera>>,
+    mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut setup_error_event: EventWriter<NetworkSetupErrorEvent>,
+    mut disconnect_notice: ResMut<DisconnectNotice>,
+    scores: Option<ResMut<SessionScores>>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+) {
+    let Some(HostLostEvent(reason)) = host_lost_event.read().last() else {
+        return;
+    };
+
+    if migrating.is_some() {
+        log::error!("Host migration failed: {}", reason);
+        leave(
+            &mut setup_error_event,
+            &mut *next_state_lobby,
+            tr!("error.migration_unreachable", reason = reason),
+        );
+        return;
+    }
+    let Some(me) = own_id.client_id() else {
+        // never joined, nothing to migrate
+        leave(&mut setup_error_event, &mut *next_state_lobby, reason.clone());
+        return;
+    };
+    let Some(candidate) = plan.candidates.first().copied() else {
+        disconnect_notice.0 = Some(reason.clone());
+        next_state_lobby.set(LobbyState::None);
+        return;
+    };
+    log::info!("Host lost ({}), migrating to {:?}", reason, candidate);
+
+    // the new host respawns everyone, nothing of the old session stays in the world
+    for (id, player_data) in lobby.players.iter() {
+        let Some(entity) = player_data.entity() else {
+            log::warn!("Player {:?} has no character to despawn", id);
+            continue;
+        };
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in tied_camera_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    unload_actors_event.send(UnloadActorsEvent);
+    commands.remove_resource::<RenetClient>();
+    commands.remove_resource::<NetcodeClientTransport>();
+    // the candidate is the host from now on, for itself and for those reconnecting to it
+    if let Some(mut scores) = scores {
+        scores.promote_to_host(candidate.id);
+    }
+
+    if candidate.id == me {
+        let Some(port) = plan.port else {
+            leave(&mut setup_error_event, &mut *next_state_lobby, reason.clone());
+            return;
+        };
+        commands.insert_resource(MigratedSession {
+            level: current_level.0.clone(),
+            own_color: lobby
+                .players
+                .get(&PlayerId::Client(me))
+                .map(|player_data| player_data.color),
+            colors: lobby
+                .players
+                .iter()
+                .filter_map(|(player_id, player_data)| match player_id {
+                    PlayerId::Client(id) if *id != me => Some((*id, player_data.color)),
+                    _ => None,
+                })
+                .collect(),
+        });
+        host_resource.address = Some(format!("0.0.0.0:{}", port));
+        host_resource.username = client_resource.username.clone();
+        log::info!("Taking over the session on port {}", port);
+        next_state_lobby.set(LobbyState::Host);
+        return;
+    }
+
+    let address = candidate.address.to_string();
+    let info = ConnectInfo {
+        username: client_resource.username.clone().unwrap_or_default(),
+        password: client_resource.password.clone(),
+    };
+    match new_client_transport(&address, info, me.raw()) {
+        Ok(transport) => {
+            client_resource.address = Some(address);
+            commands.insert_resource(new_client());
+            commands.insert_resource(transport);
+            commands.insert_resource(TransportDataResource::default());
+            commands.insert_resource(ChunkReceiver::default());
+            *lobby = Lobby::default();
+            // the new host initializes the connection again
+            *own_id = OwnId::default();
+            plan.candidates.clear();
+            commands.insert_resource(Migrating(Timer::new(MIGRATION_TIMEOUT, TimerMode::Once)));
+        }
+        Err(err) => leave(
+            &mut setup_error_event,
+            &mut *next_state_lobby,
+            tr!("error.migration_reconnect", address = address, error = err),
+        ),
+    }
+}
+
+/// Falls back to leaving the lobby with `error`, as without migration.
+fn leave(
+    setup_error_event: &mut EventWriter<NetworkSetupErrorEvent>,
+    next_state_lobby: &mut NextState<LobbyState>,
+    error: String,
+) {
+    setup_error_event.send(NetworkSetupErrorEvent(error));
+    next_state_lobby.set(LobbyState::None);
+}
+
+fn migration_timeout(
+    mut commands: Commands,
+    mut migrating: ResMut<Migrating>,
+    time: Res<Time>,
+    client: Option<Res<RenetClient>>,
+    mut setup_error_event: EventWriter<NetworkSetupErrorEvent>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+) {
+    if client.is_some_and(|client| client.is_connected()) {
+        log::info!("Reconnected to the new host");
+        commands.remove_resource::<Migrating>();
+        return;
+    }
+    if migrating.0.tick(time.delta()).finished() {
+        log::error!("The new host did not accept the reconnection in time");
+        commands.remove_resource::<Migrating>();
+        leave(
+            &mut setup_error_event,
+            &mut *next_state_lobby,
+            tr!("error.migration_timeout"),
+        );
+    }
+}
+
+fn reset_host(mut commands: Commands, mut roster: ResMut<MigrationRoster>) {
+    *roster = MigrationRoster::default();
+    commands.remove_resource::<MigratedSession>();
+}
+
+fn reset_client(mut commands: Commands, mut plan: ResMut<MigrationPlan>) {
+    *plan = MigrationPlan::default();
+    commands.remove_resource::<Migrating>();
+}
@@ -2,8 +2,30 @@
 
 mod lobby;
 
+pub mod admin;
+pub mod afk;
+pub mod approval;
+pub mod bots;
 pub mod client;
+pub mod delta;
+#[cfg(feature = "dev")]
+pub mod ghosts;
 pub mod host;
+pub mod host_address;
+pub mod host_settings;
+pub mod interest;
+pub mod migration;
+pub mod outbox;
+pub mod pending_links;
+pub mod prediction;
+pub mod quick_chat;
+pub mod ready;
+pub mod session_log;
 pub mod single;
+pub mod sync_policy;
+pub mod team;
+pub mod validation;
+pub mod vote_kick;
+pub mod word_filter;
 
 pub use lobby::*;
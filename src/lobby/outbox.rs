@@ -0,0 +1,236 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use bevy_renet::RenetSend;
+use renet::{ClientId, RenetServer};
+
+use crate::network::{Channel, MAX_UNCHUNKED_SIZE};
+use crate::replay::{ReplayChannel, ReplayRecorder};
+
+use super::approval::PendingApproval;
+use super::{LobbyState, ServerMessages};
+
+/// Bytes bincode writes before the messages of a [`ServerMessages::Batch`]:
+/// the `u32` variant index and the `u64` message count.
+pub const BATCH_HEADER_SIZE: usize = 4 + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recipient {
+    All,
+    Client(ClientId),
+}
+
+impl Recipient {
+    fn includes(self, client_id: ClientId) -> bool {
+        match self {
+            Recipient::All => true,
+            Recipient::Client(recipient) => recipient == client_id,
+        }
+    }
+}
+
+/// Reliable messages of the host, sent at the end of the frame.
+///
+/// Every message is serialized once, the messages of a client are packed into
+/// [`ServerMessages::Batch`]es of at most [`MAX_UNCHUNKED_SIZE`] bytes in the order they were queued,
+/// so a burst of events does not turn into as many messages on [`Channel::Control`].
+#[derive(Debug, Default, Resource)]
+pub struct ServerOutbox {
+    queue: Vec<(Recipient, ServerMessages)>,
+}
+
+impl ServerOutbox {
+    /// Queues `message` for every client, it is recorded into the replay if any.
+    pub fn queue(&mut self, message: ServerMessages) {
+        self.queue.push((Recipient::All, message));
+    }
+
+    /// Queues `message` for `client_id` only.
+    pub fn queue_for(&mut self, client_id: ClientId, message: ServerMessages) {
+        self.queue.push((Recipient::Client(client_id), message));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+pub struct OutboxPlugins;
+
+impl Plugin for OutboxPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerOutbox>()
+            .add_systems(
+                PostUpdate,
+                drain_outbox
+                    .before(RenetSend)
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(OnExit(LobbyState::Host), clear_outbox);
+    }
+}
+
+/// Ranges of consecutive messages of `sizes` bytes fitting into batches of `max_size` bytes.
+///
+/// A message too large for a batch of its own still gets a range alone.
+pub fn split_batches(sizes: &[usize], max_size: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut batch_size = BATCH_HEADER_SIZE;
+    for (index, size) in sizes.iter().enumerate() {
+        if index > start && batch_size + size > max_size {
+            ranges.push(start..index);
+            start = index;
+            batch_size = BATCH_HEADER_SIZE;
+        }
+        batch_size += size;
+    }
+    if start < sizes.len() {
+        ranges.push(start..sizes.len());
+    }
+    ranges
+}
+
+/// Packs serialized messages into payloads of at most `max_size` bytes,
+/// a lone message is sent as it is.
+pub fn batch_payloads(payloads: &[&[u8]], max_size: usize) -> Vec<Vec<u8>> {
+    let sizes: Vec<usize> = payloads.iter().map(|payload| payload.len()).collect();
+    split_batches(&sizes, max_size)
+        .into_iter()
+        .map(|range| match &payloads[range] {
+            [payload] => payload.to_vec(),
+            group => encode_batch(group),
+        })
+        .collect()
+}
+
+/// [`ServerMessages::Batch`] of already serialized messages,
+/// bincode writes the elements of a `Vec` back to back after its length.
+fn encode_batch(group: &[&[u8]]) -> Vec<u8> {
+    let mut payload = bincode::serialize(&ServerMessages::Batch(Vec::new()))
+        .expect("an empty batch always serializes");
+    let count_at = payload.len() - 8;
+    payload[count_at..].copy_from_slice(&(group.len() as u64).to_le_bytes());
+    for message in group {
+        payload.extend_from_slice(message);
+    }
+    payload
+}
+
+fn drain_outbox(
+    mut outbox: ResMut<ServerOutbox>,
+    mut server: ResMut<RenetServer>,
+    pending: Res<PendingApproval>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+) {
+    if outbox.is_empty() {
+        return;
+    }
+
+    let mut encoded = Vec::new();
+    for (recipient, message) in outbox.queue.drain(..) {
+        match bincode::serialize(&message) {
+            Ok(payload) => encoded.push((recipient, payload)),
+            Err(err) => log::error!("Dropped {:?}, it failed to serialize: {}", message, err),
+        }
+    }
+
+    // recorded one by one, the playback does not care how they were sent
+    if let Some(recorder) = recorder.as_deref_mut() {
+        for (recipient, payload) in encoded.iter() {
+            if *recipient == Recipient::All {
+                recorder.record(ReplayChannel::Reliable, payload);
+            }
+        }
+    }
+
+    for client_id in server.clients_id() {
+        // a client not admitted yet only gets what is sent to it
+        let waiting = pending.contains(client_id);
+        let payloads: Vec<&[u8]> = encoded
+            .iter()
+            .filter(|(recipient, _)| {
+                recipient.includes(client_id) && !(waiting && *recipient == Recipient::All)
+            })
+            .map(|(_, payload)| payload.as_slice())
+            .collect();
+        for batch in batch_payloads(&payloads, MAX_UNCHUNKED_SIZE) {
+            server.send_message(client_id, Channel::Control, batch);
+        }
+    }
+}
+
+fn clear_outbox(mut outbox: ResMut<ServerOutbox>) {
+    outbox.queue.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lobby::PlayerId;
+
+    fn chat(text: String) -> ServerMessages {
+        ServerMessages::Chat {
+            from: PlayerId::HostOrSingle,
+            text,
+        }
+    }
+
+    fn texts(messages: Vec<ServerMessages>) -> Vec<String> {
+        messages
+            .into_iter()
+            .map(|message| match message {
+                ServerMessages::Chat { text, .. } => text,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_batches_respects_the_size_cap() {
+        let sizes = [40, 40, 40, 40, 40];
+        let ranges = split_batches(&sizes, BATCH_HEADER_SIZE + 100);
+        assert_eq!(ranges, vec![0..2, 2..4, 4..5]);
+    }
+
+    #[test]
+    fn split_batches_keeps_an_oversized_message_alone() {
+        let sizes = [10, 500, 10];
+        let ranges = split_batches(&sizes, 100);
+        assert_eq!(ranges, vec![0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn split_batches_of_nothing_is_empty() {
+        assert!(split_batches(&[], MAX_UNCHUNKED_SIZE).is_empty());
+    }
+
+    #[test]
+    fn batches_fit_and_decode_in_order() {
+        let sent: Vec<String> = (0..200).map(|i| format!("{i:0>300}")).collect();
+        let encoded: Vec<Vec<u8>> = sent
+            .iter()
+            .map(|text| bincode::serialize(&chat(text.clone())).unwrap())
+            .collect();
+        let payloads: Vec<&[u8]> = encoded.iter().map(Vec::as_slice).collect();
+
+        let max_size = 4 * 1024;
+        let batches = batch_payloads(&payloads, max_size);
+        assert!(batches.len() > 1);
+
+        let mut received = Vec::new();
+        for batch in batches {
+            assert!(batch.len() <= max_size, "{} bytes", batch.len());
+            let message: ServerMessages = bincode::deserialize(&batch).unwrap();
+            received.extend(texts(message.unbatch()));
+        }
+        assert_eq!(received, sent);
+    }
+
+    #[test]
+    fn lone_message_is_not_wrapped() {
+        let payload = bincode::serialize(&chat("hi".into())).unwrap();
+        let batches = batch_payloads(&[&payload], MAX_UNCHUNKED_SIZE);
+        assert_eq!(batches, vec![payload]);
+    }
+}
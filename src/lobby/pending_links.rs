@@ -0,0 +1,430 @@
+//! Spawns, despawns and snapshots of linked actors reach a client out of order: an
+//! [`ServerMessages::ActorDespawn`](super::ServerMessages::ActorDespawn) queued by the host
+//! a frame before the spawn, or a snapshot moving an actor not spawned yet.
+//!
+//! Whatever arrives before its actor waits in [`PendingLinks`] for a few seconds instead of
+//! being dropped, a shell is never spawned for an actor already gone.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::world::LinkId;
+
+use super::ActorTransportData;
+
+/// Seconds a despawn or a transform waits for its actor before it is dropped
+const PENDING_TIMEOUT: f32 = 5.;
+/// Despawns and transforms kept waiting at most each, later ones are dropped
+const MAX_PENDING: usize = 256;
+
+#[derive(Debug, Clone, Copy)]
+struct PendingTransform {
+    translation: Vec3,
+    /// `None` for position-only updates, the rotation of the actor is kept
+    rotation: Option<Quat>,
+    received_at: f32,
+}
+
+/// What the client received for linked actors it does not have yet.
+#[derive(Debug, Default, Resource)]
+pub struct PendingLinks {
+    /// Despawns received before the spawn of their actor, by reception time
+    despawns: HashMap<LinkId, f32>,
+    /// Latest transform of actors not spawned yet
+    transforms: HashMap<LinkId, PendingTransform>,
+    /// Shells spawned this frame, missing from the queries until the commands are applied
+    queued: HashMap<LinkId, Entity>,
+}
+
+impl PendingLinks {
+    pub fn clear(&mut self) {
+        self.despawns.clear();
+        self.transforms.clear();
+        self.queued.clear();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.despawns.is_empty() && self.transforms.is_empty()
+    }
+
+    /// Drops what waited longer than [`PENDING_TIMEOUT`].
+    fn expire(&mut self, now: f32) {
+        self.despawns.retain(|_, received_at| now - *received_at < PENDING_TIMEOUT);
+        self.transforms
+            .retain(|_, transform| now - transform.received_at < PENDING_TIMEOUT);
+    }
+}
+
+/// Linked actors of the client, with the shells spawned but not applied yet.
+#[derive(SystemParam)]
+pub struct LinkedActors<'w, 's> {
+    /// Transform of a linked actor is kept on position-only updates
+    query: Query<'w, 's, (Entity, &'static LinkId, Option<&'static mut Transform>)>,
+    pending: ResMut<'w, PendingLinks>,
+    time: Res<'w, Time>,
+}
+
+impl LinkedActors<'_, '_> {
+    /// Entities of `link_id` and their transform, `None` for a shell not applied yet.
+    pub fn entities(&self, link_id: &LinkId) -> Vec<(Entity, Option<&Transform>)> {
+        let mut entities: Vec<(Entity, Option<&Transform>)> = self
+            .query
+            .iter()
+            .filter(|(_, id, _)| *id == link_id)
+            .map(|(entity, _, transform)| (entity, transform))
+            .collect();
+        if let Some(entity) = self.pending.queued.get(link_id) {
+            if entities.iter().all(|(known, _)| known != entity) {
+                entities.push((*entity, None));
+            }
+        }
+        entities
+    }
+
+    pub fn clear_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &LinkId)> {
+        self.query.iter().map(|(entity, link_id, _)| (entity, link_id))
+    }
+
+    /// Remembers a shell spawned with commands, so a despawn or a transform right after it
+    /// in the same frame finds it.
+    pub fn queue_spawn(&mut self, link_id: LinkId, entity: Entity) {
+        self.pending.queued.insert(link_id, entity);
+    }
+
+    /// Consumes the despawn of `link_id` received before its spawn, if any.
+    pub fn take_despawn(&mut self, link_id: &LinkId) -> bool {
+        self.pending.despawns.remove(link_id).is_some()
+    }
+
+    /// Despawns the entities of `link_id`, or keeps the despawn for its spawn to come.
+    pub fn despawn(&mut self, commands: &mut Commands, link_id: LinkId) {
+        let entities: Vec<Entity> = self
+            .entities(&link_id)
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        if entities.is_empty() {
+            self.pending.transforms.remove(&link_id);
+            if self.pending.despawns.len() >= MAX_PENDING {
+                log::debug!("Despawn of {} dropped: too many pending", link_id);
+                return;
+            }
+            log::debug!("Despawn of {} before its spawn, kept", link_id);
+            let now = self.time.elapsed_seconds();
+            self.pending.despawns.insert(link_id, now);
+            return;
+        }
+        for entity in entities {
+            commands.entity(entity).despawn_recursive();
+        }
+        self.pending.queued.remove(&link_id);
+    }
+
+    /// Moves the linked actors of a snapshot in place, their scale is kept.
+    ///
+    /// Only a shell without its transform yet gets one inserted, the actors not spawned yet
+    /// wait for it, see [`LinkedActors::buffer_transform`]. Returns the entities moved.
+    pub fn apply_snapshot(
+        &mut self,
+        commands: &mut Commands,
+        actors: &HashMap<LinkId, ActorTransportData>,
+        positions: &HashMap<LinkId, Vec3>,
+    ) -> Vec<Entity> {
+        let mut moved = Vec::with_capacity(actors.len() + positions.len());
+        let mut applied = HashSet::with_capacity(actors.len() + positions.len());
+        // one pass over the actors, a lookup per actor would be quadratic
+        for (entity, link_id, transform) in self.query.iter_mut() {
+            let (translation, rotation) = if let Some((key, data)) = actors.get_key_value(link_id) {
+                applied.insert(key);
+                (data.position, Some(data.rotation))
+            } else if let Some((key, position)) = positions.get_key_value(link_id) {
+                applied.insert(key);
+                (*position, None)
+            } else {
+                continue;
+            };
+            match transform {
+                Some(mut transform) => {
+                    transform.translation = translation;
+                    if let Some(rotation) = rotation {
+                        transform.rotation = rotation;
+                    }
+                }
+                None => {
+                    commands.entity(entity).try_insert(Transform {
+                        translation,
+                        rotation: rotation.unwrap_or_default(),
+                        ..default()
+                    });
+                }
+            }
+            moved.push(entity);
+        }
+
+        let missing = actors
+            .iter()
+            .map(|(link_id, data)| (link_id, data.position, Some(data.rotation)))
+            .chain(positions.iter().map(|(link_id, position)| (link_id, *position, None)))
+            .filter(|(link_id, ..)| !applied.contains(link_id));
+        for (link_id, translation, rotation) in missing {
+            // spawned this frame, its commands are not applied yet
+            if let Some(entity) = self.pending.queued.get(link_id) {
+                commands.entity(*entity).try_insert(Transform {
+                    translation,
+                    rotation: rotation.unwrap_or_default(),
+                    ..default()
+                });
+                moved.push(*entity);
+                continue;
+            }
+            self.buffer_transform(link_id, translation, rotation);
+        }
+        moved
+    }
+
+    /// Keeps the latest transform of an actor not spawned yet, applied once it is.
+    pub fn buffer_transform(
+        &mut self,
+        link_id: &LinkId,
+        translation: Vec3,
+        rotation: Option<Quat>,
+    ) {
+        // a despawned actor does not move anymore
+        if self.pending.despawns.contains_key(link_id) {
+            return;
+        }
+        let pending = &mut self.pending.transforms;
+        if pending.len() >= MAX_PENDING && !pending.contains_key(link_id) {
+            log::debug!("Transform of {} dropped: too many pending", link_id);
+            return;
+        }
+        let received_at = self.time.elapsed_seconds();
+        pending.insert(
+            link_id.clone(),
+            PendingTransform {
+                translation,
+                rotation,
+                received_at,
+            },
+        );
+    }
+}
+
+/// Applies the waiting despawns and transforms to the actors spawned since, once the commands
+/// of the message handling are applied.
+pub fn apply_pending_links(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pending: ResMut<PendingLinks>,
+    link_query: Query<(Entity, &LinkId, Option<&Transform>)>,
+) {
+    pending.queued.clear();
+    pending.expire(time.elapsed_seconds());
+    if pending.is_empty() {
+        return;
+    }
+
+    for (entity, link_id, transform) in link_query.iter() {
+        if pending.despawns.remove(link_id).is_some() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if let Some(update) = pending.transforms.remove(link_id) {
+            let rotation = update
+                .rotation
+                .or(transform.map(|transform| transform.rotation))
+                .unwrap_or_default();
+            commands.entity(entity).try_insert(Transform {
+                translation: update.translation,
+                rotation,
+                ..default()
+            });
+        }
+    }
+}
+
+pub fn clear_pending_links(mut pending: ResMut<PendingLinks>) {
+    pending.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn link() -> LinkId {
+        LinkId::Allocated {
+            session: 1,
+            index: 42,
+        }
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<PendingLinks>();
+        world
+    }
+
+    /// What the spawn handlers of the client do, without the prefab.
+    fn spawn_shell(world: &mut World) {
+        world.run_system_once(|mut commands: Commands, mut linked: LinkedActors| {
+            if linked.take_despawn(&link()) {
+                return;
+            }
+            let entity = commands.spawn(link()).id();
+            linked.queue_spawn(link(), entity);
+        });
+    }
+
+    fn despawn(world: &mut World) {
+        world.run_system_once(|mut commands: Commands, mut linked: LinkedActors| {
+            linked.despawn(&mut commands, link());
+        });
+    }
+
+    fn linked_entities(world: &mut World) -> Vec<Entity> {
+        world
+            .query_filtered::<Entity, With<LinkId>>()
+            .iter(world)
+            .collect()
+    }
+
+    #[test]
+    fn despawn_before_spawn_leaves_no_shell() {
+        let mut world = world();
+        despawn(&mut world);
+        spawn_shell(&mut world);
+        world.run_system_once(apply_pending_links);
+
+        assert!(linked_entities(&mut world).is_empty());
+        assert!(world.resource::<PendingLinks>().is_empty());
+    }
+
+    #[test]
+    fn despawn_right_after_spawn_finds_the_queued_shell() {
+        let mut world = world();
+        spawn_shell(&mut world);
+        despawn(&mut world);
+        world.run_system_once(apply_pending_links);
+
+        assert!(linked_entities(&mut world).is_empty());
+        assert!(world.resource::<PendingLinks>().is_empty());
+    }
+
+    #[test]
+    fn transform_before_spawn_is_applied_to_the_shell() {
+        let mut world = world();
+        let position = Vec3::new(1., 2., 3.);
+        let rotation = Quat::from_rotation_y(1.);
+        world.run_system_once(move |mut commands: Commands, mut linked: LinkedActors| {
+            let actors = HashMap::from([(link(), ActorTransportData { position, rotation })]);
+            let moved = linked.apply_snapshot(&mut commands, &actors, &HashMap::new());
+            assert!(moved.is_empty());
+        });
+        spawn_shell(&mut world);
+        world.run_system_once(apply_pending_links);
+
+        let entities = linked_entities(&mut world);
+        assert_eq!(entities.len(), 1);
+        let transform = world.get::<Transform>(entities[0]).unwrap();
+        assert_eq!(transform.translation, position);
+        assert_eq!(transform.rotation, rotation);
+        assert!(world.resource::<PendingLinks>().is_empty());
+    }
+
+    #[test]
+    fn pending_entries_expire() {
+        let mut world = world();
+        despawn(&mut world);
+        world.run_system_once(|mut linked: LinkedActors| {
+            linked.buffer_transform(&LinkId::Scene("crate".into()), Vec3::ONE, None);
+        });
+        assert!(!world.resource::<PendingLinks>().is_empty());
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(PENDING_TIMEOUT + 1.));
+        world.run_system_once(apply_pending_links);
+
+        assert!(world.resource::<PendingLinks>().is_empty());
+    }
+
+    #[test]
+    fn pending_despawns_are_bounded() {
+        let mut world = world();
+        world.run_system_once(|mut commands: Commands, mut linked: LinkedActors| {
+            for index in 0..MAX_PENDING as u64 * 2 {
+                linked.despawn(&mut commands, LinkId::Allocated { session: 1, index });
+            }
+        });
+        assert_eq!(world.resource::<PendingLinks>().despawns.len(), MAX_PENDING);
+    }
+
+    /// Synced actors of the snapshot stress run
+    const STRESS_ACTORS: u64 = 500;
+    /// Snapshots applied by the stress run
+    const STRESS_FRAMES: u32 = 200;
+
+    #[derive(Resource)]
+    struct StressSnapshot(HashMap<LinkId, ActorTransportData>);
+
+    fn stress_snapshot(frame: u32) -> StressSnapshot {
+        let actors = (0..STRESS_ACTORS)
+            .map(|index| {
+                let data = ActorTransportData {
+                    position: Vec3::new(index as f32, frame as f32, 0.),
+                    rotation: Quat::from_rotation_y(frame as f32),
+                };
+                (LinkId::Allocated { session: 1, index }, data)
+            })
+            .collect();
+        StressSnapshot(actors)
+    }
+
+    /// [`STRESS_FRAMES`] snapshots of [`STRESS_ACTORS`] applied in place by
+    /// [`LinkedActors::apply_snapshot`], to profile with
+    ///
+    /// `cargo test --release -- --ignored snapshot_stress`
+    #[test]
+    #[ignore = "stress run, profile it in release"]
+    fn snapshot_stress() {
+        let mut world = world();
+        for index in 0..STRESS_ACTORS {
+            world.spawn((
+                LinkId::Allocated { session: 1, index },
+                Transform::from_scale(Vec3::splat(2.)),
+            ));
+        }
+        let mut system = IntoSystem::into_system(
+            |mut commands: Commands, snapshot: Res<StressSnapshot>, mut linked: LinkedActors| {
+                linked.apply_snapshot(&mut commands, &snapshot.0, &HashMap::new());
+            },
+        );
+        system.initialize(&mut world);
+        for frame in 1..=STRESS_FRAMES {
+            world.insert_resource(stress_snapshot(frame));
+            system.run((), &mut world);
+        }
+
+        let last = stress_snapshot(STRESS_FRAMES);
+        let mut query = world.query::<(&LinkId, &Transform)>();
+        assert_eq!(query.iter(&world).count(), STRESS_ACTORS as usize);
+        for (link_id, transform) in query.iter(&world) {
+            let data = &last.0[link_id];
+            assert_eq!(transform.translation, data.position);
+            assert_eq!(transform.rotation, data.rotation);
+            // written in place, the rest of the transform is kept
+            assert_eq!(transform.scale, Vec3::splat(2.));
+        }
+    }
+}
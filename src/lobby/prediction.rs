@@ -0,0 +1,152 @@
+//! Prediction of the own character on a client.
+//!
+//! The client moves its character by its inputs right away with [`step_movement`], the movement
+//! the host simulates, instead of waiting for the snapshots. Every input is numbered and kept
+//! until a snapshot includes it, see [`PlayerTransportData::input_sequence`]. The position of
+//! the snapshot is then taken as the truth, the inputs it does not include yet are replayed on
+//! it, and the difference to the position shown is smoothed out over [`CORRECTION_TIME`].
+//! A difference over [`SNAP_DISTANCE`] is a desync, it is taken at once.
+//!
+//! Only the horizontal move is predicted, the height (jumps and falls) is the one of the host.
+//! The other characters are placed by the snapshots as before.
+//!
+//! [`PlayerTransportData::input_sequence`]: super::PlayerTransportData::input_sequence
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::actor::character::{step_movement, MoveState, MovementTuning};
+
+use super::LobbyState;
+
+/// Inputs kept for a replay, the oldest are dropped when the host stops acknowledging them
+const MAX_HISTORY: usize = 256;
+/// Seconds a correction is smoothed out over
+const CORRECTION_TIME: f32 = 0.1;
+/// Correction (in units) taken at once, e.g. a respawn or a move rejected by the host
+const SNAP_DISTANCE: f32 = 2.;
+
+#[derive(Debug, Clone, Copy)]
+struct PredictedInput {
+    sequence: u32,
+    movement: Vec2,
+    /// Simulated seconds the input was applied for
+    dt: f32,
+    /// Velocity once applied, the start of a replay from this input
+    velocity: Vec3,
+}
+
+/// Inputs of the own character not included in a snapshot yet, and its predicted state.
+#[derive(Debug, Default, Resource)]
+pub struct Prediction {
+    last_sequence: u32,
+    /// Oldest first
+    history: VecDeque<PredictedInput>,
+    /// `None` until a snapshot placed the character
+    state: Option<MoveState>,
+    /// Added to the predicted position while a correction is smoothed out
+    correction: Vec3,
+}
+
+impl Prediction {
+    /// Number of the next input, counting from `1`.
+    pub fn next_sequence(&mut self) -> u32 {
+        self.last_sequence += 1;
+        self.last_sequence
+    }
+
+    /// Applies the input `sequence` to the predicted state and smooths the correction on,
+    /// returns the position to show once a snapshot placed the character.
+    pub fn predict(
+        &mut self,
+        sequence: u32,
+        movement: Vec2,
+        dt: f32,
+        tuning: &MovementTuning,
+    ) -> Option<Vec3> {
+        let state = self.state.map(|state| step_movement(state, movement, tuning, dt));
+        self.state = state;
+        if self.history.len() >= MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(PredictedInput {
+            sequence,
+            movement,
+            dt,
+            velocity: state.map_or(Vec3::ZERO, |state| state.velocity),
+        });
+        self.correction = self.correction.lerp(Vec3::ZERO, (dt / CORRECTION_TIME).min(1.));
+        state.map(|state| state.position + self.correction)
+    }
+
+    /// Takes `position` of a snapshot including the inputs up to `acknowledged`, replays the
+    /// later ones on it. Returns the position to show instead of `shown`.
+    pub fn reconcile(
+        &mut self,
+        acknowledged: u32,
+        position: Vec3,
+        shown: Vec3,
+        tuning: &MovementTuning,
+    ) -> Vec3 {
+        let velocity = self
+            .history
+            .iter()
+            .find(|input| input.sequence == acknowledged)
+            .map(|input| input.velocity)
+            .or(self.state.map(|state| state.velocity))
+            .unwrap_or_default();
+        self.history.retain(|input| input.sequence > acknowledged);
+
+        let mut state = MoveState { position, velocity };
+        for input in self.history.iter_mut() {
+            state = step_movement(state, input.movement, tuning, input.dt);
+            input.velocity = state.velocity;
+        }
+        let first = self.state.is_none();
+        self.state = Some(state);
+
+        let error = Vec3::new(shown.x - state.position.x, 0., shown.z - state.position.z);
+        if first {
+            self.correction = Vec3::ZERO;
+        } else if error.length() > SNAP_DISTANCE {
+            log::info!("Prediction off by {:.2} units, snapping", error.length());
+            self.correction = Vec3::ZERO;
+        } else {
+            self.correction = error;
+        }
+        state.position + self.correction
+    }
+}
+
+/// Places the own character from a snapshot: its prediction is corrected on a client,
+/// anywhere else (e.g. a replay) the snapshot is taken as it is.
+pub fn reconcile_own(world: &mut World, entity: Entity, mut transform: Transform, sequence: u32) {
+    if *world.resource::<State<LobbyState>>().get() == LobbyState::Client {
+        let shown = world
+            .get::<Transform>(entity)
+            .map_or(transform.translation, |shown| shown.translation);
+        let tuning = *world.resource::<MovementTuning>();
+        let position = transform.translation;
+        let mut prediction = world.resource_mut::<Prediction>();
+        transform.translation = prediction.reconcile(sequence, position, shown, &tuning);
+    }
+    if let Some(mut shown) = world.get_mut::<Transform>(entity) {
+        *shown = transform;
+    } else if let Some(mut entity) = world.get_entity_mut(entity) {
+        entity.insert(transform);
+    }
+}
+
+pub struct PredictionPlugins;
+
+impl Plugin for PredictionPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Prediction>()
+            .add_systems(OnExit(LobbyState::Client), reset_prediction);
+    }
+}
+
+fn reset_prediction(mut prediction: ResMut<Prediction>) {
+    *prediction = Prediction::default();
+}
@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f32::consts::TAU;
+
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy_controls::contract::InputsContainer;
+use bevy_rapier3d::plugin::RapierContext;
+use renet::{RenetClient, RenetServer};
+use serde::{Deserialize, Serialize};
+
+use crate::core::{CoreAction, CoreGameState};
+use crate::network::Channel;
+use crate::physics::groups::camera_ray_filter;
+use crate::replay::ReplayRecorder;
+use crate::ui::GameMenuActionState;
+use crate::world::MainCamera;
+
+use super::client::send_to_server;
+use super::host::broadcast_reliable;
+use super::{ClientMessages, Lobby, LobbyState, PlayerId, ServerMessages};
+
+/// Minimum time (seconds) between two quick chat messages of one player accepted by the host
+pub const QUICK_CHAT_COOLDOWN: f32 = 1.;
+/// Time (seconds) a world ping stays visible
+pub const PING_LIFETIME: f32 = 5.;
+/// Farthest level geometry a ping can be placed on
+const PING_MAX_DISTANCE: f32 = 500.;
+/// Mouse travel (pixels) needed to select a wheel segment
+const WHEEL_DEAD_ZONE: f32 = 20.;
+/// Mouse travel (pixels) the wheel pointer is clamped to
+const WHEEL_RADIUS: f32 = 100.;
+/// Messages kept in the chat feed
+const CHAT_FEED_LEN: usize = 6;
+/// Time (seconds) a message stays in the chat feed
+const CHAT_ENTRY_LIFETIME: f32 = 8.;
+
+/// Predefined messages of the quick chat wheel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum QuickChatKind {
+    Help,
+    Attack,
+    Defend,
+    Retreat,
+    Regroup,
+    Yes,
+    No,
+    Thanks,
+}
+
+impl QuickChatKind {
+    /// Wheel segments, clockwise from the top.
+    pub const ALL: [QuickChatKind; 8] = [
+        QuickChatKind::Help,
+        QuickChatKind::Attack,
+        QuickChatKind::Defend,
+        QuickChatKind::Retreat,
+        QuickChatKind::Regroup,
+        QuickChatKind::Yes,
+        QuickChatKind::No,
+        QuickChatKind::Thanks,
+    ];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            QuickChatKind::Help => "Help!",
+            QuickChatKind::Attack => "Attack!",
+            QuickChatKind::Defend => "Defend!",
+            QuickChatKind::Retreat => "Retreat!",
+            QuickChatKind::Regroup => "Regroup!",
+            QuickChatKind::Yes => "Yes",
+            QuickChatKind::No => "No",
+            QuickChatKind::Thanks => "Thanks",
+        }
+    }
+
+    /// Segment pointed at by `direction` (screen space, y down), `None` inside the dead zone.
+    pub fn from_direction(direction: Vec2) -> Option<Self> {
+        if direction.length() < WHEEL_DEAD_ZONE {
+            return None;
+        }
+        // 0 at the top, growing clockwise
+        let angle = direction.x.atan2(-direction.y).rem_euclid(TAU);
+        let segment = TAU / Self::ALL.len() as f32;
+        let index = ((angle + segment / 2.) / segment) as usize % Self::ALL.len();
+        Some(Self::ALL[index])
+    }
+}
+
+/// A quick chat message to show, whatever lobby mode it came from.
+#[derive(Debug, Clone, Event)]
+pub struct QuickChatEvent {
+    pub from: PlayerId,
+    pub kind: QuickChatKind,
+    pub world_pos: Option<Vec3>,
+}
+
+/// A text chat message to show, whatever lobby mode it came from.
+#[derive(Debug, Clone, Event)]
+pub struct ChatEvent {
+    pub from: PlayerId,
+    pub text: String,
+}
+
+/// A quick chat message chosen by the local player, sent according to the lobby mode.
+#[derive(Debug, Clone, Event)]
+pub struct SendQuickChat {
+    pub kind: QuickChatKind,
+    pub world_pos: Option<Vec3>,
+}
+
+/// State of the wheel while the quick chat key is held.
+#[derive(Debug, Default, Resource)]
+pub struct QuickChatWheel {
+    pub open: bool,
+    /// Mouse travel since the wheel was opened.
+    pub pointer: Vec2,
+}
+
+impl QuickChatWheel {
+    pub fn hovered(&self) -> Option<QuickChatKind> {
+        QuickChatKind::from_direction(self.pointer)
+    }
+}
+
+/// Host side rate limit of quick chat messages.
+#[derive(Debug, Default, Resource)]
+pub struct QuickChatLimiter {
+    last_sent: HashMap<PlayerId, f32>,
+}
+
+impl QuickChatLimiter {
+    /// Whether `player` may send a message at `now` (seconds), remembers it if so.
+    pub fn allow(&mut self, player: PlayerId, now: f32) -> bool {
+        match self.last_sent.get(&player) {
+            Some(last) if now - last < QUICK_CHAT_COOLDOWN => false,
+            _ => {
+                self.last_sent.insert(player, now);
+                true
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatEntry {
+    pub from: String,
+    pub text: String,
+    timer: Timer,
+}
+
+/// Recent messages shown by the chat feed.
+#[derive(Debug, Default, Resource)]
+pub struct ChatFeed {
+    pub entries: VecDeque<ChatEntry>,
+}
+
+impl ChatFeed {
+    pub fn push(&mut self, from: String, text: String) {
+        if self.entries.len() == CHAT_FEED_LEN {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(ChatEntry {
+            from,
+            text,
+            timer: Timer::from_seconds(CHAT_ENTRY_LIFETIME, TimerMode::Once),
+        });
+    }
+}
+
+/// Players whose chat the local player does not want to see, toggled on the scoreboard.
+///
+/// Local only, the host still relays their messages. Kept until the game is closed.
+#[derive(Debug, Default, Resource)]
+pub struct MutedPlayers(pub HashSet<PlayerId>);
+
+impl MutedPlayers {
+    pub fn is_muted(&self, id: &PlayerId) -> bool {
+        self.0.contains(id)
+    }
+
+    pub fn toggle(&mut self, id: PlayerId) {
+        if !self.0.remove(&id) {
+            self.0.insert(id);
+        }
+    }
+}
+
+/// A world ping, despawned once its timer finishes.
+#[derive(Debug, Component)]
+pub struct PingMarker(Timer);
+
+/// Keeps facing the main camera.
+#[derive(Debug, Component)]
+pub struct Billboard;
+
+pub struct QuickChatPlugins;
+
+impl Plugin for QuickChatPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<QuickChatEvent>()
+            .add_event::<ChatEvent>()
+            .add_event::<SendQuickChat>()
+            .init_resource::<QuickChatWheel>()
+            .init_resource::<QuickChatLimiter>()
+            .init_resource::<ChatFeed>()
+            .init_resource::<MutedPlayers>()
+            .add_systems(
+                Update,
+                quick_chat_wheel.run_if(
+                    not(in_state(LobbyState::None))
+                        .and_then(in_state(CoreGameState::InGame))
+                        .and_then(in_state(GameMenuActionState::Disable)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    send_single.run_if(in_state(LobbyState::Single)),
+                    send_host.run_if(
+                        in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>),
+                    ),
+                    send_client.run_if(
+                        in_state(LobbyState::Client).and_then(resource_exists::<RenetClient>),
+                    ),
+                )
+                    .after(quick_chat_wheel),
+            )
+            .add_systems(
+                Update,
+                (
+                    show_quick_chat,
+                    show_chat,
+                    update_chat_feed,
+                    update_ping_markers,
+                    face_camera,
+                ),
+            )
+            .add_systems(OnExit(LobbyState::None), reset_quick_chat);
+    }
+}
+
+fn reset_quick_chat(
+    mut wheel: ResMut<QuickChatWheel>,
+    mut limiter: ResMut<QuickChatLimiter>,
+    mut feed: ResMut<ChatFeed>,
+) {
+    *wheel = QuickChatWheel::default();
+    limiter.last_sent.clear();
+    feed.entries.clear();
+}
+
+/// Opens the wheel while the quick chat key is held and sends the hovered message on release.
+fn quick_chat_wheel(
+    inputs_container: Res<Lobby>,
+    mut wheel: ResMut<QuickChatWheel>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    rapier_context: Res<RapierContext>,
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+    mut send_event: EventWriter<SendQuickChat>,
+) {
+    let pressed = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_pressed(CoreAction::QuickChat))
+        .unwrap_or(false);
+
+    if pressed {
+        let delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+        wheel.open = true;
+        wheel.pointer = (wheel.pointer + delta).clamp_length_max(WHEEL_RADIUS);
+        return;
+    }
+    mouse_motion.clear();
+
+    if !wheel.open {
+        return;
+    }
+    if let Some(kind) = wheel.hovered() {
+        let world_pos = camera_query.get_single().ok().and_then(|camera| {
+            let origin = camera.translation();
+            let direction = camera.forward();
+            rapier_context
+                .cast_ray(
+                    origin,
+                    direction,
+                    PING_MAX_DISTANCE,
+                    true,
+                    camera_ray_filter(),
+                )
+                .map(|(_, toi)| origin + direction * toi)
+        });
+        send_event.send(SendQuickChat { kind, world_pos });
+    }
+    *wheel = QuickChatWheel::default();
+}
+
+fn send_single(
+    mut send_event: EventReader<SendQuickChat>,
+    mut quick_chat_event: EventWriter<QuickChatEvent>,
+) {
+    for SendQuickChat { kind, world_pos } in send_event.read() {
+        quick_chat_event.send(QuickChatEvent {
+            from: PlayerId::HostOrSingle,
+            kind: *kind,
+            world_pos: *world_pos,
+        });
+    }
+}
+
+fn send_host(
+    mut send_event: EventReader<SendQuickChat>,
+    mut server: ResMut<RenetServer>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
+    mut quick_chat_event: EventWriter<QuickChatEvent>,
+) {
+    for SendQuickChat { kind, world_pos } in send_event.read() {
+        let message = bincode::serialize(&ServerMessages::QuickChat {
+            from: PlayerId::HostOrSingle,
+            kind: *kind,
+            world_pos: *world_pos,
+        })
+        .unwrap();
+        broadcast_reliable(&mut server, recorder.as_deref_mut(), message);
+
+        quick_chat_event.send(QuickChatEvent {
+            from: PlayerId::HostOrSingle,
+            kind: *kind,
+            world_pos: *world_pos,
+        });
+    }
+}
+
+fn send_client(mut send_event: EventReader<SendQuickChat>, mut client: ResMut<RenetClient>) {
+    for SendQuickChat { kind, world_pos } in send_event.read() {
+        send_to_server(
+            &mut client,
+            &ClientMessages::QuickChat {
+                kind: *kind,
+                world_pos: *world_pos,
+            },
+            Channel::Control,
+        );
+    }
+}
+
+/// Username and color of the player who sent a message.
+pub fn sender(lobby: Option<&Lobby>, from: &PlayerId) -> (String, Color) {
+    lobby
+        .and_then(|lobby| lobby.player(from))
+        .map(|player_data| (player_data.username.clone(), player_data.color))
+        .unwrap_or_else(|| ("?".to_string(), Color::WHITE))
+}
+
+/// Puts quick chat messages into the [`ChatFeed`] and spawns their world pings.
+///
+/// The pings of [`MutedPlayers`] are still shown, they are part of the game.
+fn show_quick_chat(
+    mut commands: Commands,
+    mut quick_chat_event: EventReader<QuickChatEvent>,
+    lobby: Option<Res<Lobby>>,
+    muted: Res<MutedPlayers>,
+    mut feed: ResMut<ChatFeed>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for QuickChatEvent {
+        from,
+        kind,
+        world_pos,
+    } in quick_chat_event.read()
+    {
+        let (username, color) = sender(lobby.as_deref(), from);
+
+        if !muted.is_muted(from) {
+            feed.push(username, kind.text().to_string());
+        }
+
+        if let Some(world_pos) = world_pos {
+            commands.spawn((
+                PbrBundle {
+                    mesh: meshes.add(Rectangle::new(1., 1.)),
+                    material: materials.add(StandardMaterial {
+                        base_color: color,
+                        unlit: true,
+                        double_sided: true,
+                        cull_mode: None,
+                        ..default()
+                    }),
+                    transform: Transform::from_translation(*world_pos + Vec3::Y),
+                    ..default()
+                },
+                PingMarker(Timer::from_seconds(PING_LIFETIME, TimerMode::Once)),
+                Billboard,
+                Name::new(format!("Ping:{}", kind.text())),
+            ));
+        }
+    }
+}
+
+/// Puts text chat messages into the [`ChatFeed`], except the ones of [`MutedPlayers`].
+fn show_chat(
+    mut chat_event: EventReader<ChatEvent>,
+    lobby: Option<Res<Lobby>>,
+    muted: Res<MutedPlayers>,
+    mut feed: ResMut<ChatFeed>,
+) {
+    for ChatEvent { from, text } in chat_event.read() {
+        if muted.is_muted(from) {
+            continue;
+        }
+        let (username, _) = sender(lobby.as_deref(), from);
+        feed.push(username, text.clone());
+    }
+}
+
+fn update_chat_feed(time: Res<Time>, mut feed: ResMut<ChatFeed>) {
+    for entry in feed.entries.iter_mut() {
+        entry.timer.tick(time.delta());
+    }
+    feed.entries.retain(|entry| !entry.timer.finished());
+}
+
+fn update_ping_markers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut PingMarker)>,
+) {
+    for (entity, mut marker) in query.iter_mut() {
+        if marker.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn face_camera(
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+    mut query: Query<&mut Transform, With<Billboard>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    for mut transform in query.iter_mut() {
+        let target = transform.translation * 2. - camera.translation();
+        transform.look_at(target, Vec3::Y);
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use renet::{RenetClient, RenetServer};
+
+use crate::core::LoadLevelEvent;
+use crate::network::Channel;
+
+use super::client::send_to_server;
+use super::host::ServerSettings;
+use super::outbox::ServerOutbox;
+use super::{
+    ChangeMapLobbyEvent, ClientMessages, LevelCode, Lobby, LobbyState, PlayerId, ServerMessages,
+};
+
+/// Seconds between everyone being ready and the match level being loaded
+pub const MATCH_COUNTDOWN: f32 = 5.;
+
+/// Match started by the host once every player is ready, enabled by
+/// [`ServerSettings::match_setup`]. Without it the players stay in the hub.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSetup {
+    pub level: LevelCode,
+    /// Players needed to start, the host included
+    pub min_players: usize,
+}
+
+/// Whether every player of the lobby is ready, empty while no match is set up.
+///
+/// Kept by the host, clients get it with [`ServerMessages::ReadyStates`].
+#[derive(Debug, Default, Clone, Resource)]
+pub struct ReadyStates(pub HashMap<PlayerId, bool>);
+
+impl ReadyStates {
+    pub fn is_ready(&self, id: &PlayerId) -> bool {
+        self.0.get(id).copied().unwrap_or(false)
+    }
+
+    fn all_ready(&self) -> bool {
+        self.0.values().all(|ready| *ready)
+    }
+}
+
+/// Countdown to the match start, `None` while not everyone is ready.
+///
+/// Ticked on the clients too, to show the remaining time.
+#[derive(Debug, Default, Resource)]
+pub struct MatchCountdown(pub Option<Timer>);
+
+impl MatchCountdown {
+    pub fn start(&mut self, seconds: f32) {
+        self.0 = Some(Timer::from_seconds(seconds, TimerMode::Once));
+    }
+
+    pub fn remaining_secs(&self) -> Option<f32> {
+        self.0.as_ref().map(Timer::remaining_secs)
+    }
+}
+
+/// The countdown ended and the match level is being loaded, the scores start over.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct MatchStartedEvent;
+
+/// The own player wants to be ready or not, sent to the host.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct SendReady(pub bool);
+
+/// A player is ready or not anymore, handled by the host.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct ReadyRequest {
+    pub id: PlayerId,
+    pub ready: bool,
+}
+
+pub struct ReadyPlugins;
+
+impl Plugin for ReadyPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReadyStates>()
+            .init_resource::<MatchCountdown>()
+            .add_event::<MatchStartedEvent>()
+            .add_event::<SendReady>()
+            .add_event::<ReadyRequest>()
+            .add_systems(
+                Update,
+                (
+                    send_host_ready,
+                    follow_match_setup,
+                    set_ready,
+                    start_countdown,
+                    run_countdown,
+                )
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    send_client_ready.run_if(resource_exists::<RenetClient>),
+                    tick_countdown,
+                )
+                    .run_if(in_state(LobbyState::Client)),
+            )
+            .add_systems(OnEnter(LobbyState::None), clear_ready);
+    }
+}
+
+fn send_host_ready(mut send_event: EventReader<SendReady>, mut request: EventWriter<ReadyRequest>) {
+    for SendReady(ready) in send_event.read() {
+        request.send(ReadyRequest {
+            id: PlayerId::HostOrSingle,
+            ready: *ready,
+        });
+    }
+}
+
+fn send_client_ready(mut send_event: EventReader<SendReady>, mut client: ResMut<RenetClient>) {
+    for SendReady(ready) in send_event.read() {
+        send_to_server(&mut client, &ClientMessages::SetReady(*ready), Channel::Control);
+    }
+}
+
+/// Keeps a ready state for every player while a match is set up, bots do not count.
+///
+/// A player joining during the countdown gets the remaining time, it does not stop it.
+fn follow_match_setup(
+    settings: Res<ServerSettings>,
+    lobby: Res<Lobby>,
+    mut ready_states: ResMut<ReadyStates>,
+    mut countdown: ResMut<MatchCountdown>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    let players: Vec<PlayerId> = match settings.match_setup {
+        Some(_) => std::iter::once(PlayerId::HostOrSingle)
+            .chain(lobby.players.keys().copied())
+            .filter(|id| !matches!(id, PlayerId::Bot(_)))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let left = ready_states.0.len();
+    ready_states.0.retain(|id, _| players.contains(id));
+    let mut changed = ready_states.0.len() != left;
+    for id in players {
+        if ready_states.0.contains_key(&id) {
+            continue;
+        }
+        ready_states.0.insert(id, false);
+        changed = true;
+        if let (PlayerId::Client(client_id), Some(seconds)) = (id, countdown.remaining_secs()) {
+            outbox.queue_for(
+                client_id,
+                ServerMessages::MatchStarting {
+                    countdown_secs: seconds,
+                },
+            );
+        }
+    }
+
+    if settings.match_setup.is_none() && countdown.0.is_some() {
+        log::info!("Match setup removed, countdown cancelled");
+        countdown.0 = None;
+        outbox.queue(ServerMessages::MatchCountdownCancelled);
+    }
+    if changed {
+        outbox.queue(ServerMessages::ReadyStates(ready_states.0.clone()));
+    }
+}
+
+fn set_ready(
+    mut requests: EventReader<ReadyRequest>,
+    mut ready_states: ResMut<ReadyStates>,
+    mut countdown: ResMut<MatchCountdown>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    for ReadyRequest { id, ready } in requests.read() {
+        let Some(state) = ready_states.0.get_mut(id) else {
+            log::debug!("Ready state of {:?} ignored: no match is set up", id);
+            continue;
+        };
+        if *state == *ready {
+            continue;
+        }
+        *state = *ready;
+        log::info!("{:?} is {}", id, if *ready { "ready" } else { "not ready" });
+        if !*ready && countdown.0.take().is_some() {
+            log::info!("Countdown cancelled by {:?}", id);
+            outbox.queue(ServerMessages::MatchCountdownCancelled);
+        }
+        outbox.queue(ServerMessages::ReadyStates(ready_states.0.clone()));
+    }
+}
+
+fn start_countdown(
+    settings: Res<ServerSettings>,
+    ready_states: Res<ReadyStates>,
+    mut countdown: ResMut<MatchCountdown>,
+    mut outbox: ResMut<ServerOutbox>,
+) {
+    let Some(setup) = settings.match_setup.as_ref() else {
+        return;
+    };
+    if countdown.0.is_some()
+        || ready_states.0.len() < setup.min_players.max(1)
+        || !ready_states.all_ready()
+    {
+        return;
+    }
+    log::info!("Everyone is ready, {:?} starts in {}s", setup.level, MATCH_COUNTDOWN);
+    countdown.start(MATCH_COUNTDOWN);
+    outbox.queue(ServerMessages::MatchStarting {
+        countdown_secs: MATCH_COUNTDOWN,
+    });
+}
+
+/// Loads the match level once the countdown ends, everyone has to be ready again for the next.
+#[allow(clippy::too_many_arguments)]
+fn run_countdown(
+    time: Res<Time>,
+    settings: Res<ServerSettings>,
+    mut ready_states: ResMut<ReadyStates>,
+    mut countdown: ResMut<MatchCountdown>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut change_map_event: EventWriter<ChangeMapLobbyEvent>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+    mut match_started_event: EventWriter<MatchStartedEvent>,
+) {
+    let Some(timer) = countdown.0.as_mut() else {
+        return;
+    };
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+    countdown.0 = None;
+    let Some(setup) = settings.match_setup.as_ref() else {
+        return;
+    };
+
+    log::info!("Match on {:?} started", setup.level);
+    change_map_event.send(ChangeMapLobbyEvent(setup.level.clone()));
+    load_level_event.send(LoadLevelEvent::new(setup.level.clone()));
+    match_started_event.send(MatchStartedEvent);
+    outbox.queue(ServerMessages::MatchStarted);
+
+    for ready in ready_states.0.values_mut() {
+        *ready = false;
+    }
+    outbox.queue(ServerMessages::ReadyStates(ready_states.0.clone()));
+}
+
+fn tick_countdown(time: Res<Time>, mut countdown: ResMut<MatchCountdown>) {
+    if let Some(timer) = countdown.0.as_mut() {
+        timer.tick(time.delta());
+    }
+}
+
+fn clear_ready(mut ready_states: ResMut<ReadyStates>, mut countdown: ResMut<MatchCountdown>) {
+    ready_states.0.clear();
+    countdown.0 = None;
+}
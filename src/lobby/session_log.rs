@@ -0,0 +1,460 @@
+//! Timeline of a hosted session for a review after the game: joins, leaves, map changes,
+//! deaths, chat lines and kicks.
+//!
+//! The host keeps the last [`ServerSettings::session_log_capacity`] events in the
+//! [`SessionLog`], from hosting until going back to the menu, map changes included.
+//! [`ExportSessionLogEvent`] writes them as JSON lines into `sessions/` next to the settings,
+//! a client asks the host for them with [`ClientMessages::RequestSessionLog`] if the host
+//! shares them, see [`ServerRules::share_session_log`].
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use renet::{ClientId, RenetClient, RenetServer};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "dev")]
+use crate::console::{no_args, CommandResult, CommandScope, ConsoleAppExt};
+use crate::core::CurrentLevel;
+use crate::network::{Channel, ChunkSender};
+use crate::settings::settings_dir;
+
+use super::admin::KickPlayerEvent;
+use super::client::send_to_server;
+use super::host::{send_large_message, ServerSettings};
+use super::host_settings::ServerRules;
+use super::quick_chat::ChatEvent;
+use super::{
+    ClientMessages, DeathCause, LevelCode, Lobby, LobbyState, MapLoaderState, PlayerDiedEvent,
+    PlayerId, ServerMessages,
+};
+
+/// Seconds a client waits between two copies of the log
+const REQUEST_COOLDOWN: f64 = 10.;
+
+/// Something that happened in the session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SessionEvent {
+    Joined { player: String },
+    Left { player: String },
+    /// A level was loaded, the first one of the session too
+    MapChanged { map: LevelCode },
+    Died {
+        player: String,
+        killer: Option<String>,
+        cause: DeathCause,
+    },
+    Chat { player: String, text: String },
+    Kicked { player: String, reason: String },
+}
+
+/// A [`SessionEvent`] and when it happened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionLogEntry {
+    /// Seconds since the session started
+    pub at: f64,
+    pub event: SessionEvent,
+}
+
+/// Events of the hosted session, the oldest are dropped past the capacity.
+#[derive(Debug, Resource)]
+pub struct SessionLog {
+    /// Unix time in seconds, names the exported file
+    started_at: u64,
+    /// [`Time::elapsed_seconds_f64`] the session started at
+    started: f64,
+    capacity: usize,
+    entries: VecDeque<SessionLogEntry>,
+    /// Last known username of the players, the ones who left included
+    usernames: HashMap<PlayerId, String>,
+    /// Clients in the lobby, to tell who joined or left
+    connected: HashSet<PlayerId>,
+    /// When the clients were last sent the log, see [`REQUEST_COOLDOWN`]
+    sent: HashMap<ClientId, f64>,
+}
+
+impl SessionLog {
+    pub fn new(capacity: usize, started: f64) -> Self {
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        Self {
+            started_at,
+            started,
+            capacity,
+            entries: VecDeque::with_capacity(capacity.min(1024)),
+            usernames: HashMap::new(),
+            connected: HashSet::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    pub fn started_at(&self) -> u64 {
+        self.started_at
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &SessionLogEntry> {
+        self.entries.iter()
+    }
+
+    /// Adds an event that happened at `now`, in [`Time::elapsed_seconds_f64`].
+    pub fn record(&mut self, now: f64, event: SessionEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(SessionLogEntry {
+            at: now - self.started,
+            event,
+        });
+    }
+
+    /// Username of a player, also once the player left.
+    fn username(&self, id: &PlayerId) -> String {
+        self.usernames
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| format!("{:?}", id))
+    }
+}
+
+/// Writes the [`SessionLog`] on the host, asks the host for it on a client.
+#[derive(Debug, Clone, Copy, Default, Event)]
+pub struct ExportSessionLogEvent;
+
+pub struct SessionLogPlugins;
+
+impl Plugin for SessionLogPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportSessionLogEvent>()
+            .add_systems(OnEnter(LobbyState::Host), start_session_log)
+            .add_systems(
+                OnEnter(MapLoaderState::Yes),
+                record_map
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<SessionLog>)),
+            )
+            .add_systems(
+                Update,
+                (record_players, record_events, export_session_log)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<SessionLog>)),
+            )
+            .add_systems(
+                Update,
+                request_session_log
+                    .run_if(in_state(LobbyState::Client).and_then(resource_exists::<RenetClient>)),
+            )
+            .add_systems(OnExit(LobbyState::Host), end_session_log);
+        #[cfg(feature = "dev")]
+        app.add_console_command("session_log", "", CommandScope::Local, no_args, export_command);
+    }
+}
+
+fn sessions_dir() -> PathBuf {
+    settings_dir().join("sessions")
+}
+
+/// Writes one JSON object per line into `session-<started_at>.jsonl` of `dir`.
+pub fn write_session_log<'a>(
+    dir: &Path,
+    started_at: u64,
+    entries: impl Iterator<Item = &'a SessionLogEntry>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("session-{}.jsonl", started_at));
+    let mut file = BufWriter::new(File::create(&path)?);
+    for entry in entries {
+        serde_json::to_writer(&mut file, entry)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()?;
+    Ok(path)
+}
+
+fn start_session_log(mut commands: Commands, time: Res<Time>, settings: Res<ServerSettings>) {
+    commands.insert_resource(SessionLog::new(
+        settings.session_log_capacity,
+        time.elapsed_seconds_f64(),
+    ));
+}
+
+fn end_session_log(mut commands: Commands) {
+    commands.remove_resource::<SessionLog>();
+}
+
+fn record_map(
+    time: Res<Time>,
+    current_level: Res<CurrentLevel>,
+    mut session_log: ResMut<SessionLog>,
+) {
+    session_log.record(
+        time.elapsed_seconds_f64(),
+        SessionEvent::MapChanged {
+            map: current_level.0.clone(),
+        },
+    );
+}
+
+/// Compares the clients of the lobby with the ones of the last change, the bots are left out.
+fn record_players(time: Res<Time>, lobby: Res<Lobby>, mut session_log: ResMut<SessionLog>) {
+    let now = time.elapsed_seconds_f64();
+    if !session_log.usernames.contains_key(&PlayerId::HostOrSingle) {
+        let player = lobby.me.username.clone();
+        session_log.usernames.insert(PlayerId::HostOrSingle, player.clone());
+        session_log.record(now, SessionEvent::Joined { player });
+    }
+    if !lobby.is_changed() {
+        return;
+    }
+
+    for (id, player_data) in lobby.players.iter() {
+        if !matches!(id, PlayerId::Client(_)) || session_log.connected.contains(id) {
+            continue;
+        }
+        let player = player_data.username.clone();
+        session_log.connected.insert(*id);
+        session_log.usernames.insert(*id, player.clone());
+        session_log.record(now, SessionEvent::Joined { player });
+    }
+    let left: Vec<PlayerId> = session_log
+        .connected
+        .iter()
+        .filter(|id| !lobby.players.contains_key(id))
+        .copied()
+        .collect();
+    for id in left {
+        session_log.connected.remove(&id);
+        let player = session_log.username(&id);
+        session_log.record(now, SessionEvent::Left { player });
+    }
+}
+
+fn record_events(
+    time: Res<Time>,
+    lobby: Res<Lobby>,
+    mut player_died_event: EventReader<PlayerDiedEvent>,
+    mut chat_event: EventReader<ChatEvent>,
+    mut kick_event: EventReader<KickPlayerEvent>,
+    mut session_log: ResMut<SessionLog>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let username = |session_log: &SessionLog, id: &PlayerId| match lobby.player(id) {
+        Some(player_data) => player_data.username.clone(),
+        None => session_log.username(id),
+    };
+
+    for PlayerDiedEvent { id, killer, cause } in player_died_event.read() {
+        let event = SessionEvent::Died {
+            player: username(&session_log, id),
+            killer: killer.map(|killer| username(&session_log, &killer)),
+            cause: *cause,
+        };
+        session_log.record(now, event);
+    }
+    for ChatEvent { from, text } in chat_event.read() {
+        let event = SessionEvent::Chat {
+            player: username(&session_log, from),
+            text: text.clone(),
+        };
+        session_log.record(now, event);
+    }
+    for KickPlayerEvent { client_id, reason } in kick_event.read() {
+        let event = SessionEvent::Kicked {
+            player: username(&session_log, &PlayerId::Client(*client_id)),
+            reason: reason.clone(),
+        };
+        session_log.record(now, event);
+    }
+}
+
+fn export_session_log(
+    mut export_event: EventReader<ExportSessionLogEvent>,
+    session_log: Res<SessionLog>,
+) {
+    if export_event.read().count() == 0 {
+        return;
+    }
+    let dir = sessions_dir();
+    match write_session_log(&dir, session_log.started_at, session_log.entries()) {
+        Ok(path) => log::info!("Session log written to {:?}", path),
+        Err(err) => log::error!("Failed to write the session log into {:?}: {}", dir, err),
+    }
+}
+
+fn request_session_log(
+    mut export_event: EventReader<ExportSessionLogEvent>,
+    rules: Res<ServerRules>,
+    mut client: ResMut<RenetClient>,
+) {
+    if export_event.read().count() == 0 {
+        return;
+    }
+    if !rules.share_session_log {
+        log::info!("The host does not share the session log");
+        return;
+    }
+    send_to_server(&mut client, &ClientMessages::RequestSessionLog, Channel::Control);
+}
+
+/// Answers a [`ClientMessages::RequestSessionLog`] over [`Channel::Bulk`], if the host shares
+/// the log and the client did not get it recently.
+pub fn send(world: &mut World, client_id: ClientId) {
+    if !world.resource::<ServerSettings>().share_session_log {
+        log::debug!("Session log not sent to {}: sharing is off", client_id);
+        return;
+    }
+    let now = world.resource::<Time>().elapsed_seconds_f64();
+    let Some(mut session_log) = world.get_resource_mut::<SessionLog>() else {
+        return;
+    };
+    if session_log
+        .sent
+        .get(&client_id)
+        .is_some_and(|sent| now - sent < REQUEST_COOLDOWN)
+    {
+        log::debug!("Session log not sent to {}: asked again too soon", client_id);
+        return;
+    }
+    session_log.sent.insert(client_id, now);
+    let message = ServerMessages::SessionLog {
+        started_at: session_log.started_at,
+        entries: session_log.entries.iter().cloned().collect(),
+    };
+
+    world.resource_scope(|world, mut server: Mut<RenetServer>| {
+        let mut chunk_sender = world.resource_mut::<ChunkSender>();
+        send_large_message(&mut server, &mut chunk_sender, client_id, &message);
+    });
+}
+
+/// Writes a [`ServerMessages::SessionLog`] next to the logs this machine hosted.
+pub fn receive(started_at: u64, entries: Vec<SessionLogEntry>) {
+    let dir = sessions_dir();
+    match write_session_log(&dir, started_at, entries.iter()) {
+        Ok(path) => log::info!("Session log of the host written to {:?}", path),
+        Err(err) => log::error!("Failed to write the session log into {:?}: {}", dir, err),
+    }
+}
+
+#[cfg(feature = "dev")]
+fn export_command(world: &mut World, _: ()) -> CommandResult {
+    match *world.resource::<State<LobbyState>>().get() {
+        LobbyState::Host => {
+            world.send_event(ExportSessionLogEvent);
+            Ok(Some(format!("writing the session log into {:?}", sessions_dir())))
+        }
+        LobbyState::Client if world.resource::<ServerRules>().share_session_log => {
+            world.send_event(ExportSessionLogEvent);
+            Ok(Some("asked the host for the session log".to_string()))
+        }
+        LobbyState::Client => Err("the host does not share the session log".to_string()),
+        _ => Err("only a multiplayer session has a log".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::KnownLevel;
+
+    fn events() -> Vec<SessionEvent> {
+        vec![
+            SessionEvent::Joined {
+                player: "ann".to_string(),
+            },
+            SessionEvent::MapChanged {
+                map: LevelCode::Known(KnownLevel::Hub),
+            },
+            SessionEvent::Died {
+                player: "ann".to_string(),
+                killer: Some("bob".to_string()),
+                cause: DeathCause::Killed,
+            },
+            SessionEvent::Chat {
+                player: "bob".to_string(),
+                text: "gg \"ann\"\nagain?".to_string(),
+            },
+            SessionEvent::Kicked {
+                player: "bob".to_string(),
+                reason: "afk".to_string(),
+            },
+            SessionEvent::Left {
+                player: "ann".to_string(),
+            },
+        ]
+    }
+
+    fn session_log(capacity: usize) -> SessionLog {
+        let mut session_log = SessionLog::new(capacity, 100.);
+        for (i, event) in events().into_iter().enumerate() {
+            session_log.record(100. + i as f64, event);
+        }
+        session_log
+    }
+
+    #[test]
+    fn entries_are_timed_from_the_session_start() {
+        let session_log = session_log(16);
+        let at: Vec<f64> = session_log.entries().map(|entry| entry.at).collect();
+        assert_eq!(at, vec![0., 1., 2., 3., 4., 5.]);
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped_past_the_capacity() {
+        let session_log = session_log(2);
+        let kept: Vec<SessionEvent> = session_log
+            .entries()
+            .map(|entry| entry.event.clone())
+            .collect();
+        assert_eq!(kept, events()[4..].to_vec());
+
+        assert_eq!(session_log(0).entries().count(), 0);
+    }
+
+    #[test]
+    fn entries_round_trip_through_bincode() {
+        let entries: Vec<SessionLogEntry> = session_log(16).entries().cloned().collect();
+        let message = ServerMessages::SessionLog {
+            started_at: 42,
+            entries: entries.clone(),
+        };
+        let bytes = bincode::serialize(&message).unwrap();
+        match bincode::deserialize(&bytes).unwrap() {
+            ServerMessages::SessionLog {
+                started_at,
+                entries: decoded,
+            } => {
+                assert_eq!(started_at, 42);
+                assert_eq!(decoded, entries);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn export_writes_one_json_entry_per_line() {
+        let dir = std::env::temp_dir().join(format!("urmom-session-log-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let session_log = session_log(16);
+
+        let path = write_session_log(&dir, 1234, session_log.entries()).unwrap();
+        assert_eq!(path, dir.join("session-1234.jsonl"));
+
+        let content = fs::read_to_string(&path).unwrap();
+        let read: Vec<SessionLogEntry> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let written: Vec<SessionLogEntry> = session_log.entries().cloned().collect();
+        assert_eq!(read, written);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
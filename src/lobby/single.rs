@@ -2,51 +2,112 @@ use crate::component::{DespawnReason, Respawn};
 use crate::core::CoreGameState;
 use crate::lobby::host::generate_player_color;
 use crate::lobby::LobbyState;
+use crate::ui::GameMenuActionState;
 use crate::world::Me;
 use crate::{
     actor::{
         character::{spawn_character, spawn_tied_camera, TiedCamera},
         UnloadActorsEvent,
     },
-    core::KnownLevel,
-    world::SpawnProperty,
+    core::CoreConfig,
+    world::{seeded_rng, SharedSeed, SpawnProperty},
 };
 use bevy::app::{App, Plugin, Update};
 use bevy::ecs::entity::Entity;
 use bevy::ecs::event::{EventReader, EventWriter, Events};
 use bevy::ecs::query::With;
-use bevy::ecs::schedule::{Condition, NextState, OnExit};
-use bevy::ecs::system::{Query, Res, ResMut};
+use bevy::ecs::schedule::{Condition, NextState, OnExit, States};
+use bevy::ecs::system::{Query, Res, ResMut, Resource};
 use bevy::hierarchy::DespawnRecursiveExt;
 use bevy::prelude::{in_state, Commands, IntoSystemConfigs, OnEnter};
+use bevy::time::{Time, Virtual};
+use bevy::transform::components::Transform;
+use bevy_rapier3d::plugin::RapierConfiguration;
 use log::info;
+use rand::Rng;
 
-use super::{ChangeMapLobbyEvent, Character, LevelCode, PlayerId};
+use super::{ChangeMapLobbyEvent, Character, MapLoaderState, PlayerId};
+
+/// Whether the single player game is frozen while its menu is open.
+///
+/// Only [`LobbyState::Single`] pauses, a networked session goes on for the other players.
+#[derive(Default, Debug, Hash, States, PartialEq, Eq, Clone, Copy)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Transform of the character in a continued save, used instead of a spawn point
+/// for the first spawn of the character.
+#[derive(Debug, Resource)]
+pub struct RestoredTransform(pub Transform);
 
 pub struct SingleLobbyPlugins;
 
 impl Plugin for SingleLobbyPlugins {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(LobbyState::Single), setup)
+        app.insert_state(PauseState::default())
+            .add_systems(OnEnter(LobbyState::Single), setup)
             .add_systems(
                 OnEnter(CoreGameState::LoadLobby),
                 init_lobby.run_if(in_state(LobbyState::Single)),
             )
             .add_systems(
-                OnEnter(CoreGameState::InGame),
-                load_processing.run_if(in_state(LobbyState::Single)),
+                Update,
+                load_processing.run_if(
+                    in_state(LobbyState::Single)
+                        .and_then(in_state(CoreGameState::InGame))
+                        .and_then(in_state(MapLoaderState::No)),
+                ),
             )
             .add_systems(
                 Update,
                 change_map
                     .run_if(in_state(LobbyState::Single).and_then(in_state(CoreGameState::InGame))),
             )
-            .add_systems(OnExit(LobbyState::Single), teardown);
+            .add_systems(
+                OnEnter(GameMenuActionState::Enable),
+                pause.run_if(in_state(LobbyState::Single)),
+            )
+            // also when the menu is closed by quitting, so nothing is left frozen
+            .add_systems(OnEnter(GameMenuActionState::Disable), resume)
+            .add_systems(OnEnter(PauseState::Paused), freeze_simulation)
+            .add_systems(OnExit(PauseState::Paused), unfreeze_simulation)
+            .add_systems(OnExit(LobbyState::Single), (teardown, resume));
     }
 }
 
-fn setup(mut map_events: ResMut<Events<ChangeMapLobbyEvent>>) {
-    map_events.send(ChangeMapLobbyEvent(LevelCode::Known(KnownLevel::Hub)));
+fn pause(mut next_state_pause: ResMut<NextState<PauseState>>) {
+    next_state_pause.set(PauseState::Paused);
+}
+
+fn resume(mut next_state_pause: ResMut<NextState<PauseState>>) {
+    next_state_pause.set(PauseState::Running);
+}
+
+/// Stops the virtual clock, so the fixed ticks (rapier, characters) and the timers stop,
+/// while egui keeps drawing the menu.
+fn freeze_simulation(
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    info!("Paused");
+    time.pause();
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn unfreeze_simulation(
+    mut time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    info!("Resumed");
+    time.unpause();
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn setup(config: Res<CoreConfig>, mut map_events: ResMut<Events<ChangeMapLobbyEvent>>) {
+    map_events.send(ChangeMapLobbyEvent(config.start_level.clone()));
 }
 
 pub fn init_lobby(
@@ -55,33 +116,55 @@ pub fn init_lobby(
         next_state_core.set(CoreGameState::InGame);
 }
 
+/// Spawns (or respawns) the character once the level spawn points are known.
 pub fn load_processing(
     mut commands: Commands,
     spawn_point: Res<SpawnProperty>,
+    seed: Res<SharedSeed>,
+    restored_transform: Option<Res<RestoredTransform>>,
     mut query: Query<&mut Respawn, With<Me>>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
 ) {
-    info!("LoadProcessing: {:#?}", spawn_point);
     if !spawn_point.is_empty() {
+        info!("LoadProcessing: {:#?}", spawn_point);
         match query.get_single_mut() {
             Err(_) => {
                 // spawn character fitst time
-                let random_i32 = rand::random::<i32>();
-                let color = generate_player_color(random_i32 as u32);
-
-                let player_entity = commands
-                    .spawn_character(PlayerId::HostOrSingle, color, spawn_point.random_point())
-                    .insert(Me)
-                    .id();
+                let color = generate_player_color(seeded_rng(&seed, "player_color").gen());
+                let player_entity = match restored_transform {
+                    Some(restored_transform) => {
+                        commands.remove_resource::<RestoredTransform>();
+                        commands
+                            .spawn_character(
+                                PlayerId::HostOrSingle,
+                                color,
+                                restored_transform.0.translation,
+                            )
+                            // replaces the transform of the spawned bundle, rotation included
+                            .insert(restored_transform.0)
+                            .insert(Me)
+                            .id()
+                    }
+                    None => {
+                        let position = spawn_point.point_with(&mut seeded_rng(&seed, "spawn:1"));
+                        commands
+                            .spawn_character(PlayerId::HostOrSingle, color, position)
+                            .insert(Me)
+                            .id()
+                    }
+                };
                 commands.spawn_tied_camera(player_entity);
             }
             Ok(mut respawn) => {
                 // respawn character
-                respawn.replase_spawn_point(spawn_point.clone());
-                respawn.insert_reason(DespawnReason::Forced);
+                respawn.replace_spawn_point(spawn_point.clone());
+                if !respawn.is_pending() {
+                    respawn.insert_reason(DespawnReason::MapChange);
+                }
             }
         }
-    } else {
-        log::error!("No spawn point on level");
+
+        next_state_map.set(MapLoaderState::Yes);
     }
 }
 
@@ -103,7 +186,11 @@ fn teardown(
     tied_camera_query: Query<Entity, With<TiedCamera>>,
     char_query: Query<Entity, With<Character>>,
     mut unload_actors_event: EventWriter<UnloadActorsEvent>,
+    mut next_state_map: ResMut<NextState<MapLoaderState>>,
 ) {
+    next_state_map.set(MapLoaderState::No);
+    // a continue that never got to the spawn
+    commands.remove_resource::<RestoredTransform>();
     if let Ok(entity) = tied_camera_query.get_single() {
         commands.entity(entity).despawn_recursive();
     }
@@ -113,3 +200,36 @@ fn teardown(
 
     unload_actors_event.send(UnloadActorsEvent);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::Assets;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::ecs::world::World;
+    use bevy::math::{Quat, Vec3};
+    use bevy::pbr::StandardMaterial;
+    use bevy::render::mesh::Mesh;
+
+    #[test]
+    fn continue_spawns_at_saved_transform() {
+        let mut world = World::new();
+        world.insert_resource(SpawnProperty::new(Vec3::X));
+        world.insert_resource(SharedSeed(1));
+        world.init_resource::<NextState<MapLoaderState>>();
+        world.init_resource::<Assets<Mesh>>();
+        world.init_resource::<Assets<StandardMaterial>>();
+        let saved = Transform::from_xyz(3., 4., 5.).with_rotation(Quat::from_rotation_y(1.));
+        world.insert_resource(RestoredTransform(saved));
+
+        world.run_system_once(load_processing);
+
+        let transform = *world.query_filtered::<&Transform, With<Me>>().single(&world);
+        assert_eq!(transform, saved);
+        assert!(world.get_resource::<RestoredTransform>().is_none());
+        assert_eq!(
+            world.resource::<NextState<MapLoaderState>>().0,
+            Some(MapLoaderState::Yes)
+        );
+    }
+}
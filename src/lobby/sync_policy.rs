@@ -0,0 +1,165 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use renet::RenetServer;
+
+use crate::world::LinkId;
+
+use super::{LobbyState, TransportData};
+
+/// Window over which [`SyncBandwidth`] is averaged
+const BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
+/// Channel the updates of an actor are sent on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncChannel {
+    /// In the snapshot on [`Channel::State`](crate::network::Channel::State),
+    /// a lost one is not resent
+    #[default]
+    Unreliable,
+    /// In a [`ServerMessages::ReliableSync`](super::ServerMessages::ReliableSync)
+    /// on [`Channel::Control`](crate::network::Channel::Control)
+    Reliable,
+}
+
+/// How the host syncs an entity with a [`LinkId`], actors without one are synced like players:
+/// every snapshot, unreliably, with the whole transform.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct SyncPolicy {
+    /// Name of the kind of actor in the bandwidth table, see [`SyncBandwidth`]
+    pub kind: &'static str,
+    /// Sent in every `divisor`th snapshot only, `1` sends it in every snapshot
+    pub divisor: u32,
+    pub channel: SyncChannel,
+    /// Only the position is sent, the clients keep the rotation they have
+    pub position_only: bool,
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        Self {
+            kind: SyncPolicy::ACTOR_KIND,
+            divisor: 1,
+            channel: SyncChannel::Unreliable,
+            position_only: false,
+        }
+    }
+}
+
+impl SyncPolicy {
+    /// Kind of the characters, which always use the default policy
+    pub const PLAYER_KIND: &'static str = "player";
+    /// Kind of the actors without a policy
+    pub const ACTOR_KIND: &'static str = "actor";
+
+    /// The default policy under another name in the bandwidth table.
+    pub fn kind(kind: &'static str) -> Self {
+        Self {
+            kind,
+            ..default()
+        }
+    }
+
+    /// Whether the actor belongs into the `snapshot`th snapshot.
+    pub fn is_due(&self, snapshot: u64) -> bool {
+        snapshot % self.divisor.max(1) as u64 == 0
+    }
+}
+
+/// Splits a snapshot by the [`SyncChannel`] of its actors, the players stay unreliable.
+///
+/// Actors synced [`SyncPolicy::position_only`] go to [`TransportData::positions`].
+pub fn split_snapshot(
+    snapshot: TransportData,
+    policies: &HashMap<LinkId, SyncPolicy>,
+) -> (TransportData, TransportData) {
+    let mut unreliable = TransportData {
+        players: snapshot.players,
+        ..default()
+    };
+    let mut reliable = TransportData::default();
+    for (link_id, data) in snapshot.actors {
+        let policy = policies.get(&link_id).copied().unwrap_or_default();
+        let target = match policy.channel {
+            SyncChannel::Unreliable => &mut unreliable,
+            SyncChannel::Reliable => &mut reliable,
+        };
+        if policy.position_only {
+            target.positions.insert(link_id, data.position);
+        } else {
+            target.actors.insert(link_id, data);
+        }
+    }
+    (unreliable, reliable)
+}
+
+/// Snapshot bytes sent per kind of actor by the host, summed over the clients.
+///
+/// Counts the serialized entries only, without the message and transport overhead.
+#[derive(Debug, Default, Resource)]
+pub struct SyncBandwidth {
+    counting: HashMap<&'static str, usize>,
+    elapsed: Duration,
+    /// Bytes per second of every kind over the last [`BANDWIDTH_WINDOW`]
+    pub per_second: BTreeMap<&'static str, f64>,
+}
+
+impl SyncBandwidth {
+    /// Counts a snapshot sent to one client.
+    pub fn count(&mut self, snapshot: &TransportData, policies: &HashMap<LinkId, SyncPolicy>) {
+        let kind = |link_id: &LinkId| {
+            policies
+                .get(link_id)
+                .map_or(SyncPolicy::ACTOR_KIND, |policy| policy.kind)
+        };
+        for data in snapshot.players.values() {
+            self.add(SyncPolicy::PLAYER_KIND, bincode::serialized_size(data));
+        }
+        for (link_id, data) in snapshot.actors.iter() {
+            let size = bincode::serialized_size(&(link_id, data));
+            self.add(kind(link_id), size);
+        }
+        for (link_id, position) in snapshot.positions.iter() {
+            let size = bincode::serialized_size(&(link_id, position));
+            self.add(kind(link_id), size);
+        }
+    }
+
+    fn add(&mut self, kind: &'static str, size: bincode::Result<u64>) {
+        *self.counting.entry(kind).or_default() += size.unwrap_or_default() as usize;
+    }
+}
+
+pub struct SyncPolicyPlugins;
+
+impl Plugin for SyncPolicyPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SyncBandwidth>()
+            .add_systems(
+                Update,
+                sample_bandwidth
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(OnExit(LobbyState::Host), clear_bandwidth);
+    }
+}
+
+fn sample_bandwidth(time: Res<Time>, mut bandwidth: ResMut<SyncBandwidth>) {
+    bandwidth.elapsed += time.delta();
+    if bandwidth.elapsed < BANDWIDTH_WINDOW {
+        return;
+    }
+
+    let seconds = bandwidth.elapsed.as_secs_f64();
+    let counted = std::mem::take(&mut bandwidth.counting);
+    bandwidth.per_second = counted
+        .into_iter()
+        .map(|(kind, bytes)| (kind, bytes as f64 / seconds))
+        .collect();
+    bandwidth.elapsed = Duration::ZERO;
+}
+
+fn clear_bandwidth(mut bandwidth: ResMut<SyncBandwidth>) {
+    *bandwidth = SyncBandwidth::default();
+}
@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use renet::RenetServer;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::character::CharacterBody;
+use crate::component::{DespawnReason, Respawn};
+use crate::world::SpawnProperty;
+
+use super::host::ServerSettings;
+use super::outbox::ServerOutbox;
+use super::{Character, Lobby, LobbyState, PlayerId, ServerMessages};
+
+/// Fraction of the golden ratio spreading the colors of a team over its hue range
+const HUE_SPREAD: f32 = 0.618_034;
+
+/// One of the two teams of a team mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TeamId {
+    A,
+    B,
+}
+
+impl TeamId {
+    pub const ALL: [TeamId; 2] = [TeamId::A, TeamId::B];
+
+    /// Position in [`TeamId::ALL`].
+    pub fn index(self) -> usize {
+        match self {
+            TeamId::A => 0,
+            TeamId::B => 1,
+        }
+    }
+
+    /// Tag of the spawn points of the team, see [`SpawnProperty::tagged`].
+    pub fn tag(self) -> &'static str {
+        match self {
+            TeamId::A => "team_a",
+            TeamId::B => "team_b",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TeamId::A => "Team A",
+            TeamId::B => "Team B",
+        }
+    }
+
+    /// First hue (degrees) and width of the hue range of the team colors, warm for A and cool for B.
+    fn hues(self) -> (f32, f32) {
+        match self {
+            TeamId::A => (-30., 90.),
+            TeamId::B => (150., 110.),
+        }
+    }
+}
+
+/// Rules of the team mode, enabled by [`ServerSettings::teams`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TeamRules {
+    /// Projectiles damage the players of the shooter team
+    pub friendly_fire: bool,
+}
+
+/// Color of the `index`th player of `team`, the first ones are kept far apart in the team range.
+pub fn team_color(team: TeamId, index: u32) -> Color {
+    let (start, width) = team.hues();
+    let hue = (start + (index as f32 * HUE_SPREAD).fract() * width).rem_euclid(360.);
+    Color::hsl(hue, 1.0, 0.5)
+}
+
+/// Players of every team, in [`TeamId::ALL`] order.
+pub fn team_sizes(lobby: &Lobby) -> [usize; 2] {
+    let mut sizes = [0; 2];
+    for player_data in std::iter::once(&lobby.me).chain(lobby.players.values()) {
+        if let Some(team) = player_data.team {
+            sizes[team.index()] += 1;
+        }
+    }
+    sizes
+}
+
+/// The smallest team, [`TeamId::A`] on a tie.
+pub fn balanced_team(lobby: &Lobby) -> TeamId {
+    let [a, b] = team_sizes(lobby);
+    if b < a {
+        TeamId::B
+    } else {
+        TeamId::A
+    }
+}
+
+/// Spawn points of a player of `team`.
+pub fn team_spawn(spawn_point: &SpawnProperty, team: Option<TeamId>) -> SpawnProperty {
+    match team {
+        Some(team) => spawn_point.tagged(team.tag()),
+        None => spawn_point.clone(),
+    }
+}
+
+/// A player asks to play in another team, approved by the host if the teams stay balanced.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct TeamSwitchRequest {
+    pub id: PlayerId,
+    pub team: TeamId,
+}
+
+pub struct TeamPlugins;
+
+impl Plugin for TeamPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TeamSwitchRequest>()
+            .add_systems(
+                Update,
+                (follow_team_rules, switch_teams)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            // the single player color is not kept in the lobby
+            .add_systems(
+                Update,
+                recolor_characters.run_if(
+                    not(in_state(LobbyState::None))
+                        .and_then(not(in_state(LobbyState::Single)))
+                        .and_then(resource_exists_and_changed::<Lobby>),
+                ),
+            );
+    }
+}
+
+/// Puts every player in a team when the team mode is enabled, and out of it when disabled.
+fn follow_team_rules(
+    settings: Res<ServerSettings>,
+    spawn_point: Res<SpawnProperty>,
+    mut lobby: ResMut<Lobby>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut respawn_query: Query<&mut Respawn>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let ids: Vec<PlayerId> = lobby.players.keys().copied().collect();
+    for id in std::iter::once(PlayerId::HostOrSingle).chain(ids) {
+        let Some(current) = lobby.player(&id).map(|player_data| player_data.team) else {
+            continue;
+        };
+        let team = match (settings.teams, current) {
+            (Some(_), None) => Some(balanced_team(&lobby)),
+            (None, Some(_)) => None,
+            _ => continue,
+        };
+        assign_team(
+            &mut lobby,
+            id,
+            team,
+            &spawn_point,
+            &mut respawn_query,
+            &mut outbox,
+        );
+    }
+}
+
+fn switch_teams(
+    settings: Res<ServerSettings>,
+    spawn_point: Res<SpawnProperty>,
+    mut switch_request: EventReader<TeamSwitchRequest>,
+    mut lobby: ResMut<Lobby>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut respawn_query: Query<&mut Respawn>,
+) {
+    for TeamSwitchRequest { id, team } in switch_request.read() {
+        if settings.teams.is_none() {
+            log::debug!("Team switch of {:?} refused: no team mode", id);
+            continue;
+        }
+        let Some(current) = lobby.player(id).map(|player_data| player_data.team) else {
+            continue;
+        };
+        if current == Some(*team) {
+            continue;
+        }
+        // the teams may differ by one player at most after the switch
+        let sizes = team_sizes(&lobby);
+        if sizes[team.index()] + 1 > sizes[1 - team.index()] {
+            log::info!(
+                "Team switch of {:?} to {} refused: teams would be unbalanced",
+                id,
+                team.name()
+            );
+            continue;
+        }
+        assign_team(
+            &mut lobby,
+            *id,
+            Some(*team),
+            &spawn_point,
+            &mut respawn_query,
+            &mut outbox,
+        );
+    }
+}
+
+/// Moves `id` to `team` with a color of the team, the character respawns on the team spawn points.
+fn assign_team(
+    lobby: &mut Lobby,
+    id: PlayerId,
+    team: Option<TeamId>,
+    spawn_point: &SpawnProperty,
+    respawn_query: &mut Query<&mut Respawn>,
+    outbox: &mut ServerOutbox,
+) {
+    let color = match team {
+        Some(team) => team_color(team, team_sizes(lobby)[team.index()] as u32),
+        None => {
+            lobby.players_seq += 1;
+            super::host::generate_player_color(lobby.players_seq as u32)
+        }
+    };
+    let Some(player_data) = lobby.player_mut(&id) else {
+        return;
+    };
+    player_data.team = team;
+    player_data.color = color;
+
+    // the host character is not spawned while the level loads
+    let respawn = player_data
+        .entity()
+        .and_then(|entity| respawn_query.get_mut(entity).ok());
+    if let Some(mut respawn) = respawn {
+        respawn.replace_spawn_point(team_spawn(spawn_point, team));
+        if !respawn.is_pending() {
+            respawn.insert_reason(DespawnReason::Forced);
+        }
+    }
+
+    // after a `PlayerConnected` of the same frame, which is queued too
+    outbox.queue(ServerMessages::TeamAssigned { id, team, color });
+}
+
+/// Keeps the body of every character in the color of its player, which changes with the team.
+fn recolor_characters(
+    lobby: Res<Lobby>,
+    character_query: Query<(&Character, &Children)>,
+    body_query: Query<&Handle<StandardMaterial>, With<CharacterBody>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (character, children) in character_query.iter() {
+        let Some(player_data) = lobby.player(&character.id) else {
+            continue;
+        };
+        for child in children.iter() {
+            let Ok(handle) = body_query.get(*child) else {
+                continue;
+            };
+            // only a real change marks the material modified
+            let outdated = materials
+                .get(handle)
+                .is_some_and(|material| material.base_color != player_data.color);
+            if outdated {
+                if let Some(material) = materials.get_mut(handle) {
+                    material.base_color = player_data.color;
+                }
+            }
+        }
+    }
+}
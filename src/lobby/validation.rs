@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use bevy::app::{App, FixedUpdate, Plugin, PostUpdate};
+use bevy::ecs::component::Component;
+use bevy::ecs::entity::Entity;
+use bevy::ecs::event::{Event, EventWriter};
+use bevy::ecs::query::Has;
+use bevy::ecs::schedule::IntoSystemConfigs;
+use bevy::ecs::system::{Commands, Query, Res, ResMut, Resource};
+use bevy::ecs::world::World;
+use bevy::math::{Vec2, Vec3};
+use bevy::prelude::{in_state, resource_changed};
+use bevy::time::{Fixed, Time};
+use bevy::transform::components::Transform;
+use bevy_rapier3d::plugin::PhysicsSet;
+use renet::ClientId;
+
+use crate::actor::character::{MovementTuning, PLAYER_MAX_SPEED};
+use crate::component::Teleported;
+
+use super::admin::KickPlayerEvent;
+
+use super::{Character, LobbyState, PlayerId};
+
+/// Limits the host uses to validate client-influenced movement.
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct MovementValidation {
+    /// Maximum horizontal speed (units per second) a character can legitimately reach.
+    pub max_speed: f32,
+    /// Multiplier applied on top of `max_speed` to absorb physics jitter.
+    pub tolerance: f32,
+}
+
+impl Default for MovementValidation {
+    fn default() -> Self {
+        Self {
+            max_speed: PLAYER_MAX_SPEED,
+            tolerance: 1.5,
+        }
+    }
+}
+
+/// Last position of a character accepted by the host.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct ValidatedPosition(pub Vec3);
+
+/// Seconds left during which the moves of a character launched by the level (e.g. a bounce pad)
+/// are accepted as is, the launch is faster than the character could move on its own.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct MovementGrace(pub f32);
+
+/// Input messages a client may send per simulation tick, the rest are dropped.
+pub const MAX_INPUTS_PER_TICK: u32 = 2;
+/// Rejected moves within [`STRIKE_WINDOW`] that make a player suspected of cheating.
+pub const STRIKES_TO_SUSPECT: usize = 3;
+/// Seconds a rejected move counts as a strike.
+pub const STRIKE_WINDOW: f32 = 60.;
+/// Malformed messages within [`MALFORMED_WINDOW`] that get a client disconnected.
+pub const MALFORMED_TO_KICK: usize = 10;
+/// Seconds a malformed message counts against its client.
+pub const MALFORMED_WINDOW: f32 = 60.;
+
+/// Movement axes of a client character as last accepted by the host.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct MovementInput(pub Vec2);
+
+/// Sequence numbers of the [`MovementInput`]s of a client, for the prediction of that client.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct InputSequence {
+    /// Of the last input received, an older one arriving late is dropped
+    pub received: u32,
+    /// Of the input the character was last moved by, echoed in the snapshots
+    pub applied: u32,
+}
+
+/// Sets the [`MovementInput`] of the client character `entity`, unless a newer one arrived first.
+pub fn receive_input(world: &mut World, entity: Entity, movement: Vec2, sequence: u32) {
+    let Some(mut entity) = world.get_entity_mut(entity) else {
+        return;
+    };
+    let mut input_sequence = entity.get::<InputSequence>().copied().unwrap_or_default();
+    // inputs are sent unreliably, they may arrive out of order
+    if sequence <= input_sequence.received {
+        return;
+    }
+    input_sequence.received = sequence;
+    entity.insert((MovementInput(clamp_axis(movement)), input_sequence));
+}
+
+/// Keeps every axis in `-1..=1`, a non-finite input is treated as no input.
+pub fn clamp_axis(axis: Vec2) -> Vec2 {
+    if !axis.is_finite() {
+        return Vec2::ZERO;
+    }
+    axis.clamp(Vec2::splat(-1.), Vec2::splat(1.))
+}
+
+/// Counts input messages of every player in the current tick.
+#[derive(Debug, Default, Resource)]
+pub struct InputRateLimiter {
+    tick: u64,
+    counts: HashMap<PlayerId, u32>,
+}
+
+impl InputRateLimiter {
+    /// Whether `player_id` may send one more input in `tick`.
+    pub fn allow(&mut self, player_id: PlayerId, tick: u64) -> bool {
+        if tick != self.tick {
+            self.tick = tick;
+            self.counts.clear();
+        }
+        let count = self.counts.entry(player_id).or_default();
+        *count += 1;
+        *count <= MAX_INPUTS_PER_TICK
+    }
+}
+
+/// Rejected moves of every player.
+#[derive(Debug, Default, Resource)]
+pub struct CheatStrikes(HashMap<PlayerId, Vec<f32>>);
+
+impl CheatStrikes {
+    /// Records a strike at `now` (seconds), returns `true` once the player collected
+    /// [`STRIKES_TO_SUSPECT`] strikes within [`STRIKE_WINDOW`], the strikes are then forgotten.
+    pub fn strike(&mut self, player_id: PlayerId, now: f32) -> bool {
+        let strikes = self.0.entry(player_id).or_default();
+        strikes.retain(|time| now - *time < STRIKE_WINDOW);
+        strikes.push(now);
+        if strikes.len() >= STRIKES_TO_SUSPECT {
+            strikes.clear();
+            return true;
+        }
+        false
+    }
+}
+
+/// Messages of every client that could not be decoded.
+#[derive(Debug, Default, Resource)]
+pub struct MalformedMessages(HashMap<ClientId, Vec<f32>>);
+
+impl MalformedMessages {
+    /// Records a malformed message at `now` (seconds), returns `true` once the client sent
+    /// [`MALFORMED_TO_KICK`] within [`MALFORMED_WINDOW`], it is then forgotten.
+    pub fn record(&mut self, client_id: ClientId, now: f32) -> bool {
+        let times = self.0.entry(client_id).or_default();
+        times.retain(|time| now - *time < MALFORMED_WINDOW);
+        times.push(now);
+        if times.len() >= MALFORMED_TO_KICK {
+            self.0.remove(&client_id);
+            return true;
+        }
+        false
+    }
+}
+
+/// Counts a message of `client_id` the host could not decode, the client is either broken or
+/// hostile and is disconnected after [`MALFORMED_TO_KICK`].
+pub fn count_malformed(world: &mut World, client_id: ClientId) {
+    let now = world.resource::<Time>().elapsed_seconds();
+    if world.resource_mut::<MalformedMessages>().record(client_id, now) {
+        log::warn!("{} sent {} malformed messages, disconnecting", client_id, MALFORMED_TO_KICK);
+        world.send_event(KickPlayerEvent {
+            client_id,
+            reason: "malformed messages".to_string(),
+        });
+    }
+}
+
+/// A client character keeps moving further than the movement config allows.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct CheatSuspectedEvent(pub PlayerId);
+
+pub struct MovementValidationPlugins;
+
+impl Plugin for MovementValidationPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MovementValidation>()
+            .init_resource::<InputRateLimiter>()
+            .init_resource::<CheatStrikes>()
+            .init_resource::<MalformedMessages>()
+            .add_event::<CheatSuspectedEvent>()
+            .add_systems(
+                PostUpdate,
+                follow_movement_tuning.run_if(resource_changed::<MovementTuning>),
+            )
+            .add_systems(
+                FixedUpdate,
+                validate_movement
+                    .after(PhysicsSet::Writeback)
+                    .run_if(in_state(LobbyState::Host)),
+            );
+    }
+}
+
+/// Keeps the validated speed at the one of the loaded level.
+fn follow_movement_tuning(tuning: Res<MovementTuning>, mut config: ResMut<MovementValidation>) {
+    config.max_speed = tuning.max_speed;
+}
+
+/// Snaps back characters of clients that moved further than [`MovementValidation`] allows since the last tick
+/// and gives their player a strike, see [`CheatStrikes`].
+///
+/// Only the horizontal delta is checked, falling is driven by gravity and is not limited by the move speed.
+/// Entities marked [`Teleported`] (respawn, map change) or with a [`MovementGrace`] left
+/// are accepted as is.
+#[allow(clippy::type_complexity)]
+pub(crate) fn validate_movement(
+    mut commands: Commands,
+    config: Res<MovementValidation>,
+    time: Res<Time<Fixed>>,
+    mut strikes: ResMut<CheatStrikes>,
+    mut cheat_suspected_event: EventWriter<CheatSuspectedEvent>,
+    mut query: Query<(
+        Entity,
+        &Character,
+        &mut Transform,
+        Option<&mut ValidatedPosition>,
+        Has<Teleported>,
+        Option<&mut MovementGrace>,
+    )>,
+) {
+    let max_distance = config.max_speed * config.tolerance * time.delta_seconds();
+
+    for (entity, character, mut transform, validated, teleported, grace) in query.iter_mut() {
+        // host is authoritative for its own character and its bots
+        if matches!(character.id, PlayerId::HostOrSingle | PlayerId::Bot(_)) {
+            continue;
+        }
+
+        let Some(mut validated) = validated else {
+            commands
+                .entity(entity)
+                .insert(ValidatedPosition(transform.translation));
+            continue;
+        };
+
+        if teleported {
+            validated.0 = transform.translation;
+            commands.entity(entity).remove::<Teleported>();
+            continue;
+        }
+        if let Some(mut grace) = grace {
+            grace.0 -= time.delta_seconds();
+            if grace.0 <= 0. {
+                commands.entity(entity).remove::<MovementGrace>();
+            }
+            validated.0 = transform.translation;
+            continue;
+        }
+
+        let delta = transform.translation - validated.0;
+        let horizontal = Vec3::new(delta.x, 0., delta.z);
+        if horizontal.length() > max_distance {
+            log::warn!(
+                "Rejected move of {:?}: {:.2} units in {:.3}s (max {:.2})",
+                character.id,
+                horizontal.length(),
+                time.delta_seconds(),
+                max_distance,
+            );
+            transform.translation = validated.0 + Vec3::new(0., delta.y, 0.);
+            if strikes.strike(character.id, time.elapsed_seconds()) {
+                log::warn!("{:?} is suspected of cheating", character.id);
+                cheat_suspected_event.send(CheatSuspectedEvent(character.id));
+            }
+        }
+
+        validated.0 = transform.translation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn client() -> PlayerId {
+        PlayerId::Client(ClientId::from_raw(1))
+    }
+
+    #[test]
+    fn axes_are_clamped() {
+        assert_eq!(clamp_axis(Vec2::new(5., -3.)), Vec2::new(1., -1.));
+        assert_eq!(clamp_axis(Vec2::new(0.5, -0.25)), Vec2::new(0.5, -0.25));
+        assert_eq!(clamp_axis(Vec2::new(f32::NAN, 1.)), Vec2::ZERO);
+        assert_eq!(clamp_axis(Vec2::new(f32::INFINITY, 0.)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn inputs_are_limited_per_tick() {
+        let mut limiter = InputRateLimiter::default();
+        for _ in 0..MAX_INPUTS_PER_TICK {
+            assert!(limiter.allow(client(), 1));
+        }
+        assert!(!limiter.allow(client(), 1));
+        assert!(limiter.allow(PlayerId::Client(ClientId::from_raw(2)), 1));
+        assert!(limiter.allow(client(), 2));
+    }
+
+    #[test]
+    fn strikes_accumulate_within_the_window() {
+        let mut strikes = CheatStrikes::default();
+        for i in 0..STRIKES_TO_SUSPECT - 1 {
+            assert!(!strikes.strike(client(), i as f32));
+        }
+        assert!(strikes.strike(client(), STRIKES_TO_SUSPECT as f32));
+        // forgotten once suspected
+        assert!(!strikes.strike(client(), STRIKES_TO_SUSPECT as f32 + 1.));
+    }
+
+    #[test]
+    fn old_strikes_expire() {
+        let mut strikes = CheatStrikes::default();
+        for i in 0..STRIKES_TO_SUSPECT - 1 {
+            assert!(!strikes.strike(client(), i as f32));
+        }
+        assert!(!strikes.strike(client(), STRIKE_WINDOW + STRIKES_TO_SUSPECT as f32));
+    }
+
+    /// A world ready to run [`validate_movement`] for one tick, with a client character
+    /// validated at the origin and moved to `position`.
+    fn moved_character(position: Vec3, teleported: bool) -> (World, Entity) {
+        let mut world = World::new();
+        let mut time = Time::<Fixed>::from_hz(64.);
+        time.advance_by(Duration::from_secs_f64(1. / 64.));
+        world.insert_resource(time);
+        world.init_resource::<MovementValidation>();
+        world.init_resource::<CheatStrikes>();
+        world.init_resource::<Events<CheatSuspectedEvent>>();
+        let mut character = world.spawn((
+            Character { id: client() },
+            Transform::from_translation(position),
+            ValidatedPosition(Vec3::ZERO),
+        ));
+        if teleported {
+            character.insert(Teleported);
+        }
+        let entity = character.id();
+        (world, entity)
+    }
+
+    #[test]
+    fn too_far_move_is_snapped_back() {
+        let (mut world, entity) = moved_character(Vec3::new(100., 2., 0.), false);
+        world.run_system_once(validate_movement);
+
+        // falling is not limited
+        assert_eq!(
+            world.get::<Transform>(entity).unwrap().translation,
+            Vec3::new(0., 2., 0.)
+        );
+        assert_eq!(world.resource::<CheatStrikes>().0[&client()].len(), 1);
+    }
+
+    #[test]
+    fn teleport_of_the_host_is_exempt() {
+        let (mut world, entity) = moved_character(Vec3::new(100., 2., 0.), true);
+        world.run_system_once(validate_movement);
+
+        let translation = world.get::<Transform>(entity).unwrap().translation;
+        assert_eq!(translation, Vec3::new(100., 2., 0.));
+        assert_eq!(world.get::<ValidatedPosition>(entity).unwrap().0, translation);
+        assert!(world.get::<Teleported>(entity).is_none());
+        assert!(world.resource::<CheatStrikes>().0.is_empty());
+    }
+
+    #[test]
+    fn malformed_messages_kick_within_the_window() {
+        let client_id = ClientId::from_raw(1);
+        let mut malformed = MalformedMessages::default();
+        for i in 0..MALFORMED_TO_KICK - 1 {
+            assert!(!malformed.record(client_id, i as f32));
+        }
+        assert!(!malformed.record(ClientId::from_raw(2), 0.));
+        assert!(malformed.record(client_id, MALFORMED_TO_KICK as f32));
+        // forgotten once kicked
+        assert!(!malformed.record(client_id, MALFORMED_TO_KICK as f32 + 1.));
+    }
+
+    #[test]
+    fn old_malformed_messages_are_forgotten() {
+        let client_id = ClientId::from_raw(1);
+        let mut malformed = MalformedMessages::default();
+        for i in 0..MALFORMED_TO_KICK * 3 {
+            assert!(!malformed.record(client_id, i as f32 * MALFORMED_WINDOW));
+        }
+    }
+
+    #[test]
+    fn count_malformed_sends_a_kick() {
+        let client_id = ClientId::from_raw(1);
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<MalformedMessages>();
+        world.init_resource::<Events<KickPlayerEvent>>();
+        for _ in 0..MALFORMED_TO_KICK - 1 {
+            count_malformed(&mut world, client_id);
+        }
+        assert!(world.resource::<Events<KickPlayerEvent>>().is_empty());
+
+        count_malformed(&mut world, client_id);
+        let events = world.resource::<Events<KickPlayerEvent>>();
+        let kicked: Vec<_> = events.get_reader().read(events).map(|kick| kick.client_id).collect();
+        assert_eq!(kicked, vec![client_id]);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use renet::{ClientId, RenetServer};
+
+use super::admin::{find_client, AdminCommandRequest, Admins, KickPlayerEvent};
+use super::outbox::ServerOutbox;
+use super::quick_chat::ChatFeed;
+use super::{Lobby, LobbyState, PlayerId, ServerMessages};
+
+/// Seconds a vote-kick stays open before it fails
+const VOTE_KICK_TIMEOUT: f32 = 30.;
+
+const USAGE: &str = "vote commands: /votekick <name>, /vote <yes|no>";
+
+/// Chat commands any client can use while no admin is connected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoteCommand {
+    /// Starts a vote to kick the named player
+    VoteKick(String),
+    Vote(bool),
+}
+
+impl VoteCommand {
+    /// Parses a chat message, `None` if it is not a vote command.
+    pub fn parse(text: &str) -> Option<Result<VoteCommand, String>> {
+        let text = text.strip_prefix('/')?;
+        let (name, argument) = text.split_once(' ').unwrap_or((text, ""));
+        let command = match (name, argument.trim()) {
+            ("votekick", "") => Err(USAGE.to_string()),
+            ("votekick", username) => Ok(VoteCommand::VoteKick(username.to_string())),
+            ("vote", "yes") => Ok(VoteCommand::Vote(true)),
+            ("vote", "no") => Ok(VoteCommand::Vote(false)),
+            ("vote", _) => Err(USAGE.to_string()),
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+/// Vote-kick in progress, there is at most one at a time.
+#[derive(Debug)]
+struct ActiveVote {
+    target: ClientId,
+    username: String,
+    yes: HashSet<ClientId>,
+    no: HashSet<ClientId>,
+    timer: Timer,
+}
+
+impl ActiveVote {
+    /// Connected clients allowed to vote, the target and the host are not
+    fn voters(&self, lobby: &Lobby) -> HashSet<ClientId> {
+        lobby
+            .players
+            .keys()
+            .filter_map(|id| match id {
+                PlayerId::Client(client_id) if *client_id != self.target => Some(*client_id),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Resource)]
+struct VoteKick(Option<ActiveVote>);
+
+pub struct VoteKickPlugins;
+
+impl Plugin for VoteKickPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoteKick>()
+            .add_systems(
+                Update,
+                (run_vote_commands, count_votes)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(resource_exists::<RenetServer>)),
+            )
+            .add_systems(OnExit(LobbyState::Host), clear_vote);
+    }
+}
+
+/// Sends `text` to every player, the host sees it in its chat feed.
+fn announce(outbox: &mut ServerOutbox, chat_feed: &mut ChatFeed, text: String) {
+    chat_feed.push("Host".to_string(), text.clone());
+    outbox.queue(ServerMessages::Chat {
+        from: PlayerId::HostOrSingle,
+        text,
+    });
+}
+
+fn run_vote_commands(
+    mut requests: EventReader<AdminCommandRequest>,
+    admins: Res<Admins>,
+    lobby: Res<Lobby>,
+    mut vote_kick: ResMut<VoteKick>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut chat_feed: ResMut<ChatFeed>,
+) {
+    for AdminCommandRequest { from, text } in requests.read() {
+        let Some(command) = VoteCommand::parse(text) else {
+            continue;
+        };
+        // the host kicks with `/kick` instead
+        let PlayerId::Client(client_id) = *from else {
+            chat_feed.push("Host".to_string(), "use /kick <name>".to_string());
+            continue;
+        };
+
+        let reply = match command {
+            Err(usage) => usage,
+            Ok(VoteCommand::VoteKick(_)) if vote_kick.0.is_some() => {
+                "a vote is already running".to_string()
+            }
+            Ok(VoteCommand::VoteKick(_)) if admins.any_connected(&lobby) => {
+                "an admin is connected, ask them".to_string()
+            }
+            Ok(VoteCommand::VoteKick(username)) => match find_client(&lobby, &username) {
+                Some(target) if target == client_id => "you cannot vote on yourself".to_string(),
+                Some(target) => {
+                    log::info!("{:?} started a vote to kick {}", from, username);
+                    vote_kick.0 = Some(ActiveVote {
+                        target,
+                        username: username.clone(),
+                        // the one who started it is for it
+                        yes: HashSet::from([client_id]),
+                        no: HashSet::new(),
+                        timer: Timer::from_seconds(VOTE_KICK_TIMEOUT, TimerMode::Once),
+                    });
+                    announce(
+                        &mut outbox,
+                        &mut chat_feed,
+                        format!(
+                            "vote to kick {} started, /vote yes or /vote no within {}s",
+                            username, VOTE_KICK_TIMEOUT
+                        ),
+                    );
+                    continue;
+                }
+                None => format!("no player {}", username),
+            },
+            Ok(VoteCommand::Vote(yes)) => match vote_kick.0.as_mut() {
+                None => "no vote is running".to_string(),
+                Some(vote) if vote.target == client_id => "you cannot vote on yourself".to_string(),
+                Some(vote) => {
+                    // the last vote of a player counts
+                    vote.yes.remove(&client_id);
+                    vote.no.remove(&client_id);
+                    if yes {
+                        vote.yes.insert(client_id);
+                    } else {
+                        vote.no.insert(client_id);
+                    }
+                    let text = format!(
+                        "vote to kick {}: {} yes, {} no of {}",
+                        vote.username,
+                        vote.yes.len(),
+                        vote.no.len(),
+                        vote.voters(&lobby).len()
+                    );
+                    announce(&mut outbox, &mut chat_feed, text);
+                    continue;
+                }
+            },
+        };
+
+        outbox.queue_for(
+            client_id,
+            ServerMessages::Chat {
+                from: PlayerId::HostOrSingle,
+                text: reply,
+            },
+        );
+    }
+}
+
+/// Ends the vote once a majority of the voters decided or it timed out.
+fn count_votes(
+    time: Res<Time>,
+    lobby: Res<Lobby>,
+    mut vote_kick: ResMut<VoteKick>,
+    mut outbox: ResMut<ServerOutbox>,
+    mut chat_feed: ResMut<ChatFeed>,
+    mut kick_event: EventWriter<KickPlayerEvent>,
+) {
+    let Some(vote) = vote_kick.0.as_mut() else {
+        return;
+    };
+    vote.timer.tick(time.delta());
+
+    let target_connected = lobby.players.contains_key(&PlayerId::Client(vote.target));
+    // votes of players who left do not count
+    let voters = vote.voters(&lobby);
+    vote.yes.retain(|client_id| voters.contains(client_id));
+    vote.no.retain(|client_id| voters.contains(client_id));
+    let majority = voters.len() / 2 + 1;
+
+    let text = if !target_connected {
+        format!("vote to kick {} ended, the player left", vote.username)
+    } else if vote.yes.len() >= majority {
+        kick_event.send(KickPlayerEvent {
+            client_id: vote.target,
+            reason: "kicked by vote".to_string(),
+        });
+        format!("vote passed, {} is kicked", vote.username)
+    } else if vote.no.len() + majority > voters.len() {
+        // the yes votes cannot reach the majority anymore
+        format!("vote to kick {} failed", vote.username)
+    } else if vote.timer.finished() {
+        format!("vote to kick {} failed, time is up", vote.username)
+    } else {
+        return;
+    };
+    log::info!("{}", text);
+    announce(&mut outbox, &mut chat_feed, text);
+    vote_kick.0 = None;
+}
+
+fn clear_vote(mut vote_kick: ResMut<VoteKick>) {
+    vote_kick.0 = None;
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::ecs::system::Resource;
+use renet::ClientId;
+
+/// Terms the host masks in chat and refuses in usernames, set by [`HostResource::word_filter`].
+///
+/// Matching ignores case and works on chars, so multibyte text is never cut inside a char.
+/// An empty filter lets everything through.
+///
+/// [`HostResource::word_filter`]: super::HostResource::word_filter
+#[derive(Debug, Default, Resource)]
+pub struct WordFilter {
+    /// Lowercase chars of every term
+    terms: Vec<Vec<char>>,
+}
+
+impl WordFilter {
+    /// Reads a word list with one term per line, empty lines and lines starting with `#` are
+    /// skipped. A missing or unreadable file gives an empty filter.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::new(content.lines()),
+            Err(err) => {
+                log::error!("Failed to read word filter ({:?}): {}", path, err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn new<'a>(terms: impl IntoIterator<Item = &'a str>) -> Self {
+        let terms = terms
+            .into_iter()
+            .map(str::trim)
+            .filter(|term| !term.is_empty() && !term.starts_with('#'))
+            .map(|term| term.chars().map(fold).collect())
+            .collect();
+        Self { terms }
+    }
+
+    /// Whether `text` contains a filtered term.
+    pub fn matches(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        (0..chars.len()).any(|start| self.match_len(&chars[start..]).is_some())
+    }
+
+    /// `text` with every char of a filtered term replaced by `*`.
+    pub fn mask(&self, text: &str) -> String {
+        let mut chars: Vec<char> = text.chars().collect();
+        let mut start = 0;
+        while start < chars.len() {
+            match self.match_len(&chars[start..]) {
+                Some(len) => {
+                    chars[start..start + len].fill('*');
+                    start += len;
+                }
+                None => start += 1,
+            }
+        }
+        chars.into_iter().collect()
+    }
+
+    /// `username`, or a neutral `player###` if it contains a filtered term.
+    pub fn username(&self, username: String, client_id: ClientId) -> String {
+        if !self.matches(&username) {
+            return username;
+        }
+        log::info!("Replaced filtered username of {}", client_id);
+        // ascii only, so it always fits into `Username::MAX_LEN`
+        format!("player{:03}", client_id.raw() % 1000)
+    }
+
+    /// Length in chars of the longest term `chars` starts with
+    fn match_len(&self, chars: &[char]) -> Option<usize> {
+        self.terms
+            .iter()
+            .filter(|term| {
+                term.len() <= chars.len()
+                    && term.iter().zip(chars).all(|(term_char, char)| *term_char == fold(*char))
+            })
+            .map(Vec::len)
+            .max()
+    }
+}
+
+/// Case of `char` ignored, chars lowercased into several keep their first one
+fn fold(char: char) -> char {
+    char.to_lowercase().next().unwrap_or(char)
+}
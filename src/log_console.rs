@@ -0,0 +1,256 @@
+//! In-game window with the last log records, opened with the `logs` console command.
+//!
+//! A layer of the `LogPlugin` subscriber copies every record that passed the log filter,
+//! [`LogRecords`] keeps the last [`MAX_RECORDS`] of them. Older records are dropped without
+//! a word, the console must not grow while nobody reads it.
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, PoisonError};
+
+use bevy::log::tracing_subscriber::filter::LevelFilter;
+use bevy::log::tracing_subscriber::layer::{Context, SubscriberExt};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::BoxedSubscriber;
+use bevy::prelude::*;
+use bevy::utils::tracing::span::{Attributes, Id};
+use bevy::utils::tracing::{Event, Level, Subscriber};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::console::{no_args, CommandResult, CommandScope, ConsoleAppExt};
+use crate::log_file::{FieldsVisitor, SpanLabels};
+use crate::log_filter::{log_filter, set_log_filter};
+
+/// Records kept by [`LogRecords`]
+pub const MAX_RECORDS: usize = 2000;
+/// Chars of a message kept, the rest is cut off
+const MAX_MESSAGE_LEN: usize = 1000;
+const LEVELS: [Level; 5] = [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE];
+
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    /// Label of the span the record happened in, see [`SpanLabels`]
+    pub span: Option<String>,
+    pub message: String,
+}
+
+lazy_static::lazy_static! {
+    /// Records captured since the last [`collect_log_records`], bounded like [`LogRecords`]
+    static ref PENDING: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+}
+
+/// Ring buffer of the last [`MAX_RECORDS`] log records, oldest first.
+#[derive(Debug, Resource)]
+pub struct LogRecords(VecDeque<LogRecord>);
+
+impl Default for LogRecords {
+    fn default() -> Self {
+        Self(VecDeque::with_capacity(MAX_RECORDS))
+    }
+}
+
+impl LogRecords {
+    fn push(&mut self, record: LogRecord) {
+        push_bounded(&mut self.0, record);
+    }
+}
+
+fn push_bounded(records: &mut VecDeque<LogRecord>, record: LogRecord) {
+    if records.len() >= MAX_RECORDS {
+        records.pop_front();
+    }
+    records.push_back(record);
+}
+
+/// Copies every event into [`PENDING`].
+struct LogCaptureLayer(SpanLabels);
+
+impl<S: Subscriber> Layer<S> for LogCaptureLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.0.new_span(attrs, id, &ctx);
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.0.close(&id);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldsVisitor(String::new());
+        event.record(&mut fields);
+        let mut message = fields.0.trim_start().to_string();
+        if let Some((index, _)) = message.char_indices().nth(MAX_MESSAGE_LEN) {
+            message.truncate(index);
+        }
+
+        let metadata = event.metadata();
+        let record = LogRecord {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            span: self.0.of(event, &ctx),
+            message,
+        };
+        push_bounded(&mut PENDING.lock().unwrap_or_else(PoisonError::into_inner), record);
+    }
+}
+
+/// Adds the capture of [`LogConsolePlugins`] to the subscriber of the `LogPlugin`.
+pub fn with_log_capture(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    Box::new(subscriber.with(LogCaptureLayer(SpanLabels::default())))
+}
+
+#[derive(Debug, Resource)]
+struct LogConsoleState {
+    open: bool,
+    /// Most verbose level shown
+    level: Level,
+    search: String,
+}
+
+impl Default for LogConsoleState {
+    fn default() -> Self {
+        Self {
+            open: false,
+            level: Level::INFO,
+            search: String::new(),
+        }
+    }
+}
+
+pub struct LogConsolePlugins;
+
+impl Plugin for LogConsolePlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LogRecords>()
+            .init_resource::<LogConsoleState>()
+            .add_systems(First, collect_log_records)
+            .add_systems(
+                Update,
+                log_console_window.run_if(|state: Res<LogConsoleState>| state.open),
+            )
+            .add_console_command("logs", "", CommandScope::Local, no_args, toggle_log_console)
+            .add_console_command(
+                "loglevel",
+                "[module] <off|error|warn|info|debug|trace>",
+                CommandScope::Local,
+                parse_log_level,
+                log_level,
+            );
+    }
+}
+
+fn toggle_log_console(world: &mut World, _: ()) -> CommandResult {
+    let mut state = world.resource_mut::<LogConsoleState>();
+    state.open = !state.open;
+    Ok(None)
+}
+
+/// Module, `None` for the default level, and its level, `None` shows the filter.
+fn parse_log_level(args: &[&str]) -> Result<Option<(Option<String>, LevelFilter)>, String> {
+    let parse = |level: &str| {
+        level
+            .parse::<LevelFilter>()
+            .map_err(|_| format!("invalid level `{}`", level))
+    };
+    match args {
+        [] => Ok(None),
+        [level] => Ok(Some((None, parse(level)?))),
+        [module, level] => Ok(Some((Some(module.to_string()), parse(level)?))),
+        _ => Err(format!("expected at most 2 arguments, got {}", args.len())),
+    }
+}
+
+/// Records above `RUST_LOG` stay filtered out if it is set, see [`crate::log_filter`].
+fn log_level(_world: &mut World, change: Option<(Option<String>, LevelFilter)>) -> CommandResult {
+    let mut filter = log_filter();
+    match change {
+        Some((Some(module), level)) => filter.set_module(module, level),
+        Some((None, level)) => filter.level = level,
+        None => return Ok(Some(filter.to_string())),
+    }
+    set_log_filter(filter.clone());
+    Ok(Some(filter.to_string()))
+}
+
+/// Moves the captured records into [`LogRecords`], outside of the lock the loggers wait on.
+fn collect_log_records(mut records: ResMut<LogRecords>) {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(PoisonError::into_inner));
+    for record in pending {
+        records.push(record);
+    }
+}
+
+fn log_console_window(
+    mut context: EguiContexts,
+    mut state: ResMut<LogConsoleState>,
+    records: Res<LogRecords>,
+) {
+    let mut open = true;
+    let state = &mut *state;
+    let search = state.search.to_lowercase();
+    let shown: Vec<&LogRecord> = records
+        .0
+        .iter()
+        .filter(|record| record.level <= state.level)
+        .filter(|record| {
+            search.is_empty()
+                || record.message.to_lowercase().contains(&search)
+                || record.target.to_lowercase().contains(&search)
+                || record
+                    .span
+                    .as_ref()
+                    .is_some_and(|span| span.to_lowercase().contains(&search))
+        })
+        .collect();
+
+    egui::Window::new("Logs")
+        .open(&mut open)
+        .default_size([700., 400.])
+        .show(context.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("level")
+                    .selected_text(state.level.as_str())
+                    .show_ui(ui, |ui| {
+                        for level in LEVELS {
+                            ui.selectable_value(&mut state.level, level, level.as_str());
+                        }
+                    });
+                ui.label("search");
+                ui.text_edit_singleline(&mut state.search);
+                ui.label(format!("{}/{}", shown.len(), records.0.len()));
+            });
+            ui.separator();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, shown.len(), |ui, rows| {
+                    for record in &shown[rows] {
+                        let color = if record.level == Level::ERROR {
+                            egui::Color32::RED
+                        } else if record.level == Level::WARN {
+                            egui::Color32::YELLOW
+                        } else {
+                            ui.visuals().text_color()
+                        };
+                        let span = record
+                            .span
+                            .as_ref()
+                            .map(|span| format!(" {}", span))
+                            .unwrap_or_default();
+                        let text = format!(
+                            "{:>5} {}{}: {}",
+                            record.level, record.target, span, record.message
+                        );
+                        // one line each, the rows have to be of the same height
+                        ui.add(
+                            egui::Label::new(egui::RichText::new(text).monospace().color(color))
+                                .wrap(false),
+                        );
+                    }
+                });
+        });
+
+    state.open = open;
+}
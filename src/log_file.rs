@@ -0,0 +1,329 @@
+//! Copy of the log written to a file, for dedicated hosts nobody watches the console of.
+//!
+//! Enabled by the `LOG_DIR` environment variable, the file is rotated once it grows over
+//! `LOG_MAX_SIZE` bytes. Events go through the same log filter as the console.
+//!
+//! The instance log of [`with_instance_log`] is a file per run and lobby role instead,
+//! so two instances started side by side can be read one by one.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::{self, Write as _};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::app::{App, Plugin, Update};
+use bevy::ecs::schedule::common_conditions::state_changed;
+use bevy::ecs::schedule::{IntoSystemConfigs, State};
+use bevy::ecs::system::Res;
+use bevy::log::tracing_subscriber::layer::{Context, SubscriberExt};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::BoxedSubscriber;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::span::{Attributes, Id};
+use bevy::utils::tracing::{Event, Subscriber};
+
+use crate::lobby::LobbyState;
+
+/// Directory of the log files, no file is written without it
+pub const LOG_DIR_VAR: &str = "LOG_DIR";
+/// Size in bytes after which the log file is rotated
+pub const LOG_MAX_SIZE_VAR: &str = "LOG_MAX_SIZE";
+
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+/// Rotated files kept next to the current one, the oldest is deleted
+const KEPT_FILES: usize = 5;
+const FILE_NAME: &str = "urmom.log";
+
+/// `urmom.log` rotated to `urmom.log.1`, `urmom.log.1` to `urmom.log.2` and so on.
+pub struct RotatingFile {
+    dir: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Appends to the current file of `dir`.
+    pub fn open(dir: &Path, max_size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(FILE_NAME))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("{}.{}", FILE_NAME, index))
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        // the oldest one is overwritten by the rename
+        for index in (1..KEPT_FILES).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        fs::rename(self.dir.join(FILE_NAME), self.rotated(1))?;
+        self.file = File::create(self.dir.join(FILE_NAME))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            // the logger cannot log its own failures, the console still gets them
+            if let Err(err) = self.rotate() {
+                eprintln!("Failed to rotate log file in {:?}: {}", self.dir, err);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Names and fields of the open spans together with the ones of their parents, e.g.
+/// `connection{client_id=1}:sync_send{tick=42}`, so a line tells which flow it belongs to.
+///
+/// Kept by the layers themselves, the boxed subscriber of the `LogPlugin` cannot look spans up.
+#[derive(Default)]
+pub struct SpanLabels(Mutex<HashMap<Id, String>>);
+
+impl SpanLabels {
+    pub fn new_span<S: Subscriber>(&self, attrs: &Attributes<'_>, id: &Id, ctx: &Context<'_, S>) {
+        let parent = match attrs.parent() {
+            Some(parent) => Some(parent.clone()),
+            None if attrs.is_contextual() => ctx.current_span().id().cloned(),
+            None => None,
+        };
+        let mut fields = FieldsVisitor(String::new());
+        attrs.record(&mut fields);
+
+        let mut labels = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut label = parent
+            .and_then(|parent| labels.get(&parent))
+            .map(|parent| format!("{}:", parent))
+            .unwrap_or_default();
+        label.push_str(attrs.metadata().name());
+        if !fields.0.is_empty() {
+            let _ = write!(label, "{{{}}}", fields.0.trim_start());
+        }
+        labels.insert(id.clone(), label);
+    }
+
+    pub fn close(&self, id: &Id) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(id);
+    }
+
+    /// Label of the span `event` happened in.
+    pub fn of<S: Subscriber>(&self, event: &Event<'_>, ctx: &Context<'_, S>) -> Option<String> {
+        let id = match event.parent() {
+            Some(parent) => parent.clone(),
+            None if event.is_contextual() => ctx.current_span().id()?.clone(),
+            None => return None,
+        };
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .cloned()
+    }
+}
+
+/// `event` as one line of a log file, with the label of its span if any.
+pub fn format_line(event: &Event<'_>, span: Option<&str>) -> String {
+    let mut fields = FieldsVisitor(String::new());
+    event.record(&mut fields);
+
+    let metadata = event.metadata();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "{}.{:03} {:>5} {}{}:{}\n",
+        timestamp.as_secs(),
+        timestamp.subsec_millis(),
+        metadata.level(),
+        metadata.target(),
+        span.map(|span| format!(" {}", span)).unwrap_or_default(),
+        fields.0
+    )
+}
+
+/// Writes every event that passed the filter as one line.
+struct LogFileLayer {
+    file: Mutex<RotatingFile>,
+    spans: SpanLabels,
+}
+
+impl<S: Subscriber> Layer<S> for LogFileLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.spans.new_span(attrs, id, &ctx);
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.spans.close(&id);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let line = format_line(event, self.spans.of(event, &ctx).as_deref());
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            eprintln!("Failed to write log file: {}", err);
+        }
+    }
+}
+
+/// Collects the fields of an event or a span, ` name=value` each and the message without a name.
+pub struct FieldsVisitor(pub String);
+
+impl Visit for FieldsVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let _ = if field.name() == "message" {
+            write!(self.0, " {:?}", value)
+        } else {
+            write!(self.0, " {}={:?}", field.name(), value)
+        };
+    }
+}
+
+/// Adds the log file to the subscriber of the `LogPlugin` if [`LOG_DIR_VAR`] is set.
+pub fn with_log_file(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    let Some(dir) = env::var_os(LOG_DIR_VAR) else {
+        return subscriber;
+    };
+    let max_size = env::var(LOG_MAX_SIZE_VAR)
+        .ok()
+        .and_then(|max_size| max_size.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SIZE);
+
+    match RotatingFile::open(Path::new(&dir), max_size) {
+        Ok(file) => Box::new(subscriber.with(LogFileLayer {
+            file: Mutex::new(file),
+            spans: SpanLabels::default(),
+        })),
+        Err(err) => {
+            eprintln!("Failed to open log file in {:?}: {}", dir, err);
+            subscriber
+        }
+    }
+}
+
+/// Directory and current file of the instance log.
+struct InstanceLog {
+    dir: PathBuf,
+    /// Lobby role the current file is named after
+    role: String,
+    file: Option<File>,
+}
+
+lazy_static::lazy_static! {
+    /// Set by [`with_instance_log`], the layer writes into whatever file is current
+    static ref INSTANCE_LOG: Mutex<Option<InstanceLog>> = Mutex::new(None);
+}
+
+/// Writes every event into the current file of the instance log.
+struct InstanceLogLayer(SpanLabels);
+
+impl<S: Subscriber> Layer<S> for InstanceLogLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.0.new_span(attrs, id, &ctx);
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.0.close(&id);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let line = format_line(event, self.0.of(event, &ctx).as_deref());
+        let mut instance_log = INSTANCE_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(file) = instance_log.as_mut().and_then(|log| log.file.as_mut()) else {
+            return;
+        };
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            eprintln!("Failed to write instance log: {}", err);
+        }
+    }
+}
+
+/// Adds the instance log to the subscriber of the `LogPlugin` if `dir` is given.
+///
+/// Every run writes into its own `urmom-<role>-<timestamp>.log` files of `dir`,
+/// a new one each time [`InstanceLogPlugin`] sees the lobby role change.
+pub fn with_instance_log(subscriber: BoxedSubscriber, dir: Option<PathBuf>) -> BoxedSubscriber {
+    let Some(dir) = dir else {
+        return subscriber;
+    };
+    if let Err(err) = fs::create_dir_all(&dir) {
+        eprintln!("Failed to create instance log directory {:?}: {}", dir, err);
+        return subscriber;
+    }
+    *INSTANCE_LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(InstanceLog {
+        dir,
+        role: String::new(),
+        file: None,
+    });
+    switch_instance_log(&role_name(&LobbyState::default()));
+    Box::new(subscriber.with(InstanceLogLayer(SpanLabels::default())))
+}
+
+/// Continues the instance log in a new file named after `role`, nothing without an instance log.
+pub fn switch_instance_log(role: &str) {
+    let mut instance_log = INSTANCE_LOG.lock().unwrap_or_else(PoisonError::into_inner);
+    let Some(instance_log) = instance_log.as_mut().filter(|log| log.role != role) else {
+        return;
+    };
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = instance_log.dir.join(format!("urmom-{}-{}.log", role, timestamp));
+    // the logger cannot log its own failures, the console still gets the events
+    match File::create(&path) {
+        Ok(file) => instance_log.file = Some(file),
+        Err(err) => eprintln!("Failed to create instance log {:?}: {}", path, err),
+    }
+    instance_log.role = role.to_string();
+}
+
+fn role_name(lobby_state: &LobbyState) -> String {
+    format!("{:?}", lobby_state).to_lowercase()
+}
+
+/// Switches the instance log to a new file when the lobby role changes.
+pub struct InstanceLogPlugin;
+
+impl Plugin for InstanceLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            switch_instance_log_role.run_if(state_changed::<LobbyState>),
+        );
+    }
+}
+
+fn switch_instance_log_role(lobby_state: Res<State<LobbyState>>) {
+    switch_instance_log(&role_name(lobby_state.get()));
+}
@@ -0,0 +1,161 @@
+//! Typed filter of the log, set from code instead of a `RUST_LOG` string.
+//!
+//! A layer added by [`with_log_filter`] applies the current [`LogFilter`] to every record,
+//! [`set_log_filter`] and [`set_module_level`] change it while the game runs, e.g. to bump
+//! `urmom::lobby` to debug while a session misbehaves.
+//!
+//! An explicitly set `RUST_LOG` still wins: the `LogPlugin` filters with it before this layer,
+//! so nothing set at runtime logs more than it allows.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{PoisonError, RwLock};
+
+use bevy::log::tracing_subscriber::filter::LevelFilter;
+use bevy::log::tracing_subscriber::layer::{Context, SubscriberExt};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::BoxedSubscriber;
+use bevy::utils::tracing::callsite::rebuild_interest_cache;
+use bevy::utils::tracing::subscriber::Interest;
+use bevy::utils::tracing::{Metadata, Subscriber};
+
+/// Filter of the `LogPlugin`, overriding [`LogFilter::default`] when set
+pub const RUST_LOG_VAR: &str = "RUST_LOG";
+
+lazy_static::lazy_static! {
+    /// Filter applied by [`LogFilterLayer`]
+    static ref CURRENT: RwLock<LogFilter> = RwLock::new(LogFilter::new(LevelFilter::TRACE));
+}
+
+/// Most verbose level of the records logged, by module.
+///
+/// A module matches the targets starting with it, the longest matching module applies and
+/// `level` applies to the targets no module matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    pub level: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+/// `info,wgpu_core=error`: wgpu_core floods the logs on info level.
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self::new(LevelFilter::INFO).with_module("wgpu_core", LevelFilter::ERROR)
+    }
+}
+
+impl LogFilter {
+    pub fn new(level: LevelFilter) -> Self {
+        Self {
+            level,
+            modules: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_module(mut self, module: impl Into<String>, level: LevelFilter) -> Self {
+        self.set_module(module, level);
+        self
+    }
+
+    pub fn set_module(&mut self, module: impl Into<String>, level: LevelFilter) {
+        self.modules.insert(module.into(), level);
+    }
+
+    /// The module falls back to [`LogFilter::level`] or to a shorter module matching it.
+    pub fn remove_module(&mut self, module: &str) {
+        self.modules.remove(module);
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = (&str, LevelFilter)> {
+        self.modules.iter().map(|(module, level)| (module.as_str(), *level))
+    }
+
+    /// Level applied to the records of `target`.
+    pub fn level_of(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| target.starts_with(module.as_str()))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level_of(metadata.target())
+    }
+
+    /// Most verbose level any target is logged at.
+    fn max_level(&self) -> LevelFilter {
+        self.modules.values().copied().fold(self.level, Ord::max)
+    }
+}
+
+/// The same directives as a `RUST_LOG` string, e.g. `info,wgpu_core=error`.
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level.to_string().to_lowercase())?;
+        for (module, level) in self.modules.iter() {
+            write!(f, ",{}={}", module, level.to_string().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// The filter applied now.
+pub fn log_filter() -> LogFilter {
+    CURRENT.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+/// Replaces the filter, the records already skipped by the callsites are checked again.
+pub fn set_log_filter(filter: LogFilter) {
+    *CURRENT.write().unwrap_or_else(PoisonError::into_inner) = filter;
+    rebuild_interest_cache();
+}
+
+/// Changes the level of one module of the current filter.
+pub fn set_module_level(module: &str, level: LevelFilter) {
+    let mut filter = log_filter();
+    filter.set_module(module, level);
+    set_log_filter(filter);
+}
+
+/// `RUST_LOG` if it is set, it replaces the typed filter.
+pub fn rust_log_override() -> Option<String> {
+    std::env::var(RUST_LOG_VAR)
+        .ok()
+        .filter(|directives| !directives.trim().is_empty())
+}
+
+/// Filters with [`CURRENT`], records disabled by it are not even built.
+struct LogFilterLayer;
+
+impl<S: Subscriber> Layer<S> for LogFilterLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if CURRENT.read().unwrap_or_else(PoisonError::into_inner).enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    // the records of the `log` crate skip the callsite interest
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        CURRENT.read().unwrap_or_else(PoisonError::into_inner).enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(CURRENT.read().unwrap_or_else(PoisonError::into_inner).max_level())
+    }
+}
+
+/// Adds the [`LogFilter`] layer to the subscriber of the `LogPlugin`, starting with `filter`.
+///
+/// The `LogPlugin` must let everything through, with [`rust_log_override`] the typed filter
+/// starts letting everything through instead.
+pub fn with_log_filter(subscriber: BoxedSubscriber, filter: LogFilter) -> BoxedSubscriber {
+    if rust_log_override().is_some() {
+        set_log_filter(LogFilter::new(LevelFilter::TRACE));
+    } else {
+        set_log_filter(filter);
+    }
+    Box::new(subscriber.with(LogFilterLayer))
+}
@@ -1,22 +1,20 @@
-use std::env;
-
+use bevy::log::{BoxedSubscriber, Level, LogPlugin};
 use bevy::prelude::*;
-use bevy::winit::WinitWindows;
 use bevy_egui::EguiPlugin;
 use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin};
-use urmom::core::CorePlugins;
-use urmom::ASSET_DIR;
-use winit::window::Icon;
+use std::path::PathBuf;
+use std::time::Duration;
+use urmom::core::{CorePlugins, DEFAULT_CONNECT_TIMEOUT};
+use urmom::crash_report::{install_panic_hook, with_crash_log_tail, CrashReportPlugin};
+use urmom::log_file::{with_instance_log, with_log_file, InstanceLogPlugin};
+use urmom::log_filter::{with_log_filter, LogFilter};
+use urmom::match_results::MatchResultsDir;
+use urmom::replay::{RecordReplay, ReplayPlayback, ReplayPlaybackPlugins};
+use urmom::window_icon::set_window_icon;
+use urmom::{SimulationConfig, ASSET_DIR};
 #[cfg(all(debug_assertions, feature = "dev"))]
 use urmom::DEBUG;
 
-/// default value for logging
-///
-/// wgpu_core fluds the logs on info level therefore we need to set it to error
-const RUST_LOG_DEFAULT: &str = "info,wgpu_core=error";
-/// The path to the icon
-const ICON_PATH: &str = "icon.png";
-
 /// The name of the application
 const APP_NAME: &str = "pih-pah";
 
@@ -28,11 +26,97 @@ lazy_static::lazy_static! {
     pub static ref VERSIONED_APP_NAME: String = format!("{APP_NAME} v{}", *VERSION);
 }
 
+/// Title of the window instead of [`APP_NAME`], e.g. for streams or custom builds
+const WINDOW_TITLE_VAR: &str = "WINDOW_TITLE";
+/// `0` or `false` leaves the version out of the window title
+const WINDOW_TITLE_VERSION_VAR: &str = "WINDOW_TITLE_VERSION";
+
+/// The window title: [`VERSIONED_APP_NAME`] unless [`WINDOW_TITLE_VAR`] is set.
+///
+/// A blank title falls back to [`APP_NAME`], a titlebar without text is never wanted.
+fn window_title() -> String {
+    let name = std::env::var(WINDOW_TITLE_VAR)
+        .ok()
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty());
+    let with_version = std::env::var(WINDOW_TITLE_VERSION_VAR)
+        .map_or(true, |value| !matches!(value.trim(), "0" | "false"));
+    match (name, with_version) {
+        (None, true) => VERSIONED_APP_NAME.clone(),
+        (None, false) => APP_NAME.to_string(),
+        (Some(name), true) => format!("{name} v{}", *VERSION),
+        (Some(name), false) => name,
+    }
+}
+
+/// Returns the value following `flag` in the command line arguments
+fn cli_path(flag: &str) -> Option<PathBuf> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next().map(PathBuf::from)
+}
+
+/// Returns the positive number following `flag`, an invalid one is ignored with a warning
+fn cli_positive(flag: &str, unit: &str) -> Option<f64> {
+    let value = cli_path(flag)?;
+    match value.to_string_lossy().parse::<f64>() {
+        Ok(number) if number > 0. => Some(number),
+        _ => {
+            warn!("Ignoring {} {:?}, expected a positive number of {}", flag, value, unit);
+            None
+        }
+    }
+}
+
+/// Returns the rate in Hz following `flag`
+fn cli_rate(flag: &str) -> Option<f64> {
+    cli_positive(flag, "Hz")
+}
+
+/// --connect-timeout <seconds> changes how long a client waits for the host
+fn connect_timeout() -> Duration {
+    cli_positive("--connect-timeout", "seconds")
+        .map_or(DEFAULT_CONNECT_TIMEOUT, Duration::from_secs_f64)
+}
+
+/// --tick-rate <hz> and --sync-rate <hz> change the physics tick and the snapshot rates
+fn simulation_config() -> SimulationConfig {
+    let mut config = SimulationConfig::default();
+    if let Some(rate) = cli_rate("--tick-rate") {
+        config.physics_hz = rate;
+    }
+    if let Some(rate) = cli_rate("--sync-rate") {
+        config.net_sync_hz = rate;
+    }
+    config
+}
+
+/// Logs to the console, and to a rotating file if `LOG_DIR` is set
+///
+/// Everything passes the filter of the plugin unless `RUST_LOG` is set, the [`LogFilter`] layer
+/// filters instead so it can be changed at runtime.
+fn log_plugin() -> LogPlugin {
+    LogPlugin {
+        level: Level::TRACE,
+        filter: String::new(),
+        update_subscriber: Some(log_layers),
+    }
+}
+
+/// --instance-log <dir> writes this instance into its own files, named after the lobby role
+#[allow(clippy::let_and_return)]
+fn log_layers(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    let subscriber = with_crash_log_tail(with_log_filter(subscriber, LogFilter::default()));
+    let subscriber = with_instance_log(with_log_file(subscriber), cli_path("--instance-log"));
+    // records for the `logs` console window
+    #[cfg(feature = "dev")]
+    let subscriber = urmom::log_console::with_log_capture(subscriber);
+    subscriber
+}
+
 fn main() {
-    std::env::set_var(
-        "RUST_LOG",
-        std::env::var("RUST_LOG").unwrap_or(String::from(RUST_LOG_DEFAULT)),
-    );
+    // first, a panic while the app is built is reported too
+    install_panic_hook(VERSION.clone());
 
     let mut app = App::new();
 
@@ -45,18 +129,30 @@ fn main() {
     fn default_build(app: &mut App, asset_plugin: AssetPlugin) -> &mut App {
         let window_plugin_override = WindowPlugin {
             primary_window: Some(Window {
-                title: VERSIONED_APP_NAME.clone(),
+                title: window_title(),
                 //fit_canvas_to_parent: true,
                 prevent_default_event_handling: false,
                 ..default()
             }),
+            // the quit dialog decides, see `QuitDialogPlugins`
+            close_when_requested: false,
             ..default()
         };
         app.add_plugins((
-            DefaultPlugins.set(window_plugin_override).set(asset_plugin),
+            DefaultPlugins
+                .set(window_plugin_override)
+                .set(asset_plugin)
+                .set(log_plugin()),
             EguiPlugin,
-            RapierPhysicsPlugin::<NoUserData>::default(),
-        ))
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+        ));
+        // off until toggled, see `PhysicsDebugPlugins`
+        #[cfg(feature = "dev")]
+        app.add_plugins(bevy_rapier3d::render::RapierDebugRenderPlugin {
+            enabled: false,
+            ..default()
+        });
+        app
     }
 
     #[cfg(not(feature = "dev"))]
@@ -73,7 +169,7 @@ fn main() {
 
         let window_plugin_override = WindowPlugin {
             primary_window: Some(Window {
-                title: VERSIONED_APP_NAME.clone(),
+                title: window_title(),
                 resolution: WindowResolution::default(),
                 present_mode: PresentMode::AutoNoVsync,
                 // Tells wasm to resize the window according to the available canvas
@@ -82,47 +178,58 @@ fn main() {
                 prevent_default_event_handling: false,
                 ..default()
             }),
+            // the quit dialog decides, see `QuitDialogPlugins`
+            close_when_requested: false,
             ..default()
         };
         app.add_plugins((
-            DefaultPlugins.set(window_plugin_override).set(asset_plugin),
+            DefaultPlugins
+                .set(window_plugin_override)
+                .set(asset_plugin)
+                .set(log_plugin()),
             EguiPlugin,
-            RapierPhysicsPlugin::<NoUserData>::default(),
+            RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+            // on from the start with DEBUG, still toggled by `PhysicsDebugPlugins`
             RapierDebugRenderPlugin::default(),
             EditorPlugins,
         ));
     }
 
-    // it can be difficult to make physics undependent from the frame rate
-    // but we cannot use FixedUpdate because it is not supported by bevy_xpbd_3d as well as
+    // rapier steps in FixedUpdate, tick rates are controlled by the `SimulationConfig` resource
+    // so the simulation does not depend on the frame rate
     app.add_systems(Startup, set_window_icon)
-        .add_plugins(CorePlugins);
+        .add_plugins(
+            CorePlugins::default()
+                .with_simulation(simulation_config())
+                .with_connect_timeout(connect_timeout()),
+        )
+        .add_plugins(CrashReportPlugin);
+
+    // --record <file> records the hosted session, --replay <file> plays one back without networking
+    if let Some(path) = cli_path("--record") {
+        app.insert_resource(RecordReplay(path));
+    }
+    // --results <dir> writes the results of hosted matches there instead of next to the settings
+    if let Some(path) = cli_path("--results") {
+        app.insert_resource(MatchResultsDir(path));
+    }
+    if cli_path("--instance-log").is_some() {
+        app.add_plugins(InstanceLogPlugin);
+    }
+    if let Some(path) = cli_path("--replay") {
+        match ReplayPlayback::open(&path) {
+            Ok(playback) => {
+                app.insert_resource(playback)
+                    .add_plugins(ReplayPlaybackPlugins);
+            }
+            Err(err) => {
+                error!("Cannot play replay {:?}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
 
     info!("Starting {APP_NAME} v{}", *VERSION);
 
     app.run();
 }
-
-fn set_window_icon(windows: NonSend<WinitWindows>) {
-    let exe_path = env::current_exe().expect("Failed to find executable path");
-    let exe_dir = exe_path
-        .parent()
-        .expect("Failed to find executable directory");
-    let (icon_rgba, icon_width, icon_height) = {
-        if let Ok(image) = image::open(exe_dir.join(ICON_PATH)) {
-            let image = image.into_rgba8();
-            let (width, height) = image.dimensions();
-            let rgba = image.into_raw();
-            (rgba, width, height)
-        } else {
-            // TODO load default icon from url
-            warn!("Failed to load icon");
-            return;
-        }
-    };
-    let icon = Icon::from_rgba(icon_rgba, icon_width, icon_height).unwrap();
-
-    for window in windows.windows.values() {
-        window.set_window_icon(Some(icon.clone()));
-    }
-}
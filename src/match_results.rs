@@ -0,0 +1,193 @@
+//! Results of the matches hosted on this machine, one JSON file per match.
+//!
+//! A match is a level played by a host, it ends when the host changes the level or leaves the lobby.
+//! The files go to `results/` next to the settings, or to the directory given with `--results <dir>`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::core::CurrentLevel;
+use crate::lobby::team::TeamId;
+use crate::lobby::{
+    ChangeMapLobbyEvent, LevelCode, Lobby, LobbyState, MapLoaderState, PlayerDiedEvent, PlayerId,
+};
+use crate::settings::settings_dir;
+
+/// Lifecycle of a hosted match.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
+pub enum MatchState {
+    #[default]
+    None,
+    Running,
+    /// The results are written on enter, then the state goes back to [`MatchState::None`]
+    Ended,
+}
+
+/// Directory the match results are written to.
+#[derive(Debug, Clone, Resource, Deref)]
+pub struct MatchResultsDir(pub PathBuf);
+
+impl Default for MatchResultsDir {
+    fn default() -> Self {
+        Self(settings_dir().join("results"))
+    }
+}
+
+/// Result of a player, kept after the player leaves.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlayerResult {
+    pub username: String,
+    pub team: Option<TeamId>,
+    pub kills: u32,
+    pub deaths: u32,
+    /// Kills minus deaths
+    pub score: i64,
+}
+
+/// Content of a results file.
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchSummary {
+    pub map: LevelCode,
+    /// Seconds since the level was loaded
+    pub duration: f64,
+    /// Unix time in seconds
+    pub ended_at: u64,
+    /// Best score first
+    pub players: Vec<PlayerResult>,
+}
+
+/// Counters of the running match.
+#[derive(Debug, Default, Resource)]
+struct MatchRecord {
+    map: Option<LevelCode>,
+    started_at: f64,
+    players: HashMap<PlayerId, PlayerResult>,
+}
+
+pub struct MatchResultsPlugin;
+
+impl Plugin for MatchResultsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_state(MatchState::default())
+            .init_resource::<MatchResultsDir>()
+            .init_resource::<MatchRecord>()
+            .add_systems(
+                OnEnter(MapLoaderState::Yes),
+                start_match.run_if(in_state(LobbyState::Host)),
+            )
+            .add_systems(
+                Update,
+                (track_players, end_match_on_change_map)
+                    .chain()
+                    .run_if(in_state(LobbyState::Host).and_then(in_state(MatchState::Running))),
+            )
+            .add_systems(
+                OnExit(LobbyState::Host),
+                end_match.run_if(in_state(MatchState::Running)),
+            )
+            .add_systems(OnEnter(MatchState::Ended), write_results);
+    }
+}
+
+fn start_match(
+    time: Res<Time>,
+    current_level: Res<CurrentLevel>,
+    mut record: ResMut<MatchRecord>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    *record = MatchRecord {
+        map: Some(current_level.0.clone()),
+        started_at: time.elapsed_seconds_f64(),
+        players: HashMap::new(),
+    };
+    next_state.set(MatchState::Running);
+}
+
+fn track_players(
+    lobby: Res<Lobby>,
+    mut player_died_event: EventReader<PlayerDiedEvent>,
+    mut record: ResMut<MatchRecord>,
+) {
+    // the last known name and team stay once a player leaves
+    let players = std::iter::once((PlayerId::HostOrSingle, &lobby.me))
+        .chain(lobby.players.iter().map(|(id, player_data)| (*id, player_data)));
+    for (id, player_data) in players {
+        let result = record.players.entry(id).or_default();
+        result.username.clone_from(&player_data.username);
+        result.team = player_data.team;
+    }
+
+    for PlayerDiedEvent { id, killer, .. } in player_died_event.read() {
+        record.players.entry(*id).or_default().deaths += 1;
+        // self-kills are not counted as kills
+        if let Some(killer) = killer.filter(|killer| killer != id) {
+            record.players.entry(killer).or_default().kills += 1;
+        }
+    }
+}
+
+fn end_match_on_change_map(
+    mut change_map_event: EventReader<ChangeMapLobbyEvent>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if change_map_event.read().count() > 0 {
+        next_state.set(MatchState::Ended);
+    }
+}
+
+fn end_match(mut next_state: ResMut<NextState<MatchState>>) {
+    next_state.set(MatchState::Ended);
+}
+
+fn write_results(
+    time: Res<Time>,
+    results_dir: Res<MatchResultsDir>,
+    mut record: ResMut<MatchRecord>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    next_state.set(MatchState::None);
+    let record = std::mem::take(&mut *record);
+    let Some(map) = record.map else {
+        return;
+    };
+
+    let mut players: Vec<PlayerResult> = record
+        .players
+        .into_values()
+        .map(|mut result| {
+            result.score = result.kills as i64 - result.deaths as i64;
+            result
+        })
+        .collect();
+    players.sort_by(|a, b| b.score.cmp(&a.score).then(a.username.cmp(&b.username)));
+
+    let ended_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let summary = MatchSummary {
+        map,
+        duration: time.elapsed_seconds_f64() - record.started_at,
+        ended_at: ended_at.as_secs(),
+        players,
+    };
+
+    let path = results_dir.join(format!("match-{}.json", ended_at.as_millis()));
+    match write_summary(&path, &summary) {
+        Ok(()) => log::info!("Match results written to {:?}", path),
+        Err(err) => log::error!("Failed to write match results ({:?}): {}", path, err),
+    }
+}
+
+fn write_summary(path: &Path, summary: &MatchSummary) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string_pretty(summary)?;
+    fs::write(path, content)?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use renet::{ChannelConfig, ConnectionConfig, SendType};
+
+/// Bytes a connection may send per tick, shared by every channel
+const AVAILABLE_BYTES_PER_TICK: u64 = 256 * 1024;
+const RESEND_TIME: Duration = Duration::from_millis(200);
+
+/// Channels of the game protocol, the same on the host and the clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    /// Small reliable ordered messages: connections, spawns, despawns, chat.
+    Control,
+    /// Large reliable messages (e.g. levels, world snapshots), sliced by renet.
+    ///
+    /// Not ordered, even relative to [`Channel::Control`], so a big transfer does not stall the control messages.
+    Bulk,
+    /// Unreliable snapshots and inputs, only the latest one matters.
+    State,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Control, Channel::Bulk, Channel::State];
+
+    fn config(self) -> ChannelConfig {
+        let (max_memory_usage_bytes, send_type) = match self {
+            Channel::Control => (
+                5 * 1024 * 1024,
+                SendType::ReliableOrdered {
+                    resend_time: RESEND_TIME,
+                },
+            ),
+            Channel::Bulk => (
+                64 * 1024 * 1024,
+                SendType::ReliableUnordered {
+                    resend_time: RESEND_TIME,
+                },
+            ),
+            Channel::State => (5 * 1024 * 1024, SendType::Unreliable),
+        };
+        ChannelConfig {
+            channel_id: self.into(),
+            max_memory_usage_bytes,
+            send_type,
+        }
+    }
+}
+
+impl From<Channel> for u8 {
+    fn from(channel: Channel) -> Self {
+        match channel {
+            Channel::Control => 0,
+            Channel::Bulk => 1,
+            Channel::State => 2,
+        }
+    }
+}
+
+/// Connection config of both ends, a channel mismatch silently loses messages
+/// so the host and the clients must build it here.
+pub fn connection_config() -> ConnectionConfig {
+    let channels: Vec<ChannelConfig> = Channel::ALL.into_iter().map(Channel::config).collect();
+    ConnectionConfig {
+        available_bytes_per_tick: AVAILABLE_BYTES_PER_TICK,
+        server_channels_config: channels.clone(),
+        client_channels_config: channels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_ids_are_unique_and_in_order() {
+        let config = connection_config();
+        let ids: Vec<u8> = config
+            .server_channels_config
+            .iter()
+            .map(|channel| channel.channel_id)
+            .collect();
+        let expected: Vec<u8> = Channel::ALL.into_iter().map(u8::from).collect();
+        assert_eq!(ids, expected);
+        assert_eq!(expected, vec![0, 1, 2]);
+    }
+}
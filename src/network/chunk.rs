@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use bevy::ecs::system::Resource;
+use serde::{Deserialize, Serialize};
+
+/// Payloads bigger than this are split into [`Chunk`]s.
+pub const MAX_UNCHUNKED_SIZE: usize = 32 * 1024;
+/// Size of the payload carried by a single [`Chunk`].
+pub const CHUNK_PAYLOAD_SIZE: usize = 16 * 1024;
+/// Upper bound of chunks in one transfer, larger transfers are rejected by the receiver.
+pub const MAX_CHUNK_COUNT: u32 = 1024;
+/// Seconds without a new chunk after which the receiver drops an incomplete transfer.
+pub const TRANSFER_TIMEOUT: f32 = 10.;
+/// Incomplete transfers the receiver keeps at once, the one idle the longest makes room for a new one.
+pub const MAX_PENDING_TRANSFERS: usize = 4;
+
+/// A sequenced fragment of a large payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Identifies the payload the fragment belongs to.
+    pub transfer_id: u32,
+    /// Position of the fragment inside the payload.
+    pub index: u32,
+    /// Total number of fragments of the payload.
+    pub count: u32,
+    pub data: Vec<u8>,
+}
+
+/// Splits large payloads into [`Chunk`]s.
+#[derive(Debug, Default, Resource)]
+pub struct ChunkSender {
+    next_transfer_id: u32,
+}
+
+impl ChunkSender {
+    /// Splits `payload` into sequenced chunks of at most [`CHUNK_PAYLOAD_SIZE`] bytes.
+    pub fn split(&mut self, payload: &[u8]) -> Vec<Chunk> {
+        let transfer_id = self.next_transfer_id;
+        self.next_transfer_id = self.next_transfer_id.wrapping_add(1);
+
+        let count = payload.len().div_ceil(CHUNK_PAYLOAD_SIZE).max(1) as u32;
+        if payload.is_empty() {
+            return vec![Chunk {
+                transfer_id,
+                index: 0,
+                count,
+                data: Vec::new(),
+            }];
+        }
+
+        payload
+            .chunks(CHUNK_PAYLOAD_SIZE)
+            .enumerate()
+            .map(|(index, data)| Chunk {
+                transfer_id,
+                index: index as u32,
+                count,
+                data: data.to_vec(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct PartialTransfer {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: u32,
+    /// Time the last chunk arrived at, see [`TRANSFER_TIMEOUT`]
+    last_chunk: f32,
+}
+
+/// Reassembles payloads split by [`ChunkSender`].
+#[derive(Debug, Default, Resource)]
+pub struct ChunkReceiver {
+    transfers: HashMap<u32, PartialTransfer>,
+}
+
+impl ChunkReceiver {
+    /// Stores `chunk` received at `now` (seconds) and returns the original payload once all its
+    /// chunks arrived.
+    ///
+    /// Malformed chunks (out of range index, inconsistent count, oversized data) are dropped
+    /// with an error log. Incomplete transfers are dropped after [`TRANSFER_TIMEOUT`] or to keep
+    /// at most [`MAX_PENDING_TRANSFERS`], so a peer never finishing them does not grow memory.
+    pub fn receive(&mut self, chunk: Chunk, now: f32) -> Option<Vec<u8>> {
+        if chunk.count == 0
+            || chunk.count > MAX_CHUNK_COUNT
+            || chunk.index >= chunk.count
+            || chunk.data.len() > CHUNK_PAYLOAD_SIZE
+        {
+            log::error!(
+                "Dropping malformed chunk {}/{} of transfer {}",
+                chunk.index,
+                chunk.count,
+                chunk.transfer_id
+            );
+            return None;
+        }
+
+        self.expire(now);
+        if !self.transfers.contains_key(&chunk.transfer_id)
+            && self.transfers.len() >= MAX_PENDING_TRANSFERS
+        {
+            let idlest = self
+                .transfers
+                .iter()
+                .min_by(|(_, a), (_, b)| a.last_chunk.total_cmp(&b.last_chunk))
+                .map(|(id, _)| *id);
+            if let Some(id) = idlest {
+                log::warn!("Too many incomplete transfers, dropping transfer {}", id);
+                self.transfers.remove(&id);
+            }
+        }
+
+        let transfer = self
+            .transfers
+            .entry(chunk.transfer_id)
+            .or_insert_with(|| PartialTransfer {
+                chunks: vec![None; chunk.count as usize],
+                received: 0,
+                last_chunk: now,
+            });
+
+        if transfer.chunks.len() != chunk.count as usize {
+            log::error!(
+                "Chunk count of transfer {} changed, restarting it",
+                chunk.transfer_id
+            );
+            *transfer = PartialTransfer {
+                chunks: vec![None; chunk.count as usize],
+                received: 0,
+                last_chunk: now,
+            };
+        }
+
+        transfer.last_chunk = now;
+        let slot = &mut transfer.chunks[chunk.index as usize];
+        if slot.is_none() {
+            *slot = Some(chunk.data);
+            transfer.received += 1;
+        }
+
+        if transfer.received < chunk.count {
+            return None;
+        }
+
+        let transfer = self.transfers.remove(&chunk.transfer_id)?;
+        Some(transfer.chunks.into_iter().flatten().flatten().collect())
+    }
+
+    /// Drops the incomplete transfers that got no chunk for [`TRANSFER_TIMEOUT`].
+    fn expire(&mut self, now: f32) {
+        self.transfers.retain(|id, transfer| {
+            let alive = now - transfer.last_chunk < TRANSFER_TIMEOUT;
+            if !alive {
+                log::warn!(
+                    "Transfer {} timed out with {}/{} chunks",
+                    id,
+                    transfer.received,
+                    transfer.chunks.len()
+                );
+            }
+            alive
+        });
+    }
+
+    /// Number of transfers waiting for chunks.
+    pub fn pending(&self) -> usize {
+        self.transfers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn large_payload_round_trips() {
+        let payload = payload(200 * 1024);
+        let chunks = ChunkSender::default().split(&payload);
+        assert_eq!(chunks.len(), 200 * 1024 / CHUNK_PAYLOAD_SIZE + 1);
+
+        let mut receiver = ChunkReceiver::default();
+        let count = chunks.len();
+        let mut reassembled = None;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            reassembled = receiver.receive(chunk, 0.);
+            assert_eq!(reassembled.is_some(), i == count - 1);
+        }
+        assert_eq!(reassembled, Some(payload));
+        assert_eq!(receiver.pending(), 0);
+    }
+
+    #[test]
+    fn out_of_order_chunks_reassemble() {
+        let payload = payload(3 * CHUNK_PAYLOAD_SIZE);
+        let mut chunks = ChunkSender::default().split(&payload);
+        chunks.reverse();
+
+        let mut receiver = ChunkReceiver::default();
+        let reassembled = chunks
+            .into_iter()
+            .filter_map(|chunk| receiver.receive(chunk, 0.))
+            .last();
+        assert_eq!(reassembled, Some(payload));
+    }
+
+    #[test]
+    fn idle_transfer_expires() {
+        let mut sender = ChunkSender::default();
+        let mut receiver = ChunkReceiver::default();
+        let stalled = sender.split(&payload(2 * CHUNK_PAYLOAD_SIZE));
+        assert!(receiver.receive(stalled[0].clone(), 0.).is_none());
+        assert_eq!(receiver.pending(), 1);
+
+        let next = sender.split(&payload(2 * CHUNK_PAYLOAD_SIZE));
+        receiver.receive(next[0].clone(), TRANSFER_TIMEOUT);
+        assert_eq!(receiver.pending(), 1);
+        // the rest of the expired transfer starts it over instead of completing it
+        assert!(receiver.receive(stalled[1].clone(), TRANSFER_TIMEOUT).is_none());
+    }
+
+    #[test]
+    fn pending_transfers_are_capped() {
+        let mut sender = ChunkSender::default();
+        let mut receiver = ChunkReceiver::default();
+        for i in 0..MAX_PENDING_TRANSFERS * 2 {
+            let chunks = sender.split(&payload(2 * CHUNK_PAYLOAD_SIZE));
+            receiver.receive(chunks[0].clone(), i as f32 * 0.1);
+        }
+        assert_eq!(receiver.pending(), MAX_PENDING_TRANSFERS);
+    }
+
+    #[test]
+    fn oversized_chunk_is_dropped() {
+        let mut receiver = ChunkReceiver::default();
+        let chunk = Chunk {
+            transfer_id: 0,
+            index: 0,
+            count: 1,
+            data: payload(CHUNK_PAYLOAD_SIZE + 1),
+        };
+        assert!(receiver.receive(chunk, 0.).is_none());
+        assert_eq!(receiver.pending(), 0);
+    }
+}
@@ -0,0 +1,151 @@
+//! Simulated loss and latency of the unreliable [`Channel::State`] traffic, to test the game
+//! against a bad network on a perfect local one.
+//!
+//! Messages are conditioned where they are received, so the host conditions the inputs of
+//! its clients and a client the snapshots of the host. Reliable channels are not touched,
+//! renet would resend what is dropped anyway.
+//!
+//! Only built in debug builds with the `dev` feature. It must never be enabled in release builds,
+//! players would get the simulated loss on top of their real one.
+//!
+//! [`Channel::State`]: super::Channel::State
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use rand::Rng;
+use renet::ClientId;
+
+use crate::console::{no_args, CommandResult, CommandScope, ConsoleAppExt};
+use crate::lobby::LobbyState;
+
+/// Upper bound of the latency and jitter knobs
+const MAX_DELAY_MS: u64 = 1000;
+
+/// Knobs of the simulated link, set in the window opened with the `netsim` console command.
+#[derive(Debug, Resource)]
+pub struct LinkConditioner {
+    pub enabled: bool,
+    /// Fraction of dropped messages in `0.0..=1.0`
+    pub loss: f32,
+    /// Delay added to every message
+    pub latency: Duration,
+    /// Random extra delay up to this, reorders the messages like a real link does
+    pub jitter: Duration,
+    window: bool,
+    /// Delayed messages with the time they are due, `None` source is the host
+    queue: Vec<(Instant, Option<ClientId>, Vec<u8>)>,
+}
+
+impl Default for LinkConditioner {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            loss: 0.1,
+            latency: Duration::from_millis(150),
+            jitter: Duration::ZERO,
+            window: false,
+            queue: Vec::new(),
+        }
+    }
+}
+
+impl LinkConditioner {
+    /// Passes the `messages` just received from `source` through the simulated link,
+    /// returns the messages of `source` that are due now, including earlier delayed ones.
+    pub fn condition(
+        &mut self,
+        source: Option<ClientId>,
+        messages: Vec<Vec<u8>>,
+    ) -> Vec<Vec<u8>> {
+        let now = Instant::now();
+        if !self.enabled {
+            // flushed, so turning it off does not lose what is in flight
+            let mut due = self.take_due(source, None);
+            due.extend(messages);
+            return due;
+        }
+
+        let mut rng = rand::thread_rng();
+        for message in messages {
+            if rng.gen::<f32>() < self.loss {
+                continue;
+            }
+            let jitter = rng.gen_range(Duration::ZERO..=self.jitter);
+            self.queue.push((now + self.latency + jitter, source, message));
+        }
+        self.take_due(source, Some(now))
+    }
+
+    /// Removes the messages of `source` due by `now`, all of them if `None`.
+    fn take_due(&mut self, source: Option<ClientId>, now: Option<Instant>) -> Vec<Vec<u8>> {
+        let mut due = Vec::new();
+        let mut i = 0;
+        while i < self.queue.len() {
+            let (at, from, _) = &self.queue[i];
+            if *from == source && now.map_or(true, |now| *at <= now) {
+                // `remove` rather than `swap_remove` keeps the order of equally delayed messages
+                due.push(self.queue.remove(i).2);
+            } else {
+                i += 1;
+            }
+        }
+        due
+    }
+}
+
+pub struct LinkConditionerPlugins;
+
+impl Plugin for LinkConditionerPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LinkConditioner>()
+            .add_systems(Update, link_conditioner_window)
+            .add_systems(OnEnter(LobbyState::None), clear_link_conditioner)
+            .add_console_command(
+                "netsim",
+                "",
+                CommandScope::Local,
+                no_args,
+                toggle_link_conditioner_window,
+            );
+    }
+}
+
+fn toggle_link_conditioner_window(world: &mut World, _: ()) -> CommandResult {
+    let mut conditioner = world.resource_mut::<LinkConditioner>();
+    conditioner.window = !conditioner.window;
+    Ok(None)
+}
+
+/// Drops the messages still in flight of the left session.
+fn clear_link_conditioner(mut conditioner: ResMut<LinkConditioner>) {
+    conditioner.queue.clear();
+}
+
+fn link_conditioner_window(mut context: EguiContexts, mut conditioner: ResMut<LinkConditioner>) {
+    if !conditioner.window {
+        return;
+    }
+
+    let mut open = true;
+    let mut loss = conditioner.loss * 100.;
+    let mut latency = conditioner.latency.as_millis() as u64;
+    let mut jitter = conditioner.jitter.as_millis() as u64;
+
+    egui::Window::new("Link conditioner")
+        .open(&mut open)
+        .resizable(false)
+        .show(context.ctx_mut(), |ui| {
+            ui.checkbox(&mut conditioner.enabled, "Enabled");
+            ui.add(egui::Slider::new(&mut loss, 0.0..=100.).text("loss %"));
+            ui.add(egui::Slider::new(&mut latency, 0..=MAX_DELAY_MS).text("latency ms"));
+            ui.add(egui::Slider::new(&mut jitter, 0..=MAX_DELAY_MS).text("jitter ms"));
+            ui.label(format!("in flight: {}", conditioner.queue.len()));
+        });
+
+    conditioner.window = open;
+    conditioner.loss = loss / 100.;
+    conditioner.latency = Duration::from_millis(latency);
+    conditioner.jitter = Duration::from_millis(jitter);
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+
+use super::{CHUNK_PAYLOAD_SIZE, MAX_CHUNK_COUNT};
+
+/// Largest message a client sends to the host, bigger ones are not decoded
+pub const MAX_CLIENT_MESSAGE_SIZE: usize = 64 * 1024;
+/// Largest message of the host, a reassembled chunked transfer included
+pub const MAX_SERVER_MESSAGE_SIZE: usize = MAX_CHUNK_COUNT as usize * CHUNK_PAYLOAD_SIZE;
+/// Bytes of a rejected payload shown in the logs
+const LOGGED_BYTES: usize = 16;
+
+/// Why a received payload was dropped.
+#[derive(Debug)]
+pub enum DecodeError {
+    TooLarge { size: usize, max: usize },
+    Malformed(bincode::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { size, max } => write!(f, "{} bytes, at most {} accepted", size, max),
+            Self::Malformed(err) => write!(f, "malformed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Deserializes a payload of the other side, which may be corrupted or crafted.
+///
+/// Same encoding as `bincode::deserialize`, but a payload over `max_size` is rejected before
+/// decoding and the lengths inside it are checked against `max_size` too.
+pub fn decode<T: DeserializeOwned>(payload: &[u8], max_size: usize) -> Result<T, DecodeError> {
+    if payload.len() > max_size {
+        return Err(DecodeError::TooLarge {
+            size: payload.len(),
+            max: max_size,
+        });
+    }
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(max_size as u64)
+        .deserialize(payload)
+        .map_err(DecodeError::Malformed)
+}
+
+/// The first bytes of `payload` in hex, to recognize a rejected one in the logs.
+pub fn payload_head(payload: &[u8]) -> String {
+    let head: Vec<String> = payload
+        .iter()
+        .take(LOGGED_BYTES)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let more = if payload.len() > LOGGED_BYTES { " .." } else { "" };
+    format!("[{}{}] ({} bytes)", head.join(" "), more, payload.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::lobby::{ClientMessages, ServerMessages, TransportData};
+
+    #[test]
+    fn random_bytes_never_panic() {
+        let mut rng = StdRng::seed_from_u64(1099);
+        for _ in 0..2000 {
+            let len = rng.gen_range(0..256);
+            let payload: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let _ = decode::<ClientMessages>(&payload, MAX_CLIENT_MESSAGE_SIZE);
+            let _ = decode::<ServerMessages>(&payload, MAX_SERVER_MESSAGE_SIZE);
+            let _ = decode::<TransportData>(&payload, MAX_SERVER_MESSAGE_SIZE);
+        }
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let message = ClientMessages::Chat {
+            text: "hello there".to_string(),
+        };
+        let payload = bincode::serialize(&message).unwrap();
+        assert!(decode::<ClientMessages>(&payload, MAX_CLIENT_MESSAGE_SIZE).is_ok());
+        for len in 0..payload.len() {
+            let result = decode::<ClientMessages>(&payload[..len], MAX_CLIENT_MESSAGE_SIZE);
+            assert!(matches!(result, Err(DecodeError::Malformed(_))), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn oversized_payloads_are_not_decoded() {
+        let payload = vec![0; MAX_CLIENT_MESSAGE_SIZE + 1];
+        let result = decode::<ClientMessages>(&payload, MAX_CLIENT_MESSAGE_SIZE);
+        assert!(matches!(result, Err(DecodeError::TooLarge { .. })));
+    }
+
+    #[test]
+    fn huge_lengths_inside_are_rejected() {
+        // `Chat` claiming a text of u64::MAX bytes
+        let mut payload = bincode::serialize(&ClientMessages::Chat {
+            text: String::new(),
+        })
+        .unwrap();
+        let len_at = payload.len() - 8;
+        payload[len_at..].copy_from_slice(&u64::MAX.to_le_bytes());
+        let result = decode::<ClientMessages>(&payload, MAX_CLIENT_MESSAGE_SIZE);
+        assert!(matches!(result, Err(DecodeError::Malformed(_))));
+    }
+
+    #[test]
+    fn payload_head_is_bounded() {
+        assert_eq!(payload_head(&[0xab, 0x01]), "[ab 01] (2 bytes)");
+        let head = payload_head(&[0xff; 100]);
+        assert!(head.ends_with(" ..] (100 bytes)"), "{}", head);
+        assert_eq!(head.matches("ff").count(), LOGGED_BYTES);
+    }
+}
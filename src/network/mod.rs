@@ -0,0 +1,17 @@
+#![allow(clippy::module_inception)]
+
+mod channels;
+mod chunk;
+#[cfg(all(debug_assertions, feature = "dev"))]
+mod conditioner;
+mod decode;
+mod stats;
+mod transport;
+
+pub use channels::*;
+pub use chunk::*;
+#[cfg(all(debug_assertions, feature = "dev"))]
+pub use conditioner::*;
+pub use decode::*;
+pub use stats::*;
+pub use transport::*;
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_renet::RenetReceive;
+use renet::{ClientId, NetworkInfo, RenetClient, RenetServer};
+
+/// Stats of one connection, as estimated by renet.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LinkStats {
+    pub bytes_sent_per_second: f64,
+    pub bytes_received_per_second: f64,
+    /// Round trip time in seconds
+    pub rtt: f64,
+    /// Estimated fraction of lost packets in `0.0..=1.0`
+    pub packet_loss: f64,
+}
+
+impl From<NetworkInfo> for LinkStats {
+    fn from(info: NetworkInfo) -> Self {
+        Self {
+            bytes_sent_per_second: info.bytes_sent_per_second,
+            bytes_received_per_second: info.bytes_received_per_second,
+            rtt: info.rtt,
+            packet_loss: info.packet_loss,
+        }
+    }
+}
+
+/// Network stats of this side of the session, updated every frame, empty when not connected.
+///
+/// Read it instead of the [`RenetServer`] or [`RenetClient`], so only this module knows renet.
+#[derive(Debug, Default, Clone, Resource)]
+pub struct NetStats {
+    /// Every client of the host, rates summed and round trip and loss averaged,
+    /// or the connection to the host on a client
+    pub total: LinkStats,
+    /// Connection of each client, host side only
+    pub clients: HashMap<ClientId, LinkStats>,
+    /// Clients on the host, `1` on a connected client
+    pub connections: usize,
+}
+
+impl NetStats {
+    /// Rates summed, round trip and loss averaged.
+    fn aggregate(links: impl IntoIterator<Item = LinkStats>) -> (LinkStats, usize) {
+        let mut total = LinkStats::default();
+        let mut count = 0;
+        for link in links {
+            total.bytes_sent_per_second += link.bytes_sent_per_second;
+            total.bytes_received_per_second += link.bytes_received_per_second;
+            total.rtt += link.rtt;
+            total.packet_loss += link.packet_loss;
+            count += 1;
+        }
+        if count > 0 {
+            total.rtt /= count as f64;
+            total.packet_loss /= count as f64;
+        }
+        (total, count)
+    }
+
+    pub fn client(&self, client_id: ClientId) -> Option<&LinkStats> {
+        self.clients.get(&client_id)
+    }
+}
+
+/// Keeps [`NetStats`] up to date in every lobby mode, right after renet received the packets.
+pub struct NetStatsPlugin;
+
+impl Plugin for NetStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetStats>()
+            .add_systems(PreUpdate, update_net_stats.after(RenetReceive));
+    }
+}
+
+fn update_net_stats(
+    server: Option<Res<RenetServer>>,
+    client: Option<Res<RenetClient>>,
+    mut stats: ResMut<NetStats>,
+) {
+    stats.clients.clear();
+    if let Some(server) = server {
+        for client_id in server.clients_id() {
+            if let Ok(info) = server.network_info(client_id) {
+                stats.clients.insert(client_id, info.into());
+            }
+        }
+        (stats.total, stats.connections) = NetStats::aggregate(stats.clients.values().copied());
+    } else if let Some(client) = client.filter(|client| client.is_connected()) {
+        stats.total = client.network_info().into();
+        stats.connections = 1;
+    } else {
+        stats.total = LinkStats::default();
+        stats.connections = 0;
+    }
+}
@@ -0,0 +1,104 @@
+//! Transports the [`RenetClient`](renet::RenetClient) and [`RenetServer`](renet::RenetServer) run on.
+//!
+//! Every connection is made here, so another transport only has to be added next to these.
+//! There is only the netcode one over a [`UdpSocket`].
+// TODO: a browser (wasm32) build, it needs a WebSocket transport here and a listener for it
+// on the native host, neither exists yet
+
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::SystemTime;
+
+use renet::transport::{
+    ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication,
+    ServerConfig,
+};
+
+use crate::lobby::{ConnectInfo, PROTOCOL_ID};
+
+/// Clients a host accepts at once
+pub const MAX_CLIENTS: usize = 64;
+
+/// Transport connecting to `address` as `client_id`, a migrated client reconnects with its old id.
+pub fn new_client_transport(
+    address: &str,
+    info: ConnectInfo,
+    client_id: u64,
+) -> Result<NetcodeClientTransport, Box<dyn std::error::Error>> {
+    let server_addr = address.parse()?;
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+
+    let user_data = match info.to_user_data() {
+        Ok(bytes) => Some(bytes),
+        Err(_) => None,
+    };
+
+    let authentication = ClientAuthentication::Unsecure {
+        client_id,
+        protocol_id: PROTOCOL_ID,
+        server_addr,
+        user_data,
+    };
+
+    Ok(NetcodeClientTransport::new(current_time, authentication, socket)?)
+}
+
+/// Where a host listens and where the players of its network reach it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostAddresses {
+    /// Address of the socket, with the port the OS picked if none was asked for
+    pub bound: SocketAddr,
+    /// Address in the connect tokens, the LAN one when bound to every interface
+    pub public: SocketAddr,
+}
+
+/// Parses the address a host listens on, without a port (`0.0.0.0` or `0.0.0.0:`)
+/// or with port `0` the OS picks a free one.
+pub fn parse_bind_address(addr: &str) -> Result<SocketAddr, AddrParseError> {
+    let addr = addr.trim();
+    let addr = addr.strip_suffix(':').unwrap_or(addr);
+    match addr.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, 0)),
+        Err(_) => addr.parse(),
+    }
+}
+
+/// Address of the interface the default route goes out of, the one other machines
+/// of the network most likely reach this one on. `None` without a network.
+pub fn lan_address() -> Option<IpAddr> {
+    // connecting a UDP socket sends nothing, it only picks the interface
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+/// Transport of a host listening on `addr`, see [`parse_bind_address`].
+pub fn new_server_transport(
+    addr: &str,
+) -> Result<(NetcodeServerTransport, HostAddresses), Box<dyn std::error::Error>> {
+    let socket = UdpSocket::bind(parse_bind_address(addr)?)?;
+    // the port is only known now if the OS picked it
+    let bound = socket.local_addr()?;
+    let public = if bound.ip().is_unspecified() {
+        let ip = lan_address().unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        SocketAddr::new(ip, bound.port())
+    } else {
+        bound
+    };
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let server_config = ServerConfig {
+        current_time,
+        max_clients: MAX_CLIENTS,
+        protocol_id: PROTOCOL_ID,
+        public_addresses: vec![public],
+        authentication: ServerAuthentication::Unsecure,
+    };
+
+    let transport = NetcodeServerTransport::new(server_config, socket)?;
+    Ok((transport, HostAddresses { bound, public }))
+}
@@ -0,0 +1,49 @@
+//! Runtime toggle of the rapier debug render, with the `physics_debug` console command or
+//! [`TOGGLE_KEY`]. The render plugin is registered off in dev builds, see `main`.
+
+use bevy::prelude::*;
+use bevy_rapier3d::render::DebugRenderContext;
+
+use crate::console::{no_args, CommandResult, CommandScope, ConsoleAppExt};
+
+/// Dev key toggling the debug render, like the editor pick mode key
+const TOGGLE_KEY: KeyCode = KeyCode::F8;
+
+pub struct PhysicsDebugPlugins;
+
+impl Plugin for PhysicsDebugPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_key.run_if(resource_exists::<DebugRenderContext>),
+        )
+        .add_console_command(
+            "physics_debug",
+            "",
+            CommandScope::Local,
+            no_args,
+            toggle_physics_debug,
+        );
+    }
+}
+
+/// Flips the debug render, returns whether it is on now.
+fn toggle(context: &mut DebugRenderContext) -> bool {
+    context.enabled = !context.enabled;
+    log::info!("Physics debug render {}", if context.enabled { "on" } else { "off" });
+    context.enabled
+}
+
+fn toggle_key(keyboard: Res<ButtonInput<KeyCode>>, mut context: ResMut<DebugRenderContext>) {
+    if keyboard.just_pressed(TOGGLE_KEY) {
+        toggle(&mut context);
+    }
+}
+
+fn toggle_physics_debug(world: &mut World, _: ()) -> CommandResult {
+    let mut context = world
+        .get_resource_mut::<DebugRenderContext>()
+        .ok_or_else(|| "the physics debug render is not registered".to_string())?;
+    let enabled = toggle(&mut context);
+    Ok(Some(format!("physics debug {}", if enabled { "on" } else { "off" })))
+}
@@ -0,0 +1,159 @@
+//! Collision groups shared by every collider, so new bodies do not interact with the wrong ones.
+//!
+//! [`INTERACTIONS`] is the whole matrix, the constructors below only read it. A collider
+//! without groups (e.g. one of a custom level scene) is in all of them and acts as [`LEVEL`].
+//! Rapier only lets two colliders interact when each one is in the filter of the other,
+//! the tests check the matrix is symmetric and keeps the rules of the actors.
+
+use bevy_rapier3d::prelude::{CollisionGroups, Group, QueryFilter};
+
+pub const CHARACTER: Group = Group::GROUP_1;
+pub const PROJECTILE: Group = Group::GROUP_2;
+/// Static and moving level geometry
+pub const LEVEL: Group = Group::GROUP_3;
+/// Dynamic scene actors, pushed around and pulled by the gravity zones
+pub const PROP: Group = Group::GROUP_4;
+/// Gravity zones, bounce pads
+pub const SENSOR: Group = Group::GROUP_5;
+/// Rays cast from the camera, e.g. the quick chat ping
+pub const CAMERA_RAY: Group = Group::GROUP_6;
+
+/// Every group with the groups it interacts with.
+pub const INTERACTIONS: [(Group, Group); 6] = [
+    (CHARACTER, union(&[CHARACTER, PROJECTILE, LEVEL, PROP, SENSOR])),
+    (PROJECTILE, union(&[CHARACTER, LEVEL, PROP])),
+    (LEVEL, union(&[CHARACTER, PROJECTILE, LEVEL, PROP, CAMERA_RAY])),
+    (PROP, union(&[CHARACTER, PROJECTILE, LEVEL, PROP, SENSOR])),
+    (SENSOR, union(&[CHARACTER, PROP])),
+    (CAMERA_RAY, union(&[LEVEL])),
+];
+
+const fn union(groups: &[Group]) -> Group {
+    let mut bits = 0;
+    let mut i = 0;
+    while i < groups.len() {
+        bits |= groups[i].bits();
+        i += 1;
+    }
+    Group::from_bits_truncate(bits)
+}
+
+/// Groups `group` interacts with according to [`INTERACTIONS`], none if it is not there.
+const fn filter(group: Group) -> Group {
+    let mut i = 0;
+    while i < INTERACTIONS.len() {
+        if INTERACTIONS[i].0.bits() == group.bits() {
+            return INTERACTIONS[i].1;
+        }
+        i += 1;
+    }
+    Group::empty()
+}
+
+/// Whether colliders of the groups `a` and `b` interact.
+pub const fn interacts(a: Group, b: Group) -> bool {
+    filter(a).bits() & b.bits() != 0 && filter(b).bits() & a.bits() != 0
+}
+
+fn groups(group: Group) -> CollisionGroups {
+    CollisionGroups::new(group, filter(group))
+}
+
+pub fn character_groups() -> CollisionGroups {
+    groups(CHARACTER)
+}
+
+pub fn projectile_groups() -> CollisionGroups {
+    groups(PROJECTILE)
+}
+
+pub fn level_groups() -> CollisionGroups {
+    groups(LEVEL)
+}
+
+pub fn prop_groups() -> CollisionGroups {
+    groups(PROP)
+}
+
+pub fn sensor_groups() -> CollisionGroups {
+    groups(SENSOR)
+}
+
+/// Ray cast from the camera, hits the level and nothing in front of it.
+pub fn camera_ray_filter() -> QueryFilter<'static> {
+    QueryFilter::default().groups(groups(CAMERA_RAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Group; 6] = [CHARACTER, PROJECTILE, LEVEL, PROP, SENSOR, CAMERA_RAY];
+
+    #[test]
+    fn every_group_is_in_the_matrix() {
+        for group in ALL {
+            assert!(
+                INTERACTIONS.iter().any(|(listed, _)| *listed == group),
+                "{:?} is missing",
+                group
+            );
+        }
+    }
+
+    #[test]
+    fn matrix_is_symmetric() {
+        for a in ALL {
+            for b in ALL {
+                assert_eq!(
+                    filter(a).contains(b),
+                    filter(b).contains(a),
+                    "{:?} and {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn projectiles_only_hit_bodies() {
+        assert!(!interacts(PROJECTILE, SENSOR), "projectiles must not trigger sensors");
+        assert!(!interacts(PROJECTILE, PROJECTILE), "projectiles must not hit each other");
+        assert!(interacts(PROJECTILE, CHARACTER));
+        assert!(interacts(PROJECTILE, LEVEL));
+        assert!(interacts(PROJECTILE, PROP));
+    }
+
+    #[test]
+    fn sensors_only_touch_what_they_act_on() {
+        assert!(!interacts(SENSOR, LEVEL), "sensors must not touch the level");
+        assert!(interacts(CHARACTER, SENSOR), "characters must trigger sensors");
+        assert!(interacts(PROP, SENSOR), "props must be pulled by gravity zones");
+    }
+
+    #[test]
+    fn camera_rays_only_hit_the_level() {
+        assert_eq!(filter(CAMERA_RAY), LEVEL);
+        for group in ALL {
+            assert_eq!(interacts(CAMERA_RAY, group), group == LEVEL, "{:?}", group);
+        }
+        let groups = camera_ray_filter().groups.unwrap();
+        assert_eq!(groups.memberships, CAMERA_RAY);
+        assert_eq!(groups.filters, LEVEL);
+    }
+
+    #[test]
+    fn constructors_follow_the_matrix() {
+        for (groups, group) in [
+            (character_groups(), CHARACTER),
+            (projectile_groups(), PROJECTILE),
+            (level_groups(), LEVEL),
+            (prop_groups(), PROP),
+            (sensor_groups(), SENSOR),
+        ] {
+            assert_eq!(groups.memberships, group);
+            assert_eq!(groups.filters, filter(group));
+        }
+    }
+}
@@ -0,0 +1,3 @@
+#[cfg(feature = "dev")]
+pub mod debug;
+pub mod groups;
@@ -0,0 +1,705 @@
+//! Recording and playback of the messages the host sends to its clients.
+//!
+//! A replay file starts with [`REPLAY_MAGIC`] and a [`ReplayHeader`],
+//! followed by [`ReplayFrame`]s, every record is a little endian `u32` length and its bincode.
+//!
+//! Recording can be toggled during a session, a recording started late or continued in a new part
+//! (see [`MAX_REPLAY_PART_SIZE`]) begins with the level and the players so it plays back on its own.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bevy::app::{App, AppExit, FixedFirst, FixedUpdate, Last, Plugin, Update};
+use bevy::ecs::entity::Entity;
+use bevy::ecs::event::EventReader;
+use bevy::ecs::query::With;
+use bevy::ecs::schedule::{Condition, IntoSystemConfigs, OnEnter, OnExit, State};
+use bevy::ecs::system::{Commands, Query, Res, ResMut, Resource};
+use bevy::prelude::{in_state, resource_exists};
+use bevy_controls::contract::InputsContainer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::actor::character::{spawn_tied_camera, TiedCamera};
+use crate::core::{CoreAction, CurrentLevel};
+use crate::level::level_checksum;
+use crate::lobby::client::{OwnId, ServerMessageHandler};
+use crate::lobby::{
+    Lobby, LobbyState, PlayerData, PlayerId, ServerMessages, TransportData, PROTOCOL_ID,
+};
+use crate::world::{DayPhase, EnvironmentSettings, LevelPhysics, SimulationTick};
+
+/// First bytes of every replay file.
+pub const REPLAY_MAGIC: [u8; 4] = *b"URRP";
+
+/// Records larger than this are treated as a corrupted file.
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Capacity of the recorder buffer, frames hit the disk when it is full or on [`FLUSH_INTERVAL`].
+const RECORD_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Buffered frames are written out at least this often, so a crash loses little of the replay.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A replay file growing past this is closed and the recording goes on in the next part,
+/// `replay.urrp` continues in `replay.1.urrp`, `replay.2.urrp`...
+pub const MAX_REPLAY_PART_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Identifies the build that recorded a replay, replays of other builds are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub protocol_id: u64,
+    pub version: String,
+}
+
+impl ReplayHeader {
+    /// Header of replays recorded by this build.
+    pub fn current() -> Self {
+        Self {
+            protocol_id: PROTOCOL_ID,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// Channel the recorded payload was sent over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplayChannel {
+    /// A serialized [`ServerMessages`].
+    Reliable,
+    /// A serialized [`TransportData`] snapshot.
+    Unreliable,
+}
+
+/// A single message sent by the host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// Fixed tick the message was sent on, counted from the start of the recording.
+    pub tick: u64,
+    /// Milliseconds since the start of the recording.
+    pub millis: u64,
+    pub channel: ReplayChannel,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Decode(bincode::Error),
+    /// The file does not start with [`REPLAY_MAGIC`] or a record is malformed.
+    NotAReplay,
+    /// The replay was recorded by another build.
+    Incompatible(ReplayHeader),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "replay io error: {err}"),
+            ReplayError::Decode(err) => write!(f, "replay decode error: {err}"),
+            ReplayError::NotAReplay => write!(f, "not a replay file"),
+            ReplayError::Incompatible(header) => {
+                let current = ReplayHeader::current();
+                write!(
+                    f,
+                    "replay recorded by v{} (protocol {}) cannot be played by v{} (protocol {})",
+                    header.version, header.protocol_id, current.version, current.protocol_id
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for ReplayError {
+    fn from(err: bincode::Error) -> Self {
+        ReplayError::Decode(err)
+    }
+}
+
+/// Writes a record, returns the number of bytes written.
+fn write_record<W: Write, T: Serialize>(writer: &mut W, value: &T) -> Result<u64, ReplayError> {
+    let bytes = bincode::serialize(value)?;
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(&bytes)?;
+    Ok(4 + bytes.len() as u64)
+}
+
+/// Reads the next record, `None` at the end of the file.
+fn read_record<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>, ReplayError> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_RECORD_SIZE {
+        return Err(ReplayError::NotAReplay);
+    }
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(Some(bincode::deserialize(&bytes)?))
+}
+
+pub struct ReplayWriter<W: Write> {
+    writer: W,
+    written: u64,
+}
+
+impl<W: Write> ReplayWriter<W> {
+    /// Writes the replay header of this build.
+    pub fn new(mut writer: W) -> Result<Self, ReplayError> {
+        writer.write_all(&REPLAY_MAGIC)?;
+        let header_size = write_record(&mut writer, &ReplayHeader::current())?;
+        Ok(Self {
+            writer,
+            written: REPLAY_MAGIC.len() as u64 + header_size,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &ReplayFrame) -> Result<(), ReplayError> {
+        self.written += write_record(&mut self.writer, frame)?;
+        Ok(())
+    }
+
+    /// Bytes written so far, including the header.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct ReplayReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> ReplayReader<R> {
+    /// Checks the replay header, fails if the replay was recorded by another build.
+    pub fn new(mut reader: R) -> Result<Self, ReplayError> {
+        let mut magic = [0u8; 4];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| ReplayError::NotAReplay)?;
+        if magic != REPLAY_MAGIC {
+            return Err(ReplayError::NotAReplay);
+        }
+
+        let header: ReplayHeader = read_record(&mut reader)?.ok_or(ReplayError::NotAReplay)?;
+        if header != ReplayHeader::current() {
+            return Err(ReplayError::Incompatible(header));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Reads the next frame, `None` at the end of the replay.
+    pub fn next_frame(&mut self) -> Result<Option<ReplayFrame>, ReplayError> {
+        read_record(&mut self.reader)
+    }
+}
+
+/// Makes the host record everything it broadcasts into the given file.
+#[derive(Debug, Clone, Resource)]
+pub struct RecordReplay(pub PathBuf);
+
+/// Replay being recorded by the host or a client.
+///
+/// Frames are buffered and flushed every [`FLUSH_INTERVAL`] and when the recording stops.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    writer: ReplayWriter<BufWriter<File>>,
+    path: PathBuf,
+    /// Part being written, `0` is `path` itself.
+    part: u32,
+    start_tick: u64,
+    tick: u64,
+    started: Instant,
+    last_flush: Instant,
+}
+
+impl ReplayRecorder {
+    pub fn create(path: &Path, start_tick: u64) -> Result<Self, ReplayError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let now = Instant::now();
+        Ok(Self {
+            writer: open_writer(path)?,
+            path: path.to_path_buf(),
+            part: 0,
+            start_tick,
+            tick: 0,
+            started: now,
+            last_flush: now,
+        })
+    }
+
+    pub fn record(&mut self, channel: ReplayChannel, payload: &[u8]) {
+        let frame = ReplayFrame {
+            tick: self.tick,
+            millis: self.started.elapsed().as_millis() as u64,
+            channel,
+            payload: payload.to_vec(),
+        };
+        if let Err(err) = self.writer.write_frame(&frame) {
+            log::error!("Failed to record replay frame: {}", err);
+        }
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.last_flush = Instant::now();
+            if let Err(err) = self.writer.flush() {
+                log::error!("Failed to flush replay: {}", err);
+            }
+        }
+    }
+
+    /// The current part reached [`MAX_REPLAY_PART_SIZE`] and should be rotated.
+    pub fn is_full(&self) -> bool {
+        self.writer.written() >= MAX_REPLAY_PART_SIZE
+    }
+
+    /// Closes the current part and continues in the next one.
+    ///
+    /// The caller records the session prelude right after, the new part does not depend on the previous ones.
+    fn rotate(&mut self) -> Result<(), ReplayError> {
+        self.writer.flush()?;
+        self.part += 1;
+        let path = part_path(&self.path, self.part);
+        self.writer = open_writer(&path)?;
+        log::info!("Replay continues in {:?}", path);
+        Ok(())
+    }
+}
+
+fn open_writer(path: &Path) -> Result<ReplayWriter<BufWriter<File>>, ReplayError> {
+    ReplayWriter::new(BufWriter::with_capacity(RECORD_BUFFER_SIZE, File::create(path)?))
+}
+
+/// `replay.urrp` for the part `0`, `replay.<part>.urrp` for the next ones.
+fn part_path(path: &Path, part: u32) -> PathBuf {
+    if part == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, part, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, part),
+    };
+    path.with_file_name(name)
+}
+
+/// `replays/replay-<millis>.urrp` next to the executable, used when recording is toggled without [`RecordReplay`].
+fn default_replay_path() -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+        .join("replays")
+        .join(format!("replay-{}.urrp", timestamp))
+}
+
+/// Messages bringing a fresh playback to the current state of the session:
+/// the level with its physics and environment and every player with its color, username and team.
+fn session_prelude(
+    lobby: &Lobby,
+    me: Option<PlayerId>,
+    level: &CurrentLevel,
+    physics: &LevelPhysics,
+    environment: &EnvironmentSettings,
+    day_phase: &DayPhase,
+) -> Vec<ServerMessages> {
+    let mut messages = vec![ServerMessages::ChangeMap {
+        level: level.0.clone(),
+        checksum: level_checksum(&level.0),
+        physics: *physics,
+        environment: environment.clone(),
+        phase: **day_phase,
+    }];
+    if let Some(me) = me {
+        messages.push(ServerMessages::PlayerConnected {
+            id: me,
+            color: lobby.me.color,
+            username: lobby.me.username.clone(),
+            team: lobby.me.team,
+        });
+    }
+    for (player_id, player_data) in lobby.players.iter() {
+        messages.push(ServerMessages::PlayerConnected {
+            id: *player_id,
+            color: player_data.color,
+            username: player_data.username.clone(),
+            team: player_data.team,
+        });
+    }
+    messages
+}
+
+fn record_prelude(
+    recorder: &mut ReplayRecorder,
+    lobby: &Lobby,
+    me: Option<PlayerId>,
+    level: &CurrentLevel,
+    physics: &LevelPhysics,
+    environment: &EnvironmentSettings,
+    day_phase: &DayPhase,
+) {
+    for message in session_prelude(lobby, me, level, physics, environment, day_phase) {
+        match bincode::serialize(&message) {
+            Ok(payload) => recorder.record(ReplayChannel::Reliable, &payload),
+            Err(err) => log::error!("Failed to record replay prelude: {}", err),
+        }
+    }
+}
+
+/// Player of this side of the session, `None` on a client not initialized by the server yet.
+fn own_player_id(lobby_state: &LobbyState, own_id: Option<&OwnId>) -> Option<PlayerId> {
+    match lobby_state {
+        LobbyState::Client => own_id.and_then(OwnId::player_id),
+        _ => Some(PlayerId::HostOrSingle),
+    }
+}
+
+/// Records host or client traffic while [`RecordReplay`] is present,
+/// [`CoreAction::RecordReplay`] starts and stops the recording during a session.
+pub struct ReplayRecordPlugins;
+
+impl Plugin for ReplayRecordPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(LobbyState::Host),
+            start_recording.run_if(resource_exists::<RecordReplay>),
+        )
+        .add_systems(
+            OnEnter(LobbyState::Client),
+            start_recording.run_if(resource_exists::<RecordReplay>),
+        )
+        .add_systems(
+            FixedFirst,
+            update_recorder_tick.run_if(resource_exists::<ReplayRecorder>),
+        )
+        .add_systems(
+            Update,
+            toggle_recording.run_if(
+                resource_exists::<Lobby>
+                    .and_then(in_state(LobbyState::Host).or_else(in_state(LobbyState::Client))),
+            ),
+        )
+        .add_systems(
+            Last,
+            rotate_recording.run_if(
+                resource_exists::<ReplayRecorder>
+                    .and_then(|recorder: Res<ReplayRecorder>| recorder.is_full()),
+            ),
+        )
+        .add_systems(OnExit(LobbyState::Host), stop_recording)
+        .add_systems(OnExit(LobbyState::Client), stop_recording)
+        .add_systems(
+            Last,
+            stop_recording.run_if(
+                resource_exists::<ReplayRecorder>
+                    .and_then(|exit: EventReader<AppExit>| !exit.is_empty()),
+            ),
+        );
+    }
+}
+
+fn start_recording(mut commands: Commands, record: Res<RecordReplay>, tick: Res<SimulationTick>) {
+    match ReplayRecorder::create(&record.0, **tick) {
+        Ok(recorder) => {
+            log::info!("Recording replay to {:?}", record.0);
+            commands.insert_resource(recorder);
+        }
+        Err(err) => log::error!("Failed to start replay recording {:?}: {}", record.0, err),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn toggle_recording(
+    mut commands: Commands,
+    lobby: Res<Lobby>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    record: Option<Res<RecordReplay>>,
+    tick: Res<SimulationTick>,
+    lobby_state: Res<State<LobbyState>>,
+    own_id: Option<Res<OwnId>>,
+    current_level: Res<CurrentLevel>,
+    level_physics: Res<LevelPhysics>,
+    environment: Res<EnvironmentSettings>,
+    day_phase: Res<DayPhase>,
+) {
+    let pressed = lobby
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::RecordReplay))
+        .unwrap_or(false);
+    if !pressed {
+        return;
+    }
+
+    if recorder.is_some() {
+        log::info!("Replay recording stopped");
+        stop_recording(commands, recorder);
+        return;
+    }
+
+    // a file given on the command line is overwritten by every recording of the run
+    let path = record.map_or_else(default_replay_path, |record| record.0.clone());
+    match ReplayRecorder::create(&path, **tick) {
+        Ok(mut recorder) => {
+            log::info!("Recording replay to {:?}", path);
+            let me = own_player_id(lobby_state.get(), own_id.as_deref());
+            record_prelude(
+                &mut recorder,
+                &lobby,
+                me,
+                &current_level,
+                &level_physics,
+                &environment,
+                &day_phase,
+            );
+            commands.insert_resource(recorder);
+        }
+        Err(err) => log::error!("Failed to start replay recording {:?}: {}", path, err),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rotate_recording(
+    mut commands: Commands,
+    mut recorder: ResMut<ReplayRecorder>,
+    lobby: Option<Res<Lobby>>,
+    lobby_state: Res<State<LobbyState>>,
+    own_id: Option<Res<OwnId>>,
+    current_level: Res<CurrentLevel>,
+    level_physics: Res<LevelPhysics>,
+    environment: Res<EnvironmentSettings>,
+    day_phase: Res<DayPhase>,
+) {
+    if let Err(err) = recorder.rotate() {
+        log::error!("Failed to continue the replay in a new part, recording stopped: {}", err);
+        commands.remove_resource::<ReplayRecorder>();
+        return;
+    }
+    if let Some(lobby) = lobby {
+        let me = own_player_id(lobby_state.get(), own_id.as_deref());
+        record_prelude(
+            &mut recorder,
+            &lobby,
+            me,
+            &current_level,
+            &level_physics,
+            &environment,
+            &day_phase,
+        );
+    }
+}
+
+fn update_recorder_tick(mut recorder: ResMut<ReplayRecorder>, tick: Res<SimulationTick>) {
+    recorder.tick = tick.saturating_sub(recorder.start_tick);
+}
+
+fn stop_recording(mut commands: Commands, recorder: Option<ResMut<ReplayRecorder>>) {
+    if let Some(mut recorder) = recorder {
+        if let Err(err) = recorder.writer.flush() {
+            log::error!("Failed to flush replay: {}", err);
+        }
+        commands.remove_resource::<ReplayRecorder>();
+    }
+}
+
+/// Replay being played back, frames are applied on the fixed tick they were recorded on.
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    reader: ReplayReader<BufReader<File>>,
+    next: Option<ReplayFrame>,
+    tick: u64,
+    finished: bool,
+}
+
+impl ReplayPlayback {
+    pub fn open(path: &Path) -> Result<Self, ReplayError> {
+        let mut reader = ReplayReader::new(BufReader::new(File::open(path)?))?;
+        let next = reader.next_frame()?;
+        // later parts of a rotated recording do not start at the tick 0
+        let tick = next.as_ref().map_or(0, |frame| frame.tick);
+        Ok(Self {
+            reader,
+            next,
+            tick,
+            finished: false,
+        })
+    }
+
+    /// Pops the frames recorded up to the current tick.
+    fn due_frames(&mut self) -> Vec<ReplayFrame> {
+        let mut frames = Vec::new();
+        while self
+            .next
+            .as_ref()
+            .is_some_and(|frame| frame.tick <= self.tick)
+        {
+            frames.extend(self.next.take());
+            match self.reader.next_frame() {
+                Ok(next) => self.next = next,
+                Err(err) => log::error!("Replay is truncated: {}", err),
+            }
+        }
+        frames
+    }
+}
+
+/// Plays the [`ReplayPlayback`] resource back through the client message handling, without networking.
+pub struct ReplayPlaybackPlugins;
+
+impl Plugin for ReplayPlaybackPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Lobby>()
+            .init_resource::<OwnId>()
+            .add_systems(FixedUpdate, playback)
+            .add_systems(Update, follow_first_player);
+    }
+}
+
+fn playback(mut replay: ResMut<ReplayPlayback>, mut handler: ServerMessageHandler) {
+    if replay.finished {
+        return;
+    }
+
+    for frame in replay.due_frames() {
+        let handled = match frame.channel {
+            ReplayChannel::Reliable => bincode::deserialize::<ServerMessages>(&frame.payload)
+                .map(|message| handler.handle_message(message)),
+            ReplayChannel::Unreliable => bincode::deserialize::<TransportData>(&frame.payload)
+                .map(|data| {
+                    handler.apply_snapshot(&data);
+                    true
+                }),
+        };
+        match handled {
+            Ok(true) => {}
+            Ok(false) => replay.next = None,
+            Err(err) => log::error!("Skipping corrupted replay frame {}: {}", frame.tick, err),
+        }
+    }
+
+    if replay.next.is_none() {
+        log::info!("Replay finished");
+        replay.finished = true;
+    }
+    replay.tick += 1;
+}
+
+/// There is no own character in a replay, the camera follows the first recorded player.
+fn follow_first_player(
+    mut commands: Commands,
+    lobby: Res<Lobby>,
+    camera_query: Query<Entity, With<TiedCamera>>,
+) {
+    if !camera_query.is_empty() {
+        return;
+    }
+    if let Some(entity) = lobby.players.values().find_map(PlayerData::entity) {
+        commands.spawn_tied_camera(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bevy::math::Vec3;
+
+    use super::*;
+    use crate::lobby::PlayerTransportData;
+
+    fn snapshot(x: f32) -> TransportData {
+        let mut data = TransportData::default();
+        data.players.insert(
+            PlayerId::HostOrSingle,
+            PlayerTransportData {
+                position: Vec3::new(x, 0., 0.),
+                ..Default::default()
+            },
+        );
+        data
+    }
+
+    #[test]
+    fn recorded_ticks_play_back_in_order() {
+        let path = env::temp_dir().join(format!("urmom-replay-{}.urrp", std::process::id()));
+        let mut recorder = ReplayRecorder::create(&path, 10).unwrap();
+        for tick in 0..3 {
+            recorder.tick = tick;
+            let payload = bincode::serialize(&snapshot(tick as f32)).unwrap();
+            recorder.record(ReplayChannel::Unreliable, &payload);
+        }
+        recorder.writer.flush().unwrap();
+        drop(recorder);
+
+        let mut playback = ReplayPlayback::open(&path).unwrap();
+        for tick in 0..3 {
+            let frames = playback.due_frames();
+            assert_eq!(frames.len(), 1);
+            assert_eq!(frames[0].tick, tick);
+            assert_eq!(frames[0].channel, ReplayChannel::Unreliable);
+            let data: TransportData = bincode::deserialize(&frames[0].payload).unwrap();
+            assert_eq!(
+                data.players[&PlayerId::HostOrSingle].position,
+                Vec3::new(tick as f32, 0., 0.)
+            );
+            playback.tick += 1;
+        }
+        assert!(playback.next.is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replay_of_another_build_is_rejected() {
+        let header = ReplayHeader {
+            version: "0.0.0-other".to_string(),
+            ..ReplayHeader::current()
+        };
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        write_record(&mut bytes, &header).unwrap();
+
+        match ReplayReader::new(Cursor::new(bytes)) {
+            Err(ReplayError::Incompatible(read)) => assert_eq!(read, header),
+            Err(err) => panic!("unexpected error {err}"),
+            Ok(_) => panic!("replay of another build was accepted"),
+        }
+    }
+
+    #[test]
+    fn not_a_replay_is_rejected() {
+        let result = ReplayReader::new(Cursor::new(b"RIFF....".to_vec()));
+        assert!(matches!(result, Err(ReplayError::NotAReplay)));
+    }
+
+    #[test]
+    fn parts_are_numbered_before_the_extension() {
+        let path = Path::new("replays/replay.urrp");
+        assert_eq!(part_path(path, 0), path);
+        assert_eq!(part_path(path, 2), Path::new("replays/replay.2.urrp"));
+    }
+}
@@ -0,0 +1,461 @@
+//! Saving the dynamic state of a host or single session to a file and restoring it.
+//!
+//! A save stores the level, character and scene actor transforms, the health of the characters
+//! and the scores of the session as RON.
+//! Loading a save made on another level changes the level first,
+//! the transforms are applied once the level is loaded ([`MapLoaderState::Yes`]).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::app::{App, Plugin, Update};
+use bevy::ecs::event::{Event, EventReader, EventWriter};
+use bevy::ecs::query::Without;
+use bevy::ecs::schedule::{Condition, IntoSystemConfigs};
+use bevy::ecs::entity::Entity;
+use bevy::ecs::system::{Commands, Query, Res, ResMut, Resource};
+use bevy::prelude::OnEnter;
+use bevy::prelude::{in_state, resource_exists};
+use bevy::transform::components::Transform;
+use serde::{Deserialize, Serialize};
+
+use crate::component::{Health, HealthChangedEvent, Teleported};
+use crate::core::{CoreGameState, CurrentLevel, KnownLevel, LoadLevelEvent};
+use crate::level::level_exists;
+use crate::lobby::{
+    ActorTransportData, ChangeMapLobbyEvent, Character, LevelCode, LobbyState, MapLoaderState,
+    PlayerId,
+};
+use crate::settings::settings_dir;
+use crate::ui::SessionScores;
+use crate::world::LinkId;
+
+/// Version of the saves written by this build.
+pub const SAVE_VERSION: u32 = 1;
+/// File of the single player save, next to the settings
+const SINGLE_SAVE_FILE: &str = "single.ron";
+
+/// Path of the save the single player game is saved to and continued from.
+pub fn single_save_path() -> PathBuf {
+    settings_dir().join(SINGLE_SAVE_FILE)
+}
+
+/// Dynamic state of a session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldSave {
+    pub version: u32,
+    pub level: LevelCode,
+    #[serde(default)]
+    pub players: HashMap<PlayerId, ActorTransportData>,
+    /// Scene actors (e.g. props), projectiles are not saved.
+    #[serde(default)]
+    pub actors: HashMap<LinkId, ActorTransportData>,
+    #[serde(default)]
+    pub health: HashMap<PlayerId, Health>,
+    #[serde(default)]
+    pub scores: SessionScores,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Encode(ron::Error),
+    Decode(ron::error::SpannedError),
+    /// The save was written by a newer build.
+    Incompatible(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "io error: {}", err),
+            SaveError::Encode(err) => write!(f, "failed to encode save: {}", err),
+            SaveError::Decode(err) => write!(f, "failed to decode save: {}", err),
+            SaveError::Incompatible(version) => write!(
+                f,
+                "save version {} is newer than supported {}",
+                version, SAVE_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl WorldSave {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(SaveError::Encode)
+    }
+
+    pub fn from_ron(content: &str) -> Result<Self, SaveError> {
+        let save: WorldSave = ron::from_str(content).map_err(SaveError::Decode)?;
+        if save.version > SAVE_VERSION {
+            return Err(SaveError::Incompatible(save.version));
+        }
+        Ok(save)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        Self::from_ron(&fs::read_to_string(path)?)
+    }
+
+    /// The save moved to the hub if its level cannot be loaded anymore,
+    /// the transforms belong to the missing level and are dropped, the scores are kept.
+    pub fn or_hub(self) -> Self {
+        if level_exists(&self.level) {
+            return self;
+        }
+        log::warn!("Saved level {:?} does not exist anymore, loading the hub", self.level);
+        Self {
+            level: LevelCode::Known(KnownLevel::Hub),
+            players: HashMap::new(),
+            actors: HashMap::new(),
+            health: HashMap::new(),
+            ..self
+        }
+    }
+}
+
+/// Writes the current session to the file.
+#[derive(Debug, Event)]
+pub struct SaveWorldEvent(pub PathBuf);
+
+/// Restores a session from the file.
+#[derive(Debug, Event)]
+pub struct LoadWorldEvent(pub PathBuf);
+
+/// A read save waiting for its level to be loaded.
+#[derive(Debug, Resource)]
+struct PendingWorldLoad {
+    save: WorldSave,
+    /// The level is being changed, the old one is still loaded until [`MapLoaderState::No`].
+    level_changing: bool,
+}
+
+pub struct WorldSavePlugins;
+
+impl Plugin for WorldSavePlugins {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveWorldEvent>()
+            .add_event::<LoadWorldEvent>()
+            .add_systems(
+                Update,
+                (
+                    (save_world, load_world).run_if(in_state(MapLoaderState::Yes)),
+                    apply_world_load.run_if(
+                        resource_exists::<PendingWorldLoad>
+                            .and_then(in_state(CoreGameState::InGame))
+                            .and_then(in_state(MapLoaderState::Yes)),
+                    ),
+                )
+                    .chain()
+                    .run_if(in_state(LobbyState::Single).or_else(in_state(LobbyState::Host))),
+            )
+            .add_systems(
+                OnEnter(MapLoaderState::No),
+                level_unloaded.run_if(resource_exists::<PendingWorldLoad>),
+            );
+
+        #[cfg(feature = "dev")]
+        app.add_systems(
+            Update,
+            quick_save_keys
+                .run_if(in_state(LobbyState::Single).or_else(in_state(LobbyState::Host))),
+        );
+    }
+}
+
+fn save_world(
+    mut save_event: EventReader<SaveWorldEvent>,
+    current_level: Res<CurrentLevel>,
+    scores: Option<Res<SessionScores>>,
+    character_query: Query<(&Character, &Transform, Option<&Health>)>,
+    actor_query: Query<(&LinkId, &Transform)>,
+) {
+    for SaveWorldEvent(path) in save_event.read() {
+        let save = WorldSave {
+            version: SAVE_VERSION,
+            level: current_level.0.clone(),
+            players: character_query
+                .iter()
+                .map(|(character, transform, _)| (character.id, transport_data(transform)))
+                .collect(),
+            actors: actor_query
+                .iter()
+                .filter(|(link_id, _)| matches!(link_id, LinkId::Scene(_)))
+                .map(|(link_id, transform)| (link_id.clone(), transport_data(transform)))
+                .collect(),
+            health: character_query
+                .iter()
+                .filter_map(|(character, _, health)| Some((character.id, *health?)))
+                .collect(),
+            scores: scores.map(|scores| scores.clone()).unwrap_or_default(),
+        };
+
+        match save.write(path) {
+            Ok(()) => log::info!("World saved to {:?}", path),
+            Err(err) => log::error!("Failed to save world to {:?}: {}", path, err),
+        }
+    }
+}
+
+fn load_world(
+    mut commands: Commands,
+    mut load_event: EventReader<LoadWorldEvent>,
+    current_level: Res<CurrentLevel>,
+    mut change_map_event: EventWriter<ChangeMapLobbyEvent>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+) {
+    for LoadWorldEvent(path) in load_event.read() {
+        let save = match WorldSave::read(path) {
+            Ok(save) => save,
+            Err(err) => {
+                log::error!("Failed to load world from {:?}: {}", path, err);
+                continue;
+            }
+        };
+
+        let level_changing = save.level != current_level.0;
+        if level_changing {
+            log::info!("World save is on {:?}, changing the level first", save.level);
+            change_map_event.send(ChangeMapLobbyEvent(save.level.clone()));
+            load_level_event.send(LoadLevelEvent::new(save.level.clone()));
+        }
+        commands.insert_resource(PendingWorldLoad {
+            save,
+            level_changing,
+        });
+    }
+}
+
+fn level_unloaded(mut pending: ResMut<PendingWorldLoad>) {
+    pending.level_changing = false;
+}
+
+fn apply_world_load(
+    mut commands: Commands,
+    pending: Res<PendingWorldLoad>,
+    scores: Option<ResMut<SessionScores>>,
+    mut health_changed_event: EventWriter<HealthChangedEvent>,
+    mut character_query: Query<(Entity, &Character, &mut Transform, Option<&mut Health>)>,
+    mut actor_query: Query<(Entity, &LinkId, &mut Transform), Without<Character>>,
+) {
+    if pending.level_changing {
+        return;
+    }
+    let save = &pending.save;
+
+    for (entity, character, mut transform, health) in character_query.iter_mut() {
+        let id = character.id;
+        if let (Some(mut health), Some(saved)) = (health, save.health.get(&id)) {
+            *health = *saved;
+            health_changed_event.send(HealthChangedEvent {
+                id,
+                health: *saved,
+                attacker: None,
+            });
+        }
+        let Some(data) = save.players.get(&id) else {
+            continue;
+        };
+        transform.translation = data.position;
+        transform.rotation = data.rotation;
+        // the load moved it, not its own movement
+        commands.entity(entity).insert(Teleported);
+    }
+    for player_id in save.players.keys() {
+        if !character_query.iter().any(|(_, character, ..)| character.id == *player_id) {
+            log::warn!("Saved player {:?} is not in the lobby, skipped", player_id);
+        }
+    }
+
+    for (link_id, data) in save.actors.iter() {
+        let Some((entity, _, mut transform)) =
+            actor_query.iter_mut().find(|(_, id, _)| *id == link_id)
+        else {
+            log::warn!("Saved actor {} does not exist in the level, skipped", link_id);
+            continue;
+        };
+        transform.translation = data.position;
+        transform.rotation = data.rotation;
+        commands.entity(entity).insert(Teleported);
+    }
+
+    if let Some(mut scores) = scores {
+        *scores = save.scores.clone();
+    }
+
+    log::info!("World restored on {:?}", save.level);
+    commands.remove_resource::<PendingWorldLoad>();
+}
+
+fn transport_data(transform: &Transform) -> ActorTransportData {
+    ActorTransportData {
+        position: transform.translation,
+        rotation: transform.rotation,
+    }
+}
+
+/// F5 quick-saves and F9 quick-loads next to the settings.
+#[cfg(feature = "dev")]
+fn quick_save_keys(
+    keyboard: Res<bevy::input::ButtonInput<bevy::input::keyboard::KeyCode>>,
+    mut save_event: EventWriter<SaveWorldEvent>,
+    mut load_event: EventWriter<LoadWorldEvent>,
+) {
+    use bevy::input::keyboard::KeyCode;
+
+    let path = crate::settings::settings_dir().join("quicksave.ron");
+    if keyboard.just_pressed(KeyCode::F5) {
+        save_event.send(SaveWorldEvent(path));
+    } else if keyboard.just_pressed(KeyCode::F9) {
+        load_event.send(LoadWorldEvent(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::ecs::world::World;
+    use bevy::math::{Quat, Vec3};
+    use renet::ClientId;
+
+    use super::*;
+
+    fn transport(position: Vec3) -> ActorTransportData {
+        ActorTransportData {
+            position,
+            rotation: Quat::from_rotation_y(1.),
+        }
+    }
+
+    fn save(level: LevelCode) -> WorldSave {
+        WorldSave {
+            version: SAVE_VERSION,
+            level,
+            players: HashMap::from([
+                (PlayerId::HostOrSingle, transport(Vec3::X)),
+                (PlayerId::Client(ClientId::from_raw(7)), transport(Vec3::Y)),
+            ]),
+            actors: HashMap::from([(LinkId::Scene("Crate".to_string()), transport(Vec3::Z))]),
+            health: HashMap::from([(
+                PlayerId::Client(ClientId::from_raw(7)),
+                Health {
+                    current: 40.,
+                    max: 100.,
+                },
+            )]),
+            scores: SessionScores {
+                kills: HashMap::from([(PlayerId::HostOrSingle, 3)]),
+                deaths: HashMap::from([(PlayerId::Client(ClientId::from_raw(7)), 3)]),
+            },
+        }
+    }
+
+    #[test]
+    fn save_round_trips_through_ron() {
+        let saved = save(LevelCode::Known(KnownLevel::Hub));
+        let loaded = WorldSave::from_ron(&saved.to_ron().unwrap()).unwrap();
+
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert_eq!(loaded.level, saved.level);
+        assert_eq!(loaded.players.len(), 2);
+        let client = &loaded.players[&PlayerId::Client(ClientId::from_raw(7))];
+        assert_eq!(client.position, Vec3::Y);
+        assert!(client.rotation.abs_diff_eq(Quat::from_rotation_y(1.), 1e-6));
+        assert_eq!(
+            loaded.actors[&LinkId::Scene("Crate".to_string())].position,
+            Vec3::Z
+        );
+        assert_eq!(loaded.health, saved.health);
+        assert_eq!(loaded.scores, saved.scores);
+    }
+
+    #[test]
+    fn save_of_a_newer_build_is_rejected() {
+        let mut saved = save(LevelCode::Known(KnownLevel::Hub));
+        saved.version = SAVE_VERSION + 1;
+        assert!(matches!(
+            WorldSave::from_ron(&saved.to_ron().unwrap()),
+            Err(SaveError::Incompatible(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn missing_transforms_default_to_empty() {
+        let loaded = WorldSave::from_ron("(version: 1, level: Known(Hub))").unwrap();
+        assert!(loaded.players.is_empty());
+        assert!(loaded.actors.is_empty());
+        assert!(loaded.health.is_empty());
+        assert_eq!(loaded.scores, SessionScores::default());
+    }
+
+    #[test]
+    fn save_of_a_missing_level_moves_to_the_hub() {
+        let saved = save(LevelCode::Path("no/such/level.glb".to_string())).or_hub();
+        assert_eq!(saved.level, LevelCode::Known(KnownLevel::Hub));
+        assert!(saved.players.is_empty());
+        assert!(saved.actors.is_empty());
+        assert!(saved.health.is_empty());
+        assert_eq!(saved.scores.kills[&PlayerId::HostOrSingle], 3);
+
+        let hub = save(LevelCode::Known(KnownLevel::Hub)).or_hub();
+        assert_eq!(hub.players.len(), 2);
+    }
+
+    #[test]
+    fn load_restores_the_world_and_marks_it_teleported() {
+        let client = PlayerId::Client(ClientId::from_raw(7));
+        let mut world = World::new();
+        world.init_resource::<SessionScores>();
+        world.init_resource::<Events<HealthChangedEvent>>();
+        world.insert_resource(PendingWorldLoad {
+            save: save(LevelCode::Known(KnownLevel::Hub)),
+            level_changing: false,
+        });
+        let host = world
+            .spawn((
+                Character {
+                    id: PlayerId::HostOrSingle,
+                },
+                Transform::default(),
+                Health::default(),
+            ))
+            .id();
+        let character = world
+            .spawn((Character { id: client }, Transform::default(), Health::default()))
+            .id();
+        let actor = world
+            .spawn((LinkId::Scene("Crate".to_string()), Transform::default()))
+            .id();
+
+        world.run_system_once(apply_world_load);
+
+        for (entity, position) in [(host, Vec3::X), (character, Vec3::Y), (actor, Vec3::Z)] {
+            let transform = world.get::<Transform>(entity).unwrap();
+            assert_eq!(transform.translation, position);
+            assert!(transform.rotation.abs_diff_eq(Quat::from_rotation_y(1.), 1e-6));
+            assert!(world.get::<Teleported>(entity).is_some(), "{:?}", entity);
+        }
+        assert_eq!(world.get::<Health>(character).unwrap().current, 40.);
+        assert_eq!(world.get::<Health>(host).unwrap().current, 100.);
+        assert_eq!(world.resource::<Events<HealthChangedEvent>>().len(), 1);
+        assert_eq!(world.resource::<SessionScores>().kills[&PlayerId::HostOrSingle], 3);
+        assert!(world.get_resource::<PendingWorldLoad>().is_none());
+    }
+
+}
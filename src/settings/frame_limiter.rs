@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::{App, Last, Plugin},
+    ecs::{
+        schedule::IntoSystemConfigs,
+        system::{Local, Res},
+    },
+    prelude::resource_exists,
+};
+
+use super::Settings;
+
+/// Left to spin instead of sleeping, sleep is not precise enough to hit the frame deadline
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+/// Caps the frame rate to [`Settings::fps_limit`].
+///
+/// Only the render pacing is affected, physics runs in the fixed schedule and keeps its step.
+pub struct FrameLimiterPlugins;
+
+impl Plugin for FrameLimiterPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Last, limit_frame_rate.run_if(resource_exists::<Settings>));
+    }
+}
+
+fn limit_frame_rate(settings: Res<Settings>, mut frame_start: Local<Option<Instant>>) {
+    let Some(fps_limit) = settings.fps_limit.filter(|fps| *fps > 0) else {
+        *frame_start = None;
+        return;
+    };
+
+    let frame_time = Duration::from_secs_f64(1. / fps_limit as f64);
+    if let Some(start) = *frame_start {
+        let deadline = start + frame_time;
+        if let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            if remaining > SPIN_MARGIN {
+                std::thread::sleep(remaining - SPIN_MARGIN);
+            }
+            while Instant::now() < deadline {
+                std::hint::spin_loop();
+            }
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
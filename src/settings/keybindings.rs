@@ -0,0 +1,195 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bevy::{
+    ecs::{
+        event::Event,
+        system::{Res, ResMut, Resource},
+    },
+    input::{
+        gamepad::{GamepadButton, GamepadButtonType},
+        keyboard::KeyCode,
+        mouse::MouseButton,
+        ButtonInput,
+    },
+    log::{error, warn},
+    prelude::Deref,
+};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::core::CoreAction;
+
+use super::settings_dir;
+
+/// A physical input an action can be bound to
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoundInput {
+    Keyboard(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl fmt::Display for BoundInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoundInput::Keyboard(key) => write!(f, "{:?}", key),
+            BoundInput::Mouse(button) => write!(f, "Mouse {:?}", button),
+            BoundInput::Gamepad(button) => write!(f, "Pad {:?}", button),
+        }
+    }
+}
+
+/// Input of every [`CoreAction`], edited by the controls window
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Resource)]
+pub struct KeyBindings(pub HashMap<CoreAction, BoundInput>);
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self(HashMap::from([
+            (CoreAction::InGameMenu, BoundInput::Keyboard(KeyCode::Escape)),
+            (CoreAction::QuickChat, BoundInput::Keyboard(KeyCode::KeyV)),
+            (CoreAction::Kick, BoundInput::Keyboard(KeyCode::KeyF)),
+            (CoreAction::Screenshot, BoundInput::Keyboard(KeyCode::F12)),
+            (CoreAction::RecordReplay, BoundInput::Keyboard(KeyCode::F10)),
+            (CoreAction::MoveForward, BoundInput::Keyboard(KeyCode::KeyW)),
+            (CoreAction::MoveBack, BoundInput::Keyboard(KeyCode::KeyS)),
+            (CoreAction::MoveLeft, BoundInput::Keyboard(KeyCode::KeyA)),
+            (CoreAction::MoveRight, BoundInput::Keyboard(KeyCode::KeyD)),
+            (CoreAction::Sprint, BoundInput::Keyboard(KeyCode::ShiftLeft)),
+            (CoreAction::FreeCamera, BoundInput::Keyboard(KeyCode::KeyC)),
+            (CoreAction::NetworkStats, BoundInput::Keyboard(KeyCode::F3)),
+            (CoreAction::Jump, BoundInput::Keyboard(KeyCode::Space)),
+            (CoreAction::Fire, BoundInput::Mouse(MouseButton::Left)),
+            (CoreAction::Scoreboard, BoundInput::Keyboard(KeyCode::Tab)),
+            (CoreAction::CameraMode, BoundInput::Keyboard(KeyCode::F5)),
+            (CoreAction::Quit, BoundInput::Keyboard(KeyCode::F4)),
+        ]))
+    }
+}
+
+impl KeyBindings {
+    /// Input of `action`, the default one if the file did not mention it
+    pub fn get(&self, action: CoreAction) -> Option<BoundInput> {
+        self.0
+            .get(&action)
+            .copied()
+            .or_else(|| KeyBindings::default().0.get(&action).copied())
+    }
+
+    /// Actions bound to the same input as another action
+    pub fn conflicts(&self) -> HashSet<CoreAction> {
+        let mut by_input: HashMap<BoundInput, Vec<CoreAction>> = HashMap::new();
+        for action in CoreAction::iter() {
+            if let Some(input) = self.get(action) {
+                by_input.entry(input).or_default().push(action);
+            }
+        }
+        by_input
+            .into_values()
+            .filter(|actions| actions.len() > 1)
+            .flatten()
+            .collect()
+    }
+}
+
+/// Bindings as they were last applied, restored when the controls window is cancelled
+#[derive(Debug, Resource, Default, Deref)]
+pub struct AppliedKeyBindings(pub KeyBindings);
+
+/// Action waiting for the next pressed input to be bound to it
+#[derive(Debug, Resource, Default)]
+pub struct KeyBindingCapture(pub Option<CoreAction>);
+
+#[derive(Debug, Resource, Deref)]
+pub struct KeyBindingsPath(pub Arc<PathBuf>);
+
+/// Saves the edited [`KeyBindings`] and re-applies them to the controls
+#[derive(Debug, Event)]
+pub struct ApplyKeyBindings;
+
+/// Drops the edited [`KeyBindings`] in favour of the applied ones
+#[derive(Debug, Event)]
+pub struct ExemptKeyBindings;
+
+/// Binds the next pressed key, mouse or gamepad button to the captured action
+pub fn capture_binding(
+    mut capture: ResMut<KeyBindingCapture>,
+    mut bindings: ResMut<KeyBindings>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepad: Res<ButtonInput<GamepadButton>>,
+) {
+    let Some(action) = capture.0 else {
+        return;
+    };
+
+    let input = keyboard
+        .get_just_pressed()
+        .next()
+        .map(|key| BoundInput::Keyboard(*key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .next()
+                .map(|button| BoundInput::Mouse(*button))
+        })
+        .or_else(|| {
+            gamepad
+                .get_just_pressed()
+                .next()
+                .map(|button| BoundInput::Gamepad(button.button_type))
+        });
+
+    if let Some(input) = input {
+        bindings.0.insert(action, input);
+        capture.0 = None;
+    }
+}
+
+/// Path of the bindings file, next to the settings one
+pub fn key_bindings_path() -> PathBuf {
+    settings_dir().join("keybindings.yaml")
+}
+
+pub fn write_key_bindings(
+    path: &Path,
+    bindings: &KeyBindings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    file.write_all(serde_yaml::to_string(bindings)?.as_bytes())?;
+    Ok(())
+}
+
+/// Reads the bindings file, a missing or corrupted one is replaced with defaults
+pub fn read_key_bindings(path: &Path) -> KeyBindings {
+    let bindings = File::open(path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_yaml::from_reader(file).map_err(|err| err.to_string()));
+
+    match bindings {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            if path.exists() {
+                warn!(
+                    "Failed to read key bindings file ({:#?}), using defaults \n error: {}",
+                    path, err
+                );
+            }
+            let bindings = KeyBindings::default();
+            if let Err(err) = write_key_bindings(path, &bindings) {
+                error!(
+                    "Failed to write key bindings file ({:#?}) \n error: {}",
+                    path, err
+                );
+            }
+            bindings
+        }
+    }
+}
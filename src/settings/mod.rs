@@ -1,4 +1,8 @@
 #![allow(clippy::module_inception)]
 
+mod frame_limiter;
+mod keybindings;
 mod settings;
+pub use frame_limiter::*;
+pub use keybindings::*;
 pub use settings::*;
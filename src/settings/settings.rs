@@ -1,41 +1,317 @@
 use std::{
     env,
-    fs::{File, OpenOptions},
+    fs::{self, File},
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
 use bevy::{
-    app::{App, Last, Plugin, PostStartup},
-    asset::Assets,
+    app::{App, Last, Plugin, PostStartup, Update},
     ecs::{
-        event::{Event, EventReader},
-        system::{Commands, Res, ResMut, Resource},
+        entity::Entity,
+        event::{Event, EventReader, EventWriter},
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, NonSend, Query, Res, ResMut, Resource},
     },
-    log::warn,
-    prelude::Deref,
+    input::{keyboard::KeyCode, ButtonInput},
+    log::{error, info, warn},
+    prelude::{resource_changed, resource_exists, Deref, Msaa},
+    render::camera::Projection,
+    time::{Time, Timer, TimerMode},
+    window::{PresentMode, PrimaryWindow, Window, WindowMode},
+    winit::WinitWindows,
 };
-use bevy_kira_audio::{prelude::Volume, AudioInstance, AudioTween};
 use serde::{self, Deserialize, Serialize};
 
-use crate::sound::MenuMusic;
+use crate::util::i18n::{set_locale, Language, Locale};
 
-#[allow(dead_code)]
+use super::FrameLimiterPlugins;
+use crate::world::MainCamera;
+
+/// Settings as they were last applied, restored when the settings window is cancelled
 #[derive(Debug, Resource, Default)]
-struct AppliedSettings {
-    music_volume: f64,
-}
+struct AppliedSettings(Settings);
 
-#[allow(dead_code)]
-#[derive(Deserialize, Serialize, Debug, Resource)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Resource)]
+#[serde(default)]
 pub struct Settings {
     pub music_volume: f64,
+    /// Volume of every sound in percent
+    pub master_volume: f64,
+    /// Volume of the sound effects in percent, on top of [`Settings::master_volume`]
+    pub effects_volume: f64,
+    /// Vertical field of view of the camera in degrees
+    pub fov: f32,
+    /// Multiplier of the mouse look speed
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    pub present_mode: PresentModeSetting,
+    /// Frame rate cap, `None` renders uncapped
+    pub fps_limit: Option<u32>,
+    /// Show the nametag above the own character too
+    pub show_own_nametag: bool,
+    /// Top-down map of the players in a corner of the screen
+    pub show_minimap: bool,
+    /// Window mode, size and anti-aliasing, VSync is [`Settings::present_mode`]
+    pub graphics: GraphicsSettings,
+    /// Toggled in game by [`CoreAction::CameraMode`](crate::core::CoreAction::CameraMode)
+    pub camera_mode: CameraMode,
+    /// Language of the user interface, see [`Locale`]
+    pub language: Language,
+    /// Ask an external service for the public address of a hosted game, to show it
+    pub lookup_public_address: bool,
+    /// Ask before quitting on a closed window or the quit key
+    pub confirm_quit: bool,
+    /// No camera shake, for the players it makes sick
+    pub reduce_motion: bool,
+    pub crosshair: CrosshairSettings,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub fullscreen: FullscreenSetting,
+    /// Physical size of the window, `None` keeps the size chosen by the platform.
+    /// Clamped to the monitor when applied
+    pub resolution: Option<(u32, u32)>,
+    pub msaa: MsaaSetting,
+}
+
+/// Look of the crosshair in the middle of the screen, in points
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct CrosshairSettings {
+    pub enabled: bool,
+    /// Length of each of the four lines
+    pub size: f32,
+    /// Space between the center and the lines
+    pub gap: f32,
+    pub thickness: f32,
+    /// sRGB
+    pub color: [u8; 3],
+}
+
+impl Default for CrosshairSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            size: 8.,
+            gap: 4.,
+            thickness: 2.,
+            color: [255, 255, 255],
+        }
+    }
+}
+
+impl CrosshairSettings {
+    pub const SIZE_RANGE: std::ops::RangeInclusive<f32> = 2.0..=32.0;
+    pub const GAP_RANGE: std::ops::RangeInclusive<f32> = 0.0..=24.0;
+    pub const THICKNESS_RANGE: std::ops::RangeInclusive<f32> = 1.0..=6.0;
+}
+
+impl GraphicsSettings {
+    /// Window sizes offered by the settings window
+    pub const RESOLUTIONS: [(u32, u32); 6] = [
+        (1280, 720),
+        (1366, 768),
+        (1600, 900),
+        (1920, 1080),
+        (2560, 1440),
+        (3840, 2160),
+    ];
+    /// Seconds a new window mode or size is kept without being confirmed
+    pub const REVERT_DELAY: f32 = 10.;
+
+    /// The part of the settings that can leave the player without a usable window
+    fn display(&self) -> (FullscreenSetting, Option<(u32, u32)>) {
+        (self.fullscreen, self.resolution)
+    }
+}
+
+/// Window modes a player can choose, see [`WindowMode`]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullscreenSetting {
+    #[default]
+    Windowed,
+    /// Fullscreen window without changing the monitor video mode
+    Borderless,
+    /// Fullscreen in the video mode of the window size
+    Exclusive,
+}
+
+impl FullscreenSetting {
+    pub const ALL: [FullscreenSetting; 3] = [
+        FullscreenSetting::Windowed,
+        FullscreenSetting::Borderless,
+        FullscreenSetting::Exclusive,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FullscreenSetting::Windowed => "Windowed",
+            FullscreenSetting::Borderless => "Borderless",
+            FullscreenSetting::Exclusive => "Fullscreen",
+        }
+    }
+
+    fn window_mode(&self) -> WindowMode {
+        match self {
+            FullscreenSetting::Windowed => WindowMode::Windowed,
+            FullscreenSetting::Borderless => WindowMode::BorderlessFullscreen,
+            FullscreenSetting::Exclusive => WindowMode::SizedFullscreen,
+        }
+    }
+}
+
+/// Multisample anti-aliasing levels a player can choose, see [`Msaa`]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MsaaSetting {
+    Off,
+    Sample2,
+    #[default]
+    Sample4,
+    Sample8,
+}
+
+impl MsaaSetting {
+    pub const ALL: [MsaaSetting; 4] = [
+        MsaaSetting::Off,
+        MsaaSetting::Sample2,
+        MsaaSetting::Sample4,
+        MsaaSetting::Sample8,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MsaaSetting::Off => "Off",
+            MsaaSetting::Sample2 => "2x",
+            MsaaSetting::Sample4 => "4x",
+            MsaaSetting::Sample8 => "8x",
+        }
+    }
+
+    fn msaa(&self) -> Msaa {
+        match self {
+            MsaaSetting::Off => Msaa::Off,
+            MsaaSetting::Sample2 => Msaa::Sample2,
+            MsaaSetting::Sample4 => Msaa::Sample4,
+            MsaaSetting::Sample8 => Msaa::Sample8,
+        }
+    }
+}
+
+/// Where the camera following the own character is
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    /// Orbits the character at the distance of its view
+    #[default]
+    ThirdPerson,
+    /// At the head of the character, which is hidden
+    FirstPerson,
+}
+
+impl CameraMode {
+    pub const ALL: [CameraMode; 2] = [CameraMode::ThirdPerson, CameraMode::FirstPerson];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CameraMode::ThirdPerson => "Third person",
+            CameraMode::FirstPerson => "First person",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            CameraMode::ThirdPerson => CameraMode::FirstPerson,
+            CameraMode::FirstPerson => CameraMode::ThirdPerson,
+        }
+    }
+}
+
+/// Present modes a player can choose, see [`PresentMode`]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentModeSetting {
+    /// VSync on
+    #[default]
+    AutoVsync,
+    /// VSync off, tearing avoided when the platform allows it
+    AutoNoVsync,
+    /// VSync off, may tear
+    Immediate,
+}
+
+impl PresentModeSetting {
+    pub const ALL: [PresentModeSetting; 3] = [
+        PresentModeSetting::AutoVsync,
+        PresentModeSetting::AutoNoVsync,
+        PresentModeSetting::Immediate,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PresentModeSetting::AutoVsync => "VSync",
+            PresentModeSetting::AutoNoVsync => "No VSync",
+            PresentModeSetting::Immediate => "Immediate",
+        }
+    }
+
+    /// The window present mode, unsupported ones fall back to [`PresentMode::AutoNoVsync`]
+    fn present_mode(&self) -> PresentMode {
+        match self {
+            PresentModeSetting::AutoVsync => PresentMode::AutoVsync,
+            PresentModeSetting::AutoNoVsync => PresentMode::AutoNoVsync,
+            // browsers always synchronize presentation
+            PresentModeSetting::Immediate if cfg!(target_arch = "wasm32") => {
+                warn!("Immediate present mode is not supported on this platform, using AutoNoVsync");
+                PresentMode::AutoNoVsync
+            }
+            PresentModeSetting::Immediate => PresentMode::Immediate,
+        }
+    }
 }
 
 impl Default for Settings {
     fn default() -> Self {
-        Self { music_volume: 10. }
+        Self {
+            music_volume: 10.,
+            master_volume: 100.,
+            effects_volume: 100.,
+            fov: 45.,
+            mouse_sensitivity: 1.,
+            invert_y: false,
+            present_mode: PresentModeSetting::default(),
+            fps_limit: None,
+            show_own_nametag: false,
+            show_minimap: true,
+            graphics: GraphicsSettings::default(),
+            camera_mode: CameraMode::default(),
+            language: Language::default(),
+            lookup_public_address: false,
+            confirm_quit: true,
+            reduce_motion: false,
+            crosshair: CrosshairSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub const FOV_RANGE: std::ops::RangeInclusive<f32> = 30.0..=120.0;
+    pub const SENSITIVITY_RANGE: std::ops::RangeInclusive<f32> = 0.1..=5.0;
+    pub const FPS_LIMIT_RANGE: std::ops::RangeInclusive<u32> = 20..=360;
+    /// Frame rate cap suggested when the limiter is enabled
+    pub const DEFAULT_FPS_LIMIT: u32 = 60;
+    pub const VOLUME_RANGE: std::ops::RangeInclusive<f64> = 0.0..=100.0;
+
+    /// Amplitude of the music, master volume included
+    pub fn music_amplitude(&self) -> f64 {
+        self.music_volume / 10. * self.master_volume / 100.
+    }
+
+    /// Amplitude of the sound effects, master volume included
+    pub fn effects_amplitude(&self) -> f64 {
+        self.master_volume / 100. * self.effects_volume / 100.
     }
 }
 
@@ -48,15 +324,65 @@ pub struct ApplySettings;
 #[derive(Debug, Event)]
 pub struct ExemptSettings;
 
+/// Countdown restoring the last confirmed window mode and size,
+/// started when they change so an unusable mode does not stick.
+#[derive(Debug, Resource, Default)]
+pub struct DisplayRevert {
+    kept: (FullscreenSetting, Option<(u32, u32)>),
+    /// Mode and size the countdown was started for, the settings windows touch
+    /// [`Settings`] every frame
+    shown: (FullscreenSetting, Option<(u32, u32)>),
+    countdown: Option<Timer>,
+}
+
+impl DisplayRevert {
+    /// Seconds before the change is reverted, `None` if nothing waits for a confirmation
+    pub fn remaining_secs(&self) -> Option<f32> {
+        self.countdown.as_ref().map(Timer::remaining_secs)
+    }
+
+    /// Confirms the current window mode and size
+    pub fn keep(&mut self, graphics: &GraphicsSettings) {
+        self.kept = graphics.display();
+        self.shown = self.kept;
+        self.countdown = None;
+    }
+
+    /// Ends the countdown early, restoring the last confirmed mode and size
+    pub fn revert(&mut self, graphics: &mut GraphicsSettings) {
+        (graphics.fullscreen, graphics.resolution) = self.kept;
+        self.countdown = None;
+    }
+}
+
 pub struct SettingsPlugins;
 
 impl Plugin for SettingsPlugins {
     fn build(&self, app: &mut App) {
-        app.init_resource::<AppliedSettings>()
+        app.add_plugins(FrameLimiterPlugins)
+            .init_resource::<AppliedSettings>()
+            .init_resource::<DisplayRevert>()
+            .init_resource::<Locale>()
             .add_event::<ApplySettings>()
             .add_event::<ExemptSettings>()
             .add_systems(PostStartup, setup)
-            .add_systems(Last, (apply_settings, exempt_settings));
+            .add_systems(Last, (apply_settings, exempt_settings))
+            .add_systems(
+                Update,
+                (
+                    apply_camera_settings,
+                    toggle_fullscreen,
+                    (
+                        watch_display_changes.run_if(resource_changed::<Settings>),
+                        revert_display,
+                    )
+                        .chain(),
+                    apply_window_settings.run_if(resource_changed::<Settings>),
+                    apply_language.run_if(resource_changed::<Settings>),
+                )
+                    .chain()
+                    .run_if(resource_exists::<Settings>),
+            );
     }
 }
 
@@ -66,118 +392,222 @@ fn exempt_settings(
     applied_settings: Res<AppliedSettings>,
 ) {
     for _ in event.read() {
-        commands.insert_resource(Settings {
-            music_volume: applied_settings.music_volume,
-        });
+        commands.insert_resource(applied_settings.0.clone());
     }
 }
 
 fn apply_settings(
-    mut commands: Commands,
     mut event: EventReader<ApplySettings>,
     settings: Res<Settings>,
-    menu_music: ResMut<MenuMusic>,
-    mut audio_sources: ResMut<Assets<AudioInstance>>,
+    mut applied_settings: ResMut<AppliedSettings>,
     settings_path: Res<SettingsPath>,
 ) {
     for _ in event.read() {
-        if let Some(instance) = audio_sources.get_mut(&menu_music.instance_handle) {
-            instance.set_volume(
-                Volume::Amplitude(settings.music_volume / 10.),
-                AudioTween::default(),
+        applied_settings.0 = settings.clone();
+
+        if let Err(err) = write_settings(settings_path.as_ref().as_ref(), &settings) {
+            error!(
+                "Failed to write settings file ({:#?}) \n error: {}",
+                settings_path.as_ref(),
+                err
             );
-        } else {
-            warn!("Failed to get music source");
         }
+    }
+}
 
-        commands.insert_resource(AppliedSettings {
-            music_volume: settings.music_volume,
-        });
-
-        let settings_path = settings_path.as_ref().as_ref();
-        let mut file = OpenOptions::new()
-            .write(true)
-            .open(settings_path)
-            .unwrap_or_else(|err| {
-                panic!(
-                    "Failed to create settings file ({:#?}) \n error: {:#?}",
-                    settings_path, err
-                )
-            });
-        file.write_all(serde_yaml::to_string(settings.as_ref()).unwrap().as_bytes())
-            .unwrap_or_else(|err| {
-                panic!(
-                    "Failed to write to settings file ({:#?}) \n error: {:#?}",
-                    settings_path, err
-                )
-            });
+/// Applies the camera part of the settings live, including cameras spawned later
+fn apply_camera_settings(
+    settings: Res<Settings>,
+    mut query: Query<&mut Projection, With<MainCamera>>,
+) {
+    let fov = settings.fov.to_radians();
+    for mut projection in query.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_ref() {
+            if perspective.fov == fov {
+                continue;
+            }
+        }
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov;
+        }
     }
 }
 
-fn setup(mut commands: Commands) {
-    let exe_path = env::current_exe().expect("Failed to find executable path");
+/// Alt+Enter switches between windowed and borderless fullscreen, the choice is saved right away
+fn toggle_fullscreen(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut revert: ResMut<DisplayRevert>,
+    mut apply_settings: EventWriter<ApplySettings>,
+) {
+    let alt = keyboard.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if !alt || !keyboard.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    settings.graphics.fullscreen = match settings.graphics.fullscreen {
+        FullscreenSetting::Windowed => FullscreenSetting::Borderless,
+        _ => FullscreenSetting::Windowed,
+    };
+    // the player can press it again, no confirmation needed
+    revert.keep(&settings.graphics);
+    apply_settings.send(ApplySettings);
+}
 
-    let exe_dir = exe_path
-        .parent()
-        .expect("Failed to find executable directory");
+/// Starts the revert countdown when the window mode or size leaves the confirmed one
+fn watch_display_changes(settings: Res<Settings>, mut revert: ResMut<DisplayRevert>) {
+    let display = settings.graphics.display();
+    if display == revert.shown {
+        return;
+    }
+    revert.shown = display;
+    revert.countdown = (display != revert.kept)
+        .then(|| Timer::from_seconds(GraphicsSettings::REVERT_DELAY, TimerMode::Once));
+}
 
-    let yaml_path = exe_dir.join("settings.yaml");
-    let yml_path = exe_dir.join("settings.yml");
+/// Restores and saves the confirmed mode and size once the countdown is over
+fn revert_display(
+    time: Res<Time>,
+    mut settings: ResMut<Settings>,
+    mut revert: ResMut<DisplayRevert>,
+    mut apply_settings: EventWriter<ApplySettings>,
+) {
+    let Some(countdown) = revert.countdown.as_mut() else {
+        return;
+    };
+    if countdown.tick(time.delta()).finished() {
+        info!("Display settings were not confirmed, reverting");
+        revert.revert(&mut settings.graphics);
+        apply_settings.send(ApplySettings);
+    }
+}
 
-    let settings = {
-        if yaml_path.exists() {
-            let file = File::open(&yaml_path).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to open exist settings file ({:#?}) \n error: {:#?}",
-                    &yaml_path, err
-                )
-            });
+/// Loads the catalog of a newly chosen language, the menus are drawn with it from the next frame
+fn apply_language(settings: Res<Settings>, mut locale: ResMut<Locale>) {
+    if locale.language == settings.language {
+        return;
+    }
+    *locale = Locale::load(settings.language);
+    set_locale(locale.clone());
+}
 
-            commands.insert_resource(SettingsPath(yaml_path.clone().into()));
+/// Applies the window part of the settings live to the primary window
+fn apply_window_settings(
+    settings: Res<Settings>,
+    mut query: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+    winit_windows: Option<NonSend<WinitWindows>>,
+    msaa: Option<ResMut<Msaa>>,
+) {
+    if let Some(mut msaa) = msaa {
+        let samples = settings.graphics.msaa.msaa();
+        if *msaa != samples {
+            *msaa = samples;
+        }
+    }
 
-            serde_yaml::from_reader(file).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to read settings file ({:#?}) \n error: {:#?}",
-                    &yaml_path, err
-                )
-            })
-        } else if yml_path.exists() {
-            let file = File::open(&yml_path).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to open exist settings file ({:#?}) \n error: {:#?}",
-                    &yml_path, err
-                )
-            });
+    let Ok((entity, mut window)) = query.get_single_mut() else {
+        return;
+    };
+    let present_mode = settings.present_mode.present_mode();
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+    let mode = settings.graphics.fullscreen.window_mode();
+    if window.mode != mode {
+        window.mode = mode;
+    }
 
-            commands.insert_resource(SettingsPath(yml_path.clone().into()));
+    let Some((mut width, mut height)) = settings.graphics.resolution else {
+        return;
+    };
+    // a saved size from a bigger monitor would put the window off screen
+    let monitor = winit_windows
+        .as_ref()
+        .and_then(|windows| windows.get_window(entity))
+        .and_then(|window| window.current_monitor())
+        .map(|monitor| monitor.size());
+    if let Some(monitor) = monitor {
+        width = width.min(monitor.width);
+        height = height.min(monitor.height);
+    }
+    if window.resolution.physical_width() != width
+        || window.resolution.physical_height() != height
+    {
+        window.resolution.set_physical_resolution(width, height);
+    }
+}
 
-            serde_yaml::from_reader(&file).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to read settings file ({:#?}) \n error: {:#?}",
-                    &yml_path, err
-                )
-            })
-        } else {
-            let mut file: File = File::create(&yaml_path).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to create settings file ({:#?}) \n error: {:#?}",
-                    &yaml_path, err
-                )
-            });
+/// The user config directory of the game, `None` if the platform does not define one
+fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
 
-            commands.insert_resource(SettingsPath(yaml_path.clone().into()));
+    base.map(|base| base.join(env!("CARGO_PKG_NAME")))
+}
 
-            let settings = Settings::default();
-            serde_yaml::to_writer(&mut file, &settings).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to write to settings file ({:#?}) \n error: {:#?}",
-                    &yaml_path, err
-                )
-            });
+/// Directory of the settings file: the user config directory, or the executable one as a fallback
+pub(crate) fn settings_dir() -> PathBuf {
+    config_dir()
+        .filter(|dir| fs::create_dir_all(dir).is_ok())
+        .or_else(|| {
+            env::current_exe()
+                .ok()
+                .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+        })
+        .unwrap_or_default()
+}
 
+fn write_settings(path: &Path, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = File::create(path)?;
+    file.write_all(serde_yaml::to_string(settings)?.as_bytes())?;
+    Ok(())
+}
+
+/// Reads the settings file, a missing or corrupted one is replaced with defaults
+fn read_settings(path: &Path) -> Settings {
+    let settings = File::open(path)
+        .map_err(|err| err.to_string())
+        .and_then(|file| serde_yaml::from_reader(file).map_err(|err| err.to_string()));
+
+    match settings {
+        Ok(settings) => settings,
+        Err(err) => {
+            if path.exists() {
+                warn!(
+                    "Failed to read settings file ({:#?}), using defaults \n error: {}",
+                    path, err
+                );
+            }
+            let settings = Settings::default();
+            if let Err(err) = write_settings(path, &settings) {
+                error!("Failed to write settings file ({:#?}) \n error: {}", path, err);
+            }
             settings
         }
+    }
+}
+
+fn setup(mut commands: Commands, mut revert: ResMut<DisplayRevert>) {
+    let dir = settings_dir();
+    let yaml_path = dir.join("settings.yaml");
+    let yml_path = dir.join("settings.yml");
+
+    let path = if !yaml_path.exists() && yml_path.exists() {
+        yml_path
+    } else {
+        yaml_path
     };
 
+    let settings = read_settings(&path);
+    // the saved mode worked the last time, applied by `apply_window_settings` once inserted
+    revert.keep(&settings.graphics);
+    commands.insert_resource(SettingsPath(path.into()));
+    commands.insert_resource(AppliedSettings(settings.clone()));
     commands.insert_resource(settings);
 }
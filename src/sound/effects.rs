@@ -0,0 +1,329 @@
+//! Sound effects of gameplay events.
+//!
+//! Effects are `.wav` files in the `audio` asset directory, a missing file is warned about once
+//! and its effect stays silent. Gameplay effects are spatial, they play from an [`AudioEmitter`]
+//! on the entity they belong to and are heard from the [`MainCamera`]. The effects are driven by events
+//! both the simulating side and clients get (e.g. [`PlayerDiedEvent`]), so remote players are heard too.
+//! A build without an audio device (e.g. a headless host) runs the same systems, `bevy_kira_audio`
+//! plays nothing then.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use bevy_kira_audio::prelude::*;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::actor::character::{AnimationState, CharacterAnimation};
+use crate::actor::Owner;
+use crate::component::Teleported;
+use crate::lobby::{Character, PlayerDiedEvent};
+use crate::settings::Settings;
+use crate::world::MainCamera;
+
+/// Effects farther than this from the listener are not heard
+const MAX_HEARING_DISTANCE: f32 = 40.;
+/// Seconds between two footsteps of a running character
+const FOOTSTEP_INTERVAL: f32 = 0.35;
+/// Seconds an emitter left where an entity disappeared is kept, longer than any effect
+const TRANSIENT_EMITTER_LIFETIME: f32 = 3.;
+
+/// Effects the game can play, systems of the features they belong to send [`PlaySoundEvent`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter)]
+pub enum SoundEffect {
+    Jump,
+    Land,
+    Footstep,
+    ProjectileFire,
+    ProjectileImpact,
+    Death,
+    Respawn,
+    Checkpoint,
+    UiClick,
+}
+
+impl SoundEffect {
+    fn path(&self) -> &'static str {
+        match self {
+            SoundEffect::Jump => "audio/jump.wav",
+            SoundEffect::Land => "audio/land.wav",
+            SoundEffect::Footstep => "audio/footstep.wav",
+            SoundEffect::ProjectileFire => "audio/projectile_fire.wav",
+            SoundEffect::ProjectileImpact => "audio/projectile_impact.wav",
+            SoundEffect::Death => "audio/death.wav",
+            SoundEffect::Respawn => "audio/respawn.wav",
+            SoundEffect::Checkpoint => "audio/checkpoint.wav",
+            SoundEffect::UiClick => "audio/ui_click.wav",
+        }
+    }
+}
+
+/// Plays an effect from `emitter`, or not spatially (e.g. UI) if `None`.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct PlaySoundEvent {
+    pub effect: SoundEffect,
+    pub emitter: Option<Entity>,
+}
+
+impl PlaySoundEvent {
+    pub fn at(effect: SoundEffect, emitter: Entity) -> Self {
+        Self {
+            effect,
+            emitter: Some(emitter),
+        }
+    }
+
+    pub fn ui(effect: SoundEffect) -> Self {
+        Self {
+            effect,
+            emitter: None,
+        }
+    }
+}
+
+/// Emitter standing where an entity disappeared, e.g. a projectile on its impact.
+#[derive(Debug, Component)]
+struct TransientEmitter(Timer);
+
+/// Movement sounds of a character, kept by [`character_sounds`].
+#[derive(Debug, Default)]
+struct CharacterSteps {
+    state: AnimationState,
+    /// Seconds before the next footstep while running
+    next_step: f32,
+}
+
+#[derive(Debug, Default, Resource)]
+struct SoundEffects {
+    handles: HashMap<SoundEffect, Handle<AudioSource>>,
+    /// Effects whose file failed to load, already warned about
+    failed: HashSet<SoundEffect>,
+}
+
+pub struct SoundEffectPlugins;
+
+impl Plugin for SoundEffectPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoundEffects>()
+            .insert_resource(SpatialAudio {
+                max_distance: MAX_HEARING_DISTANCE,
+            })
+            .add_event::<PlaySoundEvent>()
+            .add_systems(Startup, load_effects)
+            .add_systems(
+                Update,
+                (
+                    (
+                        projectile_fired,
+                        projectile_impact,
+                        character_sounds,
+                        player_died,
+                        player_respawned,
+                        ui_clicked,
+                    ),
+                    play_effects,
+                    (prune_emitters, despawn_transient_emitters),
+                )
+                    .chain(),
+            )
+            .add_systems(Update, follow_main_camera);
+    }
+}
+
+fn load_effects(asset_server: Res<AssetServer>, mut effects: ResMut<SoundEffects>) {
+    for effect in SoundEffect::iter() {
+        effects
+            .handles
+            .insert(effect, asset_server.load(effect.path()));
+    }
+}
+
+fn projectile_fired(
+    query: Query<Entity, Added<Owner>>,
+    mut play_sound_event: EventWriter<PlaySoundEvent>,
+) {
+    for entity in query.iter() {
+        play_sound_event.send(PlaySoundEvent::at(SoundEffect::ProjectileFire, entity));
+    }
+}
+
+/// A projectile is despawned on its impact, on every side, its sound plays where it was last seen.
+fn projectile_impact(
+    mut commands: Commands,
+    mut positions: Local<HashMap<Entity, Vec3>>,
+    mut removed_owners: RemovedComponents<Owner>,
+    query: Query<(Entity, &GlobalTransform), With<Owner>>,
+    mut play_sound_event: EventWriter<PlaySoundEvent>,
+) {
+    for entity in removed_owners.read() {
+        let Some(position) = positions.remove(&entity) else {
+            continue;
+        };
+        let emitter = commands
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_translation(position)),
+                TransientEmitter(Timer::from_seconds(TRANSIENT_EMITTER_LIFETIME, TimerMode::Once)),
+                Name::new("Impact sound"),
+            ))
+            .id();
+        play_sound_event.send(PlaySoundEvent::at(SoundEffect::ProjectileImpact, emitter));
+    }
+    for (entity, transform) in query.iter() {
+        positions.insert(entity, transform.translation());
+    }
+}
+
+/// Footsteps, jumps and landings of every character, from its [`CharacterAnimation`],
+/// which clients follow from the snapshots of the host.
+fn character_sounds(
+    time: Res<Time>,
+    mut steps: Local<HashMap<Entity, CharacterSteps>>,
+    query: Query<(Entity, &CharacterAnimation)>,
+    mut play_sound_event: EventWriter<PlaySoundEvent>,
+) {
+    steps.retain(|entity, _| query.contains(*entity));
+    for (entity, animation) in query.iter() {
+        let steps = steps.entry(entity).or_default();
+        let entered = steps.state != animation.state;
+        steps.state = animation.state;
+
+        let effect = match animation.state {
+            AnimationState::Jump if entered => Some(SoundEffect::Jump),
+            AnimationState::Land if entered => Some(SoundEffect::Land),
+            AnimationState::Run => {
+                steps.next_step -= time.delta_seconds();
+                (steps.next_step <= 0.).then(|| {
+                    steps.next_step = FOOTSTEP_INTERVAL;
+                    SoundEffect::Footstep
+                })
+            }
+            _ => {
+                // the first step of a run is heard right away
+                steps.next_step = 0.;
+                None
+            }
+        };
+        if let Some(effect) = effect {
+            play_sound_event.send(PlaySoundEvent::at(effect, entity));
+        }
+    }
+}
+
+fn player_died(
+    mut player_died_event: EventReader<PlayerDiedEvent>,
+    character_query: Query<(Entity, &Character)>,
+    mut play_sound_event: EventWriter<PlaySoundEvent>,
+) {
+    for PlayerDiedEvent { id, .. } in player_died_event.read() {
+        let character = character_query
+            .iter()
+            .find(|(_, character)| character.id == *id);
+        if let Some((entity, _)) = character {
+            play_sound_event.send(PlaySoundEvent::at(SoundEffect::Death, entity));
+        }
+    }
+}
+
+fn player_respawned(
+    query: Query<Entity, (Added<Teleported>, With<Character>)>,
+    mut play_sound_event: EventWriter<PlaySoundEvent>,
+) {
+    for entity in query.iter() {
+        play_sound_event.send(PlaySoundEvent::at(SoundEffect::Respawn, entity));
+    }
+}
+
+fn ui_clicked(mut context: EguiContexts, mut play_sound_event: EventWriter<PlaySoundEvent>) {
+    let ctx = context.ctx_mut();
+    if ctx.is_pointer_over_area() && ctx.input(|input| input.pointer.any_click()) {
+        play_sound_event.send(PlaySoundEvent::ui(SoundEffect::UiClick));
+    }
+}
+
+fn play_effects(
+    mut commands: Commands,
+    mut play_sound_event: EventReader<PlaySoundEvent>,
+    mut effects: ResMut<SoundEffects>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    settings: Option<Res<Settings>>,
+    mut emitter_query: Query<&mut AudioEmitter>,
+) {
+    let volume = settings.map_or(1., |settings| settings.effects_amplitude());
+
+    for PlaySoundEvent { effect, emitter } in play_sound_event.read() {
+        let Some(handle) = effects.handles.get(effect).cloned() else {
+            continue;
+        };
+        match asset_server.load_state(&handle) {
+            LoadState::Loaded => {}
+            LoadState::Failed => {
+                if effects.failed.insert(*effect) {
+                    warn!("Sound effect {} failed to load, it stays silent", effect.path());
+                }
+                continue;
+            }
+            // still loading, this one is skipped rather than played late
+            _ => continue,
+        }
+
+        let instance = audio
+            .play(handle)
+            .with_volume(Volume::Amplitude(volume))
+            .handle();
+
+        let Some(entity) = *emitter else {
+            continue;
+        };
+        if let Ok(mut emitter) = emitter_query.get_mut(entity) {
+            emitter.instances.push(instance);
+        } else if let Some(mut entity_commands) = commands.get_entity(entity) {
+            entity_commands.insert(AudioEmitter {
+                instances: vec![instance],
+            });
+        }
+    }
+}
+
+/// Drops the finished instances, so emitters do not grow for the whole session.
+fn prune_emitters(
+    audio_instances: Res<Assets<AudioInstance>>,
+    mut emitter_query: Query<&mut AudioEmitter>,
+) {
+    for mut emitter in emitter_query.iter_mut() {
+        // an instance is only created once the audio plugin handles the play command
+        emitter.instances.retain(|instance| {
+            audio_instances
+                .get(instance)
+                .map_or(true, |instance| instance.state() != PlaybackState::Stopped)
+        });
+    }
+}
+
+fn despawn_transient_emitters(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut TransientEmitter)>,
+) {
+    for (entity, mut emitter) in query.iter_mut() {
+        if emitter.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Keeps a single [`AudioReceiver`], on the current [`MainCamera`] (tied or free).
+fn follow_main_camera(
+    mut commands: Commands,
+    camera_query: Query<Entity, (With<MainCamera>, Without<AudioReceiver>)>,
+    stale_query: Query<Entity, (With<AudioReceiver>, Without<MainCamera>)>,
+) {
+    for entity in stale_query.iter() {
+        commands.entity(entity).remove::<AudioReceiver>();
+    }
+    for entity in camera_query.iter() {
+        commands.entity(entity).insert(AudioReceiver);
+    }
+}
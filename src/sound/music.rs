@@ -1,71 +1,172 @@
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
 use rand::{thread_rng, Rng};
+use std::path::Path;
 use std::time::Duration;
 
 use crate::{
-    core::{AudioAssets, CoreGameState},
-    settings::ApplySettings,
+    core::{AudioAssets, CoreGameState, CurrentLevel, KnownLevel},
+    lobby::LevelCode,
+    settings::Settings,
+    ASSET_DIR,
 };
 
 const MINIMAL_DELAY: f32 = 15.;
 const MAXIMAL_DELAY: f32 = 90.;
+/// Seconds the music of the previous and the next map overlap
+const CROSSFADE: Duration = Duration::from_secs(2);
 
-#[derive(Default, Resource, Deref, DerefMut)]
-struct MusicTimer(Timer);
+/// Music of a part of the game, the menu or a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MusicTrack {
+    /// Played with a pause between the repetitions
+    Menu,
+    /// Asset path of the looped music of a map, see [`level_music_path`]
+    Level(String),
+}
 
+/// The music playing, at most one track is faded in while the previous one fades out.
 #[derive(Default, Resource)]
-pub struct MenuMusic {
-    source_handle: Handle<AudioSource>,
-    pub instance_handle: Handle<AudioInstance>,
-    duration: Option<Duration>,
+struct Music {
+    track: Option<MusicTrack>,
+    instance: Option<Handle<AudioInstance>>,
+    /// Previous tracks still fading out
+    fading: Vec<Handle<AudioInstance>>,
+    /// Pause before the menu music is played again
+    menu_replay: Option<Timer>,
 }
 
 pub struct MusicPlugins;
 
 impl Plugin for MusicPlugins {
     fn build(&self, app: &mut App) {
-        app.init_resource::<MenuMusic>()
-            .init_resource::<MusicTimer>()
-            .add_systems(OnExit(CoreGameState::PrimaryLoad), setup)
-            .add_systems(Update, play_music.run_if(in_state(CoreGameState::Hub)));
+        app.init_resource::<Music>().add_systems(
+            Update,
+            (
+                switch_music,
+                replay_menu_music.run_if(in_state(CoreGameState::Hub)),
+                apply_music_volume.run_if(resource_changed::<Settings>),
+            )
+                .chain()
+                .run_if(resource_exists::<AudioAssets>.and_then(resource_exists::<Settings>)),
+        );
     }
 }
 
-fn setup(
-    mut commands: Commands,
-    mut menu_music: ResMut<MenuMusic>,
+/// Asset path of the music of `level_code`, a `.wav` next to the sound effects.
+///
+/// `None` if the map has no music, which is not an error.
+fn level_music_path(level_code: &LevelCode) -> Option<String> {
+    let name = match level_code {
+        LevelCode::Known(KnownLevel::Hub) => "hub",
+        LevelCode::Path(path) => path,
+        // downloaded maps do not bring music
+        LevelCode::Url(_) => return None,
+    };
+    let path = format!("audio/music/{name}.wav");
+    Path::new(ASSET_DIR).join(&path).exists().then_some(path)
+}
+
+/// Crossfades to the track of the current state and map when they change.
+fn switch_music(
+    core_state: Res<State<CoreGameState>>,
+    current_level: Res<CurrentLevel>,
+    settings: Res<Settings>,
     audio_assets: Res<AudioAssets>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    mut music: ResMut<Music>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
 ) {
-    commands.insert_resource(MusicTimer(Timer::from_seconds(0.0, TimerMode::Repeating)));
-    menu_music.source_handle = audio_assets.background.clone();
+    let track = match core_state.get() {
+        CoreGameState::PrimaryLoad => None,
+        CoreGameState::Hub => Some(MusicTrack::Menu),
+        _ => level_music_path(&current_level.0).map(MusicTrack::Level),
+    };
+    // an instance is only created once the audio plugin handles the play command
+    music.fading.retain(|instance| {
+        audio_instances
+            .get(instance)
+            .map_or(true, |instance| instance.state() != PlaybackState::Stopped)
+    });
+    if track == music.track {
+        return;
+    }
+
+    // a quick change of maps must not stack the tracks, only the last one fades out
+    for instance in std::mem::take(&mut music.fading) {
+        if let Some(instance) = audio_instances.get_mut(&instance) {
+            instance.stop(AudioTween::default());
+        }
+    }
+    if let Some(instance) = music.instance.take() {
+        if let Some(audio_instance) = audio_instances.get_mut(&instance) {
+            audio_instance.stop(AudioTween::linear(CROSSFADE));
+        }
+        music.fading.push(instance);
+    }
+    music.menu_replay = None;
+    music.track = track.clone();
+
+    let source = match track {
+        None => return,
+        Some(MusicTrack::Menu) => audio_assets.background.clone(),
+        Some(MusicTrack::Level(path)) => asset_server.load(path),
+    };
+    let mut command = audio.play(source);
+    command
+        .fade_in(AudioTween::linear(CROSSFADE))
+        .with_volume(Volume::Amplitude(settings.music_amplitude()));
+    if music.track != Some(MusicTrack::Menu) {
+        command.looped();
+    }
+    music.instance = Some(command.handle());
 }
 
-fn play_music(
+/// Plays the menu music again after a random pause once it ended.
+fn replay_menu_music(
     time: Res<Time>,
+    settings: Res<Settings>,
     audio: Res<Audio>,
-    mut music_timer: ResMut<MusicTimer>,
-    mut menu_music: ResMut<MenuMusic>,
+    audio_assets: Res<AudioAssets>,
     audio_sources: Res<Assets<AudioSource>>,
-    mut event: EventWriter<ApplySettings>,
+    mut music: ResMut<Music>,
 ) {
-    if music_timer.tick(time.delta()).just_finished() {
-        if menu_music.duration.is_none() {
-            if let Some(audio_source) = audio_sources.get(&menu_music.source_handle) {
-                let duration = audio_source.sound.duration();
-                menu_music.duration = Some(duration);
-            } else {
-                return;
-            }
-        }
-
-        menu_music.instance_handle = audio.play(menu_music.source_handle.clone()).handle();
-
+    let Some(timer) = music.menu_replay.as_mut() else {
+        // the pause starts with the track, it ends with the track and the delay
+        let Some(audio_source) = audio_sources.get(&audio_assets.background) else {
+            return;
+        };
         let delay = thread_rng().gen_range(MINIMAL_DELAY..MAXIMAL_DELAY)
-            + menu_music.duration.unwrap().as_secs_f32();
-        music_timer.set_duration(Duration::from_secs_f32(delay));
-        music_timer.reset();
+            + audio_source.sound.duration().as_secs_f32();
+        music.menu_replay = Some(Timer::from_seconds(delay, TimerMode::Once));
+        return;
+    };
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
 
-        event.send(ApplySettings);
+    music.menu_replay = None;
+    let instance = audio
+        .play(audio_assets.background.clone())
+        .with_volume(Volume::Amplitude(settings.music_amplitude()))
+        .handle();
+    music.instance = Some(instance);
+}
+
+/// Follows the music volume live, `0` mutes it.
+fn apply_music_volume(
+    settings: Res<Settings>,
+    music: Res<Music>,
+    mut audio_instances: ResMut<Assets<AudioInstance>>,
+) {
+    let Some(instance) = music.instance.as_ref() else {
+        return;
+    };
+    if let Some(instance) = audio_instances.get_mut(instance) {
+        instance.set_volume(
+            Volume::Amplitude(settings.music_amplitude()),
+            AudioTween::default(),
+        );
     }
 }
@@ -1,3 +1,4 @@
+use crate::sound::effects::SoundEffectPlugins;
 use crate::sound::music::MusicPlugins;
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
@@ -6,6 +7,6 @@ pub struct SoundPlugins;
 
 impl Plugin for SoundPlugins {
     fn build(&self, app: &mut App) {
-        app.add_plugins((AudioPlugin, MusicPlugins));
+        app.add_plugins((AudioPlugin, MusicPlugins, SoundEffectPlugins));
     }
 }
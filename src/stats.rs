@@ -0,0 +1,384 @@
+//! Lifetime statistics of the players of this machine, keyed by username.
+//!
+//! Stored as RON next to the settings. Counters of the running session are kept apart
+//! and added to the file content on every flush, so two running games do not overwrite each other.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::app::{App, AppExit, Last, Plugin, PostStartup, Update};
+use bevy::ecs::event::EventReader;
+use bevy::ecs::schedule::{Condition, IntoSystemConfigs, OnEnter, OnExit, State};
+use bevy::ecs::system::{Commands, Res, ResMut, Resource};
+use bevy::prelude::{in_state, resource_exists};
+use bevy::time::Time;
+use serde::{Deserialize, Serialize};
+
+use crate::core::CoreGameState;
+use crate::lobby::client::OwnId;
+use crate::lobby::{ClientResource, HostResource, LobbyState, PlayerDiedEvent, PlayerId, Username};
+use crate::settings::settings_dir;
+
+/// Version of the stats file written by this build.
+pub const STATS_VERSION: u32 = 1;
+
+/// Counters of a single username.
+///
+/// Unknown fields are ignored and missing ones default to zero, so files of other builds load.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerRecord {
+    pub kills: u64,
+    pub deaths: u64,
+    /// Seconds spent in game.
+    pub playtime: f64,
+    pub sessions_hosted: u64,
+    pub sessions_joined: u64,
+}
+
+impl PlayerRecord {
+    /// Adds the counters of `other` to these.
+    pub fn merge(&mut self, other: &PlayerRecord) {
+        self.kills += other.kills;
+        self.deaths += other.deaths;
+        self.playtime += other.playtime;
+        self.sessions_hosted += other.sessions_hosted;
+        self.sessions_joined += other.sessions_joined;
+    }
+}
+
+/// Content of the stats file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsFile {
+    pub version: u32,
+    pub players: BTreeMap<String, PlayerRecord>,
+}
+
+impl Default for StatsFile {
+    fn default() -> Self {
+        Self {
+            version: STATS_VERSION,
+            players: BTreeMap::new(),
+        }
+    }
+}
+
+/// Statistics loaded at startup plus the counters of the running session.
+#[derive(Debug, Default, Resource)]
+pub struct PlayerStats {
+    path: PathBuf,
+    /// Content of the file at the last load or flush.
+    stored: StatsFile,
+    /// Counters not written yet.
+    pending: BTreeMap<String, PlayerRecord>,
+    /// Username and player id of the running session.
+    session: Option<(String, Option<PlayerId>)>,
+}
+
+impl PlayerStats {
+    pub fn new(path: PathBuf) -> Self {
+        let stored = read_stats(&path);
+        Self {
+            path,
+            stored,
+            ..Default::default()
+        }
+    }
+
+    /// Stored and pending counters of every username, for display.
+    pub fn records(&self) -> BTreeMap<String, PlayerRecord> {
+        let mut records = self.stored.players.clone();
+        for (username, pending) in self.pending.iter() {
+            records.entry(username.clone()).or_default().merge(pending);
+        }
+        records
+    }
+
+    /// Pending counters of the running session, `None` outside of a session.
+    fn session_record(&mut self) -> Option<&mut PlayerRecord> {
+        let (username, _) = self.session.as_ref()?;
+        Some(self.pending.entry(username.clone()).or_default())
+    }
+
+    /// Re-reads the file, adds the pending counters and writes it back.
+    pub fn flush(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut stored = read_stats(&self.path);
+        for (username, pending) in self.pending.iter() {
+            stored.players.entry(username.clone()).or_default().merge(pending);
+        }
+        stored.version = STATS_VERSION;
+        write_stats(&self.path, &stored)?;
+
+        self.stored = stored;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+pub struct PlayerStatsPlugin;
+
+impl Plugin for PlayerStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostStartup, setup)
+            .add_systems(
+                OnEnter(LobbyState::Single),
+                start_session.run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                OnEnter(LobbyState::Host),
+                start_session.run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                OnEnter(LobbyState::Client),
+                start_session.run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                OnExit(LobbyState::Single),
+                end_session.run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                OnExit(LobbyState::Host),
+                end_session.run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                OnExit(LobbyState::Client),
+                end_session.run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                Update,
+                (
+                    count_deaths,
+                    count_playtime.run_if(in_state(CoreGameState::InGame)),
+                )
+                    .run_if(resource_exists::<PlayerStats>),
+            )
+            .add_systems(
+                Last,
+                flush_on_exit.run_if(
+                    resource_exists::<PlayerStats>
+                        .and_then(|exit: EventReader<AppExit>| !exit.is_empty()),
+                ),
+            );
+    }
+}
+
+fn setup(mut commands: Commands) {
+    commands.insert_resource(PlayerStats::new(settings_dir().join("stats.ron")));
+}
+
+fn start_session(
+    mut stats: ResMut<PlayerStats>,
+    lobby_state: Res<State<LobbyState>>,
+    host_resource: Res<HostResource>,
+    client_resource: Res<ClientResource>,
+) {
+    let (username, player_id) = match lobby_state.get() {
+        LobbyState::Host => (host_resource.username.clone(), Some(PlayerId::HostOrSingle)),
+        // the id is known once the server initializes the connection
+        LobbyState::Client => (client_resource.username.clone(), None),
+        _ => (None, Some(PlayerId::HostOrSingle)),
+    };
+    let username = username.unwrap_or_else(|| Username::default().0);
+    stats.session = Some((username, player_id));
+
+    let lobby_state = *lobby_state.get();
+    if let Some(record) = stats.session_record() {
+        match lobby_state {
+            LobbyState::Host => record.sessions_hosted += 1,
+            LobbyState::Client => record.sessions_joined += 1,
+            _ => {}
+        }
+    }
+}
+
+fn end_session(mut stats: ResMut<PlayerStats>) {
+    stats.session = None;
+    if let Err(err) = stats.flush() {
+        log::error!("Failed to write stats file ({:?}): {}", stats.path, err);
+    }
+}
+
+fn count_deaths(
+    mut stats: ResMut<PlayerStats>,
+    mut player_died_event: EventReader<PlayerDiedEvent>,
+    own_id: Option<Res<OwnId>>,
+) {
+    let Some((_, session_id)) = stats.session.as_ref() else {
+        player_died_event.clear();
+        return;
+    };
+    let Some(me) = session_id.or_else(|| own_id.and_then(|own_id| own_id.player_id())) else {
+        return;
+    };
+
+    for event in player_died_event.read() {
+        let Some(record) = stats.session_record() else {
+            break;
+        };
+        if event.id == me {
+            record.deaths += 1;
+        }
+        if event.killer == Some(me) && event.id != me {
+            record.kills += 1;
+        }
+    }
+}
+
+fn count_playtime(mut stats: ResMut<PlayerStats>, time: Res<Time>) {
+    if let Some(record) = stats.session_record() {
+        record.playtime += time.delta_seconds_f64();
+    }
+}
+
+fn flush_on_exit(mut stats: ResMut<PlayerStats>) {
+    if let Err(err) = stats.flush() {
+        log::error!("Failed to write stats file ({:?}): {}", stats.path, err);
+    }
+}
+
+fn write_stats(path: &Path, stats: &StatsFile) -> Result<(), Box<dyn std::error::Error>> {
+    let content = ron::ser::to_string_pretty(stats, ron::ser::PrettyConfig::default())?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Reads the stats file, a corrupted one is moved to `.bak` and replaced with an empty one
+fn read_stats(path: &Path) -> StatsFile {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return StatsFile::default(),
+    };
+
+    match ron::from_str::<StatsFile>(&content) {
+        Ok(stats) => stats,
+        Err(err) => {
+            let backup = path.with_extension("ron.bak");
+            log::warn!(
+                "Failed to read stats file ({:?}), moving it to {:?} \n error: {}",
+                path,
+                backup,
+                err
+            );
+            if let Err(err) = fs::rename(path, &backup) {
+                log::error!("Failed to back up stats file ({:?}): {}", path, err);
+            }
+            let stats = StatsFile::default();
+            if let Err(err) = write_stats(path, &stats) {
+                log::error!("Failed to write stats file ({:?}): {}", path, err);
+            }
+            stats
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stats file in an empty directory of the test.
+    fn stats_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("urmom-stats-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("stats.ron")
+    }
+
+    fn record(kills: u64, deaths: u64) -> PlayerRecord {
+        PlayerRecord {
+            kills,
+            deaths,
+            ..Default::default()
+        }
+    }
+
+    fn file_with(username: &str, record: PlayerRecord) -> StatsFile {
+        let mut stats = StatsFile::default();
+        stats.players.insert(username.to_string(), record);
+        stats
+    }
+
+    #[test]
+    fn merge_adds_every_counter() {
+        let mut a = PlayerRecord {
+            kills: 1,
+            deaths: 2,
+            playtime: 3.,
+            sessions_hosted: 4,
+            sessions_joined: 5,
+        };
+        a.merge(&a.clone());
+        assert_eq!(
+            a,
+            PlayerRecord {
+                kills: 2,
+                deaths: 4,
+                playtime: 6.,
+                sessions_hosted: 8,
+                sessions_joined: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn flush_merges_with_the_file_written_meanwhile() {
+        let path = stats_path("flush");
+        write_stats(&path, &file_with("alice", record(2, 0))).unwrap();
+
+        let mut stats = PlayerStats::new(path.clone());
+        assert_eq!(stats.records()["alice"], record(2, 0));
+        stats.pending.insert("alice".to_string(), record(1, 0));
+        assert_eq!(stats.records()["alice"], record(3, 0));
+
+        // another running game flushed its own session
+        write_stats(&path, &file_with("alice", record(2, 3))).unwrap();
+        stats.flush().unwrap();
+
+        assert_eq!(read_stats(&path).players["alice"], record(3, 3));
+        assert!(stats.pending.is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn flush_without_pending_counters_does_not_write() {
+        let path = stats_path("idle");
+        let mut stats = PlayerStats::new(path.clone());
+        stats.flush().unwrap();
+        assert!(!path.exists());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unknown_and_missing_fields_are_tolerated() {
+        let path = stats_path("fields");
+        fs::write(
+            &path,
+            r#"(version: 2, players: {"bob": (kills: 4, headshots: 1)}, achievements: [])"#,
+        )
+        .unwrap();
+
+        let stats = read_stats(&path);
+        assert_eq!(stats.version, 2);
+        assert_eq!(stats.players["bob"], record(4, 0));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn corrupted_file_is_backed_up_and_recreated() {
+        let path = stats_path("corrupted");
+        fs::write(&path, "not ron at all {").unwrap();
+
+        assert_eq!(read_stats(&path), StatsFile::default());
+        assert_eq!(
+            fs::read_to_string(path.with_extension("ron.bak")).unwrap(),
+            "not ron at all {"
+        );
+        assert_eq!(read_stats(&path), StatsFile::default());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+//! Headless apps to drive a lobby from integration tests, enabled by the `test-utils` feature.
+//!
+//! Apps are only updated by the test, e.g. a host and a client talking over the loopback:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use urmom::test_harness::{wait_for, TestClient, TestServer};
+//!
+//! let mut server = TestServer::new(0);
+//! let mut client = TestClient::connect(server.address, "tester");
+//! assert!(wait_for(
+//!     &mut [&mut server.app, &mut client.app],
+//!     |apps| TestServer::characters_of(apps[0]).len() == 2,
+//!     Duration::from_secs(10),
+//! ));
+//! ```
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::gltf::GltfPlugin;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::scene::ScenePlugin;
+use bevy_egui::EguiPlugin;
+use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin};
+use renet::RenetClient;
+
+use crate::core::{CoreGameState, CorePlugins};
+use crate::lobby::{ClientResource, HostResource, LobbyState};
+use crate::ASSET_DIR;
+
+pub use crate::lobby::{Character, Lobby, PlayerId, ServerMessages};
+
+/// Longest wait for an app to leave the primary asset loading.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Messages received by a [`TestClient`] from its host, serialized as they came.
+#[derive(Debug, Default, Resource)]
+pub struct ReceivedServerMessages(pub Vec<Vec<u8>>);
+
+/// A hosting app listening on the loopback.
+pub struct TestServer {
+    pub app: App,
+    pub address: SocketAddr,
+}
+
+impl TestServer {
+    /// Hosts on `port`, `0` picks a free one so tests can run in parallel.
+    pub fn new(port: u16) -> Self {
+        let mut app = headless_app();
+        app.world.insert_resource(HostResource {
+            address: Some(SocketAddr::from(([127, 0, 0, 1], port)).to_string()),
+            username: Some("host".to_string()),
+            ..default()
+        });
+        app.world
+            .resource_mut::<NextState<LobbyState>>()
+            .set(LobbyState::Host);
+        app.update();
+
+        // the transport keeps the socket it bound, the port is known only now
+        let address = app
+            .world
+            .resource::<HostResource>()
+            .bound_address
+            .expect("host did not bind on the loopback");
+
+        Self { app, address }
+    }
+
+    pub fn tick(&mut self, n: usize) {
+        tick_all(&mut [&mut self.app], n);
+    }
+
+    /// Updates the app until `condition` holds, `false` if `timeout` elapsed first.
+    pub fn wait_for(
+        &mut self,
+        mut condition: impl FnMut(&mut World) -> bool,
+        timeout: Duration,
+    ) -> bool {
+        wait_for(&mut [&mut self.app], |apps| condition(&mut apps[0].world), timeout)
+    }
+
+    pub fn lobby(&self) -> Option<&Lobby> {
+        self.app.world.get_resource::<Lobby>()
+    }
+
+    pub fn characters(&mut self) -> Vec<(Entity, PlayerId)> {
+        Self::characters_of(&mut self.app)
+    }
+
+    /// Characters spawned in `app`, for conditions of [`wait_for`].
+    pub fn characters_of(app: &mut App) -> Vec<(Entity, PlayerId)> {
+        app.world
+            .query::<(Entity, &Character)>()
+            .iter(&app.world)
+            .map(|(entity, character)| (entity, character.id))
+            .collect()
+    }
+}
+
+/// A client app connected to a host.
+pub struct TestClient {
+    pub app: App,
+}
+
+impl TestClient {
+    pub fn connect(address: SocketAddr, username: &str) -> Self {
+        let mut app = headless_app();
+        app.init_resource::<ReceivedServerMessages>();
+        app.world.insert_resource(ClientResource {
+            address: Some(address.to_string()),
+            username: Some(username.to_string()),
+            password: None,
+        });
+        app.world
+            .resource_mut::<NextState<LobbyState>>()
+            .set(LobbyState::Client);
+        app.update();
+
+        Self { app }
+    }
+
+    /// Leaves the lobby, the host is told right away instead of timing the client out.
+    pub fn disconnect(&mut self) {
+        if let Some(mut client) = self.app.world.get_resource_mut::<RenetClient>() {
+            client.disconnect();
+        }
+        // the transport sends the disconnection on the next update
+        self.app.update();
+        self.app
+            .world
+            .resource_mut::<NextState<LobbyState>>()
+            .set(LobbyState::None);
+        self.app.update();
+    }
+
+    pub fn tick(&mut self, n: usize) {
+        tick_all(&mut [&mut self.app], n);
+    }
+
+    /// Updates the app until `condition` holds, `false` if `timeout` elapsed first.
+    pub fn wait_for(
+        &mut self,
+        mut condition: impl FnMut(&mut World) -> bool,
+        timeout: Duration,
+    ) -> bool {
+        wait_for(&mut [&mut self.app], |apps| condition(&mut apps[0].world), timeout)
+    }
+
+    pub fn lobby(&self) -> Option<&Lobby> {
+        self.app.world.get_resource::<Lobby>()
+    }
+
+    pub fn characters(&mut self) -> Vec<(Entity, PlayerId)> {
+        TestServer::characters_of(&mut self.app)
+    }
+
+    /// Messages received from the host so far, oldest first, batches unpacked.
+    pub fn received_messages(&self) -> Vec<ServerMessages> {
+        self.app
+            .world
+            .get_resource::<ReceivedServerMessages>()
+            .map(|received| {
+                received
+                    .0
+                    .iter()
+                    .filter_map(|message| bincode::deserialize(message).ok())
+                    .flat_map(ServerMessages::unbatch)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Updates every app `n` times, in turn so they exchange messages.
+pub fn tick_all(apps: &mut [&mut App], n: usize) {
+    for _ in 0..n {
+        for app in apps.iter_mut() {
+            app.update();
+        }
+    }
+}
+
+/// Updates every app until `condition` holds, `false` if `timeout` elapsed first.
+pub fn wait_for(
+    apps: &mut [&mut App],
+    mut condition: impl FnMut(&mut [&mut App]) -> bool,
+    timeout: Duration,
+) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        tick_all(apps, 1);
+        if condition(apps) {
+            return true;
+        }
+        // the network needs real time to pass, not only updates
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    false
+}
+
+/// The game without window, rendering and audio output, past the primary loading.
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        // a primary window that is never opened, for the systems looking for it
+        WindowPlugin::default(),
+        AssetPlugin {
+            file_path: ASSET_DIR.into(),
+            ..default()
+        },
+        TransformPlugin,
+        HierarchyPlugin,
+        InputPlugin,
+        ScenePlugin,
+        GltfPlugin::default(),
+        EguiPlugin,
+        RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule(),
+    ))
+    // asset types and resources the render plugins would provide
+    .init_asset::<Mesh>()
+    .init_asset::<StandardMaterial>()
+    .init_asset::<Image>()
+    .init_resource::<ScreenshotManager>()
+    .add_plugins(CorePlugins::default());
+
+    let started = wait_for(
+        &mut [&mut app],
+        |apps| *apps[0].world.resource::<State<CoreGameState>>() != CoreGameState::PrimaryLoad,
+        STARTUP_TIMEOUT,
+    );
+    assert!(started, "primary assets were not loaded in {:?}", STARTUP_TIMEOUT);
+    app
+}
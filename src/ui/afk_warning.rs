@@ -0,0 +1,49 @@
+use crate::lobby::afk::AfkNotice;
+use crate::tr;
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+/// Shows the inactivity warning of the host until it runs out or the host withdraws it.
+pub struct AfkWarningPlugins;
+
+impl Plugin for AfkWarningPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (update_afk_notice, afk_warning.run_if(|notice: Res<AfkNotice>| notice.0.is_some()))
+                .chain(),
+        );
+    }
+}
+
+fn update_afk_notice(time: Res<Time>, mut notice: ResMut<AfkNotice>) {
+    let Some(timer) = notice.0.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        notice.0 = None;
+    }
+}
+
+fn afk_warning(mut context: EguiContexts, notice: Res<AfkNotice>) {
+    let Some(timer) = notice.0.as_ref() else {
+        return;
+    };
+
+    egui::Area::new(egui::Id::new("afk_warning"))
+        .anchor(Align2::CENTER_CENTER, [0., 0.])
+        .interactable(false)
+        .show(context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(
+                    egui::RichText::new(tr!(
+                        "afk.warning",
+                        seconds = timer.remaining_secs().ceil()
+                    ))
+                    .size(20.)
+                    .color(egui::Color32::YELLOW),
+                );
+            });
+        });
+}
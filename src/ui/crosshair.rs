@@ -0,0 +1,139 @@
+use crate::component::HealthChangedEvent;
+use crate::core::CoreGameState;
+use crate::lobby::afk::AfkSpectator;
+use crate::lobby::{Character, Lobby, LobbyState, PlayerData};
+use crate::settings::Settings;
+use crate::world::{FreeCamera, Me};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use super::{GameMenuActionState, ViewportRect};
+
+/// Seconds a hit marker is shown, fading out
+const HIT_MARKER_LIFETIME: f32 = 0.3;
+/// Length of the diagonal lines of a hit marker, in points
+const HIT_MARKER_SIZE: f32 = 7.;
+const KILL_MARKER_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 40, 40);
+/// Directions of the lines of the crosshair
+const AXES: [egui::Vec2; 4] = [
+    egui::vec2(1., 0.),
+    egui::vec2(-1., 0.),
+    egui::vec2(0., 1.),
+    egui::vec2(0., -1.),
+];
+/// Directions of the lines of a hit marker
+const DIAGONALS: [egui::Vec2; 4] = [
+    egui::vec2(FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    egui::vec2(-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    egui::vec2(FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+    egui::vec2(-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+];
+
+/// Hit confirmed by the host for the own player, shown on the crosshair.
+#[derive(Debug, Default, Resource)]
+struct HitMarker {
+    /// Seconds left, `0` when none is shown
+    left: f32,
+    kill: bool,
+}
+
+/// Crosshair of the own character, a client-side overlay only.
+///
+/// Hidden in the menus, in the free camera and while spectating.
+pub struct CrosshairPlugins;
+
+impl Plugin for CrosshairPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitMarker>()
+            .add_systems(
+                Update,
+                (
+                    collect_hits,
+                    crosshair.run_if(
+                        in_state(CoreGameState::InGame)
+                            .and_then(in_state(GameMenuActionState::Disable))
+                            .and_then(not(any_with_component::<FreeCamera>))
+                            .and_then(resource_exists::<Settings>),
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(LobbyState::None), clear_hit_marker);
+    }
+}
+
+/// Hits of the own player: the host and single see them happen, a client is told by the host.
+fn collect_hits(
+    time: Res<Time>,
+    lobby: Option<Res<Lobby>>,
+    mut marker: ResMut<HitMarker>,
+    mut health_changed_event: EventReader<HealthChangedEvent>,
+    me_query: Query<Entity, (With<Me>, With<Character>)>,
+) {
+    marker.left = (marker.left - time.delta_seconds()).max(0.);
+    let Ok(me) = me_query.get_single() else {
+        health_changed_event.clear();
+        return;
+    };
+    let Some(lobby) = lobby else {
+        return;
+    };
+    let entity_of = |id| lobby.player(id).and_then(PlayerData::entity);
+    for event in health_changed_event.read() {
+        let Some(attacker) = event.attacker else {
+            continue;
+        };
+        // hurting yourself is not a hit
+        if entity_of(&attacker) != Some(me) || entity_of(&event.id) == Some(me) {
+            continue;
+        }
+        marker.left = HIT_MARKER_LIFETIME;
+        marker.kill = event.health.current <= 0.;
+    }
+}
+
+fn crosshair(
+    mut context: EguiContexts,
+    settings: Res<Settings>,
+    marker: Res<HitMarker>,
+    viewport: Res<ViewportRect>,
+    me_query: Query<(), (With<Me>, With<Character>, Without<AfkSpectator>)>,
+) {
+    let style = settings.crosshair;
+    if !style.enabled || me_query.is_empty() {
+        return;
+    }
+    let painter = context.ctx_mut().layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("crosshair"),
+    ));
+    let center = viewport.center();
+    let [r, g, b] = style.color;
+    let stroke = egui::Stroke::new(style.thickness, egui::Color32::from_rgb(r, g, b));
+
+    for direction in AXES {
+        let start = center + direction * style.gap;
+        painter.line_segment([start, start + direction * style.size], stroke);
+    }
+
+    if marker.left <= 0. {
+        return;
+    }
+    let alpha = marker.left / HIT_MARKER_LIFETIME;
+    let color = if marker.kill {
+        KILL_MARKER_COLOR
+    } else {
+        egui::Color32::from_rgb(r, g, b)
+    };
+    let stroke = egui::Stroke::new(style.thickness, color.gamma_multiply(alpha));
+    let offset = style.gap + style.size / 2.;
+    for direction in DIAGONALS {
+        let start = center + direction * offset;
+        painter.line_segment([start, start + direction * HIT_MARKER_SIZE], stroke);
+    }
+}
+
+fn clear_hit_marker(mut marker: ResMut<HitMarker>) {
+    *marker = HitMarker::default();
+}
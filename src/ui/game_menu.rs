@@ -1,11 +1,29 @@
-use crate::core::CoreGameState;
-use crate::lobby::{ChangeMapLobbyEvent, LobbyState};
-use crate::settings::{ApplySettings, ExemptSettings, Settings};
-use crate::ui::{rich_text, TRANSPARENT};
+use crate::core::{CoreAction, CoreGameState};
+use crate::lobby::afk::AfkRules;
+use crate::lobby::host::ServerSettings;
+use crate::lobby::host_settings::{
+    player_count, ChangeHostSettingsEvent, HostSettings, ServerRules,
+};
+use crate::lobby::session_log::ExportSessionLogEvent;
+use crate::lobby::single::PauseState;
+use crate::lobby::{ChangeMapLobbyEvent, HostResource, Lobby, LobbyState};
+use crate::save::{single_save_path, SaveWorldEvent};
+use crate::settings::{
+    ApplyKeyBindings, ApplySettings, ExemptKeyBindings, ExemptSettings, KeyBindingCapture,
+    KeyBindings, Settings,
+};
+use crate::tr;
+use crate::ui::{
+    audio_settings, camera_settings, crosshair_settings, graphics_settings, language_settings,
+    rich_text, TRANSPARENT,
+};
 use crate::util::i18n::Uniq::Module;
+use crate::world::SimulationConfig;
 use bevy::prelude::*;
+use std::net::SocketAddr;
 use bevy_egui::egui::Align2;
 use bevy_egui::{egui, EguiContexts};
+use strum::IntoEnumIterator;
 
 use super::{MouseGrabState, ViewportRect};
 
@@ -41,8 +59,14 @@ enum WindowState {
     #[default]
     None,
     Settings,
+    Controls,
+    ServerAdmin,
 }
 
+/// Host settings edited in the Server Admin window, applied with its buttons
+#[derive(Debug, Default, Resource)]
+struct ServerAdminDraft(Option<HostSettings>);
+
 pub struct GameMenuPlugins;
 
 impl Plugin for GameMenuPlugins {
@@ -50,6 +74,7 @@ impl Plugin for GameMenuPlugins {
         app.insert_state(WindowState::default())
             .insert_state(GameMenuActionState::default())
             .init_resource::<EguiState>()
+            .init_resource::<ServerAdminDraft>()
             .add_systems(
                 Update,
                 menu.run_if(
@@ -64,7 +89,27 @@ impl Plugin for GameMenuPlugins {
                         .and_then(in_state(WindowState::Settings)),
                 ),
             )
-            .add_systems(OnExit(WindowState::Settings), exempt_setting);
+            .add_systems(
+                Update,
+                controls_window.run_if(
+                    in_state(CoreGameState::InGame)
+                        .and_then(in_state(GameMenuActionState::Enable))
+                        .and_then(in_state(WindowState::Controls)),
+                ),
+            )
+            .add_systems(
+                Update,
+                server_admin_window.run_if(
+                    in_state(CoreGameState::InGame)
+                        .and_then(in_state(GameMenuActionState::Enable))
+                        .and_then(in_state(WindowState::ServerAdmin))
+                        .and_then(in_state(LobbyState::Host))
+                        .and_then(resource_exists::<Lobby>),
+                ),
+            )
+            .add_systems(OnExit(WindowState::Settings), exempt_setting)
+            .add_systems(OnExit(WindowState::Controls), exempt_key_bindings)
+            .add_systems(OnExit(WindowState::ServerAdmin), drop_server_admin_draft);
     }
 }
 
@@ -79,8 +124,19 @@ fn menu(
     ui_frame_rect: ResMut<ViewportRect>,
     mut windows: Query<&Window>,
     mut nex_state_mouse_grab: ResMut<NextState<MouseGrabState>>,
+    pause_state: Res<State<PauseState>>,
+    lobby_state: Res<State<LobbyState>>,
+    host_resource: Res<HostResource>,
+    rules: Res<ServerRules>,
+    mut save_event: EventWriter<SaveWorldEvent>,
+    mut export_event: EventWriter<ExportSessionLogEvent>,
 ) {
     let ctx = context.ctx_mut();
+    let (title, back) = if *pause_state.get() == PauseState::Paused {
+        (tr!("menu.paused"), tr!("menu.resume"))
+    } else {
+        (tr!("menu.menu"), tr!("menu.back"))
+    };
 
     let font = egui::FontId {
         family: egui::FontFamily::Monospace,
@@ -90,7 +146,7 @@ fn menu(
     let window = windows.single_mut();
     let window_size = egui::vec2(window.width(), window.height());
 
-    egui::Window::new(rich_text("Menu".to_string(), Module(&MODULE), &font))
+    egui::Window::new(rich_text(title, Module(&MODULE), &font))
         .frame(*TRANSPARENT)
         .anchor(
             egui::Align2::LEFT_BOTTOM,
@@ -104,21 +160,61 @@ fn menu(
         .movable(false)
         .show(ctx, |ui| {
             if ui
-                .button(rich_text("Back".to_string(), Module(&MODULE), &font))
+                .button(rich_text(back, Module(&MODULE), &font))
                 .clicked()
             {
                 nex_state_mouse_grab.set(MouseGrabState::Enable);
                 next_state_menu_window.set(WindowState::None);
                 next_state_game_menu_action.set(GameMenuActionState::Disable);
             }
+            // only single player games pause, and only they are continued from the main menu
+            if *pause_state.get() == PauseState::Paused
+                && ui
+                    .button(rich_text(tr!("menu.save"), Module(&MODULE), &font))
+                    .clicked()
+            {
+                save_event.send(SaveWorldEvent(single_save_path()));
+            }
             if ui
-                .button(rich_text("Settings".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.settings"), Module(&MODULE), &font))
                 .clicked()
             {
                 next_state_menu_window.set(WindowState::Settings);
             }
             if ui
-                .button(rich_text("Menu".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.controls"), Module(&MODULE), &font))
+                .clicked()
+            {
+                next_state_menu_window.set(WindowState::Controls);
+            }
+            if *lobby_state.get() == LobbyState::Host
+                && ui
+                    .button(rich_text(tr!("menu.server_admin"), Module(&MODULE), &font))
+                    .clicked()
+            {
+                next_state_menu_window.set(WindowState::ServerAdmin);
+            }
+            // the host exports its own in the Server Admin window
+            if *lobby_state.get() == LobbyState::Client
+                && rules.share_session_log
+                && ui
+                    .button(rich_text(tr!("menu.session_log"), Module(&MODULE), &font))
+                    .clicked()
+            {
+                export_event.send(ExportSessionLogEvent);
+            }
+            // only set while hosting
+            if let Some(address) = host_resource.public_address {
+                copyable_address(ui, tr!("menu.lan_address", address = address), address);
+            }
+            let external = host_resource.external_ip.zip(host_resource.public_address);
+            if let Some((ip, address)) = external {
+                // the port forwarded on the router is expected to be the same
+                let address = SocketAddr::new(ip, address.port());
+                copyable_address(ui, tr!("menu.public_address", address = address), address);
+            }
+            if ui
+                .button(rich_text(tr!("menu.menu"), Module(&MODULE), &font))
                 .clicked()
             {
                 state.is_active = false;
@@ -130,6 +226,15 @@ fn menu(
         });
 }
 
+fn copyable_address(ui: &mut egui::Ui, label: String, address: SocketAddr) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        if ui.button(tr!("menu.copy")).clicked() {
+            ui.output_mut(|output| output.copied_text = address.to_string());
+        }
+    });
+}
+
 #[allow(clippy::too_many_arguments)]
 fn settings_window(
     mut next_state_menu_window: ResMut<NextState<WindowState>>,
@@ -154,7 +259,7 @@ fn settings_window(
 
     let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
 
-    egui::Window::new(rich_text("Settings".to_string(), Module(&MODULE), &font))
+    egui::Window::new(rich_text(tr!("menu.settings"), Module(&MODULE), &font))
         .pivot(Align2::CENTER_CENTER)
         .fixed_size(egui_window_size)
         .fixed_pos(center_position)
@@ -162,20 +267,27 @@ fn settings_window(
         .resizable(false)
         .movable(false)
         .show(ctx, |ui| {
-            ui.label(rich_text("Audio: ".to_string(), Module(&MODULE), &font));
+            language_settings(ui, &mut settings);
+            ui.label(rich_text(tr!("settings.audio"), Module(&MODULE), &font));
             ui.horizontal(|ui| {
                 ui.label(rich_text(
-                    format!("Music: {}", settings.music_volume),
+                    tr!("settings.music", volume = settings.music_volume),
                     Module(&MODULE),
                     &font,
                 ));
                 ui.add(egui::Slider::new(&mut settings.music_volume, 0.0..=200.0).text("%"));
             });
+            audio_settings(ui, &mut settings);
+            ui.label(rich_text(tr!("settings.controls"), Module(&MODULE), &font));
+            camera_settings(ui, &mut settings);
+            crosshair_settings(ui, &mut settings);
+            ui.label(rich_text(tr!("settings.graphics"), Module(&MODULE), &font));
+            graphics_settings(ui, &mut settings);
             if *lobby_state.get() != LobbyState::Client {
-                ui.label(rich_text("Map: ".to_string(), Module(&MODULE), &font));
+                ui.label(rich_text(tr!("settings.map"), Module(&MODULE), &font));
                 ui.horizontal(|ui| {
                     egui::ComboBox::from_label(rich_text(
-                        "Map".to_string(),
+                        tr!("settings.map_select"),
                         Module(&MODULE),
                         &font,
                     ))
@@ -196,13 +308,13 @@ fn settings_window(
             }
             ui.horizontal(|ui| {
                 if ui
-                    .button(rich_text("Cansel".to_string(), Module(&MODULE), &font))
+                    .button(rich_text(tr!("menu.cancel"), Module(&MODULE), &font))
                     .clicked()
                 {
                     next_state_menu_window.set(WindowState::None);
                 }
                 if ui
-                    .button(rich_text("Apply".to_string(), Module(&MODULE), &font))
+                    .button(rich_text(tr!("menu.apply"), Module(&MODULE), &font))
                     .clicked()
                 {
                     //if state.selected_map_applied != state.selected_map {
@@ -213,7 +325,7 @@ fn settings_window(
                     settings_applying.send(ApplySettings);
                 }
                 if ui
-                    .button(rich_text("Ok".to_string(), Module(&MODULE), &font))
+                    .button(rich_text(tr!("menu.ok"), Module(&MODULE), &font))
                     .clicked()
                 {
                     //if state.selected_map_applied != state.selected_map {
@@ -228,6 +340,260 @@ fn settings_window(
         });
 }
 
+fn controls_window(
+    mut next_state_menu_window: ResMut<NextState<WindowState>>,
+    mut context: EguiContexts,
+    mut bindings: ResMut<KeyBindings>,
+    mut capture: ResMut<KeyBindingCapture>,
+    ui_frame_rect: ResMut<ViewportRect>,
+    mut bindings_applying: EventWriter<ApplyKeyBindings>,
+) {
+    let frame_size = ui_frame_rect.max - ui_frame_rect.min;
+
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let egui_window_size = egui::vec2(400.0, 200.0);
+
+    let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
+
+    let conflicts = bindings.conflicts();
+
+    egui::Window::new(rich_text(tr!("menu.controls"), Module(&MODULE), &font))
+        .pivot(Align2::CENTER_CENTER)
+        .fixed_size(egui_window_size)
+        .fixed_pos(center_position)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("key_bindings").show(ui, |ui| {
+                for action in CoreAction::iter() {
+                    let label = tr!(&format!("action.{:?}", action));
+                    let mut name = rich_text(label, Module(&MODULE), &font);
+                    if conflicts.contains(&action) {
+                        name = name.color(egui::Color32::RED);
+                    }
+                    ui.label(name);
+
+                    let input = if capture.0 == Some(action) {
+                        "...".to_string()
+                    } else {
+                        bindings
+                            .get(action)
+                            .map(|input| input.to_string())
+                            .unwrap_or_default()
+                    };
+                    if ui.button(egui::RichText::new(input).font(font.clone())).clicked() {
+                        capture.0 = Some(action);
+                    }
+                    ui.end_row();
+                }
+            });
+
+            if capture.0.is_some() {
+                ui.label(rich_text(
+                    tr!("controls.press_key"),
+                    Module(&MODULE),
+                    &font,
+                ));
+            }
+            if !conflicts.is_empty() {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    tr!("controls.conflict"),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .button(rich_text(tr!("menu.cancel"), Module(&MODULE), &font))
+                    .clicked()
+                {
+                    next_state_menu_window.set(WindowState::None);
+                }
+                if ui
+                    .button(rich_text(tr!("menu.default"), Module(&MODULE), &font))
+                    .clicked()
+                {
+                    capture.0 = None;
+                    *bindings = KeyBindings::default();
+                }
+                let resolved = conflicts.is_empty() && capture.0.is_none();
+                if ui
+                    .add_enabled(
+                        resolved,
+                        egui::Button::new(rich_text(tr!("menu.apply"), Module(&MODULE), &font)),
+                    )
+                    .clicked()
+                {
+                    bindings_applying.send(ApplyKeyBindings);
+                }
+                if ui
+                    .add_enabled(
+                        resolved,
+                        egui::Button::new(rich_text(tr!("menu.ok"), Module(&MODULE), &font)),
+                    )
+                    .clicked()
+                {
+                    bindings_applying.send(ApplyKeyBindings);
+                    next_state_menu_window.set(WindowState::None);
+                }
+            });
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn server_admin_window(
+    mut next_state_menu_window: ResMut<NextState<WindowState>>,
+    mut context: EguiContexts,
+    mut draft: ResMut<ServerAdminDraft>,
+    server_settings: Res<ServerSettings>,
+    simulation: Res<SimulationConfig>,
+    lobby: Res<Lobby>,
+    ui_frame_rect: ResMut<ViewportRect>,
+    mut change_event: EventWriter<ChangeHostSettingsEvent>,
+    mut export_event: EventWriter<ExportSessionLogEvent>,
+) {
+    let frame_size = ui_frame_rect.max - ui_frame_rect.min;
+
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let egui_window_size = egui::vec2(400.0, 200.0);
+
+    let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
+
+    let teams = server_settings.teams.is_some();
+    let players = player_count(&lobby);
+    let host_settings = draft
+        .0
+        .get_or_insert_with(|| HostSettings::current(&server_settings, &simulation));
+
+    egui::Window::new(rich_text(tr!("menu.server_admin"), Module(&MODULE), &font))
+        .pivot(Align2::CENTER_CENTER)
+        .fixed_size(egui_window_size)
+        .fixed_pos(center_position)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr!("server_admin.max_players", players = players));
+                ui.add(egui::Slider::new(
+                    &mut host_settings.max_players,
+                    1..=HostSettings::MAX_PLAYERS,
+                ));
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr!("server_admin.net_sync"));
+                ui.add(
+                    egui::Slider::new(&mut host_settings.net_sync_hz, HostSettings::NET_SYNC_RANGE)
+                        .text("Hz"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr!("server_admin.interest_radius"));
+                ui.add(egui::Slider::new(
+                    &mut host_settings.interest_radius,
+                    HostSettings::INTEREST_RADIUS_RANGE,
+                ));
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr!("server_admin.respawn_delay"));
+                ui.add(
+                    egui::Slider::new(
+                        &mut host_settings.respawn_delay,
+                        HostSettings::RESPAWN_DELAY_RANGE,
+                    )
+                    .text("s"),
+                );
+            });
+            ui.horizontal(|ui| {
+                let mut afk = host_settings.afk_timeout.is_some();
+                ui.checkbox(&mut afk, tr!("server_admin.afk_timeout"));
+                let timeout = host_settings
+                    .afk_timeout
+                    .get_or_insert(AfkRules::default().timeout);
+                ui.add_enabled(
+                    afk,
+                    egui::Slider::new(timeout, HostSettings::AFK_TIMEOUT_RANGE).text("s"),
+                );
+                if !afk {
+                    host_settings.afk_timeout = None;
+                }
+            });
+            ui.add_enabled(
+                teams,
+                egui::Checkbox::new(
+                    &mut host_settings.friendly_fire,
+                    tr!("server_admin.friendly_fire"),
+                ),
+            );
+            if !teams {
+                ui.label(tr!("server_admin.no_teams"));
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(
+                    &mut host_settings.share_session_log,
+                    tr!("server_admin.share_session_log"),
+                );
+                if ui.button(tr!("server_admin.export_session_log")).clicked() {
+                    export_event.send(ExportSessionLogEvent);
+                }
+            });
+
+            let valid = host_settings.validate(players);
+            if let Err(err) = &valid {
+                ui.colored_label(egui::Color32::RED, err.as_str());
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .button(rich_text(tr!("menu.cancel"), Module(&MODULE), &font))
+                    .clicked()
+                {
+                    next_state_menu_window.set(WindowState::None);
+                }
+                if ui
+                    .add_enabled(
+                        valid.is_ok(),
+                        egui::Button::new(rich_text(tr!("menu.apply"), Module(&MODULE), &font)),
+                    )
+                    .clicked()
+                {
+                    change_event.send(ChangeHostSettingsEvent(*host_settings));
+                }
+                if ui
+                    .add_enabled(
+                        valid.is_ok(),
+                        egui::Button::new(rich_text(tr!("menu.ok"), Module(&MODULE), &font)),
+                    )
+                    .clicked()
+                {
+                    change_event.send(ChangeHostSettingsEvent(*host_settings));
+                    next_state_menu_window.set(WindowState::None);
+                }
+            });
+        });
+}
+
+fn drop_server_admin_draft(mut draft: ResMut<ServerAdminDraft>) {
+    draft.0 = None;
+}
+
+fn exempt_key_bindings(mut event: EventWriter<ExemptKeyBindings>) {
+    event.send(ExemptKeyBindings);
+}
+
 fn exempt_setting(mut event: EventWriter<ExemptSettings>, _state: ResMut<EguiState>) {
     //state.selected_map = state.selected_map_applied;
     event.send(ExemptSettings);
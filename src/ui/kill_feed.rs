@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+
+use crate::core::CoreGameState;
+use crate::lobby::quick_chat::sender;
+use crate::lobby::{DeathCause, Lobby, LobbyState, PlayerDiedEvent};
+use crate::tr;
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::ViewportRect;
+
+/// Kills shown at once, the oldest is dropped first
+const KILL_FEED_LEN: usize = 5;
+/// Seconds a kill stays in the feed
+const KILL_ENTRY_LIFETIME: f32 = 6.;
+/// Last seconds of [`KILL_ENTRY_LIFETIME`] during which the entry fades out
+const KILL_ENTRY_FADE: f32 = 1.5;
+
+/// Username and color of a player, resolved when the kill happened
+/// so the entry survives the player leaving.
+#[derive(Debug, Clone)]
+struct FeedPlayer {
+    username: String,
+    color: Color,
+}
+
+#[derive(Debug)]
+struct KillEntry {
+    /// `None` for deaths by the level and self-kills
+    killer: Option<FeedPlayer>,
+    victim: FeedPlayer,
+    cause: DeathCause,
+    timer: Timer,
+}
+
+/// Recent kills shown in the top right corner.
+#[derive(Debug, Default, Resource)]
+struct KillFeed {
+    entries: VecDeque<KillEntry>,
+}
+
+pub struct KillFeedPlugins;
+
+impl Plugin for KillFeedPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillFeed>()
+            .add_systems(
+                Update,
+                (
+                    collect_kills,
+                    update_kill_feed,
+                    kill_feed
+                        .run_if(|feed: Res<KillFeed>| !feed.entries.is_empty())
+                        .run_if(in_state(CoreGameState::InGame)),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(LobbyState::None), clear_kill_feed);
+    }
+}
+
+fn collect_kills(
+    mut player_died_event: EventReader<PlayerDiedEvent>,
+    lobby: Option<Res<Lobby>>,
+    mut feed: ResMut<KillFeed>,
+) {
+    for PlayerDiedEvent { id, killer, cause } in player_died_event.read() {
+        let player = |id| {
+            let (username, color) = sender(lobby.as_deref(), id);
+            FeedPlayer { username, color }
+        };
+
+        if feed.entries.len() == KILL_FEED_LEN {
+            feed.entries.pop_front();
+        }
+        feed.entries.push_back(KillEntry {
+            killer: killer.filter(|killer| killer != id).map(|killer| player(&killer)),
+            victim: player(id),
+            cause: *cause,
+            timer: Timer::from_seconds(KILL_ENTRY_LIFETIME, TimerMode::Once),
+        });
+    }
+}
+
+fn update_kill_feed(time: Res<Time>, mut feed: ResMut<KillFeed>) {
+    for entry in feed.entries.iter_mut() {
+        entry.timer.tick(time.delta());
+    }
+    feed.entries.retain(|entry| !entry.timer.finished());
+}
+
+fn kill_feed(mut context: EguiContexts, feed: Res<KillFeed>, ui_frame_rect: Res<ViewportRect>) {
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    egui::Area::new(egui::Id::new("kill_feed"))
+        .anchor(
+            Align2::RIGHT_TOP,
+            [-10., ui_frame_rect.min.y + 40.],
+        )
+        .interactable(false)
+        .show(ctx, |ui| {
+            for entry in feed.entries.iter() {
+                let remaining = entry.timer.remaining_secs();
+                let alpha = (remaining / KILL_ENTRY_FADE).min(1.);
+                let text = |text: String, color: Color| {
+                    let [r, g, b, _] = color.as_rgba_u8();
+                    egui::RichText::new(text)
+                        .font(font.clone())
+                        .color(egui::Color32::from_rgba_unmultiplied(
+                            r,
+                            g,
+                            b,
+                            (alpha * 255.) as u8,
+                        ))
+                };
+                let plain = Color::WHITE;
+
+                ui.horizontal(|ui| match &entry.killer {
+                    Some(killer) => {
+                        ui.label(text(killer.username.clone(), killer.color));
+                        ui.label(text(tr!("kill_feed.killed"), plain));
+                        ui.label(text(entry.victim.username.clone(), entry.victim.color));
+                    }
+                    None => {
+                        ui.label(text(entry.victim.username.clone(), entry.victim.color));
+                        let verb = match entry.cause {
+                            DeathCause::Void => tr!("kill_feed.fell"),
+                            _ => tr!("kill_feed.died"),
+                        };
+                        ui.label(text(verb, plain));
+                    }
+                });
+            }
+        });
+}
+
+fn clear_kill_feed(mut feed: ResMut<KillFeed>) {
+    feed.entries.clear();
+}
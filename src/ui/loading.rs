@@ -0,0 +1,67 @@
+use crate::core::{CoreGameState, LoadingProgress};
+use crate::lobby::{LobbyState, MapLoaderState};
+use crate::tr;
+use crate::ui::rich_text;
+use crate::util::i18n::Uniq::Module;
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::ViewportRect;
+
+lazy_static::lazy_static! {
+    static ref MODULE: &'static str = module_path!().splitn(3, ':').nth(2).unwrap_or(module_path!());
+}
+
+pub struct LoadingScreenPlugins;
+
+impl Plugin for LoadingScreenPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, loading_screen.run_if(is_loading));
+    }
+}
+
+/// `true` while a level is being loaded or a lobby waits for its map to be ready.
+fn is_loading(
+    core_state: Res<State<CoreGameState>>,
+    lobby_state: Res<State<LobbyState>>,
+    map_loader_state: Res<State<MapLoaderState>>,
+) -> bool {
+    matches!(
+        core_state.get(),
+        CoreGameState::LoadCustomLevel | CoreGameState::LoadLobby
+    ) || (*lobby_state.get() != LobbyState::None && *map_loader_state.get() == MapLoaderState::No)
+}
+
+fn loading_screen(
+    mut context: EguiContexts,
+    progress: Res<LoadingProgress>,
+    ui_frame_rect: Res<ViewportRect>,
+) {
+    let frame_size = ui_frame_rect.max - ui_frame_rect.min;
+
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
+
+    egui::Window::new(rich_text(tr!("menu.loading"), Module(&MODULE), &font))
+        .pivot(Align2::CENTER_CENTER)
+        .fixed_pos(center_position)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(rich_text(progress.stage.clone(), Module(&MODULE), &font));
+            });
+            if let Some(fraction) = progress.fraction {
+                ui.add(egui::ProgressBar::new(fraction).show_percentage());
+            }
+        });
+}
@@ -1,7 +1,22 @@
 use crate::core::{LoadLevelEvent, CoreGameState};
-use crate::lobby::{ClientResource, HostResource, LevelCode, LobbyState};
+use crate::crash_report::{open_folder, FreshCrashReport};
+use std::net::SocketAddr;
+
+use crate::lobby::{
+    ClientResource, ConnectInfo, DisconnectNotice, HostResource, LevelCode, LobbyState,
+    NetworkSetupErrorEvent, PlayerId, Username,
+};
+use crate::network::parse_bind_address;
+use crate::lobby::single::RestoredTransform;
+use crate::replay::ReplayPlayback;
+use crate::save::{single_save_path, WorldSave};
 use crate::settings::{ApplySettings, ExemptSettings, Settings};
-use crate::ui::{rich_text, TRANSPARENT};
+use crate::stats::PlayerStats;
+use crate::tr;
+use crate::ui::{
+    audio_settings, camera_settings, crosshair_settings, graphics_settings, language_settings,
+    rich_text, TRANSPARENT,
+};
 use crate::util::i18n::Uniq::Module;
 use bevy::app::AppExit;
 use bevy::prelude::*;
@@ -23,9 +38,13 @@ enum MultiplayerState {
 #[derive(Resource)]
 struct State {
     multiplayer_state: MultiplayerState,
-    host_port: String,
+    host_address: String,
     join_address: String,
     username: String,
+    /// Password of the host to join, empty for none
+    join_password: String,
+    /// Why the last host or join attempt failed
+    connection_error: Option<String>,
 }
 
 #[derive(Default, Debug, Hash, States, PartialEq, Eq, Clone, Copy)]
@@ -34,15 +53,18 @@ enum WindowState {
     None,
     Multiplayer,
     Settings,
+    Stats,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
             multiplayer_state: MultiplayerState::Create,
-            host_port: "5000".to_string(),
+            host_address: "0.0.0.0:5000".to_string(),
             join_address: "127.0.0.1:5000".to_string(),
             username: "noname".to_string(),
+            join_password: String::new(),
+            connection_error: None,
         }
     }
 }
@@ -53,17 +75,51 @@ impl Plugin for MenuPlugins {
     fn build(&self, app: &mut App) {
         app.init_resource::<State>()
             .insert_state(WindowState::default())
-            .add_systems(Update, menu.run_if(in_state(CoreGameState::Hub)))
+            .add_systems(
+                Update,
+                menu.run_if(
+                    in_state(CoreGameState::Hub)
+                        .and_then(in_state(LobbyState::None))
+                        .and_then(not(resource_exists::<ReplayPlayback>)),
+                ),
+            )
             .add_systems(
                 Update,
                 settings_window
                     .run_if(in_state(CoreGameState::Hub).and_then(in_state(WindowState::Settings))),
             )
+            .add_systems(
+                Update,
+                disconnect_notice_window.run_if(
+                    in_state(CoreGameState::Hub)
+                        .and_then(|notice: Res<DisconnectNotice>| notice.0.is_some()),
+                ),
+            )
+            .add_systems(
+                Update,
+                stats_window.run_if(
+                    in_state(CoreGameState::Hub)
+                        .and_then(in_state(WindowState::Stats))
+                        .and_then(resource_exists::<PlayerStats>),
+                ),
+            )
+            .add_systems(
+                Update,
+                crash_report_window.run_if(in_state(CoreGameState::Hub).and_then(
+                    |report: Option<Res<FreshCrashReport>>| {
+                        report.is_some_and(|report| report.0.is_some())
+                    },
+                )),
+            )
             .add_systems(OnExit(WindowState::Settings), exempt_setting)
+            .add_systems(Update, network_setup_errors)
             .add_systems(
                 Update,
-                multiplayer_window
-                    .run_if(in_state(CoreGameState::Hub).and_then(in_state(WindowState::Multiplayer))),
+                multiplayer_window.run_if(
+                    in_state(CoreGameState::Hub)
+                        .and_then(in_state(LobbyState::None))
+                        .and_then(in_state(WindowState::Multiplayer)),
+                ),
             );
     }
 }
@@ -77,6 +133,7 @@ fn menu(
     mut windows: Query<&Window>,
     mut next_state_lobby: ResMut<NextState<LobbyState>>,
     mut load_level_event: EventWriter<LoadLevelEvent>,
+    mut commands: Commands,
 ) {
     let ctx = context.ctx_mut();
 
@@ -88,7 +145,7 @@ fn menu(
     let window = windows.single_mut();
     let window_size = egui::vec2(window.width(), window.height());
 
-    egui::Window::new(rich_text("Menu".to_string(), Module(&MODULE), &font))
+    egui::Window::new(rich_text(tr!("menu.menu"), Module(&MODULE), &font))
         .frame(*TRANSPARENT)
         .anchor(
             egui::Align2::LEFT_BOTTOM,
@@ -102,29 +159,57 @@ fn menu(
         .movable(false)
         .show(ctx, |ui| {
             if ui
-                .button(rich_text("Start".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.single"), Module(&MODULE), &font))
                 .clicked()
             {
-
                 next_state_lobby.set(LobbyState::Single);
                 load_level_event.send(LoadLevelEvent::new(
                     LevelCode::Path("Level2".into()),
                 ));
             }
+            let save_path = single_save_path();
+            if ui
+                .add_enabled(
+                    save_path.exists(),
+                    egui::Button::new(rich_text(tr!("menu.load"), Module(&MODULE), &font)),
+                )
+                .clicked()
+            {
+                match WorldSave::read(&save_path) {
+                    Ok(save) => {
+                        let save = save.or_hub();
+                        if let Some(data) = save.players.get(&PlayerId::HostOrSingle) {
+                            commands.insert_resource(RestoredTransform(
+                                Transform::from_translation(data.position)
+                                    .with_rotation(data.rotation),
+                            ));
+                        }
+                        next_state_lobby.set(LobbyState::Single);
+                        load_level_event.send(LoadLevelEvent::new(save.level));
+                    }
+                    Err(err) => log::error!("Failed to load save {:?}: {}", save_path, err),
+                }
+            }
             if ui
-                .button(rich_text("Multiplayer".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.multiplayer"), Module(&MODULE), &font))
                 .clicked()
             {
                 next_state_menu_window.set(WindowState::Multiplayer);
             }
             if ui
-                .button(rich_text("Settings".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.settings"), Module(&MODULE), &font))
                 .clicked()
             {
                 next_state_menu_window.set(WindowState::Settings);
             }
             if ui
-                .button(rich_text("Exit".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.stats"), Module(&MODULE), &font))
+                .clicked()
+            {
+                next_state_menu_window.set(WindowState::Stats);
+            }
+            if ui
+                .button(rich_text(tr!("menu.exit"), Module(&MODULE), &font))
                 .clicked()
             {
                 exit.send(AppExit);
@@ -161,7 +246,7 @@ fn multiplayer_window(
 
     // ui_base.0.show(ctx, |ui| {
 
-    egui::Window::new(rich_text("Multiplayer".to_string(), Module(&MODULE), &font))
+    egui::Window::new(rich_text(tr!("menu.multiplayer"), Module(&MODULE), &font))
         .pivot(Align2::CENTER_CENTER)
         .fixed_size(egui_window_size)
         .fixed_pos(center_position)
@@ -169,33 +254,51 @@ fn multiplayer_window(
         .resizable(false)
         .movable(false)
         .show(ctx, |ui| {
+            if let Some(error) = state.connection_error.as_ref() {
+                ui.colored_label(egui::Color32::RED, error);
+            }
             match state.multiplayer_state {
                 MultiplayerState::Create => {
                     ui.horizontal(|ui| {
-                        ui.label(rich_text("Create".to_string(), Module(&MODULE), &font));
+                        ui.label(rich_text(tr!("menu.create"), Module(&MODULE), &font));
                         if ui
-                            .button(rich_text("Join".to_string(), Module(&MODULE), &font))
+                            .button(rich_text(tr!("menu.join"), Module(&MODULE), &font))
                             .clicked()
                         {
                             state.multiplayer_state = MultiplayerState::Join;
                         }
                     });
                     ui.horizontal(|ui| {
-                        ui.label("Port:");
-                        ui.text_edit_singleline(&mut state.host_port);
+                        ui.label(tr!("menu.address"));
+                        ui.text_edit_singleline(&mut state.host_address);
                     });
                     ui.horizontal(|ui| {
-                        ui.label("Username:");
+                        ui.label(tr!("menu.username"));
                         ui.text_edit_singleline(&mut state.username);
                     });
+                    let address_valid = is_valid_bind_address(&state.host_address);
+                    let username_valid = Username::is_valid(&state.username);
+                    if !address_valid {
+                        ui.colored_label(egui::Color32::RED, tr!("menu.host_address_hint"));
+                    }
+                    if !username_valid {
+                        ui.colored_label(egui::Color32::RED, username_hint());
+                    }
                     if ui
-                        .button(rich_text("Create".to_string(), Module(&MODULE), &font))
+                        .add_enabled(
+                            address_valid && username_valid,
+                            egui::Button::new(rich_text(
+                                tr!("menu.create"),
+                                Module(&MODULE),
+                                &font,
+                            )),
+                        )
                         .clicked()
                     {
                         nex_state_mouse_grab.set(MouseGrabState::Enable);
-                        host_resource.address =
-                            Some(format!("0.0.0.0:{}", state.host_port.clone()));
+                        host_resource.address = Some(state.host_address.trim().to_string());
                         host_resource.username = Some(state.username.clone());
+                        state.connection_error = None;
                         next_state_menu_window.set(WindowState::None);
 
                         next_state_lobby.set(LobbyState::Host);
@@ -204,37 +307,65 @@ fn multiplayer_window(
                 MultiplayerState::Join => {
                     ui.horizontal(|ui| {
                         if ui
-                            .button(rich_text("Create".to_string(), Module(&MODULE), &font))
+                            .button(rich_text(tr!("menu.create"), Module(&MODULE), &font))
                             .clicked()
                         {
                             state.multiplayer_state = MultiplayerState::Create;
                         }
-                        ui.label(rich_text("Join".to_string(), Module(&MODULE), &font));
+                        ui.label(rich_text(tr!("menu.join"), Module(&MODULE), &font));
                     });
                     ui.horizontal(|ui| {
-                        ui.label("Address:");
+                        ui.label(tr!("menu.address"));
                         ui.text_edit_singleline(&mut state.join_address);
                     });
                     ui.horizontal(|ui| {
-                        ui.label("Username:");
+                        ui.label(tr!("menu.username"));
                         ui.text_edit_singleline(&mut state.username);
                     });
+                    ui.horizontal(|ui| {
+                        ui.label(tr!("menu.password"));
+                        ui.add(egui::TextEdit::singleline(&mut state.join_password).password(true));
+                    });
+                    let address_valid = is_valid_address(&state.join_address);
+                    let username_valid = Username::is_valid(&state.username);
+                    let password_valid = state.join_password.len() <= ConnectInfo::MAX_PASSWORD_LEN;
+                    if !address_valid {
+                        ui.colored_label(egui::Color32::RED, tr!("menu.join_address_hint"));
+                    }
+                    if !username_valid {
+                        ui.colored_label(egui::Color32::RED, username_hint());
+                    }
+                    if !password_valid {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            tr!("menu.password_hint", max = ConnectInfo::MAX_PASSWORD_LEN),
+                        );
+                    }
                     if ui
-                        .button(rich_text("Connect".to_string(), Module(&MODULE), &font))
+                        .add_enabled(
+                            address_valid && username_valid && password_valid,
+                            egui::Button::new(rich_text(
+                                tr!("menu.connect"),
+                                Module(&MODULE),
+                                &font,
+                            )),
+                        )
                         .clicked()
                     {
                         nex_state_mouse_grab.set(MouseGrabState::Enable);
-                        client_resource.address = Some(state.join_address.clone());
+                        client_resource.address = Some(state.join_address.trim().to_string());
                         client_resource.username = Some(state.username.clone());
+                        client_resource.password = (!state.join_password.is_empty())
+                            .then(|| state.join_password.clone());
+                        state.connection_error = None;
                         next_state_menu_window.set(WindowState::None);
-                        state.multiplayer_state = MultiplayerState::Create;
 
                         next_state_lobby.set(LobbyState::Client);
                     }
                 }
             }
             if ui
-                .button(rich_text("Back".to_string(), Module(&MODULE), &font))
+                .button(rich_text(tr!("menu.back"), Module(&MODULE), &font))
                 .clicked()
             {
                 next_state_menu_window.set(WindowState::None);
@@ -242,6 +373,23 @@ fn multiplayer_window(
         });
 }
 
+/// Whether `address` is a socket address the client can connect to.
+fn is_valid_address(address: &str) -> bool {
+    address
+        .trim()
+        .parse::<SocketAddr>()
+        .is_ok_and(|address| address.port() != 0 && !address.ip().is_unspecified())
+}
+
+/// Whether `address` is an address the host can listen on, a missing port is picked by the OS.
+fn is_valid_bind_address(address: &str) -> bool {
+    parse_bind_address(address).is_ok()
+}
+
+fn username_hint() -> String {
+    tr!("menu.username_hint", max = Username::MAX_LEN)
+}
+
 fn settings_window(
     mut next_state_menu_window: ResMut<NextState<WindowState>>,
     mut context: EguiContexts,
@@ -265,7 +413,7 @@ fn settings_window(
 
     let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
 
-    egui::Window::new(rich_text("Settings".to_string(), Module(&MODULE), &font))
+    egui::Window::new(rich_text(tr!("menu.settings"), Module(&MODULE), &font))
         .pivot(Align2::CENTER_CENTER)
         .fixed_size(egui_window_size)
         .fixed_pos(center_position)
@@ -273,25 +421,35 @@ fn settings_window(
         .resizable(false)
         .movable(false)
         .show(ctx, |ui| {
+            language_settings(ui, &mut settings);
             ui.horizontal(|ui| {
-                ui.label(format!("Music: {}", settings.music_volume));
+                ui.label(tr!("settings.music", volume = settings.music_volume));
                 ui.add(egui::Slider::new(&mut settings.music_volume, 0.0..=200.0).text("%"));
             });
+            audio_settings(ui, &mut settings);
+            camera_settings(ui, &mut settings);
+            crosshair_settings(ui, &mut settings);
+            graphics_settings(ui, &mut settings);
+            ui.checkbox(
+                &mut settings.lookup_public_address,
+                tr!("settings.lookup_public_address"),
+            );
+            ui.checkbox(&mut settings.confirm_quit, tr!("settings.confirm_quit"));
             ui.horizontal(|ui| {
                 if ui
-                    .button(rich_text("Cansel".to_string(), Module(&MODULE), &font))
+                    .button(rich_text(tr!("menu.cancel"), Module(&MODULE), &font))
                     .clicked()
                 {
                     next_state_menu_window.set(WindowState::None);
                 }
                 if ui
-                    .button(rich_text("Apply".to_string(), Module(&MODULE), &font))
+                    .button(rich_text(tr!("menu.apply"), Module(&MODULE), &font))
                     .clicked()
                 {
                     settings_applying.send(ApplySettings);
                 }
                 if ui
-                    .button(rich_text("Ok".to_string(), Module(&MODULE), &font))
+                    .button(rich_text(tr!("menu.ok"), Module(&MODULE), &font))
                     .clicked()
                 {
                     settings_applying.send(ApplySettings);
@@ -301,6 +459,147 @@ fn settings_window(
         });
 }
 
+fn stats_window(
+    mut next_state_menu_window: ResMut<NextState<WindowState>>,
+    mut context: EguiContexts,
+    stats: Res<PlayerStats>,
+    ui_frame_rect: ResMut<ViewportRect>,
+) {
+    let frame_size = ui_frame_rect.max - ui_frame_rect.min;
+
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
+
+    egui::Window::new(rich_text(tr!("menu.stats"), Module(&MODULE), &font))
+        .pivot(Align2::CENTER_CENTER)
+        .fixed_pos(center_position)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(ctx, |ui| {
+            let records = stats.records();
+            if records.is_empty() {
+                ui.label(rich_text(tr!("stats.empty"), Module(&MODULE), &font));
+            } else {
+                egui::Grid::new("player_stats").striped(true).show(ui, |ui| {
+                    let headers = ["username", "kills", "deaths", "playtime", "hosted", "joined"];
+                    for header in headers {
+                        let header = tr!(&format!("stats.{}", header));
+                        ui.label(rich_text(header, Module(&MODULE), &font));
+                    }
+                    ui.end_row();
+
+                    for (username, record) in records.iter() {
+                        let playtime = record.playtime as u64;
+                        ui.label(username);
+                        ui.label(record.kills.to_string());
+                        ui.label(record.deaths.to_string());
+                        ui.label(format!("{}h {:02}m", playtime / 3600, playtime / 60 % 60));
+                        ui.label(record.sessions_hosted.to_string());
+                        ui.label(record.sessions_joined.to_string());
+                        ui.end_row();
+                    }
+                });
+            }
+            if ui
+                .button(rich_text(tr!("menu.back"), Module(&MODULE), &font))
+                .clicked()
+            {
+                next_state_menu_window.set(WindowState::None);
+            }
+        });
+}
+
+fn disconnect_notice_window(
+    mut context: EguiContexts,
+    mut notice: ResMut<DisconnectNotice>,
+    ui_frame_rect: ResMut<ViewportRect>,
+) {
+    let frame_size = ui_frame_rect.max - ui_frame_rect.min;
+
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
+
+    egui::Window::new(rich_text(tr!("menu.disconnected"), Module(&MODULE), &font))
+        .pivot(Align2::CENTER_CENTER)
+        .fixed_pos(center_position)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(ctx, |ui| {
+            ui.label(notice.0.clone().unwrap_or_default());
+            if ui
+                .button(rich_text(tr!("menu.ok"), Module(&MODULE), &font))
+                .clicked()
+            {
+                notice.0 = None;
+            }
+        });
+}
+
+/// Offers to open the report of the last crash, once.
+fn crash_report_window(
+    mut context: EguiContexts,
+    mut report: ResMut<FreshCrashReport>,
+    ui_frame_rect: ResMut<ViewportRect>,
+) {
+    let Some(path) = report.0.clone() else {
+        return;
+    };
+    let frame_size = ui_frame_rect.max - ui_frame_rect.min;
+    let center_position = egui::pos2(frame_size.x / 2.0, frame_size.y / 2.0);
+
+    egui::Window::new(tr!("menu.crashed"))
+        .pivot(Align2::CENTER_CENTER)
+        .fixed_pos(center_position)
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(context.ctx_mut(), |ui| {
+            ui.label(tr!("menu.crash_report", path = path.display()));
+            ui.horizontal(|ui| {
+                if ui.button(tr!("menu.open_folder")).clicked() {
+                    if let Some(dir) = path.parent() {
+                        if let Err(err) = open_folder(dir) {
+                            log::warn!("Failed to open {:?}: {}", dir, err);
+                        }
+                    }
+                    report.dismiss();
+                }
+                if ui.button(tr!("menu.ok")).clicked() {
+                    report.dismiss();
+                }
+            });
+        });
+}
+
+/// Reopens the multiplayer window with the error of the failed host or join attempt.
+fn network_setup_errors(
+    mut setup_error_event: EventReader<NetworkSetupErrorEvent>,
+    mut state: ResMut<State>,
+    mut next_state_menu_window: ResMut<NextState<WindowState>>,
+    mut next_state_mouse_grab: ResMut<NextState<MouseGrabState>>,
+) {
+    let Some(NetworkSetupErrorEvent(error)) = setup_error_event.read().last() else {
+        return;
+    };
+    state.connection_error = Some(error.clone());
+    next_state_menu_window.set(WindowState::Multiplayer);
+    next_state_mouse_grab.set(MouseGrabState::Disable);
+}
+
 fn exempt_setting(mut event: EventWriter<ExemptSettings>) {
     event.send(ExemptSettings);
 }
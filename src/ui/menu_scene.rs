@@ -0,0 +1,93 @@
+use crate::core::{CoreGameState, KnownLevel, LoadLevelEvent};
+use crate::lobby::{LevelCode, LobbyState};
+use crate::replay::ReplayPlayback;
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+/// Above the camera a session on the hub leaves behind
+const MENU_CAMERA_ORDER: isize = 4;
+const ORBIT_RADIUS: f32 = 9.;
+const ORBIT_HEIGHT: f32 = 3.5;
+/// Radians per second
+const ORBIT_SPEED: f32 = 0.08;
+
+/// Background of the main menu: a camera slowly going round the hub, which is the level loaded
+/// with the menu. Everything of it is marked, so it is gone once a session starts.
+pub struct MenuScenePlugins;
+
+#[derive(Component)]
+pub struct MenuScene;
+
+#[derive(Component, Default)]
+struct MenuOrbit {
+    angle: f32,
+}
+
+impl Plugin for MenuScenePlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(LobbyState::None), return_to_menu)
+            .add_systems(
+                Update,
+                (
+                    spawn_menu_scene.run_if(not(any_with_component::<MenuScene>)),
+                    orbit_menu_camera,
+                )
+                    .chain()
+                    .run_if(
+                        in_state(CoreGameState::Hub)
+                            .and_then(in_state(LobbyState::None))
+                            .and_then(not(resource_exists::<ReplayPlayback>)),
+                    ),
+            )
+            .add_systems(OnExit(LobbyState::None), despawn_menu_scene)
+            .add_systems(OnExit(CoreGameState::Hub), despawn_menu_scene);
+    }
+}
+
+/// The menu is shown on the hub, a session left on another level goes back to it.
+fn return_to_menu(
+    core_state: Res<State<CoreGameState>>,
+    replay: Option<Res<ReplayPlayback>>,
+    mut load_level_event: EventWriter<LoadLevelEvent>,
+) {
+    let in_menu = matches!(core_state.get(), CoreGameState::PrimaryLoad | CoreGameState::Hub);
+    if in_menu || replay.is_some() {
+        return;
+    }
+    log::info!("Session left on {:?}, back to the menu", core_state.get());
+    load_level_event.send(LoadLevelEvent::new(LevelCode::Known(KnownLevel::Hub)));
+}
+
+fn spawn_menu_scene(mut commands: Commands) {
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                order: MENU_CAMERA_ORDER,
+                ..default()
+            },
+            transform: orbit_transform(0.),
+            ..default()
+        },
+        MenuOrbit::default(),
+        MenuScene,
+        Name::new("MenuCamera"),
+    ));
+}
+
+fn orbit_transform(angle: f32) -> Transform {
+    Transform::from_xyz(ORBIT_RADIUS * angle.cos(), ORBIT_HEIGHT, ORBIT_RADIUS * angle.sin())
+        .looking_at(Vec3::ZERO, Vec3::Y)
+}
+
+fn orbit_menu_camera(time: Res<Time>, mut orbit_query: Query<(&mut MenuOrbit, &mut Transform)>) {
+    for (mut orbit, mut transform) in orbit_query.iter_mut() {
+        orbit.angle = (orbit.angle + ORBIT_SPEED * time.delta_seconds()) % TAU;
+        *transform = orbit_transform(orbit.angle);
+    }
+}
+
+fn despawn_menu_scene(mut commands: Commands, scene_query: Query<Entity, With<MenuScene>>) {
+    for entity in scene_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
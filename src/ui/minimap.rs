@@ -0,0 +1,130 @@
+use crate::core::CoreGameState;
+use crate::lobby::quick_chat::sender;
+use crate::lobby::{Character, Lobby, PlayerView};
+use crate::settings::Settings;
+use crate::world::{Me, SpawnProperty};
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+/// Side of the minimap, in points
+const MINIMAP_SIZE: f32 = 160.;
+/// Space around the spawn points still on the map, in meters
+const MINIMAP_MARGIN: f32 = 15.;
+/// Half side of the map of a level without spawn points, in meters
+const DEFAULT_HALF_EXTENT: f32 = 50.;
+const DOT_RADIUS: f32 = 3.5;
+/// Length of the arrow of the own player, in points
+const ARROW_SIZE: f32 = 9.;
+
+/// Top-down map of the characters in a corner of the screen, a client-side overlay only.
+///
+/// Covers the level as far as its spawn points reach, see [`Settings::show_minimap`].
+pub struct MinimapPlugins;
+
+impl Plugin for MinimapPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            minimap.run_if(
+                in_state(CoreGameState::InGame)
+                    .and_then(resource_exists::<Lobby>)
+                    .and_then(resource_exists::<Settings>)
+                    .and_then(|settings: Res<Settings>| settings.show_minimap),
+            ),
+        );
+    }
+}
+
+/// Square of the level on the ground, `x` by `z`, with the spawn points and a margin around them.
+fn level_bounds(spawn_points: &[Vec3]) -> egui::Rect {
+    let Some(first) = spawn_points.first() else {
+        let side = egui::Vec2::splat(DEFAULT_HALF_EXTENT * 2.);
+        return egui::Rect::from_center_size(egui::Pos2::ZERO, side);
+    };
+    let mut min = first.xz();
+    let mut max = first.xz();
+    for point in spawn_points {
+        min = min.min(point.xz());
+        max = max.max(point.xz());
+    }
+    let center = (min + max) / 2.;
+    let side = (max - min).max_element() + MINIMAP_MARGIN * 2.;
+    egui::Rect::from_center_size(egui::pos2(center.x, center.y), egui::Vec2::splat(side))
+}
+
+fn minimap(
+    mut context: EguiContexts,
+    lobby: Res<Lobby>,
+    spawn_property: Res<SpawnProperty>,
+    character_query: Query<(
+        &Character,
+        &GlobalTransform,
+        &InheritedVisibility,
+        Option<&PlayerView>,
+        Has<Me>,
+    )>,
+) {
+    let bounds = level_bounds(spawn_property.points());
+
+    egui::Area::new(egui::Id::new("minimap"))
+        .anchor(Align2::RIGHT_BOTTOM, [-10., -10.])
+        .interactable(false)
+        .show(context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::Vec2::splat(MINIMAP_SIZE), egui::Sense::hover());
+                let painter = ui.painter_at(rect);
+                let scale = MINIMAP_SIZE / bounds.width();
+                // the players off the level are kept on the edge
+                let to_map = |position: Vec3| {
+                    let offset = (egui::pos2(position.x, position.z) - bounds.min) * scale;
+                    (rect.min + offset).clamp(rect.min, rect.max)
+                };
+
+                let mut own = None;
+                for (character, transform, visibility, view, me) in character_query.iter() {
+                    if me {
+                        own = Some((character.id, to_map(transform.translation()), view.copied()));
+                        continue;
+                    }
+                    // characters out of interest are hidden, their position is stale
+                    if !visibility.get() {
+                        continue;
+                    }
+                    let (_, color) = sender(Some(&lobby), &character.id);
+                    let [r, g, b, _] = color.as_rgba_u8();
+                    painter.circle_filled(
+                        to_map(transform.translation()),
+                        DOT_RADIUS,
+                        egui::Color32::from_rgb(r, g, b),
+                    );
+                }
+
+                // drawn last to stay on top of the others
+                let Some((id, position, view)) = own else {
+                    return;
+                };
+                let (_, color) = sender(Some(&lobby), &id);
+                let [r, g, b, _] = color.as_rgba_u8();
+                let fill = egui::Color32::from_rgb(r, g, b);
+                let outline = egui::Stroke::new(1.5, egui::Color32::WHITE);
+                let forward = view
+                    .map(|view| (view.direction * Vec3::NEG_Z).xz().normalize_or_zero())
+                    .unwrap_or_default();
+                if forward == Vec2::ZERO {
+                    painter.circle(position, DOT_RADIUS + 1., fill, outline);
+                    return;
+                }
+                // `z` grows downwards on the map, as `y` of the screen
+                let forward = egui::vec2(forward.x, forward.y);
+                let side = egui::vec2(-forward.y, forward.x);
+                let arrow = vec![
+                    position + forward * ARROW_SIZE,
+                    position - forward * ARROW_SIZE * 0.5 + side * ARROW_SIZE * 0.6,
+                    position - forward * ARROW_SIZE * 0.5 - side * ARROW_SIZE * 0.6,
+                ];
+                painter.add(egui::Shape::convex_polygon(arrow, fill, outline));
+            });
+        });
+}
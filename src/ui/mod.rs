@@ -1,11 +1,25 @@
 #![allow(clippy::module_inception)]
 
+mod afk_warning;
+mod crosshair;
 mod egui_frame_preset;
 mod game_menu;
+mod kill_feed;
+mod loading;
 mod menu;
+mod menu_scene;
+mod minimap;
+mod nametag;
+mod network_stats;
+mod quick_chat;
+mod quit_dialog;
+mod ready_list;
+mod scoreboard;
+mod screenshot;
 mod ui;
 
 use egui_frame_preset::*;
 pub use game_menu::*;
+pub use scoreboard::SessionScores;
 
 pub use ui::*;
@@ -0,0 +1,99 @@
+use crate::actor::character::HALPH_PLAYER_SIZE;
+use crate::core::CoreGameState;
+use crate::lobby::quick_chat::sender;
+use crate::lobby::{Character, Lobby};
+use crate::settings::Settings;
+use crate::world::{MainCamera, Me};
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::ViewportRect;
+
+/// Height of the tag above the character center
+const NAMETAG_HEIGHT: f32 = HALPH_PLAYER_SIZE + 0.5;
+/// Distance at which tags start to fade
+const NAMETAG_FADE_START: f32 = 20.;
+/// Tags farther than this are not shown
+const NAMETAG_MAX_DISTANCE: f32 = 40.;
+
+pub struct NametagPlugins;
+
+impl Plugin for NametagPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            nametags.run_if(
+                in_state(CoreGameState::InGame)
+                    .and_then(resource_exists::<Lobby>)
+                    .and_then(any_with_component::<MainCamera>),
+            ),
+        );
+    }
+}
+
+fn nametags(
+    mut context: EguiContexts,
+    lobby: Res<Lobby>,
+    settings: Option<Res<Settings>>,
+    ui_frame_rect: Res<ViewportRect>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    character_query: Query<(&Character, &GlobalTransform, &InheritedVisibility, Has<Me>)>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let show_own = settings.is_some_and(|settings| settings.show_own_nametag);
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    for (character, transform, visibility, me) in character_query.iter() {
+        // characters out of interest are hidden, so are their tags
+        if (me && !show_own) || !visibility.get() {
+            continue;
+        }
+
+        let position = transform.translation() + Vec3::Y * NAMETAG_HEIGHT;
+        let distance = camera_transform.translation().distance(position);
+        if distance > NAMETAG_MAX_DISTANCE {
+            continue;
+        }
+        // `None` behind the camera
+        let Some(viewport_position) = camera.world_to_viewport(camera_transform, position) else {
+            continue;
+        };
+        let screen_position =
+            ui_frame_rect.min + egui::vec2(viewport_position.x, viewport_position.y);
+        if !ui_frame_rect.contains(screen_position) {
+            continue;
+        }
+
+        let fade = 1.
+            - ((distance - NAMETAG_FADE_START) / (NAMETAG_MAX_DISTANCE - NAMETAG_FADE_START))
+                .clamp(0., 1.);
+        // looked up every frame, so renames show right away
+        let (username, color) = sender(Some(&lobby), &character.id);
+        let [r, g, b, _] = color.as_rgba_u8();
+
+        egui::Area::new(egui::Id::new(("nametag", character.id)))
+            .pivot(Align2::CENTER_BOTTOM)
+            .fixed_pos(screen_position)
+            .interactable(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    egui::RichText::new(username)
+                        .font(font.clone())
+                        .color(egui::Color32::from_rgba_unmultiplied(
+                            r,
+                            g,
+                            b,
+                            (fade * 255.) as u8,
+                        )),
+                );
+            });
+    }
+}
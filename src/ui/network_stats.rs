@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use crate::core::CoreAction;
+use crate::lobby::Lobby;
+use crate::network::NetStats;
+use crate::ui::rich_text;
+use crate::util::i18n::Uniq::Module;
+use bevy::prelude::*;
+use bevy_controls::contract::InputsContainer;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::ViewportRect;
+
+lazy_static::lazy_static! {
+    static ref MODULE: &'static str = module_path!().splitn(3, ':').nth(2).unwrap_or(module_path!());
+}
+
+/// How often [`NetworkStats`] is sampled
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// [`NetStats`] of this side of the session sampled for the overlay, zeros when not connected.
+///
+/// On the host the rates are summed over the clients and the round trip and loss are averaged.
+/// renet does not count packets, the loss it estimates is shown instead.
+#[derive(Debug, Default, Clone, Copy, Resource)]
+pub struct NetworkStats {
+    pub bytes_sent_per_second: f64,
+    pub bytes_received_per_second: f64,
+    /// Round trip time in seconds
+    pub rtt: f64,
+    /// Estimated fraction of lost packets in `0.0..=1.0`
+    pub packet_loss: f64,
+    /// Clients on the host, `1` on a connected client
+    pub connections: usize,
+}
+
+/// Whether the overlay is shown, toggled with [`CoreAction::NetworkStats`]
+#[derive(Debug, Default, Resource)]
+struct NetworkStatsOverlay(bool);
+
+pub struct NetworkStatsPlugins;
+
+impl Plugin for NetworkStatsPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetworkStats>()
+            .init_resource::<NetworkStatsOverlay>()
+            .add_systems(
+                Update,
+                (
+                    toggle_network_stats.run_if(resource_exists::<Lobby>),
+                    sample_network_stats,
+                    network_stats.run_if(|overlay: Res<NetworkStatsOverlay>| overlay.0),
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn toggle_network_stats(lobby: Res<Lobby>, mut overlay: ResMut<NetworkStatsOverlay>) {
+    let pressed = lobby
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::NetworkStats))
+        .unwrap_or(false);
+    if pressed {
+        overlay.0 = !overlay.0;
+    }
+}
+
+fn sample_network_stats(
+    mut timer: Local<Option<Timer>>,
+    time: Res<Time>,
+    net_stats: Res<NetStats>,
+    mut stats: ResMut<NetworkStats>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::new(SAMPLE_INTERVAL, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let total = net_stats.total;
+    *stats = NetworkStats {
+        bytes_sent_per_second: total.bytes_sent_per_second,
+        bytes_received_per_second: total.bytes_received_per_second,
+        rtt: total.rtt,
+        packet_loss: total.packet_loss,
+        connections: net_stats.connections,
+    };
+}
+
+fn network_stats(
+    mut context: EguiContexts,
+    stats: Res<NetworkStats>,
+    ui_frame_rect: Res<ViewportRect>,
+    #[cfg(feature = "dev")] bandwidth: Res<crate::lobby::sync_policy::SyncBandwidth>,
+) {
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let lines = [
+        format!("connections: {}", stats.connections),
+        format!("in:   {:>8.1} KiB/s", stats.bytes_received_per_second / 1024.),
+        format!("out:  {:>8.1} KiB/s", stats.bytes_sent_per_second / 1024.),
+        format!("rtt:  {:>8.1} ms", stats.rtt * 1000.),
+        format!("loss: {:>8.1} %", stats.packet_loss * 100.),
+    ];
+
+    egui::Area::new(egui::Id::new("network_stats"))
+        .anchor(
+            Align2::LEFT_BOTTOM,
+            [ui_frame_rect.min.x + 10., -10.],
+        )
+        .interactable(false)
+        .show(context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for line in lines {
+                    ui.label(rich_text(line, Module(&MODULE), &font));
+                }
+
+                // snapshot bytes per kind of actor, to measure the sync policies
+                #[cfg(feature = "dev")]
+                if !bandwidth.per_second.is_empty() {
+                    ui.separator();
+                    egui::Grid::new("sync_bandwidth").show(ui, |ui| {
+                        for (kind, bytes) in bandwidth.per_second.iter() {
+                            ui.label(egui::RichText::new(*kind).font(font.clone()));
+                            ui.label(
+                                egui::RichText::new(format!("{:>8.1} KiB/s", bytes / 1024.))
+                                    .font(font.clone()),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                }
+            });
+        });
+}
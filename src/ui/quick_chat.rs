@@ -0,0 +1,93 @@
+use crate::core::CoreGameState;
+use crate::lobby::quick_chat::{ChatFeed, QuickChatKind, QuickChatWheel};
+use crate::ui::{rich_text, TRANSPARENT};
+use crate::util::i18n::Uniq::Module;
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::ViewportRect;
+
+lazy_static::lazy_static! {
+    static ref MODULE: &'static str = module_path!().splitn(3, ':').nth(2).unwrap_or(module_path!());
+}
+
+/// Distance from the wheel center to the segment labels
+const WHEEL_LABEL_RADIUS: f32 = 120.;
+
+pub struct QuickChatUiPlugins;
+
+impl Plugin for QuickChatUiPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                quick_chat_wheel.run_if(|wheel: Res<QuickChatWheel>| wheel.open),
+                chat_feed.run_if(|feed: Res<ChatFeed>| !feed.entries.is_empty()),
+            )
+                .run_if(in_state(CoreGameState::InGame)),
+        );
+    }
+}
+
+fn quick_chat_wheel(
+    mut context: EguiContexts,
+    wheel: Res<QuickChatWheel>,
+    ui_frame_rect: Res<ViewportRect>,
+) {
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    let center = ui_frame_rect.center();
+    let hovered = wheel.hovered();
+    let segment = std::f32::consts::TAU / QuickChatKind::ALL.len() as f32;
+
+    for (index, kind) in QuickChatKind::ALL.iter().enumerate() {
+        // 0 at the top, growing clockwise
+        let angle = segment * index as f32;
+        let position = center + egui::vec2(angle.sin(), -angle.cos()) * WHEEL_LABEL_RADIUS;
+
+        egui::Area::new(egui::Id::new(("quick_chat_wheel", index)))
+            .pivot(Align2::CENTER_CENTER)
+            .fixed_pos(position)
+            .interactable(false)
+            .show(ctx, |ui| {
+                let frame = if Some(*kind) == hovered {
+                    egui::Frame::popup(ui.style())
+                } else {
+                    *TRANSPARENT
+                };
+                frame.show(ui, |ui| {
+                    ui.label(rich_text(kind.text().to_string(), Module(&MODULE), &font));
+                });
+            });
+    }
+}
+
+fn chat_feed(mut context: EguiContexts, feed: Res<ChatFeed>, ui_frame_rect: Res<ViewportRect>) {
+    let ctx = context.ctx_mut();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    egui::Area::new(egui::Id::new("chat_feed"))
+        .anchor(
+            Align2::LEFT_TOP,
+            [ui_frame_rect.min.x + 10., ui_frame_rect.min.y + 10.],
+        )
+        .interactable(false)
+        .show(ctx, |ui| {
+            for entry in feed.entries.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(format!("{}:", entry.from)).font(font.clone()));
+                    ui.label(rich_text(entry.text.clone(), Module(&MODULE), &font));
+                });
+            }
+        });
+}
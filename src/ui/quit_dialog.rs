@@ -0,0 +1,106 @@
+use crate::core::CoreAction;
+use crate::lobby::{Lobby, LobbyState};
+use crate::settings::Settings;
+use crate::tr;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+use bevy_controls::contract::InputsContainer;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+/// Asks before quitting on a closed window or [`CoreAction::Quit`],
+/// unless [`Settings::confirm_quit`] is off.
+///
+/// The window is not closed by bevy itself, see `close_when_requested` in `main`.
+pub struct QuitDialogPlugins;
+
+#[derive(Debug, Default, Resource)]
+struct QuitDialog {
+    open: bool,
+    /// Confirmed while hosting, the app exits once the host is torn down
+    exiting: bool,
+}
+
+impl Plugin for QuitDialogPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<QuitDialog>().add_systems(
+            Update,
+            (
+                request_quit,
+                quit_dialog.run_if(|dialog: Res<QuitDialog>| dialog.open),
+                exit_after_teardown.run_if(|dialog: Res<QuitDialog>| dialog.exiting),
+            )
+                .chain(),
+        );
+    }
+}
+
+fn request_quit(
+    mut close_requested: EventReader<WindowCloseRequested>,
+    inputs_container: Res<Lobby>,
+    settings: Option<Res<Settings>>,
+    mut dialog: ResMut<QuitDialog>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let closed = close_requested.read().count() > 0;
+    let pressed = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::Quit))
+        .unwrap_or(false);
+    if !closed && !pressed {
+        return;
+    }
+
+    // the settings are read at startup, a window closed before that is asked about
+    if settings.map_or(true, |settings| settings.confirm_quit) {
+        dialog.open = true;
+    } else {
+        exit.send(AppExit);
+    }
+}
+
+fn quit_dialog(
+    mut context: EguiContexts,
+    mut dialog: ResMut<QuitDialog>,
+    lobby_state: Res<State<LobbyState>>,
+    mut next_state_lobby: ResMut<NextState<LobbyState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let hosting = *lobby_state.get() == LobbyState::Host;
+
+    egui::Window::new(tr!("quit.title"))
+        .anchor(Align2::CENTER_CENTER, [0., 0.])
+        .collapsible(false)
+        .resizable(false)
+        .movable(false)
+        .show(context.ctx_mut(), |ui| {
+            ui.label(tr!("quit.confirm"));
+            if hosting {
+                ui.label(
+                    egui::RichText::new(tr!("quit.host_warning")).color(egui::Color32::YELLOW),
+                );
+            }
+            ui.horizontal(|ui| {
+                if ui.button(tr!("quit.quit")).clicked() {
+                    dialog.open = false;
+                    if hosting {
+                        // the teardown tells the clients the host stopped the game
+                        next_state_lobby.set(LobbyState::None);
+                        dialog.exiting = true;
+                    } else {
+                        exit.send(AppExit);
+                    }
+                }
+                if ui.button(tr!("menu.cancel")).clicked() {
+                    dialog.open = false;
+                }
+            });
+        });
+}
+
+fn exit_after_teardown(lobby_state: Res<State<LobbyState>>, mut exit: EventWriter<AppExit>) {
+    if *lobby_state.get() != LobbyState::Host {
+        exit.send(AppExit);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::core::CoreGameState;
+use crate::lobby::client::OwnId;
+use crate::lobby::ready::{MatchCountdown, ReadyStates, SendReady};
+use crate::lobby::{Lobby, LobbyState, PlayerId};
+use crate::tr;
+use bevy::prelude::*;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::{MouseGrabState, ViewportRect};
+
+/// Ready state of every player while the host has a match set up, see
+/// [`ReadyPlugins`](crate::lobby::ready::ReadyPlugins). With the mouse free the own state
+/// can be toggled from it.
+pub struct ReadyListPlugins;
+
+impl Plugin for ReadyListPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            ready_list.run_if(
+                in_state(CoreGameState::InGame)
+                    .and_then(resource_exists::<Lobby>)
+                    .and_then(|ready_states: Res<ReadyStates>| !ready_states.0.is_empty()),
+            ),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ready_list(
+    mut context: EguiContexts,
+    lobby: Res<Lobby>,
+    ready_states: Res<ReadyStates>,
+    countdown: Res<MatchCountdown>,
+    lobby_state: Res<State<LobbyState>>,
+    own_id: Option<Res<OwnId>>,
+    mouse_grab_state: Res<State<MouseGrabState>>,
+    ui_frame_rect: Res<ViewportRect>,
+    mut send_ready: EventWriter<SendReady>,
+) {
+    let me = if *lobby_state.get() == LobbyState::Client {
+        own_id.and_then(|own_id| own_id.player_id())
+    } else {
+        Some(PlayerId::HostOrSingle)
+    };
+    // a grabbed mouse cannot click the ready button
+    let interactable = *mouse_grab_state.get() == MouseGrabState::Disable;
+
+    let mut players: Vec<(String, bool)> = ready_states
+        .0
+        .iter()
+        .map(|(id, ready)| {
+            let username = lobby
+                .player(id)
+                .map(|player_data| player_data.username.clone())
+                .unwrap_or_else(|| "?".to_string());
+            (username, *ready)
+        })
+        .collect();
+    players.sort();
+
+    egui::Area::new(egui::Id::new("ready_list"))
+        .anchor(Align2::RIGHT_TOP, [-10., ui_frame_rect.min.y + 60.])
+        .interactable(interactable)
+        .show(context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(egui::RichText::new(tr!("ready.title")).strong());
+                egui::Grid::new("ready_list").num_columns(2).show(ui, |ui| {
+                    for (username, ready) in players.iter() {
+                        ui.label(username);
+                        if *ready {
+                            ui.colored_label(egui::Color32::GREEN, tr!("ready.ready"));
+                        } else {
+                            ui.colored_label(egui::Color32::GRAY, tr!("ready.not_ready"));
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                if let Some(seconds) = countdown.remaining_secs() {
+                    ui.label(
+                        egui::RichText::new(tr!("ready.countdown", seconds = seconds.ceil()))
+                            .color(egui::Color32::YELLOW),
+                    );
+                }
+
+                let Some(me) = me else {
+                    return;
+                };
+                let ready = ready_states.is_ready(&me);
+                let label = if ready { tr!("ready.unset") } else { tr!("ready.set") };
+                if ui.button(label).clicked() {
+                    send_ready.send(SendReady(!ready));
+                }
+                if !interactable {
+                    ui.label(egui::RichText::new(tr!("ready.hint")).color(egui::Color32::GRAY));
+                }
+            });
+        });
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::core::{CoreAction, CoreGameState};
+use crate::lobby::client::OwnId;
+use crate::lobby::host_settings::ServerRules;
+use crate::lobby::quick_chat::MutedPlayers;
+use crate::lobby::ready::MatchStartedEvent;
+use crate::lobby::team::TeamId;
+use crate::lobby::{Lobby, LobbyState, PlayerData, PlayerDiedEvent, PlayerId};
+use crate::tr;
+use bevy::prelude::*;
+use bevy_controls::contract::InputsContainer;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+use renet::ClientId;
+use serde::{Deserialize, Serialize};
+
+use super::{MouseGrabState, ViewportRect};
+
+/// Kills and deaths of every player since the lobby was joined or the last match started.
+#[derive(Debug, Default, Clone, PartialEq, Resource, Serialize, Deserialize)]
+pub struct SessionScores {
+    pub kills: HashMap<PlayerId, u32>,
+    pub deaths: HashMap<PlayerId, u32>,
+}
+
+impl SessionScores {
+    /// Hands the scores of `client_id` to [`PlayerId::HostOrSingle`] when that client
+    /// takes over the session, the scores of the lost host are dropped.
+    pub fn promote_to_host(&mut self, client_id: ClientId) {
+        for scores in [&mut self.kills, &mut self.deaths] {
+            scores.remove(&PlayerId::HostOrSingle);
+            if let Some(score) = scores.remove(&PlayerId::Client(client_id)) {
+                scores.insert(PlayerId::HostOrSingle, score);
+            }
+        }
+    }
+}
+
+/// Players grouped by team, shown while [`CoreAction::Scoreboard`] is held.
+///
+/// With the mouse free, the chat of a player can be muted from it, see [`MutedPlayers`].
+pub struct ScoreboardPlugins;
+
+impl Plugin for ScoreboardPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SessionScores>()
+            .add_systems(
+                Update,
+                (
+                    count_scores,
+                    scoreboard
+                        .run_if(resource_exists::<Lobby>)
+                        .run_if(in_state(CoreGameState::InGame)),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(LobbyState::None), clear_scores)
+            .add_systems(Update, clear_scores.run_if(on_event::<MatchStartedEvent>()));
+    }
+}
+
+fn count_scores(
+    mut player_died_event: EventReader<PlayerDiedEvent>,
+    mut scores: ResMut<SessionScores>,
+) {
+    for PlayerDiedEvent { id, killer, .. } in player_died_event.read() {
+        *scores.deaths.entry(*id).or_default() += 1;
+        // self-kills are not counted as kills
+        if let Some(killer) = killer.filter(|killer| killer != id) {
+            *scores.kills.entry(killer).or_default() += 1;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn scoreboard(
+    mut context: EguiContexts,
+    lobby: Res<Lobby>,
+    scores: Res<SessionScores>,
+    rules: Res<ServerRules>,
+    lobby_state: Res<State<LobbyState>>,
+    own_id: Option<Res<OwnId>>,
+    mouse_grab_state: Res<State<MouseGrabState>>,
+    mut muted: ResMut<MutedPlayers>,
+    ui_frame_rect: Res<ViewportRect>,
+) {
+    let held = lobby
+        .me()
+        .and_then(|inputs| inputs.get_pressed(CoreAction::Scoreboard))
+        .unwrap_or(false);
+    if !held {
+        return;
+    }
+
+    // the host and single are `me`, clients have themselves in the players
+    let mut players: Vec<(PlayerId, &PlayerData)> = lobby
+        .players
+        .iter()
+        .map(|(id, player_data)| (*id, player_data))
+        .collect();
+    let me = if *lobby_state.get() != LobbyState::Client {
+        players.push((PlayerId::HostOrSingle, &lobby.me));
+        Some(PlayerId::HostOrSingle)
+    } else {
+        own_id.and_then(|own_id| own_id.player_id())
+    };
+    // a grabbed mouse cannot click the mute buttons
+    let interactable = *mouse_grab_state.get() == MouseGrabState::Disable;
+    players.sort_by(|(a, a_data), (b, b_data)| {
+        let kills = |id: &PlayerId| scores.kills.get(id).copied().unwrap_or(0);
+        kills(b).cmp(&kills(a)).then(a_data.username.cmp(&b_data.username))
+    });
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+    let text = |text: String, color: Color| {
+        let [r, g, b, _] = color.as_rgba_u8();
+        egui::RichText::new(text)
+            .font(font.clone())
+            .color(egui::Color32::from_rgb(r, g, b))
+    };
+
+    let teams = players.iter().any(|(_, player_data)| player_data.team.is_some());
+    let groups: Vec<(Option<TeamId>, String)> = if teams {
+        TeamId::ALL
+            .iter()
+            .map(|team| (Some(*team), team.name().to_string()))
+            .chain(std::iter::once((None, tr!("scoreboard.no_team"))))
+            .collect()
+    } else {
+        vec![(None, tr!("scoreboard.players"))]
+    };
+
+    egui::Area::new(egui::Id::new("scoreboard"))
+        .anchor(Align2::CENTER_TOP, [0., ui_frame_rect.min.y + 60.])
+        .interactable(interactable)
+        .show(context.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for (team, title) in groups.iter() {
+                    let members: Vec<_> = players
+                        .iter()
+                        .filter(|(_, player_data)| player_data.team == *team)
+                        .collect();
+                    // empty teams stay listed so they can be joined, players without a team only if any
+                    if team.is_none() && teams && members.is_empty() {
+                        continue;
+                    }
+
+                    ui.label(text(title.clone(), Color::WHITE).strong());
+                    egui::Grid::new(("scoreboard", title.as_str()))
+                        .num_columns(4)
+                        .min_col_width(60.)
+                        .show(ui, |ui| {
+                            ui.label(text(tr!("scoreboard.player"), Color::GRAY));
+                            ui.label(text(tr!("scoreboard.kills"), Color::GRAY));
+                            ui.label(text(tr!("scoreboard.deaths"), Color::GRAY));
+                            ui.label(text(tr!("scoreboard.chat"), Color::GRAY));
+                            ui.end_row();
+                            for (id, player_data) in members {
+                                let count = |map: &HashMap<PlayerId, u32>| {
+                                    map.get(id).copied().unwrap_or(0).to_string()
+                                };
+                                ui.label(text(player_data.username.clone(), player_data.color));
+                                ui.label(text(count(&scores.kills), Color::WHITE));
+                                ui.label(text(count(&scores.deaths), Color::WHITE));
+                                if Some(*id) == me {
+                                    ui.label("");
+                                } else {
+                                    let label = if muted.is_muted(id) { "unmute" } else { "mute" };
+                                    let label = tr!(&format!("scoreboard.{}", label));
+                                    let button = text(label, Color::WHITE);
+                                    if ui.small_button(button).clicked() {
+                                        muted.toggle(*id);
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    ui.add_space(6.);
+                }
+                if teams && rules.friendly_fire {
+                    ui.label(text(tr!("scoreboard.friendly_fire"), Color::GRAY));
+                }
+                if rules.respawn_delay > 0. {
+                    let seconds = format!("{:.1}", rules.respawn_delay);
+                    ui.label(text(tr!("scoreboard.respawn_delay", seconds = seconds), Color::GRAY));
+                }
+                if !interactable {
+                    ui.label(text(tr!("scoreboard.mute_hint"), Color::GRAY));
+                }
+            });
+        });
+}
+
+fn clear_scores(mut scores: ResMut<SessionScores>) {
+    *scores = SessionScores::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promoted_client_keeps_its_scores_as_host() {
+        let old_host = ClientId::from_raw(1);
+        let other = ClientId::from_raw(2);
+        let mut scores = SessionScores::default();
+        scores.kills.insert(PlayerId::HostOrSingle, 5);
+        scores.kills.insert(PlayerId::Client(old_host), 3);
+        scores.kills.insert(PlayerId::Client(other), 1);
+        scores.deaths.insert(PlayerId::Client(old_host), 2);
+
+        scores.promote_to_host(old_host);
+
+        assert_eq!(scores.kills.get(&PlayerId::HostOrSingle), Some(&3));
+        assert_eq!(scores.kills.get(&PlayerId::Client(old_host)), None);
+        assert_eq!(scores.kills.get(&PlayerId::Client(other)), Some(&1));
+        assert_eq!(scores.deaths.get(&PlayerId::HostOrSingle), Some(&2));
+    }
+
+    #[test]
+    fn promoted_client_without_scores_starts_clean() {
+        let mut scores = SessionScores::default();
+        scores.deaths.insert(PlayerId::HostOrSingle, 4);
+
+        scores.promote_to_host(ClientId::from_raw(1));
+
+        assert!(scores.deaths.is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::core::CoreAction;
+use crate::lobby::Lobby;
+use crate::tr;
+use crate::ui::rich_text;
+use crate::util::i18n::Uniq::Module;
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::tasks::IoTaskPool;
+use bevy::window::PrimaryWindow;
+use bevy_controls::contract::InputsContainer;
+use bevy_egui::egui::Align2;
+use bevy_egui::{egui, EguiContexts};
+
+use super::ViewportRect;
+
+lazy_static::lazy_static! {
+    static ref MODULE: &'static str = module_path!().splitn(3, ':').nth(2).unwrap_or(module_path!());
+}
+
+/// Seconds the confirmation stays on screen
+const NOTICE_DURATION: f32 = 3.;
+
+/// Results of screenshots written in the background
+#[derive(Debug, Default, Resource, Clone)]
+struct SavedScreenshots(Arc<Mutex<Vec<Result<PathBuf, String>>>>);
+
+/// Confirmation shown after a screenshot
+#[derive(Debug, Default, Resource)]
+struct ScreenshotNotice(Option<(String, Timer)>);
+
+pub struct ScreenshotPlugins;
+
+impl Plugin for ScreenshotPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SavedScreenshots>()
+            .init_resource::<ScreenshotNotice>()
+            .add_systems(
+                Update,
+                (
+                    take_screenshot.run_if(resource_exists::<Lobby>),
+                    collect_saved,
+                    screenshot_notice.run_if(|notice: Res<ScreenshotNotice>| notice.0.is_some()),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// `screenshots/` next to the executable
+fn screenshot_dir() -> PathBuf {
+    env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+        .join("screenshots")
+}
+
+fn take_screenshot(
+    inputs_container: Res<Lobby>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    saved: Res<SavedScreenshots>,
+) {
+    let pressed = inputs_container
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::Screenshot))
+        .unwrap_or(false);
+    if !pressed {
+        return;
+    }
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = screenshot_dir().join(format!("screenshot-{}.png", timestamp));
+    let results = saved.0.clone();
+
+    let requested = screenshot_manager.take_screenshot(window, move |image| {
+        // encoding is slow, keep it off the thread that hands the frame over
+        IoTaskPool::get()
+            .spawn(async move {
+                let result = save_png(&image, &path).map(|_| path);
+                results.lock().unwrap().push(result);
+            })
+            .detach();
+    });
+    if requested.is_err() {
+        log::warn!("A screenshot is already requested this frame");
+    }
+}
+
+fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    let size = image.size();
+    let mut data = image.data.clone();
+    match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {}
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+            for pixel in data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        format => return Err(format!("unsupported texture format {:?}", format)),
+    }
+    // the alpha channel of the swap chain is not meaningful
+    for pixel in data.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+
+    let buffer = image::RgbaImage::from_raw(size.x, size.y, data)
+        .ok_or_else(|| "screenshot size does not match its data".to_string())?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    buffer.save(path).map_err(|err| err.to_string())
+}
+
+fn collect_saved(saved: Res<SavedScreenshots>, mut notice: ResMut<ScreenshotNotice>) {
+    let results = std::mem::take(&mut *saved.0.lock().unwrap());
+    for result in results {
+        let text = match result {
+            Ok(path) => {
+                log::info!("Screenshot saved to {:?}", path);
+                tr!("screenshot.saved", path = path.display())
+            }
+            Err(err) => {
+                log::error!("Failed to save screenshot: {}", err);
+                tr!("screenshot.failed")
+            }
+        };
+        notice.0 = Some((text, Timer::from_seconds(NOTICE_DURATION, TimerMode::Once)));
+    }
+}
+
+fn screenshot_notice(
+    mut context: EguiContexts,
+    mut notice: ResMut<ScreenshotNotice>,
+    time: Res<Time>,
+    ui_frame_rect: Res<ViewportRect>,
+) {
+    let Some((text, timer)) = notice.0.as_mut() else {
+        return;
+    };
+    if timer.tick(time.delta()).finished() {
+        notice.0 = None;
+        return;
+    }
+    let text = text.clone();
+
+    let font = egui::FontId {
+        family: egui::FontFamily::Monospace,
+        ..default()
+    };
+
+    egui::Area::new(egui::Id::new("screenshot_notice"))
+        .anchor(
+            Align2::RIGHT_TOP,
+            [-10., ui_frame_rect.min.y + 10.],
+        )
+        .interactable(false)
+        .show(context.ctx_mut(), |ui| {
+            ui.label(rich_text(text, Module(&MODULE), &font));
+        });
+}
@@ -1,9 +1,28 @@
 use crate::core::CoreGameState;
+use crate::ui::afk_warning::AfkWarningPlugins;
+use crate::ui::crosshair::CrosshairPlugins;
+use crate::settings::{
+    ApplySettings, CameraMode, CrosshairSettings, DisplayRevert, FullscreenSetting,
+    GraphicsSettings, MsaaSetting, PresentModeSetting, Settings,
+};
+use crate::ui::kill_feed::KillFeedPlugins;
+use crate::ui::loading::LoadingScreenPlugins;
 use crate::ui::menu::MenuPlugins;
-use crate::util::i18n::{trans, Uniq};
+use crate::ui::menu_scene::MenuScenePlugins;
+use crate::ui::minimap::MinimapPlugins;
+use crate::ui::nametag::NametagPlugins;
+use crate::ui::network_stats::NetworkStatsPlugins;
+use crate::ui::quick_chat::QuickChatUiPlugins;
+use crate::ui::quit_dialog::QuitDialogPlugins;
+use crate::ui::ready_list::ReadyListPlugins;
+use crate::ui::scoreboard::ScoreboardPlugins;
+use crate::ui::screenshot::ScreenshotPlugins;
+use crate::tr;
+use crate::util::i18n::{trans, Language, Uniq};
 use bevy::prelude::*;
 use bevy::window::CursorGrabMode;
-use bevy_egui::egui::FontId;
+use bevy_egui::egui::{Align2, FontId};
+use bevy_egui::EguiContexts;
 use std::sync::Arc;
 
 use super::GameMenuPlugins;
@@ -53,12 +72,35 @@ impl Plugin for UiPlugins {
         app
             .insert_state(MouseGrabState::default())
             .init_resource::<ViewportRect>()
-            .add_plugins((MenuPlugins, GameMenuPlugins))
+            .add_plugins((
+                MenuPlugins,
+                MenuScenePlugins,
+                GameMenuPlugins,
+                LoadingScreenPlugins,
+                QuickChatUiPlugins,
+                ScreenshotPlugins,
+                KillFeedPlugins,
+                CrosshairPlugins,
+                NametagPlugins,
+                MinimapPlugins,
+                NetworkStatsPlugins,
+                ScoreboardPlugins,
+                AfkWarningPlugins,
+                QuitDialogPlugins,
+                ReadyListPlugins,
+            ))
             .add_systems(OnEnter(CoreGameState::InGame), grab_mouse_on)
             .add_systems(OnEnter(MouseGrabState::Enable), grab_mouse_on)
             .add_systems(OnEnter(MouseGrabState::Disable), grab_mouse_off)
             // Not to friecventrly?
-            .add_systems(Update, frame_rect);
+            .add_systems(Update, frame_rect)
+            .add_systems(
+                Update,
+                display_revert_prompt.run_if(
+                    resource_exists::<Settings>
+                        .and_then(|revert: Res<DisplayRevert>| revert.remaining_secs().is_some()),
+                ),
+            );
     }
 }
 
@@ -99,6 +141,163 @@ pub fn rich_text(text: impl Into<Arc<String>>, uniq: Uniq, font: &FontId) -> egu
     egui::WidgetText::RichText(egui::RichText::new(trans(text.into(), uniq)).font(font.clone()))
 }
 
+/// Language of the texts shared by the settings windows, see [`Locale`](crate::util::i18n::Locale)
+pub fn language_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    egui::ComboBox::from_label(tr!("settings.language"))
+        .selected_text(settings.language.label())
+        .show_ui(ui, |ui| {
+            for language in Language::ALL {
+                ui.selectable_value(&mut settings.language, language, language.label());
+            }
+        });
+}
+
+/// Master and effect volumes shared by the settings windows, the music slider is next to them
+pub fn audio_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label(tr!("settings.master", volume = format!("{:.0}", settings.master_volume)));
+        ui.add(egui::Slider::new(&mut settings.master_volume, Settings::VOLUME_RANGE).text("%"));
+    });
+    ui.horizontal(|ui| {
+        ui.label(tr!("settings.effects", volume = format!("{:.0}", settings.effects_volume)));
+        ui.add(egui::Slider::new(&mut settings.effects_volume, Settings::VOLUME_RANGE).text("%"));
+    });
+}
+
+/// Camera and mouse controls shared by the settings windows, changes are applied live
+pub fn camera_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    ui.horizontal(|ui| {
+        ui.label(tr!("settings.fov", fov = format!("{:.0}", settings.fov)));
+        ui.add(egui::Slider::new(&mut settings.fov, Settings::FOV_RANGE).text("°"));
+    });
+    ui.horizontal(|ui| {
+        let sensitivity = format!("{:.2}", settings.mouse_sensitivity);
+        ui.label(tr!("settings.sensitivity", sensitivity = sensitivity));
+        ui.add(egui::Slider::new(
+            &mut settings.mouse_sensitivity,
+            Settings::SENSITIVITY_RANGE,
+        ));
+    });
+    ui.checkbox(&mut settings.invert_y, tr!("settings.invert_y"));
+    ui.checkbox(&mut settings.reduce_motion, tr!("settings.reduce_motion"));
+    egui::ComboBox::from_label(tr!("settings.camera"))
+        .selected_text(settings.camera_mode.label())
+        .show_ui(ui, |ui| {
+            for mode in CameraMode::ALL {
+                ui.selectable_value(&mut settings.camera_mode, mode, mode.label());
+            }
+        });
+}
+
+/// Crosshair shared by the settings windows, changes are applied live
+pub fn crosshair_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    let crosshair = &mut settings.crosshair;
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut crosshair.enabled, tr!("settings.crosshair"));
+        ui.color_edit_button_srgb(&mut crosshair.color);
+    });
+    if !crosshair.enabled {
+        return;
+    }
+    ui.horizontal(|ui| {
+        ui.label(tr!("settings.crosshair_size"));
+        ui.add(egui::Slider::new(&mut crosshair.size, CrosshairSettings::SIZE_RANGE));
+    });
+    ui.horizontal(|ui| {
+        ui.label(tr!("settings.crosshair_gap"));
+        ui.add(egui::Slider::new(&mut crosshair.gap, CrosshairSettings::GAP_RANGE));
+    });
+    ui.horizontal(|ui| {
+        ui.label(tr!("settings.crosshair_thickness"));
+        ui.add(egui::Slider::new(
+            &mut crosshair.thickness,
+            CrosshairSettings::THICKNESS_RANGE,
+        ));
+    });
+}
+
+/// Window settings shared by the settings windows, changes are applied live
+pub fn graphics_settings(ui: &mut egui::Ui, settings: &mut Settings) {
+    egui::ComboBox::from_label(tr!("settings.present_mode"))
+        .selected_text(settings.present_mode.label())
+        .show_ui(ui, |ui| {
+            for mode in PresentModeSetting::ALL {
+                ui.selectable_value(&mut settings.present_mode, mode, mode.label());
+            }
+        });
+
+    let mut limited = settings.fps_limit.is_some();
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut limited, tr!("settings.limit_fps")).changed() {
+            settings.fps_limit = limited.then_some(Settings::DEFAULT_FPS_LIMIT);
+        }
+        if let Some(fps_limit) = settings.fps_limit.as_mut() {
+            ui.add(egui::Slider::new(fps_limit, Settings::FPS_LIMIT_RANGE).text("fps"));
+        }
+    });
+    ui.checkbox(&mut settings.show_own_nametag, tr!("settings.own_nametag"));
+    ui.checkbox(&mut settings.show_minimap, tr!("settings.minimap"));
+
+    let graphics = &mut settings.graphics;
+    egui::ComboBox::from_label(tr!("settings.window_mode"))
+        .selected_text(graphics.fullscreen.label())
+        .show_ui(ui, |ui| {
+            for mode in FullscreenSetting::ALL {
+                ui.selectable_value(&mut graphics.fullscreen, mode, mode.label());
+            }
+        });
+    let resolution_label = |resolution: Option<(u32, u32)>| match resolution {
+        Some((width, height)) => format!("{}x{}", width, height),
+        None => tr!("settings.default_resolution"),
+    };
+    egui::ComboBox::from_label(tr!("settings.resolution"))
+        .selected_text(resolution_label(graphics.resolution))
+        .show_ui(ui, |ui| {
+            let resolutions = GraphicsSettings::RESOLUTIONS.into_iter().map(Some);
+            for resolution in std::iter::once(None).chain(resolutions) {
+                let label = resolution_label(resolution);
+                ui.selectable_value(&mut graphics.resolution, resolution, label);
+            }
+        });
+    egui::ComboBox::from_label(tr!("settings.msaa"))
+        .selected_text(graphics.msaa.label())
+        .show_ui(ui, |ui| {
+            for msaa in MsaaSetting::ALL {
+                ui.selectable_value(&mut graphics.msaa, msaa, msaa.label());
+            }
+        });
+}
+
+/// Asks to confirm a new window mode or size before [`DisplayRevert`] restores the previous one
+fn display_revert_prompt(
+    mut context: EguiContexts,
+    mut settings: ResMut<Settings>,
+    mut revert: ResMut<DisplayRevert>,
+    mut apply_settings: EventWriter<ApplySettings>,
+) {
+    let Some(remaining) = revert.remaining_secs() else {
+        return;
+    };
+
+    egui::Window::new(tr!("display.keep_title"))
+        .anchor(Align2::CENTER_TOP, [0., 40.])
+        .collapsible(false)
+        .resizable(false)
+        .show(context.ctx_mut(), |ui| {
+            ui.label(tr!("display.reverting", seconds = remaining.ceil()));
+            ui.horizontal(|ui| {
+                if ui.button(tr!("display.keep")).clicked() {
+                    revert.keep(&settings.graphics);
+                    apply_settings.send(ApplySettings);
+                }
+                if ui.button(tr!("display.revert")).clicked() {
+                    revert.revert(&mut settings.graphics);
+                    apply_settings.send(ApplySettings);
+                }
+            });
+        });
+}
+
 //pub fn rich_text(text: impl Into<Arc<String>>, uniq: Uniq, font: &FontId) -> egui::RichText {
 //    egui::RichText::new(trans(text.into(), uniq)).font(font.clone())
 //}
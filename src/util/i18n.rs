@@ -1,17 +1,153 @@
 use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
 use sha2::Sha256;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use bevy::ecs::system::Resource;
+
+use crate::ASSET_DIR;
 
 const HASH_LENGTH: usize = 25;
+/// File of a language directory with its strings, a RON map of keys to text
+const CATALOG_FILE: &str = "main.ron";
 
-#[allow(dead_code)]
+/// Languages with a catalog in `ASSET_DIR/locales/<code>/`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum Language {
     Ru,
+    /// Also the fallback of the keys missing in another language
+    #[default]
     En,
 }
 
+impl Language {
+    pub const ALL: [Language; 2] = [Language::En, Language::Ru];
+
+    /// Name of the directory of the catalog
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::Ru => "ru",
+            Language::En => "en",
+        }
+    }
+
+    /// Name of the language in itself, so it can be found whatever language is shown
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::Ru => "Русский",
+            Language::En => "English",
+        }
+    }
+}
+
+type Catalog = HashMap<String, String>;
+
+/// Translated user-facing strings of the chosen [`Language`], see [`tr!`](crate::tr).
+///
+/// Keys missing in the language are taken from English, keys missing there too are shown as is.
+#[derive(Debug, Clone, Resource)]
+pub struct Locale {
+    pub language: Language,
+    catalog: Arc<Catalog>,
+    fallback: Arc<Catalog>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::load(Language::default())
+    }
+}
+
+impl Locale {
+    /// Reads the catalog of `language` and the English one, a missing file leaves its keys out.
+    pub fn load(language: Language) -> Self {
+        let fallback = Arc::new(read_catalog(Language::En));
+        let catalog = match language {
+            Language::En => fallback.clone(),
+            _ => Arc::new(read_catalog(language)),
+        };
+        Self {
+            language,
+            catalog,
+            fallback,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> String {
+        self.catalog
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .cloned()
+            .unwrap_or_else(|| key.to_string())
+    }
+
+    /// Text of `key` with every `{name}` replaced by the value of `name` in `args`.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let mut text = self.get(key);
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+}
+
+/// Path of the catalog of `language`
+pub fn catalog_path(language: Language) -> PathBuf {
+    PathBuf::from(ASSET_DIR)
+        .join("locales")
+        .join(language.code())
+        .join(CATALOG_FILE)
+}
+
+fn read_catalog(language: Language) -> Catalog {
+    let path = catalog_path(language);
+    let catalog = fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|content| ron::from_str(&content).map_err(|err| err.to_string()));
+    match catalog {
+        Ok(catalog) => catalog,
+        Err(err) => {
+            log::error!("Failed to read locale catalog ({:?}): {}", path, err);
+            Catalog::new()
+        }
+    }
+}
+
 lazy_static::lazy_static! {
-    pub static ref LANGUAGE: Language = Language::En;
+    /// Copy of the [`Locale`] resource for the UI helpers called without the world
+    static ref CURRENT: RwLock<Locale> = RwLock::new(Locale::default());
+}
+
+/// Makes `locale` the one used by [`tr!`](crate::tr).
+pub fn set_locale(locale: Locale) {
+    *CURRENT.write().unwrap_or_else(PoisonError::into_inner) = locale;
+}
+
+/// See [`tr!`](crate::tr).
+pub fn tr(key: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    CURRENT
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .format(key, args)
+}
+
+/// Translated text of a key in the current [`Locale`], e.g.
+/// `tr!("menu.single")` or `tr!("chat.connected", name = username)` for `{name}` in the text.
+#[macro_export]
+macro_rules! tr {
+    ($key:expr) => {
+        $crate::util::i18n::tr($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::util::i18n::tr(
+            $key,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
 }
 
 #[allow(dead_code)]
@@ -21,14 +157,14 @@ pub enum Uniq {
 }
 
 pub fn trans(text: Arc<String>, uniq: Uniq) -> String {
-    let _id = match uniq {
-        Uniq::Module(module) => hash_string(text.as_str(), module, HASH_LENGTH),
-        Uniq::Id(id) => id.to_string(),
-    };
-
-    // TODO get from hashmap translated data or translate by internet resources
-
-    text.to_string()
+    match uniq {
+        // TODO the hashed module ids are not in the catalogs, use `tr!` with a key
+        Uniq::Module(module) => {
+            let _id = hash_string(text.as_str(), module, HASH_LENGTH);
+            text.to_string()
+        }
+        Uniq::Id(key) => tr(key, &[]),
+    }
 }
 
 fn hash_string(input: &str, key: &str, hash_length: usize) -> String {
@@ -39,3 +175,90 @@ fn hash_string(input: &str, key: &str, hash_length: usize) -> String {
     let truncated = &result[..hash_length.min(result.len())];
     hex::encode(truncated)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use strum::IntoEnumIterator;
+
+    use crate::core::CoreAction;
+
+    /// Literal keys of every `tr!` in the `.rs` files under `dir`.
+    fn literal_keys(dir: &Path, keys: &mut Vec<(PathBuf, String)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                literal_keys(&path, keys);
+                continue;
+            }
+            if path.extension().map_or(true, |extension| extension != "rs") {
+                continue;
+            }
+            let content = fs::read_to_string(&path).unwrap();
+            for (at, _) in content.match_indices("tr!(") {
+                let Some(rest) = content[at + 4..].trim_start().strip_prefix('"') else {
+                    continue;
+                };
+                let Some(end) = rest.find('"') else {
+                    continue;
+                };
+                keys.push((path.clone(), rest[..end].to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn every_key_used_in_code_is_in_english() {
+        let english = read_catalog(Language::En);
+        assert!(!english.is_empty(), "{:?} is empty", catalog_path(Language::En));
+
+        let mut keys = Vec::new();
+        literal_keys(Path::new("src"), &mut keys);
+        // the doc of `tr!` itself shows example keys
+        keys.retain(|(path, _)| !path.ends_with("util/i18n.rs"));
+        assert!(!keys.is_empty());
+
+        // keys built at runtime
+        for label in ["mute", "unmute"] {
+            keys.push(("src/ui/scoreboard.rs".into(), format!("scoreboard.{label}")));
+        }
+        for header in ["username", "kills", "deaths", "playtime", "hosted", "joined"] {
+            keys.push(("src/ui/menu.rs".into(), format!("stats.{header}")));
+        }
+        for action in CoreAction::iter() {
+            keys.push(("src/ui/game_menu.rs".into(), format!("action.{action:?}")));
+        }
+
+        let missing: Vec<_> = keys
+            .iter()
+            .filter(|(_, key)| !english.contains_key(key))
+            .collect();
+        assert!(missing.is_empty(), "missing in English: {:?}", missing);
+    }
+
+    #[test]
+    fn every_catalog_parses() {
+        for language in Language::ALL {
+            let content = fs::read_to_string(catalog_path(language)).unwrap();
+            let catalog: Catalog = ron::from_str(&content).unwrap();
+            assert!(!catalog.is_empty(), "{:?}", language);
+        }
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_english_then_the_key() {
+        let locale = Locale {
+            language: Language::Ru,
+            catalog: Arc::new(Catalog::from([("a".to_string(), "а".to_string())])),
+            fallback: Arc::new(Catalog::from([
+                ("a".to_string(), "a".to_string()),
+                ("b".to_string(), "b {name}".to_string()),
+            ])),
+        };
+        assert_eq!(locale.get("a"), "а");
+        assert_eq!(locale.get("b"), "b {name}");
+        assert_eq!(locale.get("c"), "c");
+        assert_eq!(locale.format("b", &[("name", &"x")]), "b x");
+    }
+}
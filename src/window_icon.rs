@@ -0,0 +1,226 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::ecs::system::NonSend;
+use bevy::log::warn;
+use bevy::winit::WinitWindows;
+use image::imageops::FilterType;
+use winit::window::Icon;
+
+use crate::ASSET_DIR;
+
+/// The file name of the icon override
+pub const ICON_PATH: &str = "icon.png";
+/// Sizes of the icon set overrides, `icon-<size>.png` next to [`ICON_PATH`]
+pub const ICON_SIZES: [u32; 4] = [16, 32, 64, 256];
+/// Sizes of the title bar and taskbar icons of Windows at a scale factor of 1
+#[cfg(target_os = "windows")]
+const SMALL_ICON_SIZE: u32 = 16;
+#[cfg(target_os = "windows")]
+const LARGE_ICON_SIZE: u32 = 32;
+
+/// Icon compiled into the binary, used when no override is found or it cannot be decoded.
+/// Named apart from [`ICON_PATH`] so an override in the asset directory is never this one
+const EMBEDDED_ICON: &[u8] = include_bytes!("../asset/default_icon.png");
+
+/// Decoded icon ready to be passed to [`Icon::from_rgba`]
+#[derive(Clone)]
+pub struct IconData {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl IconData {
+    fn from_image(image: image::DynamicImage) -> Self {
+        let image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+        Self {
+            rgba: image.into_raw(),
+            width,
+            height,
+        }
+    }
+}
+
+/// Icons of several sizes, smallest first. A missing size is scaled from the closest larger one.
+pub struct IconSet(Vec<IconData>);
+
+impl IconSet {
+    fn new(mut icons: Vec<IconData>) -> Self {
+        icons.sort_by_key(|icon| icon.width);
+        Self(icons)
+    }
+
+    /// Icon of `size` pixels, scaled down from the smallest larger one,
+    /// or up from the largest one if none is large enough.
+    pub fn sized(&self, size: u32) -> IconData {
+        let source = self
+            .0
+            .iter()
+            .find(|icon| icon.width >= size)
+            .unwrap_or_else(|| self.largest());
+        if source.width == size && source.height == size {
+            return source.clone();
+        }
+        source.resized(size)
+    }
+
+    pub fn largest(&self) -> &IconData {
+        self.0.last().expect("Icon set is never empty")
+    }
+}
+
+impl IconData {
+    fn resized(&self, size: u32) -> Self {
+        let image = image::RgbaImage::from_raw(self.width, self.height, self.rgba.clone())
+            .expect("Icon data matches its size");
+        let image = image::imageops::resize(&image, size, size, FilterType::Lanczos3);
+        Self {
+            rgba: image.into_raw(),
+            width: size,
+            height: size,
+        }
+    }
+}
+
+/// Directories where icon overrides are looked up, in priority order:
+/// the executable directory first, then the asset directory.
+pub fn icon_search_dirs(exe_dir: Option<&Path>, asset_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::with_capacity(2);
+    if let Some(exe_dir) = exe_dir {
+        dirs.push(exe_dir.to_path_buf());
+    }
+    dirs.push(asset_dir.to_path_buf());
+    dirs
+}
+
+/// Decodes the icon compiled into the binary.
+pub fn embedded_icon() -> IconData {
+    let image = image::load_from_memory(EMBEDDED_ICON).expect("Embedded icon must be a valid image");
+    IconData::from_image(image)
+}
+
+/// Loads the decodable icons of the first directory having any, falling back to the embedded one.
+///
+/// A directory has `icon-<size>.png` files of the [`ICON_SIZES`], a single [`ICON_PATH`] or both.
+pub fn load_icon_set(dirs: &[PathBuf]) -> IconSet {
+    for dir in dirs {
+        let icons: Vec<IconData> = ICON_SIZES
+            .iter()
+            .map(|size| dir.join(format!("icon-{}.png", size)))
+            .chain(std::iter::once(dir.join(ICON_PATH)))
+            .filter(|path| path.exists())
+            .filter_map(|path| match image::open(&path) {
+                Ok(image) => Some(IconData::from_image(image)),
+                Err(err) => {
+                    warn!("Failed to decode icon {:?}: {}", path, err);
+                    None
+                }
+            })
+            .collect();
+        if !icons.is_empty() {
+            return IconSet::new(icons);
+        }
+    }
+
+    IconSet::new(vec![embedded_icon()])
+}
+
+fn winit_icon(icon_data: IconData) -> Option<Icon> {
+    match Icon::from_rgba(icon_data.rgba, icon_data.width, icon_data.height) {
+        Ok(icon) => Some(icon),
+        Err(err) => {
+            warn!("Failed to create window icon: {}", err);
+            None
+        }
+    }
+}
+
+pub fn set_window_icon(windows: NonSend<WinitWindows>) {
+    let exe_dir = env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(Path::to_path_buf));
+    let asset_dir = FileAssetReader::get_base_path().join(ASSET_DIR);
+    let icons = load_icon_set(&icon_search_dirs(exe_dir.as_deref(), &asset_dir));
+
+    for window in windows.windows.values() {
+        // the title bar shows the small icon, the taskbar and alt-tab the large one
+        #[cfg(target_os = "windows")]
+        {
+            use winit::platform::windows::WindowExtWindows;
+
+            let scaled = |size: u32| (size as f64 * window.scale_factor()).round() as u32;
+            window.set_window_icon(winit_icon(icons.sized(scaled(SMALL_ICON_SIZE))));
+            window.set_taskbar_icon(winit_icon(icons.sized(scaled(LARGE_ICON_SIZE))));
+        }
+        // the window managers scale the icon themselves, the largest one stays the sharpest
+        #[cfg(not(target_os = "windows"))]
+        window.set_window_icon(winit_icon(icons.largest().clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Empty directory of the test, removed by the previous run if any.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("urmom-window-icon-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a `size` square icon override into `dir`.
+    fn write_icon(dir: &Path, size: u32) {
+        image::RgbaImage::new(size, size)
+            .save(dir.join(ICON_PATH))
+            .unwrap();
+    }
+
+    #[test]
+    fn exe_dir_is_searched_before_asset_dir() {
+        let exe_dir = Path::new("exe");
+        let asset_dir = Path::new("asset");
+        assert_eq!(
+            icon_search_dirs(Some(exe_dir), asset_dir),
+            vec![exe_dir.to_path_buf(), asset_dir.to_path_buf()]
+        );
+        assert_eq!(icon_search_dirs(None, asset_dir), vec![asset_dir.to_path_buf()]);
+    }
+
+    #[test]
+    fn override_resolution_order() {
+        let exe_dir = test_dir("exe");
+        let asset_dir = test_dir("asset");
+        let dirs = icon_search_dirs(Some(&exe_dir), &asset_dir);
+        let embedded = embedded_icon();
+
+        // nothing on disk
+        assert_eq!(load_icon_set(&dirs).largest().width, embedded.width);
+
+        write_icon(&asset_dir, 3);
+        assert_eq!(load_icon_set(&dirs).largest().width, 3);
+
+        write_icon(&exe_dir, 5);
+        assert_eq!(load_icon_set(&dirs).largest().width, 5);
+
+        fs::remove_dir_all(&exe_dir).unwrap();
+        fs::remove_dir_all(&asset_dir).unwrap();
+    }
+
+    #[test]
+    fn undecodable_override_falls_back_to_embedded() {
+        let dir = test_dir("broken");
+        fs::write(dir.join(ICON_PATH), b"not a png").unwrap();
+
+        let icons = load_icon_set(&[dir.clone()]);
+        assert_eq!(icons.largest().width, embedded_icon().width);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,289 @@
+use std::f32::consts::TAU;
+
+use bevy::pbr::{FogFalloff, FogSettings};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{CoreGameState, CurrentLevel};
+use crate::level::level_environment;
+
+/// Up of the sun light, never along its path round the x axis
+const SUN_UP: Vec3 = Vec3::X;
+
+/// Colors and brightness of the lights and the sky, the part a [`DayCycle`] animates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Lighting {
+    pub sun_color: Color,
+    /// In lux, see [`DirectionalLight::illuminance`]
+    pub sun_illuminance: f32,
+    pub ambient_color: Color,
+    pub ambient_brightness: f32,
+    pub clear_color: Color,
+}
+
+impl Lighting {
+    fn lerp(&self, other: &Lighting, t: f32) -> Lighting {
+        Lighting {
+            sun_color: lerp_color(self.sun_color, other.sun_color, t),
+            sun_illuminance: lerp(self.sun_illuminance, other.sun_illuminance, t),
+            ambient_color: lerp_color(self.ambient_color, other.ambient_color, t),
+            ambient_brightness: lerp(self.ambient_brightness, other.ambient_brightness, t),
+            clear_color: lerp_color(self.clear_color, other.clear_color, t),
+        }
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn lerp_color(from: Color, to: Color, t: f32) -> Color {
+    let from = Vec4::from(from.as_linear_rgba_f32());
+    let to = Vec4::from(to.as_linear_rgba_f32());
+    let [r, g, b, a] = from.lerp(to, t).to_array();
+    Color::rgba_linear(r, g, b, a)
+}
+
+/// Linear distance fog of a level.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FogDefinition {
+    pub color: Color,
+    pub start: f32,
+    pub end: f32,
+}
+
+/// Animated time of day, the sun turns once per `period`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayCycle {
+    /// Seconds of a whole day
+    pub period: f32,
+    /// Phase a level starts at, in `0..1`
+    pub start: f32,
+    /// [`Lighting`] at phases in `0..1` in ascending order, blended in between
+    /// and from the last one back to the first one
+    pub keyframes: Vec<(f32, Lighting)>,
+}
+
+impl DayCycle {
+    fn lighting(&self, phase: f32) -> Option<Lighting> {
+        let (first, last) = (self.keyframes.first()?, self.keyframes.last()?);
+        // the last one before `phase`, or the last one of the previous day
+        let index = self.keyframes.iter().rposition(|(at, _)| *at <= phase);
+        let (from, to) = match index {
+            Some(index) => (&self.keyframes[index], self.keyframes.get(index + 1).unwrap_or(first)),
+            None => (last, first),
+        };
+        let span = (to.0 - from.0).rem_euclid(1.);
+        let t = if span > 0. {
+            (phase - from.0).rem_euclid(1.) / span
+        } else {
+            0.
+        };
+        Some(from.1.lerp(&to.1, t))
+    }
+}
+
+/// Lights, sky and fog of the loaded level, see [`level_environment`].
+///
+/// Set when a level is loaded like the [`LevelPhysics`](super::LevelPhysics),
+/// clients get the host ones with the [`ServerMessages::ChangeMap`](crate::lobby::ServerMessages)
+/// and when they join.
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+pub struct EnvironmentSettings {
+    /// Direction the sun shines in at phase `0` of the [`DayCycle`], no sun without it
+    pub sun_direction: Option<Vec3>,
+    /// Lighting without a day cycle
+    pub lighting: Lighting,
+    pub fog: Option<FogDefinition>,
+    pub day_cycle: Option<DayCycle>,
+}
+
+impl Default for EnvironmentSettings {
+    /// What a level without an environment of its own is rendered with
+    fn default() -> Self {
+        let ambient = AmbientLight::default();
+        Self {
+            sun_direction: None,
+            lighting: Lighting {
+                sun_color: Color::WHITE,
+                sun_illuminance: 0.,
+                ambient_color: ambient.color,
+                ambient_brightness: ambient.brightness,
+                clear_color: ClearColor::default().0,
+            },
+            fog: None,
+            day_cycle: None,
+        }
+    }
+}
+
+impl EnvironmentSettings {
+    /// Phase a level with these settings starts at.
+    pub fn start_phase(&self) -> f32 {
+        self.day_cycle.as_ref().map_or(0., |day_cycle| day_cycle.start)
+    }
+
+    pub fn lighting(&self, phase: f32) -> Lighting {
+        self.day_cycle
+            .as_ref()
+            .and_then(|day_cycle| day_cycle.lighting(phase))
+            .unwrap_or(self.lighting)
+    }
+
+    /// Direction of the sun at `phase`, it goes round the x axis over a day.
+    pub fn sun_direction(&self, phase: f32) -> Option<Vec3> {
+        let direction = self.sun_direction?.normalize_or_zero();
+        match self.day_cycle {
+            Some(_) => Some(Quat::from_rotation_x(phase * TAU) * direction),
+            None => Some(direction),
+        }
+    }
+}
+
+/// Time of day of the [`DayCycle`] in `0..1`, `0` without one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Resource, Deref)]
+pub struct DayPhase(pub f32);
+
+/// Changes the environment of the running level, replicated to the clients by the host.
+#[derive(Debug, Clone, Event)]
+pub struct ChangeEnvironmentEvent {
+    pub environment: EnvironmentSettings,
+    pub phase: f32,
+}
+
+/// Directional light of the [`EnvironmentSettings`], spawned while a level is shown.
+#[derive(Debug, Component)]
+pub struct Sun;
+
+pub struct EnvironmentPlugins;
+
+impl Plugin for EnvironmentPlugins {
+    fn build(&self, app: &mut App) {
+        let shown = in_state(CoreGameState::Hub).or_else(in_state(CoreGameState::InGame));
+        // the game starts in the hub without a level being loaded
+        app.insert_resource(level_environment(&CurrentLevel::default().0))
+            .init_resource::<DayPhase>()
+            .add_event::<ChangeEnvironmentEvent>()
+            .add_systems(OnEnter(CoreGameState::Hub), apply_environment)
+            .add_systems(OnEnter(CoreGameState::InGame), apply_environment)
+            // a change arriving while a level loads is kept for it
+            .add_systems(Update, change_environment)
+            .add_systems(
+                Update,
+                (
+                    advance_day_cycle,
+                    apply_environment.run_if(
+                        resource_changed::<EnvironmentSettings>
+                            .or_else(resource_changed::<DayPhase>)
+                            .or_else(camera_added),
+                    ),
+                )
+                    .chain()
+                    .after(change_environment)
+                    .run_if(shown),
+            )
+            .add_systems(OnExit(CoreGameState::Hub), revert_environment)
+            .add_systems(OnExit(CoreGameState::InGame), revert_environment);
+    }
+}
+
+fn change_environment(
+    mut change_environment_event: EventReader<ChangeEnvironmentEvent>,
+    mut environment: ResMut<EnvironmentSettings>,
+    mut day_phase: ResMut<DayPhase>,
+) {
+    if let Some(event) = change_environment_event.read().last() {
+        log::info!("Environment changed, day phase {}", event.phase);
+        environment.set_if_neq(event.environment.clone());
+        day_phase.0 = event.phase.rem_euclid(1.);
+    }
+}
+
+fn advance_day_cycle(
+    time: Res<Time>,
+    environment: Res<EnvironmentSettings>,
+    mut day_phase: ResMut<DayPhase>,
+) {
+    let Some(day_cycle) = environment.day_cycle.as_ref().filter(|cycle| cycle.period > 0.) else {
+        return;
+    };
+    day_phase.0 = (day_phase.0 + time.delta_seconds() / day_cycle.period).rem_euclid(1.);
+}
+
+/// Cameras spawned after the level, e.g. the tied camera, get the fog too
+fn camera_added(query: Query<(), Added<Camera3d>>) -> bool {
+    !query.is_empty()
+}
+
+fn apply_environment(
+    mut commands: Commands,
+    environment: Res<EnvironmentSettings>,
+    day_phase: Res<DayPhase>,
+    mut ambient: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sun_query: Query<(Entity, &mut DirectionalLight, &mut Transform), With<Sun>>,
+    camera_query: Query<Entity, With<Camera3d>>,
+) {
+    let lighting = environment.lighting(**day_phase);
+    ambient.color = lighting.ambient_color;
+    ambient.brightness = lighting.ambient_brightness;
+    clear_color.0 = lighting.clear_color;
+
+    match (environment.sun_direction(**day_phase), sun_query.get_single_mut()) {
+        (Some(direction), Ok((_, mut light, mut transform))) => {
+            light.color = lighting.sun_color;
+            light.illuminance = lighting.sun_illuminance;
+            *transform = Transform::IDENTITY.looking_to(direction, SUN_UP);
+        }
+        (Some(direction), Err(_)) => {
+            commands.spawn((
+                DirectionalLightBundle {
+                    directional_light: DirectionalLight {
+                        color: lighting.sun_color,
+                        illuminance: lighting.sun_illuminance,
+                        shadows_enabled: true,
+                        ..default()
+                    },
+                    transform: Transform::IDENTITY.looking_to(direction, SUN_UP),
+                    ..default()
+                },
+                Sun,
+                Name::new("Sun"),
+            ));
+        }
+        (None, Ok((entity, ..))) => commands.entity(entity).despawn_recursive(),
+        (None, Err(_)) => {}
+    }
+
+    for entity in camera_query.iter() {
+        match environment.fog {
+            Some(fog) => commands.entity(entity).insert(FogSettings {
+                color: fog.color,
+                falloff: FogFalloff::Linear {
+                    start: fog.start,
+                    end: fog.end,
+                },
+                ..default()
+            }),
+            None => commands.entity(entity).remove::<FogSettings>(),
+        };
+    }
+}
+
+/// Leaves the defaults behind for the next level, which applies its own environment.
+fn revert_environment(
+    mut commands: Commands,
+    mut ambient: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+    sun_query: Query<Entity, With<Sun>>,
+    camera_query: Query<Entity, With<FogSettings>>,
+) {
+    *ambient = AmbientLight::default();
+    *clear_color = ClearColor::default();
+    for entity in sun_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in camera_query.iter() {
+        commands.entity(entity).remove::<FogSettings>();
+    }
+}
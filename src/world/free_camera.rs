@@ -0,0 +1,191 @@
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy_controls::contract::InputsContainer;
+
+use crate::actor::character::TiedCamera;
+use crate::core::CoreAction;
+use crate::lobby::{Lobby, LobbyState};
+use crate::settings::Settings;
+use crate::ui::MouseGrabState;
+
+use super::MainCamera;
+
+/// Units per second the free camera flies at
+const FREE_CAMERA_SPEED: f32 = 10.;
+/// Speed multiplier while [`CoreAction::Sprint`] is held
+const FREE_CAMERA_SPRINT: f32 = 4.;
+/// Radians the view turns per pixel of mouse motion at [`Settings::mouse_sensitivity`] `1`
+const MOUSE_RADIANS_PER_PIXEL: f32 = 0.002;
+/// Pitch limit, so the view never flips over the poles
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// Camera flying on its own, used by spectators and in replays.
+///
+/// While it exists it is the [`MainCamera`], the camera it took over is kept as [`DetachedCamera`].
+#[derive(Component, Debug, Default)]
+pub struct FreeCamera {
+    yaw: f32,
+    pitch: f32,
+}
+
+impl FreeCamera {
+    fn from_rotation(rotation: Quat) -> Self {
+        let (yaw, pitch, _) = rotation.to_euler(EulerRot::YXZ);
+        Self { yaw, pitch }
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.)
+    }
+}
+
+/// Camera replaced by the [`FreeCamera`], made the [`MainCamera`] again when the free camera is left
+#[derive(Component, Debug)]
+pub struct DetachedCamera;
+
+pub struct FreeCameraPlugins;
+
+impl Plugin for FreeCameraPlugins {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_free_camera.run_if(resource_exists::<Lobby>),
+                leave_for_tied_camera.run_if(any_with_component::<FreeCamera>),
+                (
+                    fly_free_camera.run_if(resource_exists::<Lobby>),
+                    look_free_camera.run_if(in_state(MouseGrabState::Enable)),
+                )
+                    .run_if(any_with_component::<FreeCamera>),
+            )
+                .chain()
+                .run_if(not(in_state(LobbyState::None))),
+        )
+        .add_systems(OnEnter(LobbyState::None), despawn_free_camera);
+    }
+}
+
+fn toggle_free_camera(
+    mut commands: Commands,
+    lobby: Res<Lobby>,
+    free_camera_query: Query<Entity, With<FreeCamera>>,
+    mut main_camera_query: Query<
+        (Entity, &GlobalTransform, &mut Camera),
+        (With<MainCamera>, Without<FreeCamera>),
+    >,
+    mut detached_camera_query: Query<
+        (Entity, &mut Camera),
+        (With<DetachedCamera>, Without<MainCamera>),
+    >,
+) {
+    let pressed = lobby
+        .me()
+        .and_then(|inputs| inputs.get_just_pressed(CoreAction::FreeCamera))
+        .unwrap_or(false);
+    if !pressed {
+        return;
+    }
+
+    if !free_camera_query.is_empty() {
+        for entity in free_camera_query.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        for (entity, mut camera) in detached_camera_query.iter_mut() {
+            camera.is_active = true;
+            commands
+                .entity(entity)
+                .remove::<DetachedCamera>()
+                .insert(MainCamera);
+        }
+        return;
+    }
+
+    // start where the current view is, so the switch does not jump
+    let transform = match main_camera_query.get_single_mut() {
+        Ok((entity, global_transform, mut camera)) => {
+            camera.is_active = false;
+            commands
+                .entity(entity)
+                .remove::<MainCamera>()
+                .insert(DetachedCamera);
+            global_transform.compute_transform()
+        }
+        Err(_) => Transform::from_xyz(0., 10., 20.).looking_at(Vec3::ZERO, Vec3::Y),
+    };
+
+    commands.spawn((
+        Camera3dBundle {
+            transform,
+            ..default()
+        },
+        FreeCamera::from_rotation(transform.rotation),
+        MainCamera,
+        Name::new("FreeCamera"),
+    ));
+}
+
+/// A new tied camera means the player controls a character again, it takes over the free one.
+fn leave_for_tied_camera(
+    mut commands: Commands,
+    tied_camera_query: Query<(), Added<TiedCamera>>,
+    free_camera_query: Query<Entity, With<FreeCamera>>,
+    detached_camera_query: Query<Entity, With<DetachedCamera>>,
+) {
+    if tied_camera_query.is_empty() {
+        return;
+    }
+    for entity in free_camera_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    // the old tied camera may still be around, it stays inactive
+    for entity in detached_camera_query.iter() {
+        commands.entity(entity).remove::<DetachedCamera>();
+    }
+}
+
+fn fly_free_camera(
+    lobby: Res<Lobby>,
+    time: Res<Time>,
+    mut query: Query<&mut Transform, With<FreeCamera>>,
+) {
+    let Some(inputs) = lobby.me() else {
+        return;
+    };
+    let pressed = |action| inputs.get_pressed(action).unwrap_or(false) as i8 as f32;
+
+    let forward = pressed(CoreAction::MoveForward) - pressed(CoreAction::MoveBack);
+    let right = pressed(CoreAction::MoveRight) - pressed(CoreAction::MoveLeft);
+    let speed = FREE_CAMERA_SPEED * FREE_CAMERA_SPRINT.powf(pressed(CoreAction::Sprint));
+
+    for mut transform in query.iter_mut() {
+        let direction =
+            (transform.forward() * forward + transform.right() * right).normalize_or_zero();
+        transform.translation += direction * speed * time.delta_seconds();
+    }
+}
+
+fn look_free_camera(
+    mut mouse_motion: EventReader<MouseMotion>,
+    settings: Res<Settings>,
+    mut query: Query<(&mut FreeCamera, &mut Transform)>,
+) {
+    let delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    if delta == Vec2::ZERO {
+        return;
+    }
+
+    let sensitivity = settings.mouse_sensitivity * MOUSE_RADIANS_PER_PIXEL;
+    let pitch_sign = if settings.invert_y { 1. } else { -1. };
+    for (mut free_camera, mut transform) in query.iter_mut() {
+        free_camera.yaw -= delta.x * sensitivity;
+        free_camera.pitch =
+            (free_camera.pitch + pitch_sign * delta.y * sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        transform.rotation = free_camera.rotation();
+    }
+}
+
+fn despawn_free_camera(mut commands: Commands, query: Query<Entity, With<FreeCamera>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
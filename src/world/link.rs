@@ -0,0 +1,158 @@
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::core::CoreGameState;
+
+/// Identifier linking an actor of the host to its shell on the clients.
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LinkId {
+    /// Actor of a level, named by the level so that every peer (and a save) agrees on it
+    Scene(String),
+    /// Actor spawned at runtime, see [`LinkIdAllocator`]
+    Allocated { session: u32, index: u64 },
+}
+
+impl fmt::Display for LinkId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkId::Scene(name) => write!(f, "{name}"),
+            LinkId::Allocated { session, index } => write!(f, "{session:08x}:{index}"),
+        }
+    }
+}
+
+/// Hands out the [`LinkId`]s of the actors spawned at runtime on the host and single.
+///
+/// The index only grows, the session is rotated on every level load
+/// so that an id of the previous level never aliases an actor of the new one.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkIdAllocator {
+    session: u32,
+    next: u64,
+}
+
+impl Default for LinkIdAllocator {
+    fn default() -> Self {
+        Self {
+            session: rand::random(),
+            next: 0,
+        }
+    }
+}
+
+impl LinkIdAllocator {
+    /// Returns a new id, never returned before by this allocator.
+    pub fn allocate(&mut self) -> LinkId {
+        let index = self.next;
+        self.next += 1;
+        LinkId::Allocated {
+            session: self.session,
+            index,
+        }
+    }
+
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    /// Switches to a new session, different from the current one.
+    pub fn rotate(&mut self) {
+        let previous = self.session;
+        while self.session == previous {
+            self.session = rand::random();
+        }
+    }
+}
+
+pub struct LinkIdPlugin;
+
+impl Plugin for LinkIdPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LinkIdAllocator>()
+            .add_systems(OnEnter(CoreGameState::InGame), rotate_link_session);
+
+        #[cfg(feature = "dev")]
+        app.add_systems(Update, report_duplicate_link_ids);
+    }
+}
+
+fn rotate_link_session(mut allocator: ResMut<LinkIdAllocator>) {
+    allocator.rotate();
+}
+
+/// Two entities with the same id fight over one replicated transform, reported once per second.
+#[cfg(feature = "dev")]
+fn report_duplicate_link_ids(
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+    query: Query<(Entity, &LinkId)>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(1., TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let mut seen = bevy::utils::HashMap::<&LinkId, Entity>::new();
+    for (entity, link_id) in query.iter() {
+        if let Some(first) = seen.insert(link_id, entity) {
+            log::warn!("Duplicate link id {}: {:?} and {:?}", link_id, first, entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(link_id: &LinkId) -> u64 {
+        match link_id {
+            LinkId::Allocated { index, .. } => *index,
+            LinkId::Scene(name) => panic!("scene id {name} from the allocator"),
+        }
+    }
+
+    #[test]
+    fn allocated_indices_are_strictly_increasing() {
+        let mut allocator = LinkIdAllocator::default();
+        let ids: Vec<_> = (0..100).map(|_| allocator.allocate()).collect();
+        for pair in ids.windows(2) {
+            assert!(index(&pair[0]) < index(&pair[1]), "{} then {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn rotate_changes_the_session_and_keeps_counting() {
+        let mut allocator = LinkIdAllocator::default();
+        let before = allocator.allocate();
+        let session = allocator.session();
+
+        allocator.rotate();
+        let after = allocator.allocate();
+
+        assert_ne!(allocator.session(), session);
+        assert!(index(&before) < index(&after));
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn entering_a_level_rotates_the_session() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_state::<CoreGameState>()
+            .add_plugins(LinkIdPlugin);
+        app.update();
+
+        let stale = app.world.resource_mut::<LinkIdAllocator>().allocate();
+        let session = app.world.resource::<LinkIdAllocator>().session();
+
+        app.world
+            .resource_mut::<NextState<CoreGameState>>()
+            .set(CoreGameState::InGame);
+        app.update();
+
+        let fresh = app.world.resource_mut::<LinkIdAllocator>().allocate();
+        assert_ne!(app.world.resource::<LinkIdAllocator>().session(), session);
+        assert_ne!(stale, fresh);
+    }
+}
@@ -1,9 +1,19 @@
 #![allow(clippy::module_inception)]
 
 mod camera;
+mod environment;
+mod free_camera;
+mod link;
+mod shared_seed;
+mod simulation;
 mod spawn_point;
 mod world;
 
 pub use camera::*;
+pub use environment::*;
+pub use free_camera::*;
+pub use link::*;
+pub use shared_seed::*;
+pub use simulation::*;
 pub use spawn_point::*;
 pub use world::*;
@@ -0,0 +1,114 @@
+//! Randomness shared by every instance of a session.
+//!
+//! The host rolls a [`SharedSeed`] when the session starts and sends it in
+//! [`ServerMessages::InitConnection`](crate::lobby::ServerMessages::InitConnection).
+//! [`seeded_rng`] derives a stream per purpose from it, so what is drawn from it for the same
+//! purpose matches on every machine: cosmetics, or the first spawn of the players by join order.
+//!
+//! Rolls deciding the game on the host (bot goals, respawns, the link sessions) stay on
+//! `rand::thread_rng`, a client must not be able to predict them. The shared streams are only
+//! for what every instance may know, and only match between builds of the same `rand` version.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::lobby::migration::MigratedSession;
+use crate::lobby::LobbyState;
+
+use super::SpawnProperty;
+
+/// Seed of the session, rolled by the host or single and received by the clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource, Deref, Serialize, Deserialize)]
+pub struct SharedSeed(pub u64);
+
+impl Default for SharedSeed {
+    fn default() -> Self {
+        Self::roll()
+    }
+}
+
+impl SharedSeed {
+    pub fn roll() -> Self {
+        Self(rand::random())
+    }
+}
+
+/// Stream of `purpose`, the same for the same seed and purpose on every instance.
+pub fn seeded_rng(seed: &SharedSeed, purpose: &str) -> impl Rng {
+    let mut hasher = Sha256::new();
+    hasher.update(seed.0.to_le_bytes());
+    hasher.update(purpose.as_bytes());
+    StdRng::from_seed(hasher.finalize().into())
+}
+
+/// Spawn points of the level and the shared seed to pick the first spawns with.
+#[derive(SystemParam)]
+pub struct SeededSpawn<'w> {
+    pub points: Res<'w, SpawnProperty>,
+    pub seed: Res<'w, SharedSeed>,
+}
+
+impl SeededSpawn<'_> {
+    /// First spawn among `points` of the player joining as the `player_number`th,
+    /// the same join order gives the same layout.
+    pub fn first_spawn(&self, points: &SpawnProperty, player_number: u32) -> Vec3 {
+        points.point_with(&mut seeded_rng(&self.seed, &format!("spawn:{}", player_number)))
+    }
+}
+
+pub struct SharedSeedPlugin;
+
+impl Plugin for SharedSeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SharedSeed>()
+            .add_systems(OnEnter(LobbyState::Single), roll_shared_seed)
+            .add_systems(OnEnter(LobbyState::Host), roll_shared_seed);
+    }
+}
+
+/// The new host of a migrated session goes on with the seed its client received.
+fn roll_shared_seed(mut seed: ResMut<SharedSeed>, migrated: Option<Res<MigratedSession>>) {
+    if migrated.is_some() {
+        return;
+    }
+    *seed = SharedSeed::roll();
+    log::debug!("Shared seed {:016x}", seed.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(seed: u64, purpose: &str) -> Vec<u64> {
+        let mut rng = seeded_rng(&SharedSeed(seed), purpose);
+        (0..32).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn same_seed_and_purpose_give_the_same_sequence() {
+        assert_eq!(draws(7, "player_color"), draws(7, "player_color"));
+    }
+
+    #[test]
+    fn purposes_and_seeds_give_other_streams() {
+        assert_ne!(draws(7, "player_color"), draws(7, "spawn:1"));
+        assert_ne!(draws(7, "player_color"), draws(8, "player_color"));
+    }
+
+    #[test]
+    fn first_spawns_follow_the_join_order() {
+        let points = SpawnProperty::new((0..16).map(|i| Vec3::X * i as f32).collect::<Vec<_>>());
+        let spawns = |seed| {
+            (1..=8)
+                .map(|number| {
+                    points.point_with(&mut seeded_rng(&SharedSeed(seed), &format!("spawn:{}", number)))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(spawns(3), spawns(3));
+    }
+}
@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use bevy_rapier3d::plugin::{RapierConfiguration, TimestepMode};
+use serde::{Deserialize, Serialize};
+
+use crate::core::CoreConfig;
+use crate::lobby::LobbyState;
+
+/// Rates of the fixed-timestep simulation.
+///
+/// Physics (rapier) and character movement run in [`FixedUpdate`] at `physics_hz`,
+/// the host broadcasts network snapshots every `physics_hz / net_sync_hz` ticks.
+/// Both are independent from the render frame rate: a frame runs as many ticks as the time
+/// since the last one asks for, none on some frames of a 144 FPS host and several on every
+/// frame of a 30 FPS one. Rapier steps once per tick, so a snapshot always follows whole steps.
+///
+/// Set at startup with [`CorePlugins::with_simulation`](crate::core::CorePlugins::with_simulation)
+/// or the `--tick-rate` and `--sync-rate` arguments.
+#[derive(Debug, Clone, Copy, Resource, Reflect)]
+pub struct SimulationConfig {
+    /// Physics and character ticks per second.
+    pub physics_hz: f64,
+    /// Network snapshots per second sent by the host.
+    pub net_sync_hz: f64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            physics_hz: 64.,
+            net_sync_hz: 20.,
+        }
+    }
+}
+
+impl SimulationConfig {
+    /// Number of physics ticks between two network snapshots, at least one.
+    pub fn ticks_per_sync(&self) -> u64 {
+        (self.physics_hz / self.net_sync_hz).round().max(1.) as u64
+    }
+}
+
+/// Physics parameters of the loaded level.
+///
+/// Set when a level is loaded, from [`level_physics`](crate::level::level_physics) on the simulating side
+/// and from the host [`ServerMessages::ChangeMap`](crate::lobby::ServerMessages::ChangeMap) on clients.
+#[derive(Debug, Clone, Copy, PartialEq, Resource, Reflect, Serialize, Deserialize)]
+pub struct LevelPhysics {
+    pub gravity: Vec3,
+    /// Simulated seconds per real second, slows down or speeds up rapier only.
+    pub time_scale: f32,
+}
+
+impl Default for LevelPhysics {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::Y * -9.81,
+            time_scale: 1.,
+        }
+    }
+}
+
+/// Changes the [`LevelPhysics`] of the running level, replicated to the clients by the host.
+#[derive(Debug, Clone, Copy, Event)]
+pub struct ChangePhysicsEvent(pub LevelPhysics);
+
+/// Number of fixed ticks simulated since startup.
+#[derive(Debug, Default, Clone, Copy, Resource, Deref)]
+pub struct SimulationTick(u64);
+
+pub struct SimulationPlugins;
+
+impl Plugin for SimulationPlugins {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationConfig>()
+            .register_type::<SimulationConfig>()
+            .init_resource::<LevelPhysics>()
+            .register_type::<LevelPhysics>()
+            .add_event::<ChangePhysicsEvent>()
+            .init_resource::<SimulationTick>()
+            .add_systems(
+                Update,
+                (
+                    change_physics,
+                    apply_simulation_config.run_if(
+                        resource_changed::<SimulationConfig>
+                            .or_else(resource_changed::<LevelPhysics>),
+                    ),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(LobbyState::None), reset_level_physics)
+            .add_systems(FixedFirst, advance_tick);
+    }
+}
+
+fn change_physics(
+    mut change_physics_event: EventReader<ChangePhysicsEvent>,
+    mut level_physics: ResMut<LevelPhysics>,
+) {
+    if let Some(ChangePhysicsEvent(physics)) = change_physics_event.read().last() {
+        log::info!("Level physics changed: {:?}", physics);
+        *level_physics = *physics;
+    }
+}
+
+fn apply_simulation_config(
+    config: Res<SimulationConfig>,
+    level_physics: Res<LevelPhysics>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    fixed_time.set_timestep_hz(config.physics_hz);
+    // the tick rate stays the same, each tick simulates more or less time
+    rapier_config.timestep_mode = TimestepMode::Fixed {
+        dt: (level_physics.time_scale as f64 / config.physics_hz) as f32,
+        substeps: 1,
+    };
+    rapier_config.gravity = level_physics.gravity;
+}
+
+fn reset_level_physics(config: Res<CoreConfig>, mut level_physics: ResMut<LevelPhysics>) {
+    *level_physics = config.default_physics;
+}
+
+fn advance_tick(mut tick: ResMut<SimulationTick>) {
+    tick.0 += 1;
+}
+
+/// Run condition that is `true` on the fixed ticks the host should send a network snapshot.
+pub fn net_sync_tick(config: Res<SimulationConfig>, tick: Res<SimulationTick>) -> bool {
+    tick.0 % config.ticks_per_sync() == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn simulation_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<SimulationConfig>();
+        world.init_resource::<LevelPhysics>();
+        world.init_resource::<CoreConfig>();
+        world.init_resource::<Time<Fixed>>();
+        world.init_resource::<Events<ChangePhysicsEvent>>();
+        world.insert_resource(RapierConfiguration::new(1.));
+        world
+    }
+
+    /// What a level load does: the physics of the level are applied to rapier.
+    fn load_level(world: &mut World, physics: LevelPhysics) {
+        world.send_event(ChangePhysicsEvent(physics));
+        world.run_system_once(change_physics);
+        world.run_system_once(apply_simulation_config);
+    }
+
+    /// What leaving the level does.
+    fn unload_level(world: &mut World) {
+        world.run_system_once(reset_level_physics);
+        world.run_system_once(apply_simulation_config);
+    }
+
+    fn assert_applied(world: &World, physics: LevelPhysics) {
+        let rapier_config = world.resource::<RapierConfiguration>();
+        assert_eq!(rapier_config.gravity, physics.gravity);
+        let dt = match rapier_config.timestep_mode {
+            TimestepMode::Fixed { dt, .. } => dt,
+            mode => panic!("rapier is not stepped at a fixed rate: {:?}", mode),
+        };
+        let hz = world.resource::<SimulationConfig>().physics_hz;
+        assert!((dt - (physics.time_scale as f64 / hz) as f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn level_physics_are_applied_and_restored() {
+        let mut world = simulation_world();
+        let default = LevelPhysics::default();
+        let low_gravity = LevelPhysics {
+            gravity: Vec3::Y * -2.,
+            time_scale: 1.,
+        };
+        let slow_motion = LevelPhysics {
+            gravity: Vec3::X * 5.,
+            time_scale: 0.5,
+        };
+
+        load_level(&mut world, low_gravity);
+        assert_applied(&world, low_gravity);
+        unload_level(&mut world);
+        assert_applied(&world, default);
+
+        load_level(&mut world, slow_motion);
+        assert_applied(&world, slow_motion);
+        unload_level(&mut world);
+        assert_applied(&world, default);
+    }
+
+    #[test]
+    fn ticks_per_sync_is_at_least_one() {
+        let config = SimulationConfig {
+            physics_hz: 64.,
+            net_sync_hz: 20.,
+        };
+        assert_eq!(config.ticks_per_sync(), 3);
+        let config = SimulationConfig {
+            physics_hz: 30.,
+            net_sync_hz: 60.,
+        };
+        assert_eq!(config.ticks_per_sync(), 1);
+    }
+}
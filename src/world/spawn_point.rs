@@ -9,31 +9,68 @@ use rand::Rng;
 
 #[derive(Debug, Clone, Resource, InspectorOptions, Deref, DerefMut, Default, Reflect)]
 #[reflect(InspectorOptions)]
-pub struct SpawnProperty(Vec<Vec3>);
+pub struct SpawnProperty {
+    #[deref]
+    points: Vec<Vec3>,
+    /// Points also usable only by some players (e.g. a team), by tag
+    tagged: Vec<(String, Vec3)>,
+}
 
 impl SpawnProperty {
     pub fn new<T: IntoVec3Vec>(spawn_points: T) -> Self {
-        Self(spawn_points.into_vec3_vec())
+        Self {
+            points: spawn_points.into_vec3_vec(),
+            tagged: Vec::new(),
+        }
     }
 
     #[allow(dead_code)]
     pub fn empty() -> Self {
-        Self(Vec::new())
+        Self::new(Vec::new())
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.points.is_empty()
     }
 
     #[allow(dead_code)]
     pub fn points(&self) -> &[Vec3] {
-        &self.0
+        &self.points
+    }
+
+    /// Adds a point usable by everyone and by the players of `tag`.
+    pub fn push_tagged(&mut self, tag: impl Into<String>, point: Vec3) {
+        self.points.push(point);
+        self.tagged.push((tag.into(), point));
     }
 
     pub fn random_point(&self) -> Vec3 {
-        let mut rng = rand::thread_rng();
-        let index = rng.gen_range(0..self.0.len());
-        self.0[index]
+        self.point_with(&mut rand::thread_rng())
+    }
+
+    /// A point drawn from `rng`, e.g. a [`seeded_rng`](super::seeded_rng) stream.
+    pub fn point_with(&self, rng: &mut impl Rng) -> Vec3 {
+        let index = rng.gen_range(0..self.points.len());
+        self.points[index]
+    }
+
+    /// Points tagged `tag`, all the points if none is.
+    pub fn tagged(&self, tag: &str) -> SpawnProperty {
+        let points: Vec<Vec3> = self
+            .tagged
+            .iter()
+            .filter(|(point_tag, _)| point_tag == tag)
+            .map(|(_, point)| *point)
+            .collect();
+        if points.is_empty() {
+            return self.clone();
+        }
+        SpawnProperty::new(points)
+    }
+
+    /// A random point tagged `tag`, any point if none is.
+    pub fn random_tagged(&self, tag: &str) -> Vec3 {
+        self.tagged(tag).random_point()
     }
 }
 
@@ -41,6 +78,12 @@ pub trait IntoVec3Vec {
     fn into_vec3_vec(self) -> Vec<Vec3>;
 }
 
+impl IntoVec3Vec for Vec<Vec3> {
+    fn into_vec3_vec(self) -> Vec<Vec3> {
+        self
+    }
+}
+
 impl IntoVec3Vec for Vec3 {
     fn into_vec3_vec(self) -> Vec<Vec3> {
         vec![self]
@@ -3,10 +3,14 @@ use crate::component::ComponentPlugins;
 use crate::level::MapPlugins;
 use crate::lobby::{LobbyPlugins};
 use crate::settings::SettingsPlugins;
+use crate::match_results::MatchResultsPlugin;
+use crate::stats::PlayerStatsPlugin;
 use crate::sound::SoundPlugins;
+use crate::world::{
+    EnvironmentPlugins, FreeCameraPlugins, LinkIdPlugin, SharedSeedPlugin, SimulationPlugins,
+};
 use crate::ui::UiPlugins;
 use bevy::prelude::*;
-use serde::{Deserialize, Serialize};
 
 
 
@@ -43,39 +47,36 @@ use serde::{Deserialize, Serialize};
 #[derive(Component)]
 pub struct PromisedScene;
 
-#[derive(Component, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum LinkId {
-    Scene(String),
-    Projectile(usize),
-}
-
-#[derive(Resource, Default, Reflect, Debug, Clone, Copy, PartialEq, Eq, Deref, DerefMut)]
-pub struct ProjectileIdSeq(usize);
-
-// TODO:
-//impl ProjectileIdSeq {
-//    /// Returns the next projectile ID. A new ID is generated each time this method is called.
-//    pub fn shift(&mut self) -> LinkId {
-//        self.0 += 1;
-//        LinkId::Projectile(self.0)
-//    }
-//}
-
 pub struct WorldPlugins;
 
 impl Plugin for WorldPlugins {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ProjectileIdSeq>()
-            .register_type::<ProjectileIdSeq>()
-            .add_plugins((
-                SettingsPlugins,
-                SoundPlugins,
-                MapPlugins,
-                UiPlugins,
-                LobbyPlugins,
-                ActorPlugins,
-                ComponentPlugins,
-            ));
+        app.add_plugins((
+            LinkIdPlugin,
+            SharedSeedPlugin,
+            SimulationPlugins,
+            EnvironmentPlugins,
+            FreeCameraPlugins,
+            SettingsPlugins,
+            PlayerStatsPlugin,
+            MatchResultsPlugin,
+            SoundPlugins,
+            MapPlugins,
+            UiPlugins,
+            LobbyPlugins,
+            ActorPlugins,
+            ComponentPlugins,
+        ));
+
+        #[cfg(feature = "dev")]
+        app.add_plugins((
+            crate::console::ConsolePlugin,
+            crate::log_console::LogConsolePlugins,
+            crate::physics::debug::PhysicsDebugPlugins,
+            crate::lobby::ghosts::ColliderGhostPlugins,
+        ));
+        #[cfg(all(debug_assertions, feature = "dev"))]
+        app.add_plugins(crate::network::LinkConditionerPlugins);
     }
 }
 
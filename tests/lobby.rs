@@ -0,0 +1,83 @@
+//! Host and clients over the loopback, run with `cargo test --features test-utils`.
+
+use std::time::Duration;
+
+use urmom::test_harness::{wait_for, ServerMessages, TestClient, TestServer};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn connected_client_character_spawns_on_both_sides() {
+    let mut server = TestServer::new(0);
+    let mut client = TestClient::connect(server.address, "tester");
+
+    // the host character and the client one, on the host and on the client
+    assert!(wait_for(
+        &mut [&mut server.app, &mut client.app],
+        |apps| {
+            TestServer::characters_of(apps[0]).len() == 2
+                && TestServer::characters_of(apps[1]).len() == 2
+        },
+        TIMEOUT,
+    ));
+
+    let lobby = server.lobby().expect("host has no lobby");
+    assert_eq!(lobby.players.len(), 2);
+    assert!(lobby
+        .players
+        .values()
+        .any(|player_data| player_data.username == "tester"));
+    assert!(client
+        .received_messages()
+        .iter()
+        .any(|message| matches!(message, ServerMessages::PlayerConnected { .. })));
+}
+
+#[test]
+fn disconnected_client_character_is_despawned() {
+    let mut server = TestServer::new(0);
+    let mut leaving = TestClient::connect(server.address, "leaving");
+    let mut staying = TestClient::connect(server.address, "staying");
+
+    assert!(wait_for(
+        &mut [&mut server.app, &mut leaving.app, &mut staying.app],
+        |apps| apps.iter_mut().all(|app| TestServer::characters_of(app).len() == 3),
+        TIMEOUT,
+    ));
+    let leaving_id = server
+        .lobby()
+        .and_then(|lobby| {
+            lobby
+                .players
+                .iter()
+                .find(|(_, player_data)| player_data.username == "leaving")
+                .map(|(id, _)| *id)
+        })
+        .expect("leaving client is not in the lobby");
+
+    leaving.disconnect();
+
+    assert!(wait_for(
+        &mut [&mut server.app, &mut staying.app],
+        |apps| {
+            apps.iter_mut().all(|app| {
+                let characters = TestServer::characters_of(app);
+                characters.len() == 2 && characters.iter().all(|(_, id)| *id != leaving_id)
+            })
+        },
+        TIMEOUT,
+    ));
+    assert!(!server.lobby().unwrap().players.contains_key(&leaving_id));
+    assert!(staying.received_messages().iter().any(|message| matches!(
+        message,
+        ServerMessages::PlayerDisconnected { id } if *id == leaving_id
+    )));
+}
+
+#[test]
+fn servers_bind_distinct_ports() {
+    let first = TestServer::new(0);
+    let second = TestServer::new(0);
+    assert_ne!(first.address.port(), 0);
+    assert_ne!(first.address, second.address);
+}